@@ -1,10 +1,12 @@
 use near_primitives::block::BlockValidityError;
 use near_primitives::challenge::{ChunkProofs, MaybeEncodedShardChunk};
 use near_primitives::errors::{ChunkAccessError, EpochError, StorageError};
+use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardLayoutError;
 use near_primitives::sharding::{BadHeaderForProtocolVersionError, ChunkHash, ShardChunkHeader};
 use near_primitives::types::{BlockHeight, EpochId, ShardId, ShardIndex};
 use near_time::Utc;
+use std::fmt;
 use std::io;
 
 #[derive(thiserror::Error, Debug)]
@@ -242,6 +244,89 @@ pub enum Error {
     /// Anything else
     #[error("Other Error: {0}")]
     Other(String),
+    /// Any of the errors above, together with the block, shard or epoch it
+    /// happened for. Use `root` to match on the underlying error.
+    #[error("{error} ({context})")]
+    WithContext { error: Box<Error>, context: ErrorContext },
+}
+
+/// Block, shard and epoch for which an error happened. Any subset of the
+/// fields may be known at the place where the context is attached.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub block_hash: Option<CryptoHash>,
+    pub block_height: Option<BlockHeight>,
+    pub shard_id: Option<ShardId>,
+    pub epoch_id: Option<EpochId>,
+}
+
+impl ErrorContext {
+    /// Sets the fields of `self` which are not known yet from `other`.
+    fn merge(&mut self, other: ErrorContext) {
+        self.block_hash = self.block_hash.or(other.block_hash);
+        self.block_height = self.block_height.or(other.block_height);
+        self.shard_id = self.shard_id.or(other.shard_id);
+        self.epoch_id = self.epoch_id.or(other.epoch_id);
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = vec![];
+        if let Some(block_hash) = &self.block_hash {
+            fields.push(format!("block_hash={block_hash}"));
+        }
+        if let Some(block_height) = &self.block_height {
+            fields.push(format!("block_height={block_height}"));
+        }
+        if let Some(shard_id) = &self.shard_id {
+            fields.push(format!("shard_id={shard_id}"));
+        }
+        if let Some(epoch_id) = &self.epoch_id {
+            fields.push(format!("epoch_id={}", epoch_id.0));
+        }
+        write!(f, "{}", fields.join(", "))
+    }
+}
+
+/// Attaches the provenance of an error to the results of chain operations.
+/// Context attached closer to the source of the error takes precedence.
+pub trait ChainErrorContext<T> {
+    fn with_block_context(
+        self,
+        block_hash: &CryptoHash,
+        block_height: BlockHeight,
+    ) -> Result<T, Error>;
+    fn with_shard_context(self, shard_id: ShardId) -> Result<T, Error>;
+    fn with_epoch_context(self, epoch_id: &EpochId) -> Result<T, Error>;
+}
+
+impl<T> ChainErrorContext<T> for Result<T, Error> {
+    fn with_block_context(
+        self,
+        block_hash: &CryptoHash,
+        block_height: BlockHeight,
+    ) -> Result<T, Error> {
+        self.map_err(|err| {
+            err.with_context(ErrorContext {
+                block_hash: Some(*block_hash),
+                block_height: Some(block_height),
+                ..Default::default()
+            })
+        })
+    }
+
+    fn with_shard_context(self, shard_id: ShardId) -> Result<T, Error> {
+        self.map_err(|err| {
+            err.with_context(ErrorContext { shard_id: Some(shard_id), ..Default::default() })
+        })
+    }
+
+    fn with_epoch_context(self, epoch_id: &EpochId) -> Result<T, Error> {
+        self.map_err(|err| {
+            err.with_context(ErrorContext { epoch_id: Some(*epoch_id), ..Default::default() })
+        })
+    }
 }
 
 /// For now StorageError can happen at any time from ViewClient because of
@@ -260,8 +345,44 @@ impl<T> LogTransientStorageError for Result<T, Error> {
 }
 
 impl Error {
+    /// Attaches the context to the error. If the error already has a context,
+    /// only its unknown fields are set.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Error::WithContext { error, context: mut existing } => {
+                existing.merge(context);
+                Error::WithContext { error, context: existing }
+            }
+            error => Error::WithContext { error: Box::new(error), context },
+        }
+    }
+
+    /// Returns the error without its context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::WithContext { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Consumes the error and returns it without its context.
+    pub fn into_root(self) -> Error {
+        match self {
+            Error::WithContext { error, .. } => *error,
+            error => error,
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
     pub fn is_bad_data(&self) -> bool {
         match self {
+            Error::WithContext { error, .. } => error.is_bad_data(),
             Error::BlockKnown(_)
             | Error::TooManyProcessingBlocks
             | Error::Orphan
@@ -330,6 +451,7 @@ impl Error {
 
     pub fn is_error(&self) -> bool {
         match self {
+            Error::WithContext { error, .. } => error.is_error(),
             Error::IOErr(_) | Error::Other(_) | Error::DBNotFoundErr(_) => true,
             _ => false,
         }
@@ -405,6 +527,7 @@ impl Error {
             Error::NotAChunkValidator => "not_a_chunk_validator",
             Error::ReshardingError(_) => "resharding_error",
             Error::BadHeaderForProtocolVersion(_) => "bad_header_for_protocol_version",
+            Error::WithContext { error, .. } => error.prometheus_label_value(),
         }
     }
 }
@@ -0,0 +1,71 @@
+use near_chain_primitives::Error;
+use near_primitives::account::Account;
+use near_primitives::shard_layout::boundary_selection::AccountUsageStats;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, ShardId, StorageUsage};
+use near_store::{DBCol, Store, StoreUpdate};
+use std::collections::HashMap;
+
+use crate::update_shard::{NewChunkResult, ShardUpdateResult};
+
+#[derive(Default)]
+struct AccountUsageChange {
+    gas_burnt: u128,
+    /// The storage usage of the account at the end of the chunk, if the
+    /// chunk changed the account. `Some(None)` if it deleted the account.
+    storage_usage: Option<Option<StorageUsage>>,
+}
+
+/// Returns the update which adds the usage of the accounts in the new chunks
+/// of a block to `DBCol::AccountUsageStats`.
+///
+/// The stats are updated for every block applied by the node, including the
+/// blocks on forks, and only for the shards the node tracks. This is good
+/// enough to compare the accounts of a shard between each other, which is
+/// all the boundary selection needs.
+pub(crate) fn account_usage_stats_update(
+    store: &Store,
+    apply_results: &[(ShardId, Result<ShardUpdateResult, Error>)],
+) -> Result<StoreUpdate, Error> {
+    let mut changes: HashMap<AccountId, AccountUsageChange> = HashMap::new();
+    for (_, apply_result) in apply_results {
+        // Old chunks don't execute anything.
+        let Ok(ShardUpdateResult::NewChunk(NewChunkResult { apply_result, .. })) = apply_result
+        else {
+            continue;
+        };
+        for outcome in &apply_result.outcomes {
+            let change = changes.entry(outcome.outcome.executor_id.clone()).or_default();
+            change.gas_burnt = change.gas_burnt.saturating_add(outcome.outcome.gas_burnt.into());
+        }
+        for state_change in apply_result.trie_changes.state_changes() {
+            let TrieKey::Account { account_id } = &state_change.trie_key else {
+                continue;
+            };
+            let Some(value) = state_change.changes.last() else {
+                continue;
+            };
+            let storage_usage = match &value.data {
+                Some(data) => Some(borsh::from_slice::<Account>(data)?.storage_usage()),
+                None => None,
+            };
+            changes.entry(account_id.clone()).or_default().storage_usage = Some(storage_usage);
+        }
+    }
+
+    let mut store_update = store.store_update();
+    for (account_id, change) in changes {
+        if change.storage_usage == Some(None) {
+            store_update.delete(DBCol::AccountUsageStats, account_id.as_bytes());
+            continue;
+        }
+        let mut stats: AccountUsageStats =
+            store.get_ser(DBCol::AccountUsageStats, account_id.as_bytes())?.unwrap_or_default();
+        stats.gas_burnt = stats.gas_burnt.saturating_add(change.gas_burnt);
+        if let Some(Some(storage_usage)) = change.storage_usage {
+            stats.storage_usage = storage_usage;
+        }
+        store_update.set_ser(DBCol::AccountUsageStats, account_id.as_bytes(), &stats)?;
+    }
+    Ok(store_update)
+}
@@ -1,3 +1,4 @@
+use crate::account_usage_stats::account_usage_stats_update;
 use crate::approval_verification::verify_approval_with_approvers_info;
 use crate::block_processing_utils::{
     ApplyChunksDoneWaiter, ApplyChunksStillApplying, BlockPreprocessInfo, BlockProcessingArtifact,
@@ -10,8 +11,10 @@ use crate::lightclient::get_epoch_block_producers_view;
 use crate::missing_chunks::{MissingChunksPool, OptimisticBlockChunksPool};
 use crate::orphan::{Orphan, OrphanBlockPool};
 use crate::rayon_spawner::RayonAsyncComputationSpawner;
+use crate::replica::{ReplicaStateUpdates, apply_replica_state_update};
 use crate::resharding::manager::ReshardingManager;
 use crate::resharding::types::ReshardingSender;
+use crate::shard_hotspots::ShardHotspotsTracker;
 use crate::sharding::{get_receipts_shuffle_salt, shuffle_receipt_proofs};
 use crate::signature_verification::{
     verify_block_header_signature_with_epoch_manager, verify_block_vrf,
@@ -48,7 +51,7 @@ use near_async::futures::{AsyncComputationSpawner, AsyncComputationSpawnerExt};
 use near_async::messaging::{IntoMultiSender, noop};
 use near_async::time::{Clock, Duration, Instant};
 use near_chain_configs::{MutableConfigValue, MutableValidatorSigner};
-use near_chain_primitives::error::{BlockKnownError, Error};
+use near_chain_primitives::error::{BlockKnownError, ChainErrorContext, Error, ErrorContext};
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::shard_id_to_uid;
 use near_epoch_manager::shard_tracker::ShardTracker;
@@ -58,6 +61,7 @@ use near_primitives::block::{
 };
 use near_primitives::block_header::BlockHeader;
 use near_primitives::challenge::{ChunkProofs, MaybeEncodedShardChunk};
+use near_primitives::congestion_info::BlockCongestionInfo;
 use near_primitives::epoch_block_info::BlockInfo;
 use near_primitives::errors::EpochError;
 use near_primitives::hash::{CryptoHash, hash};
@@ -82,7 +86,7 @@ use near_primitives::types::{
     AccountId, Balance, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId, ShardIndex,
 };
 use near_primitives::utils::MaybeValidated;
-use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::version::{PROTOCOL_VERSION, ProtocolFeature};
 use near_primitives::views::{
     BlockStatusView, DroppedReason, ExecutionOutcomeWithIdView, ExecutionStatusView,
     FinalExecutionOutcomeView, FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus,
@@ -102,6 +106,9 @@ use time::ext::InstantExt as _;
 use tracing::{Span, debug, debug_span, error, info, warn};
 
 pub const APPLY_CHUNK_RESULTS_CACHE_SIZE: usize = 100;
+/// Number of the shard state updates kept for the replicas, a few blocks of
+/// all the shards.
+const REPLICA_STATE_UPDATES_CACHE_SIZE: usize = 100;
 
 /// The size of the invalid_blocks in-memory pool
 pub const INVALID_CHUNKS_POOL_SIZE: usize = 5000;
@@ -296,6 +303,10 @@ pub struct Chain {
     pub block_economics_config: BlockEconomicsConfig,
     pub doomslug_threshold_mode: DoomslugThresholdMode,
     pub blocks_delay_tracker: BlocksDelayTracker,
+    /// Usage of the accounts of the tracked shards, for the debug page.
+    pub shard_hotspots_tracker: ShardHotspotsTracker,
+    /// Whether to collect the usage of the accounts in `DBCol::AccountUsageStats`.
+    save_account_usage_stats: bool,
     /// Processing a block is done in three stages: preprocess_block, async_apply_chunks and
     /// postprocess_block. The async_apply_chunks is done asynchronously from the ClientActor thread.
     /// `blocks_in_processing` keeps track of all the blocks that have been preprocessed but are
@@ -308,6 +319,11 @@ pub struct Chain {
     /// Used to spawn the apply chunks jobs.
     apply_chunks_spawner: Arc<dyn AsyncComputationSpawner>,
     pub apply_chunk_results_cache: ApplyChunksResultCache,
+    /// The state updates received by a replica, or kept by a node which
+    /// serves them to the replicas.
+    pub replica_state_updates: ReplicaStateUpdates,
+    /// Whether to keep the state updates of the applied chunks.
+    serve_replica_state_updates: bool,
     /// Time when head was updated most recently.
     last_time_head_updated: Instant,
     /// Prevents re-application of known-to-be-invalid blocks, so that in case of a
@@ -421,10 +437,14 @@ impl Chain {
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
             blocks_delay_tracker: BlocksDelayTracker::new(clock.clone()),
+            shard_hotspots_tracker: ShardHotspotsTracker::new(Default::default()),
+            save_account_usage_stats: false,
             apply_chunks_sender: sc,
             apply_chunks_receiver: rc,
             apply_chunks_spawner: Arc::new(RayonAsyncComputationSpawner),
             apply_chunk_results_cache: ApplyChunksResultCache::new(APPLY_CHUNK_RESULTS_CACHE_SIZE),
+            replica_state_updates: ReplicaStateUpdates::new(REPLICA_STATE_UPDATES_CACHE_SIZE),
+            serve_replica_state_updates: false,
             last_time_head_updated: clock.now(),
             invalid_blocks: LruCache::new(NonZeroUsize::new(INVALID_CHUNKS_POOL_SIZE).unwrap()),
             pending_state_patch: Default::default(),
@@ -530,9 +550,8 @@ impl Chain {
             .cloned()
             .collect();
 
-        let head_protocol_version = epoch_manager.get_epoch_protocol_version(&tip.epoch_id)?;
-        let shard_uids_pending_resharding = epoch_manager
-            .get_shard_uids_pending_resharding(head_protocol_version, PROTOCOL_VERSION)?;
+        let shard_uids_pending_resharding =
+            epoch_manager.get_shard_uids_pending_resharding(&tip.epoch_id, PROTOCOL_VERSION)?;
         runtime_adapter.get_tries().load_memtries_for_enabled_shards(
             &tracked_shards,
             &shard_uids_pending_resharding,
@@ -579,10 +598,14 @@ impl Chain {
             block_economics_config: BlockEconomicsConfig::from(chain_genesis),
             doomslug_threshold_mode,
             blocks_delay_tracker: BlocksDelayTracker::new(clock.clone()),
+            shard_hotspots_tracker: ShardHotspotsTracker::new(chain_config.shard_hotspots),
+            save_account_usage_stats: chain_config.save_account_usage_stats,
             apply_chunks_sender: sc,
             apply_chunks_receiver: rc,
             apply_chunks_spawner,
             apply_chunk_results_cache: ApplyChunksResultCache::new(APPLY_CHUNK_RESULTS_CACHE_SIZE),
+            replica_state_updates: ReplicaStateUpdates::new(REPLICA_STATE_UPDATES_CACHE_SIZE),
+            serve_replica_state_updates: chain_config.serve_replica_state_updates,
             last_time_head_updated: clock.now(),
             pending_state_patch: Default::default(),
             snapshot_callbacks,
@@ -607,6 +630,62 @@ impl Chain {
         Ok(bp_hash)
     }
 
+    /// Returns the minimum gas price for the block with `chunks` produced on top
+    /// of `prev_hash`, where `gas_price` is the gas price of that block.
+    ///
+    /// Usually this is just the minimum gas price from the genesis config. With
+    /// `ProtocolFeature::CongestionGasPriceFloor`, once some shard has stayed
+    /// congested for `gas_price_floor_congested_blocks` consecutive blocks, the
+    /// minimum is raised above `gas_price` by `gas_price_floor_increase_rate`.
+    pub fn get_min_gas_price(
+        &self,
+        prev_hash: &CryptoHash,
+        chunks: &Chunks,
+        gas_price: Balance,
+    ) -> Result<Balance, Error> {
+        let min_gas_price = self.block_economics_config.min_gas_price();
+        let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(prev_hash)?;
+        let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
+        if !ProtocolFeature::CongestionGasPriceFloor.enabled(protocol_version) {
+            return Ok(min_gas_price);
+        }
+
+        let config =
+            &self.runtime_adapter.get_runtime_config(protocol_version).congestion_control_config;
+        let is_congested = |block_congestion_info: BlockCongestionInfo| {
+            block_congestion_info.max_congestion_level(config)
+                >= config.gas_price_floor_congestion_threshold
+        };
+
+        // Count the congested blocks in a row, starting with the new block and
+        // walking back until the threshold is reached.
+        let mut congested_blocks = 0;
+        let mut block_congestion_info = chunks.block_congestion_info();
+        let mut hash = *prev_hash;
+        while is_congested(block_congestion_info) {
+            congested_blocks += 1;
+            if congested_blocks >= config.gas_price_floor_congested_blocks {
+                break;
+            }
+            let block = self.get_block(&hash)?;
+            if block.header().is_genesis() {
+                break;
+            }
+            block_congestion_info = block.chunks().block_congestion_info();
+            hash = *block.header().prev_hash();
+        }
+        if congested_blocks < config.gas_price_floor_congested_blocks {
+            return Ok(min_gas_price);
+        }
+
+        Ok(Block::compute_congestion_gas_price_floor(
+            gas_price,
+            config.gas_price_floor_increase_rate,
+            min_gas_price,
+            self.block_economics_config.max_gas_price(),
+        ))
+    }
+
     pub fn get_last_time_head_updated(&self) -> Instant {
         self.last_time_head_updated
     }
@@ -667,14 +746,14 @@ impl Chain {
         Ok(())
     }
 
-    fn maybe_mark_block_invalid(&mut self, block_hash: CryptoHash, error: &Error) {
+    pub(crate) fn maybe_mark_block_invalid(&mut self, block_hash: CryptoHash, error: &Error) {
         // We only mark the block as invalid if the block has bad data (not for other errors that would
         // not be the fault of the block itself), except when the block has a bad signature which means
         // the block might not have been what the block producer originally produced. Either way, it's
         // OK if we miss some cases here because this is just an optimization to avoid reprocessing
         // known invalid blocks so the network recovers faster in case of any issues.
         if error.is_bad_data()
-            && !matches!(error, Error::InvalidSignature | Error::InvalidBlockHeight(_))
+            && !matches!(error.root(), Error::InvalidSignature | Error::InvalidBlockHeight(_))
         {
             metrics::NUM_INVALID_BLOCKS.with_label_values(&[error.prometheus_label_value()]).inc();
             self.invalid_blocks.put(block_hash, ());
@@ -1419,7 +1498,9 @@ impl Chain {
                         apply_chunks_done_sender.clone(),
                     ) {
                         Err(e) => {
-                            errors.insert(block_hash, e);
+                            let context =
+                                ErrorContext { block_hash: Some(block_hash), ..Default::default() };
+                            errors.insert(block_hash, e.with_context(context));
                         }
                         Ok(accepted_block) => {
                             accepted_blocks.push(accepted_block);
@@ -1614,6 +1695,12 @@ impl Chain {
             return Err(Error::InvalidSignature);
         }
 
+        // The splits deferred until the resharding of their parent is completed must be done
+        // before the chunks of the children are applied.
+        self.resharding_manager
+            .retry_pending_splits(&mut self.chain_store, self.runtime_adapter.get_tries())?;
+        self.resharding_manager.check_no_pending_split(&block)?;
+
         // 1) preprocess the block where we verify that the block is valid and ready to be processed
         //    No chain updates are applied at this step.
         let state_patch = self.pending_state_patch.take();
@@ -1807,6 +1894,15 @@ impl Chain {
         let is_caught_up = block_preprocess_info.is_caught_up;
         let provenance = block_preprocess_info.provenance.clone();
         let block_start_processing_time = block_preprocess_info.block_start_processing_time;
+        self.shard_hotspots_tracker.record_apply_results(epoch_id, &apply_results);
+        if self.serve_replica_state_updates {
+            self.replica_state_updates.record_apply_results(&block_hash, &apply_results);
+        }
+        let account_usage_stats_update = if self.save_account_usage_stats {
+            Some(account_usage_stats_update(&self.chain_store.store(), &apply_results)?)
+        } else {
+            None
+        };
         // TODO(#8055): this zip relies on the ordering of the apply_results.
         // TODO(wacban): do the above todo
         for (shard_id, apply_result) in apply_results.iter() {
@@ -1818,15 +1914,21 @@ impl Chain {
                 }
             }
         }
-        let new_head =
-            match self.postprocess_block_only(me, &block, block_preprocess_info, apply_results) {
-                Err(err) => {
-                    self.maybe_mark_block_invalid(*block.hash(), &err);
-                    self.blocks_delay_tracker.mark_block_errored(&block_hash, err.to_string());
-                    return Err(err);
-                }
-                Ok(new_head) => new_head,
-            };
+        let new_head = match self
+            .postprocess_block_only(me, &block, block_preprocess_info, apply_results)
+            .with_block_context(block.hash(), block.header().height())
+            .with_epoch_context(block.header().epoch_id())
+        {
+            Err(err) => {
+                self.maybe_mark_block_invalid(*block.hash(), &err);
+                self.blocks_delay_tracker.mark_block_errored(&block_hash, err.to_string());
+                return Err(err);
+            }
+            Ok(new_head) => new_head,
+        };
+        if let Some(store_update) = account_usage_stats_update {
+            store_update.commit()?;
+        }
 
         self.update_optimistic_blocks_pool(&block)?;
 
@@ -2263,9 +2365,10 @@ impl Chain {
             return Err(e);
         }
 
+        let min_gas_price = self.get_min_gas_price(&prev_hash, &block.chunks(), gas_price)?;
         if !block.verify_gas_price(
             gas_price,
-            self.block_economics_config.min_gas_price(),
+            min_gas_price,
             self.block_economics_config.max_gas_price(),
             self.block_economics_config.gas_price_adjustment_rate(),
         ) {
@@ -3110,6 +3213,21 @@ impl Chain {
                 )));
             }
         }
+        // The chunk is still prepared to be applied, in case the replica state
+        // update doesn't lead to the state root it claims.
+        let replica_update = match self.replica_state_updates.peek_to_apply(
+            &block.block_hash,
+            shard_id,
+            is_new_chunk,
+        ) {
+            Some(update) => {
+                debug!(target: "chain", ?shard_id, "Applying the replica state update");
+                let prev_state_root =
+                    *self.get_chunk_extra(prev_hash, &shard_context.shard_uid)?.state_root();
+                Some((update.clone(), block.clone(), prev_state_root))
+            }
+            None => None,
+        };
         debug!(target: "chain", ?shard_id, ?cached_shard_update_key, "Creating ShardUpdate job");
 
         let shard_update_reason = if is_new_chunk {
@@ -3183,6 +3301,17 @@ impl Chain {
             shard_id,
             cached_shard_update_key,
             Box::new(move |parent_span| -> Result<ShardUpdateResult, Error> {
+                if let Some((update, block, prev_state_root)) = replica_update {
+                    if let Some(result) = apply_replica_state_update(
+                        runtime.as_ref(),
+                        shard_context.shard_uid,
+                        &block,
+                        prev_state_root,
+                        update,
+                    )? {
+                        return Ok(result);
+                    }
+                }
                 Ok(process_shard_update(
                     parent_span,
                     runtime.as_ref(),
@@ -3659,7 +3788,7 @@ pub fn do_apply_chunks(
         .map(|(shard_id, cached_shard_update_key, task)| {
             // As chunks can be processed in parallel, make sure they are all tracked as children of
             // a single span.
-            (shard_id, cached_shard_update_key, task(&parent_span))
+            (shard_id, cached_shard_update_key, task(&parent_span).with_shard_context(shard_id))
         })
         .collect()
 }
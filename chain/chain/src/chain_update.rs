@@ -300,6 +300,28 @@ impl<'a> ChainUpdate<'a> {
             let shard_layout = self.epoch_manager.get_shard_layout_from_prev_block(prev.hash())?;
             SHARD_LAYOUT_VERSION.set(shard_layout.version() as i64);
             SHARD_LAYOUT_NUM_SHARDS.set(shard_layout.shard_ids().count() as i64);
+
+            // The shards of the previous layout are no longer reported.
+            let shard_layout_changed = block.header().epoch_id() != &prev_epoch_id
+                && self
+                    .epoch_manager
+                    .get_shard_layout(&prev_epoch_id)
+                    .is_ok_and(|prev_shard_layout| prev_shard_layout != shard_layout);
+            if shard_layout_changed {
+                metrics::reset_block_congestion_metrics();
+            }
+            match self.epoch_manager.get_epoch_protocol_version(block.header().epoch_id()) {
+                Ok(protocol_version) => {
+                    let runtime_config = self.runtime_adapter.get_runtime_config(protocol_version);
+                    metrics::report_block_congestion_metrics(
+                        &block.block_congestion_info(),
+                        &runtime_config.congestion_control_config,
+                    );
+                }
+                Err(err) => {
+                    warn!(target: "chain", hash = %block.hash(), ?err, "Failed to report the block congestion metrics");
+                }
+            }
         }
         Ok(res)
     }
@@ -3,14 +3,17 @@
 //! See [FlatStorageResharder] for more details about how the resharding takes place.
 
 use std::num::NonZero;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::sync::{Arc, Mutex};
 
 use near_chain_configs::{MutableConfigValue, ReshardingConfig, ReshardingHandle};
 use near_chain_primitives::Error;
+use near_o11y::events::{self, ChainEvent};
 
 use near_store::adapter::trie_store::TrieStoreAdapter;
 use tracing::{debug, error, info, warn};
 
+use crate::metrics::RESHARDING_PHASE_TIME;
 use crate::resharding::event_type::{ReshardingEventType, ReshardingSplitShardParams};
 use crate::resharding::types::{
     FlatStorageShardCatchupRequest, FlatStorageSplitShardRequest, MemtrieReloadRequest,
@@ -21,8 +24,10 @@ use crate::{ChainStore, ChainStoreAccess};
 use itertools::Itertools;
 use near_primitives::block::Tip;
 use near_primitives::hash::{CryptoHash, hash};
+use near_primitives::receipt::PromiseYieldIndices;
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::state::FlatStateValue;
+use near_primitives::trie_key::TrieKey;
 use near_primitives::trie_key::col::{self};
 use near_primitives::trie_key::trie_key_parsers::{
     parse_account_id_from_access_key_key, parse_account_id_from_account_key,
@@ -32,14 +37,21 @@ use near_primitives::trie_key::trie_key_parsers::{
 #[cfg(feature = "test_features")]
 use near_primitives::types::BlockHeightDelta;
 use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::version::{PROTOCOL_VERSION, ProtocolFeature};
+use near_store::adapter::chain_store::ChainStoreAdapter;
 use near_store::adapter::flat_store::{FlatStoreAdapter, FlatStoreUpdateAdapter};
 use near_store::adapter::{StoreAdapter, StoreUpdateAdapter};
 use near_store::flat::{
-    BlockInfo, FlatStateChanges, FlatStorageError, FlatStorageReadyStatus,
-    FlatStorageReshardingShardCatchUpMetrics, FlatStorageReshardingShardSplitMetrics,
-    FlatStorageReshardingStatus, FlatStorageStatus, ParentSplitParameters,
+    BlockInfo, FlatHeadFreezeReason, FlatStateChanges, FlatStorageReadyStatus,
+    FlatStorageReshardingProgress, FlatStorageReshardingShardCatchUpMetrics,
+    FlatStorageReshardingShardSplitMetrics, FlatStorageReshardingStatus, FlatStorageStatus,
+    ParentSplitParameters, SplitShardCheckpoint, SplitShardDeltasCheckpoint,
+    SplitShardRangeCheckpoint,
 };
-use near_store::{ShardUId, StorageError};
+use near_store::trie::AccessOptions;
+use near_store::{NibbleSlice, ShardTries, ShardUId, StorageError, get};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::iter;
 
@@ -62,8 +74,9 @@ use std::iter;
 ///   [FlatStorageResharder::split_shard_task] and [FlatStorageResharder::shard_catchup_task].
 /// - Interruptible: a reshard operation can be cancelled through a
 ///   [FlatStorageResharderController].
-///     - In the case of event `Split` the state of flat storage will go back to what it was
-///       previously.
+///     - In the case of event `Split` the progress of the copy is checkpointed at every batch in
+///       [FlatStorageReshardingProgress], so the split resumes from the last committed batch when
+///       the node restarts, even after a crash.
 ///     - Children shard catchup can be cancelled and will resume from the point where it left.
 /// - Resilience to chain forks.
 ///     - Resharding events will perform changes on the state only after their resharding block
@@ -84,6 +97,11 @@ pub struct FlatStorageResharder {
     /// If non zero, the start of scheduled tasks (such as split parent) will be postponed by
     /// the specified number of blocks.
     pub adv_task_delay_by_blocks: BlockHeightDelta,
+    #[cfg(feature = "test_features")]
+    /// TEST ONLY.
+    /// If set, the split shard task stops after committing the given number of batches, leaving
+    /// the flat storage as if the node had crashed at that point.
+    pub adv_interrupt_split_after_batches: Option<usize>,
 }
 
 impl FlatStorageResharder {
@@ -109,6 +127,8 @@ impl FlatStorageResharder {
             resharding_config,
             #[cfg(feature = "test_features")]
             adv_task_delay_by_blocks: 0,
+            #[cfg(feature = "test_features")]
+            adv_interrupt_split_after_batches: None,
         }
     }
 
@@ -143,17 +163,64 @@ impl FlatStorageResharder {
     ) -> Result<(), Error> {
         match status {
             FlatStorageReshardingStatus::CreatingChild => {
-                // Nothing to do here because the parent will take care of resuming work.
+                // The parent takes care of resuming work. It is resumed from here in case it is
+                // not a shard of the current epoch anymore, which is the case if the node
+                // restarted after the resharding block.
+                let flat_store = self.runtime.store().flat_store();
+                let Some(progress) = flat_store
+                    .get_resharding_progress(shard_uid)
+                    .map_err(|err| Into::<StorageError>::into(err))?
+                else {
+                    return Ok(());
+                };
+                let parent_shard_uid = progress.parent_shard;
+                if matches!(
+                    self.resharding_event(),
+                    Some(FlatStorageReshardingEventStatus::SplitShard(parent, ..)) if parent == parent_shard_uid
+                ) {
+                    // Already resumed, either by the parent itself or by the other child.
+                    return Ok(());
+                }
+                if let FlatStorageStatus::Resharding(parent_status) = flat_store
+                    .get_flat_storage_status(parent_shard_uid)
+                    .map_err(|err| Into::<StorageError>::into(err))?
+                {
+                    self.resume(parent_shard_uid, &parent_status)?;
+                }
             }
             FlatStorageReshardingStatus::SplittingParent(status) => {
                 let parent_shard_uid = shard_uid;
+                if matches!(
+                    self.resharding_event(),
+                    Some(FlatStorageReshardingEventStatus::SplitShard(parent, ..)) if parent == parent_shard_uid
+                ) {
+                    // Already resumed by one of the children.
+                    return Ok(());
+                }
                 info!(target: "resharding", ?parent_shard_uid, ?status, "resuming flat storage shard split");
                 self.check_new_event_is_allowed()?;
                 // On resume, flat storage status is already set correctly and read from DB.
                 // Thus, we don't need to care about cancelling other existing resharding events.
-                // However, we don't know the current state of children shards,
-                // so it's better to clean them.
-                self.clean_children_shards(&status)?;
+                // The children contain exactly the key-values copied up to their checkpoint, if
+                // any. Without a checkpoint we don't know their current state, so it's better to
+                // clean them.
+                let flat_store = self.runtime.store().flat_store();
+                let progress = flat_store
+                    .get_resharding_progress(status.left_child_shard)
+                    .map_err(|err| Into::<StorageError>::into(err))?;
+                if progress.and_then(|progress| progress.checkpoint).is_none() {
+                    self.clean_children_shards(&status)?;
+                }
+                // The flat storage of the parent isn't created on startup while it is being
+                // split, but the split task needs it to iterate the deltas.
+                let flat_storage_manager = self.runtime.get_flat_storage_manager();
+                if flat_storage_manager.get_flat_storage_for_shard(parent_shard_uid).is_none() {
+                    flat_storage_manager.create_flat_storage_for_shard(parent_shard_uid)?;
+                }
+                flat_storage_manager
+                    .get_flat_storage_for_shard(parent_shard_uid)
+                    .expect("flat storage of the parent shard must exist!")
+                    .set_flat_head_update_mode(FlatHeadFreezeReason::Resharding, false);
                 self.schedule_split_shard(parent_shard_uid, &status);
             }
             FlatStorageReshardingStatus::CatchingUp(_) => {
@@ -230,7 +297,7 @@ impl FlatStorageResharder {
             .get_flat_storage_manager()
             .get_flat_storage_for_shard(parent_shard)
             .expect("flat storage of the parent shard must exist!")
-            .set_flat_head_update_mode(false);
+            .set_flat_head_update_mode(FlatHeadFreezeReason::Resharding, false);
         store_update.set_flat_storage_status(
             left_child_shard,
             FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CreatingChild),
@@ -239,6 +306,19 @@ impl FlatStorageResharder {
             right_child_shard,
             FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CreatingChild),
         );
+        // Keep the progress made by a previous attempt to split the same parent, the split task
+        // decides whether it can be resumed.
+        for child_shard in [left_child_shard, right_child_shard] {
+            let progress = store
+                .get_resharding_progress(child_shard)
+                .map_err(|err| Into::<StorageError>::into(err))?;
+            if progress.is_none_or(|progress| progress.parent_shard != parent_shard) {
+                store_update.set_resharding_progress(
+                    child_shard,
+                    &FlatStorageReshardingProgress { parent_shard, checkpoint: None },
+                );
+            }
+        }
         store_update.commit()?;
 
         self.schedule_split_shard(parent_shard, &split_params);
@@ -345,15 +425,23 @@ impl FlatStorageResharder {
             }
         };
 
+        events::emit(ChainEvent::ReshardingStarted {
+            block_hash: resharding_block.hash,
+            height: resharding_block.height,
+            parent_shard_uid: parent_shard.to_string(),
+        });
         let metrics = FlatStorageReshardingShardSplitMetrics::new(
             parent_shard,
             split_params.left_child_shard,
             split_params.right_child_shard,
         );
+        let _timer = RESHARDING_PHASE_TIME
+            .with_label_values(&[&parent_shard.to_string(), "flat_storage_split"])
+            .start_timer();
 
         let task_status =
             self.split_shard_task_impl(parent_shard, &split_params, &resharding_block, &metrics);
-        self.split_shard_task_postprocessing(
+        let task_status = self.split_shard_task_postprocessing(
             parent_shard,
             split_params,
             &resharding_block,
@@ -419,7 +507,8 @@ impl FlatStorageResharder {
 
     /// Performs the bulk of [split_shard_task]. This method splits the flat storage of the parent shard.
     ///
-    /// Returns `true` if the routine completed successfully.
+    /// The ranges of the parent's flat state are copied to the children in parallel, then the
+    /// deltas up to the resharding block are copied one block after the other.
     fn split_shard_task_impl(
         &self,
         parent_shard: ShardUId,
@@ -432,92 +521,218 @@ impl FlatStorageResharder {
             return FlatStorageReshardingTaskResult::Cancelled;
         }
 
+        let resharding_config = self.resharding_config.get();
         // Determines after how many bytes worth of key-values the process stops to commit changes
         // and to check cancellation.
-        let batch_size = self.resharding_config.get().batch_size.as_u64() as usize;
+        let batch_size = resharding_config.batch_size.as_u64() as usize;
         metrics.set_split_shard_batch_size(batch_size);
         // Delay between every batch.
-        let batch_delay = self.resharding_config.get().batch_delay.unsigned_abs();
+        let batch_delay = resharding_config.batch_delay.unsigned_abs();
+        let parallelism = resharding_config.flat_storage_split_parallelism.max(1);
 
-        info!(target: "resharding", ?parent_shard, ?split_params, ?resharding_block, ?batch_delay, ?batch_size, "flat storage shard split task: starting key-values copy");
+        info!(target: "resharding", ?parent_shard, ?split_params, ?resharding_block, ?batch_delay, ?batch_size, ?parallelism, "flat storage shard split task: starting key-values copy");
 
-        // Prepare the store object for commits and the iterator over parent's flat storage.
-        let flat_store = self.runtime.store().flat_store();
-        let mut iter = match self.flat_storage_iterator(
-            &flat_store,
-            &parent_shard,
+        let checkpoint = match self.split_shard_checkpoint(
+            parent_shard,
+            split_params,
+            resharding_block,
+        ) {
+            Ok(Some(checkpoint)) => {
+                let num_ranges_done = checkpoint.ranges.iter().filter(|range| range.done).count();
+                info!(target: "resharding", ?parent_shard, num_ranges = checkpoint.ranges.len(), ?num_ranges_done, deltas = ?checkpoint.deltas, "flat storage shard split task: resuming from checkpoint");
+                checkpoint
+            }
+            Ok(None) => SplitShardCheckpoint {
+                resharding_block: *resharding_block,
+                ranges: self.split_shard_ranges(parent_shard, split_params, parallelism),
+                deltas: None,
+            },
+            Err(err) => {
+                error!(target: "resharding", ?parent_shard, ?err, "failed to read flat storage resharding progress");
+                return FlatStorageReshardingTaskResult::Failed;
+            }
+        };
+        let delta_blocks = match self.split_shard_delta_blocks(
+            parent_shard,
             &resharding_block.hash,
+            checkpoint.deltas.as_ref(),
         ) {
-            Ok(iter) => iter,
+            Ok(delta_blocks) => delta_blocks,
             Err(err) => {
-                error!(target: "resharding", ?parent_shard, block_hash=?resharding_block.hash, ?err, "failed to build flat storage iterator");
+                error!(target: "resharding", ?parent_shard, block_hash=?resharding_block.hash, ?err, "failed to find the flat storage deltas to split");
                 return FlatStorageReshardingTaskResult::Failed;
             }
         };
 
-        let mut num_batches_done: usize = 0;
         metrics.set_split_shard_processed_bytes(0);
-        let mut iter_exhausted = false;
+        let copy = SplitShardCopy {
+            flat_store: self.runtime.store().flat_store(),
+            parent_shard,
+            split_params,
+            controller: &self.controller,
+            metrics: Some(metrics),
+            batch_size,
+            batch_delay,
+            checkpoint: Mutex::new(checkpoint),
+            num_batches_done: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            #[cfg(feature = "test_features")]
+            interrupt_after_batches: self.adv_interrupt_split_after_batches,
+        };
+        let mut status = copy.copy_ranges(parallelism);
+        if status == SplitShardCopyStatus::Done {
+            status = copy.copy_deltas(delta_blocks);
+        }
+        match status {
+            SplitShardCopyStatus::Done => FlatStorageReshardingTaskResult::Successful {
+                num_batches_done: copy.num_batches_done.load(atomic::Ordering::Relaxed),
+            },
+            SplitShardCopyStatus::Cancelled => FlatStorageReshardingTaskResult::Cancelled,
+            SplitShardCopyStatus::Failed => FlatStorageReshardingTaskResult::Failed,
+        }
+    }
 
-        loop {
-            let _span = tracing::debug_span!(
-                target: "resharding",
-                "split_shard_task_impl/batch",
-                batch_id = ?num_batches_done)
-            .entered();
-            let store = flat_store.trie_store();
-            let mut store_update = flat_store.store_update();
-            let mut processed_size = 0;
+    /// Makes the promise yield timeout queue of the children's flat state match the one of their
+    /// trie at the resharding block, in `store_update`. The flat state of both children holds the
+    /// queue of the parent, while the queue in the trie of each child may only hold the timeouts
+    /// of its accounts, see [crate::resharding::yield_timeouts].
+    fn split_shard_promise_yield_timeouts(
+        &self,
+        parent_shard: ShardUId,
+        split_params: &ParentSplitParameters,
+        resharding_block: &BlockInfo,
+        store_update: &mut FlatStoreUpdateAdapter,
+    ) -> Result<(), Error> {
+        split_promise_yield_timeouts_in_flat_state(
+            &self.runtime.store().flat_store(),
+            &self.runtime.store().chain_store(),
+            &self.runtime.get_tries(),
+            parent_shard,
+            split_params,
+            resharding_block,
+            store_update,
+        )
+    }
 
-            // Process a `batch_size` worth of key value pairs.
-            while processed_size < batch_size && !iter_exhausted {
-                match iter.next() {
-                    // Stop iterating and commit the batch.
-                    Some(FlatStorageAndDeltaIterItem::CommitPoint) => break,
-                    Some(FlatStorageAndDeltaIterItem::Entry(Ok((key, value)))) => {
-                        processed_size += key.len() + value.as_ref().map_or(0, |v| v.size());
-                        if let Err(err) = shard_split_handle_key_value(
-                            key,
-                            value,
-                            &store,
-                            &mut store_update,
-                            &split_params,
-                        ) {
-                            error!(target: "resharding", ?err, "failed to handle flat storage key");
-                            return FlatStorageReshardingTaskResult::Failed;
-                        }
-                    }
-                    Some(FlatStorageAndDeltaIterItem::Entry(Err(err))) => {
-                        error!(target: "resharding", ?err, "failed to read flat storage value from parent shard");
-                        return FlatStorageReshardingTaskResult::Failed;
-                    }
-                    None => {
-                        iter_exhausted = true;
-                    }
-                }
+    /// Divides the key space of the parent's flat state into `num_ranges` ranges holding about
+    /// the same amount of state, measured by the memory usage of the parent's trie like for state
+    /// parts. The key space is a single range if the trie can't be read.
+    fn split_shard_ranges(
+        &self,
+        parent_shard: ShardUId,
+        split_params: &ParentSplitParameters,
+        num_ranges: usize,
+    ) -> Vec<SplitShardRangeCheckpoint> {
+        let boundaries = match self.split_shard_range_boundaries(
+            parent_shard,
+            &split_params.flat_head,
+            num_ranges,
+        ) {
+            Ok(boundaries) => boundaries,
+            Err(err) => {
+                warn!(target: "resharding", ?parent_shard, ?err, "failed to divide the flat storage into ranges, splitting it sequentially");
+                vec![]
             }
+        };
+        let starts = iter::once(vec![]).chain(boundaries.iter().cloned());
+        let ends = boundaries.into_iter().map(Some).chain(iter::once(None));
+        starts
+            .zip(ends)
+            .map(|(start, end)| SplitShardRangeCheckpoint {
+                start,
+                end,
+                last_key: None,
+                done: false,
+            })
+            .collect()
+    }
 
-            // Make a pause to commit and check if the routine should stop.
-            if let Err(err) = store_update.commit() {
-                error!(target: "resharding", ?err, "failed to commit store update");
-                return FlatStorageReshardingTaskResult::Failed;
+    /// Returns the keys between the ranges of the parent's flat state, in key order.
+    fn split_shard_range_boundaries(
+        &self,
+        parent_shard: ShardUId,
+        flat_head: &BlockInfo,
+        num_ranges: usize,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let chunk_extra =
+            self.runtime.store().chain_store().get_chunk_extra(&flat_head.hash, &parent_shard)?;
+        let trie =
+            self.runtime.get_tries().get_trie_for_shard(parent_shard, *chunk_extra.state_root());
+        let num_ranges = num_ranges as u64;
+        let mut boundaries: Vec<Vec<u8>> = vec![];
+        for range_id in 1..num_ranges {
+            let boundary = trie.find_state_part_boundary(range_id, num_ranges, PROTOCOL_VERSION)?;
+            let boundary = NibbleSlice::nibbles_to_bytes(&boundary);
+            // The boundaries of a small trie can repeat.
+            if !boundary.is_empty() && boundaries.last().is_none_or(|last| *last < boundary) {
+                boundaries.push(boundary);
             }
+        }
+        Ok(boundaries)
+    }
 
-            num_batches_done += 1;
-            metrics.set_split_shard_processed_batches(num_batches_done);
-            metrics.inc_split_shard_processed_bytes_by(processed_size);
+    /// Returns the blocks from the flat head to `block_hash` whose deltas are left to copy,
+    /// in ascending height.
+    fn split_shard_delta_blocks(
+        &self,
+        parent_shard: ShardUId,
+        block_hash: &CryptoHash,
+        checkpoint: Option<&SplitShardDeltasCheckpoint>,
+    ) -> Result<Vec<CryptoHash>, Error> {
+        let flat_storage = self
+            .runtime
+            .get_flat_storage_manager()
+            .get_flat_storage_for_shard(parent_shard)
+            .expect("the flat storage undergoing resharding must exist!");
+        // Must reverse the result because we want ascending block heights.
+        let mut blocks_to_head = flat_storage.get_blocks_to_head(block_hash).map_err(|err| {
+            StorageError::StorageInconsistentState(format!(
+                "failed to find path from block {block_hash} to flat storage head ({err})"
+            ))
+        })?;
+        blocks_to_head.reverse();
+        debug!(target = "resharding", "flat storage blocks to head len = {}", blocks_to_head.len());
+        skip_delta_blocks_before_checkpoint(blocks_to_head, block_hash, checkpoint)
+    }
 
-            // If `iter`` is exhausted we can exit after the store commit.
-            if iter_exhausted {
-                return FlatStorageReshardingTaskResult::Successful { num_batches_done };
+    /// Returns the checkpoint of a previous, interrupted, split of `parent_shard` at the same
+    /// resharding block, if any.
+    ///
+    /// If the children contain the leftovers of a split that can't be resumed they are cleaned.
+    fn split_shard_checkpoint(
+        &self,
+        parent_shard: ShardUId,
+        split_params: &ParentSplitParameters,
+        resharding_block: &BlockInfo,
+    ) -> Result<Option<SplitShardCheckpoint>, Error> {
+        let flat_store = self.runtime.store().flat_store();
+        let Some(progress) = flat_store
+            .get_resharding_progress(split_params.left_child_shard)
+            .map_err(|err| Into::<StorageError>::into(err))?
+        else {
+            return Ok(None);
+        };
+        match progress.checkpoint {
+            Some(checkpoint)
+                if progress.parent_shard == parent_shard
+                    && checkpoint.resharding_block == *resharding_block =>
+            {
+                Ok(Some(checkpoint))
             }
-            if self.controller.is_cancelled() {
-                return FlatStorageReshardingTaskResult::Cancelled;
+            Some(_) => {
+                // The key-values were copied at another resharding block.
+                self.clean_children_shards(split_params)?;
+                let mut store_update = flat_store.store_update();
+                for child_shard in [split_params.left_child_shard, split_params.right_child_shard] {
+                    store_update.set_resharding_progress(
+                        child_shard,
+                        &FlatStorageReshardingProgress { parent_shard, checkpoint: None },
+                    );
+                }
+                store_update.commit()?;
+                Ok(None)
             }
-
-            // Sleep between batches in order to throttle resharding and leave some resource for the
-            // regular node operation.
-            std::thread::sleep(batch_delay);
+            None => Ok(None),
         }
     }
 
@@ -536,13 +751,34 @@ impl FlatStorageResharder {
         resharding_block: &BlockInfo,
         metrics: &FlatStorageReshardingShardSplitMetrics,
         task_status: FlatStorageReshardingTaskResult,
-    ) {
+    ) -> FlatStorageReshardingTaskResult {
         let ParentSplitParameters { left_child_shard, right_child_shard, flat_head, .. } =
             split_params;
         let flat_store = self.runtime.store().flat_store();
         info!(target: "resharding", ?parent_shard, ?task_status, ?split_params, ?resharding_block, "flat storage shard split task: post-processing");
 
         let mut store_update = flat_store.store_update();
+        // The flat storage of the children is only read once they are caught up. Their promise
+        // yield timeouts are patched in the same update which makes them catch up, so that their
+        // flat state never disagrees with their trie for a reader.
+        let task_status = match task_status {
+            FlatStorageReshardingTaskResult::Successful { .. } => {
+                match self.split_shard_promise_yield_timeouts(
+                    parent_shard,
+                    &split_params,
+                    resharding_block,
+                    &mut store_update,
+                ) {
+                    Ok(()) => task_status,
+                    Err(err) => {
+                        error!(target: "resharding", ?parent_shard, ?err, "failed to split the promise yield timeouts");
+                        store_update = flat_store.store_update();
+                        FlatStorageReshardingTaskResult::Failed
+                    }
+                }
+            }
+            _ => task_status,
+        };
         match task_status {
             FlatStorageReshardingTaskResult::Successful { .. } => {
                 // Split shard completed successfully.
@@ -558,12 +794,16 @@ impl FlatStorageResharder {
                 }
                 // Children must perform catchup.
                 for child_shard in [left_child_shard, right_child_shard] {
+                    store_update.remove_resharding_progress(child_shard);
                     store_update.set_flat_storage_status(
                         child_shard,
                         FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
                             *resharding_block,
                         )),
                     );
+                    events::emit(ChainEvent::ReshardingCatchUp {
+                        shard_uid: child_shard.to_string(),
+                    });
                     // Catchup will happen in a separate task, so send a request to schedule the
                     // execution of `shard_catchup_task` for the child shard.
                     self.sender.flat_storage_shard_catchup_sender.send(
@@ -583,18 +823,19 @@ impl FlatStorageResharder {
                 self.runtime
                     .get_flat_storage_manager()
                     .get_flat_storage_for_shard(parent_shard)
-                    .map(|flat_storage| flat_storage.set_flat_head_update_mode(true));
+                    .map(|flat_storage| {
+                        flat_storage
+                            .set_flat_head_update_mode(FlatHeadFreezeReason::Resharding, true)
+                    });
                 // Remove children shards entirely.
                 for child_shard in [left_child_shard, right_child_shard] {
                     store_update.remove_flat_storage(child_shard);
                 }
             }
             FlatStorageReshardingTaskResult::Cancelled => {
-                // Remove children shards leftovers, but keep intact their current status and deltas
-                // plus the current status of the parent, so resharding can resume later.
-                for child_shard in [left_child_shard, right_child_shard] {
-                    store_update.remove_all_values(child_shard);
-                }
+                // Keep intact the children shards values, status, deltas and progress plus the
+                // current status of the parent, so resharding can resume later from the last
+                // checkpoint.
             }
             FlatStorageReshardingTaskResult::Postponed => {
                 panic!("can't finalize processing of a postponed split task!");
@@ -603,61 +844,7 @@ impl FlatStorageResharder {
         store_update.commit().unwrap();
         self.remove_resharding_event();
         metrics.update_shards_status(&self.runtime.get_flat_storage_manager());
-    }
-
-    /// Returns an iterator over a shard's flat storage at the given block hash. This
-    /// iterator contains both flat storage values and deltas.
-    fn flat_storage_iterator<'a>(
-        &self,
-        flat_store: &'a FlatStoreAdapter,
-        shard_uid: &ShardUId,
-        block_hash: &CryptoHash,
-    ) -> Result<Box<FlatStorageAndDeltaIter<'a>>, Error> {
-        let mut iter: Box<FlatStorageAndDeltaIter<'a>> = Box::new(
-            flat_store
-                .iter(*shard_uid)
-                // Get the flat storage iter and wrap the value in Optional::Some to
-                // match the delta iterator so that they can be chained.
-                .map_ok(|(key, value)| (key, Some(value)))
-                // Wrap the iterator's item into an Entry.
-                .map(|entry| FlatStorageAndDeltaIterItem::Entry(entry)),
-        );
-
-        // Get all the blocks from flat head to the wanted block hash.
-        let flat_storage = self
-            .runtime
-            .get_flat_storage_manager()
-            .get_flat_storage_for_shard(*shard_uid)
-            .expect("the flat storage undergoing resharding must exist!");
-        // Must reverse the result because we want ascending block heights.
-        let mut blocks_to_head = flat_storage.get_blocks_to_head(block_hash).map_err(|err| {
-            StorageError::StorageInconsistentState(format!(
-                "failed to find path from block {block_hash} to flat storage head ({err})"
-            ))
-        })?;
-        blocks_to_head.reverse();
-        debug!(target = "resharding", "flat storage blocks to head len = {}", blocks_to_head.len());
-
-        // Get all the delta iterators and wrap the items in Result to match the flat
-        // storage iter so that they can be chained.
-        for block in blocks_to_head {
-            let deltas = flat_store.get_delta(*shard_uid, block).map_err(|err| {
-                StorageError::StorageInconsistentState(format!(
-                    "can't retrieve deltas for flat storage at {block}/{shard_uid:?}({err})"
-                ))
-            })?;
-            let Some(deltas) = deltas else {
-                continue;
-            };
-            // Chain the iterators effectively adding a block worth of deltas.
-            // Before doing so insert a commit point to separate changes to the same key in different transactions.
-            iter = Box::new(iter.chain(iter::once(FlatStorageAndDeltaIterItem::CommitPoint)));
-            let deltas_iter = deltas.0.into_iter();
-            let deltas_iter = deltas_iter.map(|item| FlatStorageAndDeltaIterItem::Entry(Ok(item)));
-            iter = Box::new(iter.chain(deltas_iter));
-        }
-
-        Ok(iter)
+        task_status
     }
 
     /// Task to perform catchup and creation of a flat storage shard spawned from a previous
@@ -674,6 +861,11 @@ impl FlatStorageResharder {
         }
         info!(target: "resharding", ?shard_uid, "flat storage shard catchup task started");
         let metrics = FlatStorageReshardingShardCatchUpMetrics::new(&shard_uid);
+        // The catchup may be postponed several times, so its duration is the sum
+        // of the observed times.
+        let _timer = RESHARDING_PHASE_TIME
+            .with_label_values(&[&shard_uid.to_string(), "flat_storage_catchup"])
+            .start_timer();
         // Apply deltas and then create the flat storage.
         let (num_batches_done, flat_head) = match self.shard_catchup_apply_deltas(
             shard_uid,
@@ -877,6 +1069,10 @@ impl FlatStorageResharder {
         // Create the flat storage entry for this shard in the manager.
         self.runtime.get_flat_storage_manager().create_flat_storage_for_shard(shard_uid)?;
         info!(target: "resharding", ?shard_uid, ?flat_head, "flat storage creation done");
+        events::emit(ChainEvent::ReshardingDone {
+            shard_uid: shard_uid.to_string(),
+            flat_head_height: flat_head.height,
+        });
         Ok(())
     }
 
@@ -902,7 +1098,10 @@ impl FlatStorageResharder {
                 self.runtime
                     .get_flat_storage_manager()
                     .get_flat_storage_for_shard(parent_shard)
-                    .map(|flat_storage| flat_storage.set_flat_head_update_mode(true));
+                    .map(|flat_storage| {
+                        flat_storage
+                            .set_flat_head_update_mode(FlatHeadFreezeReason::Resharding, true)
+                    });
                 // Remove children shards status.
                 for child_shard in [split_status.left_child_shard, split_status.right_child_shard] {
                     store_update.remove_status(child_shard);
@@ -1008,17 +1207,6 @@ impl FlatStorageResharder {
     }
 }
 
-/// Enum used to wrap the `Item` of iterators over flat storage contents or flat storage deltas. Its
-/// purpose is to insert a marker to force store commits during iteration over all entries. This is
-/// necessary because otherwise deltas might set again the value of a flat storage entry inside the
-/// same transaction.
-enum FlatStorageAndDeltaIterItem {
-    Entry(Result<(Vec<u8>, Option<FlatStateValue>), FlatStorageError>),
-    CommitPoint,
-}
-
-type FlatStorageAndDeltaIter<'a> = dyn Iterator<Item = FlatStorageAndDeltaIterItem> + 'a;
-
 impl Debug for FlatStorageResharder {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FlatStorageResharder")
@@ -1028,6 +1216,255 @@ impl Debug for FlatStorageResharder {
     }
 }
 
+/// Completes, in the flat storage of a state snapshot, the split of the parents of the children
+/// among `shard_uids` which are still being created, so that the snapshot can be taken while the
+/// split is in progress instead of waiting for it.
+///
+/// The snapshot holds the flat state of the parent at its flat head, the deltas up to the
+/// resharding block and the checkpoint of the split, if any batch was committed. The copy resumes
+/// from the checkpoint in the snapshot, like the split of the node resumes after a restart, and
+/// the children are left in `CatchingUp` status at the resharding block. The copy is throttled
+/// by `resharding_config` like the split of the node. The flat storage of the node is not touched.
+pub fn split_shards_in_snapshot(
+    tries: &ShardTries,
+    snapshot_flat_store: &FlatStoreAdapter,
+    shard_uids: impl IntoIterator<Item = ShardUId>,
+    resharding_config: &ReshardingConfig,
+) -> Result<(), Error> {
+    let mut parent_shards = vec![];
+    for shard_uid in shard_uids {
+        let status = snapshot_flat_store
+            .get_flat_storage_status(shard_uid)
+            .map_err(|err| Into::<StorageError>::into(err))?;
+        if !matches!(
+            status,
+            FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CreatingChild)
+        ) {
+            continue;
+        }
+        let Some(progress) = snapshot_flat_store
+            .get_resharding_progress(shard_uid)
+            .map_err(|err| Into::<StorageError>::into(err))?
+        else {
+            return Err(Error::ReshardingError(format!(
+                "no resharding progress for the child shard {shard_uid}"
+            )));
+        };
+        if !parent_shards.contains(&progress.parent_shard) {
+            parent_shards.push(progress.parent_shard);
+        }
+    }
+    for parent_shard in parent_shards {
+        split_shard_in_snapshot(tries, snapshot_flat_store, parent_shard, resharding_config)?;
+    }
+    Ok(())
+}
+
+fn split_shard_in_snapshot(
+    tries: &ShardTries,
+    flat_store: &FlatStoreAdapter,
+    parent_shard: ShardUId,
+    resharding_config: &ReshardingConfig,
+) -> Result<(), Error> {
+    let status = flat_store
+        .get_flat_storage_status(parent_shard)
+        .map_err(|err| Into::<StorageError>::into(err))?;
+    let FlatStorageStatus::Resharding(FlatStorageReshardingStatus::SplittingParent(split_params)) =
+        status
+    else {
+        return Err(Error::ReshardingError(format!(
+            "unexpected flat storage status of the parent shard {parent_shard}: {status:?}"
+        )));
+    };
+    let chain_store = tries.store().chain_store();
+    let progress = flat_store
+        .get_resharding_progress(split_params.left_child_shard)
+        .map_err(|err| Into::<StorageError>::into(err))?;
+    let checkpoint = progress
+        .filter(|progress| progress.parent_shard == parent_shard)
+        .and_then(|progress| progress.checkpoint);
+    let checkpoint = match checkpoint {
+        Some(checkpoint) => {
+            if !split_params.resharding_blocks.contains(&checkpoint.resharding_block) {
+                return Err(Error::ReshardingError(format!(
+                    "the checkpoint of the split of {parent_shard} is at another resharding block"
+                )));
+            }
+            checkpoint
+        }
+        None => {
+            // The split hasn't started yet. The resharding block is the one of the canonical chain.
+            let Some(resharding_block) = split_params.resharding_blocks.iter().find(|block| {
+                chain_store
+                    .get_block_hash_by_height(block.height)
+                    .is_ok_and(|hash| hash == block.hash)
+            }) else {
+                return Err(Error::ReshardingError(format!(
+                    "no resharding block of {parent_shard} is in the canonical chain"
+                )));
+            };
+            SplitShardCheckpoint {
+                resharding_block: *resharding_block,
+                ranges: vec![SplitShardRangeCheckpoint {
+                    start: vec![],
+                    end: None,
+                    last_key: None,
+                    done: false,
+                }],
+                deltas: None,
+            }
+        }
+    };
+    let resharding_block = checkpoint.resharding_block;
+    info!(target: "resharding", ?parent_shard, ?resharding_block, "completing flat storage shard split in the state snapshot");
+
+    // The deltas of the parent from its flat head to the resharding block, in ascending height.
+    let deltas_metadata: HashMap<CryptoHash, BlockInfo> = flat_store
+        .get_all_deltas_metadata(parent_shard)
+        .map_err(|err| Into::<StorageError>::into(err))?
+        .into_iter()
+        .map(|metadata| (metadata.block.hash, metadata.block))
+        .collect();
+    let mut delta_blocks = vec![];
+    let mut block_hash = resharding_block.hash;
+    while block_hash != split_params.flat_head.hash {
+        let Some(block) = deltas_metadata.get(&block_hash) else {
+            return Err(StorageError::StorageInconsistentState(format!(
+                "failed to find path from block {} to flat storage head",
+                resharding_block.hash
+            ))
+            .into());
+        };
+        delta_blocks.push(block_hash);
+        block_hash = block.prev_hash;
+    }
+    delta_blocks.reverse();
+    let delta_blocks = skip_delta_blocks_before_checkpoint(
+        delta_blocks,
+        &resharding_block.hash,
+        checkpoint.deltas.as_ref(),
+    )?;
+
+    let controller = FlatStorageResharderController::new();
+    let copy = SplitShardCopy {
+        flat_store: flat_store.clone(),
+        parent_shard,
+        split_params: &split_params,
+        controller: &controller,
+        metrics: None,
+        batch_size: resharding_config.batch_size.as_u64() as usize,
+        batch_delay: resharding_config.batch_delay.unsigned_abs(),
+        checkpoint: Mutex::new(checkpoint),
+        num_batches_done: AtomicUsize::new(0),
+        stopped: AtomicBool::new(false),
+        #[cfg(feature = "test_features")]
+        interrupt_after_batches: None,
+    };
+    let mut status = copy.copy_ranges(resharding_config.flat_storage_split_parallelism.max(1));
+    if status == SplitShardCopyStatus::Done {
+        status = copy.copy_deltas(delta_blocks);
+    }
+    if status != SplitShardCopyStatus::Done {
+        return Err(Error::ReshardingError(format!(
+            "failed to split {parent_shard} in the state snapshot: {status:?}"
+        )));
+    }
+    let mut store_update = flat_store.store_update();
+    split_promise_yield_timeouts_in_flat_state(
+        flat_store,
+        &chain_store,
+        tries,
+        parent_shard,
+        &split_params,
+        &resharding_block,
+        &mut store_update,
+    )?;
+    for child_shard in [split_params.left_child_shard, split_params.right_child_shard] {
+        store_update.remove_resharding_progress(child_shard);
+        store_update.set_flat_storage_status(
+            child_shard,
+            FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
+                resharding_block,
+            )),
+        );
+    }
+    store_update.commit()?;
+    info!(target: "resharding", ?parent_shard, num_batches_done = copy.num_batches_done.load(atomic::Ordering::Relaxed), "completed flat storage shard split in the state snapshot");
+    Ok(())
+}
+
+/// Skips the blocks, in ascending height, whose deltas were completely processed before the
+/// checkpoint.
+fn skip_delta_blocks_before_checkpoint(
+    mut blocks: Vec<CryptoHash>,
+    block_hash: &CryptoHash,
+    checkpoint: Option<&SplitShardDeltasCheckpoint>,
+) -> Result<Vec<CryptoHash>, Error> {
+    if let Some(checkpoint) = checkpoint {
+        let Some(position) = blocks.iter().position(|block| *block == checkpoint.block) else {
+            return Err(StorageError::StorageInconsistentState(format!(
+                "checkpoint block {} is not between the flat storage head and {block_hash}",
+                checkpoint.block
+            ))
+            .into());
+        };
+        blocks.drain(..position);
+    }
+    Ok(blocks)
+}
+
+/// Writes to `store_update` the promise yield timeout queue of the trie of the children at the
+/// resharding block, for their flat state, if the parent had a queue and the queue of the children
+/// was re-indexed.
+fn split_promise_yield_timeouts_in_flat_state(
+    flat_store: &FlatStoreAdapter,
+    chain_store: &ChainStoreAdapter,
+    tries: &ShardTries,
+    parent_shard: ShardUId,
+    split_params: &ParentSplitParameters,
+    resharding_block: &BlockInfo,
+    store_update: &mut FlatStoreUpdateAdapter,
+) -> Result<(), Error> {
+    let indices_key = TrieKey::PromiseYieldIndices.to_vec();
+    // Both children got the queue of the parent, if there is any.
+    if flat_store
+        .get(split_params.left_child_shard, &indices_key)
+        .map_err(|err| Into::<StorageError>::into(err))?
+        .is_none()
+    {
+        return Ok(());
+    }
+    // The resharding block is the last one of its epoch, the children start in the next one.
+    let child_epoch_id = *chain_store.get_block_header(&resharding_block.hash)?.next_epoch_id();
+    let child_epoch_info = tries.store().epoch_store().get_epoch_info(&child_epoch_id)?;
+    if !ProtocolFeature::SplitAwarePromiseYieldTimeouts.enabled(child_epoch_info.protocol_version())
+    {
+        return Ok(());
+    }
+    let parent_chunk_extra = chain_store.get_chunk_extra(&resharding_block.hash, &parent_shard)?;
+    let parent_trie = tries.get_view_trie_for_shard(parent_shard, *parent_chunk_extra.state_root());
+    let Some(indices) = get::<PromiseYieldIndices>(&parent_trie, &TrieKey::PromiseYieldIndices)?
+    else {
+        return Ok(());
+    };
+    for child_shard in [split_params.left_child_shard, split_params.right_child_shard] {
+        let child_chunk_extra =
+            chain_store.get_chunk_extra(&resharding_block.hash, &child_shard)?;
+        let child_trie =
+            tries.get_view_trie_for_shard(child_shard, *child_chunk_extra.state_root());
+        let keys = iter::once(TrieKey::PromiseYieldIndices).chain(
+            (indices.first_index..indices.next_available_index)
+                .map(|index| TrieKey::PromiseYieldTimeout { index }),
+        );
+        for key in keys {
+            let key = key.to_vec();
+            let value = child_trie.get(&key, AccessOptions::NO_SIDE_EFFECTS)?;
+            store_update.set(child_shard, key, value.map(|value| FlatStateValue::on_disk(&value)));
+        }
+    }
+    Ok(())
+}
+
 /// Retrieves the flat head of the given `shard`.
 /// The shard must be in [FlatStorageStatus::Ready] state otherwise this method returns an error.
 fn retrieve_shard_flat_head(shard: ShardUId, store: &FlatStoreAdapter) -> Result<BlockInfo, Error> {
@@ -1042,72 +1479,328 @@ fn retrieve_shard_flat_head(shard: ShardUId, store: &FlatStoreAdapter) -> Result
     }
 }
 
-/// Handles the inheritance of a key-value pair from parent shard to children shards.
-fn shard_split_handle_key_value(
-    key: Vec<u8>,
-    value: Option<FlatStateValue>,
-    store: &TrieStoreAdapter,
-    store_update: &mut FlatStoreUpdateAdapter,
-    split_params: &ParentSplitParameters,
-) -> Result<(), Error> {
-    if key.is_empty() {
-        panic!("flat storage key is empty!")
+/// Outcome of copying a part of the parent's key-values to the children. The outcome of several
+/// parts is the greatest of their outcomes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SplitShardCopyStatus {
+    Done,
+    Cancelled,
+    Failed,
+}
+
+/// Copies the key-values of the parent to the children in batches, keeping the resharding
+/// progress of the children up to date with each committed batch.
+struct SplitShardCopy<'a> {
+    flat_store: FlatStoreAdapter,
+    parent_shard: ShardUId,
+    split_params: &'a ParentSplitParameters,
+    controller: &'a FlatStorageResharderController,
+    /// Not set when the split is completed in a state snapshot.
+    metrics: Option<&'a FlatStorageReshardingShardSplitMetrics>,
+    batch_size: usize,
+    batch_delay: std::time::Duration,
+    /// The progress committed so far.
+    checkpoint: Mutex<SplitShardCheckpoint>,
+    num_batches_done: AtomicUsize,
+    /// Set when one of the ranges can't be copied, so that the copy of the others stops too.
+    stopped: AtomicBool,
+    #[cfg(feature = "test_features")]
+    interrupt_after_batches: Option<usize>,
+}
+
+impl SplitShardCopy<'_> {
+    /// Copies the ranges of the parent's flat state which are not done yet, `parallelism` of them
+    /// at a time.
+    fn copy_ranges(&self, parallelism: usize) -> SplitShardCopyStatus {
+        let pending_ranges = self
+            .checkpoint
+            .lock()
+            .unwrap()
+            .ranges
+            .iter()
+            .positions(|range| !range.done)
+            .collect_vec();
+        if pending_ranges.is_empty() {
+            return SplitShardCopyStatus::Done;
+        }
+        let thread_pool = match rayon::ThreadPoolBuilder::new()
+            .num_threads(parallelism.min(pending_ranges.len()))
+            .thread_name(|index| format!("flat_storage_split_{index}"))
+            .build()
+        {
+            Ok(thread_pool) => thread_pool,
+            Err(err) => {
+                error!(target: "resharding", ?err, "failed to create the flat storage split thread pool");
+                return SplitShardCopyStatus::Failed;
+            }
+        };
+        thread_pool.install(|| {
+            pending_ranges
+                .into_par_iter()
+                .map(|range_index| self.copy_range(range_index))
+                .reduce(|| SplitShardCopyStatus::Done, Ord::max)
+        })
     }
-    let key_column_prefix = key[0];
 
-    match key_column_prefix {
-        col::ACCOUNT => copy_kv_to_child(
-            &split_params,
-            key,
-            value,
-            store_update,
-            parse_account_id_from_account_key,
-        )?,
-        col::CONTRACT_DATA => copy_kv_to_child(
-            &split_params,
-            key,
-            value,
-            store_update,
-            parse_account_id_from_contract_data_key,
-        )?,
-        col::CONTRACT_CODE => copy_kv_to_child(
-            &split_params,
-            key,
-            value,
-            store_update,
-            parse_account_id_from_contract_code_key,
-        )?,
-        col::ACCESS_KEY => copy_kv_to_child(
-            &split_params,
-            key,
-            value,
-            store_update,
-            parse_account_id_from_access_key_key,
-        )?,
-        col::RECEIVED_DATA => copy_kv_to_child(
-            &split_params,
-            key,
-            value,
-            store_update,
-            parse_account_id_from_received_data_key,
-        )?,
+    fn copy_range(&self, range_index: usize) -> SplitShardCopyStatus {
+        let _span = tracing::debug_span!(
+            target: "resharding",
+            "split_shard_task_impl/range",
+            ?range_index)
+        .entered();
+        let SplitShardRangeCheckpoint { start, end, last_key, .. } =
+            self.checkpoint.lock().unwrap().ranges[range_index].clone();
+        let from = last_key.as_ref().unwrap_or(&start).clone();
+        let mut iter = self
+            .flat_store
+            .iter_range(self.parent_shard, Some(from.as_slice()), end.as_deref())
+            // The range is inclusive, skip the key of the checkpoint itself.
+            .filter_ok(|(key, _)| Some(key) != last_key.as_ref());
+        let store = self.flat_store.trie_store();
+        loop {
+            let mut store_update = self.flat_store.store_update();
+            let mut processed_size = 0;
+            let mut batch_last_key = None;
+            let mut range_done = false;
+
+            // Process a `batch_size` worth of key value pairs.
+            while processed_size < self.batch_size {
+                match iter.next() {
+                    Some(Ok((key, value))) => {
+                        processed_size += key.len() + value.size();
+                        batch_last_key = Some(key.clone());
+                        if let Err(err) = shard_split_handle_key_value(
+                            key,
+                            Some(value),
+                            &store,
+                            &mut store_update,
+                            self.split_params,
+                        ) {
+                            error!(target: "resharding", ?err, "failed to handle flat storage key");
+                            return self.stop(SplitShardCopyStatus::Failed);
+                        }
+                    }
+                    Some(Err(err)) => {
+                        error!(target: "resharding", ?err, "failed to read flat storage value from parent shard");
+                        return self.stop(SplitShardCopyStatus::Failed);
+                    }
+                    None => {
+                        range_done = true;
+                        break;
+                    }
+                }
+            }
+
+            let committed = self.commit_batch(store_update, processed_size, |checkpoint| {
+                let range = &mut checkpoint.ranges[range_index];
+                if batch_last_key.is_some() {
+                    range.last_key = batch_last_key.clone();
+                }
+                range.done = range_done;
+            });
+            if !committed {
+                return self.stop(SplitShardCopyStatus::Failed);
+            }
+            if range_done {
+                return SplitShardCopyStatus::Done;
+            }
+            if let Some(status) = self.check_stop() {
+                return status;
+            }
+            // Sleep between batches in order to throttle resharding and leave some resource for the
+            // regular node operation.
+            std::thread::sleep(self.batch_delay);
+        }
+    }
+
+    /// Copies the deltas of `blocks` after the checkpoint, in block order. The deltas of each block
+    /// are committed separately, otherwise they might set again the value of a key inside the same
+    /// transaction.
+    fn copy_deltas(&self, blocks: Vec<CryptoHash>) -> SplitShardCopyStatus {
+        let checkpoint = self.checkpoint.lock().unwrap().deltas.clone();
+        let store = self.flat_store.trie_store();
+        for block in blocks {
+            let deltas = match self.flat_store.get_delta(self.parent_shard, block) {
+                Ok(deltas) => deltas,
+                Err(err) => {
+                    error!(target: "resharding", ?err, ?block, shard_uid = ?self.parent_shard, "can't retrieve deltas for flat storage");
+                    return SplitShardCopyStatus::Failed;
+                }
+            };
+            let Some(deltas) = deltas else {
+                continue;
+            };
+            // Sort the deltas so that the position of a key in its block can be checkpointed.
+            let mut deltas: Vec<_> = deltas.0.into_iter().collect();
+            deltas.sort_by(|(a, _), (b, _)| a.cmp(b));
+            if let Some(checkpoint) = &checkpoint {
+                if block == checkpoint.block {
+                    deltas.retain(|(key, _)| *key > checkpoint.last_key);
+                }
+            }
+
+            let mut deltas = deltas.into_iter().peekable();
+            while deltas.peek().is_some() {
+                let mut store_update = self.flat_store.store_update();
+                let mut processed_size = 0;
+                let mut batch_last_key = vec![];
+                while processed_size < self.batch_size {
+                    let Some((key, value)) = deltas.next() else {
+                        break;
+                    };
+                    processed_size += key.len() + value.as_ref().map_or(0, |v| v.size());
+                    batch_last_key = key.clone();
+                    if let Err(err) = shard_split_handle_key_value(
+                        key,
+                        value,
+                        &store,
+                        &mut store_update,
+                        self.split_params,
+                    ) {
+                        error!(target: "resharding", ?err, "failed to handle flat storage key");
+                        return SplitShardCopyStatus::Failed;
+                    }
+                }
+
+                let committed = self.commit_batch(store_update, processed_size, |checkpoint| {
+                    checkpoint.deltas = Some(SplitShardDeltasCheckpoint {
+                        block,
+                        last_key: batch_last_key.clone(),
+                    });
+                });
+                if !committed {
+                    return SplitShardCopyStatus::Failed;
+                }
+                if let Some(status) = self.check_stop() {
+                    return status;
+                }
+                std::thread::sleep(self.batch_delay);
+            }
+        }
+        SplitShardCopyStatus::Done
+    }
+
+    /// Commits a batch of key-values together with the checkpoint updated by `update_checkpoint`,
+    /// so that the split resumes exactly after the last processed keys. Returns `false` if the
+    /// commit failed.
+    ///
+    /// The batches of the ranges are committed concurrently, each with the progress committed
+    /// before it. The progress of a batch committed at the same time may then be missing from the
+    /// stored checkpoint, which only makes the split copy that batch again when it resumes.
+    fn commit_batch(
+        &self,
+        mut store_update: FlatStoreUpdateAdapter,
+        processed_size: usize,
+        update_checkpoint: impl Fn(&mut SplitShardCheckpoint),
+    ) -> bool {
+        let mut checkpoint = self.checkpoint.lock().unwrap().clone();
+        update_checkpoint(&mut checkpoint);
+        let progress = FlatStorageReshardingProgress {
+            parent_shard: self.parent_shard,
+            checkpoint: Some(checkpoint),
+        };
+        for child_shard in [self.split_params.left_child_shard, self.split_params.right_child_shard]
+        {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        if let Err(err) = store_update.commit() {
+            error!(target: "resharding", ?err, "failed to commit store update");
+            return false;
+        }
+        update_checkpoint(&mut self.checkpoint.lock().unwrap());
+
+        let num_batches_done = self.num_batches_done.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        if let Some(metrics) = self.metrics {
+            metrics.set_split_shard_processed_batches(num_batches_done);
+            metrics.inc_split_shard_processed_bytes_by(processed_size);
+        }
+        #[cfg(feature = "test_features")]
+        if self.interrupt_after_batches == Some(num_batches_done) {
+            info!(target: "resharding", parent_shard = ?self.parent_shard, ?num_batches_done, "flat storage shard split task: interrupted");
+            self.stopped.store(true, atomic::Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Returns the status to stop the copy with, if it has to stop.
+    fn check_stop(&self) -> Option<SplitShardCopyStatus> {
+        if self.controller.is_cancelled() || self.stopped.load(atomic::Ordering::Relaxed) {
+            return Some(SplitShardCopyStatus::Cancelled);
+        }
+        None
+    }
+
+    /// Stops the copy of all the ranges.
+    fn stop(&self, status: SplitShardCopyStatus) -> SplitShardCopyStatus {
+        self.stopped.store(true, atomic::Ordering::Relaxed);
+        status
+    }
+}
+
+/// The children of a split parent shard which a key of its flat storage is
+/// copied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitKeyDestination {
+    /// The child holding the account of the key.
+    Child(ShardUId),
+    /// Both children, for the shard-wide queues and states.
+    AllChildren,
+    /// Only the left child, for the buffered receipts.
+    LeftChild,
+}
+
+/// Returns the children of the split which the key of the parent's flat
+/// storage is copied to.
+pub fn split_key_destination(
+    key: &[u8],
+    split_params: &ParentSplitParameters,
+) -> Result<SplitKeyDestination, Error> {
+    if key.is_empty() {
+        panic!("flat storage key is empty!")
+    }
+    let key_column_prefix = key[0];
+
+    let account_id = match key_column_prefix {
+        col::ACCOUNT => parse_account_id_from_account_key(key)?,
+        col::CONTRACT_DATA => parse_account_id_from_contract_data_key(key)?,
+        col::CONTRACT_CODE => parse_account_id_from_contract_code_key(key)?,
+        col::ACCESS_KEY => parse_account_id_from_access_key_key(key)?,
+        col::RECEIVED_DATA => parse_account_id_from_received_data_key(key)?,
         col::POSTPONED_RECEIPT_ID
         | col::PENDING_DATA_COUNT
         | col::POSTPONED_RECEIPT
-        | col::PROMISE_YIELD_RECEIPT => {
-            copy_kv_to_child(&split_params, key, value, store_update, |raw_key: &[u8]| {
-                parse_account_id_from_trie_key_with_separator(
-                    key_column_prefix,
-                    raw_key,
-                    &format!("col at index {}", key_column_prefix),
-                )
-            })?
-        }
+        | col::PROMISE_YIELD_RECEIPT => parse_account_id_from_trie_key_with_separator(
+            key_column_prefix,
+            key,
+            &format!("col at index {}", key_column_prefix),
+        )?,
         col::DELAYED_RECEIPT_OR_INDICES
         | col::PROMISE_YIELD_INDICES
         | col::PROMISE_YIELD_TIMEOUT
         | col::BANDWIDTH_SCHEDULER_STATE
-        | col::GLOBAL_CONTRACT_CODE => {
+        | col::GLOBAL_CONTRACT_CODE => return Ok(SplitKeyDestination::AllChildren),
+        col::BUFFERED_RECEIPT_INDICES
+        | col::BUFFERED_RECEIPT
+        | col::BUFFERED_RECEIPT_GROUPS_QUEUE_DATA
+        | col::BUFFERED_RECEIPT_GROUPS_QUEUE_ITEM => return Ok(SplitKeyDestination::LeftChild),
+        _ => unreachable!("key: {:?} should not appear in flat store!", key),
+    };
+    Ok(SplitKeyDestination::Child(child_shard_of_account(split_params, account_id)))
+}
+
+/// Handles the inheritance of a key-value pair from parent shard to children shards.
+fn shard_split_handle_key_value(
+    key: Vec<u8>,
+    value: Option<FlatStateValue>,
+    store: &TrieStoreAdapter,
+    store_update: &mut FlatStoreUpdateAdapter,
+    split_params: &ParentSplitParameters,
+) -> Result<(), Error> {
+    match split_key_destination(&key, split_params)? {
+        SplitKeyDestination::Child(child_shard_uid) => {
+            store_update.set(child_shard_uid, key, value);
+        }
+        SplitKeyDestination::AllChildren => {
             copy_kv_to_all_children(&split_params, key, value.clone(), store_update);
             let Some(flat_state_value) = value else {
                 return Ok(());
@@ -1133,31 +1826,20 @@ fn shard_split_handle_key_value(
                 refcount_increment,
             );
         }
-        col::BUFFERED_RECEIPT_INDICES
-        | col::BUFFERED_RECEIPT
-        | col::BUFFERED_RECEIPT_GROUPS_QUEUE_DATA
-        | col::BUFFERED_RECEIPT_GROUPS_QUEUE_ITEM => {
+        SplitKeyDestination::LeftChild => {
             copy_kv_to_left_child(&split_params, key, value, store_update)
         }
-        _ => unreachable!("key: {:?} should not appear in flat store!", key),
     }
     Ok(())
 }
 
-/// Copies a key-value pair to the correct child shard by matching the account-id to the provided shard layout.
-fn copy_kv_to_child(
-    split_params: &ParentSplitParameters,
-    key: Vec<u8>,
-    value: Option<FlatStateValue>,
-    store_update: &mut FlatStoreUpdateAdapter,
-    account_id_parser: impl FnOnce(&[u8]) -> Result<AccountId, std::io::Error>,
-) -> Result<(), Error> {
+/// Returns the child shard of the account, by matching the account-id to the new shard layout.
+fn child_shard_of_account(split_params: &ParentSplitParameters, account_id: AccountId) -> ShardUId {
     let ParentSplitParameters { left_child_shard, right_child_shard, shard_layout, .. } =
         &split_params;
     // Derive the shard uid for this account in the new shard layout.
-    let account_id = account_id_parser(&key)?;
     let new_shard_id = shard_layout.account_id_to_shard_id(&account_id);
-    let mut new_shard_uid = ShardUId::from_shard_id_and_layout(new_shard_id, &shard_layout);
+    let new_shard_uid = ShardUId::from_shard_id_and_layout(new_shard_id, &shard_layout);
 
     // Sanity check we are truly writing to one of the expected children shards.
     if new_shard_uid != *left_child_shard && new_shard_uid != *right_child_shard {
@@ -1175,11 +1857,9 @@ fn copy_kv_to_child(
         let err_msg = "account id doesn't map to any child shard! - copying to the closer child";
         warn!(target: "resharding", ?new_shard_uid, ?closer_shard_uid, ?left_child_shard, ?right_child_shard, ?shard_layout, ?account_id, err_msg);
 
-        new_shard_uid = *closer_shard_uid;
+        return *closer_shard_uid;
     }
-    // Add the new flat store entry.
-    store_update.set(new_shard_uid, key, value);
-    Ok(())
+    new_shard_uid
 }
 
 /// Copies a key-value pair to both children.
@@ -1328,6 +2008,7 @@ mod tests {
         },
     };
     use near_store::{
+        STATE_SNAPSHOT_COLUMNS, checkpoint_hot_storage_and_cleanup_columns,
         flat::{BlockInfo, FlatStorageManager, FlatStorageReadyStatus},
         genesis::initialize_genesis_state,
         test_utils::create_test_store,
@@ -1340,7 +2021,7 @@ mod tests {
 
     use super::*;
     use assert_matches::assert_matches;
-    use more_asserts::assert_gt;
+    use more_asserts::{assert_ge, assert_gt};
     use near_async::messaging::{CanSend, IntoMultiSender};
     use near_crypto::{KeyType, PublicKey};
 
@@ -1865,6 +2546,290 @@ mod tests {
         }
     }
 
+    /// The split of a parent which hasn't started yet in the node can be completed in a state
+    /// snapshot, leaving the flat storage of the node as is.
+    #[test]
+    fn split_shard_in_snapshot() {
+        init_test_logger();
+        let (mut chain, resharder, _sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let left_child = ShardUId { version: 3, shard_id: 2 };
+        let right_child = ShardUId { version: 3, shard_id: 3 };
+        let flat_store = resharder.runtime.store().flat_store();
+
+        add_blocks_to_chain(
+            &mut chain,
+            2,
+            PreviousBlockHeight::ChainHead,
+            NextBlockHeight::ChainHeadPlusOne,
+        );
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // The split task is never run.
+        let tempdir = tempfile::tempdir().unwrap();
+        let snapshot = checkpoint_hot_storage_and_cleanup_columns(
+            &resharder.runtime.store(),
+            tempdir.path(),
+            Some(STATE_SNAPSHOT_COLUMNS),
+        )
+        .unwrap();
+        let snapshot_flat_store = snapshot.get_hot_store().flat_store();
+        split_shards_in_snapshot(
+            &resharder.runtime.get_tries(),
+            &snapshot_flat_store,
+            [left_child, right_child],
+            &resharder.resharding_config.get(),
+        )
+        .unwrap();
+
+        let resharding_block = resharder.resharding_event().map(|event| match event {
+            FlatStorageReshardingEventStatus::SplitShard(_, split_params, _) => {
+                split_params.resharding_blocks[0]
+            }
+        });
+        for child in [left_child, right_child] {
+            assert_eq!(
+                snapshot_flat_store.get_flat_storage_status(child),
+                Ok(FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
+                    resharding_block.unwrap()
+                )))
+            );
+            assert_eq!(
+                flat_store.get_flat_storage_status(child),
+                Ok(FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CreatingChild))
+            );
+            assert_eq!(flat_store.iter(child).count(), 0);
+        }
+        let account_mm_key = TrieKey::Account { account_id: account!("mm") };
+        let account_vv_key = TrieKey::Account { account_id: account!("vv") };
+        assert!(
+            snapshot_flat_store
+                .get(left_child, &account_mm_key.to_vec())
+                .is_ok_and(|val| val.is_some())
+        );
+        assert!(
+            snapshot_flat_store
+                .get(right_child, &account_vv_key.to_vec())
+                .is_ok_and(|val| val.is_some())
+        );
+    }
+
+    /// The split of a parent interrupted in the middle of a range in the node is completed in a
+    /// state snapshot from the checkpoint, without copying again the key-values processed before
+    /// it.
+    #[test]
+    fn split_shard_in_snapshot_resumes_from_checkpoint() {
+        init_test_logger();
+        let (chain, resharder, _sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            resharding_block,
+            ..
+        } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let parent_keys: Vec<Vec<u8>> =
+            flat_store.iter(parent_shard).map_ok(|(key, _)| key).collect::<Result<_, _>>().unwrap();
+        let checkpoint_key = parent_keys[parent_keys.len() / 4].clone();
+        let range_boundary = parent_keys[parent_keys.len() / 2].clone();
+
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // Pretend that the key-values of the first range up to the checkpoint and all the
+        // key-values of the second range were copied before the snapshot.
+        let progress = FlatStorageReshardingProgress {
+            parent_shard,
+            checkpoint: Some(SplitShardCheckpoint {
+                resharding_block,
+                ranges: vec![
+                    SplitShardRangeCheckpoint {
+                        start: vec![],
+                        end: Some(range_boundary.clone()),
+                        last_key: Some(checkpoint_key.clone()),
+                        done: false,
+                    },
+                    SplitShardRangeCheckpoint {
+                        start: range_boundary.clone(),
+                        end: None,
+                        last_key: None,
+                        done: true,
+                    },
+                ],
+                deltas: None,
+            }),
+        };
+        let mut store_update = flat_store.store_update();
+        for child_shard in [left_child_shard, right_child_shard] {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        store_update.commit().unwrap();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let snapshot = checkpoint_hot_storage_and_cleanup_columns(
+            &resharder.runtime.store(),
+            tempdir.path(),
+            Some(STATE_SNAPSHOT_COLUMNS),
+        )
+        .unwrap();
+        let snapshot_flat_store = snapshot.get_hot_store().flat_store();
+        split_shards_in_snapshot(
+            &resharder.runtime.get_tries(),
+            &snapshot_flat_store,
+            [left_child_shard, right_child_shard],
+            &resharder.resharding_config.get(),
+        )
+        .unwrap();
+
+        // Only the keys of the first range after the checkpoint were copied in the snapshot.
+        for key in parent_keys {
+            let copied = [left_child_shard, right_child_shard]
+                .into_iter()
+                .any(|child_shard| snapshot_flat_store.get(child_shard, &key).unwrap().is_some());
+            assert_eq!(copied, key > checkpoint_key && key < range_boundary);
+        }
+        for child_shard in [left_child_shard, right_child_shard] {
+            assert_eq!(
+                snapshot_flat_store.get_flat_storage_status(child_shard),
+                Ok(FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
+                    resharding_block
+                )))
+            );
+            assert_eq!(snapshot_flat_store.get_resharding_progress(child_shard), Ok(None));
+            assert_eq!(flat_store.get_resharding_progress(child_shard), Ok(Some(progress.clone())));
+        }
+    }
+
+    /// The split of a parent interrupted while copying the deltas of a block in the node is
+    /// completed in a state snapshot after the last key copied from the deltas of that block.
+    #[test]
+    fn split_shard_in_snapshot_resumes_from_deltas_checkpoint() {
+        init_test_logger();
+        let (mut chain, resharder, _sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        add_blocks_to_chain(
+            &mut chain,
+            2,
+            PreviousBlockHeight::ChainHead,
+            NextBlockHeight::ChainHeadPlusOne,
+        );
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            resharding_block,
+            ..
+        } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+
+        let account_key = |account: &str| TrieKey::Account { account_id: account!(account) };
+        let account_change = |account: &str| RawStateChangesWithTrieKey {
+            trie_key: account_key(account),
+            changes: vec![RawStateChange {
+                cause: StateChangeCause::InitialState,
+                data: Some(account.as_bytes().to_vec()),
+            }],
+        };
+        let manager = chain.runtime_adapter.get_flat_storage_manager();
+        let mut delta_blocks = vec![];
+        for (height, accounts) in [(1, vec!["oo", "pp", "vv"]), (2, vec!["qq"])] {
+            let block = chain.get_block_by_height(height).unwrap();
+            let state_changes = accounts.into_iter().map(account_change).collect_vec();
+            manager
+                .save_flat_state_changes(
+                    *block.hash(),
+                    *block.header().prev_hash(),
+                    height,
+                    parent_shard,
+                    &state_changes,
+                )
+                .unwrap()
+                .commit()
+                .unwrap();
+            delta_blocks.push(*block.hash());
+        }
+
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // Pretend that the flat state and the deltas of the first block up to account 'oo' were
+        // copied before the snapshot.
+        let progress = FlatStorageReshardingProgress {
+            parent_shard,
+            checkpoint: Some(SplitShardCheckpoint {
+                resharding_block,
+                ranges: vec![SplitShardRangeCheckpoint {
+                    start: vec![],
+                    end: None,
+                    last_key: None,
+                    done: true,
+                }],
+                deltas: Some(SplitShardDeltasCheckpoint {
+                    block: delta_blocks[0],
+                    last_key: account_key("oo").to_vec(),
+                }),
+            }),
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let mut store_update = flat_store.store_update();
+        for child_shard in [left_child_shard, right_child_shard] {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        store_update.commit().unwrap();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let snapshot = checkpoint_hot_storage_and_cleanup_columns(
+            &resharder.runtime.store(),
+            tempdir.path(),
+            Some(STATE_SNAPSHOT_COLUMNS),
+        )
+        .unwrap();
+        let snapshot_flat_store = snapshot.get_hot_store().flat_store();
+        split_shards_in_snapshot(
+            &resharder.runtime.get_tries(),
+            &snapshot_flat_store,
+            [left_child_shard, right_child_shard],
+            &resharder.resharding_config.get(),
+        )
+        .unwrap();
+
+        // Only the deltas after the checkpoint were copied in the snapshot.
+        let is_copied = |account: &str| {
+            [left_child_shard, right_child_shard].into_iter().any(|child_shard| {
+                snapshot_flat_store
+                    .get(child_shard, &account_key(account).to_vec())
+                    .unwrap()
+                    .is_some()
+            })
+        };
+        assert!(!is_copied("mm"));
+        assert!(!is_copied("oo"));
+        assert!(is_copied("pp"));
+        assert!(is_copied("qq"));
+        assert_eq!(
+            snapshot_flat_store.get(right_child_shard, &account_key("vv").to_vec()),
+            Ok(Some(FlatStateValue::inlined("vv".as_bytes())))
+        );
+        for child_shard in [left_child_shard, right_child_shard] {
+            assert_eq!(
+                snapshot_flat_store.get_flat_storage_status(child_shard),
+                Ok(FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
+                    resharding_block
+                )))
+            );
+            assert_eq!(flat_store.iter(child_shard).count(), 0);
+        }
+    }
+
     /// Split shard task should run in batches.
     #[test]
     fn split_shard_batching() {
@@ -1892,6 +2857,308 @@ mod tests {
         assert_gt!(num_batches_done, 1);
     }
 
+    /// Split shard task should resume right after the checkpoint left by a previous attempt in each
+    /// range, without copying again the key-values processed before it.
+    #[test]
+    fn split_shard_resumes_from_checkpoint() {
+        init_test_logger();
+        let (chain, resharder, sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            resharding_block,
+            ..
+        } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let parent_keys: Vec<Vec<u8>> =
+            flat_store.iter(parent_shard).map_ok(|(key, _)| key).collect::<Result<_, _>>().unwrap();
+        let checkpoint_key = parent_keys[parent_keys.len() / 4].clone();
+        let range_boundary = parent_keys[parent_keys.len() / 2].clone();
+
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // Pretend that the key-values of the first range up to the checkpoint and all the
+        // key-values of the second range were copied before a restart.
+        let progress = FlatStorageReshardingProgress {
+            parent_shard,
+            checkpoint: Some(SplitShardCheckpoint {
+                resharding_block,
+                ranges: vec![
+                    SplitShardRangeCheckpoint {
+                        start: vec![],
+                        end: Some(range_boundary.clone()),
+                        last_key: Some(checkpoint_key.clone()),
+                        done: false,
+                    },
+                    SplitShardRangeCheckpoint {
+                        start: range_boundary.clone(),
+                        end: None,
+                        last_key: None,
+                        done: true,
+                    },
+                ],
+                deltas: None,
+            }),
+        };
+        let mut store_update = flat_store.store_update();
+        for child_shard in [left_child_shard, right_child_shard] {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        store_update.commit().unwrap();
+
+        assert_matches!(
+            sender.call_split_shard_task(),
+            FlatStorageReshardingTaskResult::Successful { .. }
+        );
+
+        // Only the keys of the first range after the checkpoint were copied.
+        for key in parent_keys {
+            let copied = [left_child_shard, right_child_shard]
+                .into_iter()
+                .any(|child_shard| flat_store.get(child_shard, &key).unwrap().is_some());
+            assert_eq!(copied, key > checkpoint_key && key < range_boundary);
+        }
+        for child_shard in [left_child_shard, right_child_shard] {
+            assert_eq!(flat_store.get_resharding_progress(child_shard), Ok(None));
+        }
+    }
+
+    /// A split interrupted while copying the deltas of a block resumes after the last key copied
+    /// from the deltas of that block.
+    #[test]
+    fn split_shard_resumes_from_deltas_checkpoint() {
+        init_test_logger();
+        let (mut chain, resharder, sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        add_blocks_to_chain(
+            &mut chain,
+            2,
+            PreviousBlockHeight::ChainHead,
+            NextBlockHeight::ChainHeadPlusOne,
+        );
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            resharding_block,
+            ..
+        } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+        add_blocks_to_chain(
+            &mut chain,
+            2,
+            PreviousBlockHeight::ChainHead,
+            NextBlockHeight::ChainHeadPlusOne,
+        );
+
+        let account_key = |account: &str| TrieKey::Account { account_id: account!(account) };
+        let account_change = |account: &str| RawStateChangesWithTrieKey {
+            trie_key: account_key(account),
+            changes: vec![RawStateChange {
+                cause: StateChangeCause::InitialState,
+                data: Some(account.as_bytes().to_vec()),
+            }],
+        };
+        let manager = chain.runtime_adapter.get_flat_storage_manager();
+        let mut delta_blocks = vec![];
+        for (height, accounts) in [(1, vec!["oo", "pp", "vv"]), (2, vec!["qq"])] {
+            let block = chain.get_block_by_height(height).unwrap();
+            let state_changes = accounts.into_iter().map(account_change).collect_vec();
+            manager
+                .save_flat_state_changes(
+                    *block.hash(),
+                    *block.header().prev_hash(),
+                    height,
+                    parent_shard,
+                    &state_changes,
+                )
+                .unwrap()
+                .commit()
+                .unwrap();
+            delta_blocks.push(*block.hash());
+        }
+
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // Pretend that the flat state and the deltas of the first block up to account 'oo' were
+        // copied before a restart.
+        let progress = FlatStorageReshardingProgress {
+            parent_shard,
+            checkpoint: Some(SplitShardCheckpoint {
+                resharding_block,
+                ranges: vec![SplitShardRangeCheckpoint {
+                    start: vec![],
+                    end: None,
+                    last_key: None,
+                    done: true,
+                }],
+                deltas: Some(SplitShardDeltasCheckpoint {
+                    block: delta_blocks[0],
+                    last_key: account_key("oo").to_vec(),
+                }),
+            }),
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let mut store_update = flat_store.store_update();
+        for child_shard in [left_child_shard, right_child_shard] {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        store_update.commit().unwrap();
+
+        assert_matches!(
+            sender.call_split_shard_task(),
+            FlatStorageReshardingTaskResult::Successful { .. }
+        );
+
+        // Only the deltas after the checkpoint were copied.
+        let is_copied = |account: &str| {
+            [left_child_shard, right_child_shard].into_iter().any(|child_shard| {
+                flat_store.get(child_shard, &account_key(account).to_vec()).unwrap().is_some()
+            })
+        };
+        assert!(!is_copied("mm"));
+        assert!(!is_copied("oo"));
+        assert!(is_copied("pp"));
+        assert!(is_copied("qq"));
+        assert_eq!(
+            flat_store.get(right_child_shard, &account_key("vv").to_vec()),
+            Ok(Some(FlatStateValue::inlined("vv".as_bytes())))
+        );
+    }
+
+    /// The ranges of the parent's flat state copied in parallel hold together all the key-values
+    /// of the parent.
+    #[test]
+    fn split_shard_copies_ranges_in_parallel() {
+        init_test_logger();
+        let (chain, resharder, sender) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            resharding_block,
+            ..
+        } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let parent_keys: Vec<Vec<u8>> =
+            flat_store.iter(parent_shard).map_ok(|(key, _)| key).collect::<Result<_, _>>().unwrap();
+
+        let mut config = resharder.resharding_config.get();
+        config.batch_size = bytesize::ByteSize(1);
+        config.flat_storage_split_parallelism = 3;
+        resharder.resharding_config.update(config);
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+
+        // Split the parent into three ranges, whatever the boundaries derived from its trie.
+        let boundaries = [
+            parent_keys[parent_keys.len() / 3].clone(),
+            parent_keys[parent_keys.len() * 2 / 3].clone(),
+        ];
+        let progress = FlatStorageReshardingProgress {
+            parent_shard,
+            checkpoint: Some(SplitShardCheckpoint {
+                resharding_block,
+                ranges: [
+                    (vec![], Some(boundaries[0].clone())),
+                    (boundaries[0].clone(), Some(boundaries[1].clone())),
+                    (boundaries[1].clone(), None),
+                ]
+                .into_iter()
+                .map(|(start, end)| SplitShardRangeCheckpoint {
+                    start,
+                    end,
+                    last_key: None,
+                    done: false,
+                })
+                .collect(),
+                deltas: None,
+            }),
+        };
+        let mut store_update = flat_store.store_update();
+        for child_shard in [left_child_shard, right_child_shard] {
+            store_update.set_resharding_progress(child_shard, &progress);
+        }
+        store_update.commit().unwrap();
+
+        let FlatStorageReshardingTaskResult::Successful { num_batches_done } =
+            sender.call_split_shard_task()
+        else {
+            panic!("the split shard task did not succeed");
+        };
+        // Every key is a batch, plus the last empty batch of each range.
+        assert_ge!(num_batches_done, parent_keys.len() + 3);
+        for key in parent_keys {
+            let copied = [left_child_shard, right_child_shard]
+                .into_iter()
+                .any(|child_shard| flat_store.get(child_shard, &key).unwrap().is_some());
+            assert!(copied, "key {key:?} was not copied");
+        }
+    }
+
+    #[test]
+    fn split_shard_range_boundaries() {
+        init_test_logger();
+        let (chain, resharder, _) =
+            create_chain_resharder_sender::<DelayedSender>(simple_shard_layout());
+        let new_shard_layout = shard_layout_after_split();
+        let resharding_event_type = event_type_from_chain_and_layout(&chain, &new_shard_layout);
+        let ReshardingSplitShardParams { parent_shard, .. } = match resharding_event_type.clone() {
+            ReshardingEventType::SplitShard(params) => params,
+        };
+        let flat_store = resharder.runtime.store().flat_store();
+        let parent_keys: Vec<Vec<u8>> =
+            flat_store.iter(parent_shard).map_ok(|(key, _)| key).collect::<Result<_, _>>().unwrap();
+        assert!(resharder.start_resharding(resharding_event_type, &new_shard_layout).is_ok());
+        let Ok(FlatStorageStatus::Resharding(FlatStorageReshardingStatus::SplittingParent(
+            split_params,
+        ))) = flat_store.get_flat_storage_status(parent_shard)
+        else {
+            panic!("the parent is not being split");
+        };
+
+        let boundaries = resharder
+            .split_shard_range_boundaries(parent_shard, &split_params.flat_head, 1)
+            .unwrap();
+        assert_eq!(boundaries, Vec::<Vec<u8>>::new());
+
+        let num_ranges = 4;
+        let boundaries = resharder
+            .split_shard_range_boundaries(parent_shard, &split_params.flat_head, num_ranges)
+            .unwrap();
+        assert!(boundaries.len() < num_ranges, "{boundaries:?}");
+        assert!(boundaries.iter().all(|boundary| !boundary.is_empty()), "{boundaries:?}");
+        assert!(boundaries.is_sorted_by(|a, b| a < b), "{boundaries:?}");
+
+        // Every key of the parent is in exactly one of the ranges.
+        let ranges = resharder.split_shard_ranges(parent_shard, &split_params, num_ranges);
+        assert_eq!(ranges.len(), boundaries.len() + 1);
+        assert_eq!(ranges.first().unwrap().start, Vec::<u8>::new());
+        assert_eq!(ranges.last().unwrap().end, None);
+        for key in &parent_keys {
+            let num_containing_ranges = ranges
+                .iter()
+                .filter(|range| {
+                    &range.start <= key && range.end.as_ref().is_none_or(|end| key < end)
+                })
+                .count();
+            assert_eq!(num_containing_ranges, 1, "key {key:?}");
+        }
+    }
+
     #[test]
     fn cancel_split_shard() {
         init_test_logger();
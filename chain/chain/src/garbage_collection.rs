@@ -1,13 +1,15 @@
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::{fmt, io};
 
-use near_chain_configs::GCConfig;
+use near_chain_configs::{GCCategory, GCConfig};
 use near_chain_primitives::Error;
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::shard_id_to_uid;
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_primitives::block::Block;
+use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::get_block_shard_uid;
 use near_primitives::state_sync::{StateHeaderKey, StatePartKey};
@@ -19,9 +21,12 @@ use near_primitives::utils::{
 };
 use near_store::adapter::trie_store::get_shard_uid_mapping;
 use near_store::adapter::{StoreAdapter, StoreUpdateAdapter};
-use near_store::{DBCol, KeyForStateChanges, ShardTries, ShardUId, StoreUpdate};
+use near_store::flat::FlatStorageStatus;
+use near_store::{
+    DBCol, GC_CATEGORY_TAIL_KEY, KeyForStateChanges, ShardTries, ShardUId, Store, StoreUpdate,
+};
 
-use crate::types::RuntimeAdapter;
+use crate::types::{RuntimeAdapter, Tip};
 use crate::{Chain, ChainStore, ChainStoreAccess, ChainStoreUpdate, metrics};
 
 #[derive(Clone)]
@@ -169,6 +174,13 @@ impl ChainStore {
         // blocks.
         let result = self.clear_state_transition_data(epoch_manager.as_ref());
 
+        let result = result.and(self.clear_categories_data(
+            gc_config,
+            runtime_adapter.as_ref(),
+            &epoch_manager,
+            shard_tracker,
+            me,
+        ));
         result.and(self.clear_old_blocks_data(
             gc_config,
             runtime_adapter,
@@ -178,6 +190,169 @@ impl ChainStore {
         ))
     }
 
+    /// Garbage collection of the categories of data kept for fewer epochs
+    /// than the blocks, see `GCConfig::gc_retention`. Each category goes from
+    /// its own tail to its own stop height on the canonical chain, ahead of the
+    /// blocks. The data of the shards that the node doesn't track is collected
+    /// in another pass, with another tail, if it is kept for even fewer epochs.
+    fn clear_categories_data(
+        &mut self,
+        gc_config: &GCConfig,
+        runtime_adapter: &dyn RuntimeAdapter,
+        epoch_manager: &Arc<dyn EpochManagerAdapter>,
+        shard_tracker: &ShardTracker,
+        me: Option<&AccountId>,
+    ) -> Result<(), Error> {
+        if gc_config.gc_retention.is_empty() {
+            return Ok(());
+        }
+        let _span =
+            tracing::debug_span!(target: "garbage_collection", "clear_categories_data").entered();
+        let head = self.head()?;
+        if head.height == self.get_genesis_height() {
+            return Ok(());
+        }
+        let mut tracked_shard_uids = None;
+        for category in GCCategory::ALL {
+            let num_epochs_to_keep = gc_config.gc_category_num_epochs_to_keep(category);
+            if num_epochs_to_keep < gc_config.gc_num_epochs_to_keep() {
+                self.clear_category_data(
+                    gc_config,
+                    runtime_adapter,
+                    epoch_manager,
+                    category,
+                    num_epochs_to_keep,
+                    None,
+                )?;
+            }
+            if category == GCCategory::StateChanges {
+                continue;
+            }
+            let Some(num_epochs_to_keep) =
+                gc_config.gc_untracked_shards_num_epochs_to_keep(category)
+            else {
+                continue;
+            };
+            if tracked_shard_uids.is_none() {
+                tracked_shard_uids =
+                    Some(get_tracked_shard_uids(epoch_manager, shard_tracker, me, &head)?);
+            }
+            self.clear_category_data(
+                gc_config,
+                runtime_adapter,
+                epoch_manager,
+                category,
+                num_epochs_to_keep,
+                tracked_shard_uids.as_ref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Collects the data of the category of the canonical chain and of the
+    /// forks up to the stop height of `num_epochs_to_keep` epochs. If `tracked_shard_uids`,
+    /// the shards tracked at the head, is set, only the data of the other
+    /// shards is collected.
+    fn clear_category_data(
+        &mut self,
+        gc_config: &GCConfig,
+        runtime_adapter: &dyn RuntimeAdapter,
+        epoch_manager: &Arc<dyn EpochManagerAdapter>,
+        category: GCCategory,
+        num_epochs_to_keep: u64,
+        tracked_shard_uids: Option<&HashSet<ShardUId>>,
+    ) -> Result<(), Error> {
+        let tries = runtime_adapter.get_tries();
+        let head = self.head()?;
+        let gc_stop_height = runtime_adapter
+            .get_gc_stop_height_for_num_epochs(&head.last_block_hash, num_epochs_to_keep);
+        let tail_key = gc_category_tail_key(category, tracked_shard_uids.is_some());
+        let category_tail =
+            self.store().get_ser::<BlockHeight>(DBCol::BlockMisc, &tail_key)?.unwrap_or_default();
+        let shards_label = if tracked_shard_uids.is_some() { "untracked" } else { "all" };
+        let tail_metric =
+            metrics::GC_CATEGORY_TAIL_HEIGHT.with_label_values(&[category.as_str(), shards_label]);
+        // The shards of each epoch with a descendant tracked at the head.
+        let mut epoch_tracked_shard_uids = HashMap::new();
+        let mut gc_blocks_remaining = gc_config.gc_blocks_limit;
+        for height in category_tail.max(self.tail()?) + 1..gc_stop_height {
+            if gc_blocks_remaining == 0 {
+                break;
+            }
+            let canonical_hash = match self.chain_store().get_block_hash_by_height(height) {
+                Ok(block_hash) => Some(block_hash),
+                Err(Error::DBNotFoundErr(_)) => None,
+                Err(err) => return Err(err),
+            };
+            let block_hashes = self
+                .chain_store()
+                .get_all_block_hashes_by_height(height)?
+                .values()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut chain_store_update = self.store_update();
+            for block_hash in &block_hashes {
+                let header = chain_store_update.get_block_header(block_hash)?;
+                // As for the blocks, the state before a block of the canonical
+                // chain is collected, and the other data of the previous block.
+                // The forks below the stop height can't become canonical, so
+                // the state after a fork block is collected, and its own data.
+                let (gc_mode, data_block) = if Some(*block_hash) == canonical_hash {
+                    (
+                        GCMode::Canonical(tries.clone()),
+                        chain_store_update.get_block(header.prev_hash())?,
+                    )
+                } else {
+                    (GCMode::Fork(tries.clone()), chain_store_update.get_block(block_hash)?)
+                };
+                // The state of a block before a resharding also includes the
+                // children, of the shard layout of the next epoch.
+                let epoch_ids = if category == GCCategory::State {
+                    vec![*header.epoch_id(), *header.next_epoch_id()]
+                } else {
+                    vec![*data_block.header().epoch_id()]
+                };
+                let mut kept_shard_uids = None;
+                if let Some(tracked_shard_uids) = tracked_shard_uids {
+                    let kept_shard_uids = kept_shard_uids.get_or_insert_with(HashSet::new);
+                    for epoch_id in epoch_ids {
+                        let shard_uids = match epoch_tracked_shard_uids.entry(epoch_id) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => entry.insert(get_ancestor_shard_uids(
+                                epoch_manager,
+                                &head.last_block_hash,
+                                tracked_shard_uids,
+                                &epoch_id,
+                            )?),
+                        };
+                        kept_shard_uids.extend(shard_uids.iter().copied());
+                    }
+                }
+                let is_collected = |shard_uid: &ShardUId| {
+                    kept_shard_uids
+                        .as_ref()
+                        .is_none_or(|shard_uids| !shard_uids.contains(shard_uid))
+                };
+                chain_store_update.clear_category_block_data(
+                    epoch_manager.as_ref(),
+                    category,
+                    block_hash,
+                    &gc_mode,
+                    &data_block,
+                    is_collected,
+                )?;
+            }
+            gc_blocks_remaining = gc_blocks_remaining.saturating_sub(block_hashes.len() as u64);
+            let mut store_update = chain_store_update.store().store_update();
+            store_update.set_ser(DBCol::BlockMisc, &tail_key, &height)?;
+            chain_store_update.merge(store_update);
+            chain_store_update.commit()?;
+            tail_metric.set(height as i64);
+        }
+        Ok(())
+    }
+
     fn clear_old_blocks_data(
         &mut self,
         gc_config: &GCConfig,
@@ -270,6 +445,11 @@ impl ChainStore {
                     shard_tracker,
                     block_hash,
                 )?;
+                let resharding_parent_shards = if gc_config.gc_resharding_parent_shards {
+                    get_resharding_parent_shards(&epoch_manager, block_hash)?
+                } else {
+                    vec![]
+                };
 
                 chain_store_update.clear_block_data(
                     epoch_manager.as_ref(),
@@ -288,6 +468,14 @@ impl ChainStore {
                         me,
                     )?;
                 }
+                if !resharding_parent_shards.is_empty() {
+                    let store_update = gc_resharding_parent_shards(
+                        &chain_store_update.store(),
+                        &tries,
+                        &resharding_parent_shards,
+                    )?;
+                    chain_store_update.merge(store_update);
+                }
             }
             chain_store_update.update_tail(height)?;
             chain_store_update.commit()?;
@@ -715,15 +903,7 @@ impl<'a> ChainStoreUpdate<'a> {
         self.gc_col(DBCol::NextBlockHashes, block_hash.as_bytes());
         self.gc_col(DBCol::ChallengedBlocks, block_hash.as_bytes());
         self.gc_col(DBCol::BlocksToCatchup, block_hash.as_bytes());
-        let storage_key = KeyForStateChanges::for_block(&block_hash);
-        let stored_state_changes: Vec<Box<[u8]>> = self
-            .store()
-            .iter_prefix(DBCol::StateChanges, storage_key.as_ref())
-            .map(|item| item.map(|(key, _)| key))
-            .collect::<io::Result<Vec<_>>>()?;
-        for key in stored_state_changes {
-            self.gc_col(DBCol::StateChanges, &key);
-        }
+        self.gc_state_changes(&block_hash)?;
         self.gc_col(DBCol::BlockRefCount, block_hash.as_bytes());
         self.gc_outcomes(&block)?;
         match gc_mode {
@@ -760,6 +940,59 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Collects the data of the category of the shards for which
+    /// `is_collected`: the state changes of the block at `block_hash`, as for
+    /// `gc_trie_changes` with `gc_mode`, and the other data of `data_block`.
+    fn clear_category_block_data(
+        &mut self,
+        epoch_manager: &dyn EpochManagerAdapter,
+        category: GCCategory,
+        block_hash: &CryptoHash,
+        gc_mode: &GCMode,
+        data_block: &Block,
+        is_collected: impl Fn(&ShardUId) -> bool,
+    ) -> Result<(), Error> {
+        let data_hash = data_block.hash();
+        let shard_layout = epoch_manager.get_shard_layout(data_block.header().epoch_id())?;
+        match category {
+            GCCategory::State => {
+                let mut store_update = self.store().trie_store().store_update();
+                let shard_uids = self
+                    .get_shard_uids_to_gc(epoch_manager, block_hash)
+                    .into_iter()
+                    .filter(|shard_uid| is_collected(shard_uid))
+                    .collect();
+                self.gc_shard_trie_changes(*block_hash, shard_uids, gc_mode, &mut store_update)?;
+                self.merge(store_update.into());
+            }
+            GCCategory::StateChanges => {
+                self.gc_state_changes(data_hash)?;
+            }
+            GCCategory::Receipts => {
+                for shard_uid in
+                    shard_layout.shard_uids().filter(|shard_uid| is_collected(shard_uid))
+                {
+                    let shard_id = shard_uid.shard_id();
+                    self.gc_outgoing_receipts(data_hash, shard_id);
+                    self.gc_col(DBCol::IncomingReceipts, &get_block_shard_id(data_hash, shard_id));
+                }
+            }
+            GCCategory::Outcomes => {
+                for chunk_header in data_block
+                    .chunks()
+                    .iter_deprecated()
+                    .filter(|h| h.height_included() == data_block.header().height())
+                {
+                    let shard_id = chunk_header.shard_id();
+                    if is_collected(&ShardUId::new(shard_layout.version(), shard_id)) {
+                        self.gc_shard_outcomes(data_hash, shard_id)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn gc_trie_changes(
         &mut self,
         epoch_manager: &dyn EpochManagerAdapter,
@@ -768,6 +1001,16 @@ impl<'a> ChainStoreUpdate<'a> {
         store_update: &mut near_store::adapter::trie_store::TrieStoreUpdateAdapter<'_>,
     ) -> Result<(), Error> {
         let shard_uids_to_gc = self.get_shard_uids_to_gc(epoch_manager, &block_hash);
+        self.gc_shard_trie_changes(block_hash, shard_uids_to_gc, gc_mode, store_update)
+    }
+
+    fn gc_shard_trie_changes(
+        &mut self,
+        block_hash: CryptoHash,
+        shard_uids_to_gc: Vec<ShardUId>,
+        gc_mode: &GCMode,
+        store_update: &mut near_store::adapter::trie_store::TrieStoreUpdateAdapter<'_>,
+    ) -> Result<(), Error> {
         for shard_uid in shard_uids_to_gc {
             let trie_changes_key = get_block_shard_uid(&block_hash, &shard_uid);
             let trie_changes = self.store().get_ser(DBCol::TrieChanges, &trie_changes_key)?;
@@ -775,6 +1018,7 @@ impl<'a> ChainStoreUpdate<'a> {
             let Some(trie_changes) = trie_changes else {
                 continue;
             };
+            store_update.set_refcount_decrement_block_hash(Some(block_hash));
             match gc_mode.clone() {
                 GCMode::Fork(tries) => {
                     // If the block is on a fork, we delete the state that's the result of applying this block
@@ -788,6 +1032,7 @@ impl<'a> ChainStoreUpdate<'a> {
                     // Not apply the data from DBCol::TrieChanges
                 }
             }
+            store_update.set_refcount_decrement_block_hash(None);
 
             self.gc_col(DBCol::TrieChanges, &trie_changes_key);
         }
@@ -961,9 +1206,21 @@ impl<'a> ChainStoreUpdate<'a> {
         self.merge(store_update);
     }
 
+    fn gc_state_changes(&mut self, block_hash: &CryptoHash) -> Result<(), Error> {
+        let storage_key = KeyForStateChanges::for_block(block_hash);
+        let stored_state_changes: Vec<Box<[u8]>> = self
+            .store()
+            .iter_prefix(DBCol::StateChanges, storage_key.as_ref())
+            .map(|item| item.map(|(key, _)| key))
+            .collect::<io::Result<Vec<_>>>()?;
+        for key in stored_state_changes {
+            self.gc_col(DBCol::StateChanges, &key);
+        }
+        Ok(())
+    }
+
     fn gc_outcomes(&mut self, block: &Block) -> Result<(), Error> {
         let block_hash = block.hash();
-        let store_update = self.store().store_update();
         for chunk_header in block
             .chunks()
             .iter_deprecated()
@@ -971,18 +1228,25 @@ impl<'a> ChainStoreUpdate<'a> {
         {
             // It is ok to use the shard id from the header because it is a new
             // chunk. An old chunk may have the shard id from the parent shard.
-            let shard_id = chunk_header.shard_id();
-            let outcome_ids =
-                self.chain_store().get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
-            for outcome_id in outcome_ids {
-                self.gc_col(
-                    DBCol::TransactionResultForBlock,
-                    &get_outcome_id_block_hash(&outcome_id, block_hash),
-                );
-            }
-            self.gc_col(DBCol::OutcomeIds, &get_block_shard_id(block_hash, shard_id));
+            self.gc_shard_outcomes(block_hash, chunk_header.shard_id())?;
         }
-        self.merge(store_update);
+        Ok(())
+    }
+
+    fn gc_shard_outcomes(
+        &mut self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+    ) -> Result<(), Error> {
+        let outcome_ids =
+            self.chain_store().get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
+        for outcome_id in outcome_ids {
+            self.gc_col(
+                DBCol::TransactionResultForBlock,
+                &get_outcome_id_block_hash(&outcome_id, block_hash),
+            );
+        }
+        self.gc_col(DBCol::OutcomeIds, &get_block_shard_id(block_hash, shard_id));
         Ok(())
     }
 
@@ -1121,10 +1385,15 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::FlatStateChanges
             | DBCol::FlatStateDeltaMetadata
             | DBCol::FlatStorageStatus
+            | DBCol::FlatStorageReshardingProgress
             | DBCol::EpochSyncProof
             | DBCol::Misc
             | DBCol::_ReceiptIdToShardId
             | DBCol::StateShardUIdMapping
+            // BlockProductionTimings is limited in size and cleaned up when new entries are added.
+            | DBCol::BlockProductionTimings
+            // AccountUsageStats is keyed by account and kept for as long as the account exists.
+            | DBCol::AccountUsageStats
             // Note that StateSyncHashes should not ever have too many keys in them
             // because we remove unneeded keys as we add new ones.
             | DBCol::StateSyncHashes
@@ -1134,6 +1403,72 @@ impl<'a> ChainStoreUpdate<'a> {
     }
 }
 
+/// The key of the tail of the category in `DBCol::BlockMisc`, e.g.
+/// 'GC_CATEGORY_TAIL:outcomes' or 'GC_CATEGORY_TAIL:outcomes:untracked'.
+fn gc_category_tail_key(category: GCCategory, untracked_shards: bool) -> Vec<u8> {
+    let mut key = GC_CATEGORY_TAIL_KEY.to_vec();
+    key.extend(b":");
+    key.extend(category.as_str().as_bytes());
+    if untracked_shards {
+        key.extend(b":untracked");
+    }
+    key
+}
+
+/// Returns the shards of the shard layout of the head that the node tracks in
+/// the current or the next epoch.
+fn get_tracked_shard_uids(
+    epoch_manager: &Arc<dyn EpochManagerAdapter>,
+    shard_tracker: &ShardTracker,
+    me: Option<&AccountId>,
+    head: &Tip,
+) -> Result<HashSet<ShardUId>, Error> {
+    let shard_layout = epoch_manager.get_shard_layout(&head.epoch_id)?;
+    let mut tracked_shard_uids = HashSet::new();
+    for shard_uid in shard_layout.shard_uids() {
+        if shard_tracker.cares_about_shard_this_or_next_epoch(
+            me,
+            &head.prev_block_hash,
+            shard_uid.shard_id(),
+            true,
+        ) {
+            tracked_shard_uids.insert(shard_uid);
+        }
+    }
+    Ok(tracked_shard_uids)
+}
+
+/// Maps the shards of the shard layout of the epoch of `block_hash` to their
+/// ancestors in the shard layout of `epoch_id`, an epoch before, going
+/// through the reshardings in between.
+fn get_ancestor_shard_uids(
+    epoch_manager: &Arc<dyn EpochManagerAdapter>,
+    block_hash: &CryptoHash,
+    shard_uids: &HashSet<ShardUId>,
+    epoch_id: &EpochId,
+) -> Result<HashSet<ShardUId>, Error> {
+    let mut block_info = epoch_manager.get_block_info(block_hash)?;
+    let mut shard_layout = epoch_manager.get_shard_layout(block_info.epoch_id())?;
+    let mut shard_uids = shard_uids.clone();
+    while block_info.epoch_id() != epoch_id {
+        let epoch_first_block_info =
+            epoch_manager.get_block_info(block_info.epoch_first_block())?;
+        block_info = epoch_manager.get_block_info(epoch_first_block_info.prev_hash())?;
+        let prev_shard_layout = epoch_manager.get_shard_layout(block_info.epoch_id())?;
+        if prev_shard_layout != shard_layout {
+            shard_uids = shard_uids
+                .iter()
+                .map(|shard_uid| {
+                    let parent_shard_id = shard_layout.get_parent_shard_id(shard_uid.shard_id())?;
+                    Ok(ShardUId::from_shard_id_and_layout(parent_shard_id, &prev_shard_layout))
+                })
+                .collect::<Result<_, Error>>()?;
+        }
+        shard_layout = prev_shard_layout;
+    }
+    Ok(shard_uids)
+}
+
 /// Returns shards that we tracked in an epoch, given a hash of the last block in the epoch.
 /// The block has to be available, so this function has to be called before gc is run for the block.
 ///
@@ -1264,3 +1599,74 @@ fn gc_state(
     chain_store_update.merge(store_update);
     Ok(())
 }
+
+/// Returns the parent shards of the resharding at the end of the epoch of
+/// `block_hash`, if it is the last block of a finished epoch.
+fn get_resharding_parent_shards(
+    epoch_manager: &Arc<dyn EpochManagerAdapter>,
+    block_hash: &CryptoHash,
+) -> Result<Vec<ShardUId>, Error> {
+    if !epoch_manager.is_last_block_in_finished_epoch(block_hash)? {
+        return Ok(vec![]);
+    }
+    let shard_layout = epoch_manager.get_shard_layout(&epoch_manager.get_epoch_id(block_hash)?)?;
+    let next_shard_layout =
+        epoch_manager.get_shard_layout(&epoch_manager.get_next_epoch_id(block_hash)?)?;
+    // Since ShardLayoutV2, the version of the shard layout doesn't change in
+    // resharding, so the children ShardUIds share the version of the parent.
+    if shard_layout == next_shard_layout || shard_layout.version() != next_shard_layout.version() {
+        return Ok(vec![]);
+    }
+    let next_shard_uids: HashSet<ShardUId> = next_shard_layout.shard_uids().collect();
+    Ok(shard_layout.shard_uids().filter(|shard_uid| !next_shard_uids.contains(shard_uid)).collect())
+}
+
+/// Cleanup of the parent shards of a resharding, once the last block with the
+/// parent shards is garbage collected, so that the children are past the GC
+/// window.
+///
+/// Resharding usually removes the flat storage of the parents and unloads
+/// their memtries, but these may be left behind, e.g. if the node restarted
+/// during resharding. The State of the parents isn't deleted here: the
+/// children keep reading it through DBCol::StateShardUIdMapping, and it is
+/// deleted by `gc_state` once no tracked shard is mapped to it anymore.
+pub(crate) fn gc_resharding_parent_shards(
+    store: &Store,
+    tries: &ShardTries,
+    parent_shard_uids: &[ShardUId],
+) -> Result<StoreUpdate, Error> {
+    let _span =
+        tracing::debug_span!(target: "garbage_collection", "gc_resharding_parent_shards").entered();
+    let flat_storage_manager = tries.get_flat_storage_manager();
+    let mut flat_store_update = store.flat_store().store_update();
+    for &parent_shard_uid in parent_shard_uids {
+        if tries.get_memtries(parent_shard_uid).is_some() {
+            tries.unload_memtrie(&parent_shard_uid);
+            metrics::RESHARDING_PARENT_SHARDS_GC.with_label_values(&["memtrie"]).inc();
+        }
+        let flat_storage_status = store
+            .flat_store()
+            .get_flat_storage_status(parent_shard_uid)
+            .map_err(StorageError::from)?;
+        if !flat_storage_manager
+            .remove_flat_storage_for_shard(parent_shard_uid, &mut flat_store_update)?
+        {
+            flat_store_update.remove_flat_storage(parent_shard_uid);
+        }
+        if flat_storage_status != FlatStorageStatus::Empty {
+            metrics::RESHARDING_PARENT_SHARDS_GC.with_label_values(&["flat_storage"]).inc();
+        }
+        tracing::info!(
+            target: "garbage_collection",
+            ?parent_shard_uid,
+            "gc_resharding_parent_shards"
+        );
+    }
+
+    let mut store_update: StoreUpdate = flat_store_update.into();
+    // The parent ShardUId itself may be mapped to its own parent.
+    for &parent_shard_uid in parent_shard_uids {
+        store_update.delete(DBCol::StateShardUIdMapping, &parent_shard_uid.to_bytes());
+    }
+    Ok(store_update)
+}
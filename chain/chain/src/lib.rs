@@ -18,6 +18,7 @@ pub use store::{
 pub use store_validator::{ErrorMessage, StoreValidator};
 pub use types::{Block, BlockHeader, BlockStatus, ChainGenesis, LatestKnown, Provenance};
 
+mod account_usage_stats;
 mod approval_verification;
 mod block_processing_utils;
 pub mod blocks_delay_tracker;
@@ -34,8 +35,10 @@ pub mod metrics;
 pub mod missing_chunks;
 pub mod orphan;
 pub mod rayon_spawner;
+pub mod replica;
 pub mod resharding;
 pub mod runtime;
+pub mod shard_hotspots;
 pub mod sharding;
 pub mod signature_verification;
 pub mod state_snapshot_actor;
@@ -1,10 +1,12 @@
 use itertools::Itertools;
 use near_o11y::metrics::{
-    Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, exponential_buckets,
-    try_create_histogram, try_create_histogram_vec, try_create_histogram_with_buckets,
-    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge,
-    try_create_int_gauge_vec,
+    GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    exponential_buckets, try_create_gauge_vec, try_create_histogram, try_create_histogram_vec,
+    try_create_histogram_with_buckets, try_create_int_counter, try_create_int_counter_vec,
+    try_create_int_gauge, try_create_int_gauge_vec,
 };
+use near_parameters::config::CongestionControlConfig;
+use near_primitives::congestion_info::{BlockCongestionInfo, CongestionInfo};
 use std::sync::LazyLock;
 
 /// Exponential buckets for both negative and positive values.
@@ -90,6 +92,14 @@ pub static FORK_TAIL_HEIGHT: LazyLock<IntGauge> =
     LazyLock::new(|| try_create_int_gauge("near_fork_tail_height", "Height of fork tail").unwrap());
 pub static GC_STOP_HEIGHT: LazyLock<IntGauge> =
     LazyLock::new(|| try_create_int_gauge("near_gc_stop_height", "Target height of gc").unwrap());
+pub(crate) static GC_CATEGORY_TAIL_HEIGHT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_gc_category_tail_height",
+        "Height of the tail of a category of data garbage collected ahead of the blocks",
+        &["category", "shards"],
+    )
+    .unwrap()
+});
 pub static CHUNK_RECEIVED_DELAY: LazyLock<HistogramVec> = LazyLock::new(|| {
     try_create_histogram_vec(
         "near_chunk_receive_delay_seconds",
@@ -227,6 +237,78 @@ pub(crate) static SHARD_LAYOUT_NUM_SHARDS: LazyLock<IntGauge> = LazyLock::new(||
     .unwrap()
 });
 
+static BLOCK_CONGESTION_LEVEL: LazyLock<GaugeVec> = LazyLock::new(|| {
+    try_create_gauge_vec(
+        "near_block_congestion_level",
+        "Congestion level of each shard in the head block, between 0.0 and 1.0.",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+static BLOCK_CONGESTION_DELAYED_RECEIPTS_GAS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_block_congestion_delayed_receipts_gas",
+        "Gas of all the delayed receipts of each shard in the head block.",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+static BLOCK_CONGESTION_BUFFERED_RECEIPTS_GAS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_block_congestion_buffered_receipts_gas",
+        "Gas of all the buffered receipts of each shard in the head block.",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+static BLOCK_CONGESTION_ALLOWED_SHARD: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_block_congestion_allowed_shard",
+        "Shard allowed to forward receipts to each shard in the head block, even when it's fully congested.",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+/// Reports the congestion of all the shards as seen in the chunk headers of the
+/// head block. Unlike the congestion metrics of the runtime, these are
+/// available for the shards that the node doesn't track.
+pub(crate) fn report_block_congestion_metrics(
+    block_congestion_info: &BlockCongestionInfo,
+    config: &CongestionControlConfig,
+) {
+    for (shard_id, extended_congestion_info) in block_congestion_info.iter() {
+        let shard_label = shard_id.to_string();
+        let congestion_info = &extended_congestion_info.congestion_info;
+        BLOCK_CONGESTION_LEVEL
+            .with_label_values(&[&shard_label])
+            .set(congestion_info.localized_congestion_level(config));
+
+        let CongestionInfo::V1(inner) = congestion_info;
+        BLOCK_CONGESTION_DELAYED_RECEIPTS_GAS
+            .with_label_values(&[&shard_label])
+            .set(inner.delayed_receipts_gas.try_into().unwrap_or(i64::MAX));
+        BLOCK_CONGESTION_BUFFERED_RECEIPTS_GAS
+            .with_label_values(&[&shard_label])
+            .set(inner.buffered_receipts_gas.try_into().unwrap_or(i64::MAX));
+        BLOCK_CONGESTION_ALLOWED_SHARD
+            .with_label_values(&[&shard_label])
+            .set(inner.allowed_shard.into());
+    }
+}
+
+/// Removes the congestion metrics of all the shards, e.g. when the shard layout
+/// changes.
+pub(crate) fn reset_block_congestion_metrics() {
+    BLOCK_CONGESTION_LEVEL.reset();
+    BLOCK_CONGESTION_DELAYED_RECEIPTS_GAS.reset();
+    BLOCK_CONGESTION_BUFFERED_RECEIPTS_GAS.reset();
+    BLOCK_CONGESTION_ALLOWED_SHARD.reset();
+}
+
 pub(crate) static APPLY_ALL_CHUNKS_TIME: LazyLock<HistogramVec> = LazyLock::new(|| {
     try_create_histogram_vec(
         "near_apply_all_chunks_time",
@@ -255,6 +337,24 @@ pub(crate) static APPLY_CHUNK_RESULTS_CACHE_MISSES: LazyLock<IntCounterVec> = La
     .unwrap()
 });
 
+pub(crate) static REPLICA_STATE_UPDATES_APPLIED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_replica_state_updates_applied",
+        "Total number of chunks for which a replica applied the state update of a trusted node",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub(crate) static REPLICA_STATE_UPDATES_MISMATCHED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+            "near_replica_state_updates_mismatched",
+            "Total number of chunks applied as usual because the state root after the replica state update didn't match",
+            &["shard_id"],
+        )
+        .unwrap()
+});
+
 pub(crate) static STATE_TRANSITION_DATA_GC_TOTAL_ENTRIES: LazyLock<IntGauge> =
     LazyLock::new(|| {
         try_create_int_gauge(
@@ -286,3 +386,23 @@ pub(crate) static STATE_TRANSITION_DATA_GC_TIME: LazyLock<Histogram> = LazyLock:
     )
     .unwrap()
 });
+
+pub(crate) static RESHARDING_PARENT_SHARDS_GC: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_resharding_parent_shards_gc",
+        "Number of resharding parent shards whose leftover data was garbage collected, by kind of data",
+        &["data"],
+    )
+    .unwrap()
+});
+
+pub static RESHARDING_PHASE_TIME: LazyLock<HistogramVec> = LazyLock::new(|| {
+    try_create_histogram_vec(
+        "near_resharding_phase_time",
+        "Time spent in the phases of resharding of a shard. The memtrie and flat storage splits \
+         are reported for the parent shard and the catchup for each child shard.",
+        &["shard_uid", "phase"],
+        Some(exponential_buckets(0.001, 2.0, 20).unwrap()),
+    )
+    .unwrap()
+});
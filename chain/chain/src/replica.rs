@@ -0,0 +1,222 @@
+//! The state updates of the applied chunks, kept in memory by the nodes which
+//! serve replicas, and applied by the replicas instead of executing the chunks
+//! of the blocks of the block producers they trust.
+
+use std::cell::Cell;
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+use near_chain_primitives::Error;
+use near_primitives::chunk_apply_stats::ChunkApplyStatsV0;
+use near_primitives::hash::CryptoHash;
+use near_primitives::replica::ReplicaShardStateUpdate;
+use near_primitives::types::{ShardId, StateRoot};
+use near_store::trie::AccessOptions;
+use near_store::{ShardUId, WrappedTrieChanges};
+
+use crate::metrics;
+use crate::types::{ApplyChunkBlockContext, ApplyChunkResult, RuntimeAdapter};
+use crate::update_shard::{NewChunkResult, OldChunkResult, ShardUpdateResult};
+
+pub struct ReplicaStateUpdates {
+    cache: LruCache<(CryptoHash, ShardId), ReplicaShardStateUpdate>,
+    /// Number of the chunks applied from the cached updates, for tests.
+    hits: Cell<usize>,
+    /// Makes the recorded updates claim a wrong state root, so that the
+    /// replicas applying them have to apply the chunks instead.
+    #[cfg(feature = "test_features")]
+    pub adv_corrupt_state_roots: bool,
+}
+
+impl ReplicaStateUpdates {
+    pub fn new(size: usize) -> Self {
+        Self {
+            cache: LruCache::new(NonZeroUsize::new(size).unwrap()),
+            hits: Cell::new(0),
+            #[cfg(feature = "test_features")]
+            adv_corrupt_state_roots: false,
+        }
+    }
+
+    /// Keeps the state updates of the chunks successfully applied at the block.
+    pub fn record_apply_results(
+        &mut self,
+        block_hash: &CryptoHash,
+        apply_results: &[(ShardId, Result<ShardUpdateResult, Error>)],
+    ) {
+        for (shard_id, result) in apply_results {
+            let Ok(result) = result else {
+                continue;
+            };
+            let update = shard_state_update(*shard_id, result);
+            #[cfg(feature = "test_features")]
+            let update = if self.adv_corrupt_state_roots {
+                let new_root = CryptoHash::hash_bytes(update.new_root.as_bytes());
+                ReplicaShardStateUpdate { new_root, ..update }
+            } else {
+                update
+            };
+            self.cache.put((*block_hash, *shard_id), update);
+        }
+    }
+
+    pub fn insert(&mut self, block_hash: CryptoHash, update: ReplicaShardStateUpdate) {
+        self.cache.put((block_hash, update.shard_id), update);
+    }
+
+    /// Returns the updates of the given shards at the block which are cached.
+    pub fn get(
+        &self,
+        block_hash: &CryptoHash,
+        shard_ids: &[ShardId],
+    ) -> Vec<ReplicaShardStateUpdate> {
+        shard_ids
+            .iter()
+            .filter_map(|shard_id| self.cache.peek(&(*block_hash, *shard_id)))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the update of the shard at the block to apply instead of the
+    /// chunk, if it agrees on whether the block has a new chunk for the shard.
+    pub fn peek_to_apply(
+        &self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        is_new_chunk: bool,
+    ) -> Option<&ReplicaShardStateUpdate> {
+        let update = self.cache.peek(&(*block_hash, shard_id))?;
+        if update.is_new_chunk != is_new_chunk {
+            tracing::warn!(
+                target: "chain", ?block_hash, %shard_id, is_new_chunk,
+                "Ignoring the replica state update of another chunk"
+            );
+            return None;
+        }
+        self.hits.set(self.hits.get() + 1);
+        metrics::REPLICA_STATE_UPDATES_APPLIED
+            .with_label_values(&[shard_id.to_string().as_str()])
+            .inc();
+        Some(update)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.get()
+    }
+}
+
+fn shard_state_update(shard_id: ShardId, result: &ShardUpdateResult) -> ReplicaShardStateUpdate {
+    let (is_new_chunk, gas_limit, apply_result) = match result {
+        ShardUpdateResult::NewChunk(NewChunkResult { gas_limit, apply_result, .. }) => {
+            (true, *gas_limit, apply_result)
+        }
+        ShardUpdateResult::OldChunk(OldChunkResult { apply_result, .. }) => {
+            (false, 0, apply_result)
+        }
+    };
+    ReplicaShardStateUpdate {
+        shard_id,
+        is_new_chunk,
+        gas_limit,
+        new_root: apply_result.new_root,
+        state_changes: apply_result.trie_changes.state_changes().to_vec(),
+        outcomes: apply_result.outcomes.clone(),
+        outgoing_receipts: apply_result.outgoing_receipts.clone(),
+        validator_proposals: apply_result.validator_proposals.clone(),
+        total_gas_burnt: apply_result.total_gas_burnt,
+        total_balance_burnt: apply_result.total_balance_burnt,
+        applied_receipts_hash: apply_result.applied_receipts_hash,
+        congestion_info: apply_result.congestion_info,
+        bandwidth_requests: apply_result.bandwidth_requests.clone(),
+        bandwidth_scheduler_state_hash: apply_result.bandwidth_scheduler_state_hash,
+    }
+}
+
+/// Writes the state changes of the update to the trie of the shard at the
+/// previous block. The result is the same as the one of applying the chunk,
+/// except for the data only needed to produce state witnesses.
+///
+/// Returns `None` if the state changes don't lead to the state root of the
+/// update, the chunk must be applied as usual then. The update may come from
+/// another fork or be stale, which doesn't make the block invalid.
+pub fn apply_replica_state_update(
+    runtime: &dyn RuntimeAdapter,
+    shard_uid: ShardUId,
+    block: &ApplyChunkBlockContext,
+    prev_state_root: StateRoot,
+    update: ReplicaShardStateUpdate,
+) -> Result<Option<ShardUpdateResult>, Error> {
+    let _span = tracing::debug_span!(
+        target: "chain",
+        "apply_replica_state_update",
+        height = block.height,
+        shard_id = %update.shard_id,
+        num_state_changes = update.state_changes.len())
+    .entered();
+    let changes = update
+        .state_changes
+        .iter()
+        .map(|changes| {
+            let change = changes.changes.last().ok_or_else(|| {
+                Error::Other(format!("no state change of {:?}", changes.trie_key))
+            })?;
+            Ok((changes.trie_key.to_vec(), change.data.clone()))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let trie = runtime.get_trie_for_shard(
+        update.shard_id,
+        &block.prev_block_hash,
+        prev_state_root,
+        false,
+    )?;
+    let trie_changes = trie.update(changes, AccessOptions::DEFAULT)?;
+    if trie_changes.new_root != update.new_root {
+        tracing::warn!(
+            target: "chain",
+            shard_id = %update.shard_id,
+            expected = ?update.new_root,
+            actual = ?trie_changes.new_root,
+            "State root after the replica state update doesn't match, applying the chunk instead"
+        );
+        metrics::REPLICA_STATE_UPDATES_MISMATCHED
+            .with_label_values(&[update.shard_id.to_string().as_str()])
+            .inc();
+        return Ok(None);
+    }
+
+    let mut stats = ChunkApplyStatsV0::new(block.height, update.shard_id);
+    stats.is_new_chunk = update.is_new_chunk;
+    let apply_result = ApplyChunkResult {
+        trie_changes: WrappedTrieChanges::new(
+            runtime.get_tries(),
+            shard_uid,
+            trie_changes,
+            update.state_changes,
+            block.height,
+        ),
+        new_root: update.new_root,
+        outcomes: update.outcomes,
+        outgoing_receipts: update.outgoing_receipts,
+        validator_proposals: update.validator_proposals,
+        total_gas_burnt: update.total_gas_burnt,
+        total_balance_burnt: update.total_balance_burnt,
+        proof: None,
+        processed_delayed_receipts: vec![],
+        processed_yield_timeouts: vec![],
+        applied_receipts_hash: update.applied_receipts_hash,
+        congestion_info: update.congestion_info,
+        bandwidth_requests: update.bandwidth_requests,
+        bandwidth_scheduler_state_hash: update.bandwidth_scheduler_state_hash,
+        contract_updates: Default::default(),
+        stats,
+    };
+    Ok(Some(if update.is_new_chunk {
+        ShardUpdateResult::NewChunk(NewChunkResult {
+            shard_uid,
+            gas_limit: update.gas_limit,
+            apply_result,
+        })
+    } else {
+        ShardUpdateResult::OldChunk(OldChunkResult { shard_uid, apply_result })
+    }))
+}
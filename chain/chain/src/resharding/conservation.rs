@@ -0,0 +1,362 @@
+//! Checks that a shard split preserves the tokens and the pending receipts of
+//! the parent shard. The children together have to hold the balances of all
+//! the parent accounts, and every receipt of the parent queues as many times
+//! as the queue is split by account, copied to both children or only to the
+//! left one.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use borsh::BorshDeserialize;
+use near_chain_primitives::Error;
+use near_primitives::account::Account;
+use near_primitives::errors::StorageError;
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::{Receipt, ReceiptOrStateStoredReceipt};
+use near_primitives::trie_key::col;
+use near_primitives::types::{Balance, StateRoot};
+use near_store::{ShardTries, ShardUId, Trie};
+
+/// A queue of receipts pending in the state of a shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReceiptQueue {
+    Delayed,
+    Buffered,
+    Postponed,
+    PromiseYield,
+}
+
+impl ReceiptQueue {
+    fn split(&self) -> QueueSplit {
+        match self {
+            ReceiptQueue::Delayed => QueueSplit::CopiedToBothChildren,
+            ReceiptQueue::Buffered => QueueSplit::CopiedToLeftChild,
+            ReceiptQueue::Postponed | ReceiptQueue::PromiseYield => QueueSplit::ByAccount,
+        }
+    }
+}
+
+/// How the receipts of a queue of the parent are split among the children.
+enum QueueSplit {
+    ByAccount,
+    CopiedToBothChildren,
+    CopiedToLeftChild,
+}
+
+/// The number of occurrences of each receipt id in a queue.
+pub type ReceiptMultiset = BTreeMap<CryptoHash, u64>;
+
+/// The tokens and the pending receipts held in the state of a shard.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ShardConservationSummary {
+    pub num_accounts: u64,
+    /// The amount and the locked amount of all the accounts.
+    pub total_balance: Balance,
+    /// The ids of the receipts of each queue, with their number of occurrences.
+    pub receipts: BTreeMap<ReceiptQueue, ReceiptMultiset>,
+}
+
+impl ShardConservationSummary {
+    /// Reads the whole trie, from memory if it is loaded, so that the state of
+    /// the children can be read before their nodes are written to the disk.
+    pub fn from_trie(trie: &Trie) -> Result<Self, Error> {
+        let mut summary = Self::default();
+        let trie = trie.lock_for_iter();
+        for item in trie.iter()? {
+            let (key, value) = item?;
+            let queue = match key[0] {
+                col::ACCOUNT => {
+                    let account: Account = deserialize(&key, &value)?;
+                    summary.num_accounts += 1;
+                    summary.total_balance += account.amount() + account.locked();
+                    continue;
+                }
+                // The key of the indices of the queue is only the column.
+                col::DELAYED_RECEIPT_OR_INDICES if key.len() > 1 => ReceiptQueue::Delayed,
+                col::BUFFERED_RECEIPT => ReceiptQueue::Buffered,
+                col::POSTPONED_RECEIPT => ReceiptQueue::Postponed,
+                col::PROMISE_YIELD_RECEIPT => ReceiptQueue::PromiseYield,
+                _ => continue,
+            };
+            let receipt_id = match queue {
+                ReceiptQueue::Delayed | ReceiptQueue::Buffered => {
+                    let receipt: ReceiptOrStateStoredReceipt = deserialize(&key, &value)?;
+                    *receipt.get_receipt().receipt_id()
+                }
+                ReceiptQueue::Postponed | ReceiptQueue::PromiseYield => {
+                    let receipt: Receipt = deserialize(&key, &value)?;
+                    *receipt.receipt_id()
+                }
+            };
+            *summary.receipts.entry(queue).or_default().entry(receipt_id).or_default() += 1;
+        }
+        Ok(summary)
+    }
+
+    /// The hash of the sorted ids of the receipts of the queue, with their
+    /// number of occurrences.
+    pub fn receipts_hash(&self, queue: ReceiptQueue) -> CryptoHash {
+        let receipt_ids = self.receipts.get(&queue).into_iter().flatten().collect::<Vec<_>>();
+        CryptoHash::hash_borsh(receipt_ids)
+    }
+
+    fn queue_receipts(&self, queue: ReceiptQueue) -> ReceiptMultiset {
+        self.receipts.get(&queue).cloned().unwrap_or_default()
+    }
+}
+
+fn deserialize<T: BorshDeserialize>(key: &[u8], value: &[u8]) -> Result<T, StorageError> {
+    T::try_from_slice(value).map_err(|err| {
+        StorageError::StorageInconsistentState(format!(
+            "failed to deserialize the value of {key:?}: {err}"
+        ))
+    })
+}
+
+/// Checks that the children, the left one first, hold the tokens of the
+/// parent, and that they hold the receipts of each queue of the parent as
+/// many times as the queue is copied to them.
+pub fn check_split_conservation(
+    parent: &ShardConservationSummary,
+    children: &[&ShardConservationSummary],
+) -> Result<(), Error> {
+    let mut errors = vec![];
+    let children_balance = children.iter().map(|child| child.total_balance).sum::<Balance>();
+    if children_balance != parent.total_balance {
+        errors.push(format!(
+            "total balance of the parent {} != total balance of the children {}",
+            parent.total_balance, children_balance
+        ));
+    }
+    let queues = children
+        .iter()
+        .flat_map(|child| child.receipts.keys())
+        .chain(parent.receipts.keys())
+        .copied()
+        .collect::<BTreeSet<_>>();
+    for queue in queues {
+        let parent_receipts = parent.queue_receipts(queue);
+        // The receipts expected and held, by the children together or by each child.
+        let mut holders = vec![];
+        match queue.split() {
+            QueueSplit::ByAccount => {
+                let mut children_receipts = ReceiptMultiset::new();
+                for child in children {
+                    for (receipt_id, count) in child.queue_receipts(queue) {
+                        *children_receipts.entry(receipt_id).or_default() += count;
+                    }
+                }
+                holders.push(("children".to_string(), parent_receipts.clone(), children_receipts));
+            }
+            QueueSplit::CopiedToBothChildren | QueueSplit::CopiedToLeftChild => {
+                for (child_index, child) in children.iter().enumerate() {
+                    let expected = match queue.split() {
+                        QueueSplit::CopiedToLeftChild if child_index > 0 => Default::default(),
+                        _ => parent_receipts.clone(),
+                    };
+                    holders.push((
+                        format!("child {child_index}"),
+                        expected,
+                        child.queue_receipts(queue),
+                    ));
+                }
+            }
+        }
+        for (holder, expected, held) in holders {
+            if expected == held {
+                continue;
+            }
+            errors.push(format!(
+                "{queue:?} receipts of the {holder} differ, parent {} {:?}, children {:?}, \
+                 missing {:?}, unexpected {:?}",
+                parent_receipts.values().sum::<u64>(),
+                parent.receipts_hash(queue),
+                children.iter().map(|child| child.receipts_hash(queue)).collect::<Vec<_>>(),
+                multiset_difference(&expected, &held),
+                multiset_difference(&held, &expected),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::ReshardingError(format!(
+            "the split of the shard isn't conservative: {}",
+            errors.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+/// The receipts of `a` which are not in `b`, as many times as they are
+/// missing.
+fn multiset_difference(a: &ReceiptMultiset, b: &ReceiptMultiset) -> Vec<(CryptoHash, u64)> {
+    a.iter()
+        .filter_map(|(receipt_id, count)| {
+            let missing = count.saturating_sub(b.get(receipt_id).copied().unwrap_or_default());
+            (missing > 0).then_some((*receipt_id, missing))
+        })
+        .collect()
+}
+
+/// Checks that the split of the parent state into the state of the children
+/// at the resharding block preserves the tokens and the receipts. `children`
+/// are the left child first, and their state is read from their memtries.
+pub fn check_resharding_conservation(
+    tries: &ShardTries,
+    parent_shard_uid: ShardUId,
+    parent_state_root: StateRoot,
+    children: &[(ShardUId, StateRoot)],
+) -> Result<(), Error> {
+    let parent = ShardConservationSummary::from_trie(
+        &tries.get_trie_for_shard(parent_shard_uid, parent_state_root),
+    )?;
+    let children = children
+        .iter()
+        .map(|(shard_uid, state_root)| {
+            ShardConservationSummary::from_trie(&tries.get_trie_for_shard(*shard_uid, *state_root))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    tracing::debug!(
+        target: "resharding", ?parent_shard_uid, num_accounts = parent.num_accounts,
+        total_balance = parent.total_balance, "checking conservation of the split"
+    );
+    check_split_conservation(&parent, &children.iter().collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::account::AccountContract;
+    use near_primitives::receipt::ReceiptPriority;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::types::AccountId;
+    use near_store::test_utils::{TestTriesBuilder, test_populate_trie};
+    use std::borrow::Cow;
+
+    fn summary(
+        total_balance: Balance,
+        receipts: &[(ReceiptQueue, &[u8])],
+    ) -> ShardConservationSummary {
+        let mut summary = ShardConservationSummary { total_balance, ..Default::default() };
+        for (queue, ids) in receipts {
+            let queue_receipts = summary.receipts.entry(*queue).or_default();
+            for id in *ids {
+                *queue_receipts.entry(CryptoHash::hash_bytes(&[*id])).or_default() += 1;
+            }
+        }
+        summary
+    }
+
+    #[test]
+    fn test_split_conservation() {
+        let parent = summary(
+            100,
+            &[
+                (ReceiptQueue::Delayed, &[1, 2]),
+                (ReceiptQueue::Buffered, &[5]),
+                (ReceiptQueue::Postponed, &[3, 4]),
+            ],
+        );
+        // The delayed receipts are copied to both children and the buffered
+        // ones to the left child.
+        let left = summary(
+            60,
+            &[
+                (ReceiptQueue::Delayed, &[1, 2]),
+                (ReceiptQueue::Buffered, &[5]),
+                (ReceiptQueue::Postponed, &[3]),
+            ],
+        );
+        let right =
+            summary(40, &[(ReceiptQueue::Delayed, &[1, 2]), (ReceiptQueue::Postponed, &[4])]);
+        check_split_conservation(&parent, &[&left, &right]).unwrap();
+
+        let right = summary(41, &[(ReceiptQueue::Delayed, &[1, 2])]);
+        let err = check_split_conservation(&parent, &[&left, &right]).unwrap_err().to_string();
+        assert!(err.contains("total balance"), "{err}");
+        assert!(err.contains("Postponed"), "{err}");
+        assert!(!err.contains("Delayed"), "{err}");
+        assert!(!err.contains("Buffered"), "{err}");
+    }
+
+    /// A receipt held by both children, or missing in one of the children the
+    /// queue is copied to, isn't conservative even if the union of the
+    /// receipts of the children is the receipts of the parent.
+    #[test]
+    fn test_split_conservation_counts_receipts() {
+        let parent =
+            summary(100, &[(ReceiptQueue::Delayed, &[1, 2]), (ReceiptQueue::Postponed, &[3, 4])]);
+        let left =
+            summary(60, &[(ReceiptQueue::Delayed, &[1, 2]), (ReceiptQueue::Postponed, &[3, 4])]);
+        let right = summary(40, &[(ReceiptQueue::Delayed, &[1]), (ReceiptQueue::Postponed, &[4])]);
+        let err = check_split_conservation(&parent, &[&left, &right]).unwrap_err().to_string();
+        assert!(err.contains("Postponed receipts of the children"), "{err}");
+        assert!(err.contains("Delayed receipts of the child 1"), "{err}");
+        assert!(!err.contains("Delayed receipts of the child 0"), "{err}");
+
+        let buffered_in_both = summary(0, &[(ReceiptQueue::Buffered, &[5])]);
+        let parent = summary(0, &[(ReceiptQueue::Buffered, &[5])]);
+        let err = check_split_conservation(&parent, &[&buffered_in_both, &buffered_in_both])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Buffered receipts of the child 1"), "{err}");
+    }
+
+    #[test]
+    fn test_summary_from_trie() {
+        let account = |amount, locked| {
+            borsh::to_vec(&Account::new(amount, locked, AccountContract::None, 100)).unwrap()
+        };
+        let receipt = |receiver_id: &AccountId, id: u8| {
+            let mut receipt =
+                Receipt::new_balance_refund(receiver_id, 1, ReceiptPriority::NoPriority);
+            receipt.set_receipt_id(CryptoHash::hash_bytes(&[id]));
+            receipt
+        };
+        let alice: AccountId = "alice".parse().unwrap();
+        let bob: AccountId = "bob".parse().unwrap();
+        let delayed_receipt = |id| {
+            let receipt = receipt(&alice, id);
+            borsh::to_vec(&ReceiptOrStateStoredReceipt::Receipt(Cow::Owned(receipt))).unwrap()
+        };
+        let postponed = receipt(&bob, 3);
+        let changes = vec![
+            (TrieKey::Account { account_id: alice.clone() }.to_vec(), Some(account(10, 5))),
+            (TrieKey::Account { account_id: bob.clone() }.to_vec(), Some(account(20, 0))),
+            // The indices of the delayed receipts aren't a receipt.
+            (TrieKey::DelayedReceiptIndices.to_vec(), Some(vec![0; 16])),
+            (TrieKey::DelayedReceipt { index: 0 }.to_vec(), Some(delayed_receipt(1))),
+            // The same receipt twice in the queue.
+            (TrieKey::DelayedReceipt { index: 1 }.to_vec(), Some(delayed_receipt(1))),
+            (TrieKey::DelayedReceipt { index: 2 }.to_vec(), Some(delayed_receipt(2))),
+            (
+                TrieKey::PostponedReceipt {
+                    receiver_id: bob.clone(),
+                    receipt_id: *postponed.receipt_id(),
+                }
+                .to_vec(),
+                Some(borsh::to_vec(&postponed).unwrap()),
+            ),
+        ];
+        let tries = TestTriesBuilder::new().build();
+        let shard_uid = ShardUId::single_shard();
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+
+        let summary =
+            ShardConservationSummary::from_trie(&tries.get_trie_for_shard(shard_uid, root))
+                .unwrap();
+        assert_eq!(summary.num_accounts, 2);
+        assert_eq!(summary.total_balance, 35);
+        assert_eq!(
+            summary.receipts,
+            BTreeMap::from([
+                (
+                    ReceiptQueue::Delayed,
+                    BTreeMap::from([
+                        (CryptoHash::hash_bytes(&[1]), 2),
+                        (CryptoHash::hash_bytes(&[2]), 1)
+                    ])
+                ),
+                (ReceiptQueue::Postponed, BTreeMap::from([(CryptoHash::hash_bytes(&[3]), 1)])),
+            ])
+        );
+    }
+}
@@ -1,21 +1,30 @@
+use super::conservation::check_resharding_conservation;
 use super::event_type::{ReshardingEventType, ReshardingSplitShardParams};
 use super::types::ReshardingSender;
-use crate::ChainStoreUpdate;
+use super::yield_timeouts::split_promise_yield_timeouts;
 use crate::flat_storage_resharder::{FlatStorageResharder, FlatStorageResharderController};
+use crate::metrics;
 use crate::types::RuntimeAdapter;
+use crate::{ChainStore, ChainStoreAccess, ChainStoreUpdate};
 use itertools::Itertools;
 use near_chain_configs::{MutableConfigValue, ReshardingConfig, ReshardingHandle};
 use near_chain_primitives::Error;
+use near_chain_primitives::error::ChainErrorContext;
 use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::events::{self, ChainEvent};
+use near_pool::ShardedTransactionPool;
 use near_primitives::block::Block;
 use near_primitives::congestion_info::CongestionInfo;
+use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::{ShardLayout, get_block_shard_uid};
 use near_primitives::state::PartialState;
 use near_primitives::types::chunk_extra::ChunkExtra;
+use near_primitives::version::ProtocolFeature;
 use near_store::adapter::trie_store::{TrieStoreUpdateAdapter, get_shard_uid_mapping};
 use near_store::adapter::{StoreAdapter, StoreUpdateAdapter};
-use near_store::flat::BlockInfo;
+use near_store::db::RESHARDING_PENDING_SPLITS_KEY;
+use near_store::flat::{BlockInfo, FlatStorageStatus};
 use near_store::trie::mem::memtrie_update::TrackingMode;
 use near_store::trie::ops::resharding::RetainMode;
 use near_store::trie::outgoing_metadata::ReceiptGroupsQueue;
@@ -23,7 +32,7 @@ use near_store::trie::{AccessOptions, TrieRecorder};
 use near_store::{DBCol, ShardTries, ShardUId, Store, TrieAccess};
 use std::io;
 use std::num::NonZero;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub struct ReshardingManager {
     store: Store,
@@ -35,6 +44,10 @@ pub struct ReshardingManager {
     pub resharding_handle: ReshardingHandle,
     /// Takes care of performing resharding on the flat storage.
     pub flat_storage_resharder: FlatStorageResharder,
+    /// Splits deferred because the resharding of their parent shard wasn't
+    /// completed yet, as the resharding block and the parent shard. Persisted
+    /// in `DBCol::Misc` so that they survive a restart of the node.
+    pending_splits: Vec<(CryptoHash, ShardUId)>,
 }
 
 impl ReshardingManager {
@@ -45,6 +58,10 @@ impl ReshardingManager {
         resharding_config: MutableConfigValue<ReshardingConfig>,
         resharding_sender: ReshardingSender,
     ) -> Self {
+        let pending_splits = store
+            .get_ser(DBCol::Misc, RESHARDING_PENDING_SPLITS_KEY)
+            .expect("failed to read the pending splits")
+            .unwrap_or_default();
         let resharding_handle = ReshardingHandle::new();
         let flat_storage_resharder = FlatStorageResharder::new(
             runtime_adapter,
@@ -52,7 +69,14 @@ impl ReshardingManager {
             FlatStorageResharderController::from_resharding_handle(resharding_handle.clone()),
             resharding_config.clone(),
         );
-        Self { store, epoch_manager, resharding_config, flat_storage_resharder, resharding_handle }
+        Self {
+            store,
+            epoch_manager,
+            resharding_config,
+            flat_storage_resharder,
+            resharding_handle,
+            pending_splits,
+        }
     }
 
     /// Trigger resharding if shard layout changes after the given block.
@@ -107,7 +131,9 @@ impl ReshardingManager {
                     tries,
                     split_shard_event,
                     next_shard_layout,
-                )?;
+                )
+                .with_block_context(block_hash, block_height)
+                .with_shard_context(shard_uid.shard_id())?;
             }
             None => {
                 tracing::warn!(target: "resharding", ?resharding_event_type, "unsupported resharding event type, skipping");
@@ -116,6 +142,80 @@ impl ReshardingManager {
         Ok(())
     }
 
+    /// Retries the splits deferred until the resharding of their parent shard
+    /// is completed. Called before processing a block, so that the children
+    /// are created before their chunks are applied.
+    pub fn retry_pending_splits(
+        &mut self,
+        chain_store: &mut ChainStore,
+        tries: ShardTries,
+    ) -> Result<(), Error> {
+        if self.pending_splits.is_empty() {
+            return Ok(());
+        }
+        for (block_hash, shard_uid) in std::mem::take(&mut self.pending_splits) {
+            if !self.is_resharding_of_parent_completed(shard_uid, &tries)? {
+                self.pending_splits.push((block_hash, shard_uid));
+                continue;
+            }
+            tracing::info!(target: "resharding", ?block_hash, parent_shard = ?shard_uid, "retrying the deferred split");
+            let block = chain_store.get_block(&block_hash)?;
+            self.start_resharding(chain_store.store_update(), &block, shard_uid, tries.clone())?;
+        }
+        self.save_pending_splits()?;
+        Ok(())
+    }
+
+    /// Fails if the block belongs to an epoch whose shards are created by a
+    /// split which is still deferred, as the chunks of the children can't be
+    /// applied before their state exists. The error doesn't mark the block as
+    /// invalid, so it is processed again once the split is done.
+    pub fn check_no_pending_split(&self, block: &Block) -> Result<(), Error> {
+        for (resharding_block_hash, parent_shard_uid) in &self.pending_splits {
+            let children_epoch_id = self.epoch_manager.get_next_epoch_id(resharding_block_hash)?;
+            if block.header().epoch_id() == &children_epoch_id {
+                return Err(Error::ReshardingError(format!(
+                    "split of the shard {parent_shard_uid} after the block {resharding_block_hash} is still deferred"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn save_pending_splits(&self) -> io::Result<()> {
+        let mut store_update = self.store.store_update();
+        if self.pending_splits.is_empty() {
+            store_update.delete(DBCol::Misc, RESHARDING_PENDING_SPLITS_KEY);
+        } else {
+            store_update.set_ser(
+                DBCol::Misc,
+                RESHARDING_PENDING_SPLITS_KEY,
+                &self.pending_splits,
+            )?;
+        }
+        store_update.commit()
+    }
+
+    /// Moves the transactions of the pool to the shards of the layout of the
+    /// block after the given block, so that the chunk producers of the
+    /// children can include them in the first chunks of the new shard layout.
+    /// Called whenever the block becomes the head of the chain, so a reorg
+    /// back to a block before the shard layout change moves the transactions
+    /// of the children back to the parent.
+    pub fn reshard_tx_pool(
+        &self,
+        block: &Block,
+        tx_pool: &Mutex<ShardedTransactionPool>,
+    ) -> Result<(), Error> {
+        let block_hash = block.hash();
+        let next_shard_layout = self.epoch_manager.get_shard_layout_from_prev_block(block_hash)?;
+        let num_txs = tx_pool.lock().unwrap().reshard(&next_shard_layout);
+        if num_txs > 0 {
+            tracing::info!(target: "resharding", ?block_hash, num_txs, "moved the transactions of the pool to the shards of the next block");
+        }
+        Ok(())
+    }
+
     fn split_shard(
         &mut self,
         chain_store_update: ChainStoreUpdate,
@@ -131,6 +231,16 @@ impl ReshardingManager {
             return Ok(());
         }
 
+        if !self.is_resharding_of_parent_completed(shard_uid, &tries)? {
+            let block_hash = *block.hash();
+            tracing::info!(target: "resharding", ?block_hash, parent_shard = ?shard_uid, "resharding of the parent shard is not completed yet, deferring the split");
+            if !self.pending_splits.contains(&(block_hash, shard_uid)) {
+                self.pending_splits.push((block_hash, shard_uid));
+                self.save_pending_splits()?;
+            }
+            return Ok(());
+        }
+
         // Reshard the State column by setting ShardUId mapping from children to ancestor.
         self.set_state_shard_uid_mapping(&split_shard_event)?;
 
@@ -143,6 +253,14 @@ impl ReshardingManager {
             split_shard_event.clone(),
         )?;
 
+        events::emit(ChainEvent::ReshardingScheduled {
+            block_hash: *block.hash(),
+            height: block.header().height(),
+            parent_shard_uid: shard_uid.to_string(),
+            left_child_shard_uid: split_shard_event.left_child_shard.to_string(),
+            right_child_shard_uid: split_shard_event.right_child_shard.to_string(),
+        });
+
         // Trigger resharding of flat storage.
         self.flat_storage_resharder.start_resharding(
             ReshardingEventType::SplitShard(split_shard_event),
@@ -152,6 +270,34 @@ impl ReshardingManager {
         Ok(())
     }
 
+    /// The parent shard may itself be a child of a previous resharding, when
+    /// shards are split in consecutive epochs. It can't be split again until
+    /// its own flat storage is created and its memtrie, still shared with its
+    /// parent, is reloaded. Checked before anything is written for the split.
+    fn is_resharding_of_parent_completed(
+        &self,
+        parent_shard_uid: ShardUId,
+        tries: &ShardTries,
+    ) -> Result<bool, Error> {
+        let flat_storage_status = self
+            .store
+            .flat_store()
+            .get_flat_storage_status(parent_shard_uid)
+            .map_err(StorageError::from)?;
+        if !matches!(flat_storage_status, FlatStorageStatus::Ready(_)) {
+            tracing::debug!(target: "resharding", ?parent_shard_uid, ?flat_storage_status, "flat storage of the parent shard is not ready");
+            return Ok(false);
+        }
+        let is_memtrie_hybrid = tries
+            .get_memtries(parent_shard_uid)
+            .is_some_and(|memtries| memtries.read().unwrap().is_hybrid());
+        if is_memtrie_hybrid {
+            tracing::debug!(target: "resharding", ?parent_shard_uid, "memtrie of the parent shard is not reloaded after the previous resharding");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     /// Store in the database the mapping of ShardUId from children to the parent shard,
     /// so that subsequent accesses to the State will use the ancestor's ShardUId prefix
     /// as a prefix for the database key.
@@ -201,6 +347,9 @@ impl ReshardingManager {
             target: "resharding", "process_memtrie_resharding_storage_update",
             ?block_hash, block_height, ?parent_shard_uid)
         .entered();
+        let _timer = metrics::RESHARDING_PHASE_TIME
+            .with_label_values(&[&parent_shard_uid.to_string(), "memtrie_split"])
+            .start_timer();
 
         tries.freeze_parent_memtrie(parent_shard_uid, split_shard_event.children_shards())?;
 
@@ -208,13 +357,14 @@ impl ReshardingManager {
         let boundary_account = split_shard_event.boundary_account;
 
         let mut trie_store_update = self.store.store_update();
+        let mut children_state_roots = vec![];
 
         // TODO(resharding): leave only tracked shards.
         for (new_shard_uid, retain_mode) in [
             (split_shard_event.left_child_shard, RetainMode::Left),
             (split_shard_event.right_child_shard, RetainMode::Right),
         ] {
-            let Some(memtries) = tries.get_memtries(new_shard_uid) else {
+            let Some(child_memtries) = tries.get_memtries(new_shard_uid) else {
                 tracing::error!(
                     "Memtrie not loaded. Cannot process memtrie resharding storage
                      update for block {:?}, shard {:?}",
@@ -228,7 +378,7 @@ impl ReshardingManager {
                 target: "resharding", ?new_shard_uid, ?retain_mode,
                 "Creating child memtrie by retaining nodes in parent memtrie..."
             );
-            let mut memtries = memtries.write().unwrap();
+            let mut memtries = child_memtries.write().unwrap();
             let mut trie_recorder = TrieRecorder::new(None);
             let mode = TrackingMode::RefcountsAndAccesses(&mut trie_recorder);
             let memtrie_update = memtries.update(*parent_chunk_extra.state_root(), mode)?;
@@ -244,7 +394,7 @@ impl ReshardingManager {
                 parent_shard_uid,
             );
             let memtrie_changes = trie_changes.memtrie_changes.as_ref().unwrap();
-            let new_state_root = memtries.apply_memtrie_changes(block_height, memtrie_changes);
+            let mut new_state_root = memtries.apply_memtrie_changes(block_height, memtrie_changes);
             drop(memtries);
 
             // Get the congestion info for the child.
@@ -265,7 +415,35 @@ impl ReshardingManager {
                 retain_mode,
             )?;
 
-            let trie_recorder = parent_trie.take_recorder().unwrap();
+            let mut trie_recorder = parent_trie.take_recorder().unwrap();
+            let child_protocol_version =
+                self.epoch_manager.get_epoch_protocol_version(&child_epoch_id)?;
+            // Keep in the child only the yield timeouts of its accounts. The
+            // reads are recorded too, as the validators redo the split. The
+            // flat state of the child is patched when its flat storage split
+            // completes, before it can be read.
+            if ProtocolFeature::SplitAwarePromiseYieldTimeouts.enabled(child_protocol_version) {
+                let child_trie = tries
+                    .get_trie_for_shard(new_shard_uid, new_state_root)
+                    .recording_reads_with_recorder(trie_recorder);
+                let changes =
+                    split_promise_yield_timeouts(&child_trie, &child_shard_layout, new_shard_uid)?;
+                if !changes.is_empty() {
+                    let timeouts_changes = child_trie.update(changes, AccessOptions::DEFAULT)?;
+                    new_state_root = child_memtries.write().unwrap().apply_memtrie_changes(
+                        block_height,
+                        timeouts_changes.memtrie_changes.as_ref().unwrap(),
+                    );
+                    tries.apply_insertions(
+                        &timeouts_changes,
+                        new_shard_uid,
+                        &mut trie_store_update.trie_store_update(),
+                    );
+                }
+                trie_recorder = child_trie.take_recorder().unwrap();
+            }
+            children_state_roots.push((new_shard_uid, new_state_root));
+
             let partial_storage = trie_recorder.write().expect("no poison").recorded_storage();
             let partial_state_len = match &partial_storage.nodes {
                 PartialState::TrieValues(values) => values.len(),
@@ -303,6 +481,18 @@ impl ReshardingManager {
             );
         }
 
+        // Reads the whole state of the parent and the children, too slow for
+        // production nodes. Done before the split is committed, so that a
+        // split which isn't conservative is never persisted.
+        if cfg!(debug_assertions) || cfg!(feature = "test_features") {
+            check_resharding_conservation(
+                &tries,
+                parent_shard_uid,
+                *parent_chunk_extra.state_root(),
+                &children_state_roots,
+            )?;
+        }
+
         chain_store_update.merge(trie_store_update);
         chain_store_update.commit()?;
 
@@ -421,3 +611,89 @@ impl ReshardingManager {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ChainStoreAccess;
+    use crate::resharding::event_type::ReshardingSplitShardParams;
+    use crate::test_utils::setup;
+    use near_async::time::Clock;
+    use near_o11y::testonly::init_test_logger;
+    use near_primitives::shard_layout::ShardUId;
+    use near_store::adapter::StoreAdapter;
+    use near_store::adapter::trie_store::get_shard_uid_mapping;
+    use near_store::flat::{
+        BlockInfo, FlatStorageReadyStatus, FlatStorageReshardingStatus, FlatStorageStatus,
+    };
+
+    /// The split of a shard whose own resharding isn't completed yet is
+    /// deferred without writing anything, and retried once it is completed.
+    #[test]
+    fn split_shard_deferred_until_resharding_of_parent_completed() {
+        init_test_logger();
+        let (mut chain, _, _, _) = setup(Clock::real());
+        let genesis = chain.genesis_block().clone();
+        let shard_layout =
+            chain.epoch_manager.get_shard_layout(genesis.header().epoch_id()).unwrap();
+        let parent_shard = shard_layout.shard_uids().next().unwrap();
+        let left_child_shard = ShardUId { version: shard_layout.version() + 1, shard_id: 100 };
+        let right_child_shard = ShardUId { version: shard_layout.version() + 1, shard_id: 101 };
+        let resharding_block = BlockInfo {
+            hash: *genesis.hash(),
+            height: genesis.header().height(),
+            prev_hash: *genesis.header().prev_hash(),
+        };
+        let split_shard_event = ReshardingSplitShardParams {
+            parent_shard,
+            left_child_shard,
+            right_child_shard,
+            boundary_account: "boundary".parse().unwrap(),
+            resharding_block,
+        };
+        let store = chain.chain_store.store();
+        let tries = chain.runtime_adapter.get_tries();
+
+        // The parent is itself a child whose flat storage is still catching up.
+        let mut store_update = store.flat_store().store_update();
+        store_update.set_flat_storage_status(
+            parent_shard,
+            FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
+                resharding_block,
+            )),
+        );
+        store_update.commit().unwrap();
+
+        chain
+            .resharding_manager
+            .split_shard(
+                chain.chain_store.store_update(),
+                &genesis,
+                parent_shard,
+                tries.clone(),
+                split_shard_event,
+                shard_layout.clone(),
+            )
+            .unwrap();
+        assert_eq!(chain.resharding_manager.pending_splits, vec![(*genesis.hash(), parent_shard)]);
+        for child_shard in [left_child_shard, right_child_shard] {
+            assert_eq!(get_shard_uid_mapping(&store, child_shard), child_shard);
+        }
+
+        // Still not completed, the split stays pending.
+        chain
+            .resharding_manager
+            .retry_pending_splits(&mut chain.chain_store, tries.clone())
+            .unwrap();
+        assert_eq!(chain.resharding_manager.pending_splits, vec![(*genesis.hash(), parent_shard)]);
+
+        // Once completed, the split is retried.
+        let mut store_update = store.flat_store().store_update();
+        store_update.set_flat_storage_status(
+            parent_shard,
+            FlatStorageStatus::Ready(FlatStorageReadyStatus { flat_head: resharding_block }),
+        );
+        store_update.commit().unwrap();
+        chain.resharding_manager.retry_pending_splits(&mut chain.chain_store, tries).unwrap();
+        assert!(chain.resharding_manager.pending_splits.is_empty());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod conservation;
 pub mod event_type;
 pub mod manager;
 pub mod resharding_actor;
 pub mod types;
+pub mod yield_timeouts;
@@ -0,0 +1,126 @@
+//! The split of the queue of the promise yield timeouts. The entries of the
+//! queue aren't keyed by account, so the trie split copies the whole queue of
+//! the parent to both children. With `SplitAwarePromiseYieldTimeouts` the
+//! queue of each child is re-indexed right after the split, so that it only
+//! holds the timeouts of the accounts of the child and the timeouts of a child
+//! don't wait behind, or depend on, the ones of its sibling.
+
+use near_primitives::errors::StorageError;
+use near_primitives::receipt::{PromiseYieldIndices, PromiseYieldTimeout};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::trie_key::TrieKey;
+use near_store::{ShardUId, TrieAccess, get};
+
+/// Returns the changes of the state of the child which leave in its timeout
+/// queue only the timeouts of its accounts. They keep the order of the parent
+/// queue and get contiguous indices starting at the first index of the parent
+/// queue. The trie of the child must hold the queue of the parent.
+pub fn split_promise_yield_timeouts(
+    child_trie: &dyn TrieAccess,
+    child_shard_layout: &ShardLayout,
+    child_shard_uid: ShardUId,
+) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, StorageError> {
+    let Some(indices) = get::<PromiseYieldIndices>(child_trie, &TrieKey::PromiseYieldIndices)?
+    else {
+        return Ok(vec![]);
+    };
+    let mut changes = vec![];
+    let mut next_available_index = indices.first_index;
+    for index in indices.first_index..indices.next_available_index {
+        let key = TrieKey::PromiseYieldTimeout { index };
+        let timeout = get::<PromiseYieldTimeout>(child_trie, &key)?.ok_or_else(|| {
+            StorageError::StorageInconsistentState(format!(
+                "PromiseYield timeout queue entry #{index} should be in the state"
+            ))
+        })?;
+        if child_shard_layout.account_id_to_shard_uid(&timeout.account_id) != child_shard_uid {
+            continue;
+        }
+        if next_available_index != index {
+            changes.push((
+                TrieKey::PromiseYieldTimeout { index: next_available_index }.to_vec(),
+                Some(borsh::to_vec(&timeout).unwrap()),
+            ));
+        }
+        next_available_index += 1;
+    }
+    if next_available_index == indices.next_available_index {
+        return Ok(changes);
+    }
+    for index in next_available_index..indices.next_available_index {
+        changes.push((TrieKey::PromiseYieldTimeout { index }.to_vec(), None));
+    }
+    let child_indices =
+        PromiseYieldIndices { first_index: indices.first_index, next_available_index };
+    changes.push((
+        TrieKey::PromiseYieldIndices.to_vec(),
+        Some(borsh::to_vec(&child_indices).unwrap()),
+    ));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::AccountId;
+    use near_store::Trie;
+    use near_store::test_utils::{TestTriesBuilder, test_populate_trie};
+
+    fn timeout_queue(trie: &Trie) -> (PromiseYieldIndices, Vec<AccountId>) {
+        let indices: PromiseYieldIndices =
+            get(trie, &TrieKey::PromiseYieldIndices).unwrap().unwrap();
+        let accounts = (indices.first_index..indices.next_available_index)
+            .map(|index| {
+                let key = TrieKey::PromiseYieldTimeout { index };
+                get::<PromiseYieldTimeout>(trie, &key).unwrap().unwrap().account_id
+            })
+            .collect();
+        (indices, accounts)
+    }
+
+    #[test]
+    fn test_split_promise_yield_timeouts() {
+        let shard_layout = ShardLayout::multi_shard_custom(vec!["b".parse().unwrap()], 3);
+        let left_shard_uid = shard_layout.account_id_to_shard_uid(&"a".parse().unwrap());
+        let right_shard_uid = shard_layout.account_id_to_shard_uid(&"c".parse().unwrap());
+        let parent_shard_uid = ShardUId::single_shard();
+        let indices = PromiseYieldIndices { first_index: 5, next_available_index: 9 };
+        let mut changes =
+            vec![(TrieKey::PromiseYieldIndices.to_vec(), Some(borsh::to_vec(&indices).unwrap()))];
+        for (index, account_id) in (5..).zip(["a1", "c1", "a2", "c2"]) {
+            let timeout = PromiseYieldTimeout {
+                account_id: account_id.parse().unwrap(),
+                data_id: CryptoHash::hash_bytes(account_id.as_bytes()),
+                expires_at: 10 + index,
+            };
+            changes.push((
+                TrieKey::PromiseYieldTimeout { index }.to_vec(),
+                Some(borsh::to_vec(&timeout).unwrap()),
+            ));
+        }
+        let tries = TestTriesBuilder::new().build();
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, parent_shard_uid, changes);
+
+        for (shard_uid, expected_accounts) in
+            [(left_shard_uid, ["a1", "a2"]), (right_shard_uid, ["c1", "c2"])]
+        {
+            let trie = tries.get_trie_for_shard(parent_shard_uid, root);
+            let changes = split_promise_yield_timeouts(&trie, &shard_layout, shard_uid).unwrap();
+            let child_root = test_populate_trie(&tries, &root, parent_shard_uid, changes);
+            let child_trie = tries.get_trie_for_shard(parent_shard_uid, child_root);
+            let (child_indices, accounts) = timeout_queue(&child_trie);
+            assert_eq!(
+                child_indices,
+                PromiseYieldIndices { first_index: 5, next_available_index: 7 }
+            );
+            assert_eq!(
+                accounts,
+                expected_accounts.map(|account| account.parse::<AccountId>().unwrap())
+            );
+            // The entries past the end of the child queue are removed.
+            let key = TrieKey::PromiseYieldTimeout { index: 7 };
+            assert!(get::<PromiseYieldTimeout>(&child_trie, &key).unwrap().is_none());
+        }
+    }
+}
@@ -78,6 +78,16 @@ pub(crate) static CONGESTION_PREPARE_TX_GAS_LIMIT: LazyLock<IntGaugeVec> = LazyL
     .unwrap()
 });
 
+pub(crate) static MAIN_STORAGE_PROOF_SIZE_SOFT_LIMIT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_main_storage_proof_size_soft_limit",
+        "The soft limit of the storage proof of the last chunk applied per shard. Lower than \
+         the runtime config limit for the chunks before a split of the shard.",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
 pub static APPLYING_CHUNKS_TIME: LazyLock<HistogramVec> = LazyLock::new(|| {
     try_create_histogram_vec(
         "near_applying_chunks_time",
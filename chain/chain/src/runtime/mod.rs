@@ -29,7 +29,7 @@ use near_primitives::types::{
     AccountId, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas, MerkleHash,
     ShardId, StateChangeCause, StateRoot, StateRootNode,
 };
-use near_primitives::version::ProtocolVersion;
+use near_primitives::version::{ProtocolFeature, ProtocolVersion};
 use near_primitives::views::{
     AccessKeyInfoView, CallResult, ContractCodeView, QueryRequest, QueryResponse,
     QueryResponseKind, ViewStateResult,
@@ -89,13 +89,14 @@ impl NightshadeRuntime {
         gc_num_epochs_to_keep: u64,
         trie_config: TrieConfig,
         state_snapshot_config: StateSnapshotConfig,
+        record_receipt_profiles: bool,
     ) -> Arc<Self> {
         let runtime_config_store = match runtime_config_store {
             Some(store) => store,
             None => RuntimeConfigStore::for_chain_id(&genesis_config.chain_id),
         };
 
-        let runtime = Runtime::new();
+        let runtime = Runtime::new().with_receipt_profiles(record_receipt_profiles);
         let trie_viewer = TrieViewer::new(trie_viewer_state_size_limit, max_gas_burnt_view);
         let flat_storage_manager = FlatStorageManager::new(store.flat_store());
         let epoch_config = epoch_manager.read().get_epoch_config(genesis_config.protocol_version);
@@ -336,7 +337,46 @@ impl NightshadeRuntime {
         Ok(result)
     }
 
-    fn get_gc_stop_height_impl(&self, block_hash: &CryptoHash) -> Result<BlockHeight, Error> {
+    /// The soft limit of the main storage proof of the chunk of `shard_id` in
+    /// `block`. If the shard is split after the block, the witness of the first
+    /// chunks of the children also holds the proof of the split, so only half of
+    /// the limit is left to the chunk. The end of the epoch isn't known before
+    /// the block is processed, so any block which might be the last one of the
+    /// epoch gets the reduced limit.
+    fn main_storage_proof_size_soft_limit(
+        &self,
+        config: &RuntimeConfig,
+        protocol_version: ProtocolVersion,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+        block: &ApplyChunkBlockContext,
+    ) -> Result<usize, Error> {
+        let limit = config.witness_config.main_storage_proof_size_soft_limit;
+        if !ProtocolFeature::ReshardingWitnessSizeLimit.enabled(protocol_version) {
+            return Ok(limit);
+        }
+        let next_epoch_id =
+            self.epoch_manager.get_next_epoch_id_from_prev_block(&block.prev_block_hash)?;
+        let next_shard_layout = self.epoch_manager.get_shard_layout(&next_epoch_id)?;
+        let is_split = next_shard_layout
+            .get_children_shards_ids(shard_id)
+            .is_some_and(|children| children.len() > 1);
+        if !is_split {
+            return Ok(limit);
+        }
+        let epoch_start = self.epoch_manager.get_epoch_start_from_epoch_id(epoch_id)?;
+        let epoch_length = self.epoch_manager.get_epoch_config(epoch_id)?.epoch_length;
+        if block.height + 1 < epoch_start + epoch_length {
+            return Ok(limit);
+        }
+        Ok(limit / 2)
+    }
+
+    fn get_gc_stop_height_impl(
+        &self,
+        block_hash: &CryptoHash,
+        num_epochs_to_keep: u64,
+    ) -> Result<BlockHeight, Error> {
         let epoch_manager = self.epoch_manager.read();
         // an epoch must have a first block.
         let epoch_first_block = *epoch_manager.get_block_info(block_hash)?.epoch_first_block();
@@ -344,7 +384,7 @@ impl NightshadeRuntime {
         // maintain pointers to avoid cloning.
         let mut last_block_in_prev_epoch = *epoch_first_block_info.prev_hash();
         let mut epoch_start_height = epoch_first_block_info.height();
-        for _ in 0..self.gc_num_epochs_to_keep - 1 {
+        for _ in 0..num_epochs_to_keep.max(MIN_GC_NUM_EPOCHS_TO_KEEP) - 1 {
             let epoch_first_block =
                 *epoch_manager.get_block_info(&last_block_in_prev_epoch)?.epoch_first_block();
             let epoch_first_block_info = epoch_manager.get_block_info(&epoch_first_block)?;
@@ -396,11 +436,12 @@ impl NightshadeRuntime {
 
         let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(prev_hash)?;
         let shard_uid = self.get_shard_uid_from_epoch_id(shard_id, &epoch_id)?;
+        let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
 
         let trie_with_state =
             self.tries.get_trie_with_block_hash_for_shard(shard_uid, *state_root, &prev_hash, true);
         let (path_boundary_nodes, nibbles_begin, nibbles_end) = match trie_with_state
-            .get_state_part_boundaries(part_id)
+            .get_state_part_boundaries(part_id, protocol_version)
         {
             Ok(res) => res,
             Err(err) => {
@@ -418,6 +459,7 @@ impl NightshadeRuntime {
             nibbles_begin,
             nibbles_end,
             trie_with_state,
+            protocol_version,
         );
         let state_part = borsh::to_vec(&match trie_nodes {
             Ok(partial_state) => partial_state,
@@ -478,11 +520,6 @@ impl RuntimeAdapter for NightshadeRuntime {
         self.tries.get_flat_storage_manager()
     }
 
-    fn get_shard_layout(&self, protocol_version: ProtocolVersion) -> ShardLayout {
-        let epoch_manager = self.epoch_manager.read();
-        epoch_manager.get_shard_layout_from_protocol_version(protocol_version)
-    }
-
     fn validate_tx(
         &self,
         shard_layout: &ShardLayout,
@@ -559,7 +596,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         time_limit: Option<Duration>,
     ) -> Result<PreparedTransactions, Error> {
         let start_time = std::time::Instant::now();
-        let PrepareTransactionsChunkContext { shard_id, .. } = chunk;
+        let PrepareTransactionsChunkContext { shard_id, max_transactions, .. } = chunk;
 
         let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(&prev_block.block_hash)?;
         let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
@@ -615,6 +652,10 @@ impl RuntimeAdapter for NightshadeRuntime {
 
         // Add new transactions to the result until some limit is hit or the transactions run out.
         'add_txs_loop: while let Some(transaction_group_iter) = transaction_groups.next() {
+            if max_transactions.is_some_and(|max| result.transactions.len() >= max) {
+                result.limited_by = Some(PrepareTransactionsLimit::TransactionCount);
+                break;
+            }
             if total_gas_burnt >= transactions_gas_limit {
                 result.limited_by = Some(PrepareTransactionsLimit::Gas);
                 break;
@@ -737,10 +778,18 @@ impl RuntimeAdapter for NightshadeRuntime {
     }
 
     fn get_gc_stop_height(&self, block_hash: &CryptoHash) -> BlockHeight {
+        self.get_gc_stop_height_for_num_epochs(block_hash, self.gc_num_epochs_to_keep)
+    }
+
+    fn get_gc_stop_height_for_num_epochs(
+        &self,
+        block_hash: &CryptoHash,
+        num_epochs_to_keep: u64,
+    ) -> BlockHeight {
         static LAST_LOG_TIME: AtomicU64 = AtomicU64::new(0);
         const LOG_THROTTLE_INTERVAL: u64 = 10;
 
-        let result = self.get_gc_stop_height_impl(block_hash);
+        let result = self.get_gc_stop_height_impl(block_hash, num_epochs_to_keep);
         match result {
             Ok(gc_stop_height) => gc_stop_height,
             Err(error) => {
@@ -807,7 +856,16 @@ impl RuntimeAdapter for NightshadeRuntime {
         let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(&block.prev_block_hash)?;
         let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
         let config = self.runtime_config_store.get_config(protocol_version);
-        let proof_limit = config.witness_config.main_storage_proof_size_soft_limit;
+        let proof_limit = self.main_storage_proof_size_soft_limit(
+            config,
+            protocol_version,
+            &epoch_id,
+            shard_id,
+            &block,
+        )?;
+        metrics::MAIN_STORAGE_PROOF_SIZE_SOFT_LIMIT
+            .with_label_values(&[&shard_id.to_string()])
+            .set(proof_limit as i64);
         trie = trie.recording_reads_with_proof_size_limit(proof_limit);
 
         match self.process_state_update(
@@ -1008,10 +1066,23 @@ impl RuntimeAdapter for NightshadeRuntime {
         res
     }
 
-    fn validate_state_part(&self, state_root: &StateRoot, part_id: PartId, data: &[u8]) -> bool {
+    fn validate_state_part(
+        &self,
+        state_root: &StateRoot,
+        part_id: PartId,
+        data: &[u8],
+        epoch_id: &EpochId,
+    ) -> bool {
+        let protocol_version = match self.epoch_manager.get_epoch_protocol_version(epoch_id) {
+            Ok(protocol_version) => protocol_version,
+            Err(err) => {
+                tracing::error!(target: "state-parts", ?err, ?epoch_id, "Unknown epoch of state part");
+                return false;
+            }
+        };
         match BorshDeserialize::try_from_slice(data) {
             Ok(trie_nodes) => {
-                match Trie::validate_state_part(state_root, part_id, trie_nodes) {
+                match Trie::validate_state_part(state_root, part_id, trie_nodes, protocol_version) {
                     Ok(_) => true,
                     // Storage error should not happen
                     Err(err) => {
@@ -1042,8 +1113,9 @@ impl RuntimeAdapter for NightshadeRuntime {
 
         let part = BorshDeserialize::try_from_slice(data)
             .expect("Part was already validated earlier, so could never fail here");
+        let protocol_version = self.epoch_manager.get_epoch_protocol_version(epoch_id)?;
         let ApplyStatePartResult { trie_changes, flat_state_delta, contract_codes } =
-            Trie::apply_state_part(state_root, part_id, part);
+            Trie::apply_state_part(state_root, part_id, part, protocol_version);
         let tries = self.get_tries();
         let shard_uid = self.get_shard_uid_from_epoch_id(shard_id, epoch_id)?;
         let mut store_update = tries.store_update();
@@ -29,6 +29,7 @@ impl NightshadeRuntime {
             DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
             Default::default(),
             StateSnapshotConfig::enabled(home_dir, "data", "state_snapshot"),
+            false,
         )
     }
 
@@ -41,6 +42,7 @@ impl NightshadeRuntime {
         runtime_config_store: Option<RuntimeConfigStore>,
         trie_config: TrieConfig,
         gc_num_epochs_to_keep: u64,
+        record_receipt_profiles: bool,
     ) -> Arc<Self> {
         Self::new(
             store,
@@ -53,6 +55,7 @@ impl NightshadeRuntime {
             gc_num_epochs_to_keep,
             trie_config,
             StateSnapshotConfig::enabled(home_dir, "data", "state_snapshot"),
+            record_receipt_profiles,
         )
     }
 
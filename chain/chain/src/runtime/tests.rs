@@ -138,6 +138,7 @@ impl TestEnv {
             DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
             Default::default(),
             StateSnapshotConfig::enabled(dir.path(), "data", "state_snapshot"),
+            false,
         );
         let state_roots = get_genesis_state_roots(&store).unwrap().unwrap();
         let genesis_hash = hash(&[0]);
@@ -742,13 +743,19 @@ fn test_state_sync() {
     assert!(!new_env.runtime.validate_state_root_node(&root_node_wrong, &env.state_roots[0]));
     root_node_wrong.data = std::sync::Arc::new([123]);
     assert!(!new_env.runtime.validate_state_root_node(&root_node_wrong, &env.state_roots[0]));
+    let epoch_id = &new_env.head.epoch_id;
     assert!(!new_env.runtime.validate_state_part(
         &Trie::EMPTY_ROOT,
         PartId::new(0, 1),
-        &state_part
+        &state_part,
+        epoch_id
     ));
-    new_env.runtime.validate_state_part(&env.state_roots[0], PartId::new(0, 1), &state_part);
-    let epoch_id = &new_env.head.epoch_id;
+    new_env.runtime.validate_state_part(
+        &env.state_roots[0],
+        PartId::new(0, 1),
+        &state_part,
+        epoch_id,
+    );
     new_env
         .runtime
         .apply_state_part(shard_id, &env.state_roots[0], PartId::new(0, 1), &state_part, epoch_id)
@@ -1400,6 +1407,7 @@ fn prepare_transactions(
     chain: &Chain,
     transaction_groups: &mut dyn TransactionGroupIterator,
     storage_config: RuntimeStorageConfig,
+    max_transactions: Option<usize>,
 ) -> Result<PreparedTransactions, Error> {
     let prev_hash = env.head.prev_block_hash;
     let shard_layout = env.epoch_manager.get_shard_layout_from_prev_block(&prev_hash).unwrap();
@@ -1412,6 +1420,7 @@ fn prepare_transactions(
         PrepareTransactionsChunkContext {
             shard_id,
             gas_limit: env.runtime.genesis_config.gas_limit,
+            max_transactions,
         },
         PrepareTransactionsBlockContext {
             next_gas_price: env.runtime.genesis_config.min_gas_price,
@@ -1450,6 +1459,32 @@ fn test_prepare_transactions_empty_storage_proof() {
         .expect_err("prepare transactions should fail with empty storage proof");
 }
 
+/// Check that transactions stop being prepared once the maximum number of
+/// transactions is reached, so that the storage proof covers only them.
+#[test]
+fn test_prepare_transactions_max_transactions() {
+    let (env, chain, mut transaction_pool) = get_test_env_with_chain_and_pool();
+    let max_transactions = 2;
+    assert!(transaction_pool.len() > max_transactions);
+    let storage_config = RuntimeStorageConfig {
+        state_root: env.state_roots[0],
+        use_flat_storage: true,
+        source: StorageDataSource::Db,
+        state_patch: Default::default(),
+    };
+    let mut transaction_groups = PoolIteratorWrapper::new(&mut transaction_pool);
+    let prepared_transactions = prepare_transactions(
+        &env,
+        &chain,
+        &mut transaction_groups,
+        storage_config,
+        Some(max_transactions),
+    )
+    .unwrap();
+    assert_eq!(prepared_transactions.transactions.len(), max_transactions);
+    assert_eq!(prepared_transactions.limited_by, Some(PrepareTransactionsLimit::TransactionCount));
+}
+
 // Helper function to test prepare_transactions with different storage sources.
 fn test_prepare_transactions_helper(
     storage_source: StorageDataSource,
@@ -1466,7 +1501,7 @@ fn test_prepare_transactions_helper(
 
     let mut transaction_groups = PoolIteratorWrapper::new(&mut transaction_pool);
     let prepared_transactions =
-        match prepare_transactions(&env, &chain, &mut transaction_groups, storage_config) {
+        match prepare_transactions(&env, &chain, &mut transaction_groups, storage_config, None) {
             Ok(prepared_transactions) => prepared_transactions,
             Err(err) => {
                 return Err(err);
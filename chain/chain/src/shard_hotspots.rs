@@ -0,0 +1,321 @@
+use near_chain_configs::ShardHotspotsConfig;
+use near_chain_primitives::Error;
+use near_primitives::types::{AccountId, EpochId, Gas, ShardId};
+use near_primitives::views::{AccountUsageView, ShardHotspotsView};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::types::ApplyChunkResult;
+use crate::update_shard::{NewChunkResult, ShardUpdateResult};
+
+/// Keeps the usage of the accounts of each shard in the recent epochs, to
+/// find the accounts which make a shard hot. The usage is recorded for every
+/// new chunk applied by the node, so the chunks of the blocks on forks are
+/// counted as well, and only the shards the node tracks are reported.
+///
+/// Only the top accounts of each shard are kept, see
+/// `ShardUsage::retain_top_accounts`, which makes the report of the accounts
+/// approximate. The totals of the shards are exact.
+pub struct ShardHotspotsTracker {
+    config: ShardHotspotsConfig,
+    /// Usage in the recent epochs, the latest epoch is at the back.
+    epochs: VecDeque<(EpochId, BTreeMap<ShardId, ShardUsage>)>,
+}
+
+#[derive(Default)]
+struct ShardUsage {
+    num_chunks: u64,
+    total_gas_burnt: Gas,
+    total_bytes_written: u64,
+    accounts: HashMap<AccountId, AccountUsage>,
+}
+
+impl ShardUsage {
+    /// Keeps only the accounts among the top `top_k` by gas burnt or by bytes
+    /// written, once there are more than twice as many, so that the memory
+    /// used by a shard is bounded. An account which falls out of the top and
+    /// becomes hot again later in the epoch is only counted from then on.
+    fn retain_top_accounts(&mut self, top_k: usize) {
+        if self.accounts.len() <= 2 * top_k {
+            return;
+        }
+        let retained: HashSet<AccountId> =
+            top_accounts(&self.accounts, top_k, |account| account.gas_burnt)
+                .into_iter()
+                .chain(top_accounts(&self.accounts, top_k, |account| account.bytes_written))
+                .map(|(account_id, _)| account_id.clone())
+                .collect();
+        self.accounts.retain(|account_id, _| retained.contains(account_id));
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct AccountUsage {
+    gas_burnt: Gas,
+    bytes_written: u64,
+}
+
+impl ShardHotspotsTracker {
+    pub fn new(config: ShardHotspotsConfig) -> Self {
+        Self { config, epochs: VecDeque::new() }
+    }
+
+    /// Records the usage of the new chunks applied for a block of the epoch.
+    pub fn record_apply_results(
+        &mut self,
+        epoch_id: &EpochId,
+        apply_results: &[(ShardId, Result<ShardUpdateResult, Error>)],
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        for (shard_id, apply_result) in apply_results {
+            // Old chunks don't execute anything, they only copy the previous
+            // chunk of the shard.
+            let Ok(ShardUpdateResult::NewChunk(NewChunkResult { apply_result, .. })) = apply_result
+            else {
+                continue;
+            };
+            self.record_chunk(epoch_id, *shard_id, apply_result);
+        }
+    }
+
+    fn record_chunk(&mut self, epoch_id: &EpochId, shard_id: ShardId, result: &ApplyChunkResult) {
+        let top_k = self.config.top_k;
+        let gas_burnt = result
+            .outcomes
+            .iter()
+            .map(|outcome| (&outcome.outcome.executor_id, outcome.outcome.gas_burnt));
+        // Only the final value of each key is written by the chunk.
+        let bytes_written = result.trie_changes.state_changes().iter().filter_map(|change| {
+            let account_id = change.trie_key.get_account_id()?;
+            let value = change.changes.last()?;
+            let bytes = change.trie_key.len() + value.data.as_ref().map_or(0, Vec::len);
+            Some((account_id, bytes as u64))
+        });
+        let usage = self.shard_usage(epoch_id, shard_id);
+        usage.num_chunks += 1;
+        for (account_id, gas) in gas_burnt {
+            usage.total_gas_burnt = usage.total_gas_burnt.saturating_add(gas);
+            let account = usage.accounts.entry(account_id.clone()).or_default();
+            account.gas_burnt = account.gas_burnt.saturating_add(gas);
+        }
+        for (account_id, bytes) in bytes_written {
+            usage.total_bytes_written = usage.total_bytes_written.saturating_add(bytes);
+            let account = usage.accounts.entry(account_id).or_default();
+            account.bytes_written = account.bytes_written.saturating_add(bytes);
+        }
+        usage.retain_top_accounts(top_k);
+    }
+
+    fn shard_usage(&mut self, epoch_id: &EpochId, shard_id: ShardId) -> &mut ShardUsage {
+        let index = match self.epochs.iter().position(|(id, _)| id == epoch_id) {
+            Some(index) => index,
+            None => {
+                self.epochs.push_back((*epoch_id, BTreeMap::new()));
+                while self.epochs.len() > self.config.num_epochs.max(1) {
+                    self.epochs.pop_front();
+                }
+                self.epochs.len() - 1
+            }
+        };
+        self.epochs[index].1.entry(shard_id).or_default()
+    }
+
+    /// Returns the top accounts of each shard, the latest epoch first.
+    pub fn get_hotspots(&self) -> Vec<ShardHotspotsView> {
+        let top_k = self.config.top_k;
+        let mut views = vec![];
+        for (epoch_id, shards) in self.epochs.iter().rev() {
+            for (shard_id, usage) in shards {
+                let top_by = |key: fn(&AccountUsage) -> u64| {
+                    top_accounts(&usage.accounts, top_k, key)
+                        .into_iter()
+                        .map(|(account_id, account)| AccountUsageView {
+                            account_id: account_id.clone(),
+                            gas_burnt: account.gas_burnt,
+                            bytes_written: account.bytes_written,
+                        })
+                        .collect()
+                };
+                views.push(ShardHotspotsView {
+                    epoch_id: *epoch_id,
+                    shard_id: *shard_id,
+                    num_chunks: usage.num_chunks,
+                    total_gas_burnt: usage.total_gas_burnt,
+                    total_bytes_written: usage.total_bytes_written,
+                    top_gas_burnt: top_by(|account| account.gas_burnt),
+                    top_bytes_written: top_by(|account| account.bytes_written),
+                });
+            }
+        }
+        views
+    }
+}
+
+/// Returns the `top_k` accounts with the greatest `key`.
+fn top_accounts(
+    accounts: &HashMap<AccountId, AccountUsage>,
+    top_k: usize,
+    key: fn(&AccountUsage) -> u64,
+) -> Vec<(&AccountId, &AccountUsage)> {
+    let mut accounts: Vec<_> = accounts.iter().collect();
+    // Ties are broken by the account id to keep the report stable.
+    accounts.sort_by(|(a_id, a), (b_id, b)| key(b).cmp(&key(a)).then_with(|| a_id.cmp(b_id)));
+    accounts.truncate(top_k);
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::bandwidth_scheduler::BandwidthRequests;
+    use near_primitives::chunk_apply_stats::ChunkApplyStatsV0;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::{ExecutionOutcome, ExecutionOutcomeWithId};
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::types::{RawStateChange, RawStateChangesWithTrieKey, StateChangeCause};
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_store::test_utils::TestTriesBuilder;
+    use near_store::{ShardUId, Trie, TrieChanges, WrappedTrieChanges};
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn epoch(i: u8) -> EpochId {
+        EpochId(CryptoHash::hash_bytes(&[i]))
+    }
+
+    fn data_key(name: &str) -> TrieKey {
+        TrieKey::ContractData { account_id: account(name), key: vec![] }
+    }
+
+    /// Bytes written by an account writing a value of `value_len` bytes.
+    fn bytes_written(name: &str, value_len: usize) -> u64 {
+        (data_key(name).len() + value_len) as u64
+    }
+
+    /// Returns the result of a chunk in which each account burns the gas and,
+    /// unless the length is zero, writes a value of the length.
+    fn chunk_result(usages: &[(&str, Gas, usize)]) -> ApplyChunkResult {
+        let outcomes = usages
+            .iter()
+            .map(|(name, gas_burnt, _)| ExecutionOutcomeWithId {
+                id: CryptoHash::hash_bytes(name.as_bytes()),
+                outcome: ExecutionOutcome {
+                    executor_id: account(name),
+                    gas_burnt: *gas_burnt,
+                    ..Default::default()
+                },
+            })
+            .collect();
+        let state_changes = usages
+            .iter()
+            .filter(|(_, _, value_len)| *value_len > 0)
+            .map(|(name, _, value_len)| RawStateChangesWithTrieKey {
+                trie_key: data_key(name),
+                changes: vec![RawStateChange {
+                    cause: StateChangeCause::InitialState,
+                    data: Some(vec![0; *value_len]),
+                }],
+            })
+            .collect();
+        ApplyChunkResult {
+            trie_changes: WrappedTrieChanges::new(
+                TestTriesBuilder::new().build(),
+                ShardUId::single_shard(),
+                TrieChanges::empty(Trie::EMPTY_ROOT),
+                state_changes,
+                0,
+            ),
+            new_root: Trie::EMPTY_ROOT,
+            outcomes,
+            outgoing_receipts: vec![],
+            validator_proposals: vec![],
+            total_gas_burnt: 0,
+            total_balance_burnt: 0,
+            proof: None,
+            processed_delayed_receipts: vec![],
+            processed_yield_timeouts: vec![],
+            applied_receipts_hash: CryptoHash::default(),
+            congestion_info: None,
+            bandwidth_requests: BandwidthRequests::default_for_protocol_version(PROTOCOL_VERSION),
+            bandwidth_scheduler_state_hash: CryptoHash::default(),
+            contract_updates: Default::default(),
+            stats: ChunkApplyStatsV0::dummy(),
+        }
+    }
+
+    fn record(
+        tracker: &mut ShardHotspotsTracker,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+        usages: &[(&str, Gas, usize)],
+    ) {
+        tracker.record_chunk(epoch_id, shard_id, &chunk_result(usages));
+    }
+
+    fn top_accounts(accounts: &[AccountUsageView]) -> Vec<&str> {
+        accounts.iter().map(|account| account.account_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_shard_hotspots() {
+        let config = ShardHotspotsConfig { enabled: true, top_k: 2, num_epochs: 2 };
+        let mut tracker = ShardHotspotsTracker::new(config);
+        let shard_id = ShardId::new(0);
+        record(&mut tracker, &epoch(0), shard_id, &[("alice", 10, 0)]);
+        record(&mut tracker, &epoch(1), shard_id, &[("alice", 10, 5), ("bob", 30, 1)]);
+        record(&mut tracker, &epoch(1), shard_id, &[("alice", 10, 5), ("carol", 1, 100)]);
+
+        let hotspots = tracker.get_hotspots();
+        assert_eq!(hotspots.len(), 2);
+        let latest = &hotspots[0];
+        assert_eq!(latest.epoch_id, epoch(1));
+        assert_eq!(latest.num_chunks, 2);
+        assert_eq!(latest.total_gas_burnt, 51);
+        let alice_bytes_written = 2 * bytes_written("alice", 5);
+        assert_eq!(
+            latest.total_bytes_written,
+            alice_bytes_written + bytes_written("bob", 1) + bytes_written("carol", 100)
+        );
+        assert_eq!(top_accounts(&latest.top_gas_burnt), vec!["bob", "alice"]);
+        assert_eq!(top_accounts(&latest.top_bytes_written), vec!["carol", "alice"]);
+        assert_eq!(
+            latest.top_gas_burnt[1],
+            AccountUsageView {
+                account_id: account("alice"),
+                gas_burnt: 20,
+                bytes_written: alice_bytes_written
+            }
+        );
+
+        // Only the latest epochs are kept.
+        record(&mut tracker, &epoch(2), shard_id, &[]);
+        let epoch_ids: Vec<_> =
+            tracker.get_hotspots().into_iter().map(|hotspots| hotspots.epoch_id).collect();
+        assert_eq!(epoch_ids, vec![epoch(2), epoch(1)]);
+    }
+
+    #[test]
+    fn test_shard_hotspots_keep_top_accounts() {
+        let config = ShardHotspotsConfig { enabled: true, top_k: 2, num_epochs: 1 };
+        let mut tracker = ShardHotspotsTracker::new(config);
+        let shard_id = ShardId::new(0);
+        let usages: Vec<_> = (0..10).map(|i| (format!("account{i}"), 10 + i, 0)).collect();
+        for (name, gas_burnt, value_len) in &usages {
+            record(&mut tracker, &epoch(0), shard_id, &[(name.as_str(), *gas_burnt, *value_len)]);
+        }
+        record(&mut tracker, &epoch(0), shard_id, &[("writer", 1, 100)]);
+
+        // At most twice the top accounts are kept in each shard.
+        let usage = &tracker.epochs[0].1[&shard_id];
+        assert!(usage.accounts.len() <= 4, "{} accounts are kept", usage.accounts.len());
+        let hotspots = tracker.get_hotspots();
+        assert_eq!(top_accounts(&hotspots[0].top_gas_burnt), vec!["account9", "account8"]);
+        assert_eq!(top_accounts(&hotspots[0].top_bytes_written)[0], "writer");
+        // The totals still count the accounts which are not kept.
+        assert_eq!(hotspots[0].total_gas_burnt, (10..20).sum::<Gas>() + 1);
+        assert_eq!(hotspots[0].total_bytes_written, bytes_written("writer", 100));
+    }
+}
@@ -1,13 +1,14 @@
-use near_async::futures::{DelayedActionRunner, DelayedActionRunnerExt};
-use near_async::messaging::{Actor, CanSend, Handler, HandlerWithContext, Sender};
-use near_async::time::Duration;
+use crate::flat_storage_resharder::split_shards_in_snapshot;
+use near_async::messaging::{Actor, CanSend, Handler, Sender};
 use near_async::{MultiSend, MultiSenderFrom};
+use near_chain_configs::{MutableConfigValue, ReshardingConfig};
 use near_network::types::{NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest};
 use near_performance_metrics_macros::perf;
 use near_primitives::block::Block;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::{BlockHeight, EpochHeight, ShardIndex};
+use near_store::adapter::StoreAdapter;
 use near_store::flat::FlatStorageManager;
 use near_store::{ShardTries, StateSnapshotConfig};
 use std::sync::Arc;
@@ -20,6 +21,8 @@ pub struct StateSnapshotActor {
     flat_storage_manager: FlatStorageManager,
     network_adapter: PeerManagerAdapter,
     tries: ShardTries,
+    /// Throttles the completion of a flat storage split in a snapshot like the split of the node.
+    resharding_config: MutableConfigValue<ReshardingConfig>,
 }
 
 impl Actor for StateSnapshotActor {}
@@ -29,8 +32,9 @@ impl StateSnapshotActor {
         flat_storage_manager: FlatStorageManager,
         network_adapter: PeerManagerAdapter,
         tries: ShardTries,
+        resharding_config: MutableConfigValue<ReshardingConfig>,
     ) -> Self {
-        Self { flat_storage_manager, network_adapter, tries }
+        Self { flat_storage_manager, network_adapter, tries, resharding_config }
     }
 }
 
@@ -76,37 +80,7 @@ impl StateSnapshotActor {
         self.tries.delete_state_snapshot();
     }
 
-    /// Returns true if we shouldn't yet try to create a snapshot because a flat storage resharding
-    /// is in progress.
-    fn should_wait_for_resharding_split(
-        &self,
-        min_chunk_prev_height: BlockHeight,
-        shard_indexes_and_uids: &[(ShardIndex, ShardUId)],
-    ) -> anyhow::Result<bool> {
-        let shard_uids = shard_indexes_and_uids.iter().map(|(_idx, uid)| *uid);
-        let Some(min_height) =
-            self.flat_storage_manager.resharding_catchup_height_reached(shard_uids)?
-        else {
-            // No flat storage split + catchup is in progress, ok to proceed
-            return Ok(false);
-        };
-        let Some(min_height) = min_height else {
-            // storage split + catchup is in progress and not all shards have reached the catchup phase yet. Can't proceed
-            return Ok(true);
-        };
-        // Proceed if the catchup code is already reasonably close to being finished. This is not a correctness issue,
-        // as this line of code could just be replaced with Ok(false), and things would work. But in that case, if there are for
-        // some reason lots of deltas to apply (e.g. the sync hash is 1000s of blocks past the start of the epoch because of missed
-        // chunks), then we'll duplicate a lot of work that's being done by the resharding catchup code. So we might as well just
-        // come back later after most of that work has already been done.
-        Ok(min_height + 10 < min_chunk_prev_height)
-    }
-
-    pub fn handle_create_snapshot_request(
-        &mut self,
-        msg: CreateSnapshotRequest,
-        ctx: &mut dyn DelayedActionRunner<Self>,
-    ) {
+    pub fn handle_create_snapshot_request(&mut self, msg: CreateSnapshotRequest) {
         if let StateSnapshotConfig::Disabled = self.tries.state_snapshot_config() {
             tracing::info!(target: "state_snapshot", ?msg, "Snapshots are disabled");
             return;
@@ -117,29 +91,6 @@ impl StateSnapshotActor {
                 return;
             }
         }
-        let should_wait = match self.should_wait_for_resharding_split(
-            msg.min_chunk_prev_height,
-            &msg.shard_indexes_and_uids,
-        ) {
-            Ok(s) => s,
-            Err(err) => {
-                tracing::error!(target: "state_snapshot", ?err, "State Snapshot Actor failed to check resharding status. Not making snapshot");
-                return;
-            }
-        };
-        // TODO: instead of resending the same message over and over, wait on a Condvar.
-        // This would require making testloop work with Condvars that normally are meant to be woken up by another thread
-        if should_wait {
-            tracing::debug!(target: "state_snapshot", prev_block_hash=?&msg.prev_block_hash, "Postpone CreateSnapshotRequest");
-            ctx.run_later(
-                "ReshardingActor FlatStorageSplitShard",
-                Duration::seconds(1),
-                move |act, ctx| {
-                    act.handle_create_snapshot_request(msg, ctx);
-                },
-            );
-            return;
-        }
 
         tracing::debug!(target: "state_snapshot", prev_block_hash=?&msg.prev_block_hash, "Handle CreateSnapshotRequest");
         let CreateSnapshotRequest {
@@ -151,13 +102,37 @@ impl StateSnapshotActor {
         } = msg;
 
         self.tries.delete_state_snapshot();
-        let res =
-            self.tries.create_state_snapshot(prev_block_hash, &shard_indexes_and_uids, &block);
-
-        // Unlocking flat state head can be done asynchronously in state_snapshot_actor.
-        // The next flat storage update will bring flat storage to latest head.
-        // TODO(resharding): this can actually be called sooner, just after the rocksdb checkpoint is made.
-        self.flat_storage_manager.snapshot_taken(&prev_block_hash);
+        // The snapshot doesn't wait for a flat storage split in progress. The split is completed
+        // in the snapshot from the parent and the checkpoint of the split, so the checkpoint of
+        // the DB is the only moment during which the flat heads and the resharding catchup have
+        // to stay put.
+        let mut flat_storage_unlocked = false;
+        let res = self.tries.create_state_snapshot_with(
+            prev_block_hash,
+            &shard_indexes_and_uids,
+            &block,
+            |snapshot_store| {
+                self.flat_storage_manager.snapshot_taken(&prev_block_hash);
+                flat_storage_unlocked = true;
+                let shard_uids = shard_indexes_and_uids.iter().map(|(_idx, uid)| *uid);
+                split_shards_in_snapshot(
+                    &self.tries,
+                    &snapshot_store.flat_store(),
+                    shard_uids,
+                    &self.resharding_config.get(),
+                )
+                .map_err(|err| {
+                    anyhow::anyhow!(
+                        "failed to complete the flat storage split in the state snapshot: {err}"
+                    )
+                })
+            },
+        );
+        if !flat_storage_unlocked {
+            // Unlocking flat state head can be done asynchronously in state_snapshot_actor.
+            // The next flat storage update will bring flat storage to latest head.
+            self.flat_storage_manager.snapshot_taken(&prev_block_hash);
+        }
         match res {
             Ok(res_shard_uids) => {
                 let Some(res_shard_uids) = res_shard_uids else {
@@ -186,10 +161,10 @@ impl Handler<DeleteSnapshotRequest> for StateSnapshotActor {
     }
 }
 
-impl HandlerWithContext<CreateSnapshotRequest> for StateSnapshotActor {
+impl Handler<CreateSnapshotRequest> for StateSnapshotActor {
     #[perf]
-    fn handle(&mut self, msg: CreateSnapshotRequest, ctx: &mut dyn DelayedActionRunner<Self>) {
-        self.handle_create_snapshot_request(msg, ctx)
+    fn handle(&mut self, msg: CreateSnapshotRequest) {
+        self.handle_create_snapshot_request(msg)
     }
 }
 
@@ -12,6 +12,7 @@ use near_epoch_manager::EpochManagerAdapter;
 use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, verify_path};
+use near_primitives::shard_layout::ShardLayout;
 use near_primitives::sharding::{
     ChunkHashHeight, ReceiptList, ReceiptProof, ShardChunk, ShardChunkHeader, ShardProof,
 };
@@ -20,7 +21,7 @@ use near_primitives::state_sync::{
     ReceiptProofResponse, RootProof, ShardStateSyncResponseHeader, ShardStateSyncResponseHeaderV2,
     StateHeaderKey, StatePartKey, get_num_state_parts,
 };
-use near_primitives::types::ShardId;
+use near_primitives::types::{ShardId, ShardIndex};
 use near_primitives::views::RequestedStatePartsView;
 use near_store::DBCol;
 use near_store::adapter::StoreAdapter;
@@ -91,9 +92,8 @@ impl ChainStateSyncAdapter {
         let sync_prev_block = self.chain_store.get_block(sync_block_header.prev_hash())?;
 
         let shard_layout = self.epoch_manager.get_shard_layout(sync_block_epoch_id)?;
-        let prev_epoch_id = sync_prev_block.header().epoch_id();
-        let prev_shard_layout = self.epoch_manager.get_shard_layout(&prev_epoch_id)?;
-        let prev_shard_index = prev_shard_layout.get_shard_index(shard_id)?;
+        let (_, prev_shard_index) =
+            self.get_shard_in_block_layout(shard_id, &shard_layout, sync_prev_block.hash())?;
 
         // Chunk header here is the same chunk header as at the `current` height.
         let sync_prev_hash = sync_prev_block.hash();
@@ -125,10 +125,14 @@ impl ChainStateSyncAdapter {
             .get_block(block_header.prev_hash())
         {
             Ok(prev_block) => {
+                // The chunk may be the first one of the child shard after resharding, in which
+                // case the previous chunk belongs to its parent.
+                let (prev_shard_id, prev_shard_index) =
+                    self.get_shard_in_block_layout(shard_id, &shard_layout, prev_block.hash())?;
                 let prev_chunk_header = prev_block
                     .chunks()
                     .get(prev_shard_index)
-                    .ok_or(Error::InvalidShardId(shard_id))?
+                    .ok_or(Error::InvalidShardId(prev_shard_id))?
                     .clone();
                 let (prev_chunk_headers_root, prev_chunk_proofs) = merklize(
                     &prev_block
@@ -143,7 +147,7 @@ impl ChainStateSyncAdapter {
 
                 let prev_chunk_proof = prev_chunk_proofs
                     .get(prev_shard_index)
-                    .ok_or(Error::InvalidShardId(shard_id))?
+                    .ok_or(Error::InvalidShardId(prev_shard_id))?
                     .clone();
                 let prev_chunk_height_included = prev_chunk_header.height_included();
 
@@ -179,6 +183,8 @@ impl ChainStateSyncAdapter {
             let ReceiptProofResponse(block_hash, receipt_proofs) = receipt_response;
             let block_header = self.chain_store.get_block_header(&block_hash)?.clone();
             let block = self.chain_store.get_block(&block_hash)?;
+            let block_shard_layout =
+                self.epoch_manager.get_shard_layout(block.header().epoch_id())?;
             let (block_receipts_root, block_receipts_proofs) = merklize(
                 &block
                     .chunks()
@@ -195,9 +201,9 @@ impl ChainStateSyncAdapter {
             }
             for receipt_proof in receipt_proofs.iter() {
                 let ReceiptProof(receipts, shard_proof) = receipt_proof;
-                let ShardProof { from_shard_id, to_shard_id: _, proof } = shard_proof;
-                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, receipts));
-                let from_shard_index = prev_shard_layout.get_shard_index(*from_shard_id)?;
+                let ShardProof { from_shard_id, to_shard_id, proof } = shard_proof;
+                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(*to_shard_id, receipts));
+                let from_shard_index = block_shard_layout.get_shard_index(*from_shard_id)?;
 
                 let root_proof = block.chunks()[from_shard_index].prev_outgoing_receipts_root();
                 root_proofs_cur
@@ -248,6 +254,27 @@ impl ChainStateSyncAdapter {
         Ok(ShardStateSyncResponseHeader::V2(shard_state_header))
     }
 
+    /// Returns the id and the index of `shard_id` of `shard_layout` in the
+    /// shard layout of the block `block_hash`. Only the blocks of the epoch
+    /// with `shard_layout` and of the epoch before it are expected here, so
+    /// if the layouts differ the shard is mapped to its parent.
+    fn get_shard_in_block_layout(
+        &self,
+        shard_id: ShardId,
+        shard_layout: &ShardLayout,
+        block_hash: &CryptoHash,
+    ) -> Result<(ShardId, ShardIndex), Error> {
+        let epoch_id = self.epoch_manager.get_epoch_id(block_hash)?;
+        let block_shard_layout = self.epoch_manager.get_shard_layout(&epoch_id)?;
+        let block_shard_id = if &block_shard_layout == shard_layout {
+            shard_id
+        } else {
+            shard_layout.get_parent_shard_id(shard_id)?
+        };
+        let shard_index = block_shard_layout.get_shard_index(block_shard_id)?;
+        Ok((block_shard_id, shard_index))
+    }
+
     /// Returns ShardStateSyncResponseHeader for the given epoch and shard.
     /// If the header is already available in the DB, returns the cached version and doesn't recompute it.
     /// If the header was computed then it also gets cached in the DB.
@@ -432,6 +459,8 @@ impl ChainStateSyncAdapter {
             byzantine_assert!(false);
             return Err(Error::Other("set_shard_state failed: invalid proofs".into()));
         }
+        let sync_shard_layout =
+            self.epoch_manager.get_shard_layout(sync_block_header.epoch_id())?;
         let mut hash_to_compare = sync_hash;
         for (i, receipt_response) in
             shard_state_header.incoming_receipts_proofs().iter().enumerate()
@@ -461,10 +490,17 @@ impl ChainStateSyncAdapter {
             // There were no other proofs except for included chunks.
             // According to Pigeonhole principle, it's enough to ensure all receipt_proofs are distinct
             // to prove that all receipts were received and no receipts were hidden.
+            // Receipts from the blocks before resharding were sent to the parent shard.
+            let (receipts_shard_id, _) =
+                self.get_shard_in_block_layout(shard_id, &sync_shard_layout, block_hash)?;
             let mut visited_shard_ids = HashSet::<ShardId>::new();
             for (j, receipt_proof) in receipt_proofs.iter().enumerate() {
                 let ReceiptProof(receipts, shard_proof) = receipt_proof;
-                let ShardProof { from_shard_id, to_shard_id: _, proof } = shard_proof;
+                let ShardProof { from_shard_id, to_shard_id, proof } = shard_proof;
+                if *to_shard_id != receipts_shard_id {
+                    byzantine_assert!(false);
+                    return Err(Error::Other("set_shard_state failed: invalid proofs".into()));
+                }
                 // 4d. Checking uniqueness for set of `from_shard_id`
                 match visited_shard_ids.get(from_shard_id) {
                     Some(_) => {
@@ -474,7 +510,7 @@ impl ChainStateSyncAdapter {
                     _ => visited_shard_ids.insert(*from_shard_id),
                 };
                 let RootProof(root, block_proof) = &shard_state_header.root_proofs()[i][j];
-                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(shard_id, receipts));
+                let receipts_hash = CryptoHash::hash_borsh(ReceiptList(*to_shard_id, receipts));
                 // 4e. Proving the set of receipts is the subset of outgoing_receipts of shard `shard_id`
                 if !verify_path(*root, proof, &receipts_hash) {
                     byzantine_assert!(false);
@@ -527,7 +563,8 @@ impl ChainStateSyncAdapter {
         let shard_state_header = self.get_state_header(shard_id, sync_hash)?;
         let chunk = shard_state_header.take_chunk();
         let state_root = *chunk.take_header().take_inner().prev_state_root();
-        if !self.runtime_adapter.validate_state_part(&state_root, part_id, data) {
+        let epoch_id = self.epoch_manager.get_epoch_id(&sync_hash)?;
+        if !self.runtime_adapter.validate_state_part(&state_root, part_id, data, &epoch_id) {
             byzantine_assert!(false);
             return Err(Error::Other(format!(
                 "set_state_part failed: validate_state_part failed. state_root={:?}",
@@ -5,6 +5,7 @@ use crate::chain::{
 use crate::rayon_spawner::RayonAsyncComputationSpawner;
 use crate::resharding::event_type::ReshardingEventType;
 use crate::resharding::manager::ReshardingManager;
+use crate::resharding::yield_timeouts::split_promise_yield_timeouts;
 use crate::sharding::{get_receipts_shuffle_salt, shuffle_receipt_proofs};
 use crate::stateless_validation::processing_tracker::ProcessingDoneTracker;
 use crate::store::filter_incoming_receipts_for_shard;
@@ -29,6 +30,7 @@ use near_primitives::stateless_validation::state_witness::{
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{AccountId, ProtocolVersion, ShardId, ShardIndex};
 use near_primitives::utils::compression::CompressedData;
+use near_primitives::version::ProtocolFeature;
 use near_store::flat::BlockInfo;
 use near_store::trie::AccessOptions;
 use near_store::trie::ops::resharding::RetainMode;
@@ -644,7 +646,7 @@ pub fn validate_chunk_state_witness(
                 child_shard_uid,
             ) => {
                 let old_root = *chunk_extra.state_root();
-                let partial_storage = PartialStorage { nodes: transition.base_state };
+                let partial_storage = PartialStorage { nodes: transition.base_state.clone() };
                 let parent_trie = Trie::from_recorded_storage(partial_storage, old_root, true);
 
                 // Update the congestion info based on the parent shard. It's
@@ -665,11 +667,25 @@ pub fn validate_chunk_state_witness(
                     retain_mode,
                 )?;
 
-                let new_root = parent_trie.retain_split_shard(
+                let mut new_root = parent_trie.retain_split_shard(
                     &boundary_account,
                     retain_mode,
                     AccessOptions::DEFAULT,
                 )?;
+                let child_protocol_version =
+                    epoch_manager.get_epoch_protocol_version(&child_epoch_id)?;
+                if ProtocolFeature::SplitAwarePromiseYieldTimeouts.enabled(child_protocol_version) {
+                    let partial_storage = PartialStorage { nodes: transition.base_state };
+                    let child_trie = Trie::from_recorded_storage(partial_storage, new_root, true);
+                    let changes = split_promise_yield_timeouts(
+                        &child_trie,
+                        &child_shard_layout,
+                        child_shard_uid,
+                    )?;
+                    if !changes.is_empty() {
+                        new_root = child_trie.update(changes, AccessOptions::DEFAULT)?.new_root;
+                    }
+                }
 
                 (child_shard_uid, new_root, child_congestion_info)
             }
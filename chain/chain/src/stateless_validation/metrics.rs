@@ -142,6 +142,17 @@ pub(crate) static CHUNK_STATE_WITNESS_SOURCE_RECEIPT_PROOFS_SIZE: LazyLock<Histo
         .unwrap()
     });
 
+pub(crate) static CHUNK_STATE_WITNESS_IMPLICIT_TRANSITIONS_SIZE: LazyLock<HistogramVec> =
+    LazyLock::new(|| {
+        try_create_histogram_vec(
+            "near_chunk_state_witness_implicit_transitions_size",
+            "Size of ChunkStateWitness::implicit_transitions (storage proofs of the missing chunks and of the resharding)",
+            &["shard_id"],
+            Some(buckets_for_witness_field_size()),
+        )
+        .unwrap()
+    });
+
 pub fn record_witness_size_metrics(
     decoded_size: usize,
     encoded_size: usize,
@@ -170,6 +181,9 @@ fn record_witness_size_metrics_fallible(
     CHUNK_STATE_WITNESS_SOURCE_RECEIPT_PROOFS_SIZE
         .with_label_values(&[&shard_id.as_str()])
         .observe(borsh::to_vec(&witness.source_receipt_proofs)?.len() as f64);
+    CHUNK_STATE_WITNESS_IMPLICIT_TRANSITIONS_SIZE
+        .with_label_values(&[shard_id.as_str()])
+        .observe(borsh::to_vec(&witness.implicit_transitions)?.len() as f64);
     Ok(())
 }
 
@@ -0,0 +1,119 @@
+//! This module is responsible for storing timing information about the blocks produced
+//! by this node. The timings are stored in the database and can be fetched for analysis
+//! of block production delays, e.g. through the debug RPC.
+//! Only the timings of the latest blocks are kept. When a new entry is added, entries
+//! which are too far behind are removed from the database.
+//! At the moment this module is used only for debugging purposes.
+
+use near_primitives::block_production_timings::BlockProductionTimings;
+use near_primitives::types::BlockHeight;
+use near_store::DBCol;
+
+use super::ChainStore;
+use crate::ChainStoreAccess;
+
+/// Number of heights (counting back from the latest produced block) for which the
+/// block production timings are kept in the database.
+const BLOCK_PRODUCTION_TIMINGS_MAX_HEIGHTS: BlockHeight = 100_000;
+
+impl ChainStore {
+    /// Saves timings of a block produced by this node to `DBCol::BlockProductionTimings`.
+    /// Removes the entries which are more than `BLOCK_PRODUCTION_TIMINGS_MAX_HEIGHTS`
+    /// below the height of the new entry.
+    pub fn save_block_production_timings(
+        &mut self,
+        timings: &BlockProductionTimings,
+    ) -> Result<(), std::io::Error> {
+        let store = self.store();
+        let mut store_update = store.store_update();
+
+        let cutoff = timings.height.saturating_sub(BLOCK_PRODUCTION_TIMINGS_MAX_HEIGHTS);
+        let cutoff_key = cutoff.to_be_bytes();
+        for item in store.iter_range(DBCol::BlockProductionTimings, None, Some(&cutoff_key[..])) {
+            let (key, _) = item?;
+            store_update.delete(DBCol::BlockProductionTimings, &key);
+        }
+
+        store_update.set_ser(
+            DBCol::BlockProductionTimings,
+            &timings.height.to_be_bytes(),
+            timings,
+        )?;
+        store_update.commit()
+    }
+
+    /// Fetches block production timings for heights in the range `[start_height, end_height)`,
+    /// ordered by height. Heights at which this node didn't produce a block are skipped.
+    pub fn get_block_production_timings(
+        &self,
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+    ) -> Result<Vec<BlockProductionTimings>, std::io::Error> {
+        let lower_bound = start_height.to_be_bytes();
+        let upper_bound = end_height.to_be_bytes();
+        let store = self.store();
+        let mut result = vec![];
+        for item in store.iter_range(
+            DBCol::BlockProductionTimings,
+            Some(&lower_bound[..]),
+            Some(&upper_bound[..]),
+        ) {
+            let (_, value) = item?;
+            result.push(borsh::from_slice::<BlockProductionTimings>(&value)?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::EpochId;
+    use near_store::test_utils::create_test_store;
+
+    fn timings(height: BlockHeight) -> BlockProductionTimings {
+        BlockProductionTimings {
+            height,
+            block_hash: CryptoHash::hash_bytes(&height.to_le_bytes()),
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            approvals_ready_at: Some(1),
+            chunks: vec![],
+            block_produced_at: 2,
+            block_broadcast_at: 3,
+        }
+    }
+
+    #[test]
+    fn test_save_and_get_block_production_timings() {
+        let mut chain_store = ChainStore::new(create_test_store(), true, 10);
+        for height in [1, 2, 5, 7] {
+            chain_store.save_block_production_timings(&timings(height)).unwrap();
+        }
+        let heights = |result: Vec<BlockProductionTimings>| {
+            result.into_iter().map(|t| t.height).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            heights(chain_store.get_block_production_timings(0, 100).unwrap()),
+            [1, 2, 5, 7]
+        );
+        assert_eq!(heights(chain_store.get_block_production_timings(2, 7).unwrap()), [2, 5]);
+        assert_eq!(chain_store.get_block_production_timings(7, 8).unwrap(), [timings(7)]);
+    }
+
+    #[test]
+    fn test_old_block_production_timings_removed() {
+        let mut chain_store = ChainStore::new(create_test_store(), true, 10);
+        chain_store.save_block_production_timings(&timings(1)).unwrap();
+        chain_store.save_block_production_timings(&timings(2)).unwrap();
+        chain_store
+            .save_block_production_timings(&timings(BLOCK_PRODUCTION_TIMINGS_MAX_HEIGHTS + 2))
+            .unwrap();
+        let result = chain_store.get_block_production_timings(0, BlockHeight::MAX).unwrap();
+        assert_eq!(
+            result.into_iter().map(|t| t.height).collect::<Vec<_>>(),
+            [2, BLOCK_PRODUCTION_TIMINGS_MAX_HEIGHTS + 2]
+        );
+    }
+}
@@ -51,6 +51,7 @@ use std::ops::Deref;
 use std::sync::Arc;
 use utils::check_transaction_validity_period;
 
+mod block_production_timings;
 mod latest_witnesses;
 mod merkle_proof;
 pub mod utils;
@@ -2,8 +2,8 @@ use super::ValidatorSchedule;
 use crate::BlockHeader;
 use crate::types::{
     ApplyChunkBlockContext, ApplyChunkResult, ApplyChunkShardContext,
-    PrepareTransactionsBlockContext, PrepareTransactionsChunkContext, PreparedTransactions,
-    RuntimeAdapter, RuntimeStorageConfig,
+    PrepareTransactionsBlockContext, PrepareTransactionsChunkContext, PrepareTransactionsLimit,
+    PreparedTransactions, RuntimeAdapter, RuntimeStorageConfig,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use itertools::Itertools;
@@ -514,6 +514,13 @@ impl EpochManagerAdapter for MockEpochManager {
         self.get_shard_layout(&EpochId::default()).unwrap()
     }
 
+    fn get_shard_layouts_up_to_epoch(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ShardLayout>, EpochError> {
+        Ok(vec![self.get_shard_layout(epoch_id)?])
+    }
+
     fn get_shard_config(&self, _epoch_id: &EpochId) -> Result<ShardConfig, EpochError> {
         panic!("get_shard_config not implemented for KeyValueRuntime");
     }
@@ -992,10 +999,6 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(self.tries.get_view_trie_for_shard(ShardUId::new(0, shard_id), state_root))
     }
 
-    fn get_shard_layout(&self, _protocol_version: ProtocolVersion) -> ShardLayout {
-        ShardLayout::multi_shard(self.num_shards, 0)
-    }
-
     fn validate_tx(
         &self,
         _shard_layout: &ShardLayout,
@@ -1020,17 +1023,22 @@ impl RuntimeAdapter for KeyValueRuntime {
     fn prepare_transactions(
         &self,
         _storage: RuntimeStorageConfig,
-        _chunk: PrepareTransactionsChunkContext,
+        chunk: PrepareTransactionsChunkContext,
         _prev_block: PrepareTransactionsBlockContext,
         transaction_groups: &mut dyn TransactionGroupIterator,
         _chain_validate: &dyn Fn(&SignedTransaction) -> bool,
         _time_limit: Option<Duration>,
     ) -> Result<PreparedTransactions, Error> {
         let mut res = vec![];
+        let mut limited_by = None;
         while let Some(iter) = transaction_groups.next() {
+            if chunk.max_transactions.is_some_and(|max| res.len() >= max) {
+                limited_by = Some(PrepareTransactionsLimit::TransactionCount);
+                break;
+            }
             res.push(iter.next().unwrap());
         }
-        Ok(PreparedTransactions { transactions: res, limited_by: None })
+        Ok(PreparedTransactions { transactions: res, limited_by })
     }
 
     fn apply_chunk(
@@ -1291,7 +1299,13 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(data)
     }
 
-    fn validate_state_part(&self, _state_root: &StateRoot, _part_id: PartId, _data: &[u8]) -> bool {
+    fn validate_state_part(
+        &self,
+        _state_root: &StateRoot,
+        _part_id: PartId,
+        _data: &[u8],
+        _epoch_id: &EpochId,
+    ) -> bool {
         // We do not care about deeper validation in test_utils
         true
     }
@@ -1371,6 +1385,23 @@ impl RuntimeAdapter for KeyValueRuntime {
         }
     }
 
+    fn get_gc_stop_height_for_num_epochs(
+        &self,
+        block_hash: &CryptoHash,
+        num_epochs_to_keep: u64,
+    ) -> BlockHeight {
+        if self.no_gc {
+            return 0;
+        }
+        // As in `get_gc_stop_height`, every `epoch_length` blocks count as an epoch.
+        let block_height = self
+            .get_block_header(block_hash)
+            .unwrap_or_default()
+            .map(|h| h.height())
+            .unwrap_or_default();
+        block_height.saturating_sub(num_epochs_to_keep * self.epoch_length)
+    }
+
     fn get_protocol_config(&self, _epoch_id: &EpochId) -> Result<ProtocolConfig, Error> {
         Err(Error::Other("get_protocol_config should not be used in KeyValueRuntime".into()))
     }
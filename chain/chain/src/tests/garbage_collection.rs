@@ -4,7 +4,7 @@ use rand::Rng;
 use std::sync::Arc;
 
 use crate::chain::Chain;
-use crate::garbage_collection::GCMode;
+use crate::garbage_collection::{GCMode, gc_resharding_parent_shards};
 use crate::test_utils::{
     get_chain, get_chain_with_epoch_length, get_chain_with_epoch_length_and_num_shards,
     get_chain_with_num_shards,
@@ -12,17 +12,25 @@ use crate::test_utils::{
 use crate::types::Tip;
 use crate::{ChainStoreAccess, StoreValidator};
 
-use near_chain_configs::{DEFAULT_GC_NUM_EPOCHS_TO_KEEP, GCConfig, GenesisConfig};
+use itertools::Itertools;
+use near_chain_configs::{
+    DEFAULT_GC_NUM_EPOCHS_TO_KEEP, GCCategory, GCConfig, GCRetentionConfig, GenesisConfig,
+    MIN_GC_NUM_EPOCHS_TO_KEEP,
+};
 use near_epoch_manager::EpochManagerAdapter;
 use near_primitives::block::Block;
 use near_primitives::epoch_block_info::BlockInfo;
 use near_primitives::merkle::PartialMerkleTree;
-use near_primitives::shard_layout::ShardUId;
+use near_primitives::shard_layout::{ShardLayout, ShardUId};
 use near_primitives::test_utils::{TestBlockBuilder, create_test_signer};
 use near_primitives::types::{BlockHeight, NumBlocks, StateRoot};
 use near_primitives::validator_signer::ValidatorSigner;
-use near_store::test_utils::gen_changes;
-use near_store::{DBCol, ShardTries, Trie, WrappedTrieChanges};
+use near_store::adapter::StoreAdapter;
+use near_store::flat::FlatStorageStatus;
+use near_store::test_utils::{
+    TestTriesBuilder, create_test_store, gen_changes, test_populate_trie,
+};
+use near_store::{DBCol, KeyForStateChanges, ShardTries, Trie, WrappedTrieChanges};
 
 // Build a chain of num_blocks on top of prev_block
 fn do_fork(
@@ -721,6 +729,116 @@ fn test_clear_old_data() {
     }
 }
 
+/// Test that the categories of data kept for fewer epochs than the blocks are
+/// garbage collected ahead of the blocks.
+#[test]
+fn test_clear_old_data_by_category() {
+    let max_height = 14usize;
+    let mut chain = get_chain_with_epoch_length(Clock::real(), 1);
+    let epoch_manager = chain.epoch_manager.clone();
+    let genesis = chain.get_block_by_height(0).unwrap();
+    let signer = Arc::new(create_test_signer("test1"));
+    let mut prev_block = genesis;
+    let mut blocks = vec![prev_block.clone()];
+    for i in 1..=max_height {
+        add_block(
+            &mut chain,
+            epoch_manager.as_ref(),
+            &mut prev_block,
+            &mut blocks,
+            signer.clone(),
+            i as BlockHeight,
+        );
+    }
+    let state_changes_key = |block: &Block| KeyForStateChanges::from_raw_key(block.hash(), &[1]);
+    let mut store_update = chain.chain_store().store().store_update();
+    for block in &blocks {
+        store_update.set(DBCol::StateChanges, state_changes_key(block).as_ref(), &[2]);
+    }
+    store_update.commit().unwrap();
+
+    let num_epochs_to_keep = MIN_GC_NUM_EPOCHS_TO_KEEP;
+    let retention =
+        GCRetentionConfig { num_epochs_to_keep, untracked_shards_num_epochs_to_keep: None };
+    let gc_config = GCConfig {
+        gc_blocks_limit: 100,
+        gc_retention: [(GCCategory::StateChanges, retention)].into_iter().collect(),
+        ..GCConfig::default()
+    };
+    chain.clear_data(&gc_config, None).unwrap();
+
+    let store = chain.chain_store().store();
+    for i in 0..=max_height {
+        let block_removed = i < max_height - DEFAULT_GC_NUM_EPOCHS_TO_KEEP as usize;
+        let state_changes_removed = i < max_height - num_epochs_to_keep as usize;
+        assert_eq!(chain.get_block(blocks[i].hash()).is_err(), block_removed);
+        let state_changes =
+            store.get(DBCol::StateChanges, state_changes_key(&blocks[i]).as_ref()).unwrap();
+        assert_eq!(state_changes.is_none(), state_changes_removed, "height {i}");
+    }
+}
+
+/// Test that the data of the categories is collected through the forks, both
+/// on the canonical chain and on the forks.
+#[test]
+fn test_clear_old_data_by_category_with_forks() {
+    let max_height = 14usize;
+    let num_epochs_to_keep = MIN_GC_NUM_EPOCHS_TO_KEEP;
+    let mut chain = get_chain_with_epoch_length(Clock::real(), 1);
+    let epoch_manager = chain.epoch_manager.clone();
+    let genesis = chain.get_block_by_height(0).unwrap();
+    let signer = Arc::new(create_test_signer("test1"));
+    let mut prev_block = genesis;
+    let mut blocks = vec![prev_block.clone()];
+    for i in 1..=max_height {
+        add_block(
+            &mut chain,
+            epoch_manager.as_ref(),
+            &mut prev_block,
+            &mut blocks,
+            signer.clone(),
+            i as BlockHeight,
+        );
+    }
+    // A fork below the stop height of the category, but above the one of the
+    // blocks.
+    let fork_height = max_height - num_epochs_to_keep as usize - 1;
+    let fork_block = add_fork_block(
+        &mut chain,
+        epoch_manager.as_ref(),
+        &blocks[fork_height - 2],
+        signer,
+        fork_height as BlockHeight,
+    );
+    let state_changes_key = |block: &Block| KeyForStateChanges::from_raw_key(block.hash(), &[1]);
+    let mut store_update = chain.chain_store().store().store_update();
+    for block in blocks.iter().chain([&fork_block]) {
+        store_update.set(DBCol::StateChanges, state_changes_key(block).as_ref(), &[2]);
+    }
+    store_update.commit().unwrap();
+
+    let retention =
+        GCRetentionConfig { num_epochs_to_keep, untracked_shards_num_epochs_to_keep: None };
+    let gc_config = GCConfig {
+        gc_blocks_limit: 100,
+        gc_retention: [(GCCategory::StateChanges, retention)].into_iter().collect(),
+        ..GCConfig::default()
+    };
+    chain.clear_data(&gc_config, None).unwrap();
+
+    let store = chain.chain_store().store();
+    for i in 0..=max_height {
+        let state_changes_removed = i < max_height - num_epochs_to_keep as usize;
+        let state_changes =
+            store.get(DBCol::StateChanges, state_changes_key(&blocks[i]).as_ref()).unwrap();
+        assert_eq!(state_changes.is_none(), state_changes_removed, "height {i}");
+    }
+    assert!(chain.get_block(fork_block.hash()).is_ok());
+    let state_changes =
+        store.get(DBCol::StateChanges, state_changes_key(&fork_block).as_ref()).unwrap();
+    assert!(state_changes.is_none());
+}
+
 // Adds block to the chain at given height after prev_block.
 fn add_block(
     chain: &mut Chain,
@@ -768,6 +886,45 @@ fn add_block(
     *prev_block = block.clone();
 }
 
+// Adds a block to the chain at given height after prev_block, on a fork which
+// doesn't become canonical.
+fn add_fork_block(
+    chain: &mut Chain,
+    epoch_manager: &dyn EpochManagerAdapter,
+    prev_block: &Block,
+    signer: Arc<ValidatorSigner>,
+    height: u64,
+) -> Block {
+    let next_epoch_id = epoch_manager
+        .get_next_epoch_id_from_prev_block(prev_block.hash())
+        .expect("block must exist");
+    let block = if next_epoch_id == *prev_block.header().next_epoch_id() {
+        TestBlockBuilder::new(Clock::real(), prev_block, signer).height(height).build()
+    } else {
+        let epoch_id = *prev_block.header().next_epoch_id();
+        let next_bp_hash = Chain::compute_bp_hash(epoch_manager, next_epoch_id).unwrap();
+        TestBlockBuilder::new(Clock::real(), prev_block, signer)
+            .height(height)
+            .epoch_id(epoch_id)
+            .next_epoch_id(next_epoch_id)
+            .next_bp_hash(next_bp_hash)
+            .build()
+    };
+    let mut store_update = chain.mut_chain_store().store_update();
+    store_update.save_block(block.clone());
+    store_update.inc_block_refcount(block.header().prev_hash()).unwrap();
+    store_update.save_block_header(block.header().clone()).unwrap();
+    let epoch_manager_update = epoch_manager
+        .add_validator_proposals(
+            BlockInfo::from_header(block.header(), block.header().height().saturating_sub(2)),
+            *block.header().random_value(),
+        )
+        .unwrap();
+    store_update.merge(epoch_manager_update);
+    store_update.commit().unwrap();
+    block
+}
+
 #[test]
 fn test_clear_old_data_fixed_height() {
     let mut chain = get_chain(Clock::real());
@@ -979,3 +1136,35 @@ fn test_fork_chunk_tail_updates() {
         assert_eq!(store_update.chunk_tail().unwrap(), 0);
     }
 }
+
+/// Checks that the leftovers of resharding parent shards are garbage
+/// collected, except for their State which is still used by the children.
+#[test]
+fn test_gc_resharding_parent_shards() {
+    let store = create_test_store();
+    let shard_layout = ShardLayout::multi_shard(2, 3);
+    let tries = TestTriesBuilder::new()
+        .with_store(store.clone())
+        .with_shard_layout(shard_layout.clone())
+        .with_flat_storage(true)
+        .with_in_memory_tries(true)
+        .build();
+    let parents = shard_layout.shard_uids().collect_vec();
+    for &parent in &parents {
+        test_populate_trie(&tries, &Trie::EMPTY_ROOT, parent, vec![(vec![1], Some(vec![2]))]);
+    }
+    gc_resharding_parent_shards(&store, &tries, &parents).unwrap().commit().unwrap();
+
+    let has_state = |shard_uid: ShardUId| {
+        store.iter(DBCol::State).any(|kv| kv.unwrap().0.starts_with(&shard_uid.to_bytes()))
+    };
+    for parent in parents {
+        assert!(has_state(parent));
+        assert!(tries.get_memtries(parent).is_none());
+        assert!(tries.get_flat_storage_manager().get_flat_storage_for_shard(parent).is_none());
+        assert_eq!(
+            store.flat_store().get_flat_storage_status(parent),
+            Ok(FlatStorageStatus::Empty)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::near_chain_primitives::error::BlockKnownError;
+use crate::near_chain_primitives::error::{BlockKnownError, ErrorContext};
 use crate::test_utils::{setup, wait_for_all_blocks_in_processing};
 use crate::{Block, BlockProcessingArtifact, ChainStoreAccess, Error};
 use assert_matches::assert_matches;
@@ -287,3 +287,37 @@ fn block_chunk_headers_iter() {
     assert_eq!(new_headers.len(), 8);
     assert_eq!(raw_headers.len(), old_headers.len() + new_headers.len());
 }
+
+/// Errors are matched without the context attached to them when deciding
+/// whether to remember a block as invalid.
+#[test]
+fn mark_block_invalid_with_error_context() {
+    init_test_logger();
+    let (mut chain, _, _, _) = setup(Clock::real());
+    let context = |height| ErrorContext {
+        block_hash: Some(CryptoHash::hash_bytes(&[height as u8])),
+        block_height: Some(height),
+        ..Default::default()
+    };
+
+    let signature_block = CryptoHash::hash_bytes(&[1]);
+    chain.maybe_mark_block_invalid(
+        signature_block,
+        &Error::InvalidSignature.with_context(context(1)),
+    );
+    assert!(!chain.is_block_invalid(&signature_block));
+
+    let height_block = CryptoHash::hash_bytes(&[2]);
+    chain.maybe_mark_block_invalid(
+        height_block,
+        &Error::InvalidBlockHeight(2).with_context(context(2)),
+    );
+    assert!(!chain.is_block_invalid(&height_block));
+
+    let state_root_block = CryptoHash::hash_bytes(&[3]);
+    chain.maybe_mark_block_invalid(
+        state_root_block,
+        &Error::InvalidStateRoot.with_context(context(3)),
+    );
+    assert!(chain.is_block_invalid(&state_root_block));
+}
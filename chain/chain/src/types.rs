@@ -4,6 +4,7 @@ use near_chain_configs::GenesisConfig;
 use near_chain_configs::MutableConfigValue;
 use near_chain_configs::ProtocolConfig;
 use near_chain_configs::ReshardingConfig;
+use near_chain_configs::ShardHotspotsConfig;
 use near_chain_primitives::Error;
 pub use near_epoch_manager::EpochManagerAdapter;
 use near_parameters::RuntimeConfig;
@@ -204,6 +205,12 @@ pub struct ChainConfig {
     pub background_migration_threads: usize,
     /// The resharding configuration.
     pub resharding_config: MutableConfigValue<ReshardingConfig>,
+    /// Tracking of the accounts using the most resources of each shard.
+    pub shard_hotspots: ShardHotspotsConfig,
+    /// Whether to collect the usage of the accounts in `DBCol::AccountUsageStats`.
+    pub save_account_usage_stats: bool,
+    /// Whether to keep the state updates of the applied chunks for the replicas.
+    pub serve_replica_state_updates: bool,
 }
 
 impl ChainConfig {
@@ -215,6 +222,9 @@ impl ChainConfig {
                 ReshardingConfig::default(),
                 "resharding_config",
             ),
+            shard_hotspots: ShardHotspotsConfig::default(),
+            save_account_usage_stats: false,
+            serve_replica_state_updates: false,
         }
     }
 }
@@ -338,6 +348,7 @@ pub struct PreparedTransactions {
 pub enum PrepareTransactionsLimit {
     Gas,
     Size,
+    TransactionCount,
     Time,
     ReceiptCount,
     StorageProofSize,
@@ -364,6 +375,8 @@ impl From<&Block> for PrepareTransactionsBlockContext {
 pub struct PrepareTransactionsChunkContext {
     pub shard_id: ShardId,
     pub gas_limit: Gas,
+    /// Maximum number of transactions to include in the chunk, if any.
+    pub max_transactions: Option<usize>,
 }
 
 /// Bridge between the chain and the runtime.
@@ -397,8 +410,6 @@ pub trait RuntimeAdapter: Send + Sync {
 
     fn get_flat_storage_manager(&self) -> FlatStorageManager;
 
-    fn get_shard_layout(&self, protocol_version: ProtocolVersion) -> ShardLayout;
-
     #[allow(clippy::result_large_err)]
     fn validate_tx(
         &self,
@@ -441,6 +452,15 @@ pub trait RuntimeAdapter: Send + Sync {
     /// Get the block height for which garbage collection should not go over
     fn get_gc_stop_height(&self, block_hash: &CryptoHash) -> BlockHeight;
 
+    /// Get the block height for which garbage collection of the data kept for
+    /// `num_epochs_to_keep` epochs, instead of the number of epochs of the
+    /// blocks, should not go over.
+    fn get_gc_stop_height_for_num_epochs(
+        &self,
+        block_hash: &CryptoHash,
+        num_epochs_to_keep: u64,
+    ) -> BlockHeight;
+
     /// Apply transactions and receipts to given state root and return store update
     /// and new state root.
     /// Also returns transaction result for each transaction and new receipts.
@@ -480,7 +500,15 @@ pub trait RuntimeAdapter: Send + Sync {
 
     /// Validate state part that expected to be given state root with provided data.
     /// Returns false if the resulting part doesn't match the expected one.
-    fn validate_state_part(&self, state_root: &StateRoot, part_id: PartId, data: &[u8]) -> bool;
+    /// `epoch_id` is the epoch of the sync block, it determines how the state
+    /// is split into parts.
+    fn validate_state_part(
+        &self,
+        state_root: &StateRoot,
+        part_id: PartId,
+        data: &[u8],
+        epoch_id: &EpochId,
+    ) -> bool;
 
     /// Should be executed after accepting all the parts to set up a new state.
     fn apply_state_part(
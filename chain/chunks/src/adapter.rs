@@ -1,5 +1,7 @@
 use actix::Message;
 use near_chain::types::Tip;
+#[cfg(feature = "test_features")]
+use near_primitives::types::AccountId;
 use near_primitives::{
     hash::CryptoHash,
     merkle::MerklePath,
@@ -7,6 +9,8 @@ use near_primitives::{
     sharding::{EncodedShardChunk, PartialEncodedChunk, ShardChunkHeader},
     types::EpochId,
 };
+#[cfg(feature = "test_features")]
+use std::collections::HashSet;
 
 #[derive(Message, Debug, strum::IntoStaticStr, PartialEq)]
 #[rtype(result = "()")]
@@ -66,4 +70,8 @@ pub enum ShardsManagerRequestFromClient {
         epoch_id: EpochId,
         ancestor_hash: CryptoHash,
     },
+    /// Adversarial control, stops sending the parts of the distributed chunks
+    /// to the given accounts, and answering the requests for them.
+    #[cfg(feature = "test_features")]
+    AdvWithholdChunkParts(HashSet<AccountId>),
 }
@@ -102,11 +102,12 @@ use near_chain::signature_verification::{
 use near_chain::types::EpochManagerAdapter;
 use near_chain_configs::MutableValidatorSigner;
 pub use near_chunks_primitives::Error;
+use near_epoch_manager::shard_assignment::shard_id_to_uid;
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_network::shards_manager::ShardsManagerRequestFromNetwork;
 use near_network::types::{
-    AccountIdOrPeerTrackingShard, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg,
+    AccountIdOrPeerTrackingShard, ChunkPartsSampleRequest, ChunkPartsSampleResponse,
+    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
 };
 use near_network::types::{NetworkRequests, PeerManagerMessageRequest};
 use near_performance_metrics_macros::perf;
@@ -294,6 +295,14 @@ pub struct ShardsManagerActor {
     // header_head is much newer.
     chain_header_head: Tip,
     chunk_request_retry_period: Duration,
+    /// Adversarial control, the accounts not sent the parts of the chunks
+    /// distributed by this node.
+    #[cfg(feature = "test_features")]
+    adv_withhold_chunk_parts_from: HashSet<AccountId>,
+    /// Adversarial control, the chunks whose parts were withheld, for which
+    /// the requests for parts aren't answered either.
+    #[cfg(feature = "test_features")]
+    adv_withheld_chunks: HashSet<ChunkHash>,
 }
 
 impl messaging::Actor for ShardsManagerActor {
@@ -413,6 +422,10 @@ impl ShardsManagerActor {
             chain_head: initial_chain_head,
             chain_header_head: initial_chain_header_head,
             chunk_request_retry_period,
+            #[cfg(feature = "test_features")]
+            adv_withhold_chunk_parts_from: HashSet::new(),
+            #[cfg(feature = "test_features")]
+            adv_withheld_chunks: HashSet::new(),
         }
     }
 
@@ -506,6 +519,7 @@ impl ShardsManagerActor {
         };
 
         let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(ancestor_hash)?;
+        let shard_uid = shard_id_to_uid(self.epoch_manager.as_ref(), shard_id, &epoch_id)?;
 
         for part_ord in 0..self.epoch_manager.num_total_parts() {
             let part_ord = part_ord as u64;
@@ -577,6 +591,7 @@ impl ShardsManagerActor {
                     account_id: target_account,
                     prefer_peer,
                     shard_id,
+                    epoch_shard_uid: Some((epoch_id, shard_uid)),
                     only_archival: request_from_archival,
                     min_height: height.saturating_sub(CHUNK_REQUEST_PEER_HORIZON),
                 };
@@ -918,6 +933,12 @@ impl ShardsManagerActor {
             shards = ?request.tracking_shards,
             account = ?me);
 
+        #[cfg(feature = "test_features")]
+        if self.adv_withheld_chunks.contains(&request.chunk_hash) {
+            tracing::info!(target: "adversary", chunk_hash = %request.chunk_hash.0, "ignoring the request for withheld chunk parts");
+            return;
+        }
+
         let started = self.clock.now();
         let (source, response) = self.prepare_partial_encoded_chunk_response(request);
         let elapsed = (self.clock.now().signed_duration_since(started)).as_seconds_f64();
@@ -934,6 +955,30 @@ impl ShardsManagerActor {
         ));
     }
 
+    /// Responds with the requested parts of the chunk which this node has, so that the nodes
+    /// which don't track the shard can check that the chunk is available.
+    fn process_chunk_parts_sample_request(
+        &self,
+        request: ChunkPartsSampleRequest,
+        route_back: CryptoHash,
+    ) {
+        let ChunkPartsSampleRequest { chunk_hash, part_ords } = request;
+        debug!(target: "chunks", chunk_hash = %chunk_hash.0, ?part_ords, "process_chunk_parts_sample_request");
+        let (_, response) =
+            self.prepare_partial_encoded_chunk_response_unsorted(PartialEncodedChunkRequestMsg {
+                chunk_hash,
+                part_ords,
+                tracking_shards: HashSet::new(),
+            });
+        let PartialEncodedChunkResponseMsg { chunk_hash, parts, .. } = response;
+        self.peer_manager_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::ChunkPartsSampleResponse {
+                route_back,
+                response: ChunkPartsSampleResponse { chunk_hash, parts },
+            },
+        ));
+    }
+
     /// Finds the parts and receipt proofs asked for in the request, and returns a response
     /// containing whatever was found. See comment for PartialEncodedChunkResponseSource for
     /// an explanation of that part of the return value.
@@ -2080,6 +2125,12 @@ impl ShardsManagerActor {
         .map(Arc::new)
         .collect::<Vec<_>>();
         for (to_whom, part_ords) in block_producer_mapping {
+            #[cfg(feature = "test_features")]
+            if self.adv_withhold_chunk_parts_from.contains(&to_whom) {
+                tracing::info!(target: "adversary", ?to_whom, ?part_ords, "withholding chunk parts");
+                self.adv_withheld_chunks.insert(chunk_header.chunk_hash());
+                continue;
+            }
             let part_receipt_proofs = receipt_proofs
                 .iter()
                 .filter(|proof| {
@@ -2164,6 +2215,11 @@ impl ShardsManagerActor {
             ShardsManagerRequestFromClient::CheckIncompleteChunks(prev_block_hash) => {
                 self.check_incomplete_chunks(&prev_block_hash, me)
             }
+            #[cfg(feature = "test_features")]
+            ShardsManagerRequestFromClient::AdvWithholdChunkParts(account_ids) => {
+                tracing::info!(target: "adversary", ?account_ids, "withholding chunk parts");
+                self.adv_withhold_chunk_parts_from = account_ids;
+            }
             ShardsManagerRequestFromClient::ProcessOrRequestChunk {
                 candidate_chunk,
                 request_header,
@@ -2263,6 +2319,13 @@ impl ShardsManagerActor {
                 );
                 HandleNetworkRequestResult::Ok
             }
+            ShardsManagerRequestFromNetwork::ProcessChunkPartsSampleRequest {
+                chunk_parts_sample_request,
+                route_back,
+            } => {
+                self.process_chunk_parts_sample_request(chunk_parts_sample_request, route_back);
+                HandleNetworkRequestResult::Ok
+            }
         }
     }
 }
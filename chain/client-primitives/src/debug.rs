@@ -1,11 +1,12 @@
 //! Structs in this module are used for debug purposes, and might change at any time
 //! without backwards compatibility of JSON encoding.
 use crate::types::StatusError;
+use near_primitives::block_production_timings::BlockProductionTimings;
 use near_primitives::congestion_info::CongestionInfo;
 use near_primitives::types::{EpochId, ShardId};
 use near_primitives::views::{
     CatchupStatusView, ChainProcessingInfo, EpochValidatorInfo, RequestedStatePartsView,
-    SyncStatusView,
+    ShardHotspotsView, SyncStatusView,
 };
 use near_primitives::{
     block_header::ApprovalInner,
@@ -23,6 +24,10 @@ use strum::Display;
 pub struct TrackedShardsView {
     pub shards_tracked_this_epoch: Vec<bool>,
     pub shards_tracked_next_epoch: Vec<bool>,
+    /// Status of the memtries of the shards of this epoch which are loaded
+    /// in background, e.g. `IN_PROGRESS` or `DONE`.
+    #[serde(default)]
+    pub memtrie_loading_status: Vec<Option<String>>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -123,17 +128,23 @@ pub struct BlockProduction {
     pub chunks_collection_time: Vec<ChunkCollection>,
     // Time when we produced the block, None if we didn't produce the block.
     pub block_production_time: Option<Utc>,
+    // Time when we sent the produced block out to the network, None if we didn't produce the block.
+    pub block_broadcast_time: Option<Utc>,
     // Whether this block is included on the canonical chain.
     pub block_included: bool,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct ChunkCollection {
+    // Shard of the chunk
+    pub shard_id: ShardId,
     // Chunk producer of the chunk
     pub chunk_producer: AccountId,
     // Time when the chunk was received. Note that this field can be filled even if the block doesn't
     // include a chunk for the shard, if a chunk at this height was received after the block was produced.
     pub received_time: Option<Utc>,
+    // Time when we observed enough endorsements for the chunk. Only set for included chunks.
+    pub endorsed_time: Option<Utc>,
     // Whether the block included a chunk for this shard
     pub chunk_included: bool,
 }
@@ -248,6 +259,22 @@ fn default_block_status_num_blocks() -> u64 {
     50
 }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct DebugBlockProductionTimingsQuery {
+    /// Lowest height to fetch the timings for. If not set, the timings of the latest
+    /// `num_blocks` heights (up to the chain head) are returned.
+    pub starting_height: Option<u64>,
+    /// Number of heights to fetch the timings for.
+    #[serde(default = "default_block_status_num_blocks")]
+    pub num_blocks: u64,
+}
+
+impl Default for DebugBlockProductionTimingsQuery {
+    fn default() -> Self {
+        Self { starting_height: None, num_blocks: default_block_status_num_blocks() }
+    }
+}
+
 // Different debug requests that can be sent by HTML pages, via GET.
 #[derive(Debug)]
 pub enum DebugStatus {
@@ -267,6 +294,10 @@ pub enum DebugStatus {
     ChainProcessingStatus,
     // The state parts already requested.
     RequestedStateParts,
+    // Timings of the blocks produced by this node, used to attribute block production delays.
+    BlockProductionTimings(DebugBlockProductionTimingsQuery),
+    // Accounts using the most resources of the tracked shards in the recent epochs.
+    ShardHotspots,
 }
 
 impl actix::Message for DebugStatus {
@@ -288,4 +319,8 @@ pub enum DebugStatusResponse {
     ChainProcessingStatus(ChainProcessingInfo),
     // The state parts already requested.
     RequestedStateParts(Vec<RequestedStatePartsView>),
+    // Timings of the blocks produced by this node, ordered by height.
+    BlockProductionTimings(Vec<BlockProductionTimings>),
+    // Top accounts of each shard, the latest epoch first.
+    ShardHotspots(Vec<ShardHotspotsView>),
 }
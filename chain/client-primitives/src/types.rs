@@ -3,6 +3,7 @@ use near_chain_configs::{ClientConfig, ProtocolConfigView};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
+use near_primitives::shard_layout::{ShardLineage, ShardUId};
 use near_primitives::sharding::{ChunkHash, ShardChunk};
 use near_primitives::types::{
     AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
@@ -10,9 +11,10 @@ use near_primitives::types::{
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView, GasPriceView,
-    LightClientBlockLiteView, LightClientBlockView, MaintenanceWindowsView, QueryRequest,
-    QueryResponse, ReceiptView, SplitStorageInfoView, StateChangesKindsView,
+    BlockView, ChunkEndorsementsView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
+    GasPriceView, LightClientBlockLiteView, LightClientBlockView, MaintenanceWindowsView,
+    NextEpochShardAssignmentsView, QueryRequest, QueryResponse, ReceiptExecutionProfileView,
+    ReceiptView, ShardLayoutAtBlockView, SplitStorageInfoView, StateChangesKindsView,
     StateChangesRequestView, StateChangesView, StateSyncStatusView, SyncStatusView, TxStatusView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
@@ -256,14 +258,16 @@ pub enum GetBlockError {
 
 impl From<near_chain_primitives::Error> for GetBlockError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        // The context of the error is only kept in the message of unexpected errors.
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => {
                 Self::IOError { error_message: error.to_string() }
             }
             near_chain_primitives::Error::DBNotFoundErr(error_message) => {
                 Self::UnknownBlock { error_message }
             }
-            _ => Self::Unreachable { error_message: error.to_string() },
+            _ => Self::Unreachable { error_message },
         }
     }
 }
@@ -342,7 +346,8 @@ pub enum GetChunkError {
 
 impl From<near_chain_primitives::Error> for GetChunkError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => {
                 Self::IOError { error_message: error.to_string() }
             }
@@ -355,7 +360,7 @@ impl From<near_chain_primitives::Error> for GetChunkError {
             near_chain_primitives::Error::ChunkMissing(chunk_hash) => {
                 Self::UnknownChunk { chunk_hash }
             }
-            _ => Self::Unreachable { error_message: error.to_string() },
+            _ => Self::Unreachable { error_message },
         }
     }
 }
@@ -377,6 +382,27 @@ impl Message for Query {
     type Result = Result<QueryResponse, QueryError>;
 }
 
+/// Queries client for several paths / data at the same block. All the queries
+/// are resolved against the state of that block in one go.
+#[derive(Clone, Debug)]
+pub struct BatchQuery {
+    pub block_reference: BlockReference,
+    pub requests: Vec<QueryRequest>,
+}
+
+#[derive(Debug)]
+pub struct BatchQueryResponse {
+    pub block_height: near_primitives::types::BlockHeight,
+    pub block_hash: near_primitives::hash::CryptoHash,
+    /// The result of each request, in the order of the requests.
+    pub results: Vec<Result<QueryResponse, QueryError>>,
+}
+
+impl Message for BatchQuery {
+    /// Fails as a whole only if the block can't be resolved.
+    type Result = Result<BatchQueryResponse, QueryError>;
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum QueryError {
     #[error("There are no fully synchronized blocks on the node yet")]
@@ -577,14 +603,15 @@ pub enum GetGasPriceError {
 
 impl From<near_chain_primitives::Error> for GetGasPriceError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => {
                 Self::InternalError { error_message: error.to_string() }
             }
             near_chain_primitives::Error::DBNotFoundErr(error_message) => {
                 Self::UnknownBlock { error_message }
             }
-            _ => Self::Unreachable { error_message: error.to_string() },
+            _ => Self::Unreachable { error_message },
         }
     }
 }
@@ -641,6 +668,23 @@ impl Message for TxStatus {
     type Result = Result<TxStatusView, TxStatusError>;
 }
 
+/// Sent by the client to the subscribers of `Client::block_notifications` for
+/// every block that becomes the head of its chain.
+#[derive(Clone, Debug)]
+pub struct BlockNotification {
+    pub block_hash: CryptoHash,
+    pub height: BlockHeight,
+    /// Height of the last final block as of this block.
+    pub last_final_height: BlockHeight,
+    /// Hashes and signers of the transactions in the new chunks of the block.
+    /// Only the chunks the client has are included.
+    pub transactions: Vec<(CryptoHash, AccountId)>,
+    /// Ids of the transactions and receipts executed in the block, with the
+    /// ids of the receipts produced by their execution. Only the shards the
+    /// client tracks are included.
+    pub outcomes: Vec<(CryptoHash, Vec<CryptoHash>)>,
+}
+
 #[derive(Debug)]
 pub struct GetValidatorInfo {
     pub epoch_reference: EpochReference,
@@ -670,11 +714,12 @@ pub enum GetValidatorInfoError {
 
 impl From<near_chain_primitives::Error> for GetValidatorInfoError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::DBNotFoundErr(_)
             | near_chain_primitives::Error::EpochOutOfBounds(_) => Self::UnknownEpoch,
             near_chain_primitives::Error::IOErr(s) => Self::IOError(s.to_string()),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
@@ -716,14 +761,15 @@ pub enum GetStateChangesError {
 
 impl From<near_chain_primitives::Error> for GetStateChangesError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => {
                 Self::IOError { error_message: error.to_string() }
             }
             near_chain_primitives::Error::DBNotFoundErr(error_message) => {
                 Self::UnknownBlock { error_message }
             }
-            _ => Self::Unreachable { error_message: error.to_string() },
+            _ => Self::Unreachable { error_message },
         }
     }
 }
@@ -912,9 +958,10 @@ pub enum GetReceiptError {
 
 impl From<near_chain_primitives::Error> for GetReceiptError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
@@ -948,10 +995,165 @@ pub enum GetProtocolConfigError {
 
 impl From<near_chain_primitives::Error> for GetProtocolConfigError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
             near_chain_primitives::Error::DBNotFoundErr(s) => Self::UnknownBlock(s),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
+        }
+    }
+}
+
+/// Gets the shard layout of the epoch of a block and, if `account_id` is
+/// given, the shard the account belonged to at that block.
+#[derive(Debug)]
+pub struct GetShardLayoutAtBlock {
+    pub block_reference: BlockReference,
+    pub account_id: Option<AccountId>,
+}
+
+impl Message for GetShardLayoutAtBlock {
+    type Result = Result<ShardLayoutAtBlockView, GetShardLayoutAtBlockError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetShardLayoutAtBlockError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Block has never been observed: {0}")]
+    UnknownBlock(String),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error(
+        "It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}"
+    )]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetShardLayoutAtBlockError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        let error_message = error.to_string();
+        match error.into_root() {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(s) => Self::UnknownBlock(s),
+            _ => Self::Unreachable(error_message),
+        }
+    }
+}
+
+/// Gets the shards of the older shard layouts that a shard of an epoch
+/// descends from and the shards of the newer shard layouts it is split into.
+#[derive(Debug)]
+pub struct GetShardLineage {
+    pub epoch_id: EpochId,
+    pub shard_uid: ShardUId,
+}
+
+impl Message for GetShardLineage {
+    type Result = Result<ShardLineage, GetShardLineageError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetShardLineageError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Epoch not found")]
+    UnknownEpoch,
+    #[error("Invalid shard: {0}")]
+    InvalidShard(String),
+    #[error(
+        "It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}"
+    )]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetShardLineageError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        let error_message = error.to_string();
+        match error.into_root() {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::EpochOutOfBounds(_) => Self::UnknownEpoch,
+            _ => Self::Unreachable(error_message),
+        }
+    }
+}
+
+/// Gets the chunk producer assignments of the epoch after the epoch of a
+/// block and, if `account_id` is given, the shards the account has to
+/// download the state of before that epoch starts.
+#[derive(Debug)]
+pub struct GetNextEpochShardAssignments {
+    pub block_reference: BlockReference,
+    pub account_id: Option<AccountId>,
+}
+
+impl Message for GetNextEpochShardAssignments {
+    type Result = Result<NextEpochShardAssignmentsView, GetNextEpochShardAssignmentsError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetNextEpochShardAssignmentsError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Block has never been observed: {0}")]
+    UnknownBlock(String),
+    #[error("Epoch not found")]
+    UnknownEpoch,
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error(
+        "It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}"
+    )]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetNextEpochShardAssignmentsError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        let error_message = error.to_string();
+        match error.into_root() {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(s) => Self::UnknownBlock(s),
+            near_chain_primitives::Error::EpochOutOfBounds(_) => Self::UnknownEpoch,
+            _ => Self::Unreachable(error_message),
+        }
+    }
+}
+
+/// Gets the execution profiles of the receipts of a transaction, recorded by
+/// the node with `save_receipt_execution_profiles` enabled. Only the receipts
+/// executed in the chunks applied by the node have a profile.
+#[derive(Debug)]
+pub struct GetTxExecutionProfile {
+    pub tx_hash: CryptoHash,
+}
+
+impl Message for GetTxExecutionProfile {
+    type Result = Result<Vec<ReceiptExecutionProfileView>, GetTxExecutionProfileError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetTxExecutionProfileError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Transaction or one of its receipts has never been observed: {0}")]
+    UnknownTransaction(String),
+    #[error(
+        "It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}"
+    )]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetTxExecutionProfileError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        let error_message = error.to_string();
+        match error.into_root() {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(s) => Self::UnknownTransaction(s),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
@@ -977,9 +1179,10 @@ pub enum GetMaintenanceWindowsError {
 
 impl From<near_chain_primitives::Error> for GetMaintenanceWindowsError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
@@ -1007,13 +1210,36 @@ pub enum GetClientConfigError {
 
 impl From<near_chain_primitives::Error> for GetClientConfigError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
 
+/// The endorsements of the chunks created at the height, as seen by the node.
+#[derive(Debug)]
+pub struct GetChunkEndorsements {
+    pub height: BlockHeight,
+}
+
+impl Message for GetChunkEndorsements {
+    type Result = Result<Vec<ChunkEndorsementsView>, GetChunkEndorsementsError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetChunkEndorsementsError {
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error(
+        "It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}"
+    )]
+    Unreachable(String),
+}
+
 #[derive(Debug)]
 pub struct GetSplitStorageInfo {}
 
@@ -1037,9 +1263,10 @@ pub enum GetSplitStorageInfoError {
 
 impl From<near_chain_primitives::Error> for GetSplitStorageInfoError {
     fn from(error: near_chain_primitives::Error) -> Self {
-        match error {
+        let error_message = error.to_string();
+        match error.into_root() {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
-            _ => Self::Unreachable(error.to_string()),
+            _ => Self::Unreachable(error_message),
         }
     }
 }
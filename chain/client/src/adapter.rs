@@ -29,6 +29,9 @@ pub fn client_sender_for_network(
         chunk_endorsement: client_addr.clone().into_sender(),
         epoch_sync_request: client_addr.clone().into_sender(),
         epoch_sync_response: client_addr.clone().into_sender(),
-        optimistic_block_receiver: client_addr.into_sender(),
+        optimistic_block_receiver: client_addr.clone().into_sender(),
+        chunk_parts_sample_response: client_addr.clone().into_sender(),
+        replica_state_update_request: client_addr.clone().into_sender(),
+        replica_state_update_response: client_addr.into_sender(),
     }
 }
@@ -18,12 +18,14 @@ const CHUNK_HEADERS_FOR_INCLUSION_CACHE_SIZE: usize = 2048;
 const NUM_EPOCH_CHUNK_PRODUCERS_TO_KEEP_IN_BLOCKLIST: usize = 1000;
 
 // chunk_header, received_time and chunk_producer are populated when we call mark_chunk_header_ready_for_inclusion
-// endorsements is populated later during call to prepare_chunk_headers_ready_for_inclusion
+// endorsements and endorsed_time are populated later during call to prepare_chunk_headers_ready_for_inclusion
 struct ChunkInfo {
     pub chunk_header: ShardChunkHeader,
     pub received_time: Utc,
     pub chunk_producer: AccountId,
     pub endorsements: ChunkEndorsementsState,
+    // Time when we first observed that the chunk has enough endorsements.
+    pub endorsed_time: Option<Utc>,
 }
 
 pub struct ChunkInclusionTracker {
@@ -96,6 +98,7 @@ impl ChunkInclusionTracker {
             received_time: Utc::now_utc(),
             chunk_producer,
             endorsements: ChunkEndorsementsState::default(),
+            endorsed_time: None,
         };
         self.chunk_hash_to_chunk_info.insert(chunk_hash, chunk_info);
     }
@@ -127,6 +130,9 @@ impl ChunkInclusionTracker {
             let chunk_info = self.chunk_hash_to_chunk_info.get_mut(chunk_hash).unwrap();
             chunk_info.endorsements =
                 endorsement_tracker.collect_chunk_endorsements(&chunk_info.chunk_header)?;
+            if chunk_info.endorsements.is_endorsed && chunk_info.endorsed_time.is_none() {
+                chunk_info.endorsed_time = Some(Utc::now_utc());
+            }
         }
         Ok(())
     }
@@ -222,6 +228,11 @@ impl ChunkInclusionTracker {
         Ok((chunk_info.chunk_producer.clone(), chunk_info.received_time))
     }
 
+    pub fn get_chunk_endorsed_time(&self, chunk_hash: &ChunkHash) -> Result<Option<Utc>, Error> {
+        let chunk_info = self.get_chunk_info(chunk_hash)?;
+        Ok(chunk_info.endorsed_time)
+    }
+
     pub fn record_endorsement_metrics(&self, prev_block_hash: &CryptoHash, all_shards: &[ShardId]) {
         let maybe_entry = self.prev_block_to_chunk_hash_ready.peek(prev_block_hash);
         for shard_id in all_shards {
@@ -6,13 +6,13 @@ use near_chain::types::{
     PrepareTransactionsChunkContext, PreparedTransactions, RuntimeAdapter, RuntimeStorageConfig,
 };
 use near_chain::{Block, Chain, ChainStore};
-use near_chain_configs::MutableConfigValue;
-use near_chunks::client::ShardedTransactionPool;
+use near_chain_configs::{EmptyChunkFallbackConfig, MutableConfigValue};
 use near_chunks::shards_manager_actor::ShardsManagerActor;
 use near_client_primitives::debug::ChunkProduction;
 use near_client_primitives::types::Error;
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::shard_id_to_uid;
+use near_pool::ShardedTransactionPool;
 use near_primitives::epoch_info::RngSeed;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, merklize};
@@ -42,6 +42,8 @@ pub enum AdvProduceChunksMode {
     ProduceWithoutTx,
     // Produce chunks but do not bother checking if included transactions pass validity check.
     ProduceWithoutTxValidityCheck,
+    // Produce chunks with a prev state root which doesn't match the state of the shard.
+    ProduceWithCorruptedStateRoot,
 }
 
 pub struct ProduceChunkResult {
@@ -65,6 +67,7 @@ pub struct ChunkProducer {
     /// If present, limits adding transactions from the transaction
     /// pool to the chunk by certain time.
     chunk_transactions_time_limit: MutableConfigValue<Option<Duration>>,
+    empty_chunk_fallback: EmptyChunkFallbackConfig,
     chain: ChainStoreAdapter,
     epoch_manager: Arc<dyn EpochManagerAdapter>,
     runtime_adapter: Arc<dyn RuntimeAdapter>,
@@ -80,6 +83,7 @@ impl ChunkProducer {
     pub fn new(
         clock: Clock,
         chunk_transactions_time_limit: MutableConfigValue<Option<Duration>>,
+        empty_chunk_fallback: EmptyChunkFallbackConfig,
         chain_store: &ChainStoreAdapter,
         epoch_manager: Arc<dyn EpochManagerAdapter>,
         runtime_adapter: Arc<dyn RuntimeAdapter>,
@@ -98,6 +102,7 @@ impl ChunkProducer {
             produce_invalid_tx_in_chunks: false,
             clock,
             chunk_transactions_time_limit,
+            empty_chunk_fallback,
             chain: chain_store.clone(),
             epoch_manager,
             runtime_adapter,
@@ -243,6 +248,17 @@ impl ChunkProducer {
             .get_chunk_extra(&prev_block_hash, &shard_uid)
             .map_err(|err| Error::ChunkProducer(format!("No chunk extra available: {}", err)))?;
 
+        let max_transactions = if self
+            .should_fall_back_to_empty_chunk(prev_block, epoch_id, shard_id)?
+        {
+            let max_transactions = self.empty_chunk_fallback.max_transactions;
+            debug!(target: "client", ?shard_id, next_height, max_transactions, "Previous chunks are missing, producing a smaller chunk");
+            metrics::EMPTY_CHUNK_FALLBACK_TOTAL.with_label_values(&[&shard_id.to_string()]).inc();
+            Some(max_transactions)
+        } else {
+            None
+        };
+
         let prepared_transactions = {
             #[cfg(feature = "test_features")]
             match self.adv_produce_chunks {
@@ -254,10 +270,17 @@ impl ChunkProducer {
                     prev_block,
                     chunk_extra.as_ref(),
                     chain_validate,
+                    max_transactions,
                 )?,
             }
             #[cfg(not(feature = "test_features"))]
-            self.prepare_transactions(shard_uid, prev_block, chunk_extra.as_ref(), chain_validate)?
+            self.prepare_transactions(
+                shard_uid,
+                prev_block,
+                chunk_extra.as_ref(),
+                chain_validate,
+                max_transactions,
+            )?
         };
 
         #[cfg(feature = "test_features")]
@@ -284,11 +307,20 @@ impl ChunkProducer {
         #[cfg(feature = "test_features")]
         let gas_used = if self.produce_invalid_chunks { gas_used + 1 } else { gas_used };
 
+        let prev_state_root = *chunk_extra.state_root();
+        #[cfg(feature = "test_features")]
+        let prev_state_root = match self.adv_produce_chunks {
+            Some(AdvProduceChunksMode::ProduceWithCorruptedStateRoot) => {
+                near_primitives::hash::hash(prev_state_root.as_ref())
+            }
+            _ => prev_state_root,
+        };
+
         let congestion_info = chunk_extra.congestion_info();
         let (encoded_chunk, merkle_paths, outgoing_receipts) =
             ShardsManagerActor::create_encoded_shard_chunk(
                 prev_block_hash,
-                *chunk_extra.state_root(),
+                prev_state_root,
                 *chunk_extra.outcome_root(),
                 next_height,
                 shard_id,
@@ -346,6 +378,38 @@ impl ChunkProducer {
         }))
     }
 
+    /// Returns true if the chunks of the shard are missing from the last
+    /// `missing_chunks_threshold` blocks, ending with `prev_block`. A chunk is
+    /// left out of a block when its block producer doesn't get enough
+    /// endorsements for it, most likely because the state witness didn't reach
+    /// the chunk validators. Only blocks from the epoch `epoch_id` of the new
+    /// chunk are considered.
+    fn should_fall_back_to_empty_chunk(
+        &self,
+        prev_block: &Block,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+    ) -> Result<bool, Error> {
+        let config = &self.empty_chunk_fallback;
+        if !config.enabled || config.missing_chunks_threshold == 0 {
+            return Ok(false);
+        }
+        let shard_index = self
+            .epoch_manager
+            .get_shard_layout(epoch_id)?
+            .get_shard_index(shard_id)
+            .map_err(near_chain::Error::from)?;
+        let mut block_hash = *prev_block.hash();
+        for _ in 0..config.missing_chunks_threshold {
+            let header = self.chain.get_block_header(&block_hash)?;
+            if header.epoch_id() != epoch_id || header.chunk_mask()[shard_index] {
+                return Ok(false);
+            }
+            block_hash = *header.prev_hash();
+        }
+        Ok(true)
+    }
+
     /// Prepares an ordered list of valid transactions from the pool up the limits.
     fn prepare_transactions(
         &mut self,
@@ -353,6 +417,7 @@ impl ChunkProducer {
         prev_block: &Block,
         chunk_extra: &ChunkExtra,
         chain_validate: &dyn Fn(&SignedTransaction) -> bool,
+        max_transactions: Option<usize>,
     ) -> Result<PreparedTransactions, Error> {
         let shard_id = shard_uid.shard_id();
         let mut pool_guard = self.sharded_tx_pool.lock().unwrap();
@@ -366,7 +431,11 @@ impl ChunkProducer {
             };
             self.runtime_adapter.prepare_transactions(
                 storage_config,
-                PrepareTransactionsChunkContext { shard_id, gas_limit: chunk_extra.gas_limit() },
+                PrepareTransactionsChunkContext {
+                    shard_id,
+                    gas_limit: chunk_extra.gas_limit(),
+                    max_transactions,
+                },
                 prev_block.into(),
                 &mut iter,
                 chain_validate,
@@ -6,8 +6,10 @@ use crate::chunk_distribution_network::{ChunkDistributionClient, ChunkDistributi
 use crate::chunk_inclusion_tracker::ChunkInclusionTracker;
 use crate::chunk_producer::ChunkProducer;
 use crate::client_actor::ClientSenderForClient;
+use crate::data_availability_sampler::DataAvailabilitySampler;
 use crate::debug::BlockProductionTracker;
 use crate::metrics;
+use crate::replica::{PendingReplicaBlock, Replica};
 use crate::stateless_validation::chunk_endorsement::ChunkEndorsementTracker;
 use crate::stateless_validation::chunk_validator::ChunkValidator;
 use crate::stateless_validation::partial_witness::partial_witness_actor::PartialWitnessSenderForClient;
@@ -36,17 +38,23 @@ use near_chain::{
     DoomslugThresholdMode, Provenance,
 };
 use near_chain_configs::{ClientConfig, MutableValidatorSigner, UpdatableClientConfig};
+use near_chain_primitives::error::ChainErrorContext;
 use near_chunks::adapter::ShardsManagerRequestFromClient;
 use near_chunks::logic::{decode_encoded_chunk, persist_chunk};
-use near_client_primitives::types::{Error, StateSyncStatus, SyncStatus};
+use near_client_primitives::types::{BlockNotification, Error, StateSyncStatus, SyncStatus};
 use near_epoch_manager::EpochManagerAdapter;
-use near_epoch_manager::shard_assignment::shard_id_to_uid;
 use near_epoch_manager::shard_tracker::ShardTracker;
-use near_network::types::{AccountKeys, ChainInfo, PeerManagerMessageRequest, SetChainInfo};
+use near_network::types::{
+    AccountKeys, ChainInfo, EpochTrackedShards, PeerManagerMessageRequest, SetChainInfo,
+};
 use near_network::types::{
     HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter, ReasonForBan,
+    ReplicaStateUpdateRequest, ReplicaStateUpdateResponse,
+};
+use near_o11y::events::{self, ChainEvent};
+use near_primitives::block::{
+    Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Chunks, MaybeNew, Tip,
 };
-use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::block_header::ApprovalType;
 use near_primitives::epoch_info::RngSeed;
 use near_primitives::errors::EpochError;
@@ -55,6 +63,7 @@ use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
 use near_primitives::optimistic_block::OptimisticBlock;
 use near_primitives::receipt::Receipt;
+use near_primitives::shard_layout::ShardLayout;
 use near_primitives::sharding::{
     EncodedShardChunk, PartialEncodedChunk, ShardChunk, ShardChunkHeader, StateSyncInfo,
     StateSyncInfoV1,
@@ -72,6 +81,7 @@ use std::cmp::max;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use tracing::{debug, debug_span, error, info, warn};
 
 #[cfg(feature = "test_features")]
@@ -85,6 +95,10 @@ const BLOCK_HORIZON: u64 = 500;
 /// number of blocks at the epoch start for which we will log more detailed info
 pub const EPOCH_START_INFO_BLOCKS: u64 = 500;
 
+/// Number of block notifications a subscriber can fall behind before it
+/// starts missing them.
+const BLOCK_NOTIFICATIONS_CAPACITY: usize = 64;
+
 /// Defines whether in case of adversarial block production invalid blocks can
 /// be produced.
 #[cfg(feature = "test_features")]
@@ -110,6 +124,10 @@ pub struct Client {
     /// behavior on chain.
     #[cfg(feature = "test_features")]
     pub adv_produce_blocks: Option<AdvProduceBlocksMode>,
+    /// Produce state witnesses with a post state root of the main state
+    /// transition which doesn't match the result of applying the chunk.
+    #[cfg(feature = "test_features")]
+    pub adv_produce_invalid_state_witnesses: bool,
 
     /// Fast Forward accrued delta height used to calculate fast forwarded timestamps for each block.
     #[cfg(feature = "sandbox")]
@@ -154,6 +172,9 @@ pub struct Client {
     /// Cached precomputed set of TIER1 accounts.
     /// See send_network_chain_info().
     tier1_accounts_cache: Option<(EpochId, Arc<AccountKeys>)>,
+    /// Cached shards tracked in the current and the next epoch.
+    /// See send_network_chain_info().
+    tracked_shard_uids_cache: Option<(EpochId, Vec<EpochTrackedShards>)>,
     /// Resharding sender.
     pub resharding_sender: ReshardingSender,
     /// Helper module for handling chunk production.
@@ -174,6 +195,13 @@ pub struct Client {
     upgrade_schedule: ProtocolUpgradeVotingSchedule,
     /// Produced optimistic block.
     last_optimistic_block_produced: Option<OptimisticBlock>,
+    /// Samples the chunk parts of the shards which aren't tracked.
+    pub(crate) data_availability_sampler: DataAvailabilitySampler,
+    /// Applies the state updates of the trusted block producers in replica
+    /// mode, and serves them to the replicas.
+    pub(crate) replica: Replica,
+    /// Notifies the subscribers, e.g. the RPC server, of the accepted blocks.
+    pub block_notifications: broadcast::Sender<BlockNotification>,
 }
 
 impl AsRef<Client> for Client {
@@ -232,6 +260,9 @@ impl Client {
             save_trie_changes: config.save_trie_changes,
             background_migration_threads: config.client_background_migration_threads,
             resharding_config: config.resharding_config.clone(),
+            shard_hotspots: config.shard_hotspots.clone(),
+            save_account_usage_stats: config.save_account_usage_stats,
+            serve_replica_state_updates: config.serve_replica_state_updates,
         };
         let chain = Chain::new(
             clock.clone(),
@@ -300,11 +331,15 @@ impl Client {
             config.chunk_wait_mult,
             doomslug_threshold_mode,
         );
-        let chunk_endorsement_tracker =
-            ChunkEndorsementTracker::new(epoch_manager.clone(), chain.chain_store().store());
+        let chunk_endorsement_tracker = ChunkEndorsementTracker::new(
+            clock.clone(),
+            epoch_manager.clone(),
+            chain.chain_store().store(),
+        );
         let chunk_producer = ChunkProducer::new(
             clock.clone(),
             config.produce_chunk_add_transactions_time_limit.clone(),
+            config.empty_chunk_fallback.clone(),
             &chain.chain_store(),
             epoch_manager.clone(),
             runtime_adapter.clone(),
@@ -317,11 +352,20 @@ impl Client {
             runtime_adapter.clone(),
             config.orphan_state_witness_pool_size,
             async_computation_spawner,
+            chunk_endorsement_tracker.sent_endorsements(),
         );
         let chunk_distribution_network = ChunkDistributionNetwork::from_config(&config);
+        let data_availability_sampler = DataAvailabilitySampler::new(
+            clock.clone(),
+            config.data_availability_sampling.clone(),
+            network_adapter.clone(),
+        );
+        let replica = Replica::new(clock.clone(), config.replica.clone(), network_adapter.clone());
         Ok(Self {
             #[cfg(feature = "test_features")]
             adv_produce_blocks: None,
+            #[cfg(feature = "test_features")]
+            adv_produce_invalid_state_witnesses: false,
             #[cfg(feature = "sandbox")]
             accrued_fastforward_delta: 0,
             clock: clock.clone(),
@@ -355,6 +399,7 @@ impl Client {
             last_time_head_progress_made: clock.now(),
             block_production_info: BlockProductionTracker::new(),
             tier1_accounts_cache: None,
+            tracked_shard_uids_cache: None,
             resharding_sender,
             chunk_producer,
             chunk_validator,
@@ -364,6 +409,9 @@ impl Client {
             chunk_distribution_network,
             upgrade_schedule,
             last_optimistic_block_produced: None,
+            data_availability_sampler,
+            replica,
+            block_notifications: broadcast::channel(BLOCK_NOTIFICATIONS_CAPACITY).0,
         })
     }
 
@@ -382,17 +430,28 @@ impl Client {
         Ok(())
     }
 
+    /// Returns the shard layout of the transaction pool once `block` is the
+    /// head. Each time the head changes the pool is resharded to the layout
+    /// of the block after the head, see `ReshardingManager::reshard_tx_pool`.
+    /// In particular, the transactions of the last block before a shard
+    /// layout change have to be looked up by the shard of their signer in the
+    /// new layout. On a reorg the transactions of the blocks of both forks
+    /// are looked up in the layout of the new head.
+    fn tx_pool_shard_layout(&self, block: &Block) -> Result<ShardLayout, Error> {
+        Ok(self.epoch_manager.get_shard_layout_from_prev_block(block.hash())?)
+    }
+
     pub fn remove_transactions_for_block(
         &mut self,
         me: &AccountId,
         block: &Block,
+        pool_shard_layout: &ShardLayout,
     ) -> Result<(), Error> {
         let epoch_id = self.epoch_manager.get_epoch_id(block.hash())?;
         let shard_layout = self.epoch_manager.get_shard_layout(&epoch_id)?;
         for (shard_index, chunk_header) in block.chunks().iter_deprecated().enumerate() {
             let shard_id = shard_layout.get_shard_id(shard_index);
             let shard_id = shard_id.map_err(Into::<EpochError>::into)?;
-            let shard_uid = shard_id_to_uid(self.epoch_manager.as_ref(), shard_id, &epoch_id)?;
             if block.header().height() == chunk_header.height_included() {
                 if self.shard_tracker.cares_about_shard_this_or_next_epoch(
                     Some(me),
@@ -402,9 +461,14 @@ impl Client {
                 ) {
                     // By now the chunk must be in store, otherwise the block would have been orphaned
                     let chunk = self.chain.get_chunk(&chunk_header.chunk_hash()).unwrap();
-                    let transactions = chunk.to_transactions();
+                    let transactions =
+                        chunk.to_transactions().iter().cloned().into_group_map_by(|tx| {
+                            pool_shard_layout.account_id_to_shard_uid(tx.transaction.signer_id())
+                        });
                     let mut pool_guard = self.chunk_producer.sharded_tx_pool.lock().unwrap();
-                    pool_guard.remove_transactions(shard_uid, transactions);
+                    for (pool_shard_uid, transactions) in transactions {
+                        pool_guard.remove_transactions(pool_shard_uid, &transactions);
+                    }
                 }
             }
         }
@@ -415,17 +479,16 @@ impl Client {
         &mut self,
         me: &AccountId,
         block: &Block,
+        pool_shard_layout: &ShardLayout,
     ) -> Result<(), Error> {
         let epoch_id = self.epoch_manager.get_epoch_id(block.hash())?;
         let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
-        let shard_layout =
-            self.epoch_manager.get_shard_layout_from_protocol_version(protocol_version);
+        let shard_layout = self.epoch_manager.get_shard_layout(&epoch_id)?;
         let config = self.runtime_adapter.get_runtime_config(protocol_version);
 
         for (shard_index, chunk_header) in block.chunks().iter_deprecated().enumerate() {
             let shard_id = shard_layout.get_shard_id(shard_index);
             let shard_id = shard_id.map_err(Into::<EpochError>::into)?;
-            let shard_uid = shard_id_to_uid(self.epoch_manager.as_ref(), shard_id, &epoch_id)?;
 
             if block.header().height() == chunk_header.height_included() {
                 if self.shard_tracker.cares_about_shard_this_or_next_epoch(
@@ -455,11 +518,18 @@ impl Client {
                                 }
                             }
                         })
-                        .collect::<Vec<_>>();
+                        .into_group_map_by(|validated_tx| {
+                            pool_shard_layout.account_id_to_shard_uid(validated_tx.signer_id())
+                        });
 
                     let reintroduced_count = {
                         let mut pool_guard = self.chunk_producer.sharded_tx_pool.lock().unwrap();
-                        pool_guard.reintroduce_transactions(shard_uid, validated_txs)
+                        validated_txs
+                            .into_iter()
+                            .map(|(pool_shard_uid, validated_txs)| {
+                                pool_guard.reintroduce_transactions(pool_shard_uid, validated_txs)
+                            })
+                            .sum::<usize>()
                     };
 
                     if reintroduced_count < chunk.to_transactions().len() {
@@ -789,7 +859,6 @@ impl Client {
 
         let gas_price_adjustment_rate =
             self.chain.block_economics_config.gas_price_adjustment_rate();
-        let min_gas_price = self.chain.block_economics_config.min_gas_price();
         let max_gas_price = self.chain.block_economics_config.max_gas_price();
 
         let next_bp_hash = if prev_epoch_id != epoch_id {
@@ -846,6 +915,11 @@ impl Client {
         }
 
         let prev_header = &prev_block.header();
+        let min_gas_price = self.chain.get_min_gas_price(
+            &prev_hash,
+            &Chunks::from_chunk_headers(&chunk_headers, height),
+            prev_header.next_gas_price(),
+        )?;
 
         let next_epoch_id = self.epoch_manager.get_next_epoch_id_from_prev_block(&prev_hash)?;
 
@@ -926,31 +1000,30 @@ impl Client {
     ) {
         let hash = *block.hash();
         let prev_hash = *block.header().prev_hash();
+        let height = block.header().height();
+        let epoch_id = *block.header().epoch_id();
         let _span = tracing::debug_span!(
             target: "client",
             "receive_block",
             me = ?signer.as_ref().map(|vs| vs.validator_id()),
             %prev_hash,
             %hash,
-            height = block.header().height(),
+            height,
             %peer_id,
             was_requested)
         .entered();
 
-        let res = self.receive_block_impl(
-            block,
-            peer_id,
-            was_requested,
-            apply_chunks_done_sender,
-            signer,
-        );
+        let res = self
+            .receive_block_impl(block, peer_id, was_requested, apply_chunks_done_sender, signer)
+            .with_block_context(&hash, height)
+            .with_epoch_context(&epoch_id);
         // Log the errors here. Note that the real error handling logic is already
         // done within process_block_impl, this is just for logging.
         if let Err(err) = res {
             if err.is_bad_data() {
                 warn!(target: "client", ?err, "Receive bad block");
             } else if err.is_error() {
-                if let near_chain::Error::DBNotFoundErr(msg) = &err {
+                if let near_chain::Error::DBNotFoundErr(msg) = err.root() {
                     debug_assert!(!msg.starts_with("BLOCK HEIGHT"), "{:?}", err);
                 }
                 if self.sync_handler.sync_status.is_syncing() {
@@ -1002,9 +1075,39 @@ impl Client {
             return Err(near_chain::Error::InvalidSignature);
         }
 
-        let prev_hash = *block.header().prev_hash();
         let block = block.into();
         self.verify_and_rebroadcast_block(&block, was_requested, &peer_id)?;
+        if !was_requested {
+            if self.replica.is_pending(block.hash()) {
+                return Ok(());
+            }
+            if let Some((producer, shard_ids)) =
+                self.replica.trusted_producer(self.epoch_manager.as_ref(), block.header())
+            {
+                self.replica.request_state_updates(producer, shard_ids, block, peer_id);
+                return Ok(());
+            }
+        }
+        self.start_process_received_block(
+            block,
+            peer_id,
+            was_requested,
+            apply_chunks_done_sender,
+            signer,
+        )
+    }
+
+    /// Starts processing the received block, and requests its previous block
+    /// from the peer if it's an orphan.
+    fn start_process_received_block(
+        &mut self,
+        block: MaybeValidated<Block>,
+        peer_id: PeerId,
+        was_requested: bool,
+        apply_chunks_done_sender: Option<Sender<ApplyChunksDoneMessage>>,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) -> Result<(), near_chain::Error> {
+        let prev_hash = *block.header().prev_hash();
         let provenance =
             if was_requested { near_chain::Provenance::SYNC } else { near_chain::Provenance::NONE };
         let res = self.start_process_block(block, provenance, apply_chunks_done_sender, signer);
@@ -1023,6 +1126,81 @@ impl Client {
         res
     }
 
+    /// Processes the block waiting for the received replica state updates.
+    pub fn receive_replica_state_updates(
+        &mut self,
+        response: ReplicaStateUpdateResponse,
+        apply_chunks_done_sender: Option<Sender<ApplyChunksDoneMessage>>,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) {
+        let Some(pending) =
+            self.replica.process_response(response, &mut self.chain.replica_state_updates)
+        else {
+            return;
+        };
+        self.process_pending_replica_block(pending, apply_chunks_done_sender, signer);
+    }
+
+    /// Processes the blocks which didn't get their replica state updates in
+    /// time by executing their chunks.
+    pub fn process_expired_replica_blocks(
+        &mut self,
+        apply_chunks_done_sender: Option<Sender<ApplyChunksDoneMessage>>,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) {
+        for pending in self.replica.take_expired_blocks() {
+            self.process_pending_replica_block(pending, apply_chunks_done_sender.clone(), signer);
+        }
+    }
+
+    fn process_pending_replica_block(
+        &mut self,
+        pending: PendingReplicaBlock,
+        apply_chunks_done_sender: Option<Sender<ApplyChunksDoneMessage>>,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) {
+        let PendingReplicaBlock { block, peer_id, .. } = pending;
+        let block_hash = *block.hash();
+        if let Err(err) = self.start_process_received_block(
+            block,
+            peer_id,
+            false,
+            apply_chunks_done_sender,
+            signer,
+        ) {
+            debug!(target: "client", ?err, ?block_hash, "Failed to process the block of a trusted producer");
+            self.chain.blocks_delay_tracker.mark_block_errored(&block_hash, err.to_string());
+        }
+    }
+
+    /// Answers the request of a replica for the state updates of a block.
+    pub fn process_replica_state_update_request(
+        &mut self,
+        request: ReplicaStateUpdateRequest,
+        route_back: CryptoHash,
+    ) {
+        if !self.config.serve_replica_state_updates {
+            return;
+        }
+        let Some(signer) = self.validator_signer.get() else {
+            return;
+        };
+        let is_block_processed = match self.chain.block_exists(&request.block_hash) {
+            Ok(is_block_processed) => is_block_processed,
+            Err(err) => {
+                debug!(target: "client", ?err, block_hash = ?request.block_hash, "Failed to check the requested block");
+                return;
+            }
+        };
+        self.replica.process_request(
+            request,
+            route_back,
+            is_block_processed,
+            &self.chain.replica_state_updates,
+            &signer,
+        );
+    }
+
     /// Check optimistic block and start processing if is valid.
     pub fn receive_optimistic_block(&mut self, block: OptimisticBlock, peer_id: &PeerId) {
         let _span = debug_span!(target: "client", "receive_optimistic_block").entered();
@@ -1265,6 +1443,17 @@ impl Client {
         Ok(())
     }
 
+    /// Records the time when a block produced by this node was sent out to the network
+    /// and persists the timings of its production for later analysis.
+    pub fn record_block_broadcast(&mut self, block: &Block) {
+        let Some(timings) = self.block_production_info.record_block_broadcast(block) else {
+            return;
+        };
+        if let Err(err) = self.chain.mut_chain_store().save_block_production_timings(&timings) {
+            warn!(target: "client", height = timings.height, ?err, "Failed to save block production timings");
+        }
+    }
+
     fn rebroadcast_block(&mut self, block: &Block) {
         if self.rebroadcasted_blocks.get(block.hash()).is_none() {
             self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
@@ -1489,27 +1678,16 @@ impl Client {
                 error!(target: "client", ?err, "Failed to update network chain info");
             }
 
-            // If the next block is the first of the next epoch and the shard
-            // layout is changing we need to reshard the transaction pool.
-            // TODO make sure transactions don't get added for the old shard
-            // layout after the pool resharding
-            if self.epoch_manager.is_next_block_epoch_start(&block_hash).unwrap_or(false) {
-                let new_shard_layout =
-                    self.epoch_manager.get_shard_layout_from_prev_block(&block_hash);
-                let old_shard_layout =
-                    self.epoch_manager.get_shard_layout_from_prev_block(block.header().prev_hash());
-                match (old_shard_layout, new_shard_layout) {
-                    (Ok(old_shard_layout), Ok(new_shard_layout)) => {
-                        if old_shard_layout != new_shard_layout {
-                            let mut guarded_pool =
-                                self.chunk_producer.sharded_tx_pool.lock().unwrap();
-                            guarded_pool.reshard(&old_shard_layout, &new_shard_layout);
-                        }
-                    }
-                    (old_shard_layout, new_shard_layout) => {
-                        tracing::warn!(target: "client", ?old_shard_layout, ?new_shard_layout, "failed to check if shard layout is changing");
-                    }
-                }
+            // The transaction pool follows the shard layout of the block after
+            // the head, which changes on a shard layout change or on a reorg
+            // back across one. The transactions of this block are removed from
+            // the pool afterwards, see `tx_pool_shard_layout`.
+            if let Err(err) = self
+                .chain
+                .resharding_manager
+                .reshard_tx_pool(&block, &self.chunk_producer.sharded_tx_pool)
+            {
+                tracing::warn!(target: "client", ?err, "failed to reshard the transaction pool");
             }
         }
 
@@ -1543,10 +1721,111 @@ impl Client {
             }
         }
 
+        if provenance != Provenance::SYNC && status.is_new_head() {
+            let me = signer.as_ref().map(|signer| signer.validator_id());
+            if let Err(err) = self.data_availability_sampler.sample_block(
+                self.epoch_manager.as_ref(),
+                &self.shard_tracker,
+                me,
+                &block,
+            ) {
+                tracing::debug!(target: "client", ?err, ?block_hash, "failed to sample block chunks");
+            }
+        }
+
         self.shards_manager_adapter
             .send(ShardsManagerRequestFromClient::CheckIncompleteChunks(*block.hash()));
 
+        self.emit_block_events(&block);
+        if self.config.serve_replica_state_updates {
+            if let Some(signer) = signer {
+                self.replica.on_block_accepted(
+                    block.hash(),
+                    &self.chain.replica_state_updates,
+                    signer,
+                );
+            }
+        }
+
         self.process_ready_orphan_witnesses_and_clean_old(&block, signer);
+        if status.is_new_head() {
+            self.send_block_notification(&block);
+        }
+    }
+
+    fn emit_block_events(&self, block: &Block) {
+        if !events::is_enabled() {
+            return;
+        }
+        let header = block.header();
+        events::emit(ChainEvent::BlockApplied {
+            block_hash: *header.hash(),
+            height: header.height(),
+            epoch_id: header.epoch_id().0,
+            num_new_chunks: block
+                .chunks()
+                .iter()
+                .filter(|chunk| matches!(chunk, MaybeNew::New(_)))
+                .count(),
+        });
+        if !self.epoch_manager.is_next_block_epoch_start(header.prev_hash()).unwrap_or(false) {
+            return;
+        }
+        let Ok(epoch_info) = self.epoch_manager.get_epoch_info(header.epoch_id()) else {
+            return;
+        };
+        events::emit(ChainEvent::EpochSwitch {
+            block_hash: *header.hash(),
+            height: header.height(),
+            epoch_id: header.epoch_id().0,
+            epoch_height: epoch_info.epoch_height(),
+            protocol_version: epoch_info.protocol_version(),
+        });
+    }
+
+    fn send_block_notification(&self, block: &Block) {
+        if self.block_notifications.receiver_count() == 0 {
+            return;
+        }
+        let mut transactions = vec![];
+        for chunk_header in block.chunks().iter() {
+            let MaybeNew::New(chunk_header) = chunk_header else {
+                continue;
+            };
+            let Ok(chunk) = self.chain.get_chunk(&chunk_header.chunk_hash()) else {
+                continue;
+            };
+            transactions.extend(
+                chunk
+                    .to_transactions()
+                    .iter()
+                    .map(|tx| (tx.get_hash(), tx.transaction.signer_id().clone())),
+            );
+        }
+        let outcomes = self
+            .chain
+            .chain_store()
+            .get_block_execution_outcomes(block.hash())
+            .unwrap_or_default()
+            .into_values()
+            .flatten()
+            .map(|outcome| {
+                (outcome.outcome_with_id.id, outcome.outcome_with_id.outcome.receipt_ids)
+            })
+            .collect();
+        let last_final_height = self
+            .chain
+            .get_block_header(block.header().last_final_block())
+            .map_or(self.chain.genesis().height(), |header| header.height());
+        let notification = BlockNotification {
+            block_hash: *block.hash(),
+            height: block.header().height(),
+            last_final_height,
+            transactions,
+            outcomes,
+        };
+        // The subscribers may have gone away since the check above.
+        let _ = self.block_notifications.send(notification);
     }
 
     /// Reconcile the transaction pool after processing a block.
@@ -1558,11 +1837,24 @@ impl Client {
         status: BlockStatus,
         block: &Block,
     ) -> bool {
+        let pool_shard_layout = match self.tx_pool_shard_layout(block) {
+            Ok(shard_layout) => shard_layout,
+            Err(err) => {
+                tracing::debug!(
+                    target: "client",
+                    "validator {}: getting the pool shard layout for block {:?} failed with {:?}",
+                    validator_id,
+                    block,
+                    err
+                );
+                return !matches!(status, BlockStatus::Fork);
+            }
+        };
         match status {
             BlockStatus::Next => {
                 // If this block immediately follows the current tip, remove
                 // transactions from the tx pool.
-                match self.remove_transactions_for_block(&validator_id, block) {
+                match self.remove_transactions_for_block(&validator_id, block, &pool_shard_layout) {
                     Ok(()) => (),
                     Err(err) => {
                         tracing::debug!(
@@ -1610,7 +1902,11 @@ impl Client {
 
                 for to_reintroduce_hash in to_reintroduce {
                     if let Ok(block) = self.chain.get_block(&to_reintroduce_hash) {
-                        match self.reintroduce_transactions_for_block(&validator_id, &block) {
+                        match self.reintroduce_transactions_for_block(
+                            &validator_id,
+                            &block,
+                            &pool_shard_layout,
+                        ) {
                             Ok(()) => (),
                             Err(err) => {
                                 tracing::debug!(
@@ -1627,7 +1923,11 @@ impl Client {
 
                 for to_remove_hash in to_remove {
                     if let Ok(block) = self.chain.get_block(&to_remove_hash) {
-                        match self.remove_transactions_for_block(&validator_id, &block) {
+                        match self.remove_transactions_for_block(
+                            &validator_id,
+                            &block,
+                            &pool_shard_layout,
+                        ) {
                             Ok(()) => (),
                             Err(err) => {
                                 tracing::debug!(
@@ -2289,14 +2589,36 @@ impl Client {
             vec![]
         };
         let tier1_accounts = self.get_tier1_accounts(&tip)?;
+        let tracked_shard_uids = self.get_tracked_shard_uids(&tip)?;
         let block = self.chain.get_block(&tip.last_block_hash)?;
         self.network_adapter.send(SetChainInfo(ChainInfo {
             block,
             tracked_shards,
+            tracked_shard_uids,
             tier1_accounts,
         }));
         Ok(())
     }
+
+    /// The shards tracked in the epoch of the tip and in the next epoch, which
+    /// after a resharding are the children of the shards tracked now. Cached
+    /// like get_tier1_accounts().
+    fn get_tracked_shard_uids(&mut self, tip: &Tip) -> Result<Vec<EpochTrackedShards>, Error> {
+        match &self.tracked_shard_uids_cache {
+            Some(it) if it.0 == tip.epoch_id => return Ok(it.1.clone()),
+            _ => {}
+        }
+        let signer = self.validator_signer.get();
+        let account_id = signer.as_ref().map(|signer| signer.validator_id());
+        let mut tracked_shard_uids = vec![];
+        for epoch_id in [tip.epoch_id, tip.next_epoch_id] {
+            let shard_uids =
+                self.shard_tracker.tracked_shard_uids_in_epoch(account_id, &epoch_id)?;
+            tracked_shard_uids.push(EpochTrackedShards { epoch_id, shard_uids });
+        }
+        self.tracked_shard_uids_cache = Some((tip.epoch_id, tracked_shard_uids.clone()));
+        Ok(tracked_shard_uids)
+    }
 }
 
 impl Client {
@@ -44,16 +44,18 @@ use near_chain::{
 use near_chain_configs::{ClientConfig, MutableValidatorSigner, ReshardingHandle};
 use near_chain_primitives::error::EpochErrorResultToChainError;
 use near_chunks::adapter::ShardsManagerRequestFromClient;
-use near_chunks::client::{ShardedTransactionPool, ShardsManagerResponse};
+use near_chunks::client::ShardsManagerResponse;
 use near_client_primitives::types::{
-    Error, GetClientConfig, GetClientConfigError, GetNetworkInfo, NetworkInfoResponse,
-    StateSyncStatus, Status, StatusError, StatusSyncInfo, SyncStatus,
+    BlockNotification, Error, GetChunkEndorsements, GetChunkEndorsementsError, GetClientConfig,
+    GetClientConfigError, GetNetworkInfo, NetworkInfoResponse, StateSyncStatus, Status,
+    StatusError, StatusSyncInfo, SyncStatus,
 };
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_network::client::{
     BlockApproval, BlockHeadersResponse, BlockResponse, ChunkEndorsementMessage,
-    OptimisticBlockMessage, SetNetworkInfo, StateResponseReceived,
+    ChunkPartsSampleResponseMessage, OptimisticBlockMessage, ReplicaStateUpdateRequestMessage,
+    ReplicaStateUpdateResponseMessage, SetNetworkInfo, StateResponseReceived,
 };
 use near_network::types::ReasonForBan;
 use near_network::types::{
@@ -61,6 +63,7 @@ use near_network::types::{
 };
 use near_performance_metrics;
 use near_performance_metrics_macros::perf;
+use near_pool::ShardedTransactionPool;
 use near_primitives::block::Tip;
 use near_primitives::block_header::ApprovalType;
 use near_primitives::epoch_info::RngSeed;
@@ -71,7 +74,7 @@ use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::{PROTOCOL_UPGRADE_SCHEDULE, PROTOCOL_VERSION, ProtocolFeature};
-use near_primitives::views::{DetailedDebugStatus, ValidatorInfo};
+use near_primitives::views::{ChunkEndorsementsView, DetailedDebugStatus, ValidatorInfo};
 #[cfg(feature = "test_features")]
 use near_store::DBCol;
 use near_telemetry::TelemetryEvent;
@@ -119,6 +122,7 @@ pub struct StartClientResult {
     pub client_arbiter_handle: actix::ArbiterHandle,
     pub resharding_handle: ReshardingHandle,
     pub tx_pool: Arc<Mutex<ShardedTransactionPool>>,
+    pub block_notifications: broadcast::Sender<BlockNotification>,
 }
 
 /// Starts client in a separate Arbiter (thread).
@@ -192,6 +196,7 @@ pub fn start_client(
     )
     .unwrap();
     let tx_pool = client_actor_inner.client.chunk_producer.sharded_tx_pool.clone();
+    let block_notifications = client_actor_inner.client.block_notifications.clone();
 
     let client_addr = ClientActor::start_in_arbiter(&client_arbiter_handle, move |_| {
         ActixWrapper::new(client_actor_inner)
@@ -208,6 +213,7 @@ pub fn start_client(
         client_arbiter_handle,
         resharding_handle,
         tx_pool,
+        block_notifications,
     }
 }
 
@@ -408,6 +414,9 @@ pub enum NetworkAdversarialMessage {
     AdvProduceBlocks(u64, bool),
     AdvProduceChunks(AdvProduceChunksMode),
     AdvInsertInvalidTransactions(bool),
+    /// Stop sending the parts of the produced chunks to the given validators.
+    AdvWithholdChunkParts(std::collections::HashSet<AccountId>),
+    AdvProduceInvalidStateWitnesses(bool),
     AdvSwitchToHeight(u64),
     AdvDisableHeaderSync,
     AdvDisableDoomslug,
@@ -492,6 +501,17 @@ impl Handler<NetworkAdversarialMessage> for ClientActorInner {
                 self.client.chunk_producer.produce_invalid_tx_in_chunks = on;
                 None
             }
+            NetworkAdversarialMessage::AdvWithholdChunkParts(account_ids) => {
+                self.client
+                    .shards_manager_adapter
+                    .send(ShardsManagerRequestFromClient::AdvWithholdChunkParts(account_ids));
+                None
+            }
+            NetworkAdversarialMessage::AdvProduceInvalidStateWitnesses(on) => {
+                info!(target: "adversary", on, "invalid state witnesses");
+                self.client.adv_produce_invalid_state_witnesses = on;
+                None
+            }
         }
     }
 }
@@ -505,6 +525,30 @@ impl Handler<OptimisticBlockMessage> for ClientActorInner {
     }
 }
 
+impl Handler<ChunkPartsSampleResponseMessage> for ClientActorInner {
+    fn handle(&mut self, msg: ChunkPartsSampleResponseMessage) {
+        self.client.data_availability_sampler.process_response(msg.0);
+    }
+}
+
+impl Handler<ReplicaStateUpdateRequestMessage> for ClientActorInner {
+    fn handle(&mut self, msg: ReplicaStateUpdateRequestMessage) {
+        let ReplicaStateUpdateRequestMessage { request, route_back } = msg;
+        self.client.process_replica_state_update_request(request, route_back);
+    }
+}
+
+impl Handler<ReplicaStateUpdateResponseMessage> for ClientActorInner {
+    fn handle(&mut self, msg: ReplicaStateUpdateResponseMessage) {
+        let signer = self.client.validator_signer.get();
+        self.client.receive_replica_state_updates(
+            msg.0,
+            Some(self.client.myself_sender.apply_chunks_done.clone()),
+            &signer,
+        );
+    }
+}
+
 impl Handler<BlockResponse> for ClientActorInner {
     fn handle(&mut self, msg: BlockResponse) {
         let BlockResponse { block, peer_id, was_requested } = msg;
@@ -826,6 +870,10 @@ impl Handler<ApplyChunksDoneMessage> for ClientActorInner {
     fn handle(&mut self, _msg: ApplyChunksDoneMessage) {
         let validator_signer = self.client.validator_signer.get();
         self.try_process_unfinished_blocks(&validator_signer);
+        self.client.process_expired_replica_blocks(
+            Some(self.client.myself_sender.apply_chunks_done.clone()),
+            &validator_signer,
+        );
     }
 }
 
@@ -1198,6 +1246,10 @@ impl ClientActorInner {
 
         let validator_signer = self.client.validator_signer.get();
         self.try_process_unfinished_blocks(&validator_signer);
+        self.client.process_expired_replica_blocks(
+            Some(self.client.myself_sender.apply_chunks_done.clone()),
+            &validator_signer,
+        );
 
         let mut delay = near_async::time::Duration::seconds(1);
         let now = self.clock.now_utc();
@@ -1331,6 +1383,7 @@ impl ClientActorInner {
         self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
             NetworkRequests::Block { block: block.clone() },
         ));
+        self.client.record_block_broadcast(&block);
         // We’ve produced the block so that counts as validated block.
         let block = MaybeValidated::from_validated(block);
         let res = self.client.start_process_block(
@@ -1925,6 +1978,17 @@ impl Handler<GetClientConfig> for ClientActorInner {
     }
 }
 
+impl Handler<GetChunkEndorsements> for ClientActorInner {
+    fn handle(
+        &mut self,
+        msg: GetChunkEndorsements,
+    ) -> Result<Vec<ChunkEndorsementsView>, GetChunkEndorsementsError> {
+        tracing::debug!(target: "client", ?msg);
+
+        Ok(self.client.chunk_endorsement_tracker.chunk_endorsements_view(msg.height))
+    }
+}
+
 impl Handler<ChunkStateWitnessMessage> for ClientActorInner {
     #[perf]
     fn handle(&mut self, msg: ChunkStateWitnessMessage) {
@@ -0,0 +1,312 @@
+//! Experimental data availability sampling of the chunks of the shards the
+//! node doesn't track.
+//!
+//! For every new chunk in a block, a few random parts are requested from the
+//! validators owning them in the epoch of the block, and each received part is
+//! checked against the `encoded_merkle_root` of the chunk header. A chunk is
+//! considered available once all of its sampled parts arrived and are valid.
+//! This way a node can estimate whether the chunks are available without
+//! downloading them in full. The outcome is only reported in metrics.
+use std::collections::{HashMap, HashSet};
+
+use near_async::messaging::CanSend;
+use near_async::time::{Clock, Instant};
+use near_chain_configs::DataAvailabilitySamplingConfig;
+use near_client_primitives::types::Error;
+use near_epoch_manager::EpochManagerAdapter;
+use near_epoch_manager::shard_tracker::ShardTracker;
+use near_network::types::{
+    ChunkPartsSampleRequest, ChunkPartsSampleResponse, NetworkRequests, PeerManagerAdapter,
+    PeerManagerMessageRequest,
+};
+use near_primitives::block::{Block, MaybeNew};
+use near_primitives::hash::CryptoHash;
+use near_primitives::merkle::verify_path;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{AccountId, ShardId};
+
+use crate::metrics;
+
+struct PendingChunkSamples {
+    shard_id: ShardId,
+    encoded_merkle_root: CryptoHash,
+    /// Sampled parts which didn't arrive yet.
+    part_ords: HashSet<u64>,
+    requested_at: Instant,
+    /// Whether any of the received parts didn't match the chunk header.
+    has_invalid_parts: bool,
+}
+
+pub(crate) struct DataAvailabilitySampler {
+    clock: Clock,
+    config: DataAvailabilitySamplingConfig,
+    network_adapter: PeerManagerAdapter,
+    pending: HashMap<ChunkHash, PendingChunkSamples>,
+}
+
+impl DataAvailabilitySampler {
+    pub fn new(
+        clock: Clock,
+        config: DataAvailabilitySamplingConfig,
+        network_adapter: PeerManagerAdapter,
+    ) -> Self {
+        Self { clock, config, network_adapter, pending: HashMap::new() }
+    }
+
+    /// Requests random parts of the new chunks of the block, except for the
+    /// shards the node tracks, since it has these chunks in full anyway.
+    pub fn sample_block(
+        &mut self,
+        epoch_manager: &dyn EpochManagerAdapter,
+        shard_tracker: &ShardTracker,
+        me: Option<&AccountId>,
+        block: &Block,
+    ) -> Result<(), Error> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        self.expire_samples();
+
+        let epoch_id = block.header().epoch_id();
+        let prev_hash = block.header().prev_hash();
+        let num_total_parts = epoch_manager.num_total_parts();
+        let num_samples = self.config.samples_per_chunk.min(num_total_parts);
+        for chunk_header in block.chunks().iter() {
+            let MaybeNew::New(chunk_header) = chunk_header else {
+                continue;
+            };
+            let shard_id = chunk_header.shard_id();
+            let chunk_hash = chunk_header.chunk_hash();
+            if shard_tracker.cares_about_shard(me, prev_hash, shard_id, true)
+                || self.pending.contains_key(&chunk_hash)
+            {
+                continue;
+            }
+
+            let mut part_ords_by_owner = HashMap::<AccountId, Vec<u64>>::new();
+            let sampled =
+                rand::seq::index::sample(&mut rand::thread_rng(), num_total_parts, num_samples);
+            for part_ord in sampled {
+                let part_ord = part_ord as u64;
+                let owner = epoch_manager.get_part_owner(epoch_id, part_ord)?;
+                if Some(&owner) == me {
+                    continue;
+                }
+                part_ords_by_owner.entry(owner).or_default().push(part_ord);
+            }
+            self.request_samples(
+                chunk_hash,
+                shard_id,
+                chunk_header.encoded_merkle_root(),
+                part_ords_by_owner,
+            );
+        }
+        Ok(())
+    }
+
+    fn request_samples(
+        &mut self,
+        chunk_hash: ChunkHash,
+        shard_id: ShardId,
+        encoded_merkle_root: CryptoHash,
+        part_ords_by_owner: HashMap<AccountId, Vec<u64>>,
+    ) {
+        if part_ords_by_owner.is_empty() {
+            return;
+        }
+        let part_ords = part_ords_by_owner.values().flatten().copied().collect();
+        self.pending.insert(
+            chunk_hash.clone(),
+            PendingChunkSamples {
+                shard_id,
+                encoded_merkle_root,
+                part_ords,
+                requested_at: self.clock.now(),
+                has_invalid_parts: false,
+            },
+        );
+        for (target, part_ords) in part_ords_by_owner {
+            tracing::trace!(target: "client", ?chunk_hash, ?target, ?part_ords, "Requesting chunk parts samples");
+            let request = ChunkPartsSampleRequest { chunk_hash: chunk_hash.clone(), part_ords };
+            self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::ChunkPartsSampleRequest { target, request },
+            ));
+        }
+    }
+
+    /// Checks the received parts against the chunk header. The parts which
+    /// weren't requested are ignored.
+    pub fn process_response(&mut self, response: ChunkPartsSampleResponse) {
+        let Some(pending) = self.pending.get_mut(&response.chunk_hash) else {
+            return;
+        };
+        let shard_label = pending.shard_id.to_string();
+        for part in response.parts {
+            if !pending.part_ords.remove(&part.part_ord) {
+                continue;
+            }
+            let result = if verify_path(pending.encoded_merkle_root, &part.merkle_proof, &part.part)
+            {
+                "available"
+            } else {
+                tracing::debug!(target: "client", chunk_hash = ?response.chunk_hash, part_ord = part.part_ord, "Sampled chunk part doesn't match the chunk header");
+                pending.has_invalid_parts = true;
+                "invalid"
+            };
+            metrics::DATA_AVAILABILITY_SAMPLES.with_label_values(&[&shard_label, result]).inc();
+        }
+        if pending.part_ords.is_empty() {
+            let pending = self.pending.remove(&response.chunk_hash).unwrap();
+            if !pending.has_invalid_parts {
+                metrics::DATA_AVAILABILITY_SAMPLE_LATENCY
+                    .with_label_values(&[&shard_label])
+                    .observe((self.clock.now() - pending.requested_at).as_seconds_f64());
+            }
+            finish_chunk(&response.chunk_hash, pending);
+        }
+    }
+
+    /// Counts the parts which didn't arrive within `sample_timeout` as
+    /// unavailable.
+    pub fn expire_samples(&mut self) {
+        let now = self.clock.now();
+        let sample_timeout = self.config.sample_timeout;
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.requested_at + sample_timeout <= now)
+            .map(|(chunk_hash, _)| chunk_hash.clone())
+            .collect::<Vec<_>>();
+        for chunk_hash in expired {
+            let pending = self.pending.remove(&chunk_hash).unwrap();
+            metrics::DATA_AVAILABILITY_SAMPLES
+                .with_label_values(&[&pending.shard_id.to_string(), "timeout"])
+                .inc_by(pending.part_ords.len() as u64);
+            finish_chunk(&chunk_hash, pending);
+        }
+    }
+}
+
+fn finish_chunk(chunk_hash: &ChunkHash, pending: PendingChunkSamples) {
+    let is_available = pending.part_ords.is_empty() && !pending.has_invalid_parts;
+    if !is_available {
+        tracing::debug!(target: "client", ?chunk_hash, shard_id = %pending.shard_id, missing_parts = ?pending.part_ords, has_invalid_parts = pending.has_invalid_parts, "Sampled chunk is unavailable");
+    }
+    metrics::DATA_AVAILABILITY_SAMPLED_CHUNKS
+        .with_label_values(&[
+            &pending.shard_id.to_string(),
+            if is_available { "available" } else { "unavailable" },
+        ])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_async::messaging::IntoMultiSender;
+    use near_async::time::{Duration, FakeClock, Utc};
+    use near_network::test_utils::MockPeerManagerAdapter;
+    use near_primitives::merkle::merklize;
+    use near_primitives::sharding::PartialEncodedChunkPart;
+    use std::sync::Arc;
+
+    struct TestChunk {
+        chunk_hash: ChunkHash,
+        root: CryptoHash,
+        parts: Vec<PartialEncodedChunkPart>,
+    }
+
+    fn test_chunk() -> TestChunk {
+        let data = (0..4u8).map(|ord| vec![ord; 10].into_boxed_slice()).collect::<Vec<_>>();
+        let (root, proofs) = merklize(&data);
+        let parts = data
+            .into_iter()
+            .zip(proofs)
+            .enumerate()
+            .map(|(part_ord, (part, merkle_proof))| PartialEncodedChunkPart {
+                part_ord: part_ord as u64,
+                part,
+                merkle_proof,
+            })
+            .collect();
+        TestChunk { chunk_hash: ChunkHash(CryptoHash::hash_bytes(b"chunk")), root, parts }
+    }
+
+    fn sampler(clock: Clock) -> (DataAvailabilitySampler, Arc<MockPeerManagerAdapter>) {
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let config = DataAvailabilitySamplingConfig {
+            enabled: true,
+            samples_per_chunk: 2,
+            sample_timeout: Duration::seconds(1),
+        };
+        (
+            DataAvailabilitySampler::new(clock, config, network_adapter.as_multi_sender()),
+            network_adapter,
+        )
+    }
+
+    fn request(sampler: &mut DataAvailabilitySampler, chunk: &TestChunk, part_ords: Vec<u64>) {
+        let part_ords_by_owner = HashMap::from([("owner".parse().unwrap(), part_ords)]);
+        sampler.request_samples(
+            chunk.chunk_hash.clone(),
+            ShardId::new(0),
+            chunk.root,
+            part_ords_by_owner,
+        );
+    }
+
+    #[test]
+    fn test_valid_parts() {
+        let (mut sampler, network_adapter) = sampler(Clock::real());
+        let chunk = test_chunk();
+        request(&mut sampler, &chunk, vec![1, 3]);
+        assert!(network_adapter.pop().is_some());
+
+        sampler.process_response(ChunkPartsSampleResponse {
+            chunk_hash: chunk.chunk_hash.clone(),
+            parts: vec![chunk.parts[1].clone()],
+        });
+        assert!(sampler.pending.contains_key(&chunk.chunk_hash));
+        // Parts which weren't sampled are ignored.
+        sampler.process_response(ChunkPartsSampleResponse {
+            chunk_hash: chunk.chunk_hash.clone(),
+            parts: vec![chunk.parts[2].clone(), chunk.parts[3].clone()],
+        });
+        assert!(sampler.pending.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_part() {
+        let (mut sampler, _) = sampler(Clock::real());
+        let chunk = test_chunk();
+        request(&mut sampler, &chunk, vec![0]);
+        let mut part = chunk.parts[0].clone();
+        part.part = vec![42; 10].into_boxed_slice();
+        sampler.process_response(ChunkPartsSampleResponse {
+            chunk_hash: chunk.chunk_hash.clone(),
+            parts: vec![part],
+        });
+        assert!(sampler.pending.is_empty());
+    }
+
+    #[test]
+    fn test_expired_samples() {
+        let clock = FakeClock::new(Utc::UNIX_EPOCH);
+        let (mut sampler, _) = sampler(clock.clock());
+        let chunk = test_chunk();
+        request(&mut sampler, &chunk, vec![0, 1]);
+        clock.advance(Duration::milliseconds(500));
+        sampler.expire_samples();
+        assert!(sampler.pending.contains_key(&chunk.chunk_hash));
+        clock.advance(Duration::milliseconds(500));
+        sampler.expire_samples();
+        assert!(sampler.pending.is_empty());
+
+        // The parts arriving after the timeout are ignored.
+        sampler.process_response(ChunkPartsSampleResponse {
+            chunk_hash: chunk.chunk_hash.clone(),
+            parts: chunk.parts.clone(),
+        });
+        assert!(sampler.pending.is_empty());
+    }
+}
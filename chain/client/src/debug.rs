@@ -4,13 +4,13 @@ use crate::chunk_inclusion_tracker::ChunkInclusionTracker;
 use crate::client_actor::ClientActorInner;
 use itertools::Itertools;
 use near_async::messaging::Handler;
-use near_async::time::{Clock, Instant};
+use near_async::time::{Clock, Instant, Utc};
 use near_chain::crypto_hash_timer::CryptoHashTimer;
 use near_chain::{Block, Chain, ChainStoreAccess, near_chain_primitives};
 use near_client_primitives::debug::{
-    ApprovalAtHeightStatus, BlockProduction, ChunkCollection, DebugBlockStatusData,
-    DebugBlockStatusQuery, DebugBlocksStartingMode, DebugStatus, DebugStatusResponse,
-    MissedHeightInfo, ProductionAtHeight, ValidatorStatus,
+    ApprovalAtHeightStatus, BlockProduction, ChunkCollection, DebugBlockProductionTimingsQuery,
+    DebugBlockStatusData, DebugBlockStatusQuery, DebugBlocksStartingMode, DebugStatus,
+    DebugStatusResponse, MissedHeightInfo, ProductionAtHeight, ValidatorStatus,
 };
 use near_client_primitives::types::Error;
 use near_client_primitives::{
@@ -20,6 +20,7 @@ use near_client_primitives::{
 use near_epoch_manager::EpochManagerAdapter;
 use near_o11y::log_assert;
 use near_performance_metrics_macros::perf;
+use near_primitives::block_production_timings::{BlockProductionTimings, ChunkProductionTimings};
 use near_primitives::congestion_info::CongestionControl;
 use near_primitives::errors::EpochError;
 use near_primitives::state_sync::get_num_state_parts;
@@ -91,6 +92,7 @@ impl BlockProductionTracker {
                 approvals,
                 chunks_collection_time: vec![],
                 block_production_time: None,
+                block_broadcast_time: None,
                 block_included: false,
             },
         ) {
@@ -114,6 +116,44 @@ impl BlockProductionTracker {
         }
     }
 
+    /// Record the time when the produced block was sent out to the network.
+    /// Returns the timings of the block production, to be persisted in the database.
+    pub(crate) fn record_block_broadcast(
+        &mut self,
+        block: &Block,
+    ) -> Option<BlockProductionTimings> {
+        let block_production = self.0.get_mut(&block.header().height())?;
+        let block_production_time = block_production.block_production_time?;
+        let block_broadcast_time = Clock::real().now_utc();
+        block_production.block_broadcast_time = Some(block_broadcast_time);
+
+        let to_nanos = |time: Utc| time.unix_timestamp_nanos() as u64;
+        let chunks = block_production
+            .chunks_collection_time
+            .iter()
+            .map(|chunk_collection| ChunkProductionTimings {
+                shard_id: chunk_collection.shard_id,
+                chunk_producer: chunk_collection.chunk_producer.clone(),
+                chunk_received_at: chunk_collection
+                    .received_time
+                    .filter(|time| *time <= block_production_time)
+                    .map(to_nanos),
+                endorsements_ready_at: chunk_collection.endorsed_time.map(to_nanos),
+                chunk_included: chunk_collection.chunk_included,
+            })
+            .collect();
+        Some(BlockProductionTimings {
+            height: block.header().height(),
+            block_hash: *block.hash(),
+            prev_block_hash: *block.header().prev_hash(),
+            epoch_id: *block.header().epoch_id(),
+            approvals_ready_at: block_production.approvals.ready_at.map(to_nanos),
+            chunks,
+            block_produced_at: to_nanos(block_production_time),
+            block_broadcast_at: to_nanos(block_broadcast_time),
+        })
+    }
+
     /// Record chunk collected after a block is produced if the block didn't include a chunk for the shard.
     /// If called before the block was produced, nothing happens.
     pub(crate) fn record_chunk_collected(&mut self, height: BlockHeight, shard_index: ShardIndex) {
@@ -147,9 +187,12 @@ impl BlockProductionTracker {
             if let Some(chunk_hash) = new_chunks.get(&shard_id) {
                 let (chunk_producer, received_time) =
                     chunk_inclusion_tracker.get_chunk_producer_and_received_time(chunk_hash)?;
+                let endorsed_time = chunk_inclusion_tracker.get_chunk_endorsed_time(chunk_hash)?;
                 chunk_collection_info.push(ChunkCollection {
+                    shard_id,
                     chunk_producer,
                     received_time: Some(received_time),
+                    endorsed_time,
                     chunk_included: true,
                 });
             } else {
@@ -161,8 +204,10 @@ impl BlockProductionTracker {
                     })?
                     .take_account_id();
                 chunk_collection_info.push(ChunkCollection {
+                    shard_id,
                     chunk_producer,
                     received_time: None,
+                    endorsed_time: None,
                     chunk_included: false,
                 });
             }
@@ -199,6 +244,14 @@ impl Handler<DebugStatus> for ClientActorInner {
             DebugStatus::ChainProcessingStatus => Ok(DebugStatusResponse::ChainProcessingStatus(
                 self.client.chain.get_chain_processing_info(),
             )),
+            DebugStatus::BlockProductionTimings(query) => {
+                Ok(DebugStatusResponse::BlockProductionTimings(
+                    self.get_block_production_timings(query)?,
+                ))
+            }
+            DebugStatus::ShardHotspots => Ok(DebugStatusResponse::ShardHotspots(
+                self.client.chain.shard_hotspots_tracker.get_hotspots(),
+            )),
         }
     }
 }
@@ -503,7 +556,24 @@ impl ClientActorInner {
                 )
             })
             .collect();
-        Ok(TrackedShardsView { shards_tracked_this_epoch, shards_tracked_next_epoch })
+        let tries = self.client.runtime_adapter.get_tries();
+        let memtrie_loading_status = self
+            .client
+            .epoch_manager
+            .get_shard_layout(&epoch_id)?
+            .shard_uids()
+            .map(|shard_uid| {
+                tries.get_memtrie_loading_status(shard_uid).map(|status| {
+                    let status: &'static str = status.into();
+                    status.to_string()
+                })
+            })
+            .collect();
+        Ok(TrackedShardsView {
+            shards_tracked_this_epoch,
+            shards_tracked_next_epoch,
+            memtrie_loading_status,
+        })
     }
 
     fn get_recent_epoch_info(
@@ -546,6 +616,24 @@ impl ClientActorInner {
         Ok(epochs_info)
     }
 
+    /// Returns the persisted timings of the blocks produced by this node.
+    fn get_block_production_timings(
+        &self,
+        query: DebugBlockProductionTimingsQuery,
+    ) -> Result<Vec<BlockProductionTimings>, near_chain_primitives::Error> {
+        let num_blocks = min(query.num_blocks, DEBUG_MAX_BLOCKS_TO_FETCH);
+        let start_height = match query.starting_height {
+            Some(height) => height,
+            None => (self.client.chain.head()?.height + 1).saturating_sub(num_blocks),
+        };
+        let end_height = start_height.saturating_add(num_blocks);
+        Ok(self
+            .client
+            .chain
+            .chain_store()
+            .get_block_production_timings(start_height, end_height)?)
+    }
+
     fn get_last_blocks_info(
         &mut self,
         query: DebugBlockStatusQuery,
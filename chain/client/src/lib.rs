@@ -1,11 +1,13 @@
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetClientConfig, GetExecutionOutcome, GetExecutionOutcomeResponse,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
-    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetShardChunk, GetSplitStorageInfo,
+    BatchQuery, BatchQueryResponse, Error, GetBlock, GetBlockProof, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunk, GetChunkEndorsements, GetClientConfig, GetExecutionOutcome,
+    GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetGasPrice, GetMaintenanceWindows,
+    GetNetworkInfo, GetNextEpochShardAssignments, GetNextLightClientBlock, GetProtocolConfig,
+    GetReceipt, GetShardChunk, GetShardLayoutAtBlock, GetShardLineage, GetSplitStorageInfo,
     GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetTxExecutionProfile, GetValidatorInfo,
+    GetValidatorOrdered, Query, QueryError, Status, StatusResponse, SyncStatus, TxStatus,
+    TxStatusError,
 };
 
 pub use crate::client::Client;
@@ -15,7 +17,8 @@ pub use crate::client_actor::{ClientActor, StartClientResult, start_client};
 pub use crate::config_updater::ConfigUpdater;
 pub use crate::stateless_validation::chunk_validator::orphan_witness_handling::HandleOrphanWitnessOutcome;
 pub use crate::tx_request_handler::{
-    TxRequestHandler, TxRequestHandlerActor, TxRequestHandlerConfig, spawn_tx_request_handler_actor,
+    FlushForwardedTransactions, TxRequestHandler, TxRequestHandlerActor, TxRequestHandlerConfig,
+    spawn_tx_request_handler_actor, start_tx_forwarding_flush_timer,
 };
 pub use crate::view_client_actor::{ViewClientActor, ViewClientActorInner};
 pub use chunk_producer::ProduceChunkResult;
@@ -38,13 +41,16 @@ mod chunk_producer;
 mod client;
 pub mod client_actor;
 mod config_updater;
+mod data_availability_sampler;
 pub mod debug;
 pub mod gc_actor;
 mod info;
 pub mod metrics;
+mod replica;
 mod stateless_validation;
 pub mod sync;
 pub mod sync_jobs_actor;
 pub mod test_utils;
+mod tx_forwarding;
 mod tx_request_handler;
 mod view_client_actor;
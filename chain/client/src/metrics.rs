@@ -51,6 +51,15 @@ pub(crate) static PRODUCED_CHUNKS_SOME_POOL_TRANSACTIONS_DID_NOT_FIT: LazyLock<I
     .unwrap()
     });
 
+pub(crate) static EMPTY_CHUNK_FALLBACK_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_empty_chunk_fallback_total",
+        "Number of chunks produced with a limited number of transactions because the previous chunks of this node for the shard were missing from blocks",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
 pub(crate) static IS_VALIDATOR: LazyLock<IntGauge> = LazyLock::new(|| {
     try_create_int_gauge(
         "near_is_validator",
@@ -392,6 +401,81 @@ pub(crate) static TRANSACTION_RECEIVED_NON_VALIDATOR_FORWARDED: LazyLock<IntGaug
         .unwrap()
     });
 
+pub(crate) static TX_FORWARDING_SKIPPED_CHUNK_PRODUCERS: LazyLock<IntCounterVec> = LazyLock::new(
+    || {
+        try_create_int_counter_vec(
+            "near_tx_forwarding_skipped_chunk_producers_total",
+            "Number of times a transaction wasn't forwarded to a chunk producer by the circuit breaker. The reason label is either rate_limited or unresponsive.",
+            &["reason"],
+        )
+        .unwrap()
+    },
+);
+
+pub(crate) static TX_FORWARDING_CIRCUIT_BREAKER_OPENED: LazyLock<IntCounter> = LazyLock::new(
+    || {
+        try_create_int_counter(
+            "near_tx_forwarding_circuit_breaker_opened_total",
+            "Number of times forwarding to a chunk producer was stopped because it missed its chunks",
+        )
+        .unwrap()
+    },
+);
+
+pub(crate) static TX_FORWARDING_BUFFERED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_tx_forwarding_buffered_total",
+        "Transactions which couldn't be forwarded to any chunk producer, by the outcome of buffering them: buffered, rejected or expired",
+        &["outcome"],
+    )
+    .unwrap()
+});
+
+pub(crate) static TX_FORWARDING_BUFFER_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    try_create_int_gauge(
+        "near_tx_forwarding_buffer_size_bytes",
+        "Total size of the transactions waiting to be forwarded to the chunk producers",
+    )
+    .unwrap()
+});
+
+pub(crate) static DATA_AVAILABILITY_SAMPLES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_data_availability_samples_total",
+        "Chunk parts sampled for data availability, by the result: available, invalid or timeout",
+        &["shard_id", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static DATA_AVAILABILITY_SAMPLED_CHUNKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_data_availability_sampled_chunks_total",
+        "Chunks sampled for data availability, by the result: available if all the sampled parts were valid, unavailable otherwise",
+        &["shard_id", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static DATA_AVAILABILITY_SAMPLE_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    try_create_histogram_vec(
+        "near_data_availability_sample_latency_sec",
+        "Time between requesting the sampled parts of a chunk and receiving the valid ones",
+        &["shard_id"],
+        Some(exponential_buckets(0.01, 2.0, 10).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static REPLICA_STATE_UPDATE_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_replica_state_update_requests_total",
+        "State update requests of the blocks of the trusted block producers, by the result: received or timeout",
+        &["result"],
+    )
+    .unwrap()
+});
+
 pub(crate) static NODE_PROTOCOL_VERSION: LazyLock<IntGauge> = LazyLock::new(|| {
     try_create_int_gauge("near_node_protocol_version", "Max protocol version supported by the node")
         .unwrap()
@@ -0,0 +1,341 @@
+//! Replica mode, in which the node doesn't execute the chunks of the blocks
+//! produced by the block producers it trusts. For such a block, the node asks
+//! the producer for the state updates of its chunks and applies them to the
+//! tries instead, see `near_chain::replica`. If the updates don't arrive within
+//! `state_update_timeout`, the block is processed as usual.
+//!
+//! The nodes with `serve_replica_state_updates` answer these requests once they
+//! have processed the block. The responses are signed by the producer, and the
+//! replica ignores the ones which aren't.
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+
+use near_async::messaging::CanSend;
+use near_async::time::{Clock, Instant};
+use near_chain::replica::ReplicaStateUpdates;
+use near_chain_configs::ReplicaConfig;
+use near_crypto::PublicKey;
+use near_epoch_manager::EpochManagerAdapter;
+use near_network::types::{
+    NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest, ReplicaStateUpdateRequest,
+    ReplicaStateUpdateResponse,
+};
+use near_primitives::block::{Block, BlockHeader};
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::types::ShardId;
+use near_primitives::types::validator_stake::ValidatorStake;
+use near_primitives::utils::MaybeValidated;
+use near_primitives::validator_signer::ValidatorSigner;
+
+use crate::metrics;
+
+/// Requests of the replicas deferred until the block is processed.
+const NUM_DEFERRED_REQUESTS: usize = 100;
+
+pub(crate) struct PendingReplicaBlock {
+    pub block: MaybeValidated<Block>,
+    pub peer_id: PeerId,
+    /// Key of the producer of the block, which must sign the state updates.
+    producer_key: PublicKey,
+    shard_ids: Vec<ShardId>,
+    requested_at: Instant,
+}
+
+pub(crate) struct Replica {
+    clock: Clock,
+    config: ReplicaConfig,
+    network_adapter: PeerManagerAdapter,
+    /// Blocks of the trusted producers waiting for their state updates.
+    pending_blocks: HashMap<CryptoHash, PendingReplicaBlock>,
+    /// Requests for the state updates of the blocks which aren't processed yet,
+    /// with their route back.
+    deferred_requests: lru::LruCache<CryptoHash, Vec<(ReplicaStateUpdateRequest, CryptoHash)>>,
+}
+
+impl Replica {
+    pub fn new(clock: Clock, config: ReplicaConfig, network_adapter: PeerManagerAdapter) -> Self {
+        Self {
+            clock,
+            config,
+            network_adapter,
+            pending_blocks: HashMap::new(),
+            deferred_requests: lru::LruCache::new(
+                NonZeroUsize::new(NUM_DEFERRED_REQUESTS).unwrap(),
+            ),
+        }
+    }
+
+    /// Returns the producer of the block if the node trusts it, along with the
+    /// shards to request the state updates of.
+    pub fn trusted_producer(
+        &self,
+        epoch_manager: &dyn EpochManagerAdapter,
+        header: &BlockHeader,
+    ) -> Option<(ValidatorStake, Vec<ShardId>)> {
+        if !self.config.enabled {
+            return None;
+        }
+        let producer = match epoch_manager
+            .get_block_producer_info(header.epoch_id(), header.height())
+        {
+            Ok(producer) => producer,
+            Err(err) => {
+                tracing::debug!(target: "client", ?err, block_hash = ?header.hash(), "Failed to get the block producer");
+                return None;
+            }
+        };
+        if !self.config.trusted_block_producer_keys.contains(producer.public_key()) {
+            return None;
+        }
+        let shard_layout = epoch_manager.get_shard_layout(header.epoch_id()).ok()?;
+        Some((producer, shard_layout.shard_ids().collect()))
+    }
+
+    pub fn is_pending(&self, block_hash: &CryptoHash) -> bool {
+        self.pending_blocks.contains_key(block_hash)
+    }
+
+    /// Keeps the block until the state updates requested from its producer
+    /// arrive or time out.
+    pub fn request_state_updates(
+        &mut self,
+        producer: ValidatorStake,
+        shard_ids: Vec<ShardId>,
+        block: MaybeValidated<Block>,
+        peer_id: PeerId,
+    ) {
+        let block_hash = *block.hash();
+        let (producer, producer_key, _) = producer.destructure();
+        tracing::debug!(target: "client", ?block_hash, ?producer, ?shard_ids, "Requesting replica state updates");
+        let request = ReplicaStateUpdateRequest { block_hash, shard_ids: shard_ids.clone() };
+        self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::ReplicaStateUpdateRequest { target: producer, request },
+        ));
+        self.pending_blocks.insert(
+            block_hash,
+            PendingReplicaBlock {
+                block,
+                peer_id,
+                producer_key,
+                shard_ids,
+                requested_at: self.clock.now(),
+            },
+        );
+    }
+
+    /// Keeps the requested updates of the response to apply, and returns the
+    /// block to process. The responses which weren't requested or aren't
+    /// signed by the producer of the block are ignored, and the block keeps
+    /// waiting for the updates until the timeout.
+    pub fn process_response(
+        &mut self,
+        response: ReplicaStateUpdateResponse,
+        state_updates: &mut ReplicaStateUpdates,
+    ) -> Option<PendingReplicaBlock> {
+        let pending = self.pending_blocks.get(&response.block_hash)?;
+        if !response.verify_signature(&pending.producer_key) {
+            tracing::warn!(target: "client", block_hash = ?response.block_hash, "Ignoring replica state updates not signed by the block producer");
+            metrics::REPLICA_STATE_UPDATE_REQUESTS.with_label_values(&["invalid_signature"]).inc();
+            return None;
+        }
+        let pending = self.pending_blocks.remove(&response.block_hash).unwrap();
+        for update in response.updates {
+            if pending.shard_ids.contains(&update.shard_id) {
+                state_updates.insert(response.block_hash, update);
+            }
+        }
+        metrics::REPLICA_STATE_UPDATE_REQUESTS.with_label_values(&["received"]).inc();
+        Some(pending)
+    }
+
+    /// Returns the blocks which didn't get their state updates within
+    /// `state_update_timeout`.
+    pub fn take_expired_blocks(&mut self) -> Vec<PendingReplicaBlock> {
+        let now = self.clock.now();
+        let state_update_timeout = self.config.state_update_timeout;
+        let expired = self
+            .pending_blocks
+            .iter()
+            .filter(|(_, pending)| pending.requested_at + state_update_timeout <= now)
+            .map(|(block_hash, _)| *block_hash)
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .map(|block_hash| {
+                tracing::debug!(target: "client", ?block_hash, "Replica state updates timed out");
+                metrics::REPLICA_STATE_UPDATE_REQUESTS.with_label_values(&["timeout"]).inc();
+                self.pending_blocks.remove(&block_hash).unwrap()
+            })
+            .collect()
+    }
+
+    /// Answers the request with the cached state updates, or defers it until
+    /// the block is processed.
+    pub fn process_request(
+        &mut self,
+        request: ReplicaStateUpdateRequest,
+        route_back: CryptoHash,
+        is_block_processed: bool,
+        state_updates: &ReplicaStateUpdates,
+        signer: &ValidatorSigner,
+    ) {
+        if is_block_processed {
+            self.respond(request, route_back, state_updates, signer);
+            return;
+        }
+        if let Some(requests) = self.deferred_requests.get_mut(&request.block_hash) {
+            requests.push((request, route_back));
+        } else {
+            self.deferred_requests.put(request.block_hash, vec![(request, route_back)]);
+        }
+    }
+
+    /// Answers the deferred requests for the state updates of the block.
+    pub fn on_block_accepted(
+        &mut self,
+        block_hash: &CryptoHash,
+        state_updates: &ReplicaStateUpdates,
+        signer: &ValidatorSigner,
+    ) {
+        let Some(requests) = self.deferred_requests.pop(block_hash) else {
+            return;
+        };
+        for (request, route_back) in requests {
+            self.respond(request, route_back, state_updates, signer);
+        }
+    }
+
+    fn respond(
+        &self,
+        request: ReplicaStateUpdateRequest,
+        route_back: CryptoHash,
+        state_updates: &ReplicaStateUpdates,
+        signer: &ValidatorSigner,
+    ) {
+        let updates = state_updates.get(&request.block_hash, &request.shard_ids);
+        let response = ReplicaStateUpdateResponse::new(request.block_hash, updates, signer);
+        self.network_adapter.send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::ReplicaStateUpdateResponse { route_back, response },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_async::messaging::IntoMultiSender;
+    use near_async::time::{Duration, Utc};
+    use near_network::test_utils::MockPeerManagerAdapter;
+    use near_primitives::genesis::{genesis_block, genesis_chunks};
+    use near_primitives::replica::ReplicaShardStateUpdate;
+    use near_primitives::test_utils::create_test_signer;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_store::Trie;
+    use std::sync::Arc;
+
+    fn test_block() -> Block {
+        let shard_ids = vec![ShardId::new(0)];
+        let genesis_chunks = genesis_chunks(
+            vec![Trie::EMPTY_ROOT],
+            vec![Default::default()],
+            &shard_ids,
+            1_000_000,
+            0,
+            PROTOCOL_VERSION,
+        );
+        genesis_block(
+            PROTOCOL_VERSION,
+            genesis_chunks.into_iter().map(|chunk| chunk.take_header()).collect(),
+            Utc::now_utc(),
+            0,
+            100,
+            1_000_000_000,
+            &[],
+        )
+    }
+
+    fn shard_update(shard_id: ShardId) -> ReplicaShardStateUpdate {
+        ReplicaShardStateUpdate {
+            shard_id,
+            is_new_chunk: true,
+            gas_limit: 1_000_000,
+            new_root: CryptoHash::hash_bytes(b"root"),
+            state_changes: vec![],
+            outcomes: vec![],
+            outgoing_receipts: vec![],
+            validator_proposals: vec![],
+            total_gas_burnt: 0,
+            total_balance_burnt: 0,
+            applied_receipts_hash: CryptoHash::default(),
+            congestion_info: None,
+            bandwidth_requests: None,
+            bandwidth_scheduler_state_hash: CryptoHash::default(),
+        }
+    }
+
+    /// Returns the replica waiting for the state updates of the block produced
+    /// by `producer`.
+    fn pending_replica(block: &Block, producer: &ValidatorSigner) -> Replica {
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let config = ReplicaConfig {
+            enabled: true,
+            trusted_block_producer_keys: vec![producer.public_key()],
+            state_update_timeout: Duration::seconds(1),
+        };
+        let mut replica = Replica::new(Clock::real(), config, network_adapter.as_multi_sender());
+        let producer =
+            ValidatorStake::new(producer.validator_id().clone(), producer.public_key(), 0);
+        replica.request_state_updates(
+            producer,
+            vec![ShardId::new(0)],
+            block.clone().into(),
+            PeerId::random(),
+        );
+        replica
+    }
+
+    #[test]
+    fn test_response_signed_by_producer() {
+        let block = test_block();
+        let producer = create_test_signer("producer");
+        let mut replica = pending_replica(&block, &producer);
+        let mut state_updates = ReplicaStateUpdates::new(10);
+        let update = shard_update(ShardId::new(0));
+        let response = ReplicaStateUpdateResponse::new(*block.hash(), vec![update], &producer);
+        assert!(replica.process_response(response, &mut state_updates).is_some());
+        assert!(!replica.is_pending(block.hash()));
+        assert_eq!(state_updates.get(block.hash(), &[ShardId::new(0)]).len(), 1);
+    }
+
+    #[test]
+    fn test_response_not_signed_by_producer() {
+        let block = test_block();
+        let producer = create_test_signer("producer");
+        let mut replica = pending_replica(&block, &producer);
+        let mut state_updates = ReplicaStateUpdates::new(10);
+
+        // The updates signed by another node are ignored.
+        let other = create_test_signer("other");
+        let update = shard_update(ShardId::new(0));
+        let response = ReplicaStateUpdateResponse::new(*block.hash(), vec![update], &other);
+        assert!(replica.process_response(response, &mut state_updates).is_none());
+        assert!(replica.is_pending(block.hash()));
+        assert!(state_updates.get(block.hash(), &[ShardId::new(0)]).is_empty());
+
+        // So are the updates changed after the producer signed them.
+        let response = ReplicaStateUpdateResponse::new(
+            *block.hash(),
+            vec![shard_update(ShardId::new(0))],
+            &producer,
+        );
+        let mut tampered = response.clone();
+        tampered.updates[0].new_root = CryptoHash::hash_bytes(b"other root");
+        assert!(replica.process_response(tampered, &mut state_updates).is_none());
+        assert!(replica.is_pending(block.hash()));
+
+        // The block still gets its updates from the producer.
+        assert!(replica.process_response(response, &mut state_updates).is_some());
+        assert!(!replica.is_pending(block.hash()));
+    }
+}
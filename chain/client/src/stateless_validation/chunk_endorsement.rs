@@ -1,5 +1,6 @@
 use super::validate::validate_chunk_endorsement;
 use lru::LruCache;
+use near_async::time::{Clock, Utc};
 use near_chain_primitives::Error;
 use near_crypto::Signature;
 use near_epoch_manager::EpochManagerAdapter;
@@ -7,36 +8,83 @@ use near_primitives::sharding::{ChunkHash, ShardChunkHeader};
 use near_primitives::stateless_validation::ChunkProductionKey;
 use near_primitives::stateless_validation::chunk_endorsement::ChunkEndorsement;
 use near_primitives::stateless_validation::validator_assignment::ChunkEndorsementsState;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::views::{ChunkEndorsementsView, ReceivedChunkEndorsementView};
 use near_store::Store;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::num::NonZeroUsize;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 // This is the number of unique chunks for which we would track the chunk endorsements.
 // Ideally, we should not be processing more than num_shards chunks at a time.
 const NUM_CHUNKS_IN_CHUNK_ENDORSEMENTS_CACHE: usize = 100;
 
+/// Times at which the node, as a chunk validator, sent its chunk endorsements.
+/// Shared with the threads validating the state witnesses, which send them.
+#[derive(Clone)]
+pub struct SentChunkEndorsements {
+    clock: Clock,
+    sent_at: Arc<Mutex<LruCache<ChunkProductionKey, Utc>>>,
+}
+
+impl SentChunkEndorsements {
+    fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            sent_at: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(NUM_CHUNKS_IN_CHUNK_ENDORSEMENTS_CACHE).unwrap(),
+            ))),
+        }
+    }
+
+    pub fn record(&self, key: ChunkProductionKey) {
+        self.sent_at.lock().unwrap().put(key, self.clock.now_utc());
+    }
+}
+
+/// The last collection of the endorsements of a chunk by the block producer.
+struct CollectedChunkEndorsements {
+    chunk_hash: ChunkHash,
+    first_collected_at: Utc,
+    endorsed_at: Option<Utc>,
+    missing: Vec<AccountId>,
+}
+
 /// Module to track chunk endorsements received from chunk validators.
 pub struct ChunkEndorsementTracker {
+    clock: Clock,
     epoch_manager: Arc<dyn EpochManagerAdapter>,
     /// Used to find the chain HEAD when validating partial witnesses.
     store: Store,
-    /// We store the validated chunk endorsements received from chunk validators.
-    chunk_endorsements: LruCache<ChunkProductionKey, HashMap<AccountId, (ChunkHash, Signature)>>,
+    /// We store the validated chunk endorsements received from chunk validators,
+    /// along with the time they were received at.
+    chunk_endorsements:
+        LruCache<ChunkProductionKey, HashMap<AccountId, (ChunkHash, Signature, Utc)>>,
+    /// Kept for debugging only, see `chunk_endorsements_view`.
+    collected_endorsements: LruCache<ChunkProductionKey, CollectedChunkEndorsements>,
+    sent_endorsements: SentChunkEndorsements,
 }
 
 impl ChunkEndorsementTracker {
-    pub fn new(epoch_manager: Arc<dyn EpochManagerAdapter>, store: Store) -> Self {
+    pub fn new(clock: Clock, epoch_manager: Arc<dyn EpochManagerAdapter>, store: Store) -> Self {
         Self {
+            clock: clock.clone(),
             epoch_manager,
             store,
             chunk_endorsements: LruCache::new(
                 NonZeroUsize::new(NUM_CHUNKS_IN_CHUNK_ENDORSEMENTS_CACHE).unwrap(),
             ),
+            collected_endorsements: LruCache::new(
+                NonZeroUsize::new(NUM_CHUNKS_IN_CHUNK_ENDORSEMENTS_CACHE).unwrap(),
+            ),
+            sent_endorsements: SentChunkEndorsements::new(clock),
         }
     }
 
+    pub fn sent_endorsements(&self) -> SentChunkEndorsements {
+        self.sent_endorsements.clone()
+    }
+
     // Validate the chunk endorsement and store it in the cache.
     pub fn process_chunk_endorsement(
         &mut self,
@@ -52,9 +100,10 @@ impl ChunkEndorsementTracker {
 
         // Validate the chunk endorsement and store it in the cache.
         if validate_chunk_endorsement(self.epoch_manager.as_ref(), &endorsement, &self.store)? {
-            self.chunk_endorsements
-                .get_or_insert_mut(key, || HashMap::new())
-                .insert(account_id.clone(), (endorsement.chunk_hash(), endorsement.signature()));
+            self.chunk_endorsements.get_or_insert_mut(key, || HashMap::new()).insert(
+                account_id.clone(),
+                (endorsement.chunk_hash(), endorsement.signature(), self.clock.now_utc()),
+            );
         };
         Ok(())
     }
@@ -83,13 +132,84 @@ impl ChunkEndorsementTracker {
         //    1. The chunk endorsements are from valid chunk_validator for this chunk.
         //    2. The chunk endorsements signatures are valid.
         //    3. We still need to validate if the chunk_hash matches the chunk_header.chunk_hash()
-        let entry = self.chunk_endorsements.get_or_insert(key, || HashMap::new());
-        let validator_signatures = entry
+        let entry = self.chunk_endorsements.get_or_insert(key.clone(), || HashMap::new());
+        let validator_signatures: HashMap<_, _> = entry
             .into_iter()
-            .filter(|(_, (chunk_hash, _))| chunk_hash == &chunk_header.chunk_hash())
-            .map(|(account_id, (_, signature))| (account_id, signature.clone()))
+            .filter(|(_, (chunk_hash, _, _))| chunk_hash == &chunk_header.chunk_hash())
+            .map(|(account_id, (_, signature, _))| (account_id, signature.clone()))
+            .collect();
+        let missing = chunk_validator_assignments
+            .ordered_chunk_validators()
+            .into_iter()
+            .filter(|account_id| !validator_signatures.contains_key(account_id))
             .collect();
 
-        Ok(chunk_validator_assignments.compute_endorsement_state(validator_signatures))
+        let state = chunk_validator_assignments.compute_endorsement_state(validator_signatures);
+        let now = self.clock.now_utc();
+        let collected =
+            self.collected_endorsements.get_or_insert_mut(key, || CollectedChunkEndorsements {
+                chunk_hash: chunk_header.chunk_hash(),
+                first_collected_at: now,
+                endorsed_at: None,
+                missing: vec![],
+            });
+        collected.chunk_hash = chunk_header.chunk_hash();
+        collected.missing = missing;
+        if state.is_endorsed && collected.endorsed_at.is_none() {
+            collected.endorsed_at = Some(now);
+        }
+        Ok(state)
+    }
+
+    /// Returns what the node knows about the endorsements of the chunks
+    /// created at the height, for debugging.
+    pub fn chunk_endorsements_view(
+        &self,
+        height_created: BlockHeight,
+    ) -> Vec<ChunkEndorsementsView> {
+        let sent_at = self.sent_endorsements.sent_at.lock().unwrap();
+        let keys: BTreeSet<_> = self
+            .chunk_endorsements
+            .iter()
+            .map(|(key, _)| key)
+            .chain(self.collected_endorsements.iter().map(|(key, _)| key))
+            .chain(sent_at.iter().map(|(key, _)| key))
+            .filter(|key| key.height_created == height_created)
+            .map(|key| (key.shard_id, key.epoch_id))
+            .collect();
+        let to_nanos = |time: &Utc| time.unix_timestamp_nanos() as u64;
+        keys.into_iter()
+            .map(|(shard_id, epoch_id)| {
+                let key = ChunkProductionKey { shard_id, epoch_id, height_created };
+                let collected = self.collected_endorsements.peek(&key);
+                let mut received = self
+                    .chunk_endorsements
+                    .peek(&key)
+                    .into_iter()
+                    .flatten()
+                    .map(|(account_id, (chunk_hash, _, received_at))| {
+                        ReceivedChunkEndorsementView {
+                            account_id: account_id.clone(),
+                            chunk_hash: chunk_hash.clone(),
+                            received_at: to_nanos(received_at),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                received.sort_by_key(|endorsement| endorsement.received_at);
+                ChunkEndorsementsView {
+                    shard_id,
+                    height_created,
+                    chunk_hash: collected.map(|collected| collected.chunk_hash.clone()),
+                    first_collected_at: collected
+                        .map(|collected| to_nanos(&collected.first_collected_at)),
+                    endorsed_at: collected
+                        .and_then(|collected| collected.endorsed_at.as_ref())
+                        .map(to_nanos),
+                    received,
+                    missing: collected.map_or(vec![], |collected| collected.missing.clone()),
+                    sent_at: sent_at.peek(&key).map(to_nanos),
+                }
+            })
+            .collect()
     }
 }
@@ -2,6 +2,7 @@ pub mod orphan_witness_handling;
 pub mod orphan_witness_pool;
 
 use crate::Client;
+use crate::stateless_validation::chunk_endorsement::SentChunkEndorsements;
 use itertools::Itertools;
 use near_async::futures::{AsyncComputationSpawner, AsyncComputationSpawnerExt};
 use near_async::messaging::{CanSend, Sender};
@@ -44,6 +45,7 @@ pub struct ChunkValidator {
     orphan_witness_pool: OrphanStateWitnessPool,
     validation_spawner: Arc<dyn AsyncComputationSpawner>,
     main_state_transition_result_cache: chunk_validation::MainStateTransitionCache,
+    sent_endorsements: SentChunkEndorsements,
 }
 
 impl ChunkValidator {
@@ -53,6 +55,7 @@ impl ChunkValidator {
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         orphan_witness_pool_size: usize,
         validation_spawner: Arc<dyn AsyncComputationSpawner>,
+        sent_endorsements: SentChunkEndorsements,
     ) -> Self {
         Self {
             epoch_manager,
@@ -62,6 +65,7 @@ impl ChunkValidator {
             validation_spawner,
             main_state_transition_result_cache: chunk_validation::MainStateTransitionCache::default(
             ),
+            sent_endorsements,
         }
     }
 
@@ -96,6 +100,7 @@ impl ChunkValidator {
         let chunk_header = state_witness.chunk_header.clone();
         let network_sender = self.network_sender.clone();
         let epoch_manager = self.epoch_manager.clone();
+        let sent_endorsements = self.sent_endorsements.clone();
 
         // If we have the chunk extra for the previous block, we can validate
         // the chunk without state witness.
@@ -132,6 +137,7 @@ impl ChunkValidator {
                         epoch_manager.as_ref(),
                         signer,
                         &network_sender,
+                        &sent_endorsements,
                     );
                     return Ok(());
                 }
@@ -172,6 +178,7 @@ impl ChunkValidator {
                         epoch_manager.as_ref(),
                         signer.as_ref(),
                         &network_sender,
+                        &sent_endorsements,
                     );
                 }
                 Err(err) => {
@@ -201,6 +208,7 @@ pub(crate) fn send_chunk_endorsement_to_block_producers(
     epoch_manager: &dyn EpochManagerAdapter,
     signer: &ValidatorSigner,
     network_sender: &Sender<PeerManagerMessageRequest>,
+    sent_endorsements: &SentChunkEndorsements,
 ) -> Option<ChunkEndorsement> {
     let epoch_id =
         epoch_manager.get_epoch_id_from_prev_block(chunk_header.prev_block_hash()).unwrap();
@@ -226,6 +234,7 @@ pub(crate) fn send_chunk_endorsement_to_block_producers(
     );
 
     let endorsement = ChunkEndorsement::new(epoch_id, chunk_header, signer);
+    sent_endorsements.record(endorsement.chunk_production_key());
     let mut send_to_itself = None;
     for block_producer in block_producers {
         if &block_producer == signer.validator_id() {
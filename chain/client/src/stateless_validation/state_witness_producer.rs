@@ -90,6 +90,7 @@ impl Client {
                 self.epoch_manager.as_ref(),
                 my_signer.as_ref(),
                 &self.network_adapter.clone().into_sender(),
+                &self.chunk_endorsement_tracker.sent_endorsements(),
             ) {
                 self.chunk_endorsement_tracker.process_chunk_endorsement(endorsement)?;
             }
@@ -121,6 +122,13 @@ impl Client {
             applied_receipts_hash,
             contract_updates,
         } = self.collect_state_transition_data(&chunk_header, prev_chunk_header)?;
+        #[cfg(feature = "test_features")]
+        let main_transition = if self.adv_produce_invalid_state_witnesses {
+            let post_state_root = hash(main_transition.post_state_root.as_ref());
+            ChunkStateTransition { post_state_root, ..main_transition }
+        } else {
+            main_transition
+        };
 
         let source_receipt_proofs =
             self.collect_source_receipt_proofs(prev_block_header, prev_chunk_header)?;
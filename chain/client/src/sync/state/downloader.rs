@@ -10,7 +10,7 @@ use near_chain::types::RuntimeAdapter;
 use near_primitives::hash::CryptoHash;
 use near_primitives::state_part::PartId;
 use near_primitives::state_sync::{ShardStateSyncResponseHeader, StatePartKey};
-use near_primitives::types::ShardId;
+use near_primitives::types::{EpochId, ShardId};
 use near_store::{DBCol, Store};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -131,6 +131,7 @@ impl StateSyncDownloader {
         &self,
         shard_id: ShardId,
         sync_hash: CryptoHash,
+        epoch_id: EpochId,
         state_root: CryptoHash,
         num_state_parts: u64,
         part_id: u64,
@@ -178,6 +179,7 @@ impl StateSyncDownloader {
                     &state_root,
                     PartId { idx: part_id, total: num_state_parts },
                     &part,
+                    &epoch_id,
                 ) {
                     let mut store_update = store.store_update();
                     let key = borsh::to_vec(&StatePartKey(sync_hash, shard_id, part_id)).unwrap();
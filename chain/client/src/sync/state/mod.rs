@@ -18,6 +18,7 @@ use near_async::time::{Clock, Duration};
 use near_chain::Chain;
 use near_chain::types::RuntimeAdapter;
 use near_chain_configs::{ExternalStorageConfig, ExternalStorageLocation, SyncConfig};
+use near_chain_primitives::error::ErrorContext;
 use near_client_primitives::types::{ShardSyncStatus, StateSyncStatus};
 use near_epoch_manager::EpochManagerAdapter;
 use near_network::types::{
@@ -237,6 +238,11 @@ impl StateSync {
                     Ok(result) => {
                         entry.remove();
                         if let Err(err) = result {
+                            let err = err.with_context(ErrorContext {
+                                block_hash: Some(sync_hash),
+                                shard_id: Some(*shard_id),
+                                ..Default::default()
+                            });
                             tracing::error!(%shard_id, ?err, "State sync failed for shard");
                             return Err(err);
                         }
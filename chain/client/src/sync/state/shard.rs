@@ -10,6 +10,7 @@ use near_chain::types::RuntimeAdapter;
 use near_client_primitives::types::ShardSyncStatus;
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::shard_id_to_uid;
+use near_o11y::events::{self, ChainEvent, StateSyncPhase};
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::state_part::PartId;
@@ -70,7 +71,7 @@ pub(super) async fn run_state_sync_for_shard(
     future_spawner: Arc<dyn FutureSpawner>,
 ) -> Result<(), near_chain::Error> {
     tracing::info!("Running state sync for shard {}", shard_id);
-    *status.lock().unwrap() = ShardSyncStatus::StateDownloadHeader;
+    set_status(&status, shard_id, sync_hash, ShardSyncStatus::StateDownloadHeader);
     let header = downloader.ensure_shard_header(shard_id, sync_hash, cancel.clone()).await?;
     let state_root = header.chunk_prev_state_root();
     let num_parts = header.num_state_parts();
@@ -85,7 +86,7 @@ pub(super) async fn run_state_sync_for_shard(
         .set(num_parts as i64);
 
     return_if_cancelled!(cancel);
-    *status.lock().unwrap() = ShardSyncStatus::StateDownloadParts;
+    set_status(&status, shard_id, sync_hash, ShardSyncStatus::StateDownloadParts);
     let mut parts_to_download: Vec<u64> = (0..num_parts).collect();
     {
         // Peer selection is designed such that different nodes downloading the same part will tend
@@ -105,6 +106,7 @@ pub(super) async fn run_state_sync_for_shard(
                 let future = downloader.ensure_shard_part_downloaded_single_attempt(
                     shard_id,
                     sync_hash,
+                    epoch_id,
                     state_root,
                     num_parts,
                     part_id,
@@ -128,7 +130,7 @@ pub(super) async fn run_state_sync_for_shard(
     }
 
     return_if_cancelled!(cancel);
-    *status.lock().unwrap() = ShardSyncStatus::StateApplyInProgress;
+    set_status(&status, shard_id, sync_hash, ShardSyncStatus::StateApplyInProgress);
     runtime.get_tries().unload_memtrie(&shard_uid);
     let mut store_update = store.store_update();
     runtime
@@ -180,9 +182,8 @@ pub(super) async fn run_state_sync_for_shard(
     // Load memtrie.
     {
         let handle = computation_task_tracker.get_handle(&format!("shard {}", shard_id)).await;
-        let head_protocol_version = epoch_manager.get_epoch_protocol_version(&epoch_id)?;
-        let shard_uids_pending_resharding = epoch_manager
-            .get_shard_uids_pending_resharding(head_protocol_version, PROTOCOL_VERSION)?;
+        let shard_uids_pending_resharding =
+            epoch_manager.get_shard_uids_pending_resharding(&epoch_id, PROTOCOL_VERSION)?;
         handle.set_status("Loading memtrie");
         runtime.get_tries().load_memtrie_on_catchup(
             &shard_uid,
@@ -194,7 +195,7 @@ pub(super) async fn run_state_sync_for_shard(
     return_if_cancelled!(cancel);
 
     // Finalize; this needs to be done by the Chain.
-    *status.lock().unwrap() = ShardSyncStatus::StateApplyFinalizing;
+    set_status(&status, shard_id, sync_hash, ShardSyncStatus::StateApplyFinalizing);
     chain_finalization_sender
         .send_async(ChainFinalizationRequest { shard_id, sync_hash })
         .await
@@ -202,11 +203,30 @@ pub(super) async fn run_state_sync_for_shard(
         near_chain::Error::Other("Chain finalization request could not be handled".to_owned())
     })??;
 
-    *status.lock().unwrap() = ShardSyncStatus::StateSyncDone;
+    set_status(&status, shard_id, sync_hash, ShardSyncStatus::StateSyncDone);
 
     Ok(())
 }
 
+fn set_status(
+    status: &Mutex<ShardSyncStatus>,
+    shard_id: ShardId,
+    sync_hash: CryptoHash,
+    new_status: ShardSyncStatus,
+) {
+    *status.lock().unwrap() = new_status;
+    let phase = match new_status {
+        ShardSyncStatus::StateDownloadHeader => StateSyncPhase::DownloadHeader,
+        ShardSyncStatus::StateDownloadParts => StateSyncPhase::DownloadParts,
+        ShardSyncStatus::StateApplyScheduling | ShardSyncStatus::StateApplyInProgress => {
+            StateSyncPhase::ApplyParts
+        }
+        ShardSyncStatus::StateApplyFinalizing => StateSyncPhase::Finalize,
+        ShardSyncStatus::StateSyncDone => StateSyncPhase::Done,
+    };
+    events::emit(ChainEvent::StateSyncPhase { shard_id, sync_hash, phase });
+}
+
 fn create_flat_storage_for_shard(
     store: &Store,
     runtime: &dyn RuntimeAdapter,
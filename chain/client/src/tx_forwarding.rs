@@ -0,0 +1,237 @@
+//! Circuit breaker for the transactions forwarded to the upcoming chunk producers.
+//!
+//! A node which doesn't produce chunks for the shard of a transaction forwards
+//! it to the chunk producers of the next few heights. During traffic spikes
+//! this can overload them, so the breaker limits the number of transactions
+//! forwarded to each chunk producer at a height, and stops forwarding to the
+//! chunk producers which keep missing their chunks. The transactions which
+//! can't be forwarded to any chunk producer wait in a bounded buffer and are
+//! retried at the next heights.
+use std::collections::{HashMap, VecDeque};
+
+use near_chain_configs::TxForwardingConfig;
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{AccountId, BlockHeight, EpochId};
+
+use crate::metrics;
+
+/// A transaction waiting to be forwarded.
+pub(crate) struct BufferedTx {
+    pub epoch_id: EpochId,
+    pub tx: SignedTransaction,
+    /// Height at which the transaction was buffered for the first time.
+    pub buffered_at: BlockHeight,
+}
+
+#[derive(Default)]
+struct ChunkProducerState {
+    /// Transactions forwarded to the chunk producer at the current height.
+    forwarded_txs: usize,
+    /// Consecutive chunks missed by the chunk producer.
+    missed_chunks: u64,
+    /// No transactions are forwarded to the chunk producer below this height.
+    open_until: BlockHeight,
+}
+
+pub(crate) struct TxForwardingCircuitBreaker {
+    config: TxForwardingConfig,
+    chunk_producers: HashMap<AccountId, ChunkProducerState>,
+    buffer: VecDeque<BufferedTx>,
+    buffer_size: u64,
+    /// Height which the forwarded transactions are counted for.
+    height: BlockHeight,
+    /// Last block whose chunks were counted in `missed_chunks`.
+    last_observed_block: Option<CryptoHash>,
+}
+
+impl TxForwardingCircuitBreaker {
+    pub fn new(config: TxForwardingConfig) -> Self {
+        Self {
+            config,
+            chunk_producers: HashMap::new(),
+            buffer: VecDeque::new(),
+            buffer_size: 0,
+            height: 0,
+            last_observed_block: None,
+        }
+    }
+
+    pub fn last_observed_block(&self) -> Option<&CryptoHash> {
+        self.last_observed_block.as_ref()
+    }
+
+    pub fn set_last_observed_block(&mut self, block_hash: CryptoHash) {
+        self.last_observed_block = Some(block_hash);
+    }
+
+    /// Records whether the chunk the chunk producer was supposed to produce at
+    /// `height` made it into the block. After `missing_chunks_threshold`
+    /// missed chunks in a row, forwarding to the chunk producer is stopped for
+    /// `open_heights`.
+    pub fn observe_chunk(
+        &mut self,
+        chunk_producer: &AccountId,
+        height: BlockHeight,
+        is_included: bool,
+    ) {
+        let state = self.chunk_producers.entry(chunk_producer.clone()).or_default();
+        if is_included {
+            state.missed_chunks = 0;
+            return;
+        }
+        state.missed_chunks += 1;
+        if state.missed_chunks >= self.config.missing_chunks_threshold {
+            tracing::debug!(target: "client", ?chunk_producer, height, missed_chunks = state.missed_chunks, "Stopping forwarding transactions to an unresponsive chunk producer");
+            state.missed_chunks = 0;
+            state.open_until = height + self.config.open_heights;
+            metrics::TX_FORWARDING_CIRCUIT_BREAKER_OPENED.inc();
+        }
+    }
+
+    /// Moves to the given height and returns the buffered transactions to
+    /// retry, without the expired ones. Returns nothing if the height didn't
+    /// change, since none of the chunk producers can accept more transactions
+    /// until then.
+    pub fn advance_to_height(&mut self, height: BlockHeight) -> Vec<BufferedTx> {
+        if height <= self.height {
+            return vec![];
+        }
+        self.height = height;
+        self.chunk_producers.retain(|_, state| {
+            state.forwarded_txs = 0;
+            state.missed_chunks > 0 || state.open_until > height
+        });
+
+        let max_buffered_heights = self.config.max_buffered_heights;
+        let (retried, expired): (Vec<_>, Vec<_>) = self
+            .buffer
+            .drain(..)
+            .partition(|buffered| buffered.buffered_at + max_buffered_heights > height);
+        self.buffer_size = 0;
+        metrics::TX_FORWARDING_BUFFER_SIZE.set(0);
+        if !expired.is_empty() {
+            tracing::debug!(target: "client", height, num_expired = expired.len(), "Dropping buffered transactions which couldn't be forwarded");
+            metrics::TX_FORWARDING_BUFFERED
+                .with_label_values(&["expired"])
+                .inc_by(expired.len() as u64);
+        }
+        retried
+    }
+
+    /// Returns the chunk producers out of `candidates` which can accept the
+    /// transaction at the current height, and counts it for them.
+    pub fn select(&mut self, candidates: impl IntoIterator<Item = AccountId>) -> Vec<AccountId> {
+        let mut selected = vec![];
+        for chunk_producer in candidates {
+            let state = self.chunk_producers.entry(chunk_producer.clone()).or_default();
+            let reason = if state.open_until > self.height {
+                "unresponsive"
+            } else if state.forwarded_txs >= self.config.max_txs_per_height {
+                "rate_limited"
+            } else {
+                state.forwarded_txs += 1;
+                selected.push(chunk_producer);
+                continue;
+            };
+            metrics::TX_FORWARDING_SKIPPED_CHUNK_PRODUCERS.with_label_values(&[reason]).inc();
+        }
+        selected
+    }
+
+    /// Buffers a transaction which couldn't be forwarded to any chunk
+    /// producer. Returns false if the buffer is full.
+    pub fn buffer(&mut self, buffered: BufferedTx) -> bool {
+        let size = buffered.tx.get_size();
+        if self.buffer_size + size > self.config.buffer_size_limit.as_u64() {
+            metrics::TX_FORWARDING_BUFFERED.with_label_values(&["rejected"]).inc();
+            return false;
+        }
+        self.buffer_size += size;
+        self.buffer.push_back(buffered);
+        metrics::TX_FORWARDING_BUFFER_SIZE.set(self.buffer_size as i64);
+        metrics::TX_FORWARDING_BUFFERED.with_label_values(&["buffered"]).inc();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytesize::ByteSize;
+
+    fn config() -> TxForwardingConfig {
+        TxForwardingConfig {
+            enabled: true,
+            max_txs_per_height: 2,
+            missing_chunks_threshold: 2,
+            open_heights: 3,
+            buffer_size_limit: ByteSize::kb(1),
+            max_buffered_heights: 2,
+            flush_interval: near_async::time::Duration::milliseconds(100),
+        }
+    }
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn buffered_tx(buffered_at: BlockHeight) -> BufferedTx {
+        BufferedTx {
+            epoch_id: EpochId::default(),
+            tx: SignedTransaction::empty(CryptoHash::default()),
+            buffered_at,
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_per_height() {
+        let mut breaker = TxForwardingCircuitBreaker::new(config());
+        breaker.advance_to_height(1);
+        for _ in 0..2 {
+            assert_eq!(
+                breaker.select([account("a"), account("b")]),
+                vec![account("a"), account("b")]
+            );
+        }
+        assert_eq!(breaker.select([account("a"), account("c")]), vec![account("c")]);
+        breaker.advance_to_height(2);
+        assert_eq!(breaker.select([account("a")]), vec![account("a")]);
+    }
+
+    #[test]
+    fn test_unresponsive_chunk_producer() {
+        let mut breaker = TxForwardingCircuitBreaker::new(config());
+        breaker.observe_chunk(&account("a"), 1, false);
+        breaker.observe_chunk(&account("a"), 2, true);
+        breaker.observe_chunk(&account("a"), 3, false);
+        breaker.advance_to_height(4);
+        assert_eq!(breaker.select([account("a")]), vec![account("a")]);
+
+        breaker.observe_chunk(&account("a"), 4, false);
+        for height in 5..7 {
+            breaker.advance_to_height(height);
+            assert!(breaker.select([account("a")]).is_empty());
+        }
+        breaker.advance_to_height(7);
+        assert_eq!(breaker.select([account("a")]), vec![account("a")]);
+    }
+
+    #[test]
+    fn test_buffer_limits() {
+        let mut breaker = TxForwardingCircuitBreaker::new(config());
+        breaker.advance_to_height(1);
+        let tx_size = buffered_tx(1).tx.get_size();
+        let capacity = ByteSize::kb(1).as_u64() / tx_size;
+        for _ in 0..capacity {
+            assert!(breaker.buffer(buffered_tx(1)));
+        }
+        assert!(!breaker.buffer(buffered_tx(1)));
+        assert!(breaker.advance_to_height(1).is_empty());
+
+        let retried = breaker.advance_to_height(2);
+        assert_eq!(retried.len() as u64, capacity);
+        breaker.buffer(retried.into_iter().next().unwrap());
+        assert!(breaker.advance_to_height(3).is_empty());
+    }
+}
@@ -1,12 +1,16 @@
 use near_async::actix_wrapper::SyncActixWrapper;
+use near_async::futures::FutureSpawner;
+use near_async::futures::FutureSpawnerExt;
 use near_async::messaging;
 use near_async::messaging::CanSend;
 use near_async::messaging::Handler;
+use near_async::messaging::Sender;
+use near_async::time::Clock;
 use near_chain::check_transaction_validity_period;
 use near_chain::types::RuntimeAdapter;
 use near_chain::types::Tip;
 use near_chain_configs::MutableValidatorSigner;
-use near_chunks::client::ShardedTransactionPool;
+use near_chain_configs::TxForwardingConfig;
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::account_id_to_shard_id;
 use near_epoch_manager::shard_tracker::ShardTracker;
@@ -16,8 +20,10 @@ use near_network::types::NetworkRequests;
 use near_network::types::PeerManagerAdapter;
 use near_network::types::PeerManagerMessageRequest;
 use near_pool::InsertTransactionResult;
+use near_pool::ShardedTransactionPool;
 use near_primitives::stateless_validation::ChunkProductionKey;
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::AccountId;
 use near_primitives::types::BlockHeightDelta;
 use near_primitives::types::EpochId;
 use near_primitives::types::ShardId;
@@ -30,6 +36,8 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::metrics;
+use crate::tx_forwarding::BufferedTx;
+use crate::tx_forwarding::TxForwardingCircuitBreaker;
 
 pub type TxRequestHandlerActor = SyncActixWrapper<TxRequestHandler>;
 
@@ -40,8 +48,42 @@ impl Handler<ProcessTxRequest> for TxRequestHandler {
     }
 }
 
+/// Tells the handler to retry forwarding the buffered transactions.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub struct FlushForwardedTransactions;
+
+impl Handler<FlushForwardedTransactions> for TxRequestHandler {
+    fn handle(&mut self, _msg: FlushForwardedTransactions) {
+        if let Err(err) = self.flush_forwarded_txs() {
+            tracing::debug!(target: "client", ?err, "Failed to flush the buffered transactions");
+        }
+    }
+}
+
 impl messaging::Actor for TxRequestHandler {}
 
+/// Periodically sends `FlushForwardedTransactions` to the handler when the
+/// circuit breaker is enabled, so that the buffered transactions are forwarded
+/// once the chunk producers can take them even if no new transactions arrive.
+pub fn start_tx_forwarding_flush_timer(
+    clock: Clock,
+    config: &TxForwardingConfig,
+    sender: Sender<FlushForwardedTransactions>,
+    future_spawner: &dyn FutureSpawner,
+) {
+    if !config.enabled {
+        return;
+    }
+    let flush_interval = config.flush_interval;
+    future_spawner.spawn("tx forwarding flush", async move {
+        loop {
+            clock.sleep(flush_interval).await;
+            sender.send(FlushForwardedTransactions);
+        }
+    });
+}
+
 pub fn spawn_tx_request_handler_actor(
     config: TxRequestHandlerConfig,
     tx_pool: Arc<Mutex<ShardedTransactionPool>>,
@@ -69,6 +111,7 @@ pub struct TxRequestHandlerConfig {
     pub tx_routing_height_horizon: u64,
     pub epoch_length: u64,
     pub transaction_validity_period: BlockHeightDelta,
+    pub tx_forwarding: TxForwardingConfig,
 }
 
 /// Accepts `process_tx` requests. Pushes the incoming transactions to the pool.
@@ -82,6 +125,8 @@ pub struct TxRequestHandler {
     validator_signer: MutableValidatorSigner,
     runtime: Arc<dyn RuntimeAdapter>,
     network_adapter: PeerManagerAdapter,
+    /// Shared by all the handler threads.
+    tx_forwarding: Arc<Mutex<TxForwardingCircuitBreaker>>,
 }
 
 impl TxRequestHandler {
@@ -95,6 +140,8 @@ impl TxRequestHandler {
         network_adapter: PeerManagerAdapter,
     ) -> Self {
         let chain_store = runtime.store().chain_store();
+        let tx_forwarding =
+            Arc::new(Mutex::new(TxForwardingCircuitBreaker::new(config.tx_forwarding.clone())));
         Self {
             config,
             tx_pool,
@@ -104,6 +151,7 @@ impl TxRequestHandler {
             runtime,
             shard_tracker,
             network_adapter,
+            tx_forwarding,
         }
     }
 
@@ -152,8 +200,7 @@ impl TxRequestHandler {
         let gas_price = cur_block_header.next_gas_price();
         let epoch_id = self.epoch_manager.get_epoch_id_from_prev_block(&head.last_block_hash)?;
         let protocol_version = self.epoch_manager.get_epoch_protocol_version(&epoch_id)?;
-        let shard_layout =
-            self.epoch_manager.get_shard_layout_from_protocol_version(protocol_version);
+        let shard_layout = self.epoch_manager.get_shard_layout(&epoch_id)?;
         let receiver_shard =
             shard_layout.account_id_to_shard_id(signed_tx.transaction.receiver_id());
         let receiver_congestion_info =
@@ -191,8 +238,7 @@ impl TxRequestHandler {
                                 "Node has not caught up yet".to_string(),
                             ));
                         } else {
-                            self.forward_tx(&epoch_id, signed_tx, signer)?;
-                            return Ok(ProcessTxResponse::RequestRouted);
+                            return self.forward_tx(&epoch_id, signed_tx, signer);
                         }
                     }
                 };
@@ -247,8 +293,7 @@ impl TxRequestHandler {
             if !is_forwarded {
                 tracing::trace!(target: "client", ?shard_id, tx_hash = ?signed_tx.get_hash(), "Forwarding a transaction.");
                 metrics::TRANSACTION_RECEIVED_NON_VALIDATOR.inc();
-                self.forward_tx(&epoch_id, signed_tx, signer)?;
-                return Ok(ProcessTxResponse::RequestRouted);
+                return self.forward_tx(&epoch_id, signed_tx, signer);
             }
             tracing::trace!(target: "client", ?shard_id, tx_hash = ?signed_tx.get_hash(), "Non-validator received a forwarded transaction, dropping it.");
             metrics::TRANSACTION_RECEIVED_NON_VALIDATOR_FORWARDED.inc();
@@ -264,25 +309,132 @@ impl TxRequestHandler {
             return Ok(ProcessTxResponse::NoResponse);
         }
         // We are not tracking this shard, so there is no way to validate this tx. Just rerouting.
-        self.forward_tx(&epoch_id, signed_tx, signer).map(|()| ProcessTxResponse::RequestRouted)
+        self.forward_tx(&epoch_id, signed_tx, signer)
     }
 
     /// Forwards given transaction to upcoming validators.
+    ///
+    /// With the circuit breaker enabled, the overloaded and unresponsive validators are skipped.
+    /// If none of the validators can take the transaction, it's buffered and forwarded at the
+    /// next heights.
     fn forward_tx(
         &self,
         epoch_id: &EpochId,
         tx: &SignedTransaction,
         signer: &Option<Arc<ValidatorSigner>>,
+    ) -> Result<ProcessTxResponse, near_client_primitives::types::Error> {
+        // Use the header head to make sure the list of validators is as
+        // up-to-date as possible.
+        let head = self.chain_store.header_head()?;
+        if !self.config.tx_forwarding.enabled {
+            let (shard_id, validators) =
+                self.get_forwarding_targets(&head, epoch_id, tx, signer)?;
+            self.send_forwarded_tx(validators, tx, shard_id, signer);
+            return Ok(ProcessTxResponse::RequestRouted);
+        }
+
+        let mut breaker = self.tx_forwarding.lock().unwrap();
+        let mut batch = vec![];
+        self.retry_buffered_txs(&mut breaker, &head, signer, &mut batch)?;
+        let buffered = BufferedTx { epoch_id: *epoch_id, tx: tx.clone(), buffered_at: head.height };
+        let response = self.forward_or_buffer_tx(&mut breaker, &head, buffered, signer, &mut batch);
+        // Only send the batch once it's built and the circuit breaker is released, so that the
+        // other handler threads don't wait on the network.
+        drop(breaker);
+        self.send_batch(batch, signer);
+        response
+    }
+
+    /// Forwards the buffered transactions which the chunk producers can take at the current
+    /// height. Called periodically, so that the buffered transactions don't wait for a new
+    /// transaction to arrive.
+    fn flush_forwarded_txs(&self) -> Result<(), near_client_primitives::types::Error> {
+        if !self.config.tx_forwarding.enabled {
+            return Ok(());
+        }
+        let signer = self.validator_signer.get();
+        let head = self.chain_store.header_head()?;
+        let mut breaker = self.tx_forwarding.lock().unwrap();
+        let mut batch = vec![];
+        self.retry_buffered_txs(&mut breaker, &head, &signer, &mut batch)?;
+        drop(breaker);
+        self.send_batch(batch, &signer);
+        Ok(())
+    }
+
+    /// Adds the buffered transactions to `batch` if the height moved since they were last
+    /// retried, and buffers again those which still can't be forwarded.
+    fn retry_buffered_txs(
+        &self,
+        breaker: &mut TxForwardingCircuitBreaker,
+        head: &Tip,
+        signer: &Option<Arc<ValidatorSigner>>,
+        batch: &mut Vec<(Vec<AccountId>, SignedTransaction, ShardId)>,
     ) -> Result<(), near_client_primitives::types::Error> {
+        self.observe_missed_chunks(breaker)?;
+        for buffered in breaker.advance_to_height(head.height) {
+            let tx_hash = buffered.tx.get_hash();
+            if let Err(err) = self.forward_or_buffer_tx(breaker, head, buffered, signer, batch) {
+                tracing::debug!(target: "client", ?tx_hash, ?err, "Failed to forward a buffered transaction, dropping it");
+            }
+        }
+        Ok(())
+    }
+
+    fn send_batch(
+        &self,
+        batch: Vec<(Vec<AccountId>, SignedTransaction, ShardId)>,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) {
+        for (validators, tx, shard_id) in batch {
+            self.send_forwarded_tx(validators, &tx, shard_id, signer);
+        }
+    }
+
+    /// Adds the transaction to `batch` with the chunk producers it should be forwarded to, or
+    /// buffers it if they are all overloaded.
+    fn forward_or_buffer_tx(
+        &self,
+        breaker: &mut TxForwardingCircuitBreaker,
+        head: &Tip,
+        buffered: BufferedTx,
+        signer: &Option<Arc<ValidatorSigner>>,
+        batch: &mut Vec<(Vec<AccountId>, SignedTransaction, ShardId)>,
+    ) -> Result<ProcessTxResponse, near_client_primitives::types::Error> {
+        let (shard_id, validators) =
+            self.get_forwarding_targets(head, &buffered.epoch_id, &buffered.tx, signer)?;
+        if validators.is_empty() {
+            return Ok(ProcessTxResponse::RequestRouted);
+        }
+        let validators = breaker.select(validators);
+        if !validators.is_empty() {
+            batch.push((validators, buffered.tx, shard_id));
+            return Ok(ProcessTxResponse::RequestRouted);
+        }
+        let tx_hash = buffered.tx.get_hash();
+        if breaker.buffer(buffered) {
+            tracing::trace!(target: "client", ?tx_hash, ?shard_id, "Chunk producers are overloaded, buffering the transaction");
+            Ok(ProcessTxResponse::RequestRouted)
+        } else {
+            tracing::debug!(target: "client", ?tx_hash, ?shard_id, "Chunk producers are overloaded and the buffer is full, rejecting the transaction");
+            Ok(ProcessTxResponse::ForwardingOverloaded { shard_id })
+        }
+    }
+
+    /// Returns the shard of the transaction and the upcoming validators it should be forwarded to.
+    fn get_forwarding_targets(
+        &self,
+        head: &Tip,
+        epoch_id: &EpochId,
+        tx: &SignedTransaction,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) -> Result<(ShardId, HashSet<AccountId>), near_client_primitives::types::Error> {
         let shard_id = account_id_to_shard_id(
             self.epoch_manager.as_ref(),
             tx.transaction.signer_id(),
             epoch_id,
         )?;
-        // Use the header head to make sure the list of validators is as
-        // up-to-date as possible.
-        let head = self.chain_store.header_head()?;
-        let maybe_next_epoch_id = self.get_next_epoch_id_if_at_boundary(&head)?;
+        let maybe_next_epoch_id = self.get_next_epoch_id_if_at_boundary(head)?;
 
         let mut validators = HashSet::new();
         for horizon in (2..=self.config.tx_routing_height_horizon)
@@ -319,6 +471,16 @@ impl TxRequestHandler {
         if let Some(account_id) = signer.as_ref().map(|bp| bp.validator_id()) {
             validators.remove(account_id);
         }
+        Ok((shard_id, validators))
+    }
+
+    fn send_forwarded_tx(
+        &self,
+        validators: impl IntoIterator<Item = AccountId>,
+        tx: &SignedTransaction,
+        shard_id: ShardId,
+        signer: &Option<Arc<ValidatorSigner>>,
+    ) {
         for validator in validators {
             let tx_hash = tx.get_hash();
             tracing::trace!(target: "client", me = ?signer.as_ref().map(|bp| bp.validator_id()), ?tx_hash, ?validator, ?shard_id, "Routing a transaction");
@@ -328,7 +490,43 @@ impl TxRequestHandler {
                 NetworkRequests::ForwardTx(validator, tx.clone()),
             ));
         }
+    }
+
+    /// Tells the circuit breaker which chunk producers missed their chunks in the blocks since
+    /// the last observed one. Only the last `missing_chunks_threshold` blocks are looked at.
+    fn observe_missed_chunks(
+        &self,
+        breaker: &mut TxForwardingCircuitBreaker,
+    ) -> Result<(), near_client_primitives::types::Error> {
+        let head = self.chain_store.head()?;
+        let mut headers = vec![];
+        let mut block_hash = head.last_block_hash;
+        while headers.len() < self.config.tx_forwarding.missing_chunks_threshold as usize
+            && breaker.last_observed_block() != Some(&block_hash)
+        {
+            let header = self.chain_store.get_block_header(&block_hash)?;
+            if header.is_genesis() {
+                break;
+            }
+            block_hash = *header.prev_hash();
+            headers.push(header);
+        }
 
+        for header in headers.iter().rev() {
+            let shard_layout = self.epoch_manager.get_shard_layout(header.epoch_id())?;
+            for (shard_id, is_included) in shard_layout.shard_ids().zip(header.chunk_mask()) {
+                let chunk_producer = self
+                    .epoch_manager
+                    .get_chunk_producer_info(&ChunkProductionKey {
+                        epoch_id: *header.epoch_id(),
+                        height_created: header.height(),
+                        shard_id,
+                    })?
+                    .take_account_id();
+                breaker.observe_chunk(&chunk_producer, header.height(), *is_included);
+            }
+        }
+        breaker.set_last_observed_block(head.last_block_hash);
         Ok(())
     }
 
@@ -371,6 +569,8 @@ impl TxRequestHandler {
         signer: &Option<Arc<ValidatorSigner>>,
     ) -> Result<(), near_client_primitives::types::Error> {
         let head = self.chain_store.head()?;
+        // The transaction is already in the pool of this validator, so it doesn't matter whether
+        // the other validators are overloaded.
         if let Some(next_epoch_id) = self.get_next_epoch_id_if_at_boundary(&head)? {
             self.forward_tx(&next_epoch_id, tx, signer)?;
         } else {
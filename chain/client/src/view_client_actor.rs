@@ -16,16 +16,18 @@ use near_chain::{
 };
 
 use near_chain_configs::{ClientConfig, MutableValidatorSigner, ProtocolConfigView};
-use near_chain_primitives::error::EpochErrorResultToChainError;
+use near_chain_primitives::error::{ChainErrorContext, EpochErrorResultToChainError};
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
-    GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError, GetMaintenanceWindows,
-    GetMaintenanceWindowsError, GetNextLightClientBlockError, GetProtocolConfig,
-    GetProtocolConfigError, GetReceipt, GetReceiptError, GetSplitStorageInfo,
+    BatchQuery, BatchQueryResponse, Error, GetBlock, GetBlockError, GetBlockProof,
+    GetBlockProofError, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunkError,
+    GetExecutionOutcome, GetExecutionOutcomeError, GetExecutionOutcomesForBlock, GetGasPrice,
+    GetGasPriceError, GetMaintenanceWindows, GetMaintenanceWindowsError,
+    GetNextEpochShardAssignments, GetNextEpochShardAssignmentsError, GetNextLightClientBlockError,
+    GetProtocolConfig, GetProtocolConfigError, GetReceipt, GetReceiptError, GetShardLayoutAtBlock,
+    GetShardLayoutAtBlockError, GetShardLineage, GetShardLineageError, GetSplitStorageInfo,
     GetSplitStorageInfoError, GetStateChangesError, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
-    TxStatus, TxStatusError,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetTxExecutionProfile,
+    GetTxExecutionProfileError, GetValidatorInfoError, Query, QueryError, TxStatus, TxStatusError,
 };
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_assignment::{account_id_to_shard_id, shard_id_to_uid};
@@ -40,11 +42,14 @@ use near_network::types::{
 };
 use near_performance_metrics_macros::perf;
 use near_primitives::block::{Block, BlockHeader};
+use near_primitives::chunk_apply_stats::ChunkApplyStats;
 use near_primitives::epoch_info::EpochInfo;
+use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{PartialMerkleTree, merklize};
 use near_primitives::network::AnnounceAccount;
 use near_primitives::receipt::Receipt;
+use near_primitives::shard_layout::{ShardLineage, ShardUId};
 use near_primitives::sharding::ShardChunk;
 use near_primitives::state_sync::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV3,
@@ -53,17 +58,20 @@ use near_primitives::stateless_validation::ChunkProductionKey;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockId, BlockReference, EpochReference, Finality, MaybeBlockId,
-    ShardId, SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
+    ShardId, StateRoot, SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
 };
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView, ExecutionStatusView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
-    LightClientBlockView, MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView,
+    AccountShardAssignmentView, AccountShardView, BlockView, ChunkView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
+    FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView, LightClientBlockView,
+    MaintenanceWindowsView, NextEpochShardAssignmentsView, QueryRequest, QueryResponse,
+    ReceiptExecutionProfileView, ReceiptView, ShardChunkProducersView, ShardLayoutAtBlockView,
     SignedTransactionView, SplitStorageInfoView, StateChangesKindsView, StateChangesView,
     TxExecutionStatus, TxStatusView,
 };
+use near_store::adapter::trie_store::get_shard_uid_mapping;
 use near_store::flat::{FlatStorageReadyStatus, FlatStorageStatus};
 use near_store::{COLD_HEAD_KEY, DBCol, FINAL_HEAD_KEY, HEAD_KEY};
 use std::cmp::Ordering;
@@ -349,21 +357,89 @@ impl ViewClientActorInner {
         Ok(windows)
     }
 
+    /// Returns the shard of the account in the shard layout of the epoch of
+    /// the given block, which differs from the current one if there was a
+    /// resharding since. This is what lets archival nodes serve queries on
+    /// old blocks: the trie of the historical shard reads its State through
+    /// `DBCol::StateShardUIdMapping`, which the cold store keeps as well.
+    fn get_account_shard_at_block(
+        &self,
+        account_id: &AccountId,
+        header: &BlockHeader,
+    ) -> Result<AccountShardView, near_chain::Error> {
+        let shard_layout =
+            self.epoch_manager.get_shard_layout(header.epoch_id()).into_chain_error()?;
+        let shard_id = shard_layout.account_id_to_shard_id(account_id);
+        let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+        let state_shard_uid = get_shard_uid_mapping(&self.chain.chain_store().store(), shard_uid);
+        Ok(AccountShardView {
+            account_id: account_id.clone(),
+            shard_id,
+            shard_uid,
+            state_shard_uid,
+        })
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<QueryResponse, QueryError> {
-        let header = self.get_block_header_by_reference(&msg.block_reference);
-        let header = match header {
+        let header = self.get_query_block_header(&msg.block_reference)?;
+        let account_shard = self.get_query_account_shard(&header, &msg.request)?;
+        let state_root = self.get_query_state_root(&header, &account_shard)?;
+        self.query_state(&header, account_shard.shard_uid, &state_root, &msg.request)
+    }
+
+    fn handle_batch_query(&mut self, msg: BatchQuery) -> Result<BatchQueryResponse, QueryError> {
+        let header = self.get_query_block_header(&msg.block_reference)?;
+        // The state root of each shard is looked up once for all the requests
+        // about its accounts.
+        let mut state_roots = HashMap::new();
+        let results = msg
+            .requests
+            .iter()
+            .map(|request| {
+                let account_shard = self.get_query_account_shard(&header, request)?;
+                let state_root = match state_roots.get(&account_shard.shard_uid) {
+                    Some(state_root) => *state_root,
+                    None => {
+                        let state_root = self.get_query_state_root(&header, &account_shard)?;
+                        state_roots.insert(account_shard.shard_uid, state_root);
+                        state_root
+                    }
+                };
+                self.query_state(&header, account_shard.shard_uid, &state_root, request)
+            })
+            .collect();
+        Ok(BatchQueryResponse {
+            block_height: header.height(),
+            block_hash: *header.hash(),
+            results,
+        })
+    }
+
+    fn get_query_block_header(
+        &self,
+        block_reference: &BlockReference,
+    ) -> Result<BlockHeader, QueryError> {
+        let header = self.get_block_header_by_reference(block_reference);
+        match header {
             Ok(Some(header)) => Ok(header),
             Ok(None) => Err(QueryError::NoSyncedBlocks),
             Err(near_chain::near_chain_primitives::Error::DBNotFoundErr(_)) => {
-                Err(QueryError::UnknownBlock { block_reference: msg.block_reference })
+                Err(QueryError::UnknownBlock { block_reference: block_reference.clone() })
             }
             Err(near_chain::near_chain_primitives::Error::IOErr(err)) => {
                 Err(QueryError::InternalError { error_message: err.to_string() })
             }
             Err(err) => Err(QueryError::Unreachable { error_message: err.to_string() }),
-        }?;
+        }
+    }
 
-        let account_id = match &msg.request {
+    /// Returns the shard of the account the request is about.
+    fn get_query_account_shard(
+        &self,
+        header: &BlockHeader,
+        request: &QueryRequest,
+    ) -> Result<AccountShardView, QueryError> {
+        let account_id = match request {
             QueryRequest::ViewAccount { account_id, .. } => account_id,
             QueryRequest::ViewState { account_id, .. } => account_id,
             QueryRequest::ViewAccessKey { account_id, .. } => account_id,
@@ -371,13 +447,16 @@ impl ViewClientActorInner {
             QueryRequest::CallFunction { account_id, .. } => account_id,
             QueryRequest::ViewCode { account_id, .. } => account_id,
         };
-        let shard_id =
-            account_id_to_shard_id(self.epoch_manager.as_ref(), account_id, header.epoch_id())
-                .map_err(|err| QueryError::InternalError { error_message: err.to_string() })?;
-        let shard_uid =
-            shard_id_to_uid(self.epoch_manager.as_ref(), shard_id, header.epoch_id())
-                .map_err(|err| QueryError::InternalError { error_message: err.to_string() })?;
+        self.get_account_shard_at_block(account_id, header)
+            .map_err(|err| QueryError::InternalError { error_message: err.to_string() })
+    }
 
+    fn get_query_state_root(
+        &self,
+        header: &BlockHeader,
+        account_shard: &AccountShardView,
+    ) -> Result<StateRoot, QueryError> {
+        let AccountShardView { shard_id, shard_uid, .. } = *account_shard;
         let tip = self.chain.head();
         let chunk_extra =
             self.chain.get_chunk_extra(header.hash(), &shard_uid).map_err(|err| match err {
@@ -401,7 +480,16 @@ impl ViewClientActorInner {
                 _ => QueryError::Unreachable { error_message: err.to_string() },
             })?;
 
-        let state_root = chunk_extra.state_root();
+        Ok(*chunk_extra.state_root())
+    }
+
+    fn query_state(
+        &self,
+        header: &BlockHeader,
+        shard_uid: ShardUId,
+        state_root: &StateRoot,
+        request: &QueryRequest,
+    ) -> Result<QueryResponse, QueryError> {
         match self.runtime.query(
             shard_uid,
             state_root,
@@ -410,7 +498,7 @@ impl ViewClientActorInner {
             header.prev_hash(),
             header.hash(),
             header.epoch_id(),
-            &msg.request,
+            request,
         ) {
             Ok(query_response) => Ok(query_response),
             Err(query_error) => Err(match query_error {
@@ -717,6 +805,16 @@ impl Handler<Query> for ViewClientActorInner {
     }
 }
 
+impl Handler<BatchQuery> for ViewClientActorInner {
+    #[perf]
+    fn handle(&mut self, msg: BatchQuery) -> Result<BatchQueryResponse, QueryError> {
+        tracing::debug!(target: "client", num_requests = msg.requests.len(), ?msg.block_reference);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["BatchQuery"]).start_timer();
+        self.handle_batch_query(msg)
+    }
+}
+
 /// Handles retrieving block from the chain.
 impl Handler<GetBlock> for ViewClientActorInner {
     #[perf]
@@ -756,6 +854,18 @@ fn get_chunk_from_block(
     block: Block,
     shard_id: ShardId,
     chain: &Chain,
+) -> Result<ShardChunk, near_chain::Error> {
+    let block_hash = *block.hash();
+    let block_height = block.header().height();
+    get_chunk_from_block_impl(block, shard_id, chain)
+        .with_block_context(&block_hash, block_height)
+        .with_shard_context(shard_id)
+}
+
+fn get_chunk_from_block_impl(
+    block: Block,
+    shard_id: ShardId,
+    chain: &Chain,
 ) -> Result<ShardChunk, near_chain::Error> {
     let epoch_id = block.header().epoch_id();
     let shard_layout = chain.epoch_manager.get_shard_layout(epoch_id)?;
@@ -1224,6 +1334,162 @@ impl Handler<GetProtocolConfig> for ViewClientActorInner {
     }
 }
 
+impl Handler<GetShardLayoutAtBlock> for ViewClientActorInner {
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: GetShardLayoutAtBlock,
+    ) -> Result<ShardLayoutAtBlockView, GetShardLayoutAtBlockError> {
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetShardLayoutAtBlock"])
+            .start_timer();
+        let header = match self.get_block_header_by_reference(&msg.block_reference)? {
+            None => {
+                return Err(GetShardLayoutAtBlockError::UnknownBlock(
+                    "EarliestAvailable".to_string(),
+                ));
+            }
+            Some(header) => header,
+        };
+        let shard_layout =
+            self.epoch_manager.get_shard_layout(header.epoch_id()).into_chain_error()?;
+        let account_shard = msg
+            .account_id
+            .map(|account_id| self.get_account_shard_at_block(&account_id, &header))
+            .transpose()?;
+        Ok(ShardLayoutAtBlockView {
+            block_hash: *header.hash(),
+            block_height: header.height(),
+            epoch_id: *header.epoch_id(),
+            shard_layout,
+            account_shard,
+        })
+    }
+}
+
+impl Handler<GetShardLineage> for ViewClientActorInner {
+    #[perf]
+    fn handle(&mut self, msg: GetShardLineage) -> Result<ShardLineage, GetShardLineageError> {
+        tracing::debug!(target: "client", ?msg);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetShardLineage"]).start_timer();
+        // The lineage goes up to the next epoch, whose shard layout is already
+        // known.
+        let head = self.chain.head()?;
+        self.epoch_manager.shard_lineage(msg.shard_uid, &msg.epoch_id, &head.next_epoch_id).map_err(
+            |err| match err {
+                EpochError::ShardingError(error_message) => {
+                    GetShardLineageError::InvalidShard(error_message)
+                }
+                err => near_chain_primitives::Error::from(err).into(),
+            },
+        )
+    }
+}
+
+impl Handler<GetNextEpochShardAssignments> for ViewClientActorInner {
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: GetNextEpochShardAssignments,
+    ) -> Result<NextEpochShardAssignmentsView, GetNextEpochShardAssignmentsError> {
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetNextEpochShardAssignments"])
+            .start_timer();
+        let header = match self.get_block_header_by_reference(&msg.block_reference)? {
+            None => {
+                return Err(GetNextEpochShardAssignmentsError::UnknownBlock(
+                    "EarliestAvailable".to_string(),
+                ));
+            }
+            Some(header) => header,
+        };
+        let epoch_manager = self.epoch_manager.as_ref();
+        let next_epoch_id = *header.next_epoch_id();
+        let next_shard_layout =
+            epoch_manager.get_shard_layout(&next_epoch_id).into_chain_error()?;
+        let mut chunk_producers = vec![];
+        for shard_id in next_shard_layout.shard_ids() {
+            chunk_producers.push(ShardChunkProducersView {
+                shard_id,
+                chunk_producers: epoch_manager
+                    .get_epoch_chunk_producers_for_shard(&next_epoch_id, shard_id)
+                    .into_chain_error()?,
+            });
+        }
+        let chunk_validators = epoch_manager
+            .get_epoch_all_validators(&next_epoch_id)
+            .into_chain_error()?
+            .into_iter()
+            .map(|validator| validator.take_account_id())
+            .collect();
+        let account_assignment = match msg.account_id {
+            None => None,
+            Some(account_id) => {
+                let shard_ids = chunk_producers
+                    .iter()
+                    .filter(|shard| shard.chunk_producers.contains(&account_id))
+                    .map(|shard| shard.shard_id)
+                    .collect();
+                let shards_to_download = epoch_manager
+                    .get_shards_to_download_in_next_epoch(header.hash(), &account_id)
+                    .into_chain_error()?;
+                Some(AccountShardAssignmentView { account_id, shard_ids, shards_to_download })
+            }
+        };
+        Ok(NextEpochShardAssignmentsView {
+            block_hash: *header.hash(),
+            block_height: header.height(),
+            epoch_id: *header.epoch_id(),
+            next_epoch_id,
+            next_shard_layout,
+            chunk_producers,
+            chunk_validators,
+            account_assignment,
+        })
+    }
+}
+
+impl Handler<GetTxExecutionProfile> for ViewClientActorInner {
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: GetTxExecutionProfile,
+    ) -> Result<Vec<ReceiptExecutionProfileView>, GetTxExecutionProfileError> {
+        tracing::debug!(target: "client", ?msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetTxExecutionProfile"])
+            .start_timer();
+        let outcome = self.chain.get_partial_transaction_result(&msg.tx_hash)?;
+        let mut profiles = vec![];
+        for receipt_outcome in outcome.receipts_outcome {
+            let block_hash = receipt_outcome.block_hash;
+            let epoch_id = self.epoch_manager.get_epoch_id(&block_hash).into_chain_error()?;
+            let shard_id = account_id_to_shard_id(
+                self.epoch_manager.as_ref(),
+                &receipt_outcome.outcome.executor_id,
+                &epoch_id,
+            )
+            .into_chain_error()?;
+            let Some(ChunkApplyStats::V0(stats)) =
+                self.chain.chain_store().get_chunk_apply_stats(&block_hash, &shard_id)?
+            else {
+                continue;
+            };
+            let profile = stats
+                .receipt_profiles
+                .into_iter()
+                .find(|profile| profile.receipt_id == receipt_outcome.id);
+            if let Some(profile) = profile {
+                profiles.push(ReceiptExecutionProfileView::new(profile, block_hash, shard_id));
+            }
+        }
+        Ok(profiles)
+    }
+}
+
 #[cfg(feature = "test_features")]
 use crate::NetworkAdversarialMessage;
 
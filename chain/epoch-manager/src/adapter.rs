@@ -7,7 +7,7 @@ use near_primitives::epoch_info::EpochInfo;
 use near_primitives::epoch_manager::{EpochConfig, ShardConfig};
 use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{ShardLayout, ShardLineage};
 use near_primitives::stateless_validation::ChunkProductionKey;
 use near_primitives::stateless_validation::validator_assignment::ChunkValidatorAssignments;
 use near_primitives::types::validator_stake::ValidatorStake;
@@ -72,9 +72,23 @@ pub trait EpochManagerAdapter: Send + Sync {
         Ok(epoch_info.get_validator(validator_id).account_id().clone())
     }
 
+    /// The config of the epoch with the given protocol version and height,
+    /// which may change the shard layout of the protocol version, see
+    /// `ReshardingProposal`.
+    fn get_epoch_config_for_epoch_height(
+        &self,
+        protocol_version: ProtocolVersion,
+        _epoch_height: EpochHeight,
+    ) -> EpochConfig {
+        self.get_epoch_config_from_protocol_version(protocol_version)
+    }
+
     fn get_epoch_config(&self, epoch_id: &EpochId) -> Result<EpochConfig, EpochError> {
-        let protocol_version = self.get_epoch_info(epoch_id)?.protocol_version();
-        Ok(self.get_epoch_config_from_protocol_version(protocol_version))
+        let epoch_info = self.get_epoch_info(epoch_id)?;
+        Ok(self.get_epoch_config_for_epoch_height(
+            epoch_info.protocol_version(),
+            epoch_info.epoch_height(),
+        ))
     }
 
     /// Returns true, if given hash is in an epoch that already finished.
@@ -237,6 +251,56 @@ pub trait EpochManagerAdapter: Send + Sync {
         self.get_epoch_config_from_protocol_version(protocol_version).shard_layout
     }
 
+    /// The shard layouts of the epochs with the protocol version, the one of
+    /// the config of the protocol version first.
+    fn get_shard_layouts_from_protocol_version(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Vec<ShardLayout> {
+        vec![self.get_shard_layout_from_protocol_version(protocol_version)]
+    }
+
+    /// The shard layouts of the epochs of the chain up to the given one,
+    /// oldest first, each one once.
+    fn get_shard_layouts_up_to_epoch(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ShardLayout>, EpochError>;
+
+    /// The shards of the shard layouts of the earlier epochs of the chain that
+    /// the shard of the epoch descends from, and the shards of the shard
+    /// layouts of the later epochs, up to `last_epoch_id`, that it is split
+    /// into.
+    fn shard_lineage(
+        &self,
+        shard_uid: ShardUId,
+        epoch_id: &EpochId,
+        last_epoch_id: &EpochId,
+    ) -> Result<ShardLineage, EpochError> {
+        let shard_layout = self.get_shard_layout(epoch_id)?;
+        if !shard_layout.shard_uids().any(|uid| uid == shard_uid) {
+            return Err(EpochError::ShardingError(format!(
+                "{shard_uid} is not a shard of the shard layout of epoch {epoch_id:?}"
+            )));
+        }
+        // The shard layouts up to the epochs walked through for the last epoch
+        // are cached, so the second lookup doesn't walk the chain again.
+        let shard_layouts = self.get_shard_layouts_up_to_epoch(last_epoch_id)?;
+        let prev_shard_layouts = self.get_shard_layouts_up_to_epoch(epoch_id)?;
+        let Some(index) = prev_shard_layouts.iter().position(|layout| layout == &shard_layout)
+        else {
+            return Err(EpochError::ShardingError(format!(
+                "the shard layout of epoch {epoch_id:?} is not in the shard layouts of the chain"
+            )));
+        };
+        if !shard_layouts.starts_with(&prev_shard_layouts) {
+            return Err(EpochError::ShardingError(format!(
+                "epoch {epoch_id:?} is not before epoch {last_epoch_id:?}"
+            )));
+        }
+        Ok(ShardLineage::new(shard_uid, &shard_layouts, index)?)
+    }
+
     /// Get [`EpochId`] from a block belonging to the epoch.
     fn get_epoch_id(&self, block_hash: &CryptoHash) -> Result<EpochId, EpochError> {
         self.get_block_info(block_hash).map(|block_info| *block_info.epoch_id())
@@ -689,6 +753,38 @@ pub trait EpochManagerAdapter: Send + Sync {
         self.cares_about_shard_in_epoch(&prev_epoch_id, account_id, parent_shard_id)
     }
 
+    /// Returns the shards that `account_id` is assigned to as a chunk producer
+    /// in the epoch after the epoch of `block_hash` but doesn't have the state
+    /// of, because it isn't assigned to them in the epoch of the block. If the
+    /// shard layout changes, a child shard is only downloaded when the account
+    /// isn't assigned to its parent shard.
+    fn get_shards_to_download_in_next_epoch(
+        &self,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<Vec<ShardId>, EpochError> {
+        let epoch_id = self.get_epoch_id(block_hash)?;
+        let next_epoch_id = self.get_next_epoch_id(block_hash)?;
+        let shard_layout = self.get_shard_layout(&epoch_id)?;
+        let next_shard_layout = self.get_shard_layout(&next_epoch_id)?;
+
+        let mut shards_to_download = vec![];
+        for next_shard_id in next_shard_layout.shard_ids() {
+            if !self.cares_about_shard_in_epoch(&next_epoch_id, account_id, next_shard_id)? {
+                continue;
+            }
+            let shard_id = if shard_layout == next_shard_layout {
+                next_shard_id
+            } else {
+                next_shard_layout.get_parent_shard_id(next_shard_id)?
+            };
+            if !self.cares_about_shard_in_epoch(&epoch_id, account_id, shard_id)? {
+                shards_to_download.push(next_shard_id);
+            }
+        }
+        Ok(shards_to_download)
+    }
+
     fn will_shard_layout_change(&self, parent_hash: &CryptoHash) -> Result<bool, EpochError> {
         let epoch_id = self.get_epoch_id_from_prev_block(parent_hash)?;
         let next_epoch_id = self.get_next_epoch_id_from_prev_block(parent_hash)?;
@@ -800,15 +896,25 @@ pub trait EpochManagerAdapter: Send + Sync {
     /// Please note that shard 4 is not returned even though it is split later
     /// on. That is because it is a child of another parent and it should
     /// already be loaded into memory after the first resharding.
+    ///
+    /// The shard layouts of the future epochs include the resharding proposal
+    /// of the protocol version of the head, if it isn't applied yet.
     fn get_shard_uids_pending_resharding(
         &self,
-        head_protocol_version: ProtocolVersion,
+        head_epoch_id: &EpochId,
         client_protocol_version: ProtocolVersion,
     ) -> Result<HashSet<ShardUId>, Error> {
-        let head_shard_layout = self.get_shard_layout_from_protocol_version(head_protocol_version);
+        let head_protocol_version = self.get_epoch_protocol_version(head_epoch_id)?;
+        let head_shard_layout = self.get_shard_layout(head_epoch_id)?;
         let mut shard_layouts = vec![];
-        for protocol_version in head_protocol_version + 1..=client_protocol_version {
-            let shard_layout = self.get_shard_layout_from_protocol_version(protocol_version);
+        let future_shard_layouts = self
+            .get_shard_layouts_from_protocol_version(head_protocol_version)
+            .into_iter()
+            .skip_while(|shard_layout| shard_layout != &head_shard_layout)
+            .chain((head_protocol_version + 1..=client_protocol_version).flat_map(
+                |protocol_version| self.get_shard_layouts_from_protocol_version(protocol_version),
+            ));
+        for shard_layout in future_shard_layouts {
             if shard_layout == head_shard_layout {
                 continue;
             }
@@ -862,6 +968,15 @@ impl EpochManagerAdapter for EpochManagerHandle {
         epoch_manager.get_epoch_config(protocol_version)
     }
 
+    fn get_epoch_config_for_epoch_height(
+        &self,
+        protocol_version: ProtocolVersion,
+        epoch_height: EpochHeight,
+    ) -> EpochConfig {
+        let epoch_manager = self.read();
+        epoch_manager.get_epoch_config_for_epoch_height(protocol_version, epoch_height)
+    }
+
     fn get_epoch_info(&self, epoch_id: &EpochId) -> Result<Arc<EpochInfo>, EpochError> {
         let epoch_manager = self.read();
         epoch_manager.get_epoch_info(epoch_id)
@@ -884,6 +999,22 @@ impl EpochManagerAdapter for EpochManagerHandle {
         epoch_manager.get_epoch_config(protocol_version).shard_layout
     }
 
+    fn get_shard_layouts_from_protocol_version(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Vec<ShardLayout> {
+        let epoch_manager = self.read();
+        epoch_manager.config.shard_layouts_for_protocol_version(protocol_version)
+    }
+
+    fn get_shard_layouts_up_to_epoch(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ShardLayout>, EpochError> {
+        let epoch_manager = self.read();
+        epoch_manager.get_shard_layouts_up_to_epoch(epoch_id)
+    }
+
     fn get_epoch_id(&self, block_hash: &CryptoHash) -> Result<EpochId, EpochError> {
         let epoch_manager = self.read();
         epoch_manager.get_epoch_id(block_hash)
@@ -21,9 +21,9 @@ use near_primitives::shard_layout::ShardLayout;
 use near_primitives::stateless_validation::validator_assignment::ChunkValidatorAssignments;
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{
-    AccountId, ApprovalStake, Balance, BlockChunkValidatorStats, BlockHeight, ChunkStats, EpochId,
-    EpochInfoProvider, ShardId, ValidatorId, ValidatorInfoIdentifier, ValidatorKickoutReason,
-    ValidatorStats,
+    AccountId, ApprovalStake, Balance, BlockChunkValidatorStats, BlockHeight, ChunkStats,
+    EpochHeight, EpochId, EpochInfoProvider, ShardId, ValidatorId, ValidatorInfoIdentifier,
+    ValidatorKickoutReason, ValidatorStats,
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
@@ -126,6 +126,9 @@ pub struct EpochManager {
     blocks_info: SyncLruCache<CryptoHash, Arc<BlockInfo>>,
     /// Cache of epoch id to epoch start height
     epoch_id_to_start: SyncLruCache<EpochId, BlockHeight>,
+    /// Cache of the shard layouts of the epochs of the chain up to an epoch,
+    /// see [`Self::get_shard_layouts_up_to_epoch`].
+    shard_layouts_up_to_epoch: SyncLruCache<EpochId, Arc<[ShardLayout]>>,
     /// Epoch validators ordered by `block_producer_settlement`.
     epoch_validators_ordered: SyncLruCache<EpochId, Arc<[ValidatorStake]>>,
     /// Unique validators ordered by `block_producer_settlement`.
@@ -230,6 +233,7 @@ impl EpochManager {
             epochs_info: SyncLruCache::new(EPOCH_CACHE_SIZE),
             blocks_info: SyncLruCache::new(BLOCK_CACHE_SIZE),
             epoch_id_to_start: SyncLruCache::new(EPOCH_CACHE_SIZE),
+            shard_layouts_up_to_epoch: SyncLruCache::new(EPOCH_CACHE_SIZE),
             epoch_validators_ordered: SyncLruCache::new(EPOCH_CACHE_SIZE),
             epoch_validators_ordered_unique: SyncLruCache::new(EPOCH_CACHE_SIZE),
             epoch_chunk_producers_unique: SyncLruCache::new(EPOCH_CACHE_SIZE),
@@ -635,9 +639,23 @@ impl EpochManager {
                 online_thresholds,
             )
         };
-        let next_next_epoch_config = self.config.for_protocol_version(next_next_epoch_version);
-        let next_epoch_version = next_epoch_info.protocol_version();
-        let next_shard_layout = self.config.for_protocol_version(next_epoch_version).shard_layout;
+        // The shard layout may change on the same protocol version, see
+        // `ReshardingProposal`.
+        let next_next_epoch_height = next_epoch_info.epoch_height() + 1;
+        let next_next_epoch_config =
+            self.config.for_epoch(next_next_epoch_version, next_next_epoch_height);
+        let next_shard_layout = self.get_shard_layout_for_epoch(&next_epoch_info);
+        if !self.config.can_shard_layout_follow(
+            next_epoch_info.protocol_version(),
+            &next_shard_layout,
+            next_next_epoch_version,
+            &next_next_epoch_config.shard_layout,
+        ) {
+            return Err(EpochError::ShardingError(format!(
+                "shard layout of epoch {} with protocol version {} can't follow the shard layout of the previous epoch",
+                next_next_epoch_height, next_next_epoch_version,
+            )));
+        }
         let has_same_shard_layout = next_shard_layout == next_next_epoch_config.shard_layout;
         let next_next_epoch_info = match proposals_to_epoch_info(
             &next_next_epoch_config,
@@ -670,8 +688,8 @@ impl EpochManager {
                next_next_epoch_info.epoch_height(),
                &next_next_epoch_id,
                next_next_epoch_info.protocol_version(),
-               self.config.for_protocol_version(next_next_epoch_info.protocol_version()).shard_layout,
-            self.config.for_protocol_version(next_next_epoch_info.protocol_version()));
+               self.get_shard_layout_for_epoch(&next_next_epoch_info),
+               next_next_epoch_config);
         // This epoch info is computed for the epoch after next (T+2),
         // where epoch_id of it is the hash of last block in this epoch (T).
         self.save_epoch_info(store_update, &next_next_epoch_id, Arc::new(next_next_epoch_info))?;
@@ -1311,9 +1329,114 @@ impl EpochManager {
         self.config.for_protocol_version(protocol_version)
     }
 
+    pub fn get_epoch_config_for_epoch_height(
+        &self,
+        protocol_version: ProtocolVersion,
+        epoch_height: EpochHeight,
+    ) -> EpochConfig {
+        self.config.for_epoch(protocol_version, epoch_height)
+    }
+
     pub fn get_shard_layout(&self, epoch_id: &EpochId) -> Result<ShardLayout, EpochError> {
-        let protocol_version = self.get_epoch_info(epoch_id)?.protocol_version();
-        Ok(self.get_shard_layout_from_protocol_version(protocol_version))
+        let epoch_info = self.get_epoch_info(epoch_id)?;
+        Ok(self.get_shard_layout_for_epoch(&epoch_info))
+    }
+
+    fn get_shard_layout_for_epoch(&self, epoch_info: &EpochInfo) -> ShardLayout {
+        self.config.for_epoch(epoch_info.protocol_version(), epoch_info.epoch_height()).shard_layout
+    }
+
+    /// The shard layouts of the epochs of the chain up to the given one, oldest
+    /// first, each one once. Unlike the shard layouts of the protocol versions,
+    /// they include the changes of the shard layout within a protocol version.
+    ///
+    /// The epochs are walked back from the given one until an epoch whose
+    /// shard layouts are cached. The block infos needed for the walk may be
+    /// garbage collected, the older epochs are final then and are taken from
+    /// the stored epoch infos by height.
+    pub fn get_shard_layouts_up_to_epoch(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ShardLayout>, EpochError> {
+        let shard_layouts =
+            self.shard_layouts_up_to_epoch.get_or_try_put(*epoch_id, |epoch_id| {
+                let mut epoch_ids = vec![];
+                let mut shard_layouts = vec![];
+                let mut current_epoch_id = *epoch_id;
+                loop {
+                    if let Some(cached) = self.shard_layouts_up_to_epoch.get(&current_epoch_id) {
+                        shard_layouts = cached.to_vec();
+                        break;
+                    }
+                    epoch_ids.push(current_epoch_id);
+                    if current_epoch_id == EpochId::default() {
+                        break;
+                    }
+                    match self.get_prev_epoch_id_of_epoch(&current_epoch_id)? {
+                        Some(prev_epoch_id) => current_epoch_id = prev_epoch_id,
+                        None => {
+                            let epoch_height =
+                                self.get_epoch_info(&current_epoch_id)?.epoch_height();
+                            shard_layouts = self.get_shard_layouts_of_stored_epochs(epoch_height);
+                            break;
+                        }
+                    }
+                }
+                // The epochs walked through are cached too, so that the
+                // lookups of the recent epochs don't walk again.
+                for epoch_id in epoch_ids.iter().rev() {
+                    let shard_layout = self.get_shard_layout(epoch_id)?;
+                    if shard_layouts.last() != Some(&shard_layout) {
+                        shard_layouts.push(shard_layout);
+                    }
+                    self.shard_layouts_up_to_epoch.put(*epoch_id, Arc::from(shard_layouts.clone()));
+                }
+                Ok::<_, EpochError>(Arc::from(shard_layouts))
+            })?;
+        Ok(shard_layouts.to_vec())
+    }
+
+    /// The epoch before the given one, or `None` if the block infos needed to
+    /// find it are garbage collected. The id of an epoch is the hash of the
+    /// last block of the epoch two epochs before it.
+    fn get_prev_epoch_id_of_epoch(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<Option<EpochId>, EpochError> {
+        let block_info = match self.get_block_info(&epoch_id.0) {
+            Ok(block_info) => block_info,
+            Err(EpochError::MissingBlock(_)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if block_info.is_genesis() {
+            return Ok(Some(EpochId::default()));
+        }
+        match self.get_next_epoch_id_from_info(&block_info) {
+            Ok(prev_epoch_id) => Ok(Some(prev_epoch_id)),
+            Err(EpochError::MissingBlock(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The shard layouts of the stored epochs below the given height, oldest
+    /// first, each one once.
+    fn get_shard_layouts_of_stored_epochs(&self, epoch_height: EpochHeight) -> Vec<ShardLayout> {
+        let mut epoch_infos = self
+            .store
+            .epoch_store()
+            .iter_epoch_info()
+            .filter(|(_, epoch_info)| epoch_info.epoch_height() < epoch_height)
+            .map(|(_, epoch_info)| epoch_info)
+            .collect_vec();
+        epoch_infos.sort_by_key(|epoch_info| epoch_info.epoch_height());
+        let mut shard_layouts: Vec<ShardLayout> = vec![];
+        for epoch_info in epoch_infos {
+            let shard_layout = self.get_shard_layout_for_epoch(&epoch_info);
+            if !shard_layouts.contains(&shard_layout) {
+                shard_layouts.push(shard_layout);
+            }
+        }
+        shard_layouts
     }
 
     pub fn get_shard_layout_from_protocol_version(
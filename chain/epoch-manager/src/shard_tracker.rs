@@ -5,6 +5,7 @@ use near_chain_configs::TrackedShardsConfig;
 use near_chain_primitives::Error;
 use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::StateSyncInfo;
 use near_primitives::types::{AccountId, EpochId, ShardId};
 use std::sync::Arc;
@@ -262,17 +263,39 @@ impl ShardTracker {
             || self.will_care_about_shard(account_id, parent_hash, shard_id, is_me)
     }
 
+    /// The shards of the epoch the client tracks, because of its config or,
+    /// if `account_id` is set, because of the validator duties of the account.
+    pub fn tracked_shard_uids_in_epoch(
+        &self,
+        account_id: Option<&AccountId>,
+        epoch_id: &EpochId,
+    ) -> Result<Vec<ShardUId>, EpochError> {
+        let shard_layout = self.epoch_manager.get_shard_layout(epoch_id)?;
+        let mut shard_uids = vec![];
+        for shard_uid in shard_layout.shard_uids() {
+            let shard_id = shard_uid.shard_id();
+            let cares_as_validator = match account_id {
+                Some(account_id) => {
+                    self.epoch_manager.cares_about_shard_in_epoch(epoch_id, account_id, shard_id)?
+                }
+                None => false,
+            };
+            if cares_as_validator || self.tracks_shard_at_epoch(shard_id, epoch_id)? {
+                shard_uids.push(shard_uid);
+            }
+        }
+        Ok(shard_uids)
+    }
+
     /// Returns whether the node is configured for all shards tracking.
     pub fn tracks_all_shards(&self) -> bool {
         self.tracked_shards_config.tracks_all_shards()
     }
 
-    /// Return all shards that whose states need to be caught up
-    /// That has two cases:
-    /// 1) Shard layout will change in the next epoch. In this case, the method returns all shards
-    ///    in the current epoch that will be split into a future shard that `me` will track.
-    /// 2) Shard layout will be the same. In this case, the method returns all shards that `me` will
-    ///    track in the next epoch but not this epoch
+    /// Return all shards of the next epoch whose states need to be caught up,
+    /// i.e. the shards that `me` will track in the next epoch but doesn't track
+    /// now. If the shard layout changes in the next epoch, these are the child
+    /// shards, which are synced directly and not through their parents.
     fn get_shards_to_state_sync(
         &self,
         me: &Option<AccountId>,
@@ -1967,6 +1967,110 @@ fn test_protocol_version_switch_with_shard_layout_change() {
     assert_eq!(epoch_manager.will_shard_layout_change(&h[6]).unwrap(), false);
 }
 
+#[test]
+fn test_shards_to_download_in_next_epoch() {
+    let amount_staked = 1_000_000;
+    let validators = vec![("test1".parse().unwrap(), amount_staked)];
+    let mut epoch_manager = setup_default_epoch_manager(validators, 1, 1, 2, 90, 60);
+
+    let h = hash_range(4);
+    record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+    record_block(
+        &mut epoch_manager,
+        h[0],
+        h[1],
+        1,
+        vec![stake("test2".parse().unwrap(), amount_staked)],
+    );
+    record_block(&mut epoch_manager, h[1], h[2], 2, vec![]);
+
+    // test2 staked in the epoch of h[1] and becomes a chunk producer in the epoch after h[2].
+    let epoch_manager = epoch_manager.into_handle();
+    let shards_to_download = |account_id: &str| {
+        epoch_manager
+            .get_shards_to_download_in_next_epoch(&h[2], &account_id.parse().unwrap())
+            .unwrap()
+    };
+    assert_eq!(shards_to_download("test1"), vec![]);
+    assert_eq!(shards_to_download("test2"), vec![ShardId::new(0)]);
+    assert_eq!(shards_to_download("test3"), vec![]);
+}
+
+#[test]
+fn test_shards_to_download_in_next_epoch_with_shard_layout_change() {
+    let store = create_test_store();
+
+    let old_epoch_config =
+        epoch_config(2, 1, 2, 100, 90, 60, 0).for_protocol_version(PROTOCOL_VERSION);
+    let mut new_epoch_config =
+        epoch_config(2, 2, 2, 100, 90, 60, 0).for_protocol_version(PROTOCOL_VERSION);
+    new_epoch_config.shard_layout =
+        ShardLayout::derive_shard_layout(&old_epoch_config.shard_layout, "test2".parse().unwrap());
+    let old_shard_layout = old_epoch_config.shard_layout.clone();
+    let new_shard_layout = new_epoch_config.shard_layout.clone();
+    let config_store = EpochConfigStore::test(BTreeMap::from_iter(vec![
+        (PROTOCOL_VERSION - 1, Arc::new(old_epoch_config)),
+        (PROTOCOL_VERSION, Arc::new(new_epoch_config)),
+    ]));
+    let config = AllEpochConfig::from_epoch_config_store("test-chain", 2, config_store);
+
+    let amount_staked = 1_000_000;
+    let validators = vec![
+        stake("test1".parse().unwrap(), amount_staked),
+        stake("test2".parse().unwrap(), amount_staked),
+    ];
+    let mut reward_calculator = default_reward_calculator();
+    reward_calculator.genesis_protocol_version = PROTOCOL_VERSION - 1;
+    let mut epoch_manager =
+        EpochManager::new(store, config, reward_calculator, validators).unwrap();
+    let h = hash_range(8);
+    record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+    for i in 1..8 {
+        let mut block_info = block_info(
+            h[i],
+            i as u64,
+            i as u64 - 1,
+            h[i - 1],
+            h[i - 1],
+            h[0],
+            vec![],
+            DEFAULT_TOTAL_SUPPLY,
+        );
+        if i == 1 {
+            set_block_info_protocol_version(&mut block_info, PROTOCOL_VERSION - 1);
+        } else {
+            set_block_info_protocol_version(&mut block_info, PROTOCOL_VERSION);
+        }
+        epoch_manager.record_block_info(block_info, [0; 32]).unwrap();
+    }
+
+    // h[5] is in the last epoch with the old shard layout.
+    let epoch_manager = epoch_manager.into_handle();
+    let epoch_id = epoch_manager.get_epoch_id(&h[5]).unwrap();
+    let next_epoch_id = epoch_manager.get_next_epoch_id(&h[5]).unwrap();
+    assert_eq!(epoch_manager.get_shard_layout(&epoch_id).unwrap(), old_shard_layout);
+    assert_eq!(epoch_manager.get_shard_layout(&next_epoch_id).unwrap(), new_shard_layout);
+
+    // Both validators produce the chunks of the parent shard, so they already
+    // have the state of the children they are assigned to.
+    for account_id in ["test1", "test2"] {
+        let account_id: AccountId = account_id.parse().unwrap();
+        let num_children = new_shard_layout
+            .shard_ids()
+            .filter(|shard_id| {
+                epoch_manager
+                    .cares_about_shard_in_epoch(&next_epoch_id, &account_id, *shard_id)
+                    .unwrap()
+            })
+            .count();
+        assert!(num_children > 0);
+        assert_eq!(
+            epoch_manager.get_shards_to_download_in_next_epoch(&h[5], &account_id).unwrap(),
+            vec![]
+        );
+    }
+}
+
 #[test]
 fn test_protocol_version_switch_with_many_seats() {
     let store = create_test_store();
@@ -3239,12 +3343,15 @@ fn test_possible_epochs_of_height_around_tip() {
     }
 }
 
-fn test_get_shard_uids_pending_resharding_base(shard_layouts: &[ShardLayout]) -> HashSet<ShardUId> {
-    init_test_logger();
-
+/// Creates an epoch manager with a new protocol version for each provided
+/// shard layout, starting from the genesis protocol version.
+fn epoch_manager_with_shard_layouts(
+    genesis_protocol_version: ProtocolVersion,
+    shard_layouts: &[ShardLayout],
+) -> Arc<EpochManagerHandle> {
     // Create a minimal genesis.
     let mut genesis_config = GenesisConfig::default();
-    genesis_config.protocol_version = PROTOCOL_VERSION;
+    genesis_config.protocol_version = genesis_protocol_version;
     genesis_config.validators = vec![AccountInfo {
         account_id: "test".parse().unwrap(),
         public_key: PublicKey::empty(KeyType::ED25519),
@@ -3265,19 +3372,18 @@ fn test_get_shard_uids_pending_resharding_base(shard_layouts: &[ShardLayout]) ->
     let epoch_config_store = BTreeMap::from_iter(epoch_config_store.into_iter());
     let epoch_config_store = EpochConfigStore::test(epoch_config_store);
 
-    // Create the epoch manager.
     let store = create_test_store();
-    let epoch_manager = EpochManager::new_arc_handle_from_epoch_config_store(
-        store,
-        &genesis_config,
-        epoch_config_store,
-    );
+    EpochManager::new_arc_handle_from_epoch_config_store(store, &genesis_config, epoch_config_store)
+}
+
+fn test_get_shard_uids_pending_resharding_base(shard_layouts: &[ShardLayout]) -> HashSet<ShardUId> {
+    init_test_logger();
+    let epoch_manager = epoch_manager_with_shard_layouts(PROTOCOL_VERSION, shard_layouts);
 
     // Get and return the ShardUIds pending resharding.
-    let head_protocol_version = genesis_config.protocol_version;
-    let client_protocol_version = genesis_config.protocol_version + shard_layouts.len() as u32 - 1;
+    let client_protocol_version = PROTOCOL_VERSION + shard_layouts.len() as u32 - 1;
     epoch_manager
-        .get_shard_uids_pending_resharding(head_protocol_version, client_protocol_version)
+        .get_shard_uids_pending_resharding(&EpochId::default(), client_protocol_version)
         .unwrap()
 }
 
@@ -3391,3 +3497,110 @@ fn test_get_shard_uids_pending_resharding_double_same() {
     ]);
     assert_eq!(shard_uids, vec![s1].into_iter().collect::<HashSet<_>>());
 }
+
+/// The lineage of a shard follows the shard layouts of the epochs of the
+/// chain, and not the ones of the protocol versions it didn't upgrade to.
+#[test]
+fn test_shard_lineage() {
+    let store = create_test_store();
+
+    let old_epoch_config =
+        epoch_config(2, 1, 2, 100, 90, 60, 0).for_protocol_version(PROTOCOL_VERSION);
+    let mut new_epoch_config =
+        epoch_config(2, 2, 2, 100, 90, 60, 0).for_protocol_version(PROTOCOL_VERSION);
+    new_epoch_config.shard_layout =
+        ShardLayout::derive_shard_layout(&old_epoch_config.shard_layout, "test2".parse().unwrap());
+    let mut future_epoch_config =
+        epoch_config(2, 3, 2, 100, 90, 60, 0).for_protocol_version(PROTOCOL_VERSION);
+    future_epoch_config.shard_layout =
+        ShardLayout::derive_shard_layout(&new_epoch_config.shard_layout, "test3".parse().unwrap());
+    let old_shard_layout = old_epoch_config.shard_layout.clone();
+    let new_shard_layout = new_epoch_config.shard_layout.clone();
+    let config_store = EpochConfigStore::test(BTreeMap::from_iter(vec![
+        (PROTOCOL_VERSION - 2, Arc::new(old_epoch_config)),
+        (PROTOCOL_VERSION - 1, Arc::new(new_epoch_config)),
+        (PROTOCOL_VERSION, Arc::new(future_epoch_config)),
+    ]));
+    let config = AllEpochConfig::from_epoch_config_store("test-chain", 2, config_store);
+
+    let amount_staked = 1_000_000;
+    let validators = vec![
+        stake("test1".parse().unwrap(), amount_staked),
+        stake("test2".parse().unwrap(), amount_staked),
+    ];
+    let mut reward_calculator = default_reward_calculator();
+    reward_calculator.genesis_protocol_version = PROTOCOL_VERSION - 2;
+    let mut epoch_manager =
+        EpochManager::new(store, config, reward_calculator, validators).unwrap();
+    let h = hash_range(8);
+    record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+    for i in 1..8 {
+        let mut block_info = block_info(
+            h[i],
+            i as u64,
+            i as u64 - 1,
+            h[i - 1],
+            h[i - 1],
+            h[0],
+            vec![],
+            DEFAULT_TOTAL_SUPPLY,
+        );
+        let protocol_version = if i == 1 { PROTOCOL_VERSION - 2 } else { PROTOCOL_VERSION - 1 };
+        set_block_info_protocol_version(&mut block_info, protocol_version);
+        epoch_manager.record_block_info(block_info, [0; 32]).unwrap();
+    }
+    // A fork from h[2] which doesn't vote for the upgrade, so its epoch 4 keeps
+    // the old shard layout.
+    let fork = [hash(b"fork3"), hash(b"fork4")];
+    for (i, (prev_hash, hash)) in [(h[2], fork[0]), (fork[0], fork[1])].into_iter().enumerate() {
+        let height = i as u64 + 3;
+        let mut block_info = block_info(
+            hash,
+            height,
+            height - 1,
+            prev_hash,
+            prev_hash,
+            h[0],
+            vec![],
+            DEFAULT_TOTAL_SUPPLY,
+        );
+        set_block_info_protocol_version(&mut block_info, PROTOCOL_VERSION - 2);
+        epoch_manager.record_block_info(block_info, [0; 32]).unwrap();
+    }
+
+    // h[5] is in the last epoch with the old shard layout.
+    let epoch_manager = epoch_manager.into_handle();
+    let epoch_id = epoch_manager.get_epoch_id(&h[5]).unwrap();
+    let next_epoch_id = epoch_manager.get_next_epoch_id(&h[5]).unwrap();
+    assert_eq!(epoch_manager.get_shard_layout(&epoch_id).unwrap(), old_shard_layout);
+    assert_eq!(epoch_manager.get_shard_layout(&next_epoch_id).unwrap(), new_shard_layout);
+
+    let parent = old_shard_layout.account_id_to_shard_uid(&"test2".parse().unwrap());
+    let children = new_shard_layout.get_children_shards_uids(parent.shard_id()).unwrap();
+    let lineage = epoch_manager.shard_lineage(parent, &epoch_id, &next_epoch_id).unwrap();
+    assert_eq!(lineage.ancestors, vec![]);
+    assert_eq!(lineage.descendants, vec![children.clone()]);
+    for child in &children {
+        let lineage = epoch_manager.shard_lineage(*child, &next_epoch_id, &next_epoch_id).unwrap();
+        assert_eq!(lineage.ancestors, vec![parent]);
+        assert_eq!(lineage.descendants, Vec::<Vec<ShardUId>>::new());
+    }
+
+    // The children aren't shards of the epoch of the parent.
+    assert!(epoch_manager.shard_lineage(children[0], &epoch_id, &next_epoch_id).is_err());
+    // The lineage can't stop before the epoch of the shard.
+    assert!(epoch_manager.shard_lineage(children[0], &next_epoch_id, &epoch_id).is_err());
+
+    // The epochs of the fork aren't in the shard layouts of the chain, and the
+    // other way around.
+    assert_eq!(
+        epoch_manager.get_shard_layouts_up_to_epoch(&next_epoch_id).unwrap(),
+        vec![old_shard_layout.clone(), new_shard_layout]
+    );
+    let fork_epoch_id = EpochId(fork[1]);
+    assert_eq!(epoch_manager.get_shard_layout(&fork_epoch_id).unwrap(), old_shard_layout);
+    assert_eq!(
+        epoch_manager.get_shard_layouts_up_to_epoch(&fork_epoch_id).unwrap(),
+        vec![old_shard_layout]
+    );
+}
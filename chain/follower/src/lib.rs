@@ -0,0 +1,128 @@
+#![doc = include_str!("../README.md")]
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use near_async::actix::AddrWithAutoSpanContextExt;
+use near_async::futures::{ActixFutureSpawner, FutureSpawner, FutureSpawnerExt};
+use near_async::messaging::IntoMultiSender;
+use near_async::time::Clock;
+use near_chain_configs::{GenesisValidationMode, MutableConfigValue};
+use tokio::sync::mpsc;
+
+pub use near_primitives;
+use near_primitives::types::{BlockHeight, Finality, ShardId};
+use near_primitives::views;
+pub use nearcore::{NearConfig, get_default_home, init_configs};
+
+pub use streamer::ViewClientSenderForFollower;
+
+mod streamer;
+
+pub const FOLLOWER: &str = "follower";
+
+/// Configuration of the chain follower to be provided to `ChainFollower::new`.
+#[derive(Debug, Clone)]
+pub struct ChainFollowerConfig {
+    /// Path to `home_dir` where configs and keys can be found
+    pub home_dir: std::path::PathBuf,
+    /// Height of the first block to stream. If not set, streaming starts from
+    /// the latest block at the configured `finality`.
+    pub start_block_height: Option<BlockHeight>,
+    /// Finality level at which blocks are streamed
+    pub finality: Finality,
+    /// Whether to fetch the state changes of the tracked shards for every
+    /// streamed block.
+    pub with_state_changes: bool,
+    /// Tells whether to validate the genesis file before starting
+    pub validate_genesis: bool,
+}
+
+/// Message sent by the chain follower for every block it follows.
+#[derive(Debug, Clone)]
+pub struct FollowerMessage {
+    pub block: views::BlockView,
+    /// State changes in the block, grouped by shard. Only the shards tracked by
+    /// the node are present, and the map is empty when
+    /// `ChainFollowerConfig::with_state_changes` is disabled.
+    pub state_changes: HashMap<ShardId, views::StateChangesView>,
+}
+
+/// Embedded non-validating node that follows the chain and streams its blocks.
+pub struct ChainFollower {
+    follower_config: ChainFollowerConfig,
+    near_config: nearcore::NearConfig,
+    view_client: actix::Addr<near_client::ViewClientActor>,
+    client: actix::Addr<near_client::ClientActor>,
+}
+
+impl ChainFollower {
+    /// Loads the configs from `home_dir` and starts the node. Must be called
+    /// from within an actix system.
+    pub fn new(follower_config: ChainFollowerConfig) -> Result<Self, anyhow::Error> {
+        tracing::info!(
+            target: FOLLOWER,
+            "Load config from {}...",
+            follower_config.home_dir.display()
+        );
+
+        let genesis_validation_mode = if follower_config.validate_genesis {
+            GenesisValidationMode::Full
+        } else {
+            GenesisValidationMode::UnsafeFast
+        };
+        let mut near_config =
+            nearcore::config::load_config(&follower_config.home_dir, genesis_validation_mode)
+                .with_context(|| "load_config")?;
+        // The follower must never produce blocks or chunks, even if the home
+        // directory contains a validator key.
+        near_config.validator_signer = MutableConfigValue::new(None, "validator_signer");
+        near_config.disable_rpc_servers();
+
+        let nearcore::NearNode { client, view_client, .. } =
+            nearcore::start_with_config(&follower_config.home_dir, near_config.clone())
+                .with_context(|| "start_with_config")?;
+        Ok(Self { follower_config, near_config, view_client, client })
+    }
+
+    /// Starts streaming the followed blocks. The returned receiver should be
+    /// drained on the user side, dropping it stops the streaming.
+    pub fn streamer(&self) -> mpsc::Receiver<FollowerMessage> {
+        spawn_streamer(
+            Clock::real(),
+            self.view_client.clone().with_auto_span_context().into_multi_sender(),
+            self.follower_config.clone(),
+            &ActixFutureSpawner,
+        )
+    }
+
+    /// Expose neard config
+    pub fn near_config(&self) -> &nearcore::NearConfig {
+        &self.near_config
+    }
+
+    /// Internal client actors just in case. Use on your own risk, backward compatibility is not guaranteed
+    pub fn client_actors(
+        &self,
+    ) -> (actix::Addr<near_client::ViewClientActor>, actix::Addr<near_client::ClientActor>) {
+        (self.view_client.clone(), self.client.clone())
+    }
+}
+
+/// Streams the blocks followed through `view_client` the same way as
+/// `ChainFollower::streamer`, on the given clock and future spawner. This lets
+/// the streamer run on top of a node that is not started by `ChainFollower`,
+/// e.g. in tests.
+pub fn spawn_streamer(
+    clock: Clock,
+    view_client: ViewClientSenderForFollower,
+    follower_config: ChainFollowerConfig,
+    future_spawner: &dyn FutureSpawner,
+) -> mpsc::Receiver<FollowerMessage> {
+    let (sender, receiver) = mpsc::channel(100);
+    future_spawner.spawn(
+        "chain follower streamer",
+        streamer::start(clock, view_client, follower_config, sender),
+    );
+    receiver
+}
@@ -0,0 +1,101 @@
+//! Streamer follows the chain through the view client and pushes the blocks,
+//! together with the state changes of the tracked shards, to the given queue.
+use std::collections::HashMap;
+
+use near_async::actix::ActixResult;
+use near_async::messaging::{AsyncSender, IntoSender, SendAsync};
+use near_async::time::{Clock, Duration};
+use near_client::{GetBlock, GetStateChangesWithCauseInBlockForTrackedShards};
+use near_indexer::BlockStream;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{EpochId, ShardId};
+use near_primitives::views;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info};
+
+use crate::{ChainFollowerConfig, FOLLOWER, FollowerMessage};
+
+const INTERVAL: Duration = Duration::milliseconds(250);
+
+/// View client messages used by the chain follower.
+#[derive(Clone, near_async::MultiSend, near_async::MultiSenderFrom)]
+pub struct ViewClientSenderForFollower(
+    AsyncSender<GetBlock, ActixResult<GetBlock>>,
+    AsyncSender<
+        GetStateChangesWithCauseInBlockForTrackedShards,
+        ActixResult<GetStateChangesWithCauseInBlockForTrackedShards>,
+    >,
+);
+
+async fn fetch_state_changes(
+    view_client: &ViewClientSenderForFollower,
+    block_hash: CryptoHash,
+    epoch_id: EpochId,
+) -> anyhow::Result<HashMap<ShardId, views::StateChangesView>> {
+    let state_changes = view_client
+        .send_async(GetStateChangesWithCauseInBlockForTrackedShards { block_hash, epoch_id })
+        .await
+        .map_err(|err| anyhow::anyhow!("{err}"))??;
+    Ok(state_changes)
+}
+
+async fn build_follower_message(
+    view_client: &ViewClientSenderForFollower,
+    block: views::BlockView,
+    with_state_changes: bool,
+) -> anyhow::Result<FollowerMessage> {
+    let state_changes = if with_state_changes {
+        fetch_state_changes(view_client, block.header.hash, EpochId(block.header.epoch_id)).await?
+    } else {
+        HashMap::new()
+    };
+    Ok(FollowerMessage { block, state_changes })
+}
+
+/// Follows the chain at the configured finality until the receiving side of
+/// `sink` is dropped. Heights without a block are skipped.
+pub(crate) async fn start(
+    clock: Clock,
+    view_client: ViewClientSenderForFollower,
+    follower_config: ChainFollowerConfig,
+    sink: mpsc::Sender<FollowerMessage>,
+) {
+    info!(target: FOLLOWER, "Starting chain follower streamer...");
+    let mut block_stream = BlockStream::new(
+        view_client.clone().into_sender(),
+        follower_config.finality.clone(),
+        follower_config.start_block_height,
+    );
+
+    'main: loop {
+        clock.sleep(INTERVAL).await;
+        if !block_stream.fetch_latest_block().await {
+            continue;
+        }
+        while let Some(block) = block_stream.next_block().await {
+            let block_height = block.header.height;
+            let message = match build_follower_message(
+                &view_client,
+                block,
+                follower_config.with_state_changes,
+            )
+            .await
+            {
+                Ok(message) => message,
+                Err(err) => {
+                    // The data may not be available yet, retry this height later.
+                    debug!(target: FOLLOWER, block_height, ?err, "Missing data for block");
+                    block_stream.rewind_to(block_height);
+                    continue 'main;
+                }
+            };
+            if sink.send(message).await.is_err() {
+                error!(
+                    target: FOLLOWER,
+                    "Unable to send FollowerMessage to listener, listener doesn't listen. terminating..."
+                );
+                break 'main;
+            }
+        }
+    }
+}
@@ -16,7 +16,7 @@ pub use near_indexer_primitives::{
 };
 
 use near_epoch_manager::shard_tracker::ShardTracker;
-pub use streamer::build_streamer_message;
+pub use streamer::{BlockStream, build_streamer_message};
 
 mod streamer;
 
@@ -0,0 +1,89 @@
+//! Block stream walks the chain in height order up to the latest block at a
+//! given finality. It is shared by the indexer streamer and the chain follower.
+use near_async::actix::ActixResult;
+use near_async::messaging::AsyncSender;
+use near_client::GetBlock;
+use near_primitives::types::{BlockHeight, Finality};
+use near_primitives::views;
+use tracing::debug;
+
+use super::fetchers::{fetch_block_by_height, fetch_latest_block};
+use crate::INDEXER;
+
+/// Iterates over the blocks of the chain, from the start height up to the
+/// latest block at the configured finality. Heights without a block are
+/// skipped.
+pub struct BlockStream {
+    view_client: AsyncSender<GetBlock, ActixResult<GetBlock>>,
+    finality: Finality,
+    /// Height of the next block to stream. Set to the height of the first
+    /// latest block when the stream starts from the latest block.
+    next_block_height: Option<BlockHeight>,
+    /// Latest block at `finality` as of the last `fetch_latest_block` call.
+    latest_block: Option<views::BlockView>,
+}
+
+impl BlockStream {
+    /// Creates a stream starting at `start_block_height`, or at the latest
+    /// block at `finality` if it is not set.
+    pub fn new(
+        view_client: AsyncSender<GetBlock, ActixResult<GetBlock>>,
+        finality: Finality,
+        start_block_height: Option<BlockHeight>,
+    ) -> Self {
+        Self { view_client, finality, next_block_height: start_block_height, latest_block: None }
+    }
+
+    /// Fetches the latest block at the finality of the stream, up to which
+    /// `next_block` streams. Returns false if it is not available yet.
+    pub async fn fetch_latest_block(&mut self) -> bool {
+        let block = match fetch_latest_block(&self.view_client, &self.finality).await {
+            Ok(block) => block,
+            Err(err) => {
+                debug!(target: INDEXER, ?err, "Latest block is not available yet");
+                return false;
+            }
+        };
+        self.next_block_height.get_or_insert(block.header.height);
+        self.latest_block = Some(block);
+        true
+    }
+
+    /// Returns the next block of the stream, or None once the stream caught
+    /// up with the block fetched by the last `fetch_latest_block` call.
+    pub async fn next_block(&mut self) -> Option<views::BlockView> {
+        let latest_block = self.latest_block.as_ref()?;
+        let latest_block_height = latest_block.header.height;
+        let mut block_height = self.next_block_height?;
+        while block_height <= latest_block_height {
+            self.next_block_height = Some(block_height + 1);
+            if block_height == latest_block_height {
+                return Some(latest_block.clone());
+            }
+            match fetch_block_by_height(&self.view_client, block_height).await {
+                Ok(block) => return Some(block),
+                Err(err) => {
+                    debug!(target: INDEXER, block_height, ?err, "Skipping the height without a block");
+                }
+            }
+            block_height += 1;
+        }
+        None
+    }
+
+    /// Makes the stream continue from `block_height`, for example to stream
+    /// a block again after its data failed to be fetched.
+    pub fn rewind_to(&mut self, block_height: BlockHeight) {
+        self.next_block_height = Some(block_height);
+    }
+
+    /// Height of the next block to stream, if the stream has started.
+    pub fn next_block_height(&self) -> Option<BlockHeight> {
+        self.next_block_height
+    }
+
+    /// Height of the block fetched by the last `fetch_latest_block` call.
+    pub fn latest_block_height(&self) -> Option<BlockHeight> {
+        self.latest_block.as_ref().map(|block| block.header.height)
+    }
+}
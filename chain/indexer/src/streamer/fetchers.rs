@@ -6,6 +6,8 @@ use actix::Addr;
 use futures::stream::StreamExt;
 use tracing::warn;
 
+use near_async::actix::ActixResult;
+use near_async::messaging::AsyncSender;
 use near_indexer_primitives::IndexerExecutionOutcomeWithOptionalReceipt;
 use near_o11y::WithSpanContextExt;
 use near_primitives::hash::CryptoHash;
@@ -28,33 +30,29 @@ pub(crate) async fn fetch_status(
 /// Fetches the status to retrieve `latest_block_height` to determine if we need to fetch
 /// entire block or we already fetched this block.
 pub(crate) async fn fetch_latest_block(
-    client: &Addr<near_client::ViewClientActor>,
+    client: &AsyncSender<near_client::GetBlock, ActixResult<near_client::GetBlock>>,
     finality: &near_primitives::types::Finality,
 ) -> Result<views::BlockView, FailedToFetchData> {
     tracing::debug!(target: INDEXER, "Fetching latest block");
     client
-        .send(
-            near_client::GetBlock(near_primitives::types::BlockReference::Finality(
-                finality.clone(),
-            ))
-            .with_span_context(),
-        )
-        .await?
+        .send_async(near_client::GetBlock(near_primitives::types::BlockReference::Finality(
+            finality.clone(),
+        )))
+        .await
+        .map_err(|err| FailedToFetchData::String(err.to_string()))?
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
 /// Fetches specific block by it's height
 pub(crate) async fn fetch_block_by_height(
-    client: &Addr<near_client::ViewClientActor>,
+    client: &AsyncSender<near_client::GetBlock, ActixResult<near_client::GetBlock>>,
     height: u64,
 ) -> Result<views::BlockView, FailedToFetchData> {
     tracing::debug!(target: INDEXER, "Fetching block by height: {}", height);
     client
-        .send(
-            near_client::GetBlock(near_primitives::types::BlockId::Height(height).into())
-                .with_span_context(),
-        )
-        .await?
+        .send_async(near_client::GetBlock(near_primitives::types::BlockId::Height(height).into()))
+        .await
+        .map_err(|err| FailedToFetchData::String(err.to_string()))?
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
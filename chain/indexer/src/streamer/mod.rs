@@ -19,15 +19,19 @@ use near_primitives::views;
 
 use self::errors::FailedToFetchData;
 use self::fetchers::{
-    fetch_block, fetch_block_by_height, fetch_block_new_chunks, fetch_latest_block, fetch_outcomes,
-    fetch_state_changes, fetch_status,
+    fetch_block, fetch_block_new_chunks, fetch_outcomes, fetch_state_changes, fetch_status,
 };
 use self::utils::convert_transactions_sir_into_local_receipts;
 use crate::INDEXER;
 use crate::streamer::fetchers::fetch_protocol_config;
 use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use near_async::actix::AddrWithAutoSpanContextExt;
+use near_async::messaging::IntoSender;
 use near_epoch_manager::shard_tracker::ShardTracker;
 
+pub use self::block_stream::BlockStream;
+
+mod block_stream;
 mod errors;
 mod fetchers;
 mod metrics;
@@ -401,7 +405,19 @@ pub(crate) async fn start(
         Err(err) => panic!("Unable to open indexer db: {:?}", err),
     };
 
-    let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let start_block_height = match indexer_config.sync_mode {
+        crate::SyncModeEnum::FromInterruption => db
+            .get(b"last_synced_block_height")
+            .unwrap()
+            .map(|value| String::from_utf8(value).unwrap().parse::<u64>().unwrap()),
+        crate::SyncModeEnum::LatestSynced => None,
+        crate::SyncModeEnum::BlockHeight(height) => Some(height),
+    };
+    let mut block_stream = BlockStream::new(
+        view_client.clone().with_auto_span_context().into_sender(),
+        indexer_config.finality.clone(),
+        start_block_height,
+    );
 
     'main: loop {
         time::sleep(INTERVAL).await;
@@ -417,29 +433,13 @@ pub(crate) async fn start(
             AwaitForNodeSyncedEnum::StreamWhileSyncing => {}
         };
 
-        let block =
-            if let Ok(block) = fetch_latest_block(&view_client, &indexer_config.finality).await {
-                block
-            } else {
-                continue;
-            };
-
-        let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
-                    }
-                }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
+        if !block_stream.fetch_latest_block().await {
+            continue;
+        }
+        let (Some(start_syncing_block_height), Some(latest_block_height)) =
+            (block_stream.next_block_height(), block_stream.latest_block_height())
+        else {
+            continue;
         };
 
         debug!(
@@ -450,35 +450,33 @@ pub(crate) async fn start(
         );
         metrics::START_BLOCK_HEIGHT.set(start_syncing_block_height as i64);
         metrics::LATEST_BLOCK_HEIGHT.set(latest_block_height as i64);
-        for block_height in start_syncing_block_height..=latest_block_height {
+        while let Some(block) = block_stream.next_block().await {
+            let block_height = block.header.height;
             metrics::CURRENT_BLOCK_HEIGHT.set(block_height as i64);
-            if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block, &shard_tracker).await;
-
-                match response {
-                    Ok(streamer_message) => {
-                        debug!(target: INDEXER, "Sending streamer message for block #{} to the listener", streamer_message.block.header.height);
-                        if blocks_sink.send(streamer_message).await.is_err() {
-                            error!(
-                                target: INDEXER,
-                                "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
-                            );
-                            break 'main;
-                        } else {
-                            metrics::NUM_STREAMER_MESSAGES_SENT.inc();
-                        }
-                    }
-                    Err(err) => {
-                        debug!(
+            let response = build_streamer_message(&view_client, block, &shard_tracker).await;
+
+            match response {
+                Ok(streamer_message) => {
+                    debug!(target: INDEXER, "Sending streamer message for block #{} to the listener", streamer_message.block.header.height);
+                    if blocks_sink.send(streamer_message).await.is_err() {
+                        error!(
                             target: INDEXER,
-                            "Missing data, skipping block #{}...", block_height
+                            "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
                         );
-                        debug!(target: INDEXER, "{:#?}", err);
+                        break 'main;
+                    } else {
+                        metrics::NUM_STREAMER_MESSAGES_SENT.inc();
                     }
                 }
+                Err(err) => {
+                    debug!(
+                        target: INDEXER,
+                        "Missing data, skipping block #{}...", block_height
+                    );
+                    debug!(target: INDEXER, "{:#?}", err);
+                }
             }
             db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
-            last_synced_block_height = Some(block_height);
         }
     }
 }
@@ -130,6 +130,18 @@ impl RpcError {
         }
     }
 
+    /// Create an error for a request which the node refuses to process
+    /// because it's overloaded.
+    pub fn node_overloaded(method_name: String) -> Self {
+        Self::new_handler_error(
+            Some(Value::String(method_name.clone())),
+            serde_json::json!({
+                "name": "NODE_OVERLOADED",
+                "info": serde_json::json!({"method_name": method_name})
+            }),
+        )
+    }
+
     /// Create a method not found error.
     pub fn method_not_found(method: String) -> Self {
         RpcError {
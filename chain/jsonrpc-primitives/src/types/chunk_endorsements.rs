@@ -0,0 +1,42 @@
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcChunkEndorsementsRequest {
+    /// The height the chunks were created at.
+    pub height: near_primitives::types::BlockHeight,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcChunkEndorsementsResponse {
+    /// The chunks of the height the node knows the endorsements of.
+    pub chunks: Vec<near_primitives::views::ChunkEndorsementsView>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcChunkEndorsementsError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcChunkEndorsementsError> for crate::errors::RpcError {
+    fn from(error: RpcChunkEndorsementsError) -> Self {
+        let error_data = match &error {
+            RpcChunkEndorsementsError::InternalError { .. } => {
+                Some(Value::String(error.to_string()))
+            }
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcChunkEndorsementsError: {:?}", err),
+                );
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
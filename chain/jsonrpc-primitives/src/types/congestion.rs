@@ -1,3 +1,6 @@
+use near_primitives::types::ShardId;
+use near_primitives::views::CongestionInfoView;
+
 use super::chunks::{ChunkReference, RpcChunkError};
 
 // Reuse the same error as for chunk lookup since the congestion level call
@@ -13,4 +16,8 @@ pub struct RpcCongestionLevelRequest {
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct RpcCongestionLevelResponse {
     pub congestion_level: f64,
+    pub shard_id: ShardId,
+    /// The receipts backlog and the allowed shard of the referenced chunk.
+    /// `None` if the chunk was produced before congestion control.
+    pub congestion_info: Option<CongestionInfoView>,
 }
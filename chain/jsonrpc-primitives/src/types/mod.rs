@@ -1,5 +1,6 @@
 pub mod blocks;
 pub mod changes;
+pub mod chunk_endorsements;
 pub mod chunks;
 pub mod client_config;
 pub mod config;
@@ -12,7 +13,11 @@ pub mod network_info;
 pub mod query;
 pub mod receipts;
 pub mod sandbox;
+pub mod shard_assignments;
+pub mod shard_layout;
 pub mod split_storage;
 pub mod status;
 pub mod transactions;
+pub mod tx_execution_profile;
+pub mod tx_subscription;
 pub mod validator;
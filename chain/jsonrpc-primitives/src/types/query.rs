@@ -85,6 +85,29 @@ pub enum QueryResponseKind {
     AccessKeyList(near_primitives::views::AccessKeyList),
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcBatchQueryRequest {
+    #[serde(flatten)]
+    pub block_reference: near_primitives::types::BlockReference,
+    pub queries: Vec<near_primitives::views::QueryRequest>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcBatchQueryResponse {
+    pub block_height: near_primitives::types::BlockHeight,
+    pub block_hash: near_primitives::hash::CryptoHash,
+    /// The outcome of each query, in the order of the queries.
+    pub results: Vec<RpcBatchQueryResult>,
+}
+
+/// Serialized as `{"result": ...}` or `{"error": ...}`.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcBatchQueryResult {
+    Result(RpcQueryResponse),
+    Error(RpcQueryError),
+}
+
 impl From<RpcQueryError> for crate::errors::RpcError {
     fn from(error: RpcQueryError) -> Self {
         let error_data = Some(serde_json::Value::String(error.to_string()));
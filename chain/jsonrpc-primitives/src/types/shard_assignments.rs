@@ -0,0 +1,59 @@
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcNextEpochShardAssignmentsRequest {
+    #[serde(flatten)]
+    pub block_reference: near_primitives::types::BlockReference,
+    /// If given, the response also contains the shards assigned to this
+    /// account in the next epoch and the ones it has to download.
+    #[serde(default)]
+    pub account_id: Option<near_primitives::types::AccountId>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcNextEpochShardAssignmentsResponse {
+    #[serde(flatten)]
+    pub shard_assignments_view: near_primitives::views::NextEpochShardAssignmentsView,
+}
+
+#[derive(thiserror::Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcNextEpochShardAssignmentsError {
+    #[error("Block has never been observed: {error_message}")]
+    UnknownBlock {
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
+    #[error("Epoch not found")]
+    UnknownEpoch,
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcNextEpochShardAssignmentsError> for crate::errors::RpcError {
+    fn from(error: RpcNextEpochShardAssignmentsError) -> Self {
+        let error_data = match &error {
+            RpcNextEpochShardAssignmentsError::UnknownBlock { error_message } => {
+                Some(Value::String(format!("Block Not Found: {}", error_message)))
+            }
+            RpcNextEpochShardAssignmentsError::UnknownEpoch => {
+                Some(Value::String("Unknown Epoch".to_string()))
+            }
+            RpcNextEpochShardAssignmentsError::InternalError { .. } => {
+                Some(Value::String(error.to_string()))
+            }
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcNextEpochShardAssignmentsError: {:?}", err),
+                );
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
@@ -0,0 +1,54 @@
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcShardLayoutAtBlockRequest {
+    #[serde(flatten)]
+    pub block_reference: near_primitives::types::BlockReference,
+    /// If given, the response also contains the shard of this account at the
+    /// requested block.
+    #[serde(default)]
+    pub account_id: Option<near_primitives::types::AccountId>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcShardLayoutAtBlockResponse {
+    #[serde(flatten)]
+    pub shard_layout_view: near_primitives::views::ShardLayoutAtBlockView,
+}
+
+#[derive(thiserror::Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcShardLayoutAtBlockError {
+    #[error("Block has never been observed: {error_message}")]
+    UnknownBlock {
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcShardLayoutAtBlockError> for crate::errors::RpcError {
+    fn from(error: RpcShardLayoutAtBlockError) -> Self {
+        let error_data = match &error {
+            RpcShardLayoutAtBlockError::UnknownBlock { error_message } => {
+                Some(Value::String(format!("Block Not Found: {}", error_message)))
+            }
+            RpcShardLayoutAtBlockError::InternalError { .. } => {
+                Some(Value::String(error.to_string()))
+            }
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcShardLayoutAtBlockError: {:?}", err),
+                );
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
@@ -3,9 +3,11 @@ use near_client_primitives::debug::{
     DebugBlockStatusData, EpochInfoView, TrackedShardsView, ValidatorStatus,
 };
 #[cfg(feature = "debug_types")]
+use near_primitives::block_production_timings::BlockProductionTimings;
+#[cfg(feature = "debug_types")]
 use near_primitives::views::{
     CatchupStatusView, ChainProcessingInfo, NetworkGraphView, NetworkRoutesView, PeerStoreView,
-    RecentOutboundConnectionsView, RequestedStatePartsView, SnapshotHostsView,
+    RecentOutboundConnectionsView, RequestedStatePartsView, ShardHotspotsView, SnapshotHostsView,
     SplitStorageInfoView, SyncStatusView,
 };
 
@@ -36,6 +38,10 @@ pub enum DebugStatusResponse {
     Routes(NetworkRoutesView),
     SnapshotHosts(SnapshotHostsView),
     SplitStoreStatus(SplitStorageInfoView),
+    // Timings of the blocks produced by the node, ordered by height.
+    BlockProductionTimings(Vec<BlockProductionTimings>),
+    // Top accounts of each shard, the latest epoch first.
+    ShardHotspots(Vec<ShardHotspotsView>),
 }
 
 #[cfg(feature = "debug_types")]
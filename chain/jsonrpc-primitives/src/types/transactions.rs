@@ -50,6 +50,10 @@ pub enum RpcTransactionError {
     InternalError { debug_info: String },
     #[error("Timeout")]
     TimeoutError,
+    #[error(
+        "Chunk producers of shard {shard_id} are overloaded and the transaction can't be forwarded to them. Try again later"
+    )]
+    ForwardingOverloaded { shard_id: near_primitives::types::ShardId },
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -0,0 +1,50 @@
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxExecutionProfileRequest {
+    pub tx_hash: near_primitives::hash::CryptoHash,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxExecutionProfileResponse {
+    /// The profiles of the receipts of the transaction executed in the chunks
+    /// applied by the node, in execution order.
+    pub receipts: Vec<near_primitives::views::ReceiptExecutionProfileView>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcTxExecutionProfileError {
+    #[error("Transaction or one of its receipts has never been observed: {error_message}")]
+    UnknownTransaction {
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcTxExecutionProfileError> for crate::errors::RpcError {
+    fn from(error: RpcTxExecutionProfileError) -> Self {
+        let error_data = match &error {
+            RpcTxExecutionProfileError::UnknownTransaction { error_message } => {
+                Some(Value::String(format!("Transaction Not Found: {}", error_message)))
+            }
+            RpcTxExecutionProfileError::InternalError { .. } => {
+                Some(Value::String(error.to_string()))
+            }
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcTxExecutionProfileError: {:?}", err),
+                );
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
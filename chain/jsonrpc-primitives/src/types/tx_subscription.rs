@@ -0,0 +1,67 @@
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+use near_primitives::views::TxExecutionStatus;
+use serde_json::Value;
+
+/// Name of the method of the notifications pushed to the `tx_subscribe`
+/// subscribers.
+pub const TX_STATUS_NOTIFICATION_METHOD: &str = "tx_status";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RpcTxSubscribeRequest {
+    pub sender_account_id: AccountId,
+    /// The transaction to follow. If not given, every transaction of
+    /// `sender_account_id` included in a block after the subscription is
+    /// followed.
+    #[serde(default)]
+    pub tx_hash: Option<CryptoHash>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxSubscribeResponse {
+    pub subscription: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RpcTxUnsubscribeRequest {
+    pub subscription: u64,
+}
+
+/// Pushed to a `tx_subscribe` subscriber every time the status of one of its
+/// transactions changes. The last one of a transaction has the `FINAL` status.
+/// The outcome of the transaction can be queried with `tx`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct RpcTxStatusNotification {
+    pub subscription: u64,
+    pub transaction_hash: CryptoHash,
+    pub sender_account_id: AccountId,
+    pub final_execution_status: TxExecutionStatus,
+}
+
+#[derive(thiserror::Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcTxSubscriptionError {
+    #[error("Transaction subscriptions are only supported over WebSocket")]
+    WebSocketRequired,
+    #[error("The connection can't have more than {limit} subscriptions")]
+    TooManySubscriptions { limit: usize },
+    #[error("Subscription {subscription} doesn't exist")]
+    UnknownSubscription { subscription: u64 },
+}
+
+impl From<RpcTxSubscriptionError> for crate::errors::RpcError {
+    fn from(error: RpcTxSubscriptionError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcTxSubscriptionError: {:?}", err),
+                );
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
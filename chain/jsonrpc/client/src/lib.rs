@@ -208,6 +208,14 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "query", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_batch_query(
+        &self,
+        request: near_jsonrpc_primitives::types::query::RpcBatchQueryRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::query::RpcBatchQueryResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_batch_query", request)
+    }
+
     pub fn block_by_id(&self, block_id: BlockId) -> RpcRequest<BlockView> {
         call_method(&self.client, &self.server_addr, "block", [block_id])
     }
@@ -252,6 +260,30 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_receipt", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_chunk_endorsements(
+        &self,
+        request: near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsResponse>
+    {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_chunk_endorsements", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_next_epoch_shard_assignments(
+        &self,
+        request: near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsResponse,
+    > {
+        call_method(
+            &self.client,
+            &self.server_addr,
+            "EXPERIMENTAL_next_epoch_shard_assignments",
+            request,
+        )
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_protocol_config(
         &self,
@@ -260,6 +292,15 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_protocol_config", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_shard_layout_at_block(
+        &self,
+        request: near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockResponse>
+    {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_shard_layout_at_block", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_split_storage_info(
         &self,
@@ -269,6 +310,16 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_split_storage_info", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_tx_execution_profile(
+        &self,
+        request: near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileResponse,
+    > {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_tx_execution_profile", request)
+    }
+
     pub fn validators(
         &self,
         epoch_id_or_block_id: Option<EpochReference>,
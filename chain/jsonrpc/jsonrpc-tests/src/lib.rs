@@ -66,6 +66,9 @@ pub fn start_all_with_validity_period(
         #[cfg(feature = "test_features")]
         noop().into_multi_sender(),
         Arc::new(DummyEntityDebugHandler {}),
+        Default::default(),
+        // No block notifications, the transaction subscriptions aren't tested here.
+        tokio::sync::broadcast::channel(1).0,
     );
     // setup_no_network_with_validity_period should use runtime_tempdir together with real runtime.
     (actor_handles.view_client_actor, addr, actor_handles.runtime_tempdir.unwrap())
@@ -11,7 +11,9 @@ use serde_json::json;
 use near_actix_test_utils::run_actix;
 use near_crypto::{InMemorySigner, Signature};
 use near_jsonrpc::client::{ChunkId, JsonRpcClient, new_client};
-use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_jsonrpc_primitives::types::query::{
+    QueryResponseKind, RpcBatchQueryResult, RpcQueryError, RpcQueryResponse,
+};
 use near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest;
 use near_network::test_utils::wait_or_timeout;
 use near_o11y::testonly::init_test_logger;
@@ -252,6 +254,50 @@ fn test_query_access_keys() {
     });
 }
 
+/// Connect to json rpc and query several accounts at once.
+#[test]
+fn test_batch_query() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let batch_response = client
+            .EXPERIMENTAL_batch_query(near_jsonrpc_primitives::types::query::RpcBatchQueryRequest {
+                block_reference: BlockReference::BlockId(BlockId::Height(0)),
+                queries: vec![
+                    QueryRequest::ViewAccount { account_id: "test".parse().unwrap() },
+                    QueryRequest::ViewAccount { account_id: "unknown".parse().unwrap() },
+                    QueryRequest::ViewAccessKeyList { account_id: "test".parse().unwrap() },
+                ],
+            })
+            .await
+            .unwrap();
+        assert_eq!(batch_response.block_height, 0);
+        let [account, unknown_account, access_keys] = batch_response.results.as_slice() else {
+            panic!("expected 3 results, got {:?}", batch_response.results);
+        };
+        match account {
+            RpcBatchQueryResult::Result(RpcQueryResponse {
+                kind: QueryResponseKind::ViewAccount(account),
+                block_height: 0,
+                ..
+            }) => assert_eq!(account.amount, TESTING_INIT_BALANCE),
+            result => panic!("queried account, but received something else: {:?}", result),
+        }
+        match unknown_account {
+            RpcBatchQueryResult::Error(RpcQueryError::UnknownAccount {
+                requested_account_id,
+                ..
+            }) => assert_eq!(requested_account_id.as_str(), "unknown"),
+            result => panic!("queried unknown account, but received something else: {:?}", result),
+        }
+        match access_keys {
+            RpcBatchQueryResult::Result(RpcQueryResponse {
+                kind: QueryResponseKind::AccessKeyList(access_keys),
+                ..
+            }) => assert_eq!(access_keys.keys.len(), 1),
+            result => panic!("queried access keys, but received something else: {:?}", result),
+        }
+    });
+}
+
 /// Connect to json rpc and query account info with soft-deprecated query API.
 #[test]
 fn test_query_by_path_access_key() {
@@ -247,3 +247,24 @@ fn test_check_invalid_tx() {
         }
     });
 }
+
+/// Transaction subscriptions are only served over WebSocket.
+#[test]
+fn test_tx_subscribe_requires_websocket() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let result = test_utils::call_method::<serde_json::Value>(
+            &client.client,
+            &client.server_addr,
+            "tx_subscribe",
+            serde_json::json!({ "sender_account_id": "test1" }),
+        )
+        .await;
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.error_struct.unwrap(),
+            near_jsonrpc_primitives::errors::RpcErrorKind::HandlerError(
+                serde_json::json!({ "name": "WEB_SOCKET_REQUIRED" })
+            )
+        );
+    });
+}
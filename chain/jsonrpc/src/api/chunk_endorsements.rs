@@ -0,0 +1,35 @@
+use near_async::messaging::AsyncSendError;
+use near_client_primitives::types::GetChunkEndorsementsError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::chunk_endorsements::{
+    RpcChunkEndorsementsError, RpcChunkEndorsementsRequest,
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcChunkEndorsementsRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<AsyncSendError> for RpcChunkEndorsementsError {
+    fn rpc_from(error: AsyncSendError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetChunkEndorsementsError> for RpcChunkEndorsementsError {
+    fn rpc_from(error: GetChunkEndorsementsError) -> Self {
+        match error {
+            GetChunkEndorsementsError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcChunkEndorsementsError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
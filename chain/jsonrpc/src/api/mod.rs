@@ -6,6 +6,7 @@ use near_jsonrpc_primitives::errors::{RpcError, ServerError};
 
 mod blocks;
 mod changes;
+mod chunk_endorsements;
 mod chunks;
 mod client_config;
 mod config;
@@ -17,9 +18,13 @@ mod network_info;
 mod query;
 mod receipts;
 mod sandbox;
+mod shard_assignments;
+mod shard_layout;
 mod split_storage;
 mod status;
 mod transactions;
+mod tx_execution_profile;
+mod tx_subscription;
 mod validator;
 
 pub trait RpcRequest: Sized {
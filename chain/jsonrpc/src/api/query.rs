@@ -1,17 +1,23 @@
 use near_async::messaging::AsyncSendError;
 use serde_json::Value;
 
-use near_client_primitives::types::QueryError;
+use near_client_primitives::types::{BatchQueryResponse, QueryError};
 use near_jsonrpc_primitives::errors::RpcParseError;
-use near_jsonrpc_primitives::types::query::{RpcQueryError, RpcQueryRequest, RpcQueryResponse};
+use near_jsonrpc_primitives::types::query::{
+    RpcBatchQueryRequest, RpcBatchQueryResponse, RpcBatchQueryResult, RpcQueryError,
+    RpcQueryRequest, RpcQueryResponse,
+};
 use near_primitives::types::BlockReference;
 use near_primitives::views::{QueryRequest, QueryResponse};
 
-use super::{Params, RpcFrom, RpcRequest};
+use super::{Params, RpcFrom, RpcInto, RpcRequest};
 
 /// Max size of the query path (soft-deprecated)
 const QUERY_DATA_MAX_SIZE: usize = 10 * 1024;
 
+/// Max number of queries in one `EXPERIMENTAL_batch_query` request.
+const BATCH_QUERY_MAX_QUERIES: usize = 100;
+
 /// Parses base58-encoded data from legacy path+data request format.
 fn parse_bs58_data(max_len: usize, encoded: String) -> Result<Vec<u8>, RpcParseError> {
     // N-byte encoded base58 string decodes to at most N bytes so there’s no
@@ -35,6 +41,19 @@ impl RpcRequest for RpcQueryRequest {
     }
 }
 
+impl RpcRequest for RpcBatchQueryRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        let request: Self = Params::parse(value)?;
+        if request.queries.len() > BATCH_QUERY_MAX_QUERIES {
+            return Err(RpcParseError(format!(
+                "Too many queries, at most {} are allowed",
+                BATCH_QUERY_MAX_QUERIES
+            )));
+        }
+        Ok(request)
+    }
+}
+
 fn parse_path_data(path: String, data: String) -> Result<RpcQueryRequest, RpcParseError> {
     // Handle a soft-deprecated version of the query API, which is based on
     // positional arguments with a "path"-style first argument.
@@ -145,6 +164,24 @@ impl RpcFrom<QueryResponse> for RpcQueryResponse {
     }
 }
 
+impl RpcFrom<BatchQueryResponse> for RpcBatchQueryResponse {
+    fn rpc_from(batch_query_response: BatchQueryResponse) -> Self {
+        let results = batch_query_response
+            .results
+            .into_iter()
+            .map(|result| match result {
+                Ok(query_response) => RpcBatchQueryResult::Result(query_response.rpc_into()),
+                Err(error) => RpcBatchQueryResult::Error(error.rpc_into()),
+            })
+            .collect();
+        Self {
+            block_height: batch_query_response.block_height,
+            block_hash: batch_query_response.block_hash,
+            results,
+        }
+    }
+}
+
 impl RpcFrom<near_primitives::views::QueryResponseKind>
     for near_jsonrpc_primitives::types::query::QueryResponseKind
 {
@@ -0,0 +1,42 @@
+use near_async::messaging::AsyncSendError;
+use near_client_primitives::types::GetNextEpochShardAssignmentsError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::shard_assignments::{
+    RpcNextEpochShardAssignmentsError, RpcNextEpochShardAssignmentsRequest,
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcNextEpochShardAssignmentsRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<AsyncSendError> for RpcNextEpochShardAssignmentsError {
+    fn rpc_from(error: AsyncSendError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetNextEpochShardAssignmentsError> for RpcNextEpochShardAssignmentsError {
+    fn rpc_from(error: GetNextEpochShardAssignmentsError) -> Self {
+        match error {
+            GetNextEpochShardAssignmentsError::UnknownBlock(error_message) => {
+                Self::UnknownBlock { error_message }
+            }
+            GetNextEpochShardAssignmentsError::UnknownEpoch => Self::UnknownEpoch,
+            GetNextEpochShardAssignmentsError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetNextEpochShardAssignmentsError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcNextEpochShardAssignmentsError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
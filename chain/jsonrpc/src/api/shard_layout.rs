@@ -0,0 +1,41 @@
+use near_async::messaging::AsyncSendError;
+use near_client_primitives::types::GetShardLayoutAtBlockError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::shard_layout::{
+    RpcShardLayoutAtBlockError, RpcShardLayoutAtBlockRequest,
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcShardLayoutAtBlockRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<AsyncSendError> for RpcShardLayoutAtBlockError {
+    fn rpc_from(error: AsyncSendError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetShardLayoutAtBlockError> for RpcShardLayoutAtBlockError {
+    fn rpc_from(error: GetShardLayoutAtBlockError) -> Self {
+        match error {
+            GetShardLayoutAtBlockError::UnknownBlock(error_message) => {
+                Self::UnknownBlock { error_message }
+            }
+            GetShardLayoutAtBlockError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetShardLayoutAtBlockError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcShardLayoutAtBlockError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
@@ -50,6 +50,14 @@ impl RpcFrom<near_client_primitives::debug::DebugStatusResponse>
                     x,
                 )
             }
+            near_client_primitives::debug::DebugStatusResponse::BlockProductionTimings(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::BlockProductionTimings(
+                    x,
+                )
+            }
+            near_client_primitives::debug::DebugStatusResponse::ShardHotspots(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::ShardHotspots(x)
+            }
         }
     }
 }
@@ -0,0 +1,41 @@
+use near_async::messaging::AsyncSendError;
+use near_client_primitives::types::GetTxExecutionProfileError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::tx_execution_profile::{
+    RpcTxExecutionProfileError, RpcTxExecutionProfileRequest,
+};
+use serde_json::Value;
+
+use super::{Params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcTxExecutionProfileRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcFrom<AsyncSendError> for RpcTxExecutionProfileError {
+    fn rpc_from(error: AsyncSendError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetTxExecutionProfileError> for RpcTxExecutionProfileError {
+    fn rpc_from(error: GetTxExecutionProfileError) -> Self {
+        match error {
+            GetTxExecutionProfileError::UnknownTransaction(error_message) => {
+                Self::UnknownTransaction { error_message }
+            }
+            GetTxExecutionProfileError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetTxExecutionProfileError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcTxExecutionProfileError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::tx_subscription::{
+    RpcTxSubscribeRequest, RpcTxUnsubscribeRequest,
+};
+use serde_json::Value;
+
+use super::{Params, RpcRequest};
+
+impl RpcRequest for RpcTxSubscribeRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
+
+impl RpcRequest for RpcTxUnsubscribeRequest {
+    fn parse(value: Value) -> Result<Self, RpcParseError> {
+        Params::parse(value)
+    }
+}
@@ -11,13 +11,19 @@ use near_async::messaging::{
 };
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    DebugStatus, GetBlock, GetBlockProof, GetChunk, GetClientConfig, GetExecutionOutcome,
-    GetGasPrice, GetMaintenanceWindows, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig,
-    GetReceipt, GetStateChanges, GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
-    ProcessTxRequest, ProcessTxResponse, Query, Status, TxStatus,
+    BatchQuery, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetClientConfig,
+    GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
+    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
+    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, ProcessTxRequest,
+    ProcessTxResponse, Query, Status, TxStatus,
+};
+use near_client_primitives::debug::{
+    DebugBlockProductionTimingsQuery, DebugBlockStatusQuery, DebugBlocksStartingMode,
+};
+use near_client_primitives::types::{
+    BlockNotification, GetChunkEndorsements, GetNextEpochShardAssignments, GetShardLayoutAtBlock,
+    GetSplitStorageInfo, GetTxExecutionProfile,
 };
-use near_client_primitives::debug::{DebugBlockStatusQuery, DebugBlocksStartingMode};
-use near_client_primitives::types::GetSplitStorageInfo;
 pub use near_jsonrpc_client_internal as client;
 pub use near_jsonrpc_primitives as primitives;
 use near_jsonrpc_primitives::errors::{RpcError, RpcErrorKind};
@@ -32,6 +38,7 @@ use near_jsonrpc_primitives::types::split_storage::{
 use near_jsonrpc_primitives::types::transactions::{
     RpcSendTransactionRequest, RpcTransactionResponse,
 };
+use near_jsonrpc_primitives::types::tx_subscription::RpcTxSubscriptionError;
 use near_network::debug::GetDebugStatus;
 use near_network::tcp::{self, ListenerAddr};
 use near_o11y::metrics::{Encoder, TextEncoder, prometheus};
@@ -43,11 +50,16 @@ use serde_json::{Value, json};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tokio::time::{sleep, timeout};
 use tracing::{error, info};
 
 mod api;
+mod load_shedding;
 mod metrics;
+mod websocket;
+
+pub use load_shedding::RpcLoadShedder;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
@@ -155,6 +167,9 @@ impl near_jsonrpc_primitives::types::transactions::RpcTransactionError {
             ProcessTxResponse::DoesNotTrackShard | ProcessTxResponse::RequestRouted => {
                 Self::DoesNotTrackShard
             }
+            ProcessTxResponse::ForwardingOverloaded { shard_id } => {
+                Self::ForwardingOverloaded { shard_id }
+            }
             internal_error => Self::InternalError { debug_info: format!("{:?}", internal_error) },
         }
     }
@@ -231,6 +246,7 @@ pub struct ProcessTxSenderForRpc(
 #[derive(Clone, near_async::MultiSend, near_async::MultiSenderFrom)]
 pub struct ClientSenderForRpc(
     AsyncSender<DebugStatus, ActixResult<DebugStatus>>,
+    AsyncSender<GetChunkEndorsements, ActixResult<GetChunkEndorsements>>,
     AsyncSender<GetClientConfig, ActixResult<GetClientConfig>>,
     AsyncSender<GetNetworkInfo, ActixResult<GetNetworkInfo>>,
     AsyncSender<Status, ActixResult<Status>>,
@@ -249,6 +265,7 @@ pub struct ClientSenderForRpc(
 
 #[derive(Clone, near_async::MultiSend, near_async::MultiSenderFrom)]
 pub struct ViewClientSenderForRpc(
+    AsyncSender<BatchQuery, ActixResult<BatchQuery>>,
     AsyncSender<GetBlock, ActixResult<GetBlock>>,
     AsyncSender<GetBlockProof, ActixResult<GetBlockProof>>,
     AsyncSender<GetChunk, ActixResult<GetChunk>>,
@@ -256,11 +273,14 @@ pub struct ViewClientSenderForRpc(
     AsyncSender<GetGasPrice, ActixResult<GetGasPrice>>,
     AsyncSender<GetMaintenanceWindows, ActixResult<GetMaintenanceWindows>>,
     AsyncSender<GetNextLightClientBlock, ActixResult<GetNextLightClientBlock>>,
+    AsyncSender<GetNextEpochShardAssignments, ActixResult<GetNextEpochShardAssignments>>,
     AsyncSender<GetProtocolConfig, ActixResult<GetProtocolConfig>>,
     AsyncSender<GetReceipt, ActixResult<GetReceipt>>,
+    AsyncSender<GetShardLayoutAtBlock, ActixResult<GetShardLayoutAtBlock>>,
     AsyncSender<GetSplitStorageInfo, ActixResult<GetSplitStorageInfo>>,
     AsyncSender<GetStateChanges, ActixResult<GetStateChanges>>,
     AsyncSender<GetStateChangesInBlock, ActixResult<GetStateChangesInBlock>>,
+    AsyncSender<GetTxExecutionProfile, ActixResult<GetTxExecutionProfile>>,
     AsyncSender<GetValidatorInfo, ActixResult<GetValidatorInfo>>,
     AsyncSender<GetValidatorOrdered, ActixResult<GetValidatorOrdered>>,
     AsyncSender<Query, ActixResult<Query>>,
@@ -292,6 +312,8 @@ struct JsonRpcHandler {
     enable_debug_rpc: bool,
     debug_pages_src_path: Option<PathBuf>,
     entity_debug_handler: Arc<dyn EntityDebugHandler>,
+    load_shedder: RpcLoadShedder,
+    block_notifications: broadcast::Sender<BlockNotification>,
 }
 
 impl JsonRpcHandler {
@@ -333,6 +355,9 @@ impl JsonRpcHandler {
         request: Request,
     ) -> (String, Result<Value, RpcError>) {
         let method_name = request.method.to_string();
+        if self.load_shedder.should_shed(&method_name) {
+            return (method_name.clone(), Err(RpcError::node_overloaded(method_name)));
+        }
         let request = match self.process_adversarial_request_internal(request).await {
             Ok(response) => return (method_name, response),
             Err(request) => request,
@@ -406,16 +431,25 @@ impl JsonRpcHandler {
             "tx" => {
                 process_method_call(request, |params| self.tx_status_common(params, false)).await
             }
+            "tx_subscribe" | "tx_unsubscribe" => {
+                Err(RpcError::from(RpcTxSubscriptionError::WebSocketRequired))
+            }
             "validators" => process_method_call(request, |params| self.validators(params)).await,
             "client_config" => {
                 process_method_call(request, |_params: ()| self.client_config()).await
             }
+            "EXPERIMENTAL_batch_query" => {
+                process_method_call(request, |params| self.batch_query(params)).await
+            }
             "EXPERIMENTAL_changes" => {
                 process_method_call(request, |params| self.changes_in_block_by_type(params)).await
             }
             "EXPERIMENTAL_changes_in_block" => {
                 process_method_call(request, |params| self.changes_in_block(params)).await
             }
+            "EXPERIMENTAL_chunk_endorsements" => {
+                process_method_call(request, |params| self.chunk_endorsements(params)).await
+            }
             "EXPERIMENTAL_congestion_level" => {
                 process_method_call(request, |params| self.congestion_level(params)).await
             }
@@ -434,12 +468,22 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_light_client_block_proof" => {
                 process_method_call(request, |params| self.light_client_block_proof(params)).await
             }
+            "EXPERIMENTAL_next_epoch_shard_assignments" => {
+                process_method_call(request, |params| self.next_epoch_shard_assignments(params))
+                    .await
+            }
             "EXPERIMENTAL_protocol_config" => {
                 process_method_call(request, |params| self.protocol_config(params)).await
             }
             "EXPERIMENTAL_receipt" => {
                 process_method_call(request, |params| self.receipt(params)).await
             }
+            "EXPERIMENTAL_shard_layout_at_block" => {
+                process_method_call(request, |params| self.shard_layout_at_block(params)).await
+            }
+            "EXPERIMENTAL_tx_execution_profile" => {
+                process_method_call(request, |params| self.tx_execution_profile(params)).await
+            }
             "EXPERIMENTAL_tx_status" => {
                 process_method_call(request, |params| self.tx_status_common(params, true)).await
             }
@@ -807,6 +851,15 @@ impl JsonRpcHandler {
                     "/debug/api/requested_state_parts" => {
                         self.client_send(DebugStatus::RequestedStateParts).await?.rpc_into()
                     }
+                    "/debug/api/block_production_timings" => self
+                        .client_send(DebugStatus::BlockProductionTimings(
+                            DebugBlockProductionTimingsQuery::default(),
+                        ))
+                        .await?
+                        .rpc_into(),
+                    "/debug/api/shard_hotspots" => {
+                        self.client_send(DebugStatus::ShardHotspots).await?.rpc_into()
+                    }
                     "/debug/api/peer_store" => self
                         .peer_manager_send(near_network::debug::GetDebugStatus::PeerStore)
                         .await?
@@ -864,6 +917,24 @@ impl JsonRpcHandler {
         }
     }
 
+    pub async fn debug_block_production_timings(
+        &self,
+        query: DebugBlockProductionTimingsQuery,
+    ) -> Result<
+        Option<near_jsonrpc_primitives::types::status::RpcDebugStatusResponse>,
+        near_jsonrpc_primitives::types::status::RpcStatusError,
+    > {
+        if self.enable_debug_rpc {
+            let debug_status =
+                self.client_send(DebugStatus::BlockProductionTimings(query)).await?.rpc_into();
+            Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
+                status_response: debug_status,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub async fn debug_epoch_info(
         &self,
         epoch_id: Option<near_primitives::types::EpochId>,
@@ -893,6 +964,57 @@ impl JsonRpcHandler {
         Ok(RpcProtocolConfigResponse { config_view })
     }
 
+    pub async fn next_epoch_shard_assignments(
+        &self,
+        request_data: near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsResponse,
+        near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsError,
+    > {
+        let near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsRequest {
+            block_reference,
+            account_id,
+        } = request_data;
+        let shard_assignments_view = self
+            .view_client_send(GetNextEpochShardAssignments { block_reference, account_id })
+            .await?;
+        Ok(near_jsonrpc_primitives::types::shard_assignments::RpcNextEpochShardAssignmentsResponse {
+            shard_assignments_view,
+        })
+    }
+
+    pub async fn shard_layout_at_block(
+        &self,
+        request_data: near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockResponse,
+        near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockError,
+    > {
+        let near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockRequest {
+            block_reference,
+            account_id,
+        } = request_data;
+        let shard_layout_view =
+            self.view_client_send(GetShardLayoutAtBlock { block_reference, account_id }).await?;
+        Ok(near_jsonrpc_primitives::types::shard_layout::RpcShardLayoutAtBlockResponse {
+            shard_layout_view,
+        })
+    }
+
+    pub async fn tx_execution_profile(
+        &self,
+        request_data: near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileResponse,
+        near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileError,
+    > {
+        let receipts =
+            self.view_client_send(GetTxExecutionProfile { tx_hash: request_data.tx_hash }).await?;
+        Ok(near_jsonrpc_primitives::types::tx_execution_profile::RpcTxExecutionProfileResponse {
+            receipts,
+        })
+    }
+
     async fn query(
         &self,
         request_data: near_jsonrpc_primitives::types::query::RpcQueryRequest,
@@ -906,6 +1028,22 @@ impl JsonRpcHandler {
         Ok(query_response.rpc_into())
     }
 
+    async fn batch_query(
+        &self,
+        request_data: near_jsonrpc_primitives::types::query::RpcBatchQueryRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::query::RpcBatchQueryResponse,
+        near_jsonrpc_primitives::types::query::RpcQueryError,
+    > {
+        let batch_query_response = self
+            .view_client_send(BatchQuery {
+                block_reference: request_data.block_reference,
+                requests: request_data.queries,
+            })
+            .await?;
+        Ok(batch_query_response.rpc_into())
+    }
+
     async fn tx_status_common(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcTransactionStatusRequest,
@@ -971,10 +1109,13 @@ impl JsonRpcHandler {
         })?;
         let congestion_info = chunk_view.header.congestion_info;
         let congestion_level = congestion_info
+            .as_ref()
             .map(|info| info.congestion_level(config.runtime_config.congestion_control_config))
             .unwrap_or(0.0);
         Ok(near_jsonrpc_primitives::types::congestion::RpcCongestionLevelResponse {
             congestion_level,
+            shard_id: chunk_view.header.shard_id,
+            congestion_info,
         })
     }
 
@@ -1197,6 +1338,19 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::client_config::RpcClientConfigResponse { client_config })
     }
 
+    pub async fn chunk_endorsements(
+        &self,
+        request_data: near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsResponse,
+        near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsError,
+    > {
+        let chunks = self.client_send(GetChunkEndorsements { height: request_data.height }).await?;
+        Ok(near_jsonrpc_primitives::types::chunk_endorsements::RpcChunkEndorsementsResponse {
+            chunks,
+        })
+    }
+
     pub async fn split_storage_info(
         &self,
         _request_data: near_jsonrpc_primitives::types::split_storage::RpcSplitStorageInfoRequest,
@@ -1422,6 +1576,7 @@ async fn rpc_handler(
                     match error_struct.get("name").and_then(|name| name.as_str()) {
                         Some("UNKNOWN_BLOCK") => handle_unknown_block(request.0, handler).await,
                         Some("TIMEOUT_ERROR") => HttpResponse::RequestTimeout(),
+                        Some("NODE_OVERLOADED") => HttpResponse::ServiceUnavailable(),
                         _ => HttpResponse::Ok(),
                     }
                 }
@@ -1514,6 +1669,17 @@ async fn deprecated_debug_block_status_handler(
     }
 }
 
+async fn debug_block_production_timings_handler(
+    query: web::Query<DebugBlockProductionTimingsQuery>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.debug_block_production_timings(query.0).await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct DebugRpcEpochInfoRequest {
     #[serde(flatten)]
@@ -1658,6 +1824,8 @@ pub fn start_http(
     peer_manager_sender: PeerManagerSenderForRpc,
     #[cfg(feature = "test_features")] gc_sender: GCSenderForRpc,
     entity_debug_handler: Arc<dyn EntityDebugHandler>,
+    load_shedder: RpcLoadShedder,
+    block_notifications: broadcast::Sender<BlockNotification>,
 ) -> Vec<(&'static str, actix_web::dev::ServerHandle)> {
     let RpcConfig {
         addr,
@@ -1685,12 +1853,15 @@ pub fn start_http(
                 enable_debug_rpc,
                 debug_pages_src_path: debug_pages_src_path.clone().map(Into::into),
                 entity_debug_handler: entity_debug_handler.clone(),
+                load_shedder: load_shedder.clone(),
+                block_notifications: block_notifications.clone(),
                 #[cfg(feature = "test_features")]
                 gc_sender: gc_sender.clone(),
             }))
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(websocket::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1719,6 +1890,10 @@ pub fn start_http(
                     web::resource("/debug/api/block_status")
                         .route(web::get().to(debug_block_status_handler)),
                 )
+                .service(
+                    web::resource("/debug/api/block_production_timings")
+                        .route(web::get().to(debug_block_production_timings_handler)),
+                )
                 .service(
                     web::resource("/debug/api/epoch_info/{epoch_id}")
                         .route(web::get().to(debug_epoch_info_handler)),
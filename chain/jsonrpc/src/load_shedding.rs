@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Rejects the requests of the expensive methods while the node is
+/// overloaded, e.g. short of memory. The rejected requests get the
+/// `NODE_OVERLOADED` error with the 503 HTTP status, so that the clients can
+/// retry them on another node.
+#[derive(Clone, Default)]
+pub struct RpcLoadShedder(Arc<RpcLoadShedderInner>);
+
+#[derive(Default)]
+struct RpcLoadShedderInner {
+    enabled: AtomicBool,
+    methods: HashSet<String>,
+}
+
+impl RpcLoadShedder {
+    /// Creates a shedder of the given methods, which is disabled until
+    /// `set_enabled` is called.
+    pub fn new(methods: impl IntoIterator<Item = String>) -> Self {
+        Self(Arc::new(RpcLoadShedderInner {
+            enabled: AtomicBool::new(false),
+            methods: methods.into_iter().collect(),
+        }))
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn should_shed(&self, method_name: &str) -> bool {
+        self.is_enabled() && self.0.methods.contains(method_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_shed() {
+        let shedder = RpcLoadShedder::new(["query".to_string()]);
+        assert!(!shedder.should_shed("query"));
+        shedder.set_enabled(true);
+        assert!(shedder.should_shed("query"));
+        assert!(!shedder.should_shed("block"));
+        assert!(!RpcLoadShedder::default().should_shed("query"));
+    }
+}
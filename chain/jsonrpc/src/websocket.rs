@@ -0,0 +1,713 @@
+//! WebSocket endpoint of the JSON-RPC server.
+//!
+//! A connection accepts the same requests as the HTTP endpoint, plus
+//! `tx_subscribe` and `tx_unsubscribe`. The subscriber of a transaction, or
+//! of all the transactions of a signer, is pushed a `tx_status` notification
+//! every time the status of one of them changes, up to `FINAL`. The statuses
+//! are derived from the transactions and the outcomes of the blocks announced
+//! on `Client::block_notifications`, instead of being polled by the subscriber.
+//! The status of a transaction is only queried when it's subscribed to, or
+//! when the connection missed blocks. The refund receipts aren't told apart
+//! from the other receipts, so a transaction is executed once all of them are.
+
+use std::collections::{HashMap, HashSet};
+use std::pin::pin;
+
+use actix_http::ws::{self, CloseCode, Codec, Frame};
+use actix_web::http::{StatusCode, header};
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{Error as HttpError, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+use near_client::TxStatus;
+use near_client_primitives::types::BlockNotification;
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{self, Message, Request};
+use near_jsonrpc_primitives::types::transactions::RpcTransactionError;
+use near_jsonrpc_primitives::types::tx_subscription::{
+    RpcTxStatusNotification, RpcTxSubscribeRequest, RpcTxSubscribeResponse, RpcTxSubscriptionError,
+    RpcTxUnsubscribeRequest, TX_STATUS_NOTIFICATION_METHOD,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::views::{TxExecutionStatus, TxStatusView};
+use tokio::sync::broadcast;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{JsonRpcHandler, process_method_call};
+
+/// Maximum number of subscriptions of one connection.
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 100;
+
+/// Maximum number of transactions followed for one connection. The new
+/// transactions of the signer subscriptions are ignored above it.
+const MAX_FOLLOWED_TRANSACTIONS_PER_CONNECTION: usize = 1000;
+
+/// Upgrades the request to a WebSocket connection and serves it until either
+/// side closes it.
+pub(crate) async fn ws_handler(
+    request: HttpRequest,
+    payload: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    ws::verify_handshake(request.head())?;
+    // The key is present, `verify_handshake` checks it.
+    let key = request.headers().get(header::SEC_WEBSOCKET_KEY).unwrap();
+    let accept_key = ws::hash_key(key.as_bytes());
+
+    let block_notifications = handler.block_notifications.subscribe();
+    let (sender, receiver) = mpsc::unbounded();
+    let connection = Connection { handler, sender, subscriptions: Subscriptions::default() };
+    actix_web::rt::spawn(connection.run(payload, block_notifications));
+
+    let mut codec = Codec::new();
+    let frames = receiver.map(move |message| {
+        let mut buffer = BytesMut::new();
+        codec.encode(message, &mut buffer).map(|()| buffer.freeze())
+    });
+    Ok(HttpResponse::build(StatusCode::SWITCHING_PROTOCOLS)
+        .upgrade("websocket")
+        .insert_header((header::SEC_WEBSOCKET_ACCEPT, &accept_key[..]))
+        .streaming(frames))
+}
+
+/// A transaction whose status changes are pushed to a subscriber.
+struct FollowedTransaction {
+    subscription: u64,
+    tx_hash: CryptoHash,
+    sender_account_id: AccountId,
+    /// The status has to be queried, it can't be derived from the blocks
+    /// notified so far.
+    needs_refresh: bool,
+    /// The transaction, or the receipts it produced, that are still to be
+    /// executed.
+    pending: HashSet<CryptoHash>,
+    /// Height of the block including the transaction, or of a later one.
+    included_height: Option<BlockHeight>,
+    /// Height of the block executing the last pending receipt, or of a later
+    /// one.
+    executed_height: Option<BlockHeight>,
+    /// The last status pushed to the subscriber.
+    status: TxExecutionStatus,
+}
+
+impl FollowedTransaction {
+    fn new(
+        subscription: u64,
+        tx_hash: CryptoHash,
+        sender_account_id: AccountId,
+        needs_refresh: bool,
+    ) -> Self {
+        Self {
+            subscription,
+            tx_hash,
+            sender_account_id,
+            needs_refresh,
+            pending: HashSet::from([tx_hash]),
+            included_height: None,
+            executed_height: None,
+            status: TxExecutionStatus::None,
+        }
+    }
+
+    /// Resets the transaction to the queried status, as of the block at
+    /// `height`. `None` stands for a transaction not included in a block.
+    fn refresh(&mut self, status: Option<TxStatusView>, height: BlockHeight) {
+        // The height of a block that is final already.
+        const FINAL_HEIGHT: BlockHeight = 0;
+        self.needs_refresh = false;
+        self.pending = HashSet::from([self.tx_hash]);
+        let Some(status) = status else {
+            self.included_height = None;
+            self.executed_height = None;
+            return;
+        };
+        (self.included_height, self.executed_height) = match status.status {
+            TxExecutionStatus::None => (None, None),
+            TxExecutionStatus::Included => (Some(height), None),
+            TxExecutionStatus::ExecutedOptimistic => (Some(height), Some(height)),
+            TxExecutionStatus::IncludedFinal => (Some(FINAL_HEIGHT), None),
+            TxExecutionStatus::Executed => (Some(FINAL_HEIGHT), Some(height)),
+            TxExecutionStatus::Final => (Some(FINAL_HEIGHT), Some(FINAL_HEIGHT)),
+        };
+        if self.executed_height.is_some() {
+            self.pending.clear();
+            return;
+        }
+        let Some(outcome) = status.into_outcome() else {
+            return;
+        };
+        let executed = std::iter::once(&outcome.transaction_outcome)
+            .chain(&outcome.receipts_outcome)
+            .collect::<Vec<_>>();
+        self.pending = executed
+            .iter()
+            .flat_map(|outcome| outcome.outcome.receipt_ids.iter().copied())
+            .collect();
+        for outcome in executed {
+            self.pending.remove(&outcome.id);
+        }
+    }
+
+    /// Applies the transactions and the outcomes of the block at `height`.
+    fn apply_block(
+        &mut self,
+        height: BlockHeight,
+        is_included: bool,
+        outcomes: &HashMap<CryptoHash, &[CryptoHash]>,
+    ) {
+        if is_included {
+            self.included_height.get_or_insert(height);
+        }
+        // The receipts produced in the block may be executed in it too.
+        loop {
+            let executed = self
+                .pending
+                .iter()
+                .filter(|id| outcomes.contains_key(*id))
+                .copied()
+                .collect::<Vec<_>>();
+            if executed.is_empty() {
+                break;
+            }
+            for id in executed {
+                self.pending.remove(&id);
+                self.pending.extend(outcomes[&id].iter().copied());
+                if id == self.tx_hash {
+                    self.included_height.get_or_insert(height);
+                }
+            }
+        }
+        if self.included_height.is_some() && self.pending.is_empty() {
+            self.executed_height.get_or_insert(height);
+        }
+    }
+
+    fn current_status(&self, last_final_height: BlockHeight) -> TxExecutionStatus {
+        let is_final = |height: BlockHeight| height <= last_final_height;
+        match (self.included_height, self.executed_height) {
+            (None, _) => TxExecutionStatus::None,
+            (Some(_), Some(executed)) if is_final(executed) => TxExecutionStatus::Final,
+            (Some(included), Some(_)) if is_final(included) => TxExecutionStatus::Executed,
+            (Some(_), Some(_)) => TxExecutionStatus::ExecutedOptimistic,
+            (Some(included), None) if is_final(included) => TxExecutionStatus::IncludedFinal,
+            (Some(_), None) => TxExecutionStatus::Included,
+        }
+    }
+}
+
+/// The subscriptions of a connection, and the transactions followed for them.
+#[derive(Default)]
+struct Subscriptions {
+    requests: HashMap<u64, RpcTxSubscribeRequest>,
+    next_subscription: u64,
+    followed: Vec<FollowedTransaction>,
+    /// Height and last final height of the last block notified.
+    head: Option<(BlockHeight, BlockHeight)>,
+}
+
+impl Subscriptions {
+    fn subscribe(
+        &mut self,
+        request: RpcTxSubscribeRequest,
+    ) -> Result<RpcTxSubscribeResponse, RpcTxSubscriptionError> {
+        if self.requests.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+            return Err(RpcTxSubscriptionError::TooManySubscriptions {
+                limit: MAX_SUBSCRIPTIONS_PER_CONNECTION,
+            });
+        }
+        let subscription = self.next_subscription;
+        self.next_subscription += 1;
+        if let Some(tx_hash) = request.tx_hash {
+            // The transaction may have been included in a block already.
+            self.follow(FollowedTransaction::new(
+                subscription,
+                tx_hash,
+                request.sender_account_id.clone(),
+                true,
+            ));
+        }
+        self.requests.insert(subscription, request);
+        Ok(RpcTxSubscribeResponse { subscription })
+    }
+
+    fn unsubscribe(
+        &mut self,
+        request: RpcTxUnsubscribeRequest,
+    ) -> Result<(), RpcTxSubscriptionError> {
+        let subscription = request.subscription;
+        if self.requests.remove(&subscription).is_none() {
+            return Err(RpcTxSubscriptionError::UnknownSubscription { subscription });
+        }
+        self.followed.retain(|followed| followed.subscription != subscription);
+        Ok(())
+    }
+
+    fn follow(&mut self, followed: FollowedTransaction) {
+        if self.followed.len() >= MAX_FOLLOWED_TRANSACTIONS_PER_CONNECTION {
+            tracing::debug!(target: "jsonrpc", tx_hash = ?followed.tx_hash, "too many followed transactions");
+            return;
+        }
+        self.followed.push(followed);
+    }
+
+    /// Returns the transactions whose status has to be queried. They are
+    /// only returned once a block has been notified.
+    fn to_refresh(&self) -> Vec<(CryptoHash, AccountId)> {
+        if self.head.is_none() {
+            return vec![];
+        }
+        self.followed
+            .iter()
+            .filter(|followed| followed.needs_refresh)
+            .map(|followed| (followed.tx_hash, followed.sender_account_id.clone()))
+            .collect()
+    }
+
+    fn refresh(
+        &mut self,
+        tx_hash: &CryptoHash,
+        status: Option<TxStatusView>,
+    ) -> Vec<RpcTxStatusNotification> {
+        let Some((height, _)) = self.head else {
+            return vec![];
+        };
+        for followed in &mut self.followed {
+            if followed.needs_refresh && &followed.tx_hash == tx_hash {
+                followed.refresh(status.clone(), height);
+            }
+        }
+        self.take_notifications()
+    }
+
+    /// The transactions and outcomes of the missed blocks are unknown, so
+    /// the statuses of all the followed transactions have to be queried.
+    fn on_missed_blocks(&mut self) {
+        for followed in &mut self.followed {
+            followed.needs_refresh = true;
+        }
+    }
+
+    fn on_block(&mut self, block: &BlockNotification) -> Vec<RpcTxStatusNotification> {
+        self.head = Some((block.height, block.last_final_height));
+        for (tx_hash, signer_id) in &block.transactions {
+            let subscriptions = self
+                .requests
+                .iter()
+                .filter(|(_, request)| {
+                    request.tx_hash.is_none() && &request.sender_account_id == signer_id
+                })
+                .map(|(subscription, _)| *subscription)
+                .collect::<Vec<_>>();
+            for subscription in subscriptions {
+                self.follow(FollowedTransaction::new(
+                    subscription,
+                    *tx_hash,
+                    signer_id.clone(),
+                    false,
+                ));
+            }
+        }
+        let outcomes = block
+            .outcomes
+            .iter()
+            .map(|(id, receipt_ids)| (*id, receipt_ids.as_slice()))
+            .collect::<HashMap<_, _>>();
+        for followed in &mut self.followed {
+            let is_included =
+                block.transactions.iter().any(|(tx_hash, _)| tx_hash == &followed.tx_hash);
+            followed.apply_block(block.height, is_included, &outcomes);
+        }
+        self.take_notifications()
+    }
+
+    /// Returns the notifications of the status changes, and stops following
+    /// the final transactions.
+    fn take_notifications(&mut self) -> Vec<RpcTxStatusNotification> {
+        let Some((_, last_final_height)) = self.head else {
+            return vec![];
+        };
+        let mut notifications = vec![];
+        for followed in &mut self.followed {
+            if followed.needs_refresh {
+                continue;
+            }
+            let status = followed.current_status(last_final_height);
+            if status == followed.status {
+                continue;
+            }
+            followed.status = status.clone();
+            notifications.push(RpcTxStatusNotification {
+                subscription: followed.subscription,
+                transaction_hash: followed.tx_hash,
+                sender_account_id: followed.sender_account_id.clone(),
+                final_execution_status: status,
+            });
+        }
+        for followed in &self.followed {
+            if followed.status != TxExecutionStatus::Final {
+                continue;
+            }
+            // The subscription to a single transaction is over.
+            let subscription = followed.subscription;
+            if self.requests.get(&subscription).is_some_and(|request| request.tx_hash.is_some()) {
+                self.requests.remove(&subscription);
+            }
+        }
+        self.followed.retain(|followed| followed.status != TxExecutionStatus::Final);
+        notifications
+    }
+}
+
+enum Event {
+    Payload(Option<Result<Bytes, actix_web::error::PayloadError>>),
+    Block(Result<BlockNotification, broadcast::error::RecvError>),
+}
+
+struct Connection {
+    handler: web::Data<JsonRpcHandler>,
+    /// Messages to send to the other side.
+    sender: mpsc::UnboundedSender<ws::Message>,
+    subscriptions: Subscriptions,
+}
+
+impl Connection {
+    async fn run(
+        mut self,
+        mut payload: web::Payload,
+        mut block_notifications: broadcast::Receiver<BlockNotification>,
+    ) {
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+        loop {
+            let event = {
+                let payload = pin!(payload.next());
+                let block_notification = pin!(block_notifications.recv());
+                match future::select(payload, block_notification).await {
+                    Either::Left((payload, _)) => Event::Payload(payload),
+                    Either::Right((block_notification, _)) => Event::Block(block_notification),
+                }
+            };
+            match event {
+                Event::Payload(Some(Ok(bytes))) => {
+                    buffer.extend_from_slice(&bytes);
+                    loop {
+                        match codec.decode(&mut buffer) {
+                            Ok(Some(frame)) => {
+                                if !self.handle_frame(frame).await {
+                                    return;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(err) => {
+                                tracing::debug!(target: "jsonrpc", ?err, "invalid websocket frame");
+                                self.close(CloseCode::Protocol);
+                                return;
+                            }
+                        }
+                    }
+                }
+                Event::Payload(Some(Err(err))) => {
+                    tracing::debug!(target: "jsonrpc", ?err, "websocket connection failed");
+                    return;
+                }
+                Event::Payload(None) => return,
+                Event::Block(Ok(block_notification)) => {
+                    let notifications = self.subscriptions.on_block(&block_notification);
+                    self.notify(notifications);
+                    self.refresh_followed_transactions().await;
+                }
+                Event::Block(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                    // The new transactions of the signers in the skipped
+                    // blocks can't be followed anymore.
+                    tracing::debug!(target: "jsonrpc", skipped, "websocket connection missed blocks");
+                    self.subscriptions.on_missed_blocks();
+                    self.refresh_followed_transactions().await;
+                }
+                Event::Block(Err(broadcast::error::RecvError::Closed)) => {
+                    self.close(CloseCode::Away);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns false if the connection has to be closed.
+    async fn handle_frame(&mut self, frame: Frame) -> bool {
+        match frame {
+            Frame::Text(bytes) | Frame::Binary(bytes) => {
+                let response = match message::from_slice(&bytes) {
+                    Ok(Message::Request(request)) => self.handle_request(request).await,
+                    Ok(message) => self.handler.process(message).await,
+                    Err(broken) => broken.reply(),
+                };
+                self.send(response);
+                // A new subscription to a transaction gets the current status
+                // right away rather than on the next block.
+                self.refresh_followed_transactions().await;
+                true
+            }
+            Frame::Ping(bytes) => {
+                self.send_ws(ws::Message::Pong(bytes));
+                true
+            }
+            Frame::Pong(_) => true,
+            Frame::Close(reason) => {
+                self.send_ws(ws::Message::Close(reason));
+                false
+            }
+            // Fragmented messages aren't supported.
+            Frame::Continuation(_) => {
+                self.close(CloseCode::Unsupported);
+                false
+            }
+        }
+    }
+
+    async fn handle_request(&mut self, request: Request) -> Message {
+        let id = request.id.clone();
+        let subscriptions = &mut self.subscriptions;
+        let result = match request.method.as_ref() {
+            "tx_subscribe" => {
+                process_method_call(request, |request| {
+                    future::ready(subscriptions.subscribe(request))
+                })
+                .await
+            }
+            "tx_unsubscribe" => {
+                process_method_call(request, |request| {
+                    future::ready(subscriptions.unsubscribe(request))
+                })
+                .await
+            }
+            _ => return self.handler.process(Message::Request(request)).await,
+        };
+        Message::response(id, result)
+    }
+
+    /// Queries the statuses that can't be derived from the notified blocks.
+    /// The ones that fail are queried again on the next block.
+    async fn refresh_followed_transactions(&mut self) {
+        for (tx_hash, signer_account_id) in self.subscriptions.to_refresh() {
+            let status: Result<TxStatusView, RpcTransactionError> = self
+                .handler
+                .view_client_send(TxStatus { tx_hash, signer_account_id, fetch_receipt: false })
+                .await;
+            let status = match status {
+                Ok(status) => Some(status),
+                // Not included in a block yet.
+                Err(RpcTransactionError::UnknownTransaction { .. }) => None,
+                Err(err) => {
+                    tracing::debug!(target: "jsonrpc", ?err, ?tx_hash, "failed to get the status of a followed transaction");
+                    continue;
+                }
+            };
+            let notifications = self.subscriptions.refresh(&tx_hash, status);
+            self.notify(notifications);
+        }
+    }
+
+    fn notify(&self, notifications: Vec<RpcTxStatusNotification>) {
+        for notification in notifications {
+            match serde_json::to_value(notification) {
+                Ok(params) => self
+                    .send(Message::notification(TX_STATUS_NOTIFICATION_METHOD.to_string(), params)),
+                Err(err) => {
+                    self.send(Message::error(RpcError::serialization_error(err.to_string())))
+                }
+            }
+        }
+    }
+
+    fn send(&self, message: Message) {
+        let message: String = message.into();
+        self.send_ws(ws::Message::Text(message.into()));
+    }
+
+    fn send_ws(&self, message: ws::Message) {
+        // The other side may have gone away, the payload ends then too.
+        let _ = self.sender.unbounded_send(message);
+    }
+
+    fn close(&self, code: CloseCode) {
+        self.send_ws(ws::Message::Close(Some(code.into())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_client_primitives::types::BlockNotification;
+    use near_jsonrpc_primitives::types::tx_subscription::{
+        RpcTxStatusNotification, RpcTxSubscribeRequest, RpcTxSubscriptionError,
+        RpcTxUnsubscribeRequest,
+    };
+    use near_primitives::hash::{CryptoHash, hash};
+    use near_primitives::types::{AccountId, BlockHeight};
+    use near_primitives::views::TxExecutionStatus;
+
+    use super::Subscriptions;
+
+    fn block(
+        height: BlockHeight,
+        last_final_height: BlockHeight,
+        transactions: Vec<(CryptoHash, AccountId)>,
+        outcomes: Vec<(CryptoHash, Vec<CryptoHash>)>,
+    ) -> BlockNotification {
+        BlockNotification {
+            block_hash: hash(&height.to_le_bytes()),
+            height,
+            last_final_height,
+            transactions,
+            outcomes,
+        }
+    }
+
+    fn notification(
+        subscription: u64,
+        tx_hash: CryptoHash,
+        sender_account_id: &AccountId,
+        status: TxExecutionStatus,
+    ) -> RpcTxStatusNotification {
+        RpcTxStatusNotification {
+            subscription,
+            transaction_hash: tx_hash,
+            sender_account_id: sender_account_id.clone(),
+            final_execution_status: status,
+        }
+    }
+
+    #[test]
+    fn test_subscribe_notify_unsubscribe() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let (alice_tx, alice_receipt, alice_refund) = (hash(b"tx1"), hash(b"r1"), hash(b"r2"));
+        let (bob_tx, bob_receipt) = (hash(b"tx2"), hash(b"r3"));
+        let mut subscriptions = Subscriptions::default();
+
+        let alice_subscription = subscriptions
+            .subscribe(RpcTxSubscribeRequest { sender_account_id: alice.clone(), tx_hash: None })
+            .unwrap()
+            .subscription;
+        let bob_subscription = subscriptions
+            .subscribe(RpcTxSubscribeRequest {
+                sender_account_id: bob.clone(),
+                tx_hash: Some(bob_tx),
+            })
+            .unwrap()
+            .subscription;
+        // The status is only queried once a block has been notified.
+        assert_eq!(subscriptions.to_refresh(), vec![]);
+
+        let notifications = subscriptions.on_block(&block(
+            1,
+            0,
+            vec![(alice_tx, alice.clone())],
+            vec![(alice_tx, vec![alice_receipt])],
+        ));
+        assert_eq!(
+            notifications,
+            vec![notification(alice_subscription, alice_tx, &alice, TxExecutionStatus::Included)]
+        );
+        assert_eq!(subscriptions.to_refresh(), vec![(bob_tx, bob.clone())]);
+        assert_eq!(subscriptions.refresh(&bob_tx, None), vec![]);
+        assert_eq!(subscriptions.to_refresh(), vec![]);
+
+        // The refund is executed in the same block as the receipt producing it.
+        // The notifications come in the order the transactions are followed.
+        let notifications = subscriptions.on_block(&block(
+            2,
+            0,
+            vec![(bob_tx, bob.clone())],
+            vec![
+                (alice_receipt, vec![alice_refund]),
+                (alice_refund, vec![]),
+                (bob_tx, vec![bob_receipt]),
+            ],
+        ));
+        assert_eq!(
+            notifications,
+            vec![
+                notification(bob_subscription, bob_tx, &bob, TxExecutionStatus::Included),
+                notification(
+                    alice_subscription,
+                    alice_tx,
+                    &alice,
+                    TxExecutionStatus::ExecutedOptimistic
+                ),
+            ]
+        );
+
+        let notifications = subscriptions.on_block(&block(3, 1, vec![], vec![]));
+        assert_eq!(
+            notifications,
+            vec![notification(alice_subscription, alice_tx, &alice, TxExecutionStatus::Executed)]
+        );
+
+        let notifications = subscriptions.on_block(&block(4, 2, vec![], vec![]));
+        assert_eq!(
+            notifications,
+            vec![
+                notification(bob_subscription, bob_tx, &bob, TxExecutionStatus::IncludedFinal),
+                notification(alice_subscription, alice_tx, &alice, TxExecutionStatus::Final),
+            ]
+        );
+
+        // The subscription to the signer goes on, unlike the ones to a
+        // single final transaction.
+        subscriptions
+            .unsubscribe(RpcTxUnsubscribeRequest { subscription: bob_subscription })
+            .unwrap();
+        let notifications =
+            subscriptions.on_block(&block(5, 3, vec![], vec![(bob_receipt, vec![])]));
+        assert_eq!(notifications, vec![]);
+        assert!(matches!(
+            subscriptions.unsubscribe(RpcTxUnsubscribeRequest { subscription: bob_subscription }),
+            Err(RpcTxSubscriptionError::UnknownSubscription { .. })
+        ));
+        subscriptions
+            .unsubscribe(RpcTxUnsubscribeRequest { subscription: alice_subscription })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_single_transaction_subscription_ends_when_final() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let tx_hash = hash(b"tx");
+        let mut subscriptions = Subscriptions::default();
+        let subscription = subscriptions
+            .subscribe(RpcTxSubscribeRequest {
+                sender_account_id: alice.clone(),
+                tx_hash: Some(tx_hash),
+            })
+            .unwrap()
+            .subscription;
+        subscriptions.on_block(&block(1, 0, vec![], vec![]));
+        subscriptions.refresh(&tx_hash, None);
+
+        let notifications = subscriptions.on_block(&block(
+            2,
+            0,
+            vec![(tx_hash, alice.clone())],
+            vec![(tx_hash, vec![])],
+        ));
+        assert_eq!(
+            notifications,
+            vec![notification(
+                subscription,
+                tx_hash,
+                &alice,
+                TxExecutionStatus::ExecutedOptimistic
+            )]
+        );
+        let notifications = subscriptions.on_block(&block(3, 2, vec![], vec![]));
+        assert_eq!(
+            notifications,
+            vec![notification(subscription, tx_hash, &alice, TxExecutionStatus::Final)]
+        );
+        assert!(matches!(
+            subscriptions.unsubscribe(RpcTxUnsubscribeRequest { subscription }),
+            Err(RpcTxSubscriptionError::UnknownSubscription { .. })
+        ));
+    }
+}
@@ -1,5 +1,8 @@
 use crate::network_protocol::StateResponseInfo;
-use crate::types::{NetworkInfo, ReasonForBan};
+use crate::types::{
+    ChunkPartsSampleResponse, NetworkInfo, ReasonForBan, ReplicaStateUpdateRequest,
+    ReplicaStateUpdateResponse,
+};
 use near_async::messaging::{AsyncSender, Sender};
 use near_async::{MultiSend, MultiSendMessage, MultiSenderFrom};
 use near_primitives::block::{Approval, Block, BlockHeader};
@@ -108,6 +111,9 @@ pub enum ProcessTxResponse {
     /// The node being queried does not track the shard needed and therefore cannot provide useful
     /// response.
     DoesNotTrackShard,
+    /// The chunk producers of the shard are overloaded or unresponsive, and the node can't buffer
+    /// more transactions until they recover.
+    ForwardingOverloaded { shard_id: ShardId },
 }
 
 /// Account announcements that needs to be validated before being processed.
@@ -141,6 +147,21 @@ pub struct OptimisticBlockMessage {
     pub from_peer: PeerId,
 }
 
+#[derive(actix::Message, Debug, Clone, PartialEq, Eq)]
+#[rtype(result = "()")]
+pub struct ChunkPartsSampleResponseMessage(pub ChunkPartsSampleResponse);
+
+#[derive(actix::Message, Debug, Clone, PartialEq, Eq)]
+#[rtype(result = "()")]
+pub struct ReplicaStateUpdateRequestMessage {
+    pub request: ReplicaStateUpdateRequest,
+    pub route_back: CryptoHash,
+}
+
+#[derive(actix::Message, Debug, Clone, PartialEq, Eq)]
+#[rtype(result = "()")]
+pub struct ReplicaStateUpdateResponseMessage(pub ReplicaStateUpdateResponse);
+
 #[derive(Clone, MultiSend, MultiSenderFrom, MultiSendMessage)]
 #[multi_send_message_derive(Debug)]
 #[multi_send_input_derive(Debug, Clone, PartialEq, Eq)]
@@ -163,4 +184,7 @@ pub struct ClientSenderForNetwork {
     pub epoch_sync_request: Sender<EpochSyncRequestMessage>,
     pub epoch_sync_response: Sender<EpochSyncResponseMessage>,
     pub optimistic_block_receiver: Sender<OptimisticBlockMessage>,
+    pub chunk_parts_sample_response: Sender<ChunkPartsSampleResponseMessage>,
+    pub replica_state_update_request: Sender<ReplicaStateUpdateRequestMessage>,
+    pub replica_state_update_response: Sender<ReplicaStateUpdateResponseMessage>,
 }
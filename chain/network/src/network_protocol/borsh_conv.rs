@@ -14,6 +14,7 @@ impl From<&net::Handshake> for mem::Handshake {
             sender_chain_info: x.sender_chain_info.clone(),
             partial_edge_info: x.partial_edge_info.clone(),
             owned_account: None,
+            sender_tracked_shards: vec![],
         }
     }
 }
@@ -266,6 +267,10 @@ impl From<&mem::PeerMessage> for net::PeerMessage {
             mem::PeerMessage::EpochSyncResponse(proof) => {
                 net::PeerMessage::EpochSyncResponse(proof)
             }
+            // This message is not supported, we translate it to an empty RoutingTableUpdate.
+            mem::PeerMessage::TrackedShards(_) => {
+                net::PeerMessage::SyncRoutingTable(net::RoutingTableUpdate::default())
+            }
         }
     }
 }
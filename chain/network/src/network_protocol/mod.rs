@@ -48,13 +48,15 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::combine_hash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::optimistic_block::OptimisticBlock;
+use near_primitives::replica::ReplicaShardStateUpdate;
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart, ReceiptProof, ShardChunkHeader,
 };
 use near_primitives::state_sync::{ShardStateSyncResponse, ShardStateSyncResponseV1};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
-use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::types::{BlockHeight, EpochId, ShardId};
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::views::FinalExecutionOutcomeView;
 use near_schema_checker_lib::ProtocolSchema;
@@ -367,6 +369,19 @@ pub struct Handshake {
     pub(crate) partial_edge_info: PartialEdgeInfo,
     /// Account owned by the sender.
     pub(crate) owned_account: Option<SignedOwnedAccount>,
+    /// Shards tracked by the sender in the current and the next epoch. Not
+    /// sent in Borsh encoding.
+    pub(crate) sender_tracked_shards: Vec<EpochTrackedShards>,
+}
+
+/// The shards a node tracks in an epoch. The shard ids the node advertises in
+/// `PeerChainInfoV2` are those of the layout of the epoch of the handshake,
+/// while the shard uids are qualified by their epoch and stay meaningful
+/// across a resharding.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct EpochTrackedShards {
+    pub epoch_id: EpochId,
+    pub shard_uids: Vec<ShardUId>,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr)]
@@ -451,6 +466,10 @@ pub enum PeerMessage {
 
     EpochSyncRequest,
     EpochSyncResponse(CompressedEpochSyncProof),
+
+    /// Sent when the shards tracked by the sender change, e.g. once the next
+    /// epoch is known.
+    TrackedShards(Vec<EpochTrackedShards>),
 }
 
 impl fmt::Display for PeerMessage {
@@ -567,6 +586,10 @@ pub enum RoutedMessageBody {
     ContractCodeRequest(ContractCodeRequest),
     ContractCodeResponse(ContractCodeResponse),
     PartialEncodedContractDeploys(PartialEncodedContractDeploys),
+    ChunkPartsSampleRequest(ChunkPartsSampleRequest),
+    ChunkPartsSampleResponse(ChunkPartsSampleResponse),
+    ReplicaStateUpdateRequest(ReplicaStateUpdateRequest),
+    ReplicaStateUpdateResponse(ReplicaStateUpdateResponse),
 }
 
 impl RoutedMessageBody {
@@ -670,6 +693,30 @@ impl fmt::Debug for RoutedMessageBody {
             RoutedMessageBody::PartialEncodedContractDeploys(deploys) => {
                 write!(f, "PartialEncodedContractDeploys(part={:?}", deploys.part())
             }
+            RoutedMessageBody::ChunkPartsSampleRequest(request) => {
+                write!(
+                    f,
+                    "ChunkPartsSampleRequest({:?}, {:?})",
+                    request.chunk_hash, request.part_ords
+                )
+            }
+            RoutedMessageBody::ChunkPartsSampleResponse(response) => write!(
+                f,
+                "ChunkPartsSampleResponse({:?}, {:?})",
+                response.chunk_hash,
+                response.parts.iter().map(|p| p.part_ord).collect::<Vec<_>>()
+            ),
+            RoutedMessageBody::ReplicaStateUpdateRequest(request) => write!(
+                f,
+                "ReplicaStateUpdateRequest({:?}, {:?})",
+                request.block_hash, request.shard_ids
+            ),
+            RoutedMessageBody::ReplicaStateUpdateResponse(response) => write!(
+                f,
+                "ReplicaStateUpdateResponse({:?}, {:?})",
+                response.block_hash,
+                response.updates.iter().map(|update| update.shard_id).collect::<Vec<_>>()
+            ),
         }
     }
 }
@@ -755,6 +802,8 @@ impl RoutedMessage {
             RoutedMessageBody::Ping(_)
                 | RoutedMessageBody::TxStatusRequest(_, _)
                 | RoutedMessageBody::PartialEncodedChunkRequest(_)
+                | RoutedMessageBody::ChunkPartsSampleRequest(_)
+                | RoutedMessageBody::ReplicaStateUpdateRequest(_)
         )
     }
 
@@ -874,6 +923,66 @@ pub struct PartialEncodedChunkResponseMsg {
     pub receipts: Vec<ReceiptProof>,
 }
 
+/// Request for a few parts of a chunk, sent to their owners by the nodes sampling the
+/// availability of chunks they don't track.
+#[derive(
+    Clone, Debug, Eq, PartialEq, borsh::BorshSerialize, borsh::BorshDeserialize, ProtocolSchema,
+)]
+pub struct ChunkPartsSampleRequest {
+    pub chunk_hash: ChunkHash,
+    pub part_ords: Vec<u64>,
+}
+
+/// The requested parts which the node has, with their merkle proofs against the chunk header.
+#[derive(
+    Clone, Debug, Eq, PartialEq, borsh::BorshSerialize, borsh::BorshDeserialize, ProtocolSchema,
+)]
+pub struct ChunkPartsSampleResponse {
+    pub chunk_hash: ChunkHash,
+    pub parts: Vec<PartialEncodedChunkPart>,
+}
+
+/// Request for the state updates of the chunks of a block, sent by a replica
+/// to the producer of the block.
+#[derive(
+    Clone, Debug, Eq, PartialEq, borsh::BorshSerialize, borsh::BorshDeserialize, ProtocolSchema,
+)]
+pub struct ReplicaStateUpdateRequest {
+    pub block_hash: CryptoHash,
+    pub shard_ids: Vec<ShardId>,
+}
+
+/// The requested state updates which the node still has, signed by the
+/// producer of the block. The replicas apply them without executing the chunks,
+/// so they only accept the updates signed by the producers they trust.
+#[derive(
+    Clone, Debug, Eq, PartialEq, borsh::BorshSerialize, borsh::BorshDeserialize, ProtocolSchema,
+)]
+pub struct ReplicaStateUpdateResponse {
+    pub block_hash: CryptoHash,
+    pub updates: Vec<ReplicaShardStateUpdate>,
+    signature: Signature,
+}
+
+impl ReplicaStateUpdateResponse {
+    pub fn new(
+        block_hash: CryptoHash,
+        updates: Vec<ReplicaShardStateUpdate>,
+        signer: &ValidatorSigner,
+    ) -> Self {
+        let signature = signer.sign_bytes(&Self::signed_data(&block_hash, &updates));
+        Self { block_hash, updates, signature }
+    }
+
+    pub fn verify_signature(&self, public_key: &PublicKey) -> bool {
+        self.signature.verify(&Self::signed_data(&self.block_hash, &self.updates), public_key)
+    }
+
+    fn signed_data(block_hash: &CryptoHash, updates: &[ReplicaShardStateUpdate]) -> Vec<u8> {
+        borsh::to_vec(&("ReplicaStateUpdateResponse", block_hash, updates)).unwrap()
+    }
+}
+
 #[derive(
     PartialEq, Eq, Clone, Debug, borsh::BorshSerialize, borsh::BorshDeserialize, ProtocolSchema,
 )]
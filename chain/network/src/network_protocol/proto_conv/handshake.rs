@@ -2,9 +2,11 @@
 use super::*;
 
 use crate::network_protocol::proto;
-use crate::network_protocol::{Handshake, HandshakeFailureReason};
+use crate::network_protocol::{EpochTrackedShards, Handshake, HandshakeFailureReason};
 use crate::network_protocol::{PeerChainInfoV2, PeerInfo};
 use near_primitives::genesis::GenesisId;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::EpochId;
 use protobuf::MessageField as MF;
 
 impl From<&GenesisId> for proto::GenesisId {
@@ -63,6 +65,42 @@ impl TryFrom<&proto::PeerChainInfo> for PeerChainInfoV2 {
 
 //////////////////////////////////////////
 
+#[derive(thiserror::Error, Debug)]
+pub enum ParseEpochTrackedShardsError {
+    #[error("epoch_id {0}")]
+    EpochId(ParseRequiredError<ParseCryptoHashError>),
+}
+
+impl From<&EpochTrackedShards> for proto::EpochTrackedShards {
+    fn from(x: &EpochTrackedShards) -> Self {
+        Self {
+            epoch_id: MF::some((&x.epoch_id.0).into()),
+            shard_uids: x
+                .shard_uids
+                .iter()
+                .map(|shard_uid| ((shard_uid.version as u64) << 32) | shard_uid.shard_id as u64)
+                .collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::EpochTrackedShards> for EpochTrackedShards {
+    type Error = ParseEpochTrackedShardsError;
+    fn try_from(p: &proto::EpochTrackedShards) -> Result<Self, Self::Error> {
+        Ok(Self {
+            epoch_id: EpochId(try_from_required(&p.epoch_id).map_err(Self::Error::EpochId)?),
+            shard_uids: p
+                .shard_uids
+                .iter()
+                .map(|x| ShardUId { version: (x >> 32) as u32, shard_id: *x as u32 })
+                .collect(),
+        })
+    }
+}
+
+//////////////////////////////////////////
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseHandshakeError {
     #[error("sender_peer_id {0}")]
@@ -77,6 +115,8 @@ pub enum ParseHandshakeError {
     PartialEdgeInfo(ParseRequiredError<ParsePartialEdgeInfoError>),
     #[error("owned_account {0}")]
     OwnedAccount(ParseSignedOwnedAccountError),
+    #[error("sender_tracked_shards {0}")]
+    SenderTrackedShards(ParseVecError<ParseEpochTrackedShardsError>),
 }
 
 impl From<&Handshake> for proto::Handshake {
@@ -90,6 +130,7 @@ impl From<&Handshake> for proto::Handshake {
             sender_chain_info: MF::some((&x.sender_chain_info).into()),
             partial_edge_info: MF::some((&x.partial_edge_info).into()),
             owned_account: x.owned_account.as_ref().map(Into::into).into(),
+            sender_tracked_shards: x.sender_tracked_shards.iter().map(Into::into).collect(),
             ..Self::default()
         }
     }
@@ -116,6 +157,8 @@ impl TryFrom<&proto::Handshake> for Handshake {
                 .map_err(Self::Error::PartialEdgeInfo)?,
             owned_account: try_from_optional(&p.owned_account)
                 .map_err(Self::Error::OwnedAccount)?,
+            sender_tracked_shards: try_from_slice(&p.sender_tracked_shards)
+                .map_err(Self::Error::SenderTrackedShards)?,
         })
     }
 }
@@ -377,6 +377,12 @@ impl From<&PeerMessage> for proto::PeerMessage {
                         ..Default::default()
                     })
                 }
+                PeerMessage::TrackedShards(epochs) => {
+                    ProtoMT::TrackedShards(proto::TrackedShards {
+                        epochs: epochs.iter().map(Into::into).collect(),
+                        ..Default::default()
+                    })
+                }
             }),
             ..Default::default()
         }
@@ -433,6 +439,8 @@ pub enum ParsePeerMessageError {
     SyncSnapshotHosts(ParseSyncSnapshotHostsError),
     #[error("optimistic_block: {0}")]
     OptimisticBlock(ParseOptimisticBlockError),
+    #[error("tracked_shards: {0}")]
+    TrackedShards(ParseVecError<ParseEpochTrackedShardsError>),
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -544,6 +552,9 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::EpochSyncResponse(esr) => PeerMessage::EpochSyncResponse(
                 CompressedData::from_boxed_slice(esr.compressed_proof.clone().into_boxed_slice()),
             ),
+            ProtoMT::TrackedShards(ts) => PeerMessage::TrackedShards(
+                try_from_slice(&ts.epochs).map_err(Self::Error::TrackedShards)?,
+            ),
         })
     }
 }
@@ -61,8 +61,16 @@ fn serialize_deserialize_protobuf_only() {
     let mut rng = make_rng(39521947542);
     let mut clock = time::FakeClock::default();
     let chain = data::Chain::make(&mut clock, &mut rng, 12);
+    let tracked_shards = vec![EpochTrackedShards {
+        epoch_id: EpochId(CryptoHash::hash_bytes(b"epoch")),
+        shard_uids: vec![ShardUId::new(3, ShardId::new(7)), ShardUId::new(3, ShardId::new(8))],
+    }];
+    let mut handshake = data::make_handshake(&mut rng, &chain);
+    handshake.sender_tracked_shards = tracked_shards.clone();
     let msgs = [
         PeerMessage::Tier1Handshake(data::make_handshake(&mut rng, &chain)),
+        PeerMessage::Tier2Handshake(handshake),
+        PeerMessage::TrackedShards(tracked_shards),
         PeerMessage::SyncAccountsData(SyncAccountsData {
             accounts_data: (0..4)
                 .map(|_| Arc::new(data::make_signed_account_data(&mut rng, &clock.clock())))
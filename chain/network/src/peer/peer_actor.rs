@@ -35,6 +35,7 @@ use crate::types::{
 };
 use actix::fut::future::wrap_future;
 use actix::{Actor as _, ActorContext as _, ActorFutureExt as _, AsyncContext as _};
+use arc_swap::ArcSwap;
 use lru::LruCache;
 use near_async::messaging::{CanSend, SendAsync};
 use near_async::time;
@@ -466,11 +467,15 @@ impl PeerActor {
     }
 
     fn send_handshake(&self, spec: HandshakeSpec) {
-        let (height, tracked_shards) =
+        let (height, tracked_shards, tracked_shard_uids) =
             if let Some(chain_info) = self.network_state.chain_info.load().as_ref() {
-                (chain_info.block.header().height(), chain_info.tracked_shards.clone())
+                (
+                    chain_info.block.header().height(),
+                    chain_info.tracked_shards.clone(),
+                    chain_info.tracked_shard_uids.clone(),
+                )
             } else {
-                (0, vec![])
+                (0, vec![], vec![])
             };
         let handshake = Handshake {
             protocol_version: spec.protocol_version,
@@ -494,6 +499,7 @@ impl PeerActor {
                 }
                 .sign(&signer)
             }),
+            sender_tracked_shards: tracked_shard_uids,
         };
         let msg = match spec.tier {
             tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -673,6 +679,7 @@ impl PeerActor {
             owned_account: handshake.owned_account.clone(),
             genesis_id: handshake.sender_chain_info.genesis_id.clone(),
             tracked_shards: handshake.sender_chain_info.tracked_shards.clone(),
+            tracked_shard_uids: ArcSwap::new(Arc::new(handshake.sender_tracked_shards.clone())),
             archival: handshake.sender_chain_info.archival,
             last_block: Default::default(),
             peer_type: self.peer_type,
@@ -1380,6 +1387,11 @@ impl PeerActor {
                     message_processed_event();
                 }));
             }
+            PeerMessage::TrackedShards(tracked_shards) => {
+                conn.tracked_shard_uids.store(Arc::new(tracked_shards));
+                #[cfg(test)]
+                message_processed_event();
+            }
             PeerMessage::Routed(mut msg) => {
                 tracing::trace!(
                     target: "network",
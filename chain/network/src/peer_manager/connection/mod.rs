@@ -2,8 +2,8 @@ use crate::concurrency::arc_mutex::ArcMutex;
 use crate::concurrency::atomic_cell::AtomicCell;
 use crate::concurrency::demux;
 use crate::network_protocol::{
-    PeerInfo, PeerMessage, RoutedMessageBody, SignedAccountData, SignedOwnedAccount,
-    SnapshotHostInfo, SyncAccountsData, SyncSnapshotHosts,
+    EpochTrackedShards, PeerInfo, PeerMessage, RoutedMessageBody, SignedAccountData,
+    SignedOwnedAccount, SnapshotHostInfo, SyncAccountsData, SyncSnapshotHosts,
 };
 use crate::peer::peer_actor;
 use crate::peer::peer_actor::PeerActor;
@@ -17,7 +17,8 @@ use near_crypto::PublicKey;
 use near_o11y::WithSpanContextExt;
 use near_primitives::genesis::GenesisId;
 use near_primitives::network::PeerId;
-use near_primitives::types::ShardId;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::{EpochId, ShardId};
 use std::collections::{HashMap, hash_map::Entry};
 use std::fmt;
 use std::future::Future;
@@ -61,7 +62,8 @@ impl tcp::Tier {
             | PeerMessage::StateRequestHeader(..)
             | PeerMessage::StateRequestPart(..)
             | PeerMessage::EpochSyncRequest
-            | PeerMessage::EpochSyncResponse(..) => self == tcp::Tier::T2,
+            | PeerMessage::EpochSyncResponse(..)
+            | PeerMessage::TrackedShards(..) => self == tcp::Tier::T2,
         }
     }
 
@@ -87,7 +89,11 @@ impl tcp::Tier {
             | RoutedMessageBody::PartialEncodedChunkForward(..)
             | RoutedMessageBody::ChunkStateWitnessAck(..)
             | RoutedMessageBody::StatePartRequest(..)
-            | RoutedMessageBody::PartialEncodedContractDeploys(..) => self == tcp::Tier::T2,
+            | RoutedMessageBody::PartialEncodedContractDeploys(..)
+            | RoutedMessageBody::ChunkPartsSampleRequest(..)
+            | RoutedMessageBody::ChunkPartsSampleResponse(..)
+            | RoutedMessageBody::ReplicaStateUpdateRequest(..)
+            | RoutedMessageBody::ReplicaStateUpdateResponse(..) => self == tcp::Tier::T2,
             // Deprecated
             RoutedMessageBody::_UnusedQueryRequest
             | RoutedMessageBody::_UnusedQueryResponse
@@ -138,6 +144,9 @@ pub(crate) struct Connection {
     pub genesis_id: GenesisId,
     /// Shards that the peer is tracking.
     pub tracked_shards: Vec<ShardId>,
+    /// Shards that the peer is tracking in the current and the next epoch, as
+    /// last advertised by the peer.
+    pub tracked_shard_uids: ArcSwap<Vec<EpochTrackedShards>>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
     pub last_block: ArcSwap<Option<BlockInfo>>,
@@ -183,6 +192,16 @@ impl Connection {
         FullPeerInfo { peer_info: self.peer_info.clone(), chain_info }
     }
 
+    /// Whether the peer tracks the shard in the epoch, `None` if the peer
+    /// didn't advertise the shards it tracks in the epoch.
+    pub fn tracks_shard_uid(&self, epoch_id: &EpochId, shard_uid: ShardUId) -> Option<bool> {
+        self.tracked_shard_uids
+            .load()
+            .iter()
+            .find(|tracked_shards| &tracked_shards.epoch_id == epoch_id)
+            .map(|tracked_shards| tracked_shards.shard_uids.contains(&shard_uid))
+    }
+
     pub fn stop(&self, ban_reason: Option<ReasonForBan>) {
         self.addr.do_send(peer_actor::Stop { ban_reason }.with_span_context());
     }
@@ -1,8 +1,9 @@
 use crate::accounts_data::{AccountDataCache, AccountDataError};
 use crate::announce_accounts::AnnounceAccountCache;
 use crate::client::{
-    BlockApproval, ChunkEndorsementMessage, ClientSenderForNetwork, ProcessTxRequest,
-    TxStatusRequest, TxStatusResponse,
+    BlockApproval, ChunkEndorsementMessage, ChunkPartsSampleResponseMessage,
+    ClientSenderForNetwork, ProcessTxRequest, ReplicaStateUpdateRequestMessage,
+    ReplicaStateUpdateResponseMessage, TxStatusRequest, TxStatusResponse,
 };
 use crate::concurrency::demux;
 use crate::concurrency::runtime::Runtime;
@@ -817,6 +818,28 @@ impl NetworkState {
                 self.partial_witness_adapter.send(PartialEncodedContractDeploysMessage(deploys));
                 None
             }
+            RoutedMessageBody::ChunkPartsSampleRequest(request) => {
+                self.shards_manager_adapter.send(
+                    ShardsManagerRequestFromNetwork::ProcessChunkPartsSampleRequest {
+                        chunk_parts_sample_request: request,
+                        route_back: msg_hash,
+                    },
+                );
+                None
+            }
+            RoutedMessageBody::ChunkPartsSampleResponse(response) => {
+                self.client.send(ChunkPartsSampleResponseMessage(response));
+                None
+            }
+            RoutedMessageBody::ReplicaStateUpdateRequest(request) => {
+                self.client
+                    .send(ReplicaStateUpdateRequestMessage { request, route_back: msg_hash });
+                None
+            }
+            RoutedMessageBody::ReplicaStateUpdateResponse(response) => {
+                self.client.send(ReplicaStateUpdateResponseMessage(response));
+                None
+            }
             body => {
                 tracing::error!(target: "network", "Peer received unexpected message type: {:?}", body);
                 None
@@ -1004,7 +1027,20 @@ impl NetworkState {
         // synchronously, therefore, assuming actix in-order delivery,
         // there will be no race condition between subsequent SetChainInfo
         // calls.
-        self.chain_info.store(Arc::new(Some(info.clone())));
+        let prev_info = self.chain_info.swap(Arc::new(Some(info.clone())));
+
+        // The peers only learn the shards tracked by this node in the handshake
+        // and from these updates, so they are sent as soon as the shards change.
+        if prev_info
+            .as_ref()
+            .as_ref()
+            .is_none_or(|prev| prev.tracked_shard_uids != info.tracked_shard_uids)
+        {
+            let msg = Arc::new(PeerMessage::TrackedShards(info.tracked_shard_uids.clone()));
+            for conn in self.tier2.load().ready.values() {
+                conn.send_message(msg.clone());
+            }
+        }
 
         // If tier1 is not enabled, we skip set_keys() call.
         // This way self.state.accounts_data is always empty, hence no data
@@ -17,10 +17,10 @@ use crate::stats::metrics;
 use crate::store;
 use crate::tcp;
 use crate::types::{
-    ConnectedPeerInfo, HighestHeightPeerInfo, KnownProducer, NetworkInfo, NetworkRequests,
-    NetworkResponses, PeerInfo, PeerManagerMessageRequest, PeerManagerMessageResponse,
-    PeerManagerSenderForNetwork, PeerType, SetChainInfo, SnapshotHostInfo, StatePartRequestBody,
-    StateSyncEvent, Tier3Request, Tier3RequestBody,
+    AccountIdOrPeerTrackingShard, ConnectedPeerInfo, HighestHeightPeerInfo, KnownProducer,
+    NetworkInfo, NetworkRequests, NetworkResponses, PeerInfo, PeerManagerMessageRequest,
+    PeerManagerMessageResponse, PeerManagerSenderForNetwork, PeerType, SetChainInfo,
+    SnapshotHostInfo, StatePartRequestBody, StateSyncEvent, Tier3Request, Tier3RequestBody,
 };
 use ::time::ext::InstantExt as _;
 use actix::fut::future::wrap_future;
@@ -32,6 +32,7 @@ use near_o11y::{WithSpanContext, handler_debug_span, handler_trace_span};
 use near_performance_metrics_macros::perf;
 use near_primitives::genesis::GenesisId;
 use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::types::BlockHeight;
 use near_primitives::views::{
     ConnectionInfoView, EdgeView, KnownPeerStateView, NetworkGraphView, NetworkRoutesView,
     PeerStoreView, RecentOutboundConnectionsView, SnapshotHostInfoView, SnapshotHostsView,
@@ -91,6 +92,47 @@ pub(crate) const POLL_CONNECTION_STORE_INTERVAL: time::Duration = time::Duration
 /// The length of time that a Tier3 connection is allowed to idle before it is stopped
 const TIER3_IDLE_TIMEOUT: time::Duration = time::Duration::seconds(15);
 
+/// What a peer advertised which matters for sending it a chunk part request.
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkRequestCandidate {
+    pub archival: bool,
+    pub last_block_height: Option<BlockHeight>,
+    /// Whether the peer tracks the shard in the epoch of the chunk, `None` if
+    /// it didn't advertise the shards it tracks in the epoch.
+    pub tracks_shard_uid: Option<bool>,
+    /// Whether the shard ids of the handshake contain the shard of the chunk.
+    pub tracks_shard_id: bool,
+}
+
+/// The peers which can serve the chunk part request of `target`. The peers
+/// which advertised the shards they track in the epoch of the chunk are
+/// preferred, since the shard ids of the handshake may belong to the layout
+/// before a resharding.
+pub(crate) fn chunk_request_peers(
+    target: &AccountIdOrPeerTrackingShard,
+    candidates: impl IntoIterator<Item = (PeerId, ChunkRequestCandidate)>,
+) -> Vec<PeerId> {
+    let mut matching_peers = vec![];
+    let mut legacy_matching_peers = vec![];
+    for (peer_id, candidate) in candidates {
+        if !(candidate.archival || !target.only_archival)
+            || candidate.last_block_height.is_none_or(|height| height < target.min_height)
+        {
+            continue;
+        }
+        match candidate.tracks_shard_uid {
+            Some(true) => matching_peers.push(peer_id),
+            Some(false) => {}
+            None => {
+                if candidate.tracks_shard_id {
+                    legacy_matching_peers.push(peer_id);
+                }
+            }
+        }
+    }
+    if matching_peers.is_empty() { legacy_matching_peers } else { matching_peers }
+}
+
 /// Actor that manages peers connections.
 pub struct PeerManagerActor {
     pub(crate) clock: time::Clock,
@@ -950,17 +992,26 @@ impl PeerManagerActor {
                             }
                         }
                     } else {
-                        let mut matching_peers = vec![];
-                        for (peer_id, peer) in &self.state.tier2.load().ready {
-                            let last_block = peer.last_block.load();
-                            if (peer.archival || !target.only_archival)
-                                && last_block.is_some()
-                                && last_block.as_ref().unwrap().height >= target.min_height
-                                && peer.tracked_shards.contains(&target.shard_id)
-                            {
-                                matching_peers.push(peer_id.clone());
-                            }
-                        }
+                        let matching_peers = chunk_request_peers(
+                            &target,
+                            self.state.tier2.load().ready.iter().map(|(peer_id, peer)| {
+                                let tracks_shard_uid =
+                                    target.epoch_shard_uid.and_then(|(epoch_id, shard_uid)| {
+                                        peer.tracks_shard_uid(&epoch_id, shard_uid)
+                                    });
+                                let candidate = ChunkRequestCandidate {
+                                    archival: peer.archival,
+                                    last_block_height: peer
+                                        .last_block
+                                        .load()
+                                        .as_ref()
+                                        .map(|last_block| last_block.height),
+                                    tracks_shard_uid,
+                                    tracks_shard_id: peer.tracked_shards.contains(&target.shard_id),
+                                };
+                                (peer_id.clone(), candidate)
+                            }),
+                        );
 
                         if let Some(matching_peer) = matching_peers.iter().choose(&mut thread_rng())
                         {
@@ -1156,6 +1207,62 @@ impl PeerManagerActor {
                 );
                 NetworkResponses::NoResponse
             }
+            NetworkRequests::ChunkPartsSampleRequest { target, request } => {
+                if self.state.send_message_to_account(
+                    &self.clock,
+                    &target,
+                    RoutedMessageBody::ChunkPartsSampleRequest(request),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
+            NetworkRequests::ChunkPartsSampleResponse { route_back, response } => {
+                if self.state.send_message_to_peer(
+                    &self.clock,
+                    tcp::Tier::T2,
+                    self.state.sign_message(
+                        &self.clock,
+                        RawRoutedMessage {
+                            target: PeerIdOrHash::Hash(route_back),
+                            body: RoutedMessageBody::ChunkPartsSampleResponse(response),
+                        },
+                    ),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
+            NetworkRequests::ReplicaStateUpdateRequest { target, request } => {
+                if self.state.send_message_to_account(
+                    &self.clock,
+                    &target,
+                    RoutedMessageBody::ReplicaStateUpdateRequest(request),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
+            NetworkRequests::ReplicaStateUpdateResponse { route_back, response } => {
+                if self.state.send_message_to_peer(
+                    &self.clock,
+                    tcp::Tier::T2,
+                    self.state.sign_message(
+                        &self.clock,
+                        RawRoutedMessage {
+                            target: PeerIdOrHash::Hash(route_back),
+                            body: RoutedMessageBody::ReplicaStateUpdateResponse(response),
+                        },
+                    ),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
         }
     }
 
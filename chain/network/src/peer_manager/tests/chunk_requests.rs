@@ -0,0 +1,58 @@
+use crate::network_protocol::testonly as data;
+use crate::peer_manager::peer_manager_actor::{ChunkRequestCandidate, chunk_request_peers};
+use crate::testonly::make_rng;
+use crate::types::AccountIdOrPeerTrackingShard;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::{EpochId, ShardId};
+
+fn candidate(tracks_shard_uid: Option<bool>, tracks_shard_id: bool) -> ChunkRequestCandidate {
+    ChunkRequestCandidate {
+        archival: false,
+        last_block_height: Some(10),
+        tracks_shard_uid,
+        tracks_shard_id,
+    }
+}
+
+#[test]
+fn test_chunk_request_peers_prefer_advertised_shard_uids() {
+    let mut rng = make_rng(921853233);
+    let shard_id = ShardId::new(1);
+    let target = AccountIdOrPeerTrackingShard {
+        account_id: None,
+        prefer_peer: true,
+        shard_id,
+        epoch_shard_uid: Some((
+            EpochId(CryptoHash::hash_bytes(b"epoch")),
+            ShardUId::new(3, shard_id),
+        )),
+        only_archival: false,
+        min_height: 5,
+    };
+    let advertised = data::make_peer_id(&mut rng);
+    let advertised_other_shard = data::make_peer_id(&mut rng);
+    let legacy = data::make_peer_id(&mut rng);
+    let legacy_other_shard = data::make_peer_id(&mut rng);
+    let behind = data::make_peer_id(&mut rng);
+
+    let mut behind_candidate = candidate(Some(true), true);
+    behind_candidate.last_block_height = Some(4);
+    let candidates = vec![
+        (advertised.clone(), candidate(Some(true), false)),
+        // The shard ids of the handshake are ignored once the peer advertised
+        // the shards it tracks in the epoch.
+        (advertised_other_shard, candidate(Some(false), true)),
+        (legacy.clone(), candidate(None, true)),
+        (legacy_other_shard, candidate(None, false)),
+        (behind, behind_candidate),
+    ];
+    assert_eq!(chunk_request_peers(&target, candidates.clone()), vec![advertised]);
+
+    // With no peer advertising the shard uid, the shard ids of the handshake
+    // are used.
+    let candidates = candidates.into_iter().filter(|(_, candidate)| {
+        candidate.tracks_shard_uid != Some(true) || candidate.last_block_height == Some(4)
+    });
+    assert_eq!(chunk_request_peers(&target, candidates), vec![legacy]);
+}
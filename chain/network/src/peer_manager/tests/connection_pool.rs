@@ -107,6 +107,7 @@ async fn loop_connection() {
                 &pm.cfg.node_key,
             ),
             owned_account: None,
+            sender_tracked_shards: vec![],
         }))
         .await;
     let reason = events
@@ -177,6 +178,7 @@ async fn owned_account_mismatch() {
                 }
                 .sign(&signer),
             ),
+            sender_tracked_shards: vec![],
         }))
         .await;
     let reason = events
@@ -299,6 +301,7 @@ async fn invalid_edge() {
                     }
                     .sign(&signer),
                 ),
+                sender_tracked_shards: vec![],
             };
             let handshake = match tier {
                 tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -1,4 +1,5 @@
 mod accounts_data;
+mod chunk_requests;
 mod connection_pool;
 mod fuzzers;
 mod nonce;
@@ -114,6 +114,12 @@ impl Config {
             RateLimitedPeerMessageKey::EpochSyncRequest,
             SingleMessageConfig::new(1, 1.0 / 30.0, None),
         );
+        // TrackedShards is sent by a peer when the shards it tracks change, which happens a few
+        // times per epoch. A peer sending it more often only makes us redo the work.
+        config.rate_limits.insert(
+            RateLimitedPeerMessageKey::TrackedShards,
+            SingleMessageConfig::new(10, 1.0 / 60.0, None),
+        );
         config
     }
 
@@ -177,8 +183,13 @@ pub enum RateLimitedPeerMessageKey {
     ContractCodeRequest,
     ContractCodeResponse,
     PartialEncodedContractDeploys,
+    ChunkPartsSampleRequest,
+    ChunkPartsSampleResponse,
+    ReplicaStateUpdateRequest,
+    ReplicaStateUpdateResponse,
     EpochSyncRequest,
     OptimisticBlock,
+    TrackedShards,
 }
 
 /// Given a `PeerMessage` returns a tuple containing the `RateLimitedPeerMessageKey`
@@ -232,6 +243,12 @@ fn get_key_and_token_cost(message: &PeerMessage) -> Option<(RateLimitedPeerMessa
             RoutedMessageBody::PartialEncodedContractDeploys(_) => {
                 Some((PartialEncodedContractDeploys, 1))
             }
+            RoutedMessageBody::ChunkPartsSampleRequest(_) => Some((ChunkPartsSampleRequest, 1)),
+            RoutedMessageBody::ChunkPartsSampleResponse(_) => Some((ChunkPartsSampleResponse, 1)),
+            RoutedMessageBody::ReplicaStateUpdateRequest(_) => Some((ReplicaStateUpdateRequest, 1)),
+            RoutedMessageBody::ReplicaStateUpdateResponse(_) => {
+                Some((ReplicaStateUpdateResponse, 1))
+            }
             RoutedMessageBody::VersionedChunkEndorsement(_) => Some((ChunkEndorsement, 1)),
             RoutedMessageBody::_UnusedEpochSyncRequest => None,
             RoutedMessageBody::_UnusedEpochSyncResponse(_) => None,
@@ -256,6 +273,7 @@ fn get_key_and_token_cost(message: &PeerMessage) -> Option<(RateLimitedPeerMessa
         PeerMessage::VersionedStateResponse(_) => Some((VersionedStateResponse, 1)),
         PeerMessage::EpochSyncRequest => Some((EpochSyncRequest, 1)),
         PeerMessage::EpochSyncResponse(_) => None,
+        PeerMessage::TrackedShards(_) => Some((TrackedShards, 1)),
         PeerMessage::Tier1Handshake(_)
         | PeerMessage::Tier2Handshake(_)
         | PeerMessage::Tier3Handshake(_)
@@ -479,4 +497,18 @@ mod tests {
         clock.advance(Duration::seconds(30));
         assert!(rate_limits.is_allowed(&PeerMessage::EpochSyncRequest, clock.now()));
     }
+
+    #[test]
+    fn test_tracked_shards_rate_limit() {
+        let config = Config::standard_preset();
+        let clock = FakeClock::default();
+        let mut rate_limits = RateLimits::from_config(&config, clock.now());
+        let tracked_shards = PeerMessage::TrackedShards(vec![]);
+        for _ in 0..10 {
+            assert!(rate_limits.is_allowed(&tracked_shards, clock.now()));
+        }
+        assert!(!rate_limits.is_allowed(&tracked_shards, clock.now()));
+        clock.advance(Duration::seconds(60));
+        assert!(rate_limits.is_allowed(&tracked_shards, clock.now()));
+    }
 }
@@ -3,7 +3,8 @@ use near_async::time::Instant;
 use near_primitives::{hash::CryptoHash, sharding::PartialEncodedChunk};
 
 use crate::types::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    ChunkPartsSampleRequest, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg,
 };
 
 #[derive(Message, Debug, strum::IntoStaticStr, Clone, PartialEq, Eq)]
@@ -20,4 +21,8 @@ pub enum ShardsManagerRequestFromNetwork {
         partial_encoded_chunk_request: PartialEncodedChunkRequestMsg,
         route_back: CryptoHash,
     },
+    ProcessChunkPartsSampleRequest {
+        chunk_parts_sample_request: ChunkPartsSampleRequest,
+        route_back: CryptoHash,
+    },
 }
@@ -1,7 +1,8 @@
 /// Type that belong to the network protocol.
 pub use crate::network_protocol::{
-    Disconnect, Encoding, Handshake, HandshakeFailureReason, PeerMessage, RoutingTableUpdate,
-    SignedAccountData,
+    ChunkPartsSampleRequest, ChunkPartsSampleResponse, Disconnect, Encoding, EpochTrackedShards,
+    Handshake, HandshakeFailureReason, PeerMessage, ReplicaStateUpdateRequest,
+    ReplicaStateUpdateResponse, RoutingTableUpdate, SignedAccountData,
 };
 /// Exported types, which are part of network protocol.
 pub use crate::network_protocol::{
@@ -20,6 +21,7 @@ use near_primitives::genesis::GenesisId;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::optimistic_block::OptimisticBlock;
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::PartialEncodedChunkWithArcReceipts;
 use near_primitives::stateless_validation::chunk_endorsement::ChunkEndorsement;
 use near_primitives::stateless_validation::contract_distribution::{
@@ -28,7 +30,7 @@ use near_primitives::stateless_validation::contract_distribution::{
 use near_primitives::stateless_validation::partial_witness::PartialEncodedStateWitness;
 use near_primitives::stateless_validation::state_witness::ChunkStateWitnessAck;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockHeight, EpochHeight, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, EpochHeight, EpochId, ShardId};
 use near_schema_checker_lib::ProtocolSchema;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
@@ -158,6 +160,8 @@ pub type AccountKeys = HashMap<AccountId, HashSet<PublicKey>>;
 #[derive(Debug, Clone)]
 pub struct ChainInfo {
     pub tracked_shards: Vec<ShardId>,
+    /// The shards tracked in the current and the next epoch.
+    pub tracked_shard_uids: Vec<EpochTrackedShards>,
     // The latest block on chain.
     pub block: Block,
     // Public keys of accounts participating in the BFT consensus
@@ -308,6 +312,15 @@ pub enum NetworkRequests {
     /// Message originates from the chunk producer and distributed among other validators,
     /// containing the code of the newly-deployed contracts during the main state transition of the witness.
     PartialEncodedContractDeploys(Vec<AccountId>, PartialEncodedContractDeploys),
+    /// Request for a sample of chunk parts, sent to the owner of the parts.
+    ChunkPartsSampleRequest { target: AccountId, request: ChunkPartsSampleRequest },
+    /// Response to a chunk parts sample request.
+    ChunkPartsSampleResponse { route_back: CryptoHash, response: ChunkPartsSampleResponse },
+    /// Request for the state updates of a block, sent by a replica to the
+    /// producer of the block.
+    ReplicaStateUpdateRequest { target: AccountId, request: ReplicaStateUpdateRequest },
+    /// Response to a replica state update request.
+    ReplicaStateUpdateResponse { route_back: CryptoHash, response: ReplicaStateUpdateResponse },
 }
 
 #[derive(Debug, actix::Message, strum::IntoStaticStr)]
@@ -529,6 +542,9 @@ pub struct AccountIdOrPeerTrackingShard {
     pub prefer_peer: bool,
     /// Select peers that track shard `shard_id`
     pub shard_id: ShardId,
+    /// The epoch of the chunk and the uid of its shard. Peers which advertised
+    /// that they track the shard in the epoch are preferred.
+    pub epoch_shard_uid: Option<(EpochId, ShardUId)>,
     /// Select peers that are archival nodes if it is true
     pub only_archival: bool,
     /// Only send messages to peers whose latest chain height is no less `min_height`
@@ -10,8 +10,11 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::ops::Bound;
 
 mod metrics;
+mod sharded;
 pub mod types;
 
+pub use sharded::ShardedTransactionPool;
+
 #[derive(Debug, PartialEq)]
 pub enum InsertTransactionResult {
     /// Transaction was successfully inserted.
@@ -1,4 +1,4 @@
-use near_o11y::metrics::IntGaugeVec;
+use near_o11y::metrics::{IntCounterVec, IntGaugeVec};
 use std::sync::LazyLock;
 
 pub static TRANSACTION_POOL_COUNT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
@@ -18,3 +18,18 @@ pub static TRANSACTION_POOL_SIZE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
     )
     .unwrap()
 });
+
+pub static TRANSACTION_POOL_RESHARDED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    near_o11y::metrics::try_create_int_counter_vec(
+        "near_transaction_pool_resharded_transactions",
+        "Number of transactions moved to the pool of a new shard after a resharding, by the result of the insertion",
+        &["shard_id", "result"],
+    )
+    .unwrap()
+});
+
+/// Removes the metrics of the pool of a shard that doesn't exist anymore.
+pub(crate) fn remove_shard_metrics(metrics_label: &str) {
+    let _ = TRANSACTION_POOL_COUNT.remove_label_values(&[metrics_label]);
+    let _ = TRANSACTION_POOL_SIZE.remove_label_values(&[metrics_label]);
+}
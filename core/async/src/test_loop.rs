@@ -58,18 +58,22 @@
 //! A note on the order of execution of the events: all events that are due at the same
 //! timestamp are executed in FIFO order. For example, if the events are emitted in the
 //! following order: (A due 100ms), (B due 0ms), (C due 200ms), (D due 0ms), (E due 100ms)
-//! then the actual order of execution is B, D, A, E, C.
+//! then the actual order of execution is B, D, A, E, C. That order can be recorded and
+//! replayed by another run of the test, see the `schedule` module.
 pub mod data;
 pub mod futures;
 pub mod pending_events_sender;
+pub mod schedule;
 pub mod sender;
 
 use data::TestLoopData;
 use futures::{TestLoopAsyncComputationSpawner, TestLoopFutureSpawner};
 use near_time::{Clock, Duration, FakeClock};
 use pending_events_sender::{CallbackEvent, PendingEventsSender, RawPendingEventsSender};
+use schedule::{EventSchedule, ScheduleReplay, ScheduledEvent};
 use serde::Serialize;
 use std::collections::{BinaryHeap, HashSet};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use time::ext::InstantExt;
@@ -100,11 +104,17 @@ pub struct TestLoopV2 {
     /// Shutdown flag. When this flag is true, delayed action runners will no
     /// longer post any new events to the event loop.
     shutting_down: Arc<AtomicBool>,
-    /// If present, a function to call to print something every time an event is
-    /// handled. Intended only for debugging.
+    /// If present, a function to call every time an event is handled, right
+    /// before the event. Used to check the invariants of a test, or to print
+    /// something for debugging.
     every_event_callback: Option<Box<dyn FnMut(&TestLoopData)>>,
     /// All events with this identifier are ignored in testloop execution environment.
     denylisted_identifiers: HashSet<String>,
+    /// If present, the path the schedule of the events is written to when the
+    /// test loop is dropped, and the events executed so far.
+    schedule_recording: Option<(PathBuf, Vec<ScheduledEvent>)>,
+    /// If present, the schedule the order of the due events is taken from.
+    schedule_replay: Option<ScheduleReplay>,
 }
 
 /// An event waiting to be executed, ordered by the due time and then by ID.
@@ -213,6 +223,8 @@ impl TestLoopV2 {
             shutting_down,
             every_event_callback: None,
             denylisted_identifiers: HashSet::new(),
+            schedule_recording: None,
+            schedule_replay: None,
         }
     }
 
@@ -269,6 +281,36 @@ impl TestLoopV2 {
         self.clock.clock()
     }
 
+    /// Returns a clock which is `offset` ahead of the virtual time and drifts
+    /// away from it by `drift_rate`, see `FakeClock::skewed_clock`.
+    pub fn skewed_clock(&self, offset: Duration, drift_rate: f64) -> Clock {
+        self.clock.skewed_clock(offset, drift_rate)
+    }
+
+    /// Returns the seed of the randomness of the test, see `TestLoopData::seed`.
+    pub fn seed(&self) -> u64 {
+        self.data.seed()
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.data.set_seed(seed);
+    }
+
+    /// Records the schedule of the events executed by the test loop. It is
+    /// written to `path` when the test loop is dropped, also when the test
+    /// panics.
+    pub fn record_schedule(&mut self, path: PathBuf) {
+        self.schedule_recording = Some((path, vec![]));
+    }
+
+    /// Executes the events due at the same time in the order of the given
+    /// schedule, and uses its seed. Panics as soon as an event of the schedule
+    /// isn't due when it should be executed.
+    pub fn replay_schedule(&mut self, schedule: EventSchedule) {
+        self.set_seed(schedule.seed);
+        self.schedule_replay = Some(ScheduleReplay::new(schedule));
+    }
+
     pub fn set_every_event_callback(&mut self, callback: impl FnMut(&TestLoopData) + 'static) {
         self.every_event_callback = Some(Box::new(callback));
     }
@@ -285,6 +327,42 @@ impl TestLoopV2 {
         }
     }
 
+    /// Pops the next event due at the current time, which is the next one of
+    /// the replayed schedule if any, and records it.
+    fn pop_due_event(&mut self) -> EventInHeap {
+        let event = match &mut self.schedule_replay {
+            None => self.events.pop().expect("Programming error in TestLoop"),
+            Some(replay) => {
+                let mut due_events = vec![];
+                while self.events.peek().is_some_and(|event| event.due == self.current_time) {
+                    due_events.push(self.events.pop().unwrap());
+                }
+                let scheduled_events = due_events
+                    .iter()
+                    .map(|event| {
+                        ScheduledEvent::new(
+                            &event.event.identifier,
+                            &event.event.description,
+                            event.due,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let index = replay.next_event(&scheduled_events).unwrap_or(0);
+                let event = due_events.remove(index);
+                self.events.extend(due_events);
+                event
+            }
+        };
+        if let Some((_, events)) = &mut self.schedule_recording {
+            events.push(ScheduledEvent::new(
+                &event.event.identifier,
+                &event.event.description,
+                event.due,
+            ));
+        }
+        event
+    }
+
     /// Performs the logic to find the next event, advance to its time, and dequeue it.
     /// Takes a decider to determine whether to advance time, handle the next event, and/or to stop.
     fn advance_till_next_event(
@@ -314,7 +392,7 @@ impl TestLoopV2 {
             // just return that event; there's no decision to make (as we only give deciders a
             // chance to stop processing if we would advance the clock) and no need to advance time.
             if next_timestamp == Some(self.current_time) {
-                let event = self.events.pop().expect("Programming error in TestLoop");
+                let event = self.pop_due_event();
                 assert_eq!(event.due, self.current_time);
                 return Some(event);
             }
@@ -368,8 +446,10 @@ impl TestLoopV2 {
                 callback(&self.data);
             }
 
+            // The events of the chain emitted by the nodes are attributed to
+            // the identifier of the event, usually the node.
             let callback = event.event.callback;
-            callback(&mut self.data);
+            near_o11y::events::with_node(&event.event.identifier, || callback(&mut self.data));
         }
 
         // Push any new events into the queue. Do this before emitting the end log line,
@@ -438,6 +518,17 @@ impl TestLoopV2 {
 
 impl Drop for TestLoopV2 {
     fn drop(&mut self) {
+        if let Some((path, events)) = self.schedule_recording.take() {
+            let schedule = EventSchedule { seed: self.data.seed(), events };
+            match schedule.save(&path) {
+                Ok(()) => {
+                    tracing::info!(target: "test_loop", path = %path.display(), "Recorded the event schedule")
+                }
+                Err(err) => {
+                    tracing::error!(target: "test_loop", path = %path.display(), ?err, "Failed to record the event schedule")
+                }
+            }
+        }
         self.queue_received_events();
         if let Some(event) = self.events.pop() {
             // Drop any references that may be held by the event callbacks. This can help
@@ -464,8 +555,9 @@ enum AdvanceDecision {
 mod tests {
     use crate::futures::FutureSpawnerExt;
     use crate::test_loop::TestLoopV2;
-    use std::sync::Arc;
+    use crate::test_loop::schedule::EventSchedule;
     use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
     use time::Duration;
 
     // Tests that the TestLoop correctly handles futures that sleep on the fake clock.
@@ -506,4 +598,46 @@ mod tests {
         test_loop.run_for(Duration::seconds(30));
         assert_eq!(finished.load(Ordering::Relaxed), 2);
     }
+
+    /// Sends events "a", "b" and "c" due at the same time, which append their
+    /// name to the returned list when executed.
+    fn send_named_events(test_loop: &TestLoopV2) -> Arc<Mutex<Vec<&'static str>>> {
+        let executed = Arc::new(Mutex::new(vec![]));
+        for name in ["a", "b", "c"] {
+            let executed = executed.clone();
+            test_loop.send_adhoc_event(name.to_string(), move |_| {
+                executed.lock().unwrap().push(name);
+            });
+        }
+        executed
+    }
+
+    // Tests that a recorded schedule is replayed in its order, with its seed.
+    #[test]
+    fn test_record_and_replay_schedule() {
+        let path =
+            std::env::temp_dir().join(format!("test_loop_schedule_{}.json", std::process::id()));
+        let mut test_loop = TestLoopV2::new();
+        test_loop.set_seed(42);
+        test_loop.record_schedule(path.clone());
+        let executed = send_named_events(&test_loop);
+        test_loop.run_instant();
+        assert_eq!(*executed.lock().unwrap(), vec!["a", "b", "c"]);
+        drop(test_loop);
+
+        let mut schedule = EventSchedule::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(schedule.seed, 42);
+        let descriptions =
+            schedule.events.iter().map(|event| event.description.as_str()).collect::<Vec<_>>();
+        assert_eq!(descriptions, vec!["a", "b", "c"]);
+
+        schedule.events.reverse();
+        let mut test_loop = TestLoopV2::new();
+        test_loop.replay_schedule(schedule);
+        assert_eq!(test_loop.seed(), 42);
+        let executed = send_named_events(&test_loop);
+        test_loop.run_instant();
+        assert_eq!(*executed.lock().unwrap(), vec!["c", "b", "a"]);
+    }
 }
@@ -44,6 +44,8 @@ pub struct TestLoopData {
     raw_pending_events_sender: RawPendingEventsSender,
     // Atomic bool to check if the test loop is shutting down. Used mainly for registering actors.
     shutting_down: Arc<AtomicBool>,
+    // Seed of the randomness of the test, see `TestLoopV2::set_seed`.
+    seed: u64,
 }
 
 impl TestLoopData {
@@ -51,7 +53,17 @@ impl TestLoopData {
         raw_pending_events_sender: RawPendingEventsSender,
         shutting_down: Arc<AtomicBool>,
     ) -> Self {
-        Self { data: Vec::new(), raw_pending_events_sender, shutting_down }
+        Self { data: Vec::new(), raw_pending_events_sender, shutting_down, seed: 0 }
+    }
+
+    /// The seed the randomness of the test should be derived from, so that a
+    /// run of the test can be reproduced with the same seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
     }
 
     /// Function to register data of any type in the TestLoopData.
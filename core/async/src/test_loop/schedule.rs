@@ -0,0 +1,88 @@
+//! Recording and replay of the schedule of the events of a test loop.
+//!
+//! The events due at the same time are executed in the order they were sent,
+//! which may differ between two runs of a test when the events are sent in
+//! the iteration order of a hash map, or depend on randomness. A schedule
+//! holds the order in which the events of a run were executed, together with
+//! the seed of the run, so that a failed run, e.g. on CI, can be replayed
+//! locally: the replay executes the events due at the same time in the
+//! recorded order, and panics at the first event which isn't in the
+//! recording.
+use std::collections::VecDeque;
+use std::path::Path;
+
+use near_time::Duration;
+
+/// An event executed by the test loop.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledEvent {
+    pub identifier: String,
+    pub description: String,
+    /// The virtual time at which the event was executed, in nanoseconds.
+    pub due_ns: u64,
+}
+
+impl ScheduledEvent {
+    pub(crate) fn new(identifier: &str, description: &str, due: Duration) -> Self {
+        Self {
+            identifier: identifier.to_string(),
+            description: description.to_string(),
+            due_ns: due.whole_nanoseconds() as u64,
+        }
+    }
+}
+
+/// The seed and the events of a run of a test loop, in execution order.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventSchedule {
+    pub seed: u64,
+    pub events: Vec<ScheduledEvent>,
+}
+
+impl EventSchedule {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// The events of a schedule which are yet to be replayed.
+pub(crate) struct ScheduleReplay {
+    events: VecDeque<ScheduledEvent>,
+    /// The number of events replayed so far.
+    num_replayed: usize,
+}
+
+impl ScheduleReplay {
+    pub(crate) fn new(schedule: EventSchedule) -> Self {
+        Self { events: schedule.events.into(), num_replayed: 0 }
+    }
+
+    /// Returns the index, among `due_events`, of the next event of the
+    /// schedule, and moves past it. Returns `None` once the schedule is fully
+    /// replayed, in which case the events are executed in their usual order.
+    ///
+    /// Panics if none of the due events is the next one of the schedule.
+    pub(crate) fn next_event(&mut self, due_events: &[ScheduledEvent]) -> Option<usize> {
+        let expected = self.events.front()?;
+        let Some(index) = due_events.iter().position(|event| event == expected) else {
+            panic!(
+                "The test loop diverged from the replayed schedule at event #{}: \
+                    expected {:?}, but the due events are {:?}",
+                self.num_replayed, expected, due_events
+            );
+        };
+        self.events.pop_front();
+        self.num_replayed += 1;
+        Some(index)
+    }
+}
@@ -2,6 +2,7 @@
 use crate::ExternalStorageLocation::GCS;
 use crate::MutableConfigValue;
 use bytesize::ByteSize;
+use near_crypto::PublicKey;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockHeightDelta, Gas, NumBlocks, NumSeats, ShardId,
 };
@@ -9,6 +10,7 @@ use near_primitives::version::Version;
 use near_time::Duration;
 use num_rational::Rational32;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
@@ -119,6 +121,63 @@ pub struct GCConfig {
     /// How often gc should be run
     #[serde(with = "near_time::serde_duration_as_std")]
     pub gc_step_period: Duration,
+
+    /// Whether to clean up what is left of the parent shards of a resharding
+    /// once the resharding block is garbage collected. Archival nodes may
+    /// want to turn it off to keep the parent shards around.
+    pub gc_resharding_parent_shards: bool,
+
+    /// Number of epochs for which the data of some categories is kept, if
+    /// less than `gc_num_epochs_to_keep`. Such data is collected ahead of the
+    /// blocks, e.g. to keep the outcomes of the transactions longer than the
+    /// state.
+    pub gc_retention: BTreeMap<GCCategory, GCRetentionConfig>,
+}
+
+/// A category of data that can be garbage collected ahead of the blocks, see
+/// `GCConfig::gc_retention`.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum GCCategory {
+    /// The state of the shards at the blocks, i.e. the trie nodes removed by
+    /// the following blocks.
+    State,
+    /// The state changes of the blocks. They aren't indexed by shard, so they
+    /// are kept for the tracked and untracked shards alike.
+    StateChanges,
+    /// The incoming and outgoing receipts of the shards at the blocks.
+    Receipts,
+    /// The outcomes of the transactions and receipts executed in the blocks.
+    Outcomes,
+}
+
+impl GCCategory {
+    pub const ALL: [GCCategory; 4] =
+        [GCCategory::State, GCCategory::StateChanges, GCCategory::Receipts, GCCategory::Outcomes];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GCCategory::State => "state",
+            GCCategory::StateChanges => "state_changes",
+            GCCategory::Receipts => "receipts",
+            GCCategory::Outcomes => "outcomes",
+        }
+    }
+}
+
+/// Retention of a category of data, see `GCConfig::gc_retention`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct GCRetentionConfig {
+    /// Number of epochs for which the data is kept.
+    pub num_epochs_to_keep: u64,
+    /// Number of epochs for which the data of the shards that the node
+    /// doesn't track any more is kept, if less than `num_epochs_to_keep`. A
+    /// shard of an older shard layout is tracked if one of its descendants in
+    /// the current shard layout is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub untracked_shards_num_epochs_to_keep: Option<u64>,
 }
 
 impl Default for GCConfig {
@@ -128,6 +187,8 @@ impl Default for GCConfig {
             gc_fork_clean_step: 100,
             gc_num_epochs_to_keep: DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
             gc_step_period: Duration::seconds(1),
+            gc_resharding_parent_shards: true,
+            gc_retention: BTreeMap::new(),
         }
     }
 }
@@ -136,6 +197,27 @@ impl GCConfig {
     pub fn gc_num_epochs_to_keep(&self) -> u64 {
         max(MIN_GC_NUM_EPOCHS_TO_KEEP, self.gc_num_epochs_to_keep)
     }
+
+    /// Number of epochs for which the data of the category is kept, no more
+    /// than for the blocks.
+    pub fn gc_category_num_epochs_to_keep(&self, category: GCCategory) -> u64 {
+        let num_epochs_to_keep = self.gc_num_epochs_to_keep();
+        self.gc_retention.get(&category).map_or(num_epochs_to_keep, |retention| {
+            retention.num_epochs_to_keep.clamp(MIN_GC_NUM_EPOCHS_TO_KEEP, num_epochs_to_keep)
+        })
+    }
+
+    /// Number of epochs for which the data of the category is kept for the
+    /// shards that the node doesn't track, if less than for the other shards.
+    pub fn gc_untracked_shards_num_epochs_to_keep(&self, category: GCCategory) -> Option<u64> {
+        let num_epochs_to_keep = self.gc_category_num_epochs_to_keep(category);
+        let untracked_num_epochs_to_keep = self
+            .gc_retention
+            .get(&category)?
+            .untracked_shards_num_epochs_to_keep?
+            .max(MIN_GC_NUM_EPOCHS_TO_KEEP);
+        (untracked_num_epochs_to_keep < num_epochs_to_keep).then_some(untracked_num_epochs_to_keep)
+    }
 }
 
 fn default_num_concurrent_requests() -> u32 {
@@ -350,6 +432,13 @@ pub struct ReshardingConfig {
     /// This value can be decreased if resharding is consuming too many
     /// resources and interfering with regular node operation.
     pub catch_up_blocks: BlockHeightDelta,
+
+    /// The number of ranges of keys the flat state of a parent shard is
+    /// divided into during the shard split. The ranges are copied to the
+    /// children in parallel, each by its own thread with its own batches.
+    /// This value can be decreased if resharding is consuming too many
+    /// resources and interfering with regular node operation.
+    pub flat_storage_split_parallelism: usize,
 }
 
 impl Default for ReshardingConfig {
@@ -366,6 +455,7 @@ impl Default for ReshardingConfig {
             // state sync.
             max_poll_time: Duration::seconds(2 * 60 * 60), // 2 hours
             catch_up_blocks: 20,
+            flat_storage_split_parallelism: 4,
         }
     }
 }
@@ -494,6 +584,145 @@ pub struct ChunkDistributionUris {
     pub set: String,
 }
 
+/// Config for producing chunks with few or no transactions for a shard when
+/// the previous chunks of the shard didn't make it into blocks.
+/// The usual reason is that their state witnesses didn't reach enough chunk
+/// validators to collect the endorsements in time. A smaller chunk has a
+/// smaller witness, which gives the shard a chance to make progress while
+/// the network is degraded.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct EmptyChunkFallbackConfig {
+    pub enabled: bool,
+    /// Number of consecutive blocks which are missing the chunk for the
+    /// shard, after which the fallback kicks in.
+    pub missing_chunks_threshold: u64,
+    /// Maximum number of transactions included in a chunk produced by the
+    /// fallback.
+    pub max_transactions: usize,
+}
+
+impl Default for EmptyChunkFallbackConfig {
+    fn default() -> Self {
+        Self { enabled: false, missing_chunks_threshold: 3, max_transactions: 0 }
+    }
+}
+
+/// Config for the circuit breaker of the transactions forwarded by the node to
+/// the upcoming chunk producers.
+/// During traffic spikes the node stops forwarding to the chunk producers
+/// which already got their share of transactions for the current height, or
+/// which keep missing their chunks. The transactions which can't be forwarded
+/// to any chunk producer are buffered by the node and forwarded at the next
+/// heights.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TxForwardingConfig {
+    pub enabled: bool,
+    /// Maximum number of transactions forwarded to a single chunk producer
+    /// at a block height.
+    pub max_txs_per_height: usize,
+    /// Number of consecutive chunks missed by a chunk producer, after which
+    /// it's considered unresponsive and no transactions are forwarded to it.
+    pub missing_chunks_threshold: u64,
+    /// Number of heights for which no transactions are forwarded to an
+    /// unresponsive chunk producer.
+    pub open_heights: BlockHeightDelta,
+    /// Maximum total size of the buffered transactions. Once it's reached,
+    /// new transactions are rejected.
+    pub buffer_size_limit: ByteSize,
+    /// Number of heights after which a buffered transaction is dropped.
+    pub max_buffered_heights: BlockHeightDelta,
+    /// How often the buffered transactions are retried, so that they are
+    /// forwarded even when no new transactions arrive.
+    #[serde(with = "near_time::serde_duration_as_std")]
+    pub flush_interval: Duration,
+}
+
+impl Default for TxForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_txs_per_height: 2000,
+            missing_chunks_threshold: 3,
+            open_heights: 10,
+            buffer_size_limit: ByteSize::mb(32),
+            max_buffered_heights: 20,
+            flush_interval: Duration::milliseconds(100),
+        }
+    }
+}
+
+/// Config of the experimental data availability sampling.
+/// For every new chunk of a shard the node doesn't track, it requests a few
+/// random parts from their owners in the epoch of the chunk and checks them
+/// against the chunk header. The results are only reported in metrics, block
+/// processing doesn't depend on them.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DataAvailabilitySamplingConfig {
+    pub enabled: bool,
+    /// Number of parts sampled from each chunk.
+    pub samples_per_chunk: usize,
+    /// Time after which a sampled part which didn't arrive counts as
+    /// unavailable.
+    #[serde(with = "near_time::serde_duration_as_std")]
+    pub sample_timeout: Duration,
+}
+
+impl Default for DataAvailabilitySamplingConfig {
+    fn default() -> Self {
+        Self { enabled: false, samples_per_chunk: 4, sample_timeout: Duration::seconds(2) }
+    }
+}
+
+/// Config of the shard hotspots report.
+/// For every shard the node applies chunks of, it keeps the accounts which
+/// burnt the most gas and wrote the most bytes to the state in each of the
+/// recent epochs. The report is only served by the debug RPC.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ShardHotspotsConfig {
+    pub enabled: bool,
+    /// Number of top accounts reported per shard and per kind of usage.
+    pub top_k: usize,
+    /// Number of the latest epochs to keep the reports for.
+    pub num_epochs: usize,
+}
+
+impl Default for ShardHotspotsConfig {
+    fn default() -> Self {
+        Self { enabled: false, top_k: 20, num_epochs: 3 }
+    }
+}
+
+/// Config of the replica mode, for the RPC nodes fed by a block producer they
+/// trust. For the blocks of the trusted producers, the node requests the state
+/// updates of the chunks it applies from the producer, and writes them to its
+/// state instead of validating and executing the chunks. The chunks are
+/// executed as usual when the updates don't arrive in time.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ReplicaConfig {
+    pub enabled: bool,
+    /// Public keys of the block producers whose blocks aren't executed.
+    pub trusted_block_producer_keys: Vec<PublicKey>,
+    /// Time to wait for the state updates of a block before executing its
+    /// chunks.
+    #[serde(with = "near_time::serde_duration_as_std")]
+    pub state_update_timeout: Duration,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trusted_block_producer_keys: vec![],
+            state_update_timeout: Duration::milliseconds(500),
+        }
+    }
+}
+
 /// ClientConfig where some fields can be updated at runtime.
 #[derive(Clone, serde::Serialize)]
 pub struct ClientConfig {
@@ -616,6 +845,31 @@ pub struct ClientConfig {
     /// Nodes not participating will still function fine, but possibly with higher
     /// latency due to the need of requesting chunks over the peer-to-peer network.
     pub chunk_distribution_network: Option<ChunkDistributionNetworkConfig>,
+    /// Produce smaller chunks for shards whose chunks are repeatedly missing
+    /// from blocks, see `EmptyChunkFallbackConfig`.
+    pub empty_chunk_fallback: EmptyChunkFallbackConfig,
+    /// Limits the transactions forwarded to the chunk producers, see
+    /// `TxForwardingConfig`.
+    pub tx_forwarding: TxForwardingConfig,
+    /// Sample the availability of the chunks of untracked shards, see
+    /// `DataAvailabilitySamplingConfig`.
+    pub data_availability_sampling: DataAvailabilitySamplingConfig,
+    /// Track the accounts which use the most resources of each shard, see
+    /// `ShardHotspotsConfig`.
+    pub shard_hotspots: ShardHotspotsConfig,
+    /// Collect the storage usage and gas burnt of the accounts of the tracked
+    /// shards, used to propose the boundary accounts of shard splits.
+    pub save_account_usage_stats: bool,
+    /// Record the gas profile, the touched trie nodes and the outgoing
+    /// receipts of each receipt the node executes, along with the chunk apply
+    /// stats.
+    pub save_receipt_execution_profiles: bool,
+    /// Follow the blocks of trusted block producers without executing their
+    /// chunks, see `ReplicaConfig`.
+    pub replica: ReplicaConfig,
+    /// Keep the state updates of the recently applied chunks in memory and
+    /// send them to the replicas which request them.
+    pub serve_replica_state_updates: bool,
     /// OrphanStateWitnessPool keeps instances of ChunkStateWitness which can't be processed
     /// because the previous block isn't available. The witnesses wait in the pool until the
     /// required block appears. This variable controls how many witnesses can be stored in the pool.
@@ -716,6 +970,14 @@ impl ClientConfig {
                 "produce_chunk_add_transactions_time_limit",
             ),
             chunk_distribution_network: None,
+            empty_chunk_fallback: EmptyChunkFallbackConfig::default(),
+            tx_forwarding: TxForwardingConfig::default(),
+            data_availability_sampling: DataAvailabilitySamplingConfig::default(),
+            shard_hotspots: ShardHotspotsConfig::default(),
+            save_account_usage_stats: false,
+            save_receipt_execution_profiles: false,
+            replica: ReplicaConfig::default(),
+            serve_replica_state_updates: false,
             orphan_state_witness_pool_size: default_orphan_state_witness_pool_size(),
             orphan_state_witness_max_size: default_orphan_state_witness_max_size(),
             save_latest_witnesses: false,
@@ -1,8 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
 
-use near_crypto::PublicKey;
-use near_primitives::account::{AccessKey, Account, AccountContract};
+use near_crypto::{KeyType, PublicKey};
+use near_primitives::account::{
+    AccessKey, AccessKeyPermission, Account, AccountContract, FunctionCallPermission,
+};
 use near_primitives::epoch_manager::{EpochConfig, EpochConfigStore};
+use near_primitives::hash::{CryptoHash, hash};
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::state_record::StateRecord;
 use near_primitives::test_utils::{create_test_signer, create_user_test_signer};
@@ -17,7 +22,7 @@ use num_rational::Rational32;
 
 use crate::{
     FISHERMEN_THRESHOLD, Genesis, GenesisConfig, GenesisContents, GenesisRecords,
-    PROTOCOL_UPGRADE_STAKE_THRESHOLD,
+    PROTOCOL_UPGRADE_STAKE_THRESHOLD, stream_records_from_file,
 };
 
 #[derive(Debug, Clone)]
@@ -88,6 +93,7 @@ pub struct TestGenesisBuilder {
     protocol_upgrade_stake_threshold: Rational32,
     chunk_producer_assignment_changes_limit: NumSeats,
     user_accounts: Vec<UserAccount>,
+    synthetic_states: Vec<SyntheticStateSpec>,
 }
 
 #[derive(Debug, Clone)]
@@ -289,6 +295,7 @@ impl Default for TestGenesisBuilder {
             protocol_treasury_account: "near".to_string().parse().unwrap(),
             max_inflation_rate: Rational32::new(1, 1),
             user_accounts: vec![],
+            synthetic_states: vec![],
             dynamic_resharding: false,
             fishermen_threshold: 0,
             online_min_threshold: Rational32::new(90, 100),
@@ -401,6 +408,24 @@ impl TestGenesisBuilder {
         self
     }
 
+    /// Adds a user account with the given full access keys, in addition to
+    /// the key of its test signer.
+    pub fn add_user_account_with_keys(
+        mut self,
+        account_id: AccountId,
+        initial_balance: Balance,
+        access_keys: impl IntoIterator<Item = PublicKey>,
+    ) -> Self {
+        let mut user_access_keys = vec![create_user_test_signer(&account_id).public_key()];
+        user_access_keys.extend(access_keys);
+        self.user_accounts.push(UserAccount {
+            balance: initial_balance,
+            access_keys: user_access_keys,
+            account_id,
+        });
+        self
+    }
+
     pub fn add_user_accounts_simple(
         mut self,
         accounts: &[AccountId],
@@ -416,14 +441,25 @@ impl TestGenesisBuilder {
         self
     }
 
+    /// Adds the accounts of the synthetic state, along with their access keys,
+    /// contracts and contract storage.
+    pub fn add_synthetic_state(mut self, spec: SyntheticStateSpec) -> Self {
+        self.synthetic_states.push(spec);
+        self
+    }
+
     pub fn build(self) -> Genesis {
+        let synthetic_account_ids =
+            self.synthetic_states.iter().flat_map(|spec| spec.account_ids()).collect::<Vec<_>>();
+        let num_accounts = self.user_accounts.len() + synthetic_account_ids.len();
         if self
             .user_accounts
             .iter()
             .map(|account| &account.account_id)
+            .chain(&synthetic_account_ids)
             .collect::<HashSet<_>>()
             .len()
-            != self.user_accounts.len()
+            != num_accounts
         {
             panic!("Duplicate user accounts specified.");
         }
@@ -478,13 +514,13 @@ impl TestGenesisBuilder {
                 records.push(StateRecord::AccessKey {
                     account_id: user_account.account_id.clone(),
                     public_key: access_key.clone(),
-                    access_key: AccessKey {
-                        nonce: 0,
-                        permission: near_primitives::account::AccessKeyPermission::FullAccess,
-                    },
+                    access_key: AccessKey { nonce: 0, permission: AccessKeyPermission::FullAccess },
                 });
             }
         }
+        for spec in &self.synthetic_states {
+            total_supply += spec.add_records(&mut records, &mut validator_stake);
+        }
         for (account_id, balance) in validator_stake {
             records.push(StateRecord::Account {
                 account_id,
@@ -537,6 +573,203 @@ impl TestGenesisBuilder {
     }
 }
 
+/// A large state for testing, with many accounts holding contracts and
+/// contract storage. The accounts are named `{prefix}{index:06}`, so they are
+/// sorted by their index, and the tests can pick shard boundaries among them.
+/// The generated state is deterministic for a given seed.
+///
+/// By default, the contract storage mimics the balances of a fungible token
+/// contract, keyed by the holders among the synthetic accounts. With
+/// `storage_samples`, the keys and values are taken from a real state instead,
+/// e.g. loaded from a mainnet state dump with `load_storage_samples`.
+#[derive(Debug, Clone)]
+pub struct SyntheticStateSpec {
+    account_prefix: String,
+    num_accounts: usize,
+    balance: Balance,
+    num_access_keys: usize,
+    contract_code: Option<Arc<Vec<u8>>>,
+    num_contract_accounts: usize,
+    num_storage_records: usize,
+    storage_samples: Arc<Vec<(Vec<u8>, Vec<u8>)>>,
+    seed: u64,
+}
+
+impl SyntheticStateSpec {
+    pub fn new(account_prefix: &str, num_accounts: usize) -> Self {
+        Self {
+            account_prefix: account_prefix.to_string(),
+            num_accounts,
+            balance: 1_000_000 * ONE_NEAR,
+            num_access_keys: 0,
+            contract_code: None,
+            num_contract_accounts: 0,
+            num_storage_records: 0,
+            storage_samples: Arc::new(vec![]),
+            seed: 0,
+        }
+    }
+
+    pub fn balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    /// Function call access keys added to each account, in addition to the
+    /// full access key of its test signer.
+    pub fn access_keys_per_account(mut self, num_access_keys: usize) -> Self {
+        self.num_access_keys = num_access_keys;
+        self
+    }
+
+    /// Deploys the contract to every `num_accounts / num_contract_accounts`th
+    /// account, spreading the contracts over the whole range of accounts.
+    pub fn contracts(mut self, code: Vec<u8>, num_contract_accounts: usize) -> Self {
+        assert!(num_contract_accounts <= self.num_accounts);
+        self.contract_code = Some(Arc::new(code));
+        self.num_contract_accounts = num_contract_accounts;
+        self
+    }
+
+    /// Contract storage records of each account with a contract.
+    pub fn storage_records_per_contract(mut self, num_storage_records: usize) -> Self {
+        self.num_storage_records = num_storage_records;
+        self
+    }
+
+    pub fn storage_samples(mut self, storage_samples: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        self.storage_samples = Arc::new(storage_samples);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn account_ids(&self) -> Vec<AccountId> {
+        (0..self.num_accounts).map(|index| self.account_id(index)).collect()
+    }
+
+    fn account_id(&self, index: usize) -> AccountId {
+        format!("{}{:06}", self.account_prefix, index).parse().unwrap()
+    }
+
+    fn has_contract(&self, index: usize) -> bool {
+        self.contract_code.is_some()
+            && self.num_contract_accounts > 0
+            && index % (self.num_accounts / self.num_contract_accounts) == 0
+            && index / (self.num_accounts / self.num_contract_accounts) < self.num_contract_accounts
+    }
+
+    fn random(&self, index: usize, salt: &str, position: usize) -> CryptoHash {
+        hash(format!("{}/{}/{}/{}", self.seed, index, salt, position).as_bytes())
+    }
+
+    fn random_u64(&self, index: usize, salt: &str, position: usize) -> u64 {
+        u64::from_le_bytes(self.random(index, salt, position).0[..8].try_into().unwrap())
+    }
+
+    /// Adds the records of the state, and returns their total balance.
+    fn add_records(
+        &self,
+        records: &mut Vec<StateRecord>,
+        validator_stake: &mut HashMap<AccountId, Balance>,
+    ) -> Balance {
+        for index in 0..self.num_accounts {
+            let account_id = self.account_id(index);
+            let code = self.contract_code.as_ref().filter(|_| self.has_contract(index));
+            let contract = match code {
+                Some(code) => AccountContract::Local(hash(code)),
+                None => AccountContract::None,
+            };
+            records.push(StateRecord::Account {
+                account_id: account_id.clone(),
+                account: Account::new(
+                    self.balance,
+                    validator_stake.remove(&account_id).unwrap_or(0),
+                    contract,
+                    0,
+                ),
+            });
+            records.push(StateRecord::AccessKey {
+                account_id: account_id.clone(),
+                public_key: create_user_test_signer(&account_id).public_key(),
+                access_key: AccessKey { nonce: 0, permission: AccessKeyPermission::FullAccess },
+            });
+            for key_index in 0..self.num_access_keys {
+                records.push(StateRecord::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: PublicKey::from_seed(
+                        KeyType::ED25519,
+                        &format!("{}/{}/{}", self.seed, account_id, key_index),
+                    ),
+                    access_key: AccessKey {
+                        nonce: 0,
+                        permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                            allowance: None,
+                            receiver_id: account_id.to_string(),
+                            method_names: vec![],
+                        }),
+                    },
+                });
+            }
+            let Some(code) = code else {
+                continue;
+            };
+            records.push(StateRecord::Contract {
+                account_id: account_id.clone(),
+                code: code.as_ref().clone(),
+            });
+            for (data_key, value) in self.storage(index) {
+                records.push(StateRecord::Data {
+                    account_id: account_id.clone(),
+                    data_key: data_key.into(),
+                    value: value.into(),
+                });
+            }
+        }
+        self.balance * self.num_accounts as Balance
+    }
+
+    /// The contract storage of the account, sorted and without duplicate keys.
+    fn storage(&self, index: usize) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        (0..self.num_storage_records)
+            .map(|position| {
+                let random = self.random_u64(index, "storage", position);
+                if self.storage_samples.is_empty() {
+                    let holder = self.account_id(random as usize % self.num_accounts);
+                    let key = [b"t".as_slice(), holder.as_bytes()].concat();
+                    let balance = random as u128 * 1_000_000;
+                    (key, balance.to_le_bytes().to_vec())
+                } else {
+                    self.storage_samples[random as usize % self.storage_samples.len()].clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Loads up to `max_samples` contract storage records of the state records
+/// file, e.g. of a mainnet state dump, for `SyntheticStateSpec::storage_samples`.
+pub fn load_storage_samples(
+    records_file: &Path,
+    max_samples: usize,
+) -> std::io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let reader = std::io::BufReader::new(std::fs::File::open(records_file)?);
+    let mut samples = vec![];
+    stream_records_from_file(reader, |record| {
+        if samples.len() >= max_samples {
+            return;
+        }
+        if let StateRecord::Data { data_key, value, .. } = record {
+            samples.push((data_key.into(), value.into()));
+        }
+    })
+    .map_err(std::io::Error::other)?;
+    Ok(samples)
+}
+
 impl ValidatorsSpec {
     /// Specifies that we want the validators to be exactly the specified accounts.
     /// This will generate a reasonable set of parameters so that the given
@@ -0,0 +1,349 @@
+//! Structured log of the chain events, for the monitoring pipelines and the
+//! tests which would otherwise grep the free-form logs.
+//!
+//! Every event is written as a JSON object on its own line, to the sink set
+//! by the `event_log` config: a file the events are appended to, or a Unix
+//! socket. The objects hold the `schema_version`, the `timestamp_ms` and the
+//! `event` name, along with the fields of the event. Fields are only ever
+//! added to an event within the same schema version.
+//!
+//! Nothing is written, and the events are dropped, until the sink is set.
+//! The events are written by a thread of their own, and dropped when it can't
+//! keep up, so that a slow sink never holds back the node. The sink is opened
+//! again after a failed write.
+use crate::metrics::{IntCounter, try_create_int_counter};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::types::{BlockHeight, EpochHeight, ProtocolVersion, ShardId};
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Bumped on the changes of the events which aren't backwards compatible.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Where the events are written to. Relative paths are relative to the home
+/// directory of the node.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventLogConfig {
+    File(PathBuf),
+    UnixSocket(PathBuf),
+}
+
+/// The phases of the state sync of a shard.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateSyncPhase {
+    DownloadHeader,
+    DownloadParts,
+    ApplyParts,
+    Finalize,
+    Done,
+}
+
+/// The shards are identified by their shard uid, as in `s1.v3`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChainEvent {
+    BlockApplied {
+        block_hash: CryptoHash,
+        height: BlockHeight,
+        epoch_id: CryptoHash,
+        num_new_chunks: usize,
+    },
+    /// The block is the first one of a new epoch.
+    EpochSwitch {
+        block_hash: CryptoHash,
+        height: BlockHeight,
+        epoch_id: CryptoHash,
+        epoch_height: EpochHeight,
+        protocol_version: ProtocolVersion,
+    },
+    /// The parent shard is split after the block, at the end of its epoch.
+    ReshardingScheduled {
+        block_hash: CryptoHash,
+        height: BlockHeight,
+        parent_shard_uid: String,
+        left_child_shard_uid: String,
+        right_child_shard_uid: String,
+    },
+    /// The resharding block became final, and the flat storage of the parent
+    /// shard is being split.
+    ReshardingStarted {
+        block_hash: CryptoHash,
+        height: BlockHeight,
+        parent_shard_uid: String,
+    },
+    /// The flat storage of the child shard catches up with the chain.
+    ReshardingCatchUp {
+        shard_uid: String,
+    },
+    /// The flat storage of the child shard is ready.
+    ReshardingDone {
+        shard_uid: String,
+        flat_head_height: BlockHeight,
+    },
+    StateSyncPhase {
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        phase: StateSyncPhase,
+    },
+}
+
+/// A line of the event log.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EventRecord {
+    pub schema_version: u32,
+    pub timestamp_ms: u64,
+    /// The node which emitted the event, when several nodes run in the same
+    /// process, see [`with_node`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    #[serde(flatten)]
+    pub event: ChainEvent,
+}
+
+/// The number of events queued for the writer thread, past which the events
+/// are dropped.
+const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// The delay before the sink is opened again after a failure. The events are
+/// dropped in the meantime.
+const REOPEN_DELAY: Duration = Duration::from_secs(1);
+
+static EVENT_QUEUE: RwLock<Option<SyncSender<Vec<u8>>>> = RwLock::new(None);
+
+static DROPPED_EVENTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    try_create_int_counter(
+        "near_event_log_dropped_events_total",
+        "Number of the chain events dropped because the event log couldn't keep up or wasn't open",
+    )
+    .unwrap()
+});
+
+thread_local! {
+    static NODE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Opens the sink the events are written to, again after a failed write.
+type OpenSink = Box<dyn FnMut() -> std::io::Result<Box<dyn Write + Send>> + Send>;
+
+/// Opens the sink of the config and writes all the following events to it.
+pub fn init_event_log(config: &EventLogConfig, home_dir: &Path) -> std::io::Result<()> {
+    let config = config.clone();
+    let home_dir = home_dir.to_path_buf();
+    let mut open_sink: OpenSink = Box::new(move || open_sink(&config, &home_dir));
+    let sink = open_sink()?;
+    start_writer(sink, open_sink);
+    Ok(())
+}
+
+fn open_sink(config: &EventLogConfig, home_dir: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    Ok(match config {
+        EventLogConfig::File(path) => Box::new(
+            std::fs::OpenOptions::new().create(true).append(true).open(home_dir.join(path))?,
+        ),
+        #[cfg(unix)]
+        EventLogConfig::UnixSocket(path) => {
+            Box::new(std::os::unix::net::UnixStream::connect(home_dir.join(path))?)
+        }
+        #[cfg(not(unix))]
+        EventLogConfig::UnixSocket(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            ));
+        }
+    })
+}
+
+/// Writes all the following events to `sink`, e.g. to collect them in tests.
+/// The sink isn't opened again after a failed write.
+pub fn set_event_sink(sink: Box<dyn Write + Send>) {
+    let open_sink: OpenSink = Box::new(|| {
+        Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "the event sink is closed"))
+    });
+    start_writer(sink, open_sink);
+}
+
+/// Replaces the queue of the events by one to a new writer thread. The
+/// previous thread stops once it wrote the events queued before.
+fn start_writer(sink: Box<dyn Write + Send>, open_sink: OpenSink) {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(EVENT_QUEUE_CAPACITY);
+    std::thread::Builder::new()
+        .name("event_log".to_string())
+        .spawn(move || write_events(receiver, sink, open_sink))
+        .expect("failed to spawn the event log thread");
+    *EVENT_QUEUE.write().unwrap() = Some(sender);
+}
+
+fn write_events(receiver: Receiver<Vec<u8>>, sink: Box<dyn Write + Send>, mut open_sink: OpenSink) {
+    let mut sink = Some(sink);
+    let mut reopen_at = Instant::now();
+    while let Ok(line) = receiver.recv() {
+        if sink.is_none() && Instant::now() >= reopen_at {
+            match open_sink() {
+                Ok(new_sink) => sink = Some(new_sink),
+                Err(err) => {
+                    tracing::warn!(target: "o11y", ?err, "failed to open the event log again");
+                    reopen_at = Instant::now() + REOPEN_DELAY;
+                }
+            }
+        }
+        let Some(writer) = sink.as_mut() else {
+            DROPPED_EVENTS.inc();
+            continue;
+        };
+        // Flush once the queued events are written.
+        let result = std::iter::once(line)
+            .chain(receiver.try_iter())
+            .try_for_each(|line| writer.write_all(&line))
+            .and_then(|()| writer.flush());
+        if let Err(err) = result {
+            tracing::error!(target: "o11y", ?err, "failed to write to the event log, opening it again");
+            sink = None;
+            reopen_at = Instant::now() + REOPEN_DELAY;
+        }
+    }
+}
+
+/// Whether the events are written anywhere, so that the callers can skip
+/// collecting the fields of the events otherwise.
+pub fn is_enabled() -> bool {
+    EVENT_QUEUE.read().unwrap().is_some()
+}
+
+/// Runs `f` with the events emitted on this thread attributed to `node`, to
+/// tell apart the nodes of a test loop.
+pub fn with_node<R>(node: &str, f: impl FnOnce() -> R) -> R {
+    let previous = NODE.with(|current| current.replace(Some(node.to_string())));
+    let result = f();
+    NODE.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Queues the event for the writer thread, if the sink is set. The event is
+/// dropped if the queue is full.
+pub fn emit(event: ChainEvent) {
+    let queue = EVENT_QUEUE.read().unwrap();
+    let Some(sender) = queue.as_ref() else {
+        return;
+    };
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64);
+    let node = NODE.with(|node| node.borrow().clone());
+    let record = EventRecord { schema_version: EVENT_SCHEMA_VERSION, timestamp_ms, node, event };
+    let mut line = serde_json::to_vec(&record).expect("chain events serialize to JSON");
+    line.push(b'\n');
+    match sender.try_send(line) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => DROPPED_EVENTS.inc(),
+        Err(TrySendError::Disconnected(_)) => {
+            tracing::error!(target: "o11y", "the event log thread stopped");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        /// Waits for the writer thread to write `num_lines` lines.
+        fn wait_for_lines(&self, num_lines: usize) -> Vec<String> {
+            for _ in 0..500 {
+                let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+                let lines = output.lines().map(str::to_string).collect::<Vec<_>>();
+                if lines.len() >= num_lines {
+                    return lines;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            panic!("the events weren't written");
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Fails every write.
+    struct BrokenSink;
+
+    impl Write for BrokenSink {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit_json_lines() {
+        emit(ChainEvent::ReshardingCatchUp { shard_uid: "s0.v3".to_string() });
+
+        let buffer = SharedBuffer::default();
+        set_event_sink(Box::new(buffer.clone()));
+        let events = vec![
+            ChainEvent::ReshardingDone { shard_uid: "s7.v3".to_string(), flat_head_height: 12 },
+            ChainEvent::StateSyncPhase {
+                shard_id: ShardId::new(2),
+                sync_hash: CryptoHash::hash_bytes(b"sync"),
+                phase: StateSyncPhase::DownloadParts,
+            },
+        ];
+        emit(events[0].clone());
+        with_node("node1", || emit(events[1].clone()));
+
+        let lines = buffer.wait_for_lines(events.len());
+        assert_eq!(lines.len(), events.len());
+        let value: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(value["schema_version"], EVENT_SCHEMA_VERSION);
+        assert_eq!(value["event"], "resharding_done");
+        assert_eq!(value["shard_uid"], "s7.v3");
+        assert!(value.get("node").is_none());
+        let records = lines
+            .iter()
+            .map(|line| serde_json::from_str::<EventRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.iter().map(|record| record.event.clone()).collect::<Vec<_>>(), events);
+        assert_eq!(records[1].node.as_deref(), Some("node1"));
+    }
+
+    /// The sink is opened again after a failed write, and the events written
+    /// to it from then on.
+    #[test]
+    fn test_reopen_sink_after_failed_write() {
+        let buffer = SharedBuffer::default();
+        let reopened_buffer = buffer.clone();
+        let open_sink: OpenSink = Box::new(move || Ok(Box::new(reopened_buffer.clone())));
+        let (sender, receiver) = std::sync::mpsc::sync_channel(EVENT_QUEUE_CAPACITY);
+        let writer =
+            std::thread::spawn(move || write_events(receiver, Box::new(BrokenSink), open_sink));
+
+        sender.send(b"lost\n".to_vec()).unwrap();
+        // The sink is opened again only after the delay since the failure.
+        std::thread::sleep(REOPEN_DELAY + Duration::from_millis(500));
+        sender.send(b"written\n".to_vec()).unwrap();
+        drop(sender);
+        writer.join().unwrap();
+
+        assert_eq!(buffer.wait_for_lines(1), vec!["written".to_string()]);
+    }
+}
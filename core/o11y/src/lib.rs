@@ -14,6 +14,7 @@ pub use {tracing, tracing_appender, tracing_subscriber};
 /// Custom tracing subscriber implementation that produces IO traces.
 pub mod context;
 pub mod env_filter;
+pub mod events;
 mod io_tracer;
 pub mod log_config;
 mod log_counter;
@@ -6,6 +6,7 @@ use crate::parameter_table::ParameterTable;
 use near_account_id::AccountId;
 use near_primitives_core::types::{Balance, Gas, ProtocolVersion};
 use near_primitives_core::version::PROTOCOL_VERSION;
+use num_rational::Rational32;
 use std::sync::Arc;
 
 // Lowered promise yield timeout length used in integration tests.
@@ -210,6 +211,30 @@ pub struct CongestionControlConfig {
     /// to send a lot of receipts without making the state witness too large.
     /// It limits the total sum of outgoing receipts, not individual receipts.
     pub outgoing_receipts_big_size_limit: u64,
+
+    /// How congested a shard has to be for a block to count towards the
+    /// [GAS_PRICE_FLOOR_CONGESTED_BLOCKS](CongestionControlConfig::gas_price_floor_congested_blocks)
+    /// streak that raises the minimum gas price.
+    pub gas_price_floor_congestion_threshold: f64,
+
+    /// For how many consecutive blocks at least one shard has to stay above
+    /// [GAS_PRICE_FLOOR_CONGESTION_THRESHOLD](CongestionControlConfig::gas_price_floor_congestion_threshold)
+    /// before the minimum gas price starts to rise.
+    pub gas_price_floor_congested_blocks: u64,
+
+    /// By how much the minimum gas price is raised, relative to the current gas
+    /// price, for every block of sustained congestion. Once the congestion is
+    /// gone, the minimum gas price falls back to the genesis minimum and the
+    /// regular gas price adjustment brings the price down again.
+    pub gas_price_floor_increase_rate: Rational32,
+
+    /// Whether refunds, promise resume receipts and receipts with a priority
+    /// are forwarded even when the outgoing gas limit to the receiving shard is
+    /// exhausted, instead of waiting behind the receipts in the outgoing buffer.
+    /// The gas they use past the limit is capped at a fraction of
+    /// `max_outgoing_gas` per chunk, and they are still subject to the outgoing
+    /// size limit.
+    pub prioritize_outgoing_receipts: bool,
 }
 
 // The Eq cannot be automatically derived for this class because it contains a
@@ -237,6 +262,10 @@ impl CongestionControlConfig {
             reject_tx_congestion_threshold: 2.0,
             outgoing_receipts_usual_size_limit: max_value,
             outgoing_receipts_big_size_limit: max_value,
+            gas_price_floor_congestion_threshold: 2.0,
+            gas_price_floor_congested_blocks: max_value,
+            gas_price_floor_increase_rate: Rational32::from_integer(0),
+            prioritize_outgoing_receipts: false,
         }
     }
 }
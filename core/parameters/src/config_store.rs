@@ -57,6 +57,8 @@ static CONFIG_DIFFS: &[(ProtocolVersion, &str)] = &[
     (77, include_config!("77.yaml")),
     (129, include_config!("129.yaml")),
     (149, include_config!("149.yaml")),
+    (150, include_config!("150.yaml")),
+    (152, include_config!("152.yaml")),
 ];
 
 /// Testnet parameters for versions <= 29, which (incorrectly) differed from mainnet parameters
@@ -211,6 +211,10 @@ pub enum Parameter {
     MaxTxGas,
     MinTxGas,
     RejectTxCongestionThreshold,
+    GasPriceFloorCongestionThreshold,
+    GasPriceFloorCongestedBlocks,
+    GasPriceFloorIncreaseRate,
+    PrioritizeOutgoingReceipts,
 
     // Use the StateStoredReceipt structure when storing receipts in State.
     UseStateStoredReceipt,
@@ -371,6 +371,13 @@ fn get_congestion_control_config(
         outgoing_receipts_usual_size_limit: params
             .get(Parameter::OutgoingReceiptsUsualSizeLimit)?,
         outgoing_receipts_big_size_limit: params.get(Parameter::OutgoingReceiptsBigSizeLimit)?,
+        gas_price_floor_congestion_threshold: {
+            let rational: Rational32 = params.get(Parameter::GasPriceFloorCongestionThreshold)?;
+            *rational.numer() as f64 / *rational.denom() as f64
+        },
+        gas_price_floor_congested_blocks: params.get(Parameter::GasPriceFloorCongestedBlocks)?,
+        gas_price_floor_increase_rate: params.get(Parameter::GasPriceFloorIncreaseRate)?,
+        prioritize_outgoing_receipts: params.get(Parameter::PrioritizeOutgoingReceipts)?,
     };
     Ok(congestion_control_config)
 }
@@ -751,6 +751,30 @@ pub struct CongestionControlConfigView {
     /// to send a lot of receipts without making the state witness too large.
     /// It limits the total sum of outgoing receipts, not individual receipts.
     pub outgoing_receipts_big_size_limit: u64,
+
+    /// How congested a shard has to be for a block to count towards raising
+    /// the minimum gas price.
+    ///
+    /// See [`CongestionControlConfig`] for more details.
+    pub gas_price_floor_congestion_threshold: f64,
+
+    /// For how many consecutive blocks a shard has to stay congested before the
+    /// minimum gas price starts to rise.
+    ///
+    /// See [`CongestionControlConfig`] for more details.
+    pub gas_price_floor_congested_blocks: u64,
+
+    /// By how much the minimum gas price is raised per block of sustained
+    /// congestion.
+    ///
+    /// See [`CongestionControlConfig`] for more details.
+    pub gas_price_floor_increase_rate: Rational32,
+
+    /// Whether high priority receipts are forwarded ahead of the outgoing
+    /// buffer.
+    ///
+    /// See [`CongestionControlConfig`] for more details.
+    pub prioritize_outgoing_receipts: bool,
 }
 
 impl From<CongestionControlConfig> for CongestionControlConfigView {
@@ -768,6 +792,10 @@ impl From<CongestionControlConfig> for CongestionControlConfigView {
             reject_tx_congestion_threshold: other.reject_tx_congestion_threshold,
             outgoing_receipts_usual_size_limit: other.outgoing_receipts_usual_size_limit,
             outgoing_receipts_big_size_limit: other.outgoing_receipts_big_size_limit,
+            gas_price_floor_congestion_threshold: other.gas_price_floor_congestion_threshold,
+            gas_price_floor_congested_blocks: other.gas_price_floor_congested_blocks,
+            gas_price_floor_increase_rate: other.gas_price_floor_increase_rate,
+            prioritize_outgoing_receipts: other.prioritize_outgoing_receipts,
         }
     }
 }
@@ -787,6 +815,10 @@ impl From<CongestionControlConfigView> for CongestionControlConfig {
             reject_tx_congestion_threshold: other.reject_tx_congestion_threshold,
             outgoing_receipts_usual_size_limit: other.outgoing_receipts_usual_size_limit,
             outgoing_receipts_big_size_limit: other.outgoing_receipts_big_size_limit,
+            gas_price_floor_congestion_threshold: other.gas_price_floor_congestion_threshold,
+            gas_price_floor_congested_blocks: other.gas_price_floor_congested_blocks,
+            gas_price_floor_increase_rate: other.gas_price_floor_increase_rate,
+            prioritize_outgoing_receipts: other.prioritize_outgoing_receipts,
         }
     }
 }
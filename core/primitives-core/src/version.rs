@@ -303,6 +303,30 @@ pub enum ProtocolFeature {
     /// 5% for gas refunds and charge the signer this fee for gas refund
     /// receipts.
     ReducedGasRefunds,
+    /// Raise the minimum gas price of the next block gradually while any shard
+    /// stays above `gas_price_floor_congestion_threshold` for
+    /// `gas_price_floor_congested_blocks` consecutive blocks. The floor falls
+    /// back to the regular minimum once the congestion is resolved.
+    CongestionGasPriceFloor,
+    /// Move state part boundaries to the first key of the account they fall
+    /// into, so that the data of an account in a trie column is not split
+    /// between state parts.
+    StatePartsAlignedToAccounts,
+    /// Forward refunds, promise resume receipts and receipts with a priority
+    /// ahead of the outgoing receipts buffer, ignoring the outgoing gas limit,
+    /// if `prioritize_outgoing_receipts` is set.
+    OutgoingReceiptsPriority,
+    /// Halve the main storage proof soft limit of the chunks of a shard split
+    /// at the end of the epoch, so that the witness of the first chunks of the
+    /// children, which also hold the proof of the split, stays bounded.
+    ReshardingWitnessSizeLimit,
+    /// Resolve the promise yield timeouts `PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS`
+    /// after they expire, so that a resume delivered in the block of the
+    /// timeout still wins.
+    PromiseYieldTimeoutGraceWindow,
+    /// Keep in the timeout queue of each child of a split shard only the
+    /// promise yield timeouts of its own accounts.
+    SplitAwarePromiseYieldTimeouts,
 }
 
 impl ProtocolFeature {
@@ -403,6 +427,12 @@ impl ProtocolFeature {
             ProtocolFeature::ShuffleShardAssignments => 143,
             ProtocolFeature::ExcludeExistingCodeFromWitnessForCodeLen => 148,
             ProtocolFeature::ReducedGasRefunds => 149,
+            ProtocolFeature::CongestionGasPriceFloor => 150,
+            ProtocolFeature::StatePartsAlignedToAccounts => 151,
+            ProtocolFeature::OutgoingReceiptsPriority => 152,
+            ProtocolFeature::ReshardingWitnessSizeLimit => 153,
+            ProtocolFeature::PromiseYieldTimeoutGraceWindow => 154,
+            ProtocolFeature::SplitAwarePromiseYieldTimeouts => 155,
             // Place features that are not yet in Nightly below this line.
         }
     }
@@ -422,7 +452,7 @@ pub const MIN_SUPPORTED_PROTOCOL_VERSION: ProtocolVersion = 75;
 const STABLE_PROTOCOL_VERSION: ProtocolVersion = 78;
 
 // On nightly, pick big enough version to support all features.
-const NIGHTLY_PROTOCOL_VERSION: ProtocolVersion = 149;
+const NIGHTLY_PROTOCOL_VERSION: ProtocolVersion = 155;
 
 /// Largest protocol version supported by the current binary.
 pub const PROTOCOL_VERSION: ProtocolVersion =
@@ -310,6 +310,25 @@ impl Block {
         next_gas_price.clamp(U256::from(min_gas_price), U256::from(max_gas_price)).as_u128()
     }
 
+    /// Computes the minimum gas price for the next block while a shard has been
+    /// congested for long enough, according to the formula:
+    ///   gas_price_floor = gas_price * (1 + increase_rate)
+    /// and clamped between min_gas_price and max_gas_price.
+    pub fn compute_congestion_gas_price_floor(
+        gas_price: Balance,
+        increase_rate: Rational32,
+        min_gas_price: Balance,
+        max_gas_price: Balance,
+    ) -> Balance {
+        let increase_rate_numer = *increase_rate.numer() as u128;
+        let increase_rate_denom = *increase_rate.denom() as u128;
+        let gas_price_floor = U256::from(gas_price)
+            * U256::from(increase_rate_denom + increase_rate_numer)
+            / U256::from(increase_rate_denom);
+
+        gas_price_floor.clamp(U256::from(min_gas_price), U256::from(max_gas_price)).as_u128()
+    }
+
     pub fn compute_state_root<'a, T: IntoIterator<Item = &'a ShardChunkHeader>>(
         chunks: T,
     ) -> CryptoHash {
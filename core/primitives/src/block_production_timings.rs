@@ -0,0 +1,184 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::types::{AccountId, BlockHeight, ShardId};
+
+use crate::types::EpochId;
+
+/// Timing information recorded by a block producer about the production of a single block.
+/// Used to attribute delays in block production to the approvals, chunks or chunk endorsements
+/// that the block producer had to wait for.
+/// All timestamps are unix timestamps in nanoseconds, as observed by the block producer.
+///
+/// This structure is used only for debugging, fields might be added or removed at any time.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct BlockProductionTimings {
+    /// Height of the produced block.
+    pub height: BlockHeight,
+    /// Hash of the produced block.
+    pub block_hash: CryptoHash,
+    /// Hash of the block on top of which the block was produced.
+    pub prev_block_hash: CryptoHash,
+    /// Epoch of the produced block.
+    pub epoch_id: EpochId,
+    /// Time at which approvals for this height reached the doomslug threshold.
+    pub approvals_ready_at: Option<u64>,
+    /// Per-shard chunk timings, indexed by shard index.
+    pub chunks: Vec<ChunkProductionTimings>,
+    /// Time at which the block was produced.
+    pub block_produced_at: u64,
+    /// Time at which the block was handed over to the network for broadcasting.
+    pub block_broadcast_at: u64,
+}
+
+/// Timing information about a chunk that the block producer was waiting for.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct ChunkProductionTimings {
+    pub shard_id: ShardId,
+    /// Chunk producer that was responsible for the chunk.
+    pub chunk_producer: AccountId,
+    /// Time at which the chunk was ready for inclusion (all parts were received).
+    /// None if the chunk didn't arrive before the block was produced.
+    pub chunk_received_at: Option<u64>,
+    /// Time at which the block producer observed enough endorsements for the chunk.
+    /// None if the chunk didn't get enough endorsements before the block was produced.
+    pub endorsements_ready_at: Option<u64>,
+    /// Whether the chunk was included in the block.
+    pub chunk_included: bool,
+}
+
+/// The last event that the block producer was waiting for before producing the block.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BlockProductionBlocker {
+    /// Block production was waiting for approvals to reach the doomslug threshold.
+    Approvals,
+    /// Block production was waiting for the chunk to arrive.
+    Chunk { shard_id: ShardId, chunk_producer: AccountId },
+    /// Block production was waiting for the chunk to be endorsed by chunk validators.
+    ChunkEndorsements { shard_id: ShardId, chunk_producer: AccountId },
+}
+
+impl BlockProductionTimings {
+    /// Returns the event that happened last before the block was produced, together with the
+    /// time at which it happened. Chunks that were not included in the block are ignored,
+    /// as the block producer didn't wait for them.
+    pub fn last_awaited_event(&self) -> Option<(BlockProductionBlocker, u64)> {
+        let mut result =
+            self.approvals_ready_at.map(|time| (BlockProductionBlocker::Approvals, time));
+        let mut update = |blocker: BlockProductionBlocker, time: u64| {
+            if result.as_ref().is_none_or(|(_, last)| time > *last) {
+                result = Some((blocker, time));
+            }
+        };
+        for chunk in self.chunks.iter().filter(|chunk| chunk.chunk_included) {
+            if let Some(time) = chunk.chunk_received_at {
+                let blocker = BlockProductionBlocker::Chunk {
+                    shard_id: chunk.shard_id,
+                    chunk_producer: chunk.chunk_producer.clone(),
+                };
+                update(blocker, time);
+            }
+            if let Some(time) = chunk.endorsements_ready_at {
+                let blocker = BlockProductionBlocker::ChunkEndorsements {
+                    shard_id: chunk.shard_id,
+                    chunk_producer: chunk.chunk_producer.clone(),
+                };
+                update(blocker, time);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(
+        shard_id: u64,
+        received: Option<u64>,
+        endorsed: Option<u64>,
+        included: bool,
+    ) -> ChunkProductionTimings {
+        ChunkProductionTimings {
+            shard_id: ShardId::new(shard_id),
+            chunk_producer: format!("producer{shard_id}").parse().unwrap(),
+            chunk_received_at: received,
+            endorsements_ready_at: endorsed,
+            chunk_included: included,
+        }
+    }
+
+    fn timings(
+        approvals_ready_at: Option<u64>,
+        chunks: Vec<ChunkProductionTimings>,
+    ) -> BlockProductionTimings {
+        BlockProductionTimings {
+            height: 10,
+            block_hash: CryptoHash::default(),
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            approvals_ready_at,
+            chunks,
+            block_produced_at: 100,
+            block_broadcast_at: 101,
+        }
+    }
+
+    #[test]
+    fn test_last_awaited_event() {
+        let t = timings(None, vec![]);
+        assert_eq!(t.last_awaited_event(), None);
+
+        let t = timings(Some(50), vec![chunk(0, Some(10), Some(20), true)]);
+        assert_eq!(t.last_awaited_event(), Some((BlockProductionBlocker::Approvals, 50)));
+
+        let t = timings(
+            Some(50),
+            vec![chunk(0, Some(10), Some(20), true), chunk(1, Some(60), Some(70), true)],
+        );
+        assert_eq!(
+            t.last_awaited_event(),
+            Some((
+                BlockProductionBlocker::ChunkEndorsements {
+                    shard_id: ShardId::new(1),
+                    chunk_producer: "producer1".parse().unwrap(),
+                },
+                70
+            ))
+        );
+
+        // Chunks that were not included are not awaited.
+        let t = timings(
+            Some(50),
+            vec![chunk(0, Some(80), None, true), chunk(1, Some(90), None, false)],
+        );
+        assert_eq!(
+            t.last_awaited_event(),
+            Some((
+                BlockProductionBlocker::Chunk {
+                    shard_id: ShardId::new(0),
+                    chunk_producer: "producer0".parse().unwrap(),
+                },
+                80
+            ))
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use std::collections::BTreeMap;
 
 use borsh::{BorshDeserialize, BorshSerialize};
-use near_primitives_core::types::{Balance, BlockHeight, Gas, ShardId};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::types::{AccountId, Balance, BlockHeight, Compute, Gas, ShardId};
 
 use crate::bandwidth_scheduler::{
     Bandwidth, BandwidthRequest, BandwidthRequestValues, BandwidthRequests,
     BandwidthSchedulerParams, BlockBandwidthRequests,
 };
+use crate::profile_data_v3::ProfileDataV3;
 
 /// Information gathered during chunk application.
 /// Provides insight into what happened when the chunk was applied.
@@ -40,6 +42,9 @@ pub struct ChunkApplyStatsV0 {
     pub bandwidth_scheduler: BandwidthSchedulerStats,
     /// Balance stats - used in balance checker.
     pub balance: BalanceStats,
+    /// Profiles of the executed receipts, in execution order. Only recorded
+    /// by the nodes with `save_receipt_execution_profiles` enabled.
+    pub receipt_profiles: Vec<ReceiptExecutionProfile>,
 }
 
 impl ChunkApplyStatsV0 {
@@ -53,6 +58,7 @@ impl ChunkApplyStatsV0 {
             bandwidth_scheduler: Default::default(),
             balance: Default::default(),
             receipt_sink: Default::default(),
+            receipt_profiles: vec![],
         }
     }
 
@@ -75,6 +81,7 @@ impl ChunkApplyStatsV0 {
             bandwidth_scheduler: Default::default(),
             balance: Default::default(),
             receipt_sink: Default::default(),
+            receipt_profiles: vec![],
         }
     }
 }
@@ -197,6 +204,27 @@ impl ReceiptsStats {
     }
 }
 
+/// Where the execution of a receipt spent its gas and time.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ReceiptExecutionProfile {
+    pub receipt_id: CryptoHash,
+    pub executor_id: AccountId,
+    pub gas_burnt: Gas,
+    pub compute_usage: Compute,
+    /// Gas spent on the actions, the wasm instructions and the host functions.
+    pub gas_profile: ProfileDataV3,
+    /// Trie nodes read by the contract from the database and from memory.
+    pub trie_node_db_reads: u64,
+    pub trie_node_mem_reads: u64,
+    /// Size of the storage proof recorded while executing the receipt.
+    pub recorded_storage_size: u64,
+    /// Receipts created by the receipt.
+    pub outgoing_receipts: Vec<CryptoHash>,
+    /// Wall clock time of the execution, which unlike the other fields
+    /// differs between nodes.
+    pub execution_time_us: u64,
+}
+
 /// Stats about token balance, used in balance checker.
 #[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
 pub struct BalanceStats {
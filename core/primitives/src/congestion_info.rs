@@ -414,6 +414,17 @@ impl BlockCongestionInfo {
     pub fn is_empty(&self) -> bool {
         self.shards_congestion_info.is_empty()
     }
+
+    /// The highest congestion level among all shards, or 0 if there are none.
+    pub fn max_congestion_level(&self, config: &CongestionControlConfig) -> f64 {
+        self.shards_congestion_info
+            .values()
+            .map(|info| {
+                CongestionControl::new(*config, info.congestion_info, info.missed_chunks_count)
+                    .congestion_level()
+            })
+            .fold(0.0, f64::max)
+    }
 }
 
 /// The extended congestion info contains the congestion info and extra
@@ -829,4 +840,26 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_block_max_congestion_level() {
+        let config = get_config();
+        assert_eq!(BlockCongestionInfo::default().max_congestion_level(&config), 0.0);
+
+        let mut half_congested = CongestionInfo::default();
+        half_congested.add_buffered_receipt_gas(config.max_congestion_outgoing_gas / 2).unwrap();
+
+        let mut block_info = BlockCongestionInfo::default();
+        block_info.insert(ShardId::new(0), ExtendedCongestionInfo::default());
+        block_info.insert(ShardId::new(1), ExtendedCongestionInfo::new(half_congested, 0));
+        assert_eq!(block_info.max_congestion_level(&config), 0.5);
+
+        // Missed chunks contribute to the congestion level, too.
+        let missed_chunks_count = config.max_congestion_missed_chunks;
+        block_info.insert(
+            ShardId::new(2),
+            ExtendedCongestionInfo::new(CongestionInfo::default(), missed_chunks_count),
+        );
+        assert_eq!(block_info.max_congestion_level(&config), 1.0);
+    }
 }
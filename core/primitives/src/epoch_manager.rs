@@ -2,10 +2,12 @@ use crate::num_rational::Rational32;
 use crate::shard_layout::ShardLayout;
 use crate::types::validator_stake::ValidatorStake;
 use crate::types::{
-    AccountId, Balance, BlockChunkValidatorStats, BlockHeightDelta, NumSeats, ProtocolVersion,
-    ValidatorKickoutReason,
+    AccountId, Balance, BlockChunkValidatorStats, BlockHeightDelta, EpochHeight, NumSeats,
+    ProtocolVersion, ValidatorKickoutReason,
 };
+use crate::version::PROTOCOL_VERSION;
 use borsh::{BorshDeserialize, BorshSerialize};
+use itertools::Itertools;
 use near_primitives_core::hash::CryptoHash;
 use near_primitives_core::serialize::dec_format;
 use near_schema_checker_lib::ProtocolSchema;
@@ -242,9 +244,56 @@ impl AllEpochConfig {
         config
     }
 
+    /// The config of the epoch with the given protocol version and height. It
+    /// is the config of the protocol version, with the shard layout of the
+    /// resharding proposal of the protocol version once the epoch height of
+    /// the proposal is reached.
+    pub fn for_epoch(
+        &self,
+        protocol_version: ProtocolVersion,
+        epoch_height: EpochHeight,
+    ) -> EpochConfig {
+        let mut config = self.for_protocol_version(protocol_version);
+        if let Some(proposal) = self.config_store.get_resharding_proposal(protocol_version) {
+            if epoch_height >= proposal.epoch_height {
+                config.shard_layout = proposal.shard_layout.clone();
+            }
+        }
+        config
+    }
+
+    /// The shard layouts that the protocol version may use, the one of its
+    /// config and the one of its resharding proposal if any.
+    pub fn shard_layouts_for_protocol_version(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Vec<ShardLayout> {
+        let mut shard_layouts = vec![self.for_protocol_version(protocol_version).shard_layout];
+        if let Some(proposal) = self.config_store.get_resharding_proposal(protocol_version) {
+            shard_layouts.push(proposal.shard_layout.clone());
+        }
+        shard_layouts
+    }
+
     pub fn chain_id(&self) -> &str {
         &self.chain_id
     }
+
+    /// See [`EpochConfigStore::can_shard_layout_follow`].
+    pub fn can_shard_layout_follow(
+        &self,
+        prev_protocol_version: ProtocolVersion,
+        prev_shard_layout: &ShardLayout,
+        protocol_version: ProtocolVersion,
+        shard_layout: &ShardLayout,
+    ) -> bool {
+        self.config_store.can_shard_layout_follow(
+            prev_protocol_version,
+            prev_shard_layout,
+            protocol_version,
+            shard_layout,
+        )
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, ProtocolSchema)]
@@ -309,10 +358,47 @@ static CONFIGS: &[(&str, ProtocolVersion, &str)] = &[
     include_config!("testnet", 143, "143.json"),
 ];
 
+/// Pairs of consecutive protocol versions of the epoch configs of the binary
+/// whose shard layouts are not checked, see `EpochConfigStore::can_shard_layout_follow`. The nightly
+/// config of protocol version 143 has the shard layout of protocol version 76.
+/// Nightly protocol versions are never enabled on mainnet and testnet, so no
+/// chain goes through this shard layout change.
+const UNCHECKED_SHARD_LAYOUT_TRANSITIONS: &[(ProtocolVersion, ProtocolVersion)] = &[(78, 143)];
+
+/// The shard layout of an epoch config can't follow the shard layout of the
+/// config for the previous protocol version.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "shard layout for protocol version {protocol_version} cannot be derived from the shard layout for protocol version {prev_protocol_version}"
+)]
+pub struct InvalidShardLayoutTransitionError {
+    pub prev_protocol_version: ProtocolVersion,
+    pub protocol_version: ProtocolVersion,
+}
+
+/// A change of the shard layout within a protocol version. From the epoch of
+/// `epoch_height` on, the epochs of the protocol version use `shard_layout`
+/// instead of the shard layout of the config of the protocol version, so that
+/// a shard can be split without a protocol upgrade.
+///
+/// The proposals belong to the epoch config store, not to the chain state, so
+/// that all the nodes must be configured with the same ones. The epoch configs
+/// of the binary have none, they are only set by the tests.
+#[derive(Clone, Eq, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReshardingProposal {
+    /// Height of the first epoch with the new shard layout.
+    pub epoch_height: EpochHeight,
+    /// Derived from the shard layout of the config of the protocol version by
+    /// splitting a single shard.
+    pub shard_layout: ShardLayout,
+}
+
 /// Store for `[EpochConfig]` per protocol version.`
 #[derive(Debug, Clone)]
 pub struct EpochConfigStore {
     store: BTreeMap<ProtocolVersion, Arc<EpochConfig>>,
+    /// At most one resharding proposal per protocol version.
+    resharding_proposals: BTreeMap<ProtocolVersion, ReshardingProposal>,
 }
 
 impl EpochConfigStore {
@@ -323,13 +409,63 @@ impl EpochConfigStore {
         let mut store = Self::load_default_epoch_configs(chain_id);
 
         if !store.is_empty() {
-            return Some(Self { store });
+            return Some(Self { store, resharding_proposals: BTreeMap::new() });
         }
         if let Some(config_dir) = config_dir {
             store = Self::load_epoch_config_from_file_system(config_dir.to_str().unwrap());
         }
 
-        if store.is_empty() { None } else { Some(Self { store }) }
+        if store.is_empty() {
+            None
+        } else {
+            Some(Self { store, resharding_proposals: BTreeMap::new() })
+        }
+    }
+
+    /// Checks that the shard layout of every config can follow the shard layout of
+    /// the config for the previous protocol version, see `ShardLayout::can_follow`.
+    /// This allows several future reshardings to be scheduled in the configs, as long
+    /// as each protocol upgrade splits at most one shard.
+    pub fn validate_shard_layouts(&self) -> Result<(), InvalidShardLayoutTransitionError> {
+        for ((prev_version, prev_config), (version, config)) in self.store.iter().tuple_windows() {
+            if !self.can_shard_layout_follow(
+                *prev_version,
+                &prev_config.shard_layout,
+                *version,
+                &config.shard_layout,
+            ) {
+                return Err(InvalidShardLayoutTransitionError {
+                    prev_protocol_version: *prev_version,
+                    protocol_version: *version,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `shard_layout` of an epoch with `protocol_version` can follow
+    /// `prev_shard_layout` of the previous epoch, see `ShardLayout::can_follow`.
+    /// The transitions between the configs in `UNCHECKED_SHARD_LAYOUT_TRANSITIONS`
+    /// are always allowed.
+    pub fn can_shard_layout_follow(
+        &self,
+        prev_protocol_version: ProtocolVersion,
+        prev_shard_layout: &ShardLayout,
+        protocol_version: ProtocolVersion,
+        shard_layout: &ShardLayout,
+    ) -> bool {
+        if shard_layout.can_follow(prev_shard_layout) {
+            return true;
+        }
+        let config_version = |protocol_version| {
+            self.store.range(..=protocol_version).next_back().map(|(version, _)| *version)
+        };
+        match (config_version(prev_protocol_version), config_version(protocol_version)) {
+            (Some(prev_version), Some(version)) => {
+                UNCHECKED_SHARD_LAYOUT_TRANSITIONS.contains(&(prev_version, version))
+            }
+            _ => false,
+        }
     }
 
     /// Loads the default epoch configs for the given chain from the CONFIGS array.
@@ -386,7 +522,7 @@ impl EpochConfigStore {
     }
 
     pub fn test(store: BTreeMap<ProtocolVersion, Arc<EpochConfig>>) -> Self {
-        Self { store }
+        Self { store, resharding_proposals: BTreeMap::new() }
     }
 
     pub fn test_single_version(
@@ -396,6 +532,65 @@ impl EpochConfigStore {
         Self::test(BTreeMap::from([(protocol_version, Arc::new(epoch_config))]))
     }
 
+    /// Adds the resharding proposal of the protocol version, replacing the
+    /// previous one if any. Panics if the proposal isn't valid, see
+    /// `validate_resharding_proposal`.
+    pub fn with_resharding_proposal(
+        mut self,
+        protocol_version: ProtocolVersion,
+        proposal: ReshardingProposal,
+    ) -> Self {
+        self.resharding_proposals.insert(protocol_version, proposal);
+        self.validate_resharding_proposal(protocol_version);
+        self
+    }
+
+    pub fn get_resharding_proposal(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Option<&ReshardingProposal> {
+        self.resharding_proposals.get(&protocol_version)
+    }
+
+    /// Checks that every shard layout change stays a split of a single shard
+    /// at an epoch boundary, see `ShardLayout::can_follow`, whatever the epoch
+    /// the chain upgrades to the protocol version or to the next one:
+    /// * the proposal splits a single shard of the shard layout of the
+    ///   protocol version,
+    /// * the protocol version keeps the shard layout of the previous one, so
+    ///   the proposal isn't applied along with a resharding of the upgrade,
+    /// * the shard layout of the next protocol version, if supported by this
+    ///   binary, can follow the shard layout of the proposal and the one of
+    ///   the protocol version, i.e. it is the shard layout of the proposal.
+    fn validate_resharding_proposal(&self, protocol_version: ProtocolVersion) {
+        let proposal = &self.resharding_proposals[&protocol_version];
+        let shard_layout = &self.get_config(protocol_version).shard_layout;
+        assert!(
+            &proposal.shard_layout != shard_layout
+                && proposal.shard_layout.can_follow(shard_layout),
+            "Shard layout of the resharding proposal for protocol version {} must split a single shard of the shard layout of the protocol version",
+            protocol_version,
+        );
+        let prev_config = self.store.range(..protocol_version).next_back();
+        if let Some((prev_version, prev_config)) = prev_config {
+            assert_eq!(
+                &prev_config.shard_layout, shard_layout,
+                "Protocol version {} with a resharding proposal must have the shard layout of protocol version {}",
+                protocol_version, prev_version,
+            );
+        }
+        if protocol_version < PROTOCOL_VERSION {
+            let next_shard_layout = &self.get_config(protocol_version + 1).shard_layout;
+            assert!(
+                next_shard_layout.can_follow(&proposal.shard_layout)
+                    && next_shard_layout.can_follow(shard_layout),
+                "Shard layout for protocol version {} must be the shard layout of the resharding proposal for protocol version {}",
+                protocol_version + 1,
+                protocol_version,
+            );
+        }
+    }
+
     /// Returns the EpochConfig for the given protocol version.
     /// This panics if no config is found for the given version, thus the initialization via `for_chain_id` should
     /// only be performed for chains with some configs stored in files.
@@ -440,11 +635,16 @@ impl EpochConfigStore {
 
 #[cfg(test)]
 mod tests {
-    use super::EpochConfigStore;
+    use super::{
+        AllEpochConfig, EpochConfigStore, InvalidShardLayoutTransitionError, ReshardingProposal,
+    };
     use crate::epoch_manager::EpochConfig;
+    use crate::shard_layout::ShardLayout;
     use near_primitives_core::types::ProtocolVersion;
+    use std::collections::BTreeMap;
     use std::fs;
     use std::path::Path;
+    use std::sync::Arc;
 
     #[test]
     fn test_dump_epoch_configs_mainnet() {
@@ -471,6 +671,101 @@ mod tests {
         assert_eq!(epoch_config_55, epoch_config_48);
     }
 
+    #[test]
+    fn test_validate_shard_layouts() {
+        for chain_id in ["mainnet", "testnet"] {
+            let store = EpochConfigStore::for_chain_id(chain_id, None).unwrap();
+            assert_eq!(store.validate_shard_layouts(), Ok(()));
+        }
+
+        let config = parse_config_file("mainnet", 78).unwrap();
+        let mut first_resharding = config.clone();
+        first_resharding.shard_layout =
+            ShardLayout::derive_shard_layout(&config.shard_layout, "zz".parse().unwrap());
+        let mut second_resharding = config.clone();
+        second_resharding.shard_layout =
+            ShardLayout::derive_shard_layout(&first_resharding.shard_layout, "aa".parse().unwrap());
+        let store = EpochConfigStore::test(BTreeMap::from([
+            (78, Arc::new(config.clone())),
+            (80, Arc::new(first_resharding.clone())),
+            (81, Arc::new(second_resharding.clone())),
+        ]));
+        assert_eq!(store.validate_shard_layouts(), Ok(()));
+
+        // Two shards split by a single protocol upgrade.
+        let store = EpochConfigStore::test(BTreeMap::from([
+            (78, Arc::new(config.clone())),
+            (80, Arc::new(second_resharding)),
+        ]));
+        let err = store.validate_shard_layouts().unwrap_err();
+        assert_eq!(
+            err,
+            InvalidShardLayoutTransitionError { prev_protocol_version: 78, protocol_version: 80 }
+        );
+        assert_eq!(
+            err.to_string(),
+            "shard layout for protocol version 80 cannot be derived from the shard layout for protocol version 78"
+        );
+
+        // Shards merged by a protocol upgrade.
+        let store = EpochConfigStore::test(BTreeMap::from([
+            (78, Arc::new(first_resharding)),
+            (80, Arc::new(config)),
+        ]));
+        assert_eq!(
+            store.validate_shard_layouts(),
+            Err(InvalidShardLayoutTransitionError {
+                prev_protocol_version: 78,
+                protocol_version: 80
+            })
+        );
+    }
+
+    #[test]
+    fn test_can_shard_layout_follow() {
+        let store = EpochConfigStore::for_chain_id("mainnet", None).unwrap();
+        let layout_78 = &store.get_config(78).shard_layout;
+        let layout_143 = &store.get_config(143).shard_layout;
+        assert!(!layout_143.can_follow(layout_78));
+        // The epochs of any protocol versions using the exempted configs.
+        assert!(store.can_shard_layout_follow(78, layout_78, 143, layout_143));
+        assert!(store.can_shard_layout_follow(100, layout_78, 150, layout_143));
+        // Other transitions are still checked.
+        assert!(!store.can_shard_layout_follow(76, layout_78, 143, layout_143));
+        assert!(!store.can_shard_layout_follow(78, layout_78, 78, layout_143));
+    }
+
+    #[test]
+    fn test_resharding_proposal() {
+        let config = parse_config_file("mainnet", 78).unwrap();
+        let mut resharding = config.clone();
+        resharding.shard_layout =
+            ShardLayout::derive_shard_layout(&config.shard_layout, "zz".parse().unwrap());
+        let proposal =
+            ReshardingProposal { epoch_height: 10, shard_layout: resharding.shard_layout.clone() };
+        let store = EpochConfigStore::test(BTreeMap::from([
+            (76, Arc::new(config.clone())),
+            (77, Arc::new(config.clone())),
+            (78, Arc::new(resharding.clone())),
+        ]))
+        .with_resharding_proposal(77, proposal);
+        let all_epoch_config = AllEpochConfig::from_epoch_config_store("mainnet", 10, store);
+        assert_eq!(all_epoch_config.for_epoch(77, 9).shard_layout, config.shard_layout);
+        assert_eq!(all_epoch_config.for_epoch(77, 10).shard_layout, resharding.shard_layout);
+        assert_eq!(all_epoch_config.for_epoch(76, 10).shard_layout, config.shard_layout);
+        assert_eq!(all_epoch_config.for_epoch(78, 8).shard_layout, resharding.shard_layout);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the shard layout of the resharding proposal")]
+    fn test_resharding_proposal_reverted_by_next_version() {
+        let config = parse_config_file("mainnet", 78).unwrap();
+        let shard_layout =
+            ShardLayout::derive_shard_layout(&config.shard_layout, "zz".parse().unwrap());
+        EpochConfigStore::test(BTreeMap::from([(77, Arc::new(config))]))
+            .with_resharding_proposal(77, ReshardingProposal { epoch_height: 10, shard_layout });
+    }
+
     fn parse_config_file(chain_id: &str, protocol_version: ProtocolVersion) -> Option<EpochConfig> {
         let path = Path::new(env!("CARGO_MANIFEST_DIR"))
             .join("res/epoch_configs")
@@ -13,6 +13,7 @@ pub mod bandwidth_scheduler;
 pub mod block;
 pub mod block_body;
 pub mod block_header;
+pub mod block_production_timings;
 pub mod challenge;
 pub mod chunk_apply_stats;
 pub mod congestion_info;
@@ -31,6 +32,7 @@ pub mod rand;
 pub mod receipt;
 #[cfg(feature = "solomon")]
 pub mod reed_solomon;
+pub mod replica;
 pub mod sandbox;
 pub mod shard_layout;
 pub mod sharding;
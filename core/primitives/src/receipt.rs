@@ -4,7 +4,7 @@ use crate::hash::CryptoHash;
 use crate::serialize::dec_format;
 use crate::shard_layout::ShardLayout;
 use crate::transaction::{Action, TransferAction};
-use crate::types::{AccountId, Balance, BlockHeight, ShardId};
+use crate::types::{AccountId, Balance, BlockHeight, BlockHeightDelta, ShardId};
 use borsh::{BorshDeserialize, BorshSerialize};
 use itertools::Itertools;
 use near_crypto::{KeyType, PublicKey};
@@ -841,6 +841,21 @@ pub struct PromiseYieldTimeout {
     pub expires_at: BlockHeight,
 }
 
+/// The number of blocks after `expires_at` during which a resume of the yield
+/// is still delivered instead of the timeout.
+pub const PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS: BlockHeightDelta = 1;
+
+impl PromiseYieldTimeout {
+    /// The height of the first block in which the timeout is resolved.
+    pub fn resolve_height(&self, protocol_version: ProtocolVersion) -> BlockHeight {
+        if ProtocolFeature::PromiseYieldTimeoutGraceWindow.enabled(protocol_version) {
+            self.expires_at + PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS
+        } else {
+            self.expires_at
+        }
+    }
+}
+
 /// Stores indices for a persistent queue in the state trie.
 #[derive(Default, BorshSerialize, BorshDeserialize, Clone, PartialEq, Debug, ProtocolSchema)]
 pub struct TrieQueueIndices {
@@ -0,0 +1,34 @@
+use crate::bandwidth_scheduler::BandwidthRequests;
+use crate::congestion_info::CongestionInfo;
+use crate::hash::CryptoHash;
+use crate::receipt::Receipt;
+use crate::transaction::ExecutionOutcomeWithId;
+use crate::types::validator_stake::ValidatorStake;
+use crate::types::{Balance, Gas, RawStateChangesWithTrieKey, ShardId, StateRoot};
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_schema_checker_lib::ProtocolSchema;
+
+/// The result of applying the chunk of a shard at a block, sent by a node to
+/// the replicas which trust the producer of the block. A replica applies the
+/// state changes to its trie instead of executing the chunk.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq, ProtocolSchema)]
+pub struct ReplicaShardStateUpdate {
+    pub shard_id: ShardId,
+    /// Whether the block has a new chunk for the shard. The state of the shard
+    /// may change even without a new chunk.
+    pub is_new_chunk: bool,
+    pub gas_limit: Gas,
+    pub new_root: StateRoot,
+    /// The changes of the state, ordered by the trie key as they are applied
+    /// to the trie.
+    pub state_changes: Vec<RawStateChangesWithTrieKey>,
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    pub outgoing_receipts: Vec<Receipt>,
+    pub validator_proposals: Vec<ValidatorStake>,
+    pub total_gas_burnt: Gas,
+    pub total_balance_burnt: Balance,
+    pub applied_receipts_hash: CryptoHash,
+    pub congestion_info: Option<CongestionInfo>,
+    pub bandwidth_requests: Option<BandwidthRequests>,
+    pub bandwidth_scheduler_state_hash: CryptoHash,
+}
@@ -19,6 +19,8 @@ use near_schema_checker_lib::ProtocolSchema;
 use std::collections::{BTreeMap, BTreeSet};
 use std::{fmt, str};
 
+pub mod boundary_selection;
+
 /// `ShardLayout` has a version number.
 ///
 /// The version number should increment as when sharding changes. This guarantees the version
@@ -733,6 +735,85 @@ impl ShardLayout {
         ShardLayout::v2(boundary_accounts, shard_ids, Some(shards_split_map))
     }
 
+    /// Derives a new shard layout from `base_shard_layout` which splits
+    /// `shard_id` at the boundary account chosen by
+    /// [`boundary_selection::select_boundary_account`], see
+    /// `derive_shard_layout`. Returns `None` if the shard can't be split.
+    pub fn derive_shard_layout_from_usage(
+        &self,
+        shard_id: ShardId,
+        accounts: &BTreeMap<AccountId, boundary_selection::AccountUsageStats>,
+        metric: boundary_selection::SplitMetric,
+    ) -> Option<(ShardLayout, boundary_selection::ShardSplitProposal)> {
+        let proposal =
+            boundary_selection::select_boundary_account(self, shard_id, accounts, metric)?;
+        let shard_layout = Self::derive_shard_layout(self, proposal.boundary_account.clone());
+        Some((shard_layout, proposal))
+    }
+
+    /// Returns true if this shard layout can follow `prev_shard_layout` in the
+    /// next epoch, i.e. it's either the same layout or it is derived from it by
+    /// a supported resharding:
+    /// * V1 layouts were derived from the previous V0 or V1 layout by splitting
+    ///   any number of shards, with the next shard layout version.
+    /// * V2 layouts are derived from the previous V1 or V2 layout by splitting
+    ///   a single shard with `derive_shard_layout`.
+    pub fn can_follow(&self, prev_shard_layout: &ShardLayout) -> bool {
+        if self == prev_shard_layout {
+            return true;
+        }
+        match (prev_shard_layout, self) {
+            (Self::V0(_) | Self::V1(_), Self::V1(_)) => self.is_split_of(prev_shard_layout),
+            (Self::V1(_) | Self::V2(_), Self::V2(_)) => {
+                let prev_boundary_accounts = prev_shard_layout.boundary_accounts();
+                let new_boundary_accounts = self
+                    .boundary_accounts()
+                    .iter()
+                    .filter(|account| !prev_boundary_accounts.contains(account))
+                    .collect_vec();
+                let [new_boundary_account] = new_boundary_accounts.as_slice() else {
+                    return false;
+                };
+                let derived_shard_layout = ShardLayout::derive_shard_layout(
+                    prev_shard_layout,
+                    (*new_boundary_account).clone(),
+                );
+                &derived_shard_layout == self
+            }
+            (_, Self::V0(_)) | (Self::V2(_), Self::V1(_)) | (Self::V0(_), Self::V2(_)) => false,
+        }
+    }
+
+    /// Returns true if this V1 shard layout splits the shards of the previous
+    /// V0 or V1 shard layout: it has the next shard layout version, keeps the
+    /// previous boundary accounts and every previous shard is the parent of
+    /// at least one of its shards.
+    fn is_split_of(&self, prev_shard_layout: &ShardLayout) -> bool {
+        if self.version() != prev_shard_layout.version() + 1 {
+            return false;
+        }
+        let prev_boundary_accounts: &[AccountId] = match prev_shard_layout {
+            Self::V0(_) => &[],
+            _ => prev_shard_layout.boundary_accounts(),
+        };
+        let boundary_accounts = self.boundary_accounts();
+        if !prev_boundary_accounts.iter().all(|account| boundary_accounts.contains(account)) {
+            return false;
+        }
+        let prev_shard_ids: BTreeSet<ShardId> = prev_shard_layout.shard_ids().collect();
+        let mut parent_shard_ids = BTreeSet::new();
+        for shard_id in self.shard_ids() {
+            let Ok(parent_shard_id) = self.get_parent_shard_id(shard_id) else {
+                return false;
+            };
+            if !prev_shard_ids.contains(&parent_shard_id) {
+                return false;
+            }
+            parent_shard_ids.insert(parent_shard_id);
+        }
+        parent_shard_ids == prev_shard_ids
+    }
+
     #[inline]
     pub fn version(&self) -> ShardVersion {
         match self {
@@ -847,6 +928,63 @@ impl ShardLayout {
     }
 }
 
+/// The shards a shard descends from and the shards it is split into, across a
+/// sequence of shard layouts. The shard layouts in which the shards keep the
+/// same shard uids as in the previous shard layout aren't listed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ShardLineage {
+    pub shard_uid: ShardUId,
+    /// The shards of the earlier shard layouts that the shard descends from,
+    /// its parent first.
+    pub ancestors: Vec<ShardUId>,
+    /// The shards of the later shard layouts descending from the shard, the
+    /// children of the shard first.
+    pub descendants: Vec<Vec<ShardUId>>,
+}
+
+impl ShardLineage {
+    /// The lineage of the shard of `shard_layouts[index]`. The shard layouts
+    /// are ordered from the oldest, and each of them but the first must follow
+    /// the previous one and know how its shards were split from it.
+    pub fn new(
+        shard_uid: ShardUId,
+        shard_layouts: &[ShardLayout],
+        index: usize,
+    ) -> Result<Self, ShardLayoutError> {
+        let mut ancestors = vec![];
+        let mut shard_id = shard_uid.shard_id();
+        for index in (1..=index).rev() {
+            let parent_shard_id = shard_layouts[index]
+                .try_get_parent_shard_id(shard_id)?
+                .ok_or(ShardLayoutError::NoParentError { shard_id })?;
+            let parent_shard_uid =
+                ShardUId::new(shard_layouts[index - 1].version(), parent_shard_id);
+            if parent_shard_uid != ancestors.last().copied().unwrap_or(shard_uid) {
+                ancestors.push(parent_shard_uid);
+            }
+            shard_id = parent_shard_id;
+        }
+
+        let mut descendants: Vec<Vec<ShardUId>> = vec![];
+        let mut shard_uids = vec![shard_uid];
+        for shard_layout in &shard_layouts[index + 1..] {
+            let mut children_shard_uids = vec![];
+            for parent_shard_uid in &shard_uids {
+                let parent_shard_id = parent_shard_uid.shard_id();
+                let children = shard_layout
+                    .get_children_shards_uids(parent_shard_id)
+                    .ok_or(ShardLayoutError::NoParentError { shard_id: parent_shard_id })?;
+                children_shard_uids.extend(children);
+            }
+            if children_shard_uids != shard_uids {
+                descendants.push(children_shard_uids.clone());
+            }
+            shard_uids = children_shard_uids;
+        }
+        Ok(Self { shard_uid, ancestors, descendants })
+    }
+}
+
 // Validates the shards_split_map and derives the shards_parent_map from it.
 fn validate_and_derive_shard_parent_map_v2(
     shard_ids: &Vec<ShardId>,
@@ -1111,7 +1249,7 @@ impl ShardInfo {
 mod tests {
     use crate::epoch_manager::EpochConfigStore;
     use crate::shard_layout::{
-        ShardLayout, ShardLayoutV1, ShardUId, new_shard_ids_vec, new_shards_split_map,
+        ShardLayout, ShardLayoutV1, ShardLineage, ShardUId, new_shard_ids_vec, new_shards_split_map,
     };
     use itertools::Itertools;
     use near_primitives_core::types::ProtocolVersion;
@@ -1786,6 +1924,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_shard_layout_can_follow() {
+        let base_layout = ShardLayout::multi_shard_custom(parse_account_ids(&["b", "d"]), 3);
+        let one_split = ShardLayout::derive_shard_layout(&base_layout, "c".parse().unwrap());
+        let two_splits = ShardLayout::derive_shard_layout(&one_split, "a".parse().unwrap());
+
+        assert!(base_layout.can_follow(&base_layout));
+        assert!(one_split.can_follow(&base_layout));
+        assert!(two_splits.can_follow(&one_split));
+
+        // More than one split in a single resharding.
+        assert!(!two_splits.can_follow(&base_layout));
+        // Shards can't be merged.
+        assert!(!base_layout.can_follow(&one_split));
+        // The boundary account is the same, but the shard ids don't match the split.
+        let other_ids = ShardLayout::multi_shard_custom(parse_account_ids(&["b", "c", "d"]), 3);
+        assert!(!other_ids.can_follow(&base_layout));
+
+        #[allow(deprecated)]
+        let v0 = ShardLayout::v0(1, 0);
+        let v1 = ShardLayout::get_simple_nightshade_layout();
+        let v2 = ShardLayout::get_simple_nightshade_layout_v2();
+        let v3 = ShardLayout::get_simple_nightshade_layout_v3();
+        assert!(v1.can_follow(&v0));
+        assert!(v2.can_follow(&v1));
+        assert!(v3.can_follow(&v2));
+
+        // Every V1 resharding bumps the shard layout version.
+        assert!(!v3.can_follow(&v1));
+        // Shards can't be merged back into a V0 or V1 layout.
+        assert!(!v0.can_follow(&v1));
+        assert!(!v2.can_follow(&v3));
+        assert!(!v1.can_follow(&base_layout));
+    }
+
+    #[test]
+    fn test_shard_lineage() {
+        let base_layout = ShardLayout::multi_shard_custom(parse_account_ids(&["b", "d"]), 3);
+        let one_split = ShardLayout::derive_shard_layout(&base_layout, "c".parse().unwrap());
+        let two_splits = ShardLayout::derive_shard_layout(&one_split, "cc".parse().unwrap());
+        let shard_layouts = [base_layout.clone(), one_split.clone(), two_splits.clone()];
+        let shard_uid_of = |shard_layout: &ShardLayout, account: &str| {
+            shard_layout.account_id_to_shard_uid(&account.parse().unwrap())
+        };
+
+        // The shard holding "c" in the base layout is split twice.
+        let parent = shard_uid_of(&base_layout, "c");
+        let lineage = ShardLineage::new(parent, &shard_layouts, 0).unwrap();
+        assert_eq!(lineage.ancestors, vec![]);
+        assert_eq!(
+            lineage.descendants,
+            vec![
+                vec![shard_uid_of(&one_split, "b"), shard_uid_of(&one_split, "c")],
+                vec![
+                    shard_uid_of(&two_splits, "b"),
+                    shard_uid_of(&two_splits, "c"),
+                    shard_uid_of(&two_splits, "cc"),
+                ],
+            ]
+        );
+
+        // The grandchild holding "cc" descends from the child holding "c" and
+        // from the original shard.
+        let grandchild = shard_uid_of(&two_splits, "cc");
+        let lineage = ShardLineage::new(grandchild, &shard_layouts, 2).unwrap();
+        assert_eq!(lineage.ancestors, vec![shard_uid_of(&one_split, "c"), parent]);
+        assert_eq!(lineage.descendants, Vec::<Vec<ShardUId>>::new());
+
+        // A shard which is never split has no lineage.
+        let untouched = shard_uid_of(&one_split, "a");
+        let lineage = ShardLineage::new(untouched, &shard_layouts, 1).unwrap();
+        assert_eq!(lineage.ancestors, vec![]);
+        assert_eq!(lineage.descendants, Vec::<Vec<ShardUId>>::new());
+
+        // The shard layouts which don't know how they were split from the
+        // previous one break the lineage.
+        let shard_layouts = [base_layout.clone(), base_layout.clone()];
+        assert!(ShardLineage::new(parent, &shard_layouts, 0).is_err());
+        assert!(ShardLineage::new(parent, &shard_layouts, 1).is_err());
+    }
+
     // Check that the ShardLayout::multi_shard method returns interesting shard
     // layouts. A shard layout is interesting if it has non-contiguous shard
     // ids.
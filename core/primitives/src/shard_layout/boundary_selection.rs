@@ -0,0 +1,243 @@
+//! Selection of the boundary account of a shard split from the usage of the
+//! accounts of the shard.
+//!
+//! `ShardLayout::derive_shard_layout` needs the boundary account of the new
+//! split to be chosen by hand. The functions here choose it instead from the
+//! [`AccountUsageStats`] collected by the nodes in `DBCol::AccountUsageStats`,
+//! so that the two children of the split shard get about the same load.
+
+use super::ShardLayout;
+use crate::types::{AccountId, StorageUsage};
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives_core::types::ShardId;
+use std::collections::BTreeMap;
+
+/// Usage of an account, as collected in `DBCol::AccountUsageStats`.
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+)]
+pub struct AccountUsageStats {
+    /// Storage usage of the account as of the last chunk which changed it.
+    pub storage_usage: StorageUsage,
+    /// Gas burnt by the transactions and receipts executed by the account
+    /// since the stats are collected. `Gas` may overflow when summed over
+    /// many epochs, hence the `u128`.
+    pub gas_burnt: u128,
+}
+
+/// The load which the split of a shard should halve.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitMetric {
+    /// The storage usage of the accounts.
+    StateSize,
+    /// The gas burnt by the accounts.
+    GasUsage,
+    /// The share of the storage usage of the shard plus the share of the gas
+    /// burnt in the shard of each account, so that both count equally.
+    Combined,
+}
+
+/// The accounts on one side of a proposed split.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShardSplitSide {
+    pub num_accounts: u64,
+    pub storage_usage: u128,
+    pub gas_burnt: u128,
+}
+
+impl ShardSplitSide {
+    fn add(&mut self, stats: &AccountUsageStats) {
+        self.num_accounts += 1;
+        self.storage_usage = self.storage_usage.saturating_add(stats.storage_usage.into());
+        self.gas_burnt = self.gas_burnt.saturating_add(stats.gas_burnt);
+    }
+}
+
+/// A split of a shard at `boundary_account`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ShardSplitProposal {
+    pub shard_id: ShardId,
+    pub metric: SplitMetric,
+    pub boundary_account: AccountId,
+    /// Accounts lower than the boundary account.
+    pub left: ShardSplitSide,
+    /// The boundary account and the accounts greater than it.
+    pub right: ShardSplitSide,
+}
+
+/// Finds the boundary account which splits the accounts of `shard_id` into
+/// two halves of the closest load by `metric`. The accounts of `accounts`
+/// which don't belong to the shard are ignored.
+///
+/// Returns `None` if the shard has fewer than two accounts or no load.
+pub fn select_boundary_account(
+    shard_layout: &ShardLayout,
+    shard_id: ShardId,
+    accounts: &BTreeMap<AccountId, AccountUsageStats>,
+    metric: SplitMetric,
+) -> Option<ShardSplitProposal> {
+    let shard_accounts: Vec<_> = accounts
+        .iter()
+        .filter(|(account_id, _)| shard_layout.account_id_to_shard_id(account_id) == shard_id)
+        .collect();
+    if shard_accounts.len() < 2 {
+        return None;
+    }
+
+    let mut total = ShardSplitSide::default();
+    for (_, stats) in &shard_accounts {
+        total.add(stats);
+    }
+    let load = |stats: &AccountUsageStats| -> u128 {
+        let storage_usage = u128::from(stats.storage_usage);
+        match metric {
+            SplitMetric::StateSize => storage_usage,
+            SplitMetric::GasUsage => stats.gas_burnt,
+            // Both shares are scaled by `total.storage_usage * total.gas_burnt`
+            // to stay in integers.
+            SplitMetric::Combined => match (total.storage_usage, total.gas_burnt) {
+                (0, _) => stats.gas_burnt,
+                (_, 0) => storage_usage,
+                (total_storage_usage, total_gas_burnt) => storage_usage
+                    .saturating_mul(total_gas_burnt)
+                    .saturating_add(stats.gas_burnt.saturating_mul(total_storage_usage)),
+            },
+        }
+    };
+    let total_load =
+        shard_accounts.iter().fold(0u128, |sum, (_, stats)| sum.saturating_add(load(stats)));
+    if total_load == 0 {
+        return None;
+    }
+
+    // The first account of the shard can't be the boundary, otherwise the
+    // left child would be empty.
+    let mut left_load = load(shard_accounts[0].1);
+    let mut best: Option<(u128, usize)> = None;
+    for (index, (_, stats)) in shard_accounts.iter().enumerate().skip(1) {
+        let difference = left_load.abs_diff(total_load - left_load);
+        if best.is_none_or(|(best_difference, _)| difference < best_difference) {
+            best = Some((difference, index));
+        }
+        left_load = left_load.saturating_add(load(stats));
+    }
+    let (_, boundary_index) = best?;
+
+    let mut left = ShardSplitSide::default();
+    let mut right = ShardSplitSide::default();
+    for (index, (_, stats)) in shard_accounts.iter().enumerate() {
+        if index < boundary_index { left.add(stats) } else { right.add(stats) }
+    }
+    Some(ShardSplitProposal {
+        shard_id,
+        metric,
+        boundary_account: shard_accounts[boundary_index].0.clone(),
+        left,
+        right,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountUsageStats, SplitMetric, select_boundary_account};
+    use crate::shard_layout::ShardLayout;
+    use crate::types::AccountId;
+    use near_primitives_core::types::ShardId;
+    use std::collections::BTreeMap;
+
+    fn accounts(usage: &[(&str, u64, u128)]) -> BTreeMap<AccountId, AccountUsageStats> {
+        usage
+            .iter()
+            .map(|(account_id, storage_usage, gas_burnt)| {
+                let stats =
+                    AccountUsageStats { storage_usage: *storage_usage, gas_burnt: *gas_burnt };
+                (account_id.parse().unwrap(), stats)
+            })
+            .collect()
+    }
+
+    fn boundary(
+        shard_layout: &ShardLayout,
+        accounts: &BTreeMap<AccountId, AccountUsageStats>,
+        metric: SplitMetric,
+    ) -> Option<String> {
+        let shard_id = shard_layout.shard_ids().next().unwrap();
+        select_boundary_account(shard_layout, shard_id, accounts, metric)
+            .map(|proposal| proposal.boundary_account.to_string())
+    }
+
+    #[test]
+    fn test_no_split_without_load() {
+        let shard_layout = ShardLayout::single_shard();
+        assert_eq!(boundary(&shard_layout, &accounts(&[]), SplitMetric::Combined), None);
+        let one_account = accounts(&[("a.near", 100, 100)]);
+        assert_eq!(boundary(&shard_layout, &one_account, SplitMetric::Combined), None);
+        let idle_accounts = accounts(&[("a.near", 0, 0), ("b.near", 0, 0)]);
+        assert_eq!(boundary(&shard_layout, &idle_accounts, SplitMetric::Combined), None);
+    }
+
+    #[test]
+    fn test_split_by_metric() {
+        let shard_layout = ShardLayout::single_shard();
+        // The state is in the first accounts and the gas is burnt by the last ones.
+        let accounts = accounts(&[
+            ("a.near", 100, 0),
+            ("b.near", 100, 0),
+            ("c.near", 0, 10),
+            ("d.near", 0, 10),
+            ("e.near", 0, 10),
+            ("f.near", 0, 10),
+        ]);
+        assert_eq!(
+            boundary(&shard_layout, &accounts, SplitMetric::StateSize),
+            Some("b.near".to_string())
+        );
+        assert_eq!(
+            boundary(&shard_layout, &accounts, SplitMetric::GasUsage),
+            Some("e.near".to_string())
+        );
+        // Half of the load is the state of a and b, the other half the gas of the rest.
+        assert_eq!(
+            boundary(&shard_layout, &accounts, SplitMetric::Combined),
+            Some("c.near".to_string())
+        );
+    }
+
+    #[test]
+    fn test_split_ignores_other_shards() {
+        let shard_layout = ShardLayout::v2(
+            vec!["m.near".parse().unwrap()],
+            vec![ShardId::new(0), ShardId::new(1)],
+            None,
+        );
+        let accounts =
+            accounts(&[("a.near", 0, 1), ("b.near", 0, 1), ("x.near", 0, 100), ("y.near", 0, 1)]);
+        let shard_id = shard_layout.account_id_to_shard_id(&"x.near".parse().unwrap());
+        let proposal =
+            select_boundary_account(&shard_layout, shard_id, &accounts, SplitMetric::GasUsage)
+                .unwrap();
+        assert_eq!(proposal.boundary_account.as_str(), "y.near");
+        assert_eq!(proposal.left.num_accounts, 1);
+        assert_eq!(proposal.left.gas_burnt, 100);
+        assert_eq!(proposal.right.num_accounts, 1);
+        assert_eq!(proposal.right.gas_burnt, 1);
+
+        let (new_shard_layout, proposal) = shard_layout
+            .derive_shard_layout_from_usage(shard_id, &accounts, SplitMetric::GasUsage)
+            .unwrap();
+        assert_eq!(proposal.boundary_account.as_str(), "y.near");
+        assert_eq!(new_shard_layout.num_shards(), 3);
+        let child_shard_id = new_shard_layout.account_id_to_shard_id(&"y.near".parse().unwrap());
+        assert_eq!(new_shard_layout.get_parent_shard_id(child_shard_id).unwrap(), shard_id);
+    }
+}
@@ -164,7 +164,7 @@ impl StateChangesKinds {
 }
 
 /// A structure used to index state changes due to transaction/receipt processing and other things.
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, ProtocolSchema)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq, ProtocolSchema)]
 pub enum StateChangeCause {
     /// A type of update that does not get finalized. Used for verification and execution of
     /// immutable smart contract methods. Attempt fo finalize a `TrieUpdate` containing such
@@ -201,14 +201,14 @@ pub enum StateChangeCause {
 }
 
 /// This represents the committed changes in the Trie with a change cause.
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, ProtocolSchema)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq, ProtocolSchema)]
 pub struct RawStateChange {
     pub cause: StateChangeCause,
     pub data: Option<Vec<u8>>,
 }
 
 /// List of committed changes with a cause for a given TrieKey
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, ProtocolSchema)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq, ProtocolSchema)]
 pub struct RawStateChangesWithTrieKey {
     pub trie_key: TrieKey,
     pub changes: Vec<RawStateChange>,
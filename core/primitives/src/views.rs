@@ -23,6 +23,7 @@ use crate::receipt::{
     ReceiptEnum, ReceiptV1,
 };
 use crate::serialize::dec_format;
+use crate::shard_layout::{ShardLayout, ShardUId};
 use crate::sharding::shard_chunk_header_inner::ShardChunkHeaderInnerV4;
 use crate::sharding::{
     ChunkHash, ShardChunk, ShardChunkHeader, ShardChunkHeaderInner, ShardChunkHeaderInnerV2,
@@ -609,6 +610,31 @@ pub struct BlockProcessingInfo {
     pub chunks_info: Vec<Option<ChunkProcessingInfo>>,
 }
 
+/// Accounts which used the most resources of a shard in an epoch.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ShardHotspotsView {
+    pub epoch_id: EpochId,
+    pub shard_id: ShardId,
+    /// Number of the chunks of the shard applied by the node in the epoch.
+    pub num_chunks: u64,
+    pub total_gas_burnt: Gas,
+    pub total_bytes_written: u64,
+    /// Accounts ordered by the gas burnt, high to low.
+    pub top_gas_burnt: Vec<AccountUsageView>,
+    /// Accounts ordered by the bytes written to the state, high to low.
+    pub top_bytes_written: Vec<AccountUsageView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct AccountUsageView {
+    pub account_id: AccountId,
+    /// Gas burnt by the transactions signed by the account and the receipts
+    /// executed on it.
+    pub gas_burnt: Gas,
+    /// Size of the keys and the values written to the state of the account.
+    pub bytes_written: u64,
+}
+
 #[derive(
     BorshSerialize,
     BorshDeserialize,
@@ -2460,6 +2486,149 @@ pub struct SplitStorageInfoView {
     pub hot_db_kind: Option<String>,
 }
 
+/// Shard layout of the epoch of a block.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShardLayoutAtBlockView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub epoch_id: EpochId,
+    pub shard_layout: ShardLayout,
+    /// The shard of the requested account in `shard_layout`, if an account
+    /// was requested.
+    pub account_shard: Option<AccountShardView>,
+}
+
+/// The shard an account belonged to at some block.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountShardView {
+    pub account_id: AccountId,
+    pub shard_id: ShardId,
+    pub shard_uid: ShardUId,
+    /// The shard whose State the trie of `shard_uid` is read from. After a
+    /// resharding the child shards keep reading the State of their ancestor,
+    /// otherwise it is `shard_uid` itself.
+    pub state_shard_uid: ShardUId,
+}
+
+/// Chunk producer assignments of the epoch after the epoch of a block.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NextEpochShardAssignmentsView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub epoch_id: EpochId,
+    pub next_epoch_id: EpochId,
+    pub next_shard_layout: ShardLayout,
+    /// The chunk producers of each shard of `next_shard_layout`.
+    pub chunk_producers: Vec<ShardChunkProducersView>,
+    /// The validators of the next epoch. Chunk validators are sampled from
+    /// them for every height and shard, and as they validate state witnesses
+    /// they don't need the state of the shard.
+    pub chunk_validators: Vec<AccountId>,
+    /// The assignment of the requested account, if an account was requested.
+    pub account_assignment: Option<AccountShardAssignmentView>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShardChunkProducersView {
+    pub shard_id: ShardId,
+    pub chunk_producers: Vec<AccountId>,
+}
+
+/// The shards an account produces chunks for in the next epoch.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountShardAssignmentView {
+    pub account_id: AccountId,
+    pub shard_ids: Vec<ShardId>,
+    /// The shards of `shard_ids` whose state the account doesn't have in the
+    /// current epoch. Neither the shard nor, after a resharding, its parent is
+    /// assigned to it now, so the state has to be downloaded before the epoch
+    /// switch.
+    pub shards_to_download: Vec<ShardId>,
+}
+
+/// The endorsements of a chunk as seen by a node, to find out why a chunk
+/// wasn't endorsed. All timestamps are unix timestamps in nanoseconds, as
+/// observed by the node.
+///
+/// This structure is used only for debugging, fields might be added or removed at any time.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEndorsementsView {
+    pub shard_id: ShardId,
+    pub height_created: BlockHeight,
+    /// The chunk which the node, as a block producer, collected the
+    /// endorsements of. None if it didn't try to include the chunk.
+    pub chunk_hash: Option<ChunkHash>,
+    /// Time at which the node first collected the endorsements of the chunk.
+    pub first_collected_at: Option<u64>,
+    /// Time at which the endorsements of the chunk reached the required stake.
+    pub endorsed_at: Option<u64>,
+    /// The endorsements received from the chunk validators.
+    pub received: Vec<ReceivedChunkEndorsementView>,
+    /// The chunk validators which hadn't endorsed `chunk_hash` when the node
+    /// last collected the endorsements. Only known if the node collected them.
+    pub missing: Vec<AccountId>,
+    /// Time at which the node, as a chunk validator, sent its endorsement of
+    /// the chunk to the block producers.
+    pub sent_at: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedChunkEndorsementView {
+    pub account_id: AccountId,
+    /// The chunk endorsed by the chunk validator. It differs from the chunk
+    /// collected by the block producer if the chunk producer equivocated.
+    pub chunk_hash: ChunkHash,
+    pub received_at: u64,
+}
+
+/// The execution profile of a receipt, recorded by the nodes with
+/// `save_receipt_execution_profiles` enabled.
+///
+/// This structure is used only for debugging, fields might be added or removed at any time.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptExecutionProfileView {
+    pub receipt_id: CryptoHash,
+    pub executor_id: AccountId,
+    /// The block in which the receipt was executed.
+    pub block_hash: CryptoHash,
+    pub shard_id: ShardId,
+    pub gas_burnt: Gas,
+    pub compute_usage: u64,
+    /// Gas spent on the actions, the wasm instructions and the host functions.
+    pub gas_profile: Vec<CostGasUsed>,
+    pub trie_node_db_reads: u64,
+    pub trie_node_mem_reads: u64,
+    pub recorded_storage_size: u64,
+    pub outgoing_receipts: Vec<CryptoHash>,
+    /// Wall clock time of the execution on the node, in microseconds.
+    pub execution_time_us: u64,
+}
+
+impl ReceiptExecutionProfileView {
+    pub fn new(
+        profile: crate::chunk_apply_stats::ReceiptExecutionProfile,
+        block_hash: CryptoHash,
+        shard_id: ShardId,
+    ) -> Self {
+        let metadata =
+            ExecutionMetadataView::from(ExecutionMetadata::V3(Box::new(profile.gas_profile)));
+        Self {
+            receipt_id: profile.receipt_id,
+            executor_id: profile.executor_id,
+            block_hash,
+            shard_id,
+            gas_burnt: profile.gas_burnt,
+            compute_usage: profile.compute_usage,
+            gas_profile: metadata.gas_profile.unwrap_or_default(),
+            trie_node_db_reads: profile.trie_node_db_reads,
+            trie_node_mem_reads: profile.trie_node_mem_reads,
+            recorded_storage_size: profile.recorded_storage_size,
+            outgoing_receipts: profile.outgoing_receipts,
+            execution_time_us: profile.execution_time_us,
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct CongestionInfoView {
     #[serde(with = "dec_format")]
@@ -8,7 +8,7 @@ use near_primitives::state::FlatStateValue;
 use crate::flat::delta::{BlockWithChangesInfo, KeyForFlatStateDelta};
 use crate::flat::{
     FlatStateChanges, FlatStateDelta, FlatStateDeltaMetadata, FlatStateIterator, FlatStorageError,
-    FlatStorageReadyStatus, FlatStorageStatus,
+    FlatStorageReadyStatus, FlatStorageReshardingProgress, FlatStorageStatus,
 };
 use crate::{DBCol, Store, StoreUpdate};
 
@@ -66,6 +66,19 @@ impl FlatStoreAdapter {
             })
     }
 
+    pub fn get_resharding_progress(
+        &self,
+        shard_uid: ShardUId,
+    ) -> Result<Option<FlatStorageReshardingProgress>, FlatStorageError> {
+        self.store.get_ser(DBCol::FlatStorageReshardingProgress, &shard_uid.to_bytes()).map_err(
+            |err| {
+                FlatStorageError::StorageInternalError(format!(
+                    "failed to read flat storage resharding progress: {err}"
+                ))
+            },
+        )
+    }
+
     pub fn get_delta(
         &self,
         shard_uid: ShardUId,
@@ -248,6 +261,20 @@ impl<'a> FlatStoreUpdateAdapter<'a> {
         self.store_update.delete(DBCol::FlatStorageStatus, &shard_uid.to_bytes());
     }
 
+    pub fn set_resharding_progress(
+        &mut self,
+        shard_uid: ShardUId,
+        progress: &FlatStorageReshardingProgress,
+    ) {
+        self.store_update
+            .set_ser(DBCol::FlatStorageReshardingProgress, &shard_uid.to_bytes(), progress)
+            .expect("Borsh should not have failed here")
+    }
+
+    pub fn remove_resharding_progress(&mut self, shard_uid: ShardUId) {
+        self.store_update.delete(DBCol::FlatStorageReshardingProgress, &shard_uid.to_bytes());
+    }
+
     pub fn set_delta(&mut self, shard_uid: ShardUId, delta: &FlatStateDelta) {
         let key =
             KeyForFlatStateDelta { shard_uid, block_hash: delta.metadata.block.hash }.to_bytes();
@@ -270,11 +297,13 @@ impl<'a> FlatStoreUpdateAdapter<'a> {
         self.remove_range_by_shard_uid(shard_uid, DBCol::FlatStateDeltaMetadata);
     }
 
-    /// Removes flat storage in its entirety for a shard: deltas, values and status.
+    /// Removes flat storage in its entirety for a shard: deltas, values, status and resharding
+    /// progress.
     pub fn remove_flat_storage(&mut self, shard_uid: ShardUId) {
         self.remove_all_deltas(shard_uid);
         self.remove_all_values(shard_uid);
         self.remove_status(shard_uid);
+        self.remove_resharding_progress(shard_uid);
     }
 
     // helper
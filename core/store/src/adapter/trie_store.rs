@@ -102,6 +102,7 @@ impl<'a> TrieStoreUpdateAdapter<'a> {
         get_key_from_shard_uid_and_hash(&self.store_update.store, shard_uid, hash)
     }
 
+    #[track_caller]
     pub fn decrement_refcount_by(
         &mut self,
         shard_uid: ShardUId,
@@ -112,6 +113,7 @@ impl<'a> TrieStoreUpdateAdapter<'a> {
         self.store_update.decrement_refcount_by(DBCol::State, key.as_ref(), decrement);
     }
 
+    #[track_caller]
     pub fn decrement_refcount(&mut self, shard_uid: ShardUId, hash: &CryptoHash) {
         let key = self.get_key_from_shard_uid_and_hash(shard_uid, hash);
         self.store_update.decrement_refcount(DBCol::State, key.as_ref());
@@ -128,6 +130,11 @@ impl<'a> TrieStoreUpdateAdapter<'a> {
         self.store_update.increment_refcount_by(DBCol::State, key.as_ref(), data, increment);
     }
 
+    /// See [`StoreUpdate::set_refcount_decrement_block_hash`].
+    pub fn set_refcount_decrement_block_hash(&mut self, block_hash: Option<CryptoHash>) {
+        self.store_update.set_refcount_decrement_block_hash(block_hash);
+    }
+
     pub fn set_state_snapshot_hash(&mut self, hash: Option<CryptoHash>) {
         let key = STATE_SNAPSHOT_KEY;
         match hash {
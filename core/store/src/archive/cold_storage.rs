@@ -6,9 +6,9 @@ use crate::{DBCol, DBTransaction, Database, Store, TrieChanges, metrics};
 use borsh::BorshDeserialize;
 use near_primitives::block::{Block, BlockHeader, Tip};
 use near_primitives::hash::CryptoHash;
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{ShardLayout, ShardUId};
 use near_primitives::sharding::ShardChunk;
-use near_primitives::types::BlockHeight;
+use near_primitives::types::{BlockHeight, ShardId};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::collections::HashMap;
 use std::io;
@@ -360,6 +360,178 @@ pub fn copy_all_data_to_cold(
     Ok(CopyAllDataToColdStatus::EverythingCopied)
 }
 
+/// Whether the column is keyed by a shard next to a block hash. The other
+/// columns holding shard data, State and Chunks, are handled separately.
+fn is_keyed_by_shard(col: DBCol) -> bool {
+    matches!(
+        col.key_type(),
+        [DBKeyType::BlockHash, DBKeyType::ShardUId]
+            | [DBKeyType::BlockHash, DBKeyType::ShardId]
+            | [DBKeyType::ShardId, DBKeyType::BlockHash]
+    )
+}
+
+/// Returns the shard layout in which the shard ids stored next to block
+/// `block_hash` are defined: the layout of the epoch of the block, found by
+/// its previous block.
+fn shard_layout_of_block(
+    hot_store: &Store,
+    block_hash: &[u8],
+    shard_layout_after: &dyn Fn(&CryptoHash) -> io::Result<ShardLayout>,
+) -> io::Result<ShardLayout> {
+    let header: BlockHeader = hot_store.get_ser_or_err_for_cold(DBCol::BlockHeader, block_hash)?;
+    shard_layout_after(header.prev_hash())
+}
+
+/// Returns whether the `key` of a column keyed by shard (see
+/// `is_keyed_by_shard`) belongs to one of the shards `shard_uids`. The shard
+/// ids are resolved to the full `ShardUId` with the shard layout of the block,
+/// as the same shard id may identify different shards in different layouts.
+fn is_shard_key(
+    hot_store: &Store,
+    col: DBCol,
+    key: &[u8],
+    shard_uids: &[ShardUId],
+    shard_layout_after: &dyn Fn(&CryptoHash) -> io::Result<ShardLayout>,
+) -> io::Result<bool> {
+    let (block_hash, shard_id) = match col.key_type() {
+        [DBKeyType::BlockHash, DBKeyType::ShardUId] => {
+            return Ok(shard_uids.iter().any(|shard_uid| key.ends_with(&shard_uid.to_bytes())));
+        }
+        [DBKeyType::BlockHash, DBKeyType::ShardId] => {
+            let (block_hash, shard_id) = key.split_at(CryptoHash::LENGTH);
+            (block_hash, shard_id)
+        }
+        [DBKeyType::ShardId, DBKeyType::BlockHash] => {
+            let (shard_id, block_hash) = key.split_at(key.len() - CryptoHash::LENGTH);
+            (block_hash, shard_id)
+        }
+        _ => return Ok(false),
+    };
+    let shard_id = ShardId::try_from_slice(shard_id)?;
+    let shard_layout = shard_layout_of_block(hot_store, block_hash, shard_layout_after)?;
+    let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+    Ok(shard_uids.contains(&shard_uid))
+}
+
+/// Calls `callback` with the data of the shards `shard_uids` in the cold
+/// columns of `hot_store`:
+/// - the State stored under the shard prefix, which includes the State of the
+///   children mapped to it,
+/// - the chunks of the shard and the block data keyed by the shard,
+/// - the whole `StateShardUIdMapping` column, so that the State of the
+///   children can be found under the parent prefix.
+///
+/// `shard_layout_after` returns the shard layout of the epoch of the blocks
+/// following the given block, and is used to tell which shards the chunks and
+/// the data keyed by shard id belong to.
+///
+/// Stops early and returns false if `callback` returns false.
+fn for_each_shards_data(
+    hot_store: &Store,
+    shard_uids: &[ShardUId],
+    shard_layout_after: &dyn Fn(&CryptoHash) -> io::Result<ShardLayout>,
+    mut callback: impl FnMut(DBCol, &[u8], &[u8]) -> io::Result<bool>,
+) -> io::Result<bool> {
+    for shard_uid in shard_uids {
+        for result in hot_store.storage.iter_prefix(DBCol::State, &shard_uid.to_bytes()) {
+            let (key, value) = result?;
+            if !callback(DBCol::State, &key, &value)? {
+                return Ok(false);
+            }
+        }
+    }
+    for col in DBCol::iter() {
+        if !col.is_cold() || (col != DBCol::Chunks && !is_keyed_by_shard(col)) {
+            continue;
+        }
+        for result in hot_store.iter(col) {
+            let (key, value) = result?;
+            let is_shard_data = if col == DBCol::Chunks {
+                let chunk = ShardChunk::try_from_slice(&value)?;
+                let shard_layout = shard_layout_after(chunk.prev_block())?;
+                let shard_uid = ShardUId::from_shard_id_and_layout(chunk.shard_id(), &shard_layout);
+                shard_uids.contains(&shard_uid)
+            } else {
+                is_shard_key(hot_store, col, &key, shard_uids, shard_layout_after)?
+            };
+            if is_shard_data && !callback(col, &key, &value)? {
+                return Ok(false);
+            }
+        }
+    }
+    for result in hot_store.iter(DBCol::StateShardUIdMapping) {
+        let (key, value) = result?;
+        if !callback(DBCol::StateShardUIdMapping, &key, &value)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Copies the data of the shards retired by a resharding from `hot_store` to
+/// `cold_db`, so that it can be removed from the hot store.
+///
+/// The cold loop copies the data of the shards of each block as it goes, but
+/// the nodes migrated to split storage after a resharding, or whose cold head
+/// was behind it, may miss some of it. See `for_each_shards_data` for what is
+/// copied, and `check_shards_in_cold` to verify it.
+pub fn copy_shards_to_cold(
+    cold_db: Arc<ColdDB>,
+    hot_store: &Store,
+    shard_uids: &[ShardUId],
+    shard_layout_after: &dyn Fn(&CryptoHash) -> io::Result<ShardLayout>,
+    batch_size: usize,
+    keep_going: &Arc<std::sync::atomic::AtomicBool>,
+) -> io::Result<CopyAllDataToColdStatus> {
+    tracing::info!(target: "cold_store", ?shard_uids, "Started shards migration");
+    let mut transaction = BatchTransaction::new(cold_db, batch_size);
+    let everything_copied =
+        for_each_shards_data(hot_store, shard_uids, shard_layout_after, |col, key, value| {
+            if !keep_going.load(std::sync::atomic::Ordering::Relaxed) {
+                tracing::debug!(target: "cold_store", "stopping copy_shards_to_cold");
+                return Ok(false);
+            }
+            transaction.set_and_write_if_full(col, key.to_vec(), value.to_vec())?;
+            Ok(true)
+        })?;
+    if !everything_copied {
+        return Ok(CopyAllDataToColdStatus::Interrupted);
+    }
+    transaction.write()?;
+    tracing::info!(target: "cold_store", ?shard_uids, "Finished shards migration");
+    Ok(CopyAllDataToColdStatus::EverythingCopied)
+}
+
+/// The number of keys of a column checked by `check_shards_in_cold` and how
+/// many of them are missing in the cold store.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ShardsColumnCheck {
+    pub num_checked: u64,
+    pub num_missing: u64,
+}
+
+/// Checks that the data of the shards `shard_uids` in `hot_store` is present
+/// in `cold_store`, as copied by `copy_shards_to_cold`.
+pub fn check_shards_in_cold(
+    hot_store: &Store,
+    cold_store: &Store,
+    shard_uids: &[ShardUId],
+    shard_layout_after: &dyn Fn(&CryptoHash) -> io::Result<ShardLayout>,
+) -> io::Result<HashMap<DBCol, ShardsColumnCheck>> {
+    let mut checks: HashMap<DBCol, ShardsColumnCheck> = HashMap::new();
+    for_each_shards_data(hot_store, shard_uids, shard_layout_after, |col, key, value| {
+        let check = checks.entry(col).or_default();
+        check.num_checked += 1;
+        if cold_store.get(col, key)?.as_deref() != Some(value) {
+            tracing::debug!(target: "cold_store", ?col, ?key, "shard data missing in cold store");
+            check.num_missing += 1;
+        }
+        Ok(true)
+    })?;
+    Ok(checks)
+}
+
 // The copy_state_from_store function depends on the state nodes to be present
 // in the trie changes. This isn't the case for genesis so instead this method
 // can be used to copy the genesis records from hot to cold.
@@ -663,9 +835,86 @@ impl BatchTransaction {
 
 #[cfg(test)]
 mod test {
-    use super::{StoreKey, combine_keys};
+    use super::{
+        CopyAllDataToColdStatus, StoreKey, check_shards_in_cold, combine_keys, copy_shards_to_cold,
+        join_two_keys,
+    };
+    use crate::DBCol;
     use crate::columns::DBKeyType;
+    use crate::metadata::{DB_VERSION, DbKind};
+    use crate::test_utils::create_test_node_storage_with_cold;
+    use near_primitives::genesis::genesis_block;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::{ShardLayout, ShardUId};
+    use near_primitives::types::ShardId;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_time::Utc;
     use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_copy_shards_to_cold() {
+        let (storage, ..) = create_test_node_storage_with_cold(DB_VERSION, DbKind::Hot);
+        let hot_store = storage.get_hot_store();
+        let cold_store = storage.get_cold_store().unwrap();
+        let parent = ShardUId::new(3, ShardId::new(1));
+        let child = ShardUId::new(3, ShardId::new(2));
+        let other = ShardUId::new(3, ShardId::new(3));
+        // The same shard id as the parent in a layout of another version.
+        let old_parent = ShardUId::new(2, ShardId::new(1));
+        let shard_layout_after =
+            |_: &CryptoHash| -> std::io::Result<ShardLayout> { Ok(ShardLayout::multi_shard(4, 3)) };
+        let block = genesis_block(PROTOCOL_VERSION, vec![], Utc::UNIX_EPOCH, 0, 0, 0, &vec![]);
+        let block_hash = *block.hash();
+
+        let mut store_update = hot_store.store_update();
+        store_update.insert_ser(DBCol::BlockHeader, block_hash.as_bytes(), block.header()).unwrap();
+        for (shard_uid, node) in [(parent, b"parent node"), (other, b"other node ")] {
+            let key = join_two_keys(&shard_uid.to_bytes(), CryptoHash::hash_bytes(node).as_bytes());
+            store_update.increment_refcount(DBCol::State, &key, node);
+            let key = join_two_keys(block_hash.as_bytes(), &shard_uid.to_bytes());
+            store_update.set(DBCol::ChunkExtra, &key, node);
+            let key = join_two_keys(block_hash.as_bytes(), &shard_uid.shard_id().to_le_bytes());
+            store_update.set(DBCol::OutgoingReceipts, &key, node);
+        }
+        store_update.set_ser(DBCol::StateShardUIdMapping, &child.to_bytes(), &parent).unwrap();
+        store_update.commit().unwrap();
+
+        let checks =
+            check_shards_in_cold(&hot_store, &cold_store, &[parent], &shard_layout_after).unwrap();
+        for col in
+            [DBCol::State, DBCol::ChunkExtra, DBCol::OutgoingReceipts, DBCol::StateShardUIdMapping]
+        {
+            assert_eq!(checks[&col].num_checked, 1, "{col}");
+            assert_eq!(checks[&col].num_missing, 1, "{col}");
+        }
+        let checks =
+            check_shards_in_cold(&hot_store, &cold_store, &[old_parent], &shard_layout_after)
+                .unwrap();
+        assert!(!checks.contains_key(&DBCol::OutgoingReceipts));
+
+        let keep_going = Arc::new(AtomicBool::new(true));
+        let status = copy_shards_to_cold(
+            storage.cold_db().unwrap().clone(),
+            &hot_store,
+            &[parent],
+            &shard_layout_after,
+            1,
+            &keep_going,
+        )
+        .unwrap();
+        assert!(matches!(status, CopyAllDataToColdStatus::EverythingCopied));
+
+        let checks =
+            check_shards_in_cold(&hot_store, &cold_store, &[parent], &shard_layout_after).unwrap();
+        assert!(checks.values().all(|check| check.num_missing == 0));
+        let checks =
+            check_shards_in_cold(&hot_store, &cold_store, &[other], &shard_layout_after).unwrap();
+        assert_eq!(checks[&DBCol::State].num_missing, 1);
+        assert_eq!(checks[&DBCol::ChunkExtra].num_missing, 1);
+        assert_eq!(checks[&DBCol::OutgoingReceipts].num_missing, 1);
+    }
 
     #[test]
     fn test_combine_keys() {
@@ -322,6 +322,25 @@ pub enum DBCol {
     /// - *Rows*: BlockShardId (BlockHash || ShardId) - 40 bytes
     /// - *Column type*: `ChunkApplyStats`
     ChunkApplyStats,
+    /// Stores timing information about the blocks produced by this node, used to attribute
+    /// delays in block production. Only the latest blocks are kept, older entries are removed
+    /// when new ones are added.
+    /// Not necessary for the node to operate, but useful for debugging.
+    /// - *Rows*: height (u64, big-endian)
+    /// - *Column type*: `BlockProductionTimings`
+    BlockProductionTimings,
+    /// Progress of the flat storage split of a parent shard into the child shards, used to
+    /// resume the split after a restart. Only present while the child is being created.
+    /// - *Rows*: `shard_uid` of the child shard
+    /// - *Column type*: `FlatStorageReshardingProgress`
+    FlatStorageReshardingProgress,
+    /// Storage usage and gas burnt of the accounts, collected when `save_account_usage_stats`
+    /// is enabled to pick the boundary accounts of shard splits
+    /// (see `near_primitives::shard_layout::boundary_selection`).
+    /// Not necessary for the node to operate.
+    /// - *Rows*: `account_id`
+    /// - *Column type*: `AccountUsageStats`
+    AccountUsageStats,
 }
 
 /// Defines different logical parts of a db key.
@@ -494,6 +513,10 @@ impl DBCol {
             // LatestChunkStateWitnesses stores the last N observed witnesses, used only for debugging.
             DBCol::LatestChunkStateWitnesses => false,
             DBCol::LatestWitnessesByIndex => false,
+            // BlockProductionTimings stores timings of the last N produced blocks, used only for debugging.
+            DBCol::BlockProductionTimings => false,
+            // AccountUsageStats is only used to propose shard splits.
+            DBCol::AccountUsageStats => false,
             // Deprecated.
             DBCol::_ReceiptIdToShardId => false,
             // This can be re-constructed from the Chunks column, so no need to store in Cold DB.
@@ -526,6 +549,7 @@ impl DBCol {
             | DBCol::FlatStateChanges
             | DBCol::FlatStateDeltaMetadata
             | DBCol::FlatStorageStatus
+            | DBCol::FlatStorageReshardingProgress
             | DBCol::EpochSyncProof
             | DBCol::StateSyncHashes
             | DBCol::StateSyncNewChunks => false,
@@ -605,6 +629,9 @@ impl DBCol {
             DBCol::StateSyncHashes => &[DBKeyType::EpochId],
             DBCol::StateSyncNewChunks => &[DBKeyType::BlockHash],
             DBCol::ChunkApplyStats => &[DBKeyType::BlockHash, DBKeyType::ShardId],
+            DBCol::BlockProductionTimings => &[DBKeyType::BlockHeight],
+            DBCol::FlatStorageReshardingProgress => &[DBKeyType::ShardUId],
+            DBCol::AccountUsageStats => &[DBKeyType::AccountId],
         }
     }
 }
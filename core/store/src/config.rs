@@ -88,6 +88,12 @@ pub struct StoreConfig {
     /// If true, load mem trie for each shard being tracked; this has priority over `load_memtries_for_shards`.
     #[serde(rename = "load_mem_tries_for_tracked_shards")]
     pub load_memtries_for_tracked_shards: bool,
+    /// If true, the in-memory tries are loaded on startup in background
+    /// threads. Until the loading of a shard completes, its chunks are applied
+    /// with the flat storage and the disk trie, and the node switches to the
+    /// in-memory trie of the shard with the first block applied afterwards.
+    #[serde(rename = "load_mem_tries_in_background")]
+    pub load_memtries_in_background: bool,
 
     /// Path where to create RocksDB checkpoints during database migrations or
     /// `false` to disable that feature.
@@ -299,6 +305,7 @@ impl Default for StoreConfig {
             // requires more RAM and takes several minutes on startup.
             load_memtries_for_shards: Default::default(),
             load_memtries_for_tracked_shards: false,
+            load_memtries_in_background: false,
 
             migration_snapshot: Default::default(),
 
@@ -1,7 +1,7 @@
 use crate::adapter::flat_store::{FlatStoreAdapter, FlatStoreUpdateAdapter};
 use crate::flat::{
-    BlockInfo, FlatStorageReadyStatus, FlatStorageReshardingStatus, FlatStorageStatus,
-    POISONED_LOCK_ERR,
+    BlockInfo, FlatHeadFreezeReason, FlatStorageReadyStatus, FlatStorageReshardingStatus,
+    FlatStorageStatus, POISONED_LOCK_ERR,
 };
 use near_primitives::errors::StorageError;
 use near_primitives::hash::CryptoHash;
@@ -88,7 +88,7 @@ impl FlatStorageManager {
         let mut flat_storages = self.0.flat_storages.lock().expect(POISONED_LOCK_ERR);
         let flat_storage = FlatStorage::new(self.0.store.clone(), shard_uid)?;
         if disable_updates {
-            flat_storage.set_flat_head_update_mode(false);
+            flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::StateSnapshot, false);
         }
         let original_value = flat_storages.insert(shard_uid, flat_storage);
         if original_value.is_some() {
@@ -276,33 +276,6 @@ impl FlatStorageManager {
         }
     }
 
-    /// Returns None if there's no resharding flat storage split in progress
-    /// If there is, returns Some(None) if there's at least one child shard that hasn't been split and had its
-    /// status set to `CatchingUp`. If they've all been split already and are in the catchup phase,
-    /// returns the lowest height among all shards that resharding catchup has advanced to.
-    pub fn resharding_catchup_height_reached(
-        &self,
-        shard_uids: impl Iterator<Item = ShardUId>,
-    ) -> Result<Option<Option<BlockHeight>>, StorageError> {
-        let mut ret = None;
-        for shard_uid in shard_uids {
-            match self.0.store.get_flat_storage_status(shard_uid)? {
-                FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(
-                    flat_head,
-                )) => {
-                    if let Some(Some(min_height)) = ret {
-                        ret = Some(Some(std::cmp::min(min_height, flat_head.height)));
-                    } else {
-                        ret = Some(Some(flat_head.height));
-                    }
-                }
-                FlatStorageStatus::Resharding(_) => return Ok(Some(None)),
-                _ => {}
-            };
-        }
-        Ok(ret)
-    }
-
     /// Should be called when we want to take a state snapshot. Disallows flat head updates, and signals to any resharding
     /// flat storage code that it should not advance beyond this hash
     // TODO(#12919): This sets the currently active snapshot request to `block_hash` in favor of any previous requests. We
@@ -315,7 +288,7 @@ impl FlatStorageManager {
         }
         let flat_storages = self.0.flat_storages.lock().expect(POISONED_LOCK_ERR);
         for flat_storage in flat_storages.values() {
-            flat_storage.set_flat_head_update_mode(false);
+            flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::StateSnapshot, false);
         }
         tracing::debug!(target: "store", "Locked flat head updates");
     }
@@ -336,7 +309,7 @@ impl FlatStorageManager {
         }
         let flat_storages = self.0.flat_storages.lock().expect(POISONED_LOCK_ERR);
         for flat_storage in flat_storages.values() {
-            flat_storage.set_flat_head_update_mode(true);
+            flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::StateSnapshot, true);
         }
         tracing::debug!(target: "store", "Unlocked flat head updates");
     }
@@ -42,8 +42,11 @@ pub use metrics::{
 };
 pub use storage::FlatStorage;
 pub use types::{
-    BlockInfo, FetchingStateStatus, FlatStateIterator, FlatStorageCreationStatus, FlatStorageError,
-    FlatStorageReadyStatus, FlatStorageReshardingStatus, FlatStorageStatus, ParentSplitParameters,
+    BlockInfo, FetchingStateStatus, FlatHeadFreezeReason, FlatStateIterator,
+    FlatStorageCreationStatus, FlatStorageError, FlatStorageReadyStatus,
+    FlatStorageReshardingProgress, FlatStorageReshardingStatus, FlatStorageStatus,
+    ParentSplitParameters, SplitShardCheckpoint, SplitShardDeltasCheckpoint,
+    SplitShardRangeCheckpoint,
 };
 
 pub(crate) const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 use near_primitives::errors::StorageError;
@@ -16,7 +16,7 @@ use crate::flat::{FlatStorageReadyStatus, FlatStorageStatus};
 use super::FlatStorageReshardingStatus;
 use super::delta::{CachedFlatStateDelta, FlatStateDelta};
 use super::metrics::FlatStorageMetrics;
-use super::types::FlatStorageError;
+use super::types::{FlatHeadFreezeReason, FlatStorageError};
 
 /// FlatStorage stores information on which blocks flat storage current supports key lookups on.
 /// Note that this struct is shared by multiple threads, the chain thread, threads that apply chunks,
@@ -43,8 +43,9 @@ pub(crate) struct FlatStorageInner {
     flat_head: BlockInfo,
     /// Cached deltas for all blocks supported by this flat storage.
     deltas: HashMap<CryptoHash, CachedFlatStateDelta>,
-    /// Defines whether flat head can be moved forward or not.
-    move_head_enabled: bool,
+    /// Reasons for which flat head can't be moved forward. It moves only
+    /// when this is empty.
+    head_freeze_reasons: HashSet<FlatHeadFreezeReason>,
     metrics: FlatStorageMetrics,
 }
 
@@ -277,7 +278,7 @@ impl FlatStorage {
             shard_uid,
             flat_head,
             deltas,
-            move_head_enabled: true,
+            head_freeze_reasons: HashSet::new(),
             metrics,
         };
         inner.update_delta_metrics();
@@ -367,7 +368,7 @@ impl FlatStorage {
         strict: bool,
     ) -> Result<(), FlatStorageError> {
         let mut guard = self.0.write().expect(crate::flat::POISONED_LOCK_ERR);
-        if !guard.move_head_enabled {
+        if !guard.head_freeze_reasons.is_empty() {
             return Ok(());
         }
 
@@ -495,13 +496,18 @@ impl FlatStorage {
         guard.shard_uid
     }
 
-    /// Updates `move_head_enabled`. If false, this will prevent flat storage updates and deltas will accumulate
-    /// until this is called again with `enabled=true`.
-    /// TODO: This could be improved by setting a maximum block height instead of a bool that disables all updates,
+    /// Freezes or unfreezes flat head for the given reason. While flat head is frozen for any reason, this
+    /// will prevent flat storage updates and deltas will accumulate, so that unfreezing it for one reason
+    /// doesn't move it while it is still needed frozen for another.
+    /// TODO: This could be improved by setting a maximum block height instead of disabling all updates,
     /// by using the `want_snapshot` field of the flat storage manager we already have.
-    pub fn set_flat_head_update_mode(&self, enabled: bool) {
+    pub fn set_flat_head_update_mode(&self, reason: FlatHeadFreezeReason, enabled: bool) {
         let mut guard = self.0.write().expect(crate::flat::POISONED_LOCK_ERR);
-        guard.move_head_enabled = enabled;
+        if enabled {
+            guard.head_freeze_reasons.remove(&reason);
+        } else {
+            guard.head_freeze_reasons.insert(reason);
+        }
     }
 }
 
@@ -519,7 +525,7 @@ mod tests {
     use crate::flat::manager::FlatStorageManager;
     use crate::flat::storage::FlatStorageInner;
     use crate::flat::test_utils::MockChain;
-    use crate::flat::types::FlatStorageError;
+    use crate::flat::types::{FlatHeadFreezeReason, FlatStorageError};
     use crate::flat::{FlatStorageReadyStatus, FlatStorageStatus};
     use crate::test_utils::create_test_store;
     use assert_matches::assert_matches;
@@ -939,6 +945,55 @@ mod tests {
         assert_eq!(*flat_head_height, (num_blocks - 1) as BlockHeight);
     }
 
+    #[test]
+    /// Flat head moves only once it is unfrozen for all the reasons it was frozen for.
+    fn flat_head_frozen_for_overlapping_reasons() {
+        init_test_logger();
+        let num_blocks = 5;
+        let chain = MockChain::linear_chain(num_blocks);
+        let shard_uid = ShardUId::single_shard();
+        let store = create_test_store().flat_store();
+        let mut store_update = store.store_update();
+        store_update.set_flat_storage_status(
+            shard_uid,
+            FlatStorageStatus::Ready(FlatStorageReadyStatus { flat_head: chain.get_block(0) }),
+        );
+        for i in 1..num_blocks as BlockHeight {
+            let delta = FlatStateDelta {
+                changes: FlatStateChanges::default(),
+                metadata: FlatStateDeltaMetadata {
+                    block: chain.get_block(i),
+                    prev_block_with_changes: None,
+                },
+            };
+            store_update.set_delta(shard_uid, &delta);
+        }
+        store_update.commit().unwrap();
+
+        let flat_storage_manager = FlatStorageManager::new(store);
+        flat_storage_manager.create_flat_storage_for_shard(shard_uid).unwrap();
+        let flat_storage = flat_storage_manager.get_flat_storage_for_shard(shard_uid).unwrap();
+
+        let snapshot_hash = chain.get_block_hash(1);
+        flat_storage_manager.want_snapshot(snapshot_hash, 0);
+        flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, false);
+
+        // The memtrie is loaded, but the snapshot is not taken yet.
+        flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, true);
+        flat_storage.update_flat_head_impl(&chain.get_block_hash(2), true).unwrap();
+        assert_eq!(flat_storage.get_head_hash(), chain.get_block_hash(0));
+
+        // The snapshot is taken while another memtrie is loaded.
+        flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, false);
+        flat_storage_manager.snapshot_taken(&snapshot_hash);
+        flat_storage.update_flat_head_impl(&chain.get_block_hash(2), true).unwrap();
+        assert_eq!(flat_storage.get_head_hash(), chain.get_block_hash(0));
+
+        flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, true);
+        flat_storage.update_flat_head_impl(&chain.get_block_hash(2), true).unwrap();
+        assert_eq!(flat_storage.get_head_hash(), chain.get_block_hash(2));
+    }
+
     #[test]
     fn flat_storage_with_hops_random() {
         init_test_logger();
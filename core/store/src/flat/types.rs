@@ -26,6 +26,18 @@ impl From<Tip> for BlockInfo {
     }
 }
 
+/// Reason for which the flat head of a shard must not move. The flat head
+/// moves again only once every reason it was frozen for is lifted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FlatHeadFreezeReason {
+    /// A state snapshot is requested and not taken yet.
+    StateSnapshot,
+    /// The shard is the parent of a split in progress.
+    Resharding,
+    /// The memtrie of the shard is being loaded in background.
+    MemtrieLoading,
+}
+
 #[derive(strum::AsRefStr, strum::Display, Debug, PartialEq, Eq, thiserror::Error)]
 pub enum FlatStorageError {
     /// This means we can't find a path from `flat_head` to the block. Includes
@@ -201,5 +213,66 @@ pub struct ParentSplitParameters {
     pub flat_head: BlockInfo,
 }
 
+/// Progress of the split of a parent shard into a child shard, stored in
+/// `DBCol::FlatStorageReshardingProgress` for each child while its status is
+/// [FlatStorageReshardingStatus::CreatingChild]. It allows the split to resume
+/// from the last committed batch after a node restart, instead of starting
+/// over.
+#[derive(
+    BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq, serde::Serialize, ProtocolSchema,
+)]
+pub struct FlatStorageReshardingProgress {
+    /// UId of the shard being split.
+    pub parent_shard: ShardUId,
+    /// The progress of the copy of the key-values of the parent to the
+    /// children committed so far, `None` if the copy did not start yet.
+    pub checkpoint: Option<SplitShardCheckpoint>,
+}
+
+/// Position of the split shard task in the parent's flat storage. The flat
+/// state of the parent is split into ranges of keys which are copied
+/// concurrently, each in key order. The deltas of each block up to the
+/// resharding block are copied after all the ranges.
+#[derive(
+    BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq, serde::Serialize, ProtocolSchema,
+)]
+pub struct SplitShardCheckpoint {
+    /// The resharding block the split is done at. Progress made at another
+    /// resharding block, e.g. in a fork, can't be resumed.
+    pub resharding_block: BlockInfo,
+    /// The ranges of the flat state, in key order. They cover all the keys.
+    pub ranges: Vec<SplitShardRangeCheckpoint>,
+    /// The last copied key of the deltas, `None` if the copy of the deltas
+    /// did not start yet.
+    pub deltas: Option<SplitShardDeltasCheckpoint>,
+}
+
+/// Progress of the copy of a range of keys of the parent's flat state.
+#[derive(
+    BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq, serde::Serialize, ProtocolSchema,
+)]
+pub struct SplitShardRangeCheckpoint {
+    /// The first key of the range, inclusive.
+    pub start: Vec<u8>,
+    /// The end of the range, exclusive. `None` if the range is unbounded.
+    pub end: Option<Vec<u8>>,
+    /// The last copied key, `None` if the copy of the range did not start
+    /// yet.
+    pub last_key: Option<Vec<u8>>,
+    /// Whether all the keys of the range were copied.
+    pub done: bool,
+}
+
+/// Position of the split shard task in the deltas of the parent.
+#[derive(
+    BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq, serde::Serialize, ProtocolSchema,
+)]
+pub struct SplitShardDeltasCheckpoint {
+    /// Hash of the block whose delta `last_key` belongs to.
+    pub block: CryptoHash,
+    /// The last copied key.
+    pub last_key: Vec<u8>,
+}
+
 pub type FlatStateIterator<'a> =
     Box<dyn Iterator<Item = FlatStorageResult<(Vec<u8>, FlatStateValue)>> + 'a>;
@@ -5,9 +5,9 @@ extern crate core;
 pub use crate::columns::DBCol;
 pub use crate::config::{Mode, StoreConfig};
 pub use crate::db::{
-    CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, GENESIS_STATE_ROOTS_KEY,
-    HEAD_KEY, HEADER_HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, STATE_SNAPSHOT_KEY,
-    STATE_SYNC_DUMP_KEY, TAIL_KEY,
+    CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, GC_CATEGORY_TAIL_KEY,
+    GENESIS_STATE_ROOTS_KEY, HEAD_KEY, HEADER_HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
+    LATEST_KNOWN_KEY, STATE_SNAPSHOT_KEY, STATE_SYNC_DUMP_KEY, TAIL_KEY,
 };
 use crate::db::{DBTransaction, Database, StoreStatistics, metadata};
 pub use crate::node_storage::opener::{
@@ -18,11 +18,11 @@ pub use crate::node_storage::{NodeStorage, Temperature};
 pub use crate::store::{Store, StoreUpdate};
 pub use crate::trie::update::{TrieUpdate, TrieUpdateIterator, TrieUpdateValuePtr};
 pub use crate::trie::{
-    ApplyStatePartResult, KeyForStateChanges, KeyLookupMode, NibbleSlice, PartialStorage,
-    PrefetchApi, PrefetchError, RawTrieNode, RawTrieNodeWithSize, STATE_SNAPSHOT_COLUMNS,
-    ShardTries, StateSnapshot, StateSnapshotConfig, Trie, TrieAccess, TrieCache,
-    TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage, TrieStorage, WrappedTrieChanges,
-    estimator,
+    ApplyStatePartResult, KeyForStateChanges, KeyLookupMode, MemTrieLoadingStatus, NibbleSlice,
+    PartialStorage, PrefetchApi, PrefetchError, RawTrieNode, RawTrieNodeWithSize,
+    STATE_SNAPSHOT_COLUMNS, ShardTries, StateSnapshot, StateSnapshotConfig, Trie, TrieAccess,
+    TrieCache, TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage, TrieStorage,
+    WrappedTrieChanges, estimator,
 };
 pub use crate::utils::*;
 pub use near_primitives::errors::{MissingTrieValueContext, StorageError};
@@ -38,6 +38,7 @@ pub mod flat;
 pub mod genesis;
 pub mod metrics;
 pub mod migrations;
+pub mod negative_refcount;
 mod node_storage;
 mod store;
 pub mod trie;
@@ -1,10 +1,15 @@
+use crate::adapter::StoreAdapter;
 use crate::db::metadata::{DbKind, KIND_KEY};
+use crate::flat::FlatStorageStatus;
 use crate::{DBCol, Store, StoreUpdate};
 use anyhow::{Context, anyhow};
 use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::account::Account;
 use near_primitives::epoch_manager::AGGREGATOR_KEY;
 use near_primitives::epoch_manager::EpochSummary;
 use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::shard_layout::boundary_selection::AccountUsageStats;
 use near_primitives::sharding::{ChunkHash, StateSyncInfo, StateSyncInfoV0};
 use near_primitives::state::FlatStateValue;
 use near_primitives::state::PartialState;
@@ -13,6 +18,8 @@ use near_primitives::stateless_validation::stored_chunk_state_transition_data::{
     StoredChunkStateTransitionData, StoredChunkStateTransitionDataV1,
 };
 use near_primitives::transaction::{ExecutionOutcomeWithIdAndProof, ExecutionOutcomeWithProof};
+use near_primitives::trie_key::col;
+use near_primitives::trie_key::trie_key_parsers::parse_account_id_from_account_key;
 use near_primitives::types::{
     AccountId, EpochId, ShardId, ValidatorId, ValidatorKickoutReason, ValidatorStats,
     validator_stake::ValidatorStake,
@@ -528,3 +535,54 @@ pub fn migrate_44_to_45(store: &Store) -> anyhow::Result<()> {
     update.commit()?;
     Ok(())
 }
+
+/// Migrates the database from version 47 to 48.
+///
+/// Seeds the storage usage of the accounts in DBCol::AccountUsageStats from the
+/// flat storage of the shards tracked by the node, so that the accounts which
+/// aren't changed after the migration are taken into account as well. The gas
+/// burnt is only collected from the chunks applied after the migration.
+/// Nothing is done if the node doesn't collect the account usage stats.
+pub fn migrate_47_to_48(store: &Store, save_account_usage_stats: bool) -> anyhow::Result<()> {
+    if !save_account_usage_stats {
+        return Ok(());
+    }
+    let _span =
+        tracing::info_span!(target: "migrations", "Seeding account usage stats from flat storage")
+            .entered();
+
+    let flat_store = store.flat_store();
+    let trie_store = store.trie_store();
+    let mut update = BatchedStoreUpdate::new(store, 10_000_000);
+    for row in store.iter_ser::<FlatStorageStatus>(DBCol::FlatStorageStatus) {
+        let (key, status) = row.context("failed deserializing FlatStorageStatus")?;
+        let shard_uid = ShardUId::try_from(key.as_ref()).map_err(|err| {
+            anyhow!("failed deserializing ShardUId key in FlatStorageStatus: {err}")
+        })?;
+        if !matches!(status, FlatStorageStatus::Ready(_)) {
+            info!(target: "migrations", ?shard_uid, ?status, "Skipping shard without flat storage");
+            continue;
+        }
+
+        let mut num_accounts = 0;
+        let accounts =
+            flat_store.iter_range(shard_uid, Some(&[col::ACCOUNT]), Some(&[col::ACCOUNT + 1]));
+        for item in accounts {
+            let (key, value) = item?;
+            let account_id = parse_account_id_from_account_key(&key)?;
+            let data = match value {
+                FlatStateValue::Inlined(data) => data,
+                FlatStateValue::Ref(value_ref) => {
+                    trie_store.get(shard_uid, &value_ref.hash)?.to_vec()
+                }
+            };
+            let account = Account::try_from_slice(&data)?;
+            let stats = AccountUsageStats { storage_usage: account.storage_usage(), gas_burnt: 0 };
+            update.set_ser(DBCol::AccountUsageStats, account_id.as_bytes(), &stats)?;
+            num_accounts += 1;
+        }
+        info!(target: "migrations", ?shard_uid, num_accounts, "Seeded account usage stats");
+    }
+    update.finish()?;
+    Ok(())
+}
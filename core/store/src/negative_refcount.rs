@@ -0,0 +1,172 @@
+//! Diagnostics for the trie node refcounts that would go negative.
+//!
+//! A negative refcount in [`DBCol::State`] means that a trie node or value was
+//! deleted more times than it was inserted, which is a bug in the code that
+//! produced the deletions. The refcounts are never stored negative (see
+//! [`refcount::refcount_merge`]), so such a bug only shows up much later, when
+//! the same node is inserted again and disappears too early.
+//!
+//! When a [`Store`] has a [`NegativeRefcountTracker`], every commit of a
+//! [`crate::StoreUpdate`] which decrements refcounts in `DBCol::State` checks
+//! the refcounts it leaves behind. Each one that would go negative is logged
+//! and added to the in-memory report of the tracker, together with the origins
+//! of the decrements. Only test stores have a tracker and nothing is persisted.
+
+use std::collections::HashMap;
+use std::io;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+
+use crate::db::{DBOp, DBTransaction, refcount};
+use crate::{DBCol, Store};
+
+/// Where a refcount decrement comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefcountDecrementOrigin {
+    /// The block whose trie changes were applied or garbage collected, if the
+    /// caller set it with `StoreUpdate::set_refcount_decrement_block_hash`.
+    pub block_hash: Option<CryptoHash>,
+    /// Source location of the code which requested the decrement.
+    pub caller: String,
+}
+
+impl RefcountDecrementOrigin {
+    pub(crate) fn new(block_hash: Option<CryptoHash>, caller: &Location) -> Self {
+        Self { block_hash, caller: caller.to_string() }
+    }
+}
+
+/// A refcount in `DBCol::State` which would have gone negative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NegativeRefcountRecord {
+    /// The shard uid of the key. After resharding this is the ancestor shard
+    /// whose prefix the child's trie nodes are stored under.
+    pub shard_uid: ShardUId,
+    pub trie_node_or_value_hash: CryptoHash,
+    /// The refcount the commit would have left, had it been stored as is.
+    pub refcount: i64,
+    /// Origins of all the decrements of the key in the commit.
+    pub origins: Vec<RefcountDecrementOrigin>,
+}
+
+/// Collects the [`NegativeRefcountRecord`]s of a store, see the module docs.
+#[derive(Clone, Default)]
+pub struct NegativeRefcountTracker {
+    records: Arc<Mutex<Vec<NegativeRefcountRecord>>>,
+}
+
+impl NegativeRefcountTracker {
+    /// Returns all the negative refcounts recorded so far.
+    pub fn records(&self) -> Vec<NegativeRefcountRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Returns the negative refcounts recorded so far and clears the report.
+    pub fn take_records(&self) -> Vec<NegativeRefcountRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+
+    /// Checks the refcounts of the keys decremented in `transaction` and adds
+    /// a record to the report for each negative one.
+    pub(crate) fn check_transaction(
+        &self,
+        store: &Store,
+        transaction: &DBTransaction,
+        decrements: Vec<(Vec<u8>, RefcountDecrementOrigin)>,
+    ) -> io::Result<()> {
+        let mut origins: HashMap<Vec<u8>, Vec<RefcountDecrementOrigin>> = HashMap::new();
+        for (key, origin) in decrements {
+            origins.entry(key).or_default().push(origin);
+        }
+        let mut deltas: HashMap<&[u8], i64> = HashMap::new();
+        for op in &transaction.ops {
+            if let DBOp::UpdateRefcount { col: DBCol::State, key, value } = op {
+                if origins.contains_key(key) {
+                    *deltas.entry(key).or_default() += refcount::decode_value_with_rc(value).1;
+                }
+            }
+        }
+
+        let mut records = vec![];
+        for (key, delta) in deltas {
+            let stored = store.storage.get_raw_bytes(DBCol::State, key)?;
+            let stored_refcount =
+                stored.as_deref().map_or(0, |v| refcount::decode_value_with_rc(v).1);
+            let refcount = stored_refcount + delta;
+            if refcount >= 0 {
+                continue;
+            }
+            let (shard_uid, hash) = key.split_at(8);
+            let record = NegativeRefcountRecord {
+                shard_uid: ShardUId::try_from(shard_uid).map_err(io::Error::other)?,
+                trie_node_or_value_hash: CryptoHash::try_from(hash).map_err(io::Error::other)?,
+                refcount,
+                origins: origins.remove(key).unwrap_or_default(),
+            };
+            tracing::warn!(target: "store", ?record, "refcount would go negative");
+            records.push(record);
+        }
+
+        self.records.lock().unwrap().extend(records);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use near_primitives::hash::hash;
+    use near_primitives::shard_layout::ShardUId;
+
+    use crate::DBCol;
+    use crate::adapter::StoreAdapter;
+    use crate::test_utils::create_test_store;
+
+    #[test]
+    fn test_negative_refcount_is_recorded() {
+        let store = create_test_store();
+        let tracker = store.negative_refcount_tracker().cloned().unwrap();
+        let shard_uid = ShardUId::single_shard();
+        let value = b"value";
+        let value_hash = hash(value);
+
+        let mut store_update = store.trie_store().store_update();
+        store_update.increment_refcount_by(shard_uid, &value_hash, value, NonZeroU32::MIN);
+        store_update.commit().unwrap();
+
+        // Removing the value once is fine.
+        let mut store_update = store.trie_store().store_update();
+        store_update.decrement_refcount(shard_uid, &value_hash);
+        store_update.commit().unwrap();
+        assert!(tracker.records().is_empty());
+
+        let block_hash = hash(b"block");
+        let mut store_update = store.trie_store().store_update();
+        store_update.set_refcount_decrement_block_hash(Some(block_hash));
+        store_update.decrement_refcount(shard_uid, &value_hash);
+        store_update.decrement_refcount(shard_uid, &value_hash);
+        store_update.commit().unwrap();
+
+        let records = tracker.take_records();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.shard_uid, shard_uid);
+        assert_eq!(record.trie_node_or_value_hash, value_hash);
+        assert_eq!(record.refcount, -2);
+        assert_eq!(record.origins.len(), 2);
+        for origin in &record.origins {
+            assert_eq!(origin.block_hash, Some(block_hash));
+            assert!(origin.caller.contains("negative_refcount.rs"), "{}", origin.caller);
+        }
+        assert!(tracker.records().is_empty());
+
+        let mut key = shard_uid.to_bytes().to_vec();
+        key.extend(value_hash.as_ref());
+        // The value itself is gone for good.
+        assert_eq!(store.get(DBCol::State, &key).unwrap(), None);
+    }
+}
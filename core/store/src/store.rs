@@ -5,11 +5,13 @@ use std::{fmt, io};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_fmt::{AbbrBytes, StorageKey};
+use near_primitives::hash::CryptoHash;
 
 use crate::DBCol;
 use crate::adapter::{StoreAdapter, StoreUpdateAdapter};
 use crate::db::metadata::{DbKind, DbMetadata, DbVersion, KIND_KEY, VERSION_KEY};
 use crate::db::{DBIterator, DBOp, DBSlice, DBTransaction, Database, StoreStatistics, refcount};
+use crate::negative_refcount::{NegativeRefcountTracker, RefcountDecrementOrigin};
 
 const STATE_COLUMNS: [DBCol; 2] = [DBCol::State, DBCol::FlatState];
 const STATE_FILE_END_MARK: u8 = 255;
@@ -23,6 +25,9 @@ const STATE_FILE_END_MARK: u8 = 255;
 #[derive(Clone)]
 pub struct Store {
     pub(crate) storage: Arc<dyn Database>,
+    /// Records the trie node refcounts which would go negative, see
+    /// [`crate::negative_refcount`]. Only set in tests.
+    negative_refcount_tracker: Option<NegativeRefcountTracker>,
 }
 
 impl StoreAdapter for Store {
@@ -33,7 +38,19 @@ impl StoreAdapter for Store {
 
 impl Store {
     pub fn new(storage: Arc<dyn Database>) -> Self {
-        Self { storage }
+        Self { storage, negative_refcount_tracker: None }
+    }
+
+    /// Makes the store record the trie node refcounts which would go negative,
+    /// see [`crate::negative_refcount`]. The clones of the returned store
+    /// share the report.
+    pub fn with_negative_refcount_tracking(mut self) -> Self {
+        self.negative_refcount_tracker = Some(NegativeRefcountTracker::default());
+        self
+    }
+
+    pub fn negative_refcount_tracker(&self) -> Option<&NegativeRefcountTracker> {
+        self.negative_refcount_tracker.as_ref()
     }
 
     /// Fetches value from given column.
@@ -67,7 +84,12 @@ impl Store {
     }
 
     pub fn store_update(&self) -> StoreUpdate {
-        StoreUpdate { transaction: DBTransaction::new(), store: self.clone() }
+        StoreUpdate {
+            transaction: DBTransaction::new(),
+            store: self.clone(),
+            refcount_decrement_block_hash: None,
+            refcount_decrements: vec![],
+        }
     }
 
     pub fn iter<'a>(&'a self, col: DBCol) -> DBIterator<'a> {
@@ -209,6 +231,11 @@ impl Store {
 pub struct StoreUpdate {
     transaction: DBTransaction,
     pub(crate) store: Store,
+    /// Block hash attached to the origins of the refcount decrements.
+    refcount_decrement_block_hash: Option<CryptoHash>,
+    /// Keys and origins of the `DBCol::State` refcount decrements, only
+    /// collected if the store tracks negative refcounts.
+    refcount_decrements: Vec<(Vec<u8>, RefcountDecrementOrigin)>,
 }
 
 impl StoreUpdateAdapter for StoreUpdate {
@@ -287,6 +314,7 @@ impl StoreUpdate {
     ///
     /// Panics if this is used for columns which are not reference-counted
     /// (see [`DBCol::is_rc`]).
+    #[track_caller]
     pub fn decrement_refcount_by(
         &mut self,
         column: DBCol,
@@ -294,15 +322,30 @@ impl StoreUpdate {
         decrease: std::num::NonZeroU32,
     ) {
         assert!(column.is_rc(), "can't update refcount: {column}");
+        if column == DBCol::State && self.store.negative_refcount_tracker.is_some() {
+            let origin = RefcountDecrementOrigin::new(
+                self.refcount_decrement_block_hash,
+                std::panic::Location::caller(),
+            );
+            self.refcount_decrements.push((key.to_vec(), origin));
+        }
         let value = refcount::encode_negative_refcount(decrease);
         self.transaction.update_refcount(column, key.to_vec(), value.to_vec())
     }
 
     /// Same as `self.decrement_refcount_by(column, key, 1)`.
+    #[track_caller]
     pub fn decrement_refcount(&mut self, column: DBCol, key: &[u8]) {
         self.decrement_refcount_by(column, key, Self::ONE)
     }
 
+    /// Sets the block hash attached to the origins of the following refcount
+    /// decrements, so that a negative refcount can be traced back to the
+    /// block whose trie changes caused it.
+    pub fn set_refcount_decrement_block_hash(&mut self, block_hash: Option<CryptoHash>) {
+        self.refcount_decrement_block_hash = block_hash;
+    }
+
     /// Modifies a value in the database.
     ///
     /// Unlike `insert`, `increment_refcount` or `decrement_refcount`, arbitrary
@@ -372,7 +415,8 @@ impl StoreUpdate {
             Arc::as_ptr(&self.store.storage),
             Arc::as_ptr(&other.store.storage)
         ));
-        self.transaction.merge(other.transaction)
+        self.transaction.merge(other.transaction);
+        self.refcount_decrements.extend(other.refcount_decrements);
     }
 
     #[tracing::instrument(
@@ -393,7 +437,7 @@ impl StoreUpdate {
             delete_range_ops
         )
     )]
-    pub fn commit(self) -> io::Result<()> {
+    pub fn commit(mut self) -> io::Result<()> {
         debug_assert!(
             {
                 let non_refcount_keys = self
@@ -488,6 +532,12 @@ impl StoreUpdate {
                 }
             }
         }
+        if let Some(tracker) = &self.store.negative_refcount_tracker {
+            if !self.refcount_decrements.is_empty() {
+                let decrements = std::mem::take(&mut self.refcount_decrements);
+                tracker.check_transaction(&self.store, &self.transaction, decrements)?;
+            }
+        }
         self.store.storage.write(self.transaction)
     }
 }
@@ -37,6 +37,8 @@ pub struct TrieConfig {
     pub load_memtries_for_shards: Vec<ShardUId>,
     /// Whether mem-trie should be loaded for each tracked shard.
     pub load_memtries_for_tracked_shards: bool,
+    /// Whether mem-tries should be loaded in background on startup.
+    pub load_memtries_in_background: bool,
 }
 
 impl TrieConfig {
@@ -64,6 +66,7 @@ impl TrieConfig {
         this.kaiching_prefetch_config.clone_from(&config.kaiching_prefetch_config);
         this.load_memtries_for_shards.clone_from(&config.load_memtries_for_shards);
         this.load_memtries_for_tracked_shards = config.load_memtries_for_tracked_shards;
+        this.load_memtries_in_background = config.load_memtries_in_background;
 
         this
     }
@@ -168,6 +168,32 @@ mod tests {
         test_iterator(true);
     }
 
+    #[test]
+    fn test_disk_iterator_seek() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let (trie_changes, map, trie) = gen_random_trie(&mut rng, false);
+            let mut seek_keys = trie_changes.into_iter().map(|(key, _)| key).collect_vec();
+            seek_keys.push(vec![]);
+            for _ in 0..20 {
+                let key_length = rng.gen_range(1..8);
+                seek_keys.push(
+                    (0..key_length).map(|_| *b"abcdefgh".choose(&mut rng).unwrap()).collect(),
+                );
+            }
+            for seek_key in seek_keys {
+                let mut iterator = trie.disk_iter().unwrap();
+                iterator.seek(&seek_key).unwrap();
+                let got = iterator.map(Result::unwrap).collect_vec();
+                let want = map
+                    .range(seek_key.clone()..)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect_vec();
+                assert_eq!(got, want, "seek key {seek_key:x?}");
+            }
+        }
+    }
+
     #[test]
     fn test_iterator_with_prune_condition_base() {
         let mut rng = rand::thread_rng();
@@ -2,7 +2,7 @@ use super::arena::single_thread::STArena;
 use super::memtries::MemTries;
 use super::node::MemTrieNodeId;
 use crate::adapter::StoreAdapter;
-use crate::flat::FlatStorageStatus;
+use crate::flat::{FlatStateChanges, FlatStorageStatus};
 use crate::trie::AccessOptions;
 use crate::trie::mem::arena::Arena;
 use crate::trie::mem::construction::TrieConstructor;
@@ -156,19 +156,8 @@ pub fn load_trie_from_flat_state_and_delta(
         if let Some(changes) = delta {
             let old_state_root = get_state_root(store, prev_hash, shard_uid)?;
             let new_state_root = get_state_root(store, hash, shard_uid)?;
-
-            let mut trie_update = memtries.update(old_state_root, TrackingMode::None)?;
-            for (key, value) in changes.0 {
-                match value {
-                    Some(value) => {
-                        trie_update.insert_memtrie_only(&key, value)?;
-                    }
-                    None => trie_update.generic_delete(0, &key, AccessOptions::DEFAULT)?,
-                };
-            }
-
-            let memtrie_changes = trie_update.to_memtrie_changes_only();
-            let new_root_after_apply = memtries.apply_memtrie_changes(height, &memtrie_changes);
+            let new_root_after_apply =
+                apply_flat_state_changes(&mut memtries, old_state_root, height, changes)?;
             assert_eq!(new_root_after_apply, new_state_root);
         }
         debug!(target: "memtrie", %shard_uid, "Applied memtrie changes for height {}", height);
@@ -178,6 +167,63 @@ pub fn load_trie_from_flat_state_and_delta(
     Ok(memtries)
 }
 
+/// Applies the changes of a block on top of the `old_state_root` of the
+/// memtries, and returns the new state root.
+pub(crate) fn apply_flat_state_changes(
+    memtries: &mut MemTries,
+    old_state_root: StateRoot,
+    block_height: BlockHeight,
+    changes: FlatStateChanges,
+) -> Result<StateRoot, StorageError> {
+    let mut trie_update = memtries.update(old_state_root, TrackingMode::None)?;
+    for (key, value) in changes.0 {
+        match value {
+            Some(value) => {
+                trie_update.insert_memtrie_only(&key, value)?;
+            }
+            None => trie_update.generic_delete(0, &key, AccessOptions::DEFAULT)?,
+        };
+    }
+    let memtrie_changes = trie_update.to_memtrie_changes_only();
+    Ok(memtries.apply_memtrie_changes(block_height, &memtrie_changes))
+}
+
+/// Catches up memtries loaded earlier with the flat state deltas of the
+/// blocks applied since then. The deltas which are already applied, and the
+/// ones on top of a state root which the memtries don't have, are skipped.
+pub(crate) fn apply_missing_flat_state_deltas(
+    store: &Store,
+    shard_uid: ShardUId,
+    memtries: &mut MemTries,
+) -> Result<(), StorageError> {
+    let flat_store = store.flat_store();
+    let mut sorted_deltas: BTreeSet<(BlockHeight, CryptoHash, CryptoHash)> = Default::default();
+    for delta in flat_store.get_all_deltas_metadata(shard_uid)? {
+        sorted_deltas.insert((delta.block.height, delta.block.hash, delta.block.prev_hash));
+    }
+
+    let mut num_applied = 0;
+    for (height, hash, prev_hash) in sorted_deltas {
+        let new_state_root = get_state_root(store, hash, shard_uid)?;
+        if memtries.has_root_at_height(&new_state_root, height) {
+            continue;
+        }
+        let old_state_root = get_state_root(store, prev_hash, shard_uid)?;
+        if old_state_root != StateRoot::default() && memtries.get_root(&old_state_root).is_err() {
+            continue;
+        }
+        let Some(changes) = flat_store.get_delta(shard_uid, hash)? else {
+            continue;
+        };
+        let new_root_after_apply =
+            apply_flat_state_changes(memtries, old_state_root, height, changes)?;
+        assert_eq!(new_root_after_apply, new_state_root);
+        num_applied += 1;
+    }
+    debug!(target: "memtrie", %shard_uid, num_applied, "Applied missing flat state deltas");
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::load_trie_from_flat_state_and_delta;
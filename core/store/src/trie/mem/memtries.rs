@@ -74,6 +74,12 @@ impl MemTries {
         }
     }
 
+    /// Whether the memtries still share the frozen memory of the memtries
+    /// they were created from on resharding.
+    pub fn is_hybrid(&self) -> bool {
+        self.arena.has_shared_memory()
+    }
+
     pub fn new_from_arena_and_root(
         shard_uid: ShardUId,
         block_height: BlockHeight,
@@ -138,6 +144,11 @@ impl MemTries {
         })
     }
 
+    /// Returns whether the given state root was inserted at the given height.
+    pub fn has_root_at_height(&self, state_root: &StateRoot, block_height: BlockHeight) -> bool {
+        self.heights.get(&block_height).is_some_and(|state_roots| state_roots.contains(state_root))
+    }
+
     /// Expires all trie roots corresponding to a height smaller than
     /// `block_height`. This internally manages refcounts. If a trie root
     /// is expired but is still used at a higher height, it will still be
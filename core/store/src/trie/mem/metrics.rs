@@ -29,3 +29,12 @@ pub static MEMTRIE_NUM_LOOKUPS: LazyLock<IntCounter> = LazyLock::new(|| {
     )
     .unwrap()
 });
+
+pub static MEMTRIE_LOADING_STATUS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    try_create_int_gauge_vec(
+        "near_memtrie_loading_status",
+        "Status of the in-memory trie loaded in background: 1 - in progress, 2 - done, 3 - failed",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
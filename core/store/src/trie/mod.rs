@@ -8,7 +8,9 @@ pub(crate) use crate::trie::config::{
 };
 pub use crate::trie::nibble_slice::NibbleSlice;
 pub use crate::trie::prefetching_trie_storage::{PrefetchApi, PrefetchError};
-pub use crate::trie::shard_tries::{KeyForStateChanges, ShardTries, WrappedTrieChanges};
+pub use crate::trie::shard_tries::{
+    KeyForStateChanges, MemTrieLoadingStatus, ShardTries, WrappedTrieChanges,
+};
 pub use crate::trie::state_snapshot::{
     STATE_SNAPSHOT_COLUMNS, SnapshotError, StateSnapshot, StateSnapshotConfig, state_snapshots_dir,
 };
@@ -651,6 +653,30 @@ impl Trie {
         trie
     }
 
+    /// Makes a new trie over the same state, which records the accessed nodes
+    /// in its own recorder if this trie records them. The recorded nodes can
+    /// be moved to this trie with [`Self::merge_fork_recorder`].
+    pub fn fork(&self) -> Self {
+        let mut trie = Self::new_with_memtries(
+            self.storage.clone(),
+            self.memtries.clone(),
+            self.children_memtries.clone(),
+            self.root,
+            self.flat_storage_chunk_view.clone(),
+        );
+        trie.recorder = self.recorder.as_ref().map(|_| RwLock::new(TrieRecorder::new(None)));
+        trie.use_access_tracker = self.use_access_tracker;
+        trie
+    }
+
+    /// Records the nodes accessed through a fork of this trie.
+    pub fn merge_fork_recorder(&self, fork: Trie) {
+        let (Some(recorder), Some(fork_recorder)) = (&self.recorder, fork.recorder) else {
+            return;
+        };
+        recorder.write().expect("no poison").merge(fork_recorder.into_inner().expect("no poison"));
+    }
+
     pub fn take_recorder(self) -> Option<RwLock<TrieRecorder>> {
         self.recorder
     }
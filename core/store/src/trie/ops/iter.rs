@@ -116,6 +116,13 @@ where
         Ok(())
     }
 
+    /// Position the iterator on the first element with key >= `key`, unlike
+    /// `seek_prefix` it iterates past the keys starting with `key`.
+    pub fn seek<K: AsRef<[u8]>>(&mut self, key: K) -> Result<(), StorageError> {
+        self.seek_nibble_slice(NibbleSlice::new(key.as_ref()), false)?;
+        Ok(())
+    }
+
     /// Returns the hash of the last node.
     fn seek_nibble_slice(
         &mut self,
@@ -3,9 +3,12 @@ use super::mem::memtries::MemTries;
 use super::state_snapshot::{StateSnapshot, StateSnapshotConfig};
 use crate::adapter::StoreAdapter;
 use crate::adapter::trie_store::{TrieStoreAdapter, TrieStoreUpdateAdapter};
-use crate::flat::FlatStorageManager;
+use crate::flat::{FlatHeadFreezeReason, FlatStateChanges, FlatStorageManager};
 use crate::trie::config::TrieConfig;
-use crate::trie::mem::loading::load_trie_from_flat_state_and_delta;
+use crate::trie::mem::loading::{
+    apply_flat_state_changes, apply_missing_flat_state_deltas, load_trie_from_flat_state_and_delta,
+};
+use crate::trie::mem::metrics::MEMTRIE_LOADING_STATUS;
 use crate::trie::prefetching_trie_storage::PrefetchingThreadsHandle;
 use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
 use crate::trie::{POISONED_LOCK_ERR, TrieRefcountAddition};
@@ -22,6 +25,7 @@ use near_primitives::types::{
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
 struct ShardTriesInner {
@@ -53,6 +57,39 @@ struct ShardTriesInner {
     /// We would like to apply the same set of trie changes to the child memtrie to keep
     /// a consistent view across forks.
     temp_split_shard_map: RwLock<HashMap<ShardUId, Vec<ShardUId>>>,
+    /// Set while the node is short of memory, see `set_low_memory_mode`.
+    low_memory_mode: AtomicBool,
+    /// Status of the memtries loaded in background, see
+    /// `load_memtrie_in_background`. Memtries loaded synchronously aren't
+    /// present here.
+    memtrie_loading_status: Mutex<HashMap<ShardUId, MemTrieLoadingStatus>>,
+    /// Memtries whose loading in background is complete, but which aren't
+    /// used yet.
+    loaded_memtries: Mutex<HashMap<ShardUId, MemTries>>,
+}
+
+/// Status of a memtrie loaded in background.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::IntoStaticStr)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum MemTrieLoadingStatus {
+    /// The memtrie is being loaded, the chunks of the shard are applied with
+    /// the flat storage and the disk trie.
+    InProgress,
+    /// The memtrie is loaded and used to apply the chunks of the shard.
+    Done,
+    /// The loading failed, it is started again when the next chunk of the
+    /// shard is applied.
+    Failed,
+}
+
+impl MemTrieLoadingStatus {
+    fn metric_value(self) -> i64 {
+        match self {
+            MemTrieLoadingStatus::InProgress => 1,
+            MemTrieLoadingStatus::Done => 2,
+            MemTrieLoadingStatus::Failed => 3,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -80,6 +117,9 @@ impl ShardTries {
             state_snapshot: Default::default(),
             state_snapshot_config,
             temp_split_shard_map: Default::default(),
+            low_memory_mode: AtomicBool::new(false),
+            memtrie_loading_status: Default::default(),
+            loaded_memtries: Default::default(),
         }))
     }
 
@@ -126,7 +166,23 @@ impl ShardTries {
     }
 
     fn trie_cache_enabled(&self, shard_uid: ShardUId, is_view: bool) -> bool {
-        is_view || self.get_memtries(shard_uid).is_none()
+        if is_view {
+            return !self.0.low_memory_mode.load(Ordering::Relaxed);
+        }
+        self.get_memtries(shard_uid).is_none()
+    }
+
+    /// In the low memory mode, the view calls read the trie without the view
+    /// caches, which are cleared, and no memtries are loaded on catchup unless
+    /// they are required by resharding. The memtries already loaded are kept,
+    /// as they can be in use by chunk application.
+    pub fn set_low_memory_mode(&self, enabled: bool) {
+        let was_enabled = self.0.low_memory_mode.swap(enabled, Ordering::Relaxed);
+        if enabled && !was_enabled {
+            for cache in self.0.view_caches.lock().expect(POISONED_LOCK_ERR).values() {
+                cache.clear();
+            }
+        }
     }
 
     fn get_trie_for_shard_internal(
@@ -262,6 +318,7 @@ impl ShardTries {
         TrieCachingStorage::new(self.0.store.clone(), cache, shard_uid, is_view, prefetch_api)
     }
 
+    #[track_caller]
     fn apply_deletions_inner(
         &self,
         deletions: &[TrieRefcountSubtraction],
@@ -298,6 +355,7 @@ impl ShardTries {
         self.update_cache(ops, shard_uid);
     }
 
+    #[track_caller]
     fn apply_all_inner(
         &self,
         trie_changes: &TrieChanges,
@@ -342,6 +400,7 @@ impl ShardTries {
         fields(num_deletions = trie_changes.deletions().len(), shard_id = ?shard_uid.shard_id()),
         skip_all,
     )]
+    #[track_caller]
     pub fn apply_deletions(
         &self,
         trie_changes: &TrieChanges,
@@ -358,6 +417,7 @@ impl ShardTries {
         self.apply_deletions_inner(&trie_changes.deletions, shard_uid, store_update)
     }
 
+    #[track_caller]
     pub fn revert_insertions(
         &self,
         trie_changes: &TrieChanges,
@@ -380,6 +440,7 @@ impl ShardTries {
 
     /// NOTE: This method does not update memtries, thus if memtries could be enabled, also call `apply_memtrie_changes`.
     /// TODO: Consider calling apply_memtrie_changes in this function or adding a new function to call both.
+    #[track_caller]
     pub fn apply_all(
         &self,
         trie_changes: &TrieChanges,
@@ -389,12 +450,17 @@ impl ShardTries {
         self.apply_all_inner(trie_changes, shard_uid, true, store_update)
     }
 
+    /// `state_changes` are only used if the chunk was applied without the
+    /// memtrie, right before switching to a memtrie loaded in background.
     pub fn apply_memtrie_changes(
         &self,
         trie_changes: &TrieChanges,
+        state_changes: &[RawStateChangesWithTrieKey],
         shard_uid: ShardUId,
         block_height: BlockHeight,
     ) -> Option<StateRoot> {
+        self.switch_to_loaded_memtrie(shard_uid);
+
         // Apply children memtrie changes in case of forks on parent. Most of the time children_memtrie_changes
         // will be empty. Lookup children_memtrie_changes for more context.
         let split_shard_map_guard = self.0.temp_split_shard_map.read().unwrap();
@@ -411,11 +477,34 @@ impl ShardTries {
         }
 
         if let Some(memtries) = self.get_memtries(shard_uid) {
-            let changes = trie_changes
-                .memtrie_changes
-                .as_ref()
-                .expect("Memtrie changes must be present if memtrie is loaded");
-            Some(memtries.write().unwrap().apply_memtrie_changes(block_height, changes))
+            let mut memtries_guard = memtries.write().unwrap();
+            if let Some(changes) = trie_changes.memtrie_changes.as_ref() {
+                return Some(memtries_guard.apply_memtrie_changes(block_height, changes));
+            }
+            // The chunk was applied before the switch to the memtrie loaded in
+            // background.
+            assert!(
+                self.get_memtrie_loading_status(shard_uid).is_some(),
+                "Memtrie changes must be present if memtrie is loaded"
+            );
+            match apply_flat_state_changes(
+                &mut memtries_guard,
+                trie_changes.old_root,
+                block_height,
+                FlatStateChanges::from_state_changes(state_changes),
+            ) {
+                Ok(new_root) => {
+                    assert_eq!(new_root, trie_changes.new_root);
+                    Some(new_root)
+                }
+                Err(err) => {
+                    tracing::warn!(target: "memtrie", %shard_uid, ?err, "Failed to apply state changes to memtrie loaded in background, reloading it");
+                    drop(memtries_guard);
+                    self.unload_memtrie(&shard_uid);
+                    self.load_memtrie_in_background(shard_uid);
+                    None
+                }
+            }
         } else {
             assert!(
                 trie_changes.memtrie_changes.is_none(),
@@ -431,6 +520,25 @@ impl ShardTries {
         tracing::info!(target: "memtrie", "Current memtries: {:?}. Keeping memtries for shards {:?}...",
             self.0.memtries.read().unwrap().keys(), shard_uids);
         self.0.memtries.write().unwrap().retain(|shard_uid, _| shard_uids.contains(shard_uid));
+        // The status is cleared first, so that a memtrie still loading in
+        // background for a removed shard is dropped once loaded.
+        let loading_shard_uids = self
+            .0
+            .memtrie_loading_status
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|shard_uid| !shard_uids.contains(shard_uid))
+            .copied()
+            .collect_vec();
+        for shard_uid in loading_shard_uids {
+            self.finish_memtrie_loading(shard_uid, None);
+        }
+        self.0
+            .loaded_memtries
+            .lock()
+            .unwrap()
+            .retain(|shard_uid, _| shard_uids.contains(shard_uid));
         tracing::info!(target: "memtrie", "Memtries retaining complete for shards {:?}", shard_uids);
     }
 
@@ -460,6 +568,115 @@ impl ShardTries {
         Ok(())
     }
 
+    /// Starts loading the in-memory trie of the shard in a background thread.
+    /// The node switches to it when the next block of the shard is applied
+    /// after the loading is complete, see `switch_to_loaded_memtrie`.
+    ///
+    /// The flat head of the shard doesn't move until then, so that the flat
+    /// state the memtrie is loaded from stays the same, and the deltas of the
+    /// blocks applied in the meantime are still available to catch up with.
+    pub fn load_memtrie_in_background(&self, shard_uid: ShardUId) {
+        if let Some(flat_storage) =
+            self.0.flat_storage_manager.get_flat_storage_for_shard(shard_uid)
+        {
+            flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, false);
+        }
+        self.set_memtrie_loading_status(shard_uid, Some(MemTrieLoadingStatus::InProgress));
+
+        let tries = self.clone();
+        std::thread::Builder::new()
+            .name(format!("memtrie-loader-{shard_uid}"))
+            .spawn(move || {
+                tracing::info!(target: "memtrie", %shard_uid, "Loading trie to memory in background...");
+                match load_trie_from_flat_state_and_delta(
+                    &tries.0.store.store(),
+                    shard_uid,
+                    None,
+                    false,
+                ) {
+                    Ok(memtries) => {
+                        let statuses = tries.0.memtrie_loading_status.lock().unwrap();
+                        if statuses.get(&shard_uid) != Some(&MemTrieLoadingStatus::InProgress) {
+                            tracing::info!(target: "memtrie", %shard_uid, "Memtrie loaded in background is not needed anymore");
+                            return;
+                        }
+                        tries.0.loaded_memtries.lock().unwrap().insert(shard_uid, memtries);
+                        tracing::info!(target: "memtrie", %shard_uid, "Memtrie loading in background complete");
+                    }
+                    Err(err) => {
+                        tracing::error!(target: "memtrie", %shard_uid, ?err, "Failed to load memtrie in background");
+                        if tries.get_memtrie_loading_status(shard_uid)
+                            == Some(MemTrieLoadingStatus::InProgress)
+                        {
+                            tries.finish_memtrie_loading(
+                                shard_uid,
+                                Some(MemTrieLoadingStatus::Failed),
+                            );
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the memtrie loading thread");
+    }
+
+    /// Starts using the memtrie of the shard loaded in background, if its
+    /// loading is complete. The memtrie is first caught up with the blocks
+    /// applied while it was loading. A failed loading is started again.
+    fn switch_to_loaded_memtrie(&self, shard_uid: ShardUId) {
+        if self.get_memtrie_loading_status(shard_uid) == Some(MemTrieLoadingStatus::Failed) {
+            tracing::info!(target: "memtrie", %shard_uid, "Retrying to load memtrie in background");
+            self.load_memtrie_in_background(shard_uid);
+            return;
+        }
+        let Some(mut memtries) = self.0.loaded_memtries.lock().unwrap().remove(&shard_uid) else {
+            return;
+        };
+        if let Err(err) =
+            apply_missing_flat_state_deltas(&self.0.store.store(), shard_uid, &mut memtries)
+        {
+            tracing::error!(target: "memtrie", %shard_uid, ?err, "Failed to catch up memtrie loaded in background");
+            self.finish_memtrie_loading(shard_uid, Some(MemTrieLoadingStatus::Failed));
+            return;
+        }
+        self.0.memtries.write().unwrap().insert(shard_uid, Arc::new(RwLock::new(memtries)));
+        self.finish_memtrie_loading(shard_uid, Some(MemTrieLoadingStatus::Done));
+        tracing::info!(target: "memtrie", %shard_uid, "Switched to memtrie loaded in background");
+    }
+
+    fn finish_memtrie_loading(&self, shard_uid: ShardUId, status: Option<MemTrieLoadingStatus>) {
+        if let Some(flat_storage) =
+            self.0.flat_storage_manager.get_flat_storage_for_shard(shard_uid)
+        {
+            flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, true);
+        }
+        self.set_memtrie_loading_status(shard_uid, status);
+    }
+
+    fn set_memtrie_loading_status(
+        &self,
+        shard_uid: ShardUId,
+        status: Option<MemTrieLoadingStatus>,
+    ) {
+        let metric = MEMTRIE_LOADING_STATUS.with_label_values(&[&shard_uid.to_string()]);
+        let mut statuses = self.0.memtrie_loading_status.lock().unwrap();
+        match status {
+            Some(status) => {
+                metric.set(status.metric_value());
+                statuses.insert(shard_uid, status);
+            }
+            None => {
+                metric.set(0);
+                statuses.remove(&shard_uid);
+            }
+        }
+    }
+
+    /// Returns the status of the memtrie of the shard if it is loaded in
+    /// background, or `None` otherwise.
+    pub fn get_memtrie_loading_status(&self, shard_uid: ShardUId) -> Option<MemTrieLoadingStatus> {
+        self.0.memtrie_loading_status.lock().unwrap().get(&shard_uid).copied()
+    }
+
     /// Loads in-memory trie upon catchup, if it is enabled.
     /// Requires state root because `ChunkExtra` is not available at the time mem-trie is being loaded.
     /// Mem-tries of shards that are pending resharding must be loaded in any case.
@@ -469,7 +686,8 @@ impl ShardTries {
         state_root: &StateRoot,
         shard_uids_pending_resharding: &HashSet<ShardUId>,
     ) -> Result<(), StorageError> {
-        if !self.0.trie_config.load_memtries_for_tracked_shards
+        if (!self.0.trie_config.load_memtries_for_tracked_shards
+            || self.0.low_memory_mode.load(Ordering::Relaxed))
             && !shard_uids_pending_resharding.contains(shard_uid)
         {
             return Ok(());
@@ -512,6 +730,17 @@ impl ShardTries {
             ?shard_uids_pending_resharding,
             "Loading tries config"
         );
+        // The memtries of the shards pending resharding are required by the
+        // resharding, hence they are always loaded synchronously.
+        let (shard_uids_to_load, shard_uids_to_load_in_background): (Vec<_>, Vec<_>) =
+            shard_uids_to_load.into_iter().partition(|shard_uid| {
+                !trie_config.load_memtries_in_background
+                    || shard_uids_pending_resharding.contains(shard_uid)
+            });
+        for shard_uid in shard_uids_to_load_in_background {
+            self.load_memtrie_in_background(*shard_uid);
+        }
+
         tracing::info!(target: "memtrie", "Loading tries to memory for shards {:?}...", shard_uids_to_load);
         shard_uids_to_load
             .par_iter()
@@ -627,7 +856,12 @@ impl WrappedTrieChanges {
     }
 
     pub fn apply_mem_changes(&self) {
-        self.tries.apply_memtrie_changes(&self.trie_changes, self.shard_uid, self.block_height);
+        self.tries.apply_memtrie_changes(
+            &self.trie_changes,
+            &self.state_changes,
+            self.shard_uid,
+            self.block_height,
+        );
     }
 
     /// Save insertions of trie nodes into Store.
@@ -816,6 +1050,10 @@ impl KeyForStateChanges {
 #[cfg(test)]
 mod test {
     use crate::adapter::StoreAdapter;
+    use crate::flat::test_utils::MockChain;
+    use crate::flat::{
+        FlatStateDelta, FlatStateDeltaMetadata, FlatStorageReadyStatus, FlatStorageStatus,
+    };
     use crate::{
         TrieConfig, config::TrieCacheConfig, test_utils::create_test_store,
         trie::DEFAULT_SHARD_CACHE_TOTAL_SIZE_LIMIT,
@@ -943,6 +1181,25 @@ mod test {
         assert!(trie_caches.lock().unwrap().get(&shard_uid).unwrap().get(&key).is_none());
     }
 
+    #[test]
+    fn test_low_memory_mode() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = create_trie();
+        let key = CryptoHash::hash_borsh("alice");
+        let val: Vec<u8> = Vec::from([0, 1, 2, 3, 4]);
+        let view_cache = tries.get_trie_cache_for(shard_uid, true).unwrap();
+        view_cache.update_cache(Vec::from([(&key, Some(val.as_slice()))]));
+
+        // The view cache is cleared and not used anymore.
+        tries.set_low_memory_mode(true);
+        assert!(view_cache.get(&key).is_none());
+        assert!(tries.get_trie_cache_for(shard_uid, true).is_none());
+        assert!(tries.get_trie_cache_for(shard_uid, false).is_some());
+
+        tries.set_low_memory_mode(false);
+        assert!(tries.get_trie_cache_for(shard_uid, true).is_some());
+    }
+
     #[test]
     fn test_shard_cache_max_value() {
         let store = create_test_store();
@@ -986,4 +1243,49 @@ mod test {
         trie.update_cache(insert_ops, shard_uid);
         assert!(trie_caches.lock().unwrap().get(&shard_uid).unwrap().get(&key).is_none());
     }
+
+    /// Removing the memtrie of a shard which is loading in background lets its
+    /// flat head move again.
+    #[test]
+    fn test_retain_memtries_clears_loading_status() {
+        let shard_uid = ShardUId::single_shard();
+        let chain = MockChain::linear_chain(5);
+        let store = create_test_store();
+        let mut store_update = store.flat_store().store_update();
+        store_update.set_flat_storage_status(
+            shard_uid,
+            FlatStorageStatus::Ready(FlatStorageReadyStatus { flat_head: chain.get_block(0) }),
+        );
+        for height in 1..5 {
+            let delta = FlatStateDelta {
+                changes: FlatStateChanges::default(),
+                metadata: FlatStateDeltaMetadata {
+                    block: chain.get_block(height),
+                    prev_block_with_changes: None,
+                },
+            };
+            store_update.set_delta(shard_uid, &delta);
+        }
+        store_update.commit().unwrap();
+        let flat_storage_manager = FlatStorageManager::new(store.flat_store());
+        flat_storage_manager.create_flat_storage_for_shard(shard_uid).unwrap();
+        let tries = ShardTries::new(
+            store.trie_store(),
+            TrieConfig::default(),
+            &[shard_uid],
+            flat_storage_manager.clone(),
+            StateSnapshotConfig::Disabled,
+        );
+        let flat_storage = flat_storage_manager.get_flat_storage_for_shard(shard_uid).unwrap();
+
+        flat_storage.set_flat_head_update_mode(FlatHeadFreezeReason::MemtrieLoading, false);
+        tries.set_memtrie_loading_status(shard_uid, Some(MemTrieLoadingStatus::InProgress));
+        flat_storage.update_flat_head(&chain.get_block_hash(4)).unwrap();
+        assert_eq!(flat_storage.get_head_hash(), chain.get_block_hash(0));
+
+        tries.retain_memtries(&[]);
+        assert_eq!(tries.get_memtrie_loading_status(shard_uid), None);
+        flat_storage.update_flat_head(&chain.get_block_hash(4)).unwrap();
+        assert_eq!(flat_storage.get_head_hash(), chain.get_block_hash(4));
+    }
 }
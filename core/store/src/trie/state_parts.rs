@@ -11,6 +11,11 @@
 //! corresponds to some key-value pair and prefix memory usage for it is greater
 //! than a threshold.
 //!
+//! With `ProtocolFeature::StatePartsAlignedToAccounts`, a boundary which falls
+//! inside the keys of a single account in some trie column (e.g. in the middle of
+//! its contract data) is moved back to the first key of that account in the column,
+//! unless that would make the boundary not greater than the previous one.
+//!
 //! We include paths from root to both boundaries in state parts, because they are
 //! necessary for receiver to prove existence of all nodes knowing just a state root.
 //! Moreover, we include all left siblings for each path, because they are
@@ -30,7 +35,9 @@ use near_primitives::state::FlatStateValue;
 use near_primitives::state::PartialState;
 use near_primitives::state_part::PartId;
 use near_primitives::state_record::is_contract_code_key;
+use near_primitives::trie_key::trie_key_parsers::parse_account_id_from_raw_key;
 use near_primitives::types::{ShardId, StateRoot};
+use near_primitives::version::{ProtocolFeature, ProtocolVersion};
 use near_vm_runner::ContractCode;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -57,6 +64,30 @@ impl Trie {
         &self,
         part_id: u64,
         num_parts: u64,
+        protocol_version: ProtocolVersion,
+    ) -> Result<Vec<u8>, StorageError> {
+        let boundary = self.find_memory_usage_boundary(part_id, num_parts)?;
+        if !ProtocolFeature::StatePartsAlignedToAccounts.enabled(protocol_version)
+            || part_id == 0
+            || boundary == LAST_STATE_PART_BOUNDARY
+        {
+            return Ok(boundary);
+        }
+        let Some(aligned_boundary) = self.align_boundary_to_account(&boundary)? else {
+            return Ok(boundary);
+        };
+        // Don't let a large account swallow the previous part. The previous
+        // boundary is either its memory usage boundary or aligned before it.
+        let prev_boundary = self.find_memory_usage_boundary(part_id - 1, num_parts)?;
+        if aligned_boundary > prev_boundary { Ok(aligned_boundary) } else { Ok(boundary) }
+    }
+
+    /// Finds the boundary node of the `part_id`-th part by memory usage only,
+    /// see `find_state_part_boundary`.
+    fn find_memory_usage_boundary(
+        &self,
+        part_id: u64,
+        num_parts: u64,
     ) -> Result<Vec<u8>, StorageError> {
         if part_id > num_parts {
             return Err(StorageError::StorageInternalError);
@@ -73,14 +104,79 @@ impl Trie {
         self.find_node_in_dfs_order(&root_node, size_start)
     }
 
+    /// If the state key given by `boundary` in nibbles belongs to some account,
+    /// returns the first state key in nibbles with the same column and account.
+    /// Returns None if the key doesn't contain an account id or if it is already
+    /// the first key of its account.
+    fn align_boundary_to_account(&self, boundary: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        let key = NibbleSlice::nibbles_to_bytes(boundary);
+        let Ok(Some(account_id)) = parse_account_id_from_raw_key(&key) else {
+            return Ok(None);
+        };
+        let mut account_prefix = key[..1].to_vec();
+        account_prefix.extend(account_id.as_bytes());
+        if account_prefix.len() == key.len() {
+            return Ok(None);
+        }
+        let account_prefix: Vec<u8> = NibbleSlice::new(&account_prefix).iter().collect();
+        self.find_first_key_with_prefix(&account_prefix).map(Some)
+    }
+
+    /// Returns the smallest state key in nibbles starting with `prefix`.
+    /// The prefix must be a prefix of some state key. Reads only trie nodes,
+    /// not values.
+    fn find_first_key_with_prefix(&self, prefix: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let mut key_nibbles: Vec<u8> = Vec::new();
+        let mut node = self.retrieve_storage_node(&self.root)?;
+        loop {
+            let child = match &node.node {
+                TrieStorageNode::Empty => break,
+                TrieStorageNode::Leaf { extension, .. } => {
+                    let (slice, _) = NibbleSlice::from_encoded(extension);
+                    key_nibbles.extend(slice.iter());
+                    break;
+                }
+                TrieStorageNode::Branch { children, value } => {
+                    if key_nibbles.len() >= prefix.len() && value.is_some() {
+                        break;
+                    }
+                    // Follow the prefix, then descend into the leftmost child.
+                    let index = if key_nibbles.len() < prefix.len() {
+                        prefix[key_nibbles.len()] as usize
+                    } else {
+                        (0..16).find(|index| children[*index].is_some()).unwrap_or_default()
+                    };
+                    let Some(child) = children[index] else {
+                        break;
+                    };
+                    key_nibbles.push(index as u8);
+                    child
+                }
+                TrieStorageNode::Extension { extension, child } => {
+                    let (slice, _) = NibbleSlice::from_encoded(extension);
+                    key_nibbles.extend(slice.iter());
+                    *child
+                }
+            };
+            node = self.retrieve_storage_node(&child)?;
+        }
+        if !key_nibbles.starts_with(prefix) {
+            return Err(StorageError::StorageInconsistentState(format!(
+                "No state key with prefix {prefix:?}, found {key_nibbles:?}"
+            )));
+        }
+        Ok(key_nibbles)
+    }
+
     /// Generates state parts using the trie storage (i.e. State) and not using
     /// flat storage (i.e. FlatState).
     pub fn get_trie_nodes_for_part_without_flat_storage(
         &self,
         part_id: PartId,
+        protocol_version: ProtocolVersion,
     ) -> Result<PartialState, StorageError> {
         let with_recording = self.recording_reads_new_recorder();
-        with_recording.visit_nodes_for_state_part(part_id)?;
+        with_recording.visit_nodes_for_state_part(part_id, protocol_version)?;
         let recorded = with_recording.recorded_storage().unwrap();
         Ok(recorded.nodes)
     }
@@ -125,6 +221,7 @@ impl Trie {
     pub fn get_state_part_boundaries(
         &self,
         part_id: PartId,
+        protocol_version: ProtocolVersion,
     ) -> Result<(PartialState, Vec<u8>, Vec<u8>), StorageError> {
         // If chunk view is missing us ShardId::max() as fake value for metrics.
         let shard_id = self
@@ -149,9 +246,10 @@ impl Trie {
         let boundaries_read_timer = metrics::GET_STATE_PART_BOUNDARIES_ELAPSED
             .with_label_values(&[&shard_id.to_string()])
             .start_timer();
-        let nibbles_begin = recording_trie.find_state_part_boundary(part_id.idx, part_id.total)?;
+        let nibbles_begin =
+            recording_trie.find_state_part_boundary(idx, total, protocol_version)?;
         let nibbles_end =
-            recording_trie.find_state_part_boundary(part_id.idx + 1, part_id.total)?;
+            recording_trie.find_state_part_boundary(idx + 1, total, protocol_version)?;
         let boundaries_read_duration = boundaries_read_timer.stop_and_record();
         let recorded_trie = recording_trie.recorded_storage().unwrap();
 
@@ -173,6 +271,7 @@ impl Trie {
     /// * partial_state - nodes needed to generate and proof state part boundaries.
     /// * nibbles_begin and nibbles_end specify the range of flat storage to be read.
     /// * state_trie - provides access to State for random lookups of values by hash.
+    /// * protocol_version - must be the same as the one used to find the boundaries.
     pub fn get_trie_nodes_for_part_with_flat_storage(
         &self,
         part_id: PartId,
@@ -180,6 +279,7 @@ impl Trie {
         nibbles_begin: Vec<u8>,
         nibbles_end: Vec<u8>,
         state_trie: &Trie,
+        protocol_version: ProtocolVersion,
     ) -> Result<PartialState, StorageError> {
         // If chunk view is missing us ShardId::max() as fake value for metrics.
         let shard_id = self
@@ -270,7 +370,7 @@ impl Trie {
         let final_trie =
             Trie::new(Arc::new(TrieMemoryPartialStorage::new(all_nodes)), self.root, None);
 
-        final_trie.visit_nodes_for_state_part(part_id)?;
+        final_trie.visit_nodes_for_state_part(part_id, protocol_version)?;
         let final_trie_storage = final_trie.storage.as_partial_storage().unwrap();
         let final_state_part_nodes = final_trie_storage.partial_state();
         let PartialState::TrieValues(trie_values) = &final_state_part_nodes;
@@ -320,9 +420,15 @@ impl Trie {
     ///
     /// Creating a StatePart takes all these nodes, validating a StatePart checks that it has the
     /// right set of nodes.
-    fn visit_nodes_for_state_part(&self, part_id: PartId) -> Result<(), StorageError> {
-        let path_begin = self.find_state_part_boundary(part_id.idx, part_id.total)?;
-        let path_end = self.find_state_part_boundary(part_id.idx + 1, part_id.total)?;
+    fn visit_nodes_for_state_part(
+        &self,
+        part_id: PartId,
+        protocol_version: ProtocolVersion,
+    ) -> Result<(), StorageError> {
+        let path_begin =
+            self.find_state_part_boundary(part_id.idx, part_id.total, protocol_version)?;
+        let path_end =
+            self.find_state_part_boundary(part_id.idx + 1, part_id.total, protocol_version)?;
 
         let mut iterator = self.disk_iter()?;
         let nodes_list = iterator.visit_nodes_interval(&path_begin, &path_end)?;
@@ -415,11 +521,14 @@ impl Trie {
     }
 
     /// Validates state part for given state root.
+    /// `protocol_version` determines how the part boundaries are computed and
+    /// must be the same as the one used to generate the part.
     /// Returns error if state part is invalid and Ok otherwise.
     pub fn validate_state_part(
         state_root: &StateRoot,
         part_id: PartId,
         partial_state: PartialState,
+        protocol_version: ProtocolVersion,
     ) -> Result<(), StorageError> {
         let PartialState::TrieValues(nodes) = &partial_state;
         let num_nodes = nodes.len();
@@ -429,7 +538,7 @@ impl Trie {
             false,
         );
 
-        trie.visit_nodes_for_state_part(part_id)?;
+        trie.visit_nodes_for_state_part(part_id, protocol_version)?;
         let storage = trie.storage.as_partial_storage().unwrap();
 
         if storage.visited_nodes.read().expect("read visited_nodes").len() != num_nodes {
@@ -444,6 +553,7 @@ impl Trie {
         state_root: &StateRoot,
         part_id: PartId,
         part: PartialState,
+        protocol_version: ProtocolVersion,
     ) -> Result<ApplyStatePartResult, StorageError> {
         if state_root == &Trie::EMPTY_ROOT {
             return Ok(ApplyStatePartResult {
@@ -453,8 +563,10 @@ impl Trie {
             });
         }
         let trie = Trie::from_recorded_storage(PartialStorage { nodes: part }, *state_root, false);
-        let path_begin = trie.find_state_part_boundary(part_id.idx, part_id.total)?;
-        let path_end = trie.find_state_part_boundary(part_id.idx + 1, part_id.total)?;
+        let path_begin =
+            trie.find_state_part_boundary(part_id.idx, part_id.total, protocol_version)?;
+        let path_end =
+            trie.find_state_part_boundary(part_id.idx + 1, part_id.total, protocol_version)?;
         let mut iterator = trie.disk_iter()?;
         let trie_traversal_items = iterator.visit_nodes_interval(&path_begin, &path_end)?;
         let mut refcount_changes = TrieRefcountDeltaMap::new();
@@ -492,8 +604,9 @@ impl Trie {
         state_root: &StateRoot,
         part_id: PartId,
         part: PartialState,
+        protocol_version: ProtocolVersion,
     ) -> ApplyStatePartResult {
-        Self::apply_state_part_impl(state_root, part_id, part)
+        Self::apply_state_part_impl(state_root, part_id, part, protocol_version)
             .expect("apply_state_part is guaranteed to succeed when each part is valid")
     }
 
@@ -539,6 +652,8 @@ mod tests {
     use super::*;
     use crate::MissingTrieValueContext;
     use near_primitives::shard_layout::ShardUId;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::version::PROTOCOL_VERSION;
 
     /// Checks that sampling state boundaries always gives valid state keys
     /// even if trie contains intermediate nodes.
@@ -562,18 +677,21 @@ mod tests {
             test_populate_trie(&tries, &Trie::EMPTY_ROOT, ShardUId::single_shard(), trie_changes);
         let trie = tries.get_trie_for_shard(ShardUId::single_shard(), state_root);
 
-        let nibbles_boundary = trie.find_state_part_boundary(0, num_parts).unwrap();
+        let nibbles_boundary =
+            trie.find_state_part_boundary(0, num_parts, PROTOCOL_VERSION).unwrap();
         assert!(nibbles_boundary.is_empty());
 
         // Check that all boundaries correspond to some state key by calling `Trie::get`.
         // Note that some state parts can be trivial, which is not a concern.
         for part_id in 1..num_parts {
-            let nibbles_boundary = trie.find_state_part_boundary(part_id, num_parts).unwrap();
+            let nibbles_boundary =
+                trie.find_state_part_boundary(part_id, num_parts, PROTOCOL_VERSION).unwrap();
             let key_boundary = NibbleSlice::nibbles_to_bytes(&nibbles_boundary);
             assert_matches!(trie.get(&key_boundary, AccessOptions::DEFAULT), Ok(Some(_)));
         }
 
-        let nibbles_boundary = trie.find_state_part_boundary(num_parts, num_parts).unwrap();
+        let nibbles_boundary =
+            trie.find_state_part_boundary(num_parts, num_parts, PROTOCOL_VERSION).unwrap();
         assert_eq!(nibbles_boundary, LAST_STATE_PART_BOUNDARY);
     }
 
@@ -606,13 +724,81 @@ mod tests {
         let trie = tries.get_trie_for_shard(ShardUId::single_shard(), state_root);
 
         for part_id in 1..num_parts {
-            let nibbles_boundary =
-                trie.find_state_part_boundary(part_id as u64, num_parts as u64).unwrap();
+            let nibbles_boundary = trie
+                .find_state_part_boundary(part_id as u64, num_parts as u64, PROTOCOL_VERSION)
+                .unwrap();
             let key_boundary = NibbleSlice::nibbles_to_bytes(&nibbles_boundary);
             assert_eq!(key_boundary, trie_changes[part_id - 1].0);
         }
     }
 
+    /// Checks that with `StatePartsAlignedToAccounts` boundaries are moved to the
+    /// first key of their account whenever it keeps them increasing, and that
+    /// the resulting parts are still valid and cover the whole trie.
+    #[test]
+    fn boundary_aligned_to_account() {
+        let value_len = 1000usize;
+        let accounts = ["alice.near", "bob.near", "carol.near"];
+        let contract_data_key = |account: &str, i: u8| {
+            TrieKey::ContractData { account_id: account.parse().unwrap(), key: vec![i] }.to_vec()
+        };
+        let trie_changes = accounts
+            .iter()
+            .flat_map(|account| {
+                (0..10).map(|i| (contract_data_key(account, i), Some(vec![i; value_len])))
+            })
+            .collect::<Vec<_>>();
+
+        let tries = TestTriesBuilder::new().build();
+        let state_root =
+            test_populate_trie(&tries, &Trie::EMPTY_ROOT, ShardUId::single_shard(), trie_changes);
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard(), state_root);
+
+        let new_version = ProtocolFeature::StatePartsAlignedToAccounts.protocol_version();
+        let old_version = new_version - 1;
+        let mut num_aligned = 0;
+        for num_parts in 2..10 {
+            let mut prev_boundary = vec![];
+            for part_id in 1..num_parts {
+                let boundary =
+                    trie.find_state_part_boundary(part_id, num_parts, old_version).unwrap();
+                let aligned_boundary =
+                    trie.find_state_part_boundary(part_id, num_parts, new_version).unwrap();
+                // Boundaries in the middle of an account id are left as is.
+                let key = NibbleSlice::nibbles_to_bytes(&boundary);
+                let expected_boundary = match parse_account_id_from_raw_key(&key) {
+                    Ok(Some(account_id)) => {
+                        let first_key = contract_data_key(account_id.as_str(), 0);
+                        let first_key: Vec<u8> = NibbleSlice::new(&first_key).iter().collect();
+                        if first_key > prev_boundary { first_key } else { boundary.clone() }
+                    }
+                    _ => boundary.clone(),
+                };
+                assert_eq!(aligned_boundary, expected_boundary);
+                if aligned_boundary != boundary {
+                    num_aligned += 1;
+                }
+                prev_boundary = boundary;
+            }
+
+            let parts = (0..num_parts)
+                .map(|part_id| {
+                    let part_id = PartId::new(part_id, num_parts);
+                    let part = trie
+                        .get_trie_nodes_for_part_without_flat_storage(part_id, new_version)
+                        .unwrap();
+                    assert_eq!(
+                        Trie::validate_state_part(&state_root, part_id, part.clone(), new_version),
+                        Ok(())
+                    );
+                    part
+                })
+                .collect::<Vec<_>>();
+            check_combine_state_parts(&state_root, num_parts, &parts, new_version);
+        }
+        assert!(num_aligned > 0);
+    }
+
     impl Trie {
         /// Combines all parts and returns TrieChanges that can be applied to storage.
         ///
@@ -742,12 +928,14 @@ mod tests {
             &state_root,
             PartId::new(0, 1),
             PartialState::TrieValues(vec![]),
+            PROTOCOL_VERSION,
         )
         .unwrap();
         let _ = Trie::apply_state_part(
             &state_root,
             PartId::new(0, 1),
             PartialState::TrieValues(vec![]),
+            PROTOCOL_VERSION,
         );
     }
 
@@ -841,8 +1029,9 @@ mod tests {
                 // Compute proof with size and check that it doesn't exceed theoretical boundary for
                 // the path with full set of left siblings of maximal possible size.
                 let trie_recording = trie.recording_reads_new_recorder();
-                let left_nibbles_boundary =
-                    trie_recording.find_state_part_boundary(part_id, num_parts).unwrap();
+                let left_nibbles_boundary = trie_recording
+                    .find_state_part_boundary(part_id, num_parts, PROTOCOL_VERSION)
+                    .unwrap();
                 let left_key_boundary = NibbleSlice::nibbles_to_bytes(&left_nibbles_boundary);
                 if part_id != 0 {
                     assert_matches!(
@@ -863,7 +1052,10 @@ mod tests {
                 );
 
                 let PartialState::TrieValues(part_nodes) = trie
-                    .get_trie_nodes_for_part_without_flat_storage(PartId::new(part_id, num_parts))
+                    .get_trie_nodes_for_part_without_flat_storage(
+                        PartId::new(part_id, num_parts),
+                        PROTOCOL_VERSION,
+                    )
                     .unwrap();
                 // TODO (#8997): it's a bit weird that raw lengths are compared to
                 // config values. Consider better defined assertion.
@@ -941,14 +1133,16 @@ mod tests {
                 let num_parts = rng.gen_range(2..10);
                 let parts = (0..num_parts)
                     .map(|part_id| {
-                        trie.get_trie_nodes_for_part_without_flat_storage(PartId::new(
-                            part_id, num_parts,
-                        ))
+                        trie.get_trie_nodes_for_part_without_flat_storage(
+                            PartId::new(part_id, num_parts),
+                            PROTOCOL_VERSION,
+                        )
                         .unwrap()
                     })
                     .collect::<Vec<_>>();
 
-                let trie_changes = check_combine_state_parts(trie.get_root(), num_parts, &parts);
+                let trie_changes =
+                    check_combine_state_parts(trie.get_root(), num_parts, &parts, PROTOCOL_VERSION);
 
                 let mut nodes = <HashMap<CryptoHash, Arc<[u8]>>>::new();
                 let sizes_vec = parts
@@ -973,6 +1167,7 @@ mod tests {
                         trie.get_root(),
                         PartId::new(0, 1),
                         PartialState::TrieValues(all_nodes),
+                        PROTOCOL_VERSION,
                     ),
                     Ok(())
                 );
@@ -1018,6 +1213,7 @@ mod tests {
         state_root: &CryptoHash,
         num_parts: u64,
         parts: &[PartialState],
+        protocol_version: ProtocolVersion,
     ) -> TrieChanges {
         let trie_changes = Trie::combine_state_parts_naive(state_root, parts).unwrap();
 
@@ -1028,6 +1224,7 @@ mod tests {
                         state_root,
                         PartId::new(part_id, num_parts),
                         parts[part_id as usize].clone(),
+                        protocol_version,
                     )
                     .trie_changes
                 })
@@ -1070,7 +1267,7 @@ mod tests {
 
         let trie = tries.get_view_trie_for_shard(shard_uid, root);
         let PartialState::TrieValues(trie_values) = trie
-            .get_trie_nodes_for_part_without_flat_storage(part_id)
+            .get_trie_nodes_for_part_without_flat_storage(part_id, PROTOCOL_VERSION)
             .expect("State part generation using Trie must work");
         let num_trie_values = trie_values.len();
         assert!(num_trie_values >= 2);
@@ -1081,7 +1278,10 @@ mod tests {
             let mut trie_values_shuffled = trie_values.clone();
             trie_values_shuffled.shuffle(&mut rng);
             let state_part = PartialState::TrieValues(trie_values_shuffled);
-            assert_eq!(Trie::validate_state_part(&root, part_id, state_part), Ok(()));
+            assert_eq!(
+                Trie::validate_state_part(&root, part_id, state_part, PROTOCOL_VERSION),
+                Ok(())
+            );
         }
 
         // Remove middle element from state part, check that validation fails.
@@ -1090,7 +1290,7 @@ mod tests {
         let wrong_state_part = PartialState::TrieValues(trie_values_missing);
 
         assert_matches!(
-            Trie::validate_state_part(&root, part_id, wrong_state_part),
+            Trie::validate_state_part(&root, part_id, wrong_state_part, PROTOCOL_VERSION),
             Err(StorageError::MissingTrieValue(
                 MissingTrieValueContext::TrieMemoryPartialStorage,
                 _
@@ -1102,7 +1302,7 @@ mod tests {
         trie_values_extra.push(vec![11].into());
         let wrong_state_part = PartialState::TrieValues(trie_values_extra);
         assert_eq!(
-            Trie::validate_state_part(&root, part_id, wrong_state_part),
+            Trie::validate_state_part(&root, part_id, wrong_state_part, PROTOCOL_VERSION),
             Err(StorageError::UnexpectedTrieValue)
         );
 
@@ -1113,7 +1313,7 @@ mod tests {
             .push(trie_values_extra_same[trie_values_extra_same.len() / 2].clone());
         let wrong_state_part = PartialState::TrieValues(trie_values_extra_same);
         assert_eq!(
-            Trie::validate_state_part(&root, part_id, wrong_state_part),
+            Trie::validate_state_part(&root, part_id, wrong_state_part, PROTOCOL_VERSION),
             Err(StorageError::UnexpectedTrieValue)
         );
     }
@@ -1140,13 +1340,17 @@ mod tests {
                 let num_parts: u64 = rng.gen_range(1..10);
                 let part_id = rng.gen_range(0..num_parts);
                 let trie_nodes = trie
-                    .get_trie_nodes_for_part_without_flat_storage(PartId::new(part_id, num_parts))
+                    .get_trie_nodes_for_part_without_flat_storage(
+                        PartId::new(part_id, num_parts),
+                        PROTOCOL_VERSION,
+                    )
                     .unwrap();
                 assert_eq!(
                     Trie::validate_state_part(
                         trie.get_root(),
                         PartId::new(part_id, num_parts),
                         trie_nodes,
+                        PROTOCOL_VERSION,
                     ),
                     Ok(())
                 );
@@ -1186,15 +1390,18 @@ mod tests {
         // Get correct state part using trie without flat storage.
         let trie_without_flat = tries.get_view_trie_for_shard(shard_uid, root);
         let state_part = trie_without_flat
-            .get_trie_nodes_for_part_without_flat_storage(part_id)
+            .get_trie_nodes_for_part_without_flat_storage(part_id, PROTOCOL_VERSION)
             .expect("State part generation using Trie must work");
-        assert_eq!(Trie::validate_state_part(&root, part_id, state_part.clone()), Ok(()));
+        assert_eq!(
+            Trie::validate_state_part(&root, part_id, state_part.clone(), PROTOCOL_VERSION),
+            Ok(())
+        );
         assert!(state_part.len() > 0);
 
         // Check that if we try to use flat storage but it is empty, state part
         // creation fails.
         let (partial_state, nibbles_begin, nibbles_end) =
-            trie_without_flat.get_state_part_boundaries(part_id).unwrap();
+            trie_without_flat.get_state_part_boundaries(part_id, PROTOCOL_VERSION).unwrap();
 
         let view_chunk_trie =
             tries.get_trie_with_block_hash_for_shard(shard_uid, root, &block_hash, true);
@@ -1206,6 +1413,7 @@ mod tests {
                 nibbles_begin,
                 nibbles_end,
                 &trie_without_flat,
+                PROTOCOL_VERSION,
             ),
             Err(StorageError::MissingTrieValue(
                 MissingTrieValueContext::TrieMemoryPartialStorage,
@@ -1222,7 +1430,7 @@ mod tests {
         store_update.commit().unwrap();
 
         let (partial_state, nibbles_begin, nibbles_end) =
-            trie_without_flat.get_state_part_boundaries(part_id).unwrap();
+            trie_without_flat.get_state_part_boundaries(part_id, PROTOCOL_VERSION).unwrap();
 
         let view_chunk_trie =
             tries.get_trie_with_block_hash_for_shard(shard_uid, root, &block_hash, true);
@@ -1232,6 +1440,7 @@ mod tests {
             nibbles_begin.clone(),
             nibbles_end.clone(),
             &trie_without_flat,
+            PROTOCOL_VERSION,
         );
         assert_eq!(state_part_with_flat, Ok(state_part.clone()));
 
@@ -1245,7 +1454,8 @@ mod tests {
         store_update.commit().unwrap();
 
         assert_eq!(
-            trie_without_flat.get_trie_nodes_for_part_without_flat_storage(part_id),
+            trie_without_flat
+                .get_trie_nodes_for_part_without_flat_storage(part_id, PROTOCOL_VERSION),
             Err(StorageError::MissingTrieValue(MissingTrieValueContext::TrieStorage, value_hash)),
         );
 
@@ -1256,6 +1466,7 @@ mod tests {
                 nibbles_begin.clone(),
                 nibbles_end.clone(),
                 &trie_without_flat,
+                PROTOCOL_VERSION,
             ),
             Ok(state_part)
         );
@@ -1275,6 +1486,7 @@ mod tests {
                 nibbles_begin,
                 nibbles_end,
                 &trie_without_flat,
+                PROTOCOL_VERSION,
             ),
             Err(StorageError::MissingTrieValue(
                 MissingTrieValueContext::TrieMemoryPartialStorage,
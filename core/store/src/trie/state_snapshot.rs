@@ -14,6 +14,7 @@ use near_primitives::state::PartialState;
 use near_primitives::state_part::PartId;
 use near_primitives::types::ShardIndex;
 use near_primitives::types::StateRoot;
+use near_primitives::version::ProtocolVersion;
 use std::error::Error;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -225,6 +226,7 @@ impl ShardTries {
         nibbles_begin: Vec<u8>,
         nibbles_end: Vec<u8>,
         state_trie: Trie,
+        protocol_version: ProtocolVersion,
     ) -> Result<PartialState, StorageError> {
         let guard = self.state_snapshot().try_read().map_err(SnapshotError::from)?;
         let data = guard.as_ref().ok_or(SnapshotError::SnapshotNotFound(*block_hash))?;
@@ -249,6 +251,7 @@ impl ShardTries {
             nibbles_begin,
             nibbles_end,
             &state_trie,
+            protocol_version,
         )
     }
 
@@ -259,6 +262,20 @@ impl ShardTries {
         prev_block_hash: CryptoHash,
         shard_indexes_and_uids: &[(ShardIndex, ShardUId)],
         block: &Block,
+    ) -> Result<Option<Vec<ShardUId>>, anyhow::Error> {
+        self.create_state_snapshot_with(prev_block_hash, shard_indexes_and_uids, block, |_| Ok(()))
+    }
+
+    /// Same as [`Self::create_state_snapshot`], but calls `on_checkpoint` with the store of the
+    /// snapshot as soon as the checkpoint of the DB is made. From then on, the DB of the node can
+    /// change freely. `on_checkpoint` runs without holding the lock of the state snapshot, and the
+    /// snapshot is made available only once it succeeds. If it fails, the checkpoint is deleted.
+    pub fn create_state_snapshot_with(
+        &self,
+        prev_block_hash: CryptoHash,
+        shard_indexes_and_uids: &[(ShardIndex, ShardUId)],
+        block: &Block,
+        on_checkpoint: impl FnOnce(&TrieStoreAdapter) -> Result<(), anyhow::Error>,
     ) -> Result<Option<Vec<ShardUId>>, anyhow::Error> {
         metrics::HAS_STATE_SNAPSHOT.set(0);
         // The function returns an `anyhow::Error`, because no special handling of errors is done yet. The errors are logged and ignored.
@@ -273,8 +290,9 @@ impl ShardTries {
             return Ok(None);
         };
 
-        // `write()` lock is held for the whole duration of this function.
-        let mut state_snapshot_lock = self.state_snapshot().write().unwrap();
+        // `write()` lock is held until the checkpoint of the DB is made, and again to make the
+        // snapshot available.
+        let state_snapshot_lock = self.state_snapshot().write().unwrap();
         let db_snapshot_hash = self.store().get_state_snapshot_hash();
         if let Some(state_snapshot) = &*state_snapshot_lock {
             // only return Ok() when the hash stored in STATE_SNAPSHOT_KEY and in state_snapshot_lock and prev_block_hash are the same
@@ -287,14 +305,28 @@ impl ShardTries {
             tracing::error!(target: "state_snapshot", ?prev_block_hash, ?state_snapshot.prev_block_hash, "Requested a state snapshot but that is already available with a different hash");
         }
 
+        let snapshot_dir = Self::get_state_snapshot_base_dir(&prev_block_hash, state_snapshots_dir);
         let storage = checkpoint_hot_storage_and_cleanup_columns(
             &self.store().store(),
-            &Self::get_state_snapshot_base_dir(&prev_block_hash, state_snapshots_dir),
+            &snapshot_dir,
             // TODO: Cleanup Changes and DeltaMetadata to avoid extra memory usage.
             // Can't be cleaned up now because these columns are needed to `update_flat_head()`.
             Some(STATE_SNAPSHOT_COLUMNS),
         )?;
+        drop(state_snapshot_lock);
         let store = storage.get_hot_store().trie_store();
+        if let Err(err) = on_checkpoint(&store) {
+            // The checkpoint never becomes a snapshot, so it is deleted instead of being left
+            // behind until the next snapshot. The DB is closed first.
+            drop(store);
+            drop(storage);
+            if let Err(err) = std::fs::remove_dir_all(&snapshot_dir) {
+                tracing::error!(target: "state_snapshot", ?err, ?snapshot_dir, "Failed to delete the checkpoint of the state snapshot");
+            }
+            return Err(err);
+        }
+
+        let mut state_snapshot_lock = self.state_snapshot().write().unwrap();
         // It is fine to create a separate FlatStorageManager, because
         // it is used only for reading flat storage in the snapshot a
         // doesn't introduce memory overhead.
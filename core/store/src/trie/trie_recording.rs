@@ -90,6 +90,18 @@ impl TrieRecorder {
         self.upper_bound_size = self.upper_bound_size.checked_add(code_len).unwrap();
     }
 
+    /// Records everything recorded by another recorder, as if the accesses
+    /// were done through this one.
+    pub fn merge(&mut self, other: TrieRecorder) {
+        for (hash, node) in other.recorded {
+            self.record(&hash, node);
+        }
+        self.removal_counter = self.removal_counter.checked_add(other.removal_counter).unwrap();
+        self.code_len_counter = self.code_len_counter.checked_add(other.code_len_counter).unwrap();
+        self.upper_bound_size =
+            self.upper_bound_size.checked_add(other.upper_bound_size - other.size).unwrap();
+    }
+
     pub fn check_proof_size_limit_exceed(&self) -> bool {
         if let Some(proof_size_limit) = self.proof_size_limit {
             return self.upper_bound_size > proof_size_limit;
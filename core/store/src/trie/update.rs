@@ -16,6 +16,7 @@ use near_primitives::types::{
 };
 use near_vm_runner::ContractCode;
 use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 
 mod iterator;
 
@@ -220,6 +221,53 @@ impl TrieUpdate {
         self.set(key, code.code().to_vec());
     }
 
+    /// Creates an empty update on top of the same trie, which records the
+    /// accessed trie nodes separately. It allows to apply independent changes
+    /// in parallel, but the fork doesn't see the changes of this update, so
+    /// it must only access the keys which weren't changed here, see
+    /// [`Self::has_changes_with_prefix`]. The changes of the fork are moved
+    /// to this update with [`Self::merge_fork`].
+    pub fn fork(&self) -> Self {
+        Self {
+            trie: self.trie.fork(),
+            contract_storage: self.contract_storage.clone(),
+            committed: Default::default(),
+            prospective: Default::default(),
+        }
+    }
+
+    /// Moves the committed changes and the recorded trie nodes of a fork to
+    /// this update, as if they were done here.
+    pub fn merge_fork(&mut self, fork: TrieUpdate) {
+        assert!(fork.prospective.is_empty(), "Fork cannot be merged with uncommitted changes.");
+        for (raw_key, changes_with_trie_key) in fork.committed {
+            match self.committed.entry(raw_key) {
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().changes.extend(changes_with_trie_key.changes);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(changes_with_trie_key);
+                }
+            }
+        }
+        self.trie.merge_fork_recorder(fork.trie);
+    }
+
+    /// Whether any key starting with `prefix` has committed or uncommitted
+    /// changes.
+    pub fn has_changes_with_prefix(&self, prefix: &[u8]) -> bool {
+        let starts_with_prefix = |key: &Vec<u8>| key.starts_with(prefix);
+        self.prospective
+            .range(prefix.to_vec()..)
+            .next()
+            .is_some_and(|(key, _)| starts_with_prefix(key))
+            || self
+                .committed
+                .range(prefix.to_vec()..)
+                .next()
+                .is_some_and(|(key, _)| starts_with_prefix(key))
+    }
+
     pub fn commit(&mut self, event: StateChangeCause) {
         let prospective = std::mem::take(&mut self.prospective);
         for (raw_key, TrieKeyValueUpdate { trie_key, value }) in prospective.into_iter() {
@@ -575,4 +623,50 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn trie_fork_merge() {
+        let tries = TestTriesBuilder::new().build();
+        let shard_uid = ShardUId::single_shard();
+        let mut trie_update = tries.new_trie_update(shard_uid, Trie::EMPTY_ROOT);
+        trie_update.set(test_key(b"dog".to_vec()), b"puppy".to_vec());
+        trie_update
+            .commit(StateChangeCause::TransactionProcessing { tx_hash: CryptoHash::default() });
+        let trie_changes = trie_update.finalize().unwrap().trie_changes;
+        let mut store_update = tries.store_update();
+        let root = tries.apply_all(&trie_changes, shard_uid, &mut store_update);
+        store_update.commit().unwrap();
+
+        // Changing the key through a fork and merging it must give the same
+        // result as changing it directly.
+        let apply = |use_fork: bool| {
+            let trie = tries.get_trie_for_shard(shard_uid, root).recording_reads_new_recorder();
+            let mut trie_update = TrieUpdate::new(trie);
+            trie_update.set(test_key(b"cat".to_vec()), b"kitten".to_vec());
+            assert!(trie_update.has_changes_with_prefix(&test_key(b"ca".to_vec()).to_vec()));
+            assert!(!trie_update.has_changes_with_prefix(&test_key(b"do".to_vec()).to_vec()));
+            trie_update
+                .commit(StateChangeCause::TransactionProcessing { tx_hash: CryptoHash::default() });
+
+            let change_dog = |trie_update: &mut TrieUpdate| {
+                let dog = trie_update.get(&test_key(b"dog".to_vec()), AccessOptions::DEFAULT);
+                assert_eq!(dog.unwrap(), Some(b"puppy".to_vec()));
+                trie_update.set(test_key(b"dog".to_vec()), b"dog".to_vec());
+                trie_update.commit(StateChangeCause::TransactionProcessing {
+                    tx_hash: CryptoHash::default(),
+                });
+            };
+            if use_fork {
+                let mut fork = trie_update.fork();
+                change_dog(&mut fork);
+                assert!(!trie_update.has_changes_with_prefix(&test_key(b"do".to_vec()).to_vec()));
+                trie_update.merge_fork(fork);
+            } else {
+                change_dog(&mut trie_update);
+            }
+            let result = trie_update.finalize().unwrap();
+            (result.trie_changes, result.trie.recorded_storage().unwrap())
+        };
+        assert_eq!(apply(true), apply(false));
+    }
 }
@@ -66,7 +66,9 @@ pub fn create_test_node_storage_with_cold(
 
 /// Creates an in-memory database.
 pub fn create_test_store() -> Store {
-    create_test_node_storage(DB_VERSION, DbKind::RPC).get_hot_store()
+    create_test_node_storage(DB_VERSION, DbKind::RPC)
+        .get_hot_store()
+        .with_negative_refcount_tracking()
 }
 
 /// Returns a pair of (Hot, Split) store to be used for setting up archival clients.
@@ -193,7 +195,7 @@ pub fn test_populate_trie(
     let trie = tries.get_trie_for_shard(shard_uid, *root);
     let trie_changes = trie.update(changes.iter().cloned(), AccessOptions::DEFAULT).unwrap();
     let mut store_update = tries.store_update();
-    tries.apply_memtrie_changes(&trie_changes, shard_uid, 1); // TODO: don't hardcode block height
+    tries.apply_memtrie_changes(&trie_changes, &[], shard_uid, 1); // TODO: don't hardcode block height
     let root = tries.apply_all(&trie_changes, shard_uid, &mut store_update);
     store_update.commit().unwrap();
     let deduped = simplify_changes(&changes);
@@ -21,6 +21,7 @@ const FAKE_CLOCK_UTC_START: LazyLock<Utc> =
 enum ClockInner {
     Real,
     Fake(FakeClock),
+    Skewed(SkewedClock),
 }
 
 /// Clock encapsulates a system clock, allowing to replace it
@@ -46,6 +47,7 @@ impl Clock {
         match &self.0 {
             ClockInner::Real => Instant::now(),
             ClockInner::Fake(fake) => fake.now(),
+            ClockInner::Skewed(skewed) => skewed.now(),
         }
     }
 
@@ -54,6 +56,7 @@ impl Clock {
         match &self.0 {
             ClockInner::Real => Utc::now_utc(),
             ClockInner::Fake(fake) => fake.now_utc(),
+            ClockInner::Skewed(skewed) => skewed.now_utc(),
         }
     }
 
@@ -70,6 +73,7 @@ impl Clock {
         match &self.0 {
             ClockInner::Real => tokio::time::sleep_until(t.into()).await,
             ClockInner::Fake(fake) => fake.sleep_until(t).await,
+            ClockInner::Skewed(skewed) => skewed.sleep_until(t).await,
         }
     }
 
@@ -78,6 +82,7 @@ impl Clock {
         match &self.0 {
             ClockInner::Real => tokio::time::sleep(d.try_into().unwrap()).await,
             ClockInner::Fake(fake) => fake.sleep(d).await,
+            ClockInner::Skewed(skewed) => skewed.sleep(d).await,
         }
     }
 }
@@ -180,6 +185,19 @@ impl FakeClock {
         self.0.lock().unwrap().utc = utc;
     }
 
+    /// Returns a clock which is `offset` ahead of this one and, from now on,
+    /// runs `1 + drift_rate` times as fast, e.g. a `drift_rate` of 0.001
+    /// gains 1ms per second. Use it to simulate the clock of another machine.
+    pub fn skewed_clock(&self, offset: Duration, drift_rate: f64) -> Clock {
+        assert!(drift_rate > -1.0, "the skewed clock must move forward");
+        Clock(ClockInner::Skewed(SkewedClock {
+            base: self.clone(),
+            offset,
+            drift_rate,
+            start: self.now(),
+        }))
+    }
+
     /// Cancel-safe.
     pub async fn sleep(&self, d: Duration) {
         if d <= Duration::ZERO {
@@ -219,6 +237,45 @@ impl FakeClock {
     }
 }
 
+/// A clock derived from a FakeClock, see `FakeClock::skewed_clock`.
+#[derive(Clone)]
+struct SkewedClock {
+    base: FakeClock,
+    offset: Duration,
+    drift_rate: f64,
+    /// The time of the base clock when the drift started.
+    start: Instant,
+}
+
+impl SkewedClock {
+    /// Difference between this clock and the base clock at `base_now`.
+    fn skew(&self, base_now: Instant) -> Duration {
+        self.offset + base_now.signed_duration_since(self.start) * self.drift_rate
+    }
+
+    fn now(&self) -> Instant {
+        let base_now = self.base.now();
+        base_now + self.skew(base_now)
+    }
+
+    fn now_utc(&self) -> Utc {
+        self.base.now_utc() + self.skew(self.base.now())
+    }
+
+    /// The time of the base clock at which this clock reaches `t`.
+    fn base_instant(&self, t: Instant) -> Instant {
+        self.start + (t.signed_duration_since(self.start) - self.offset) / (1.0 + self.drift_rate)
+    }
+
+    async fn sleep_until(&self, t: Instant) {
+        self.base.sleep_until(self.base_instant(t)).await
+    }
+
+    async fn sleep(&self, d: Duration) {
+        self.base.sleep(d / (1.0 + self.drift_rate)).await
+    }
+}
+
 impl Default for FakeClock {
     fn default() -> FakeClock {
         Self::new(*FAKE_CLOCK_UTC_START)
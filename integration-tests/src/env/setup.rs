@@ -20,7 +20,7 @@ use near_chain::types::{ChainConfig, RuntimeAdapter};
 use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode};
 use near_chain_configs::{
     ChunkDistributionNetworkConfig, ClientConfig, Genesis, MutableConfigValue, ReshardingConfig,
-    TrackedShardsConfig,
+    ShardHotspotsConfig, TrackedShardsConfig,
 };
 use near_chunks::adapter::ShardsManagerRequestFromClient;
 use near_chunks::client::ShardsManagerResponse;
@@ -357,6 +357,7 @@ fn setup(
         tx_routing_height_horizon: config.tx_routing_height_horizon,
         epoch_length: config.epoch_length,
         transaction_validity_period,
+        tx_forwarding: config.tx_forwarding.clone(),
     };
 
     let tx_processor_addr = spawn_tx_request_handler_actor(
@@ -938,7 +939,11 @@ fn process_peer_manager_message_default(
         | NetworkRequests::SnapshotHostInfo { .. }
         | NetworkRequests::ChunkStateWitnessAck(_, _)
         | NetworkRequests::EpochSyncRequest { .. }
-        | NetworkRequests::EpochSyncResponse { .. } => {}
+        | NetworkRequests::EpochSyncResponse { .. }
+        | NetworkRequests::ChunkPartsSampleRequest { .. }
+        | NetworkRequests::ChunkPartsSampleResponse { .. }
+        | NetworkRequests::ReplicaStateUpdateRequest { .. }
+        | NetworkRequests::ReplicaStateUpdateResponse { .. } => {}
     }
 }
 
@@ -1244,6 +1249,9 @@ pub fn setup_synchronous_shards_manager(
                 ReshardingConfig::default(),
                 "resharding_config",
             ),
+            shard_hotspots: ShardHotspotsConfig::default(),
+            save_account_usage_stats: false,
+            serve_replica_state_updates: false,
         }, // irrelevant
         None,
         Arc::new(RayonAsyncComputationSpawner),
@@ -1284,6 +1292,7 @@ pub fn setup_tx_request_handler(
         tx_routing_height_horizon: client_config.tx_routing_height_horizon,
         epoch_length: chain_genesis.epoch_length,
         transaction_validity_period: chain_genesis.transaction_validity_period,
+        tx_forwarding: client_config.tx_forwarding.clone(),
     };
 
     TxRequestHandler::new(
@@ -820,6 +820,9 @@ impl TestEnv {
             | ProcessTxResponse::ValidTx => (),
             ProcessTxResponse::InvalidTx(e) => return Err(e),
             ProcessTxResponse::DoesNotTrackShard => panic!("test setup is buggy"),
+            ProcessTxResponse::ForwardingOverloaded { shard_id } => {
+                panic!("chunk producers of shard {shard_id} are overloaded")
+            }
         }
         let max_iters = 100;
         let tip = self.clients[0].chain.head().unwrap();
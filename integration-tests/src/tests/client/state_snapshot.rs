@@ -160,6 +160,47 @@ fn test_state_snapshot_disabled() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+// on_checkpoint fails, the checkpoint should be deleted and no state snapshot should be set
+fn test_state_snapshot_on_checkpoint_failure() -> anyhow::Result<()> {
+    init_test_logger();
+    let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+    let env = TestEnv::builder(&genesis.config)
+        .clients_count(1)
+        .use_state_snapshots()
+        .real_stores()
+        .nightshade_runtimes(&genesis)
+        .build();
+
+    let genesis_block = env.clients[0].chain.get_block_by_height(0)?;
+    let genesis_hash = *genesis_block.hash();
+    let store = env.clients[0].chain.chain_store().store();
+    let state_snapshot_test_env =
+        set_up_test_env_for_state_snapshots(&store, StateSnapshotType::Enabled);
+    let shard_tries = &state_snapshot_test_env.shard_tries;
+    shard_tries.delete_state_snapshot();
+
+    let result = shard_tries.create_state_snapshot_with(
+        genesis_hash,
+        &[(0, ShardUId::single_shard())],
+        &genesis_block,
+        |_| Err(anyhow::anyhow!("on_checkpoint failed")),
+    );
+    assert!(result.is_err());
+    let snapshot_path = ShardTries::get_state_snapshot_base_dir(
+        &genesis_hash,
+        &state_snapshot_test_env.state_snapshots_dir,
+    );
+    assert!(!std::fs::exists(&snapshot_path)?, "the checkpoint should be deleted");
+    assert!(shard_tries.store().get_state_snapshot_hash().is_err());
+    assert!(
+        shard_tries.maybe_open_state_snapshot(|_| Ok(vec![(0, ShardUId::single_shard())])).is_err()
+    );
+
+    // The next snapshot of the same block is made from scratch.
+    verify_make_snapshot(&state_snapshot_test_env, genesis_hash, &genesis_block)
+}
+
 fn verify_make_snapshot(
     state_snapshot_test_env: &StateSnapshotTestEnv,
     block_hash: CryptoHash,
@@ -5,11 +5,13 @@ use near_crypto::{InMemorySigner, KeyType, PublicKey, Signer};
 use near_o11y::testonly::init_test_logger;
 use near_parameters::{RuntimeConfig, RuntimeConfigStore};
 use near_primitives::account::id::AccountId;
+use near_primitives::block::Block;
 use near_primitives::congestion_info::CongestionInfo;
 use near_primitives::errors::{
     ActionErrorKind, FunctionCallError, InvalidTxError, TxExecutionError,
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::num_rational::Rational32;
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::sharding::{ShardChunk, ShardChunkHeader};
 use near_primitives::transaction::SignedTransaction;
@@ -57,7 +59,17 @@ fn set_default_congestion_control(config_store: &RuntimeConfigStore, config: &mu
 
 /// Set up the test runtime with the given protocol version and runtime configs.
 /// The test version of runtime has custom gas cost.
-fn setup_test_runtime(_sender_id: AccountId, protocol_version: ProtocolVersion) -> TestEnv {
+fn setup_test_runtime(sender_id: AccountId, protocol_version: ProtocolVersion) -> TestEnv {
+    setup_test_runtime_with(sender_id, protocol_version, |_| {})
+}
+
+/// Same as [`setup_test_runtime`] but allows the caller to adjust the runtime
+/// config on top of the defaults used by the tests.
+fn setup_test_runtime_with(
+    _sender_id: AccountId,
+    protocol_version: ProtocolVersion,
+    adjust_config: impl FnOnce(&mut RuntimeConfig),
+) -> TestEnv {
     let accounts = TestEnvBuilder::make_accounts(1);
     let mut genesis = Genesis::test_sharded_new_version(accounts, 1, vec![1, 1, 1, 1]);
     genesis.config.epoch_length = 10;
@@ -70,6 +82,7 @@ fn setup_test_runtime(_sender_id: AccountId, protocol_version: ProtocolVersion)
     let mut config = RuntimeConfig::test_protocol_version(protocol_version);
     set_wasm_cost(&mut config);
     set_default_congestion_control(&config_store, &mut config);
+    adjust_config(&mut config);
 
     let runtime_configs = vec![RuntimeConfigStore::with_one_config(config)];
     TestEnv::builder(&genesis.config)
@@ -560,3 +573,92 @@ fn test_rpc_client_rejection() {
 
     assert_matches!(response, ProcessTxResponse::InvalidTx(InvalidTxError::ShardCongested { .. }));
 }
+
+/// Test that the minimum gas price is raised while a shard stays congested for
+/// `gas_price_floor_congested_blocks` blocks in a row, and that it falls back
+/// to the genesis minimum once the congestion is resolved.
+#[test]
+fn test_gas_price_floor_during_congestion() {
+    init_test_logger();
+
+    if !ProtocolFeature::CongestionGasPriceFloor.enabled(PROTOCOL_VERSION) {
+        return;
+    }
+
+    let congestion_threshold = 0.5;
+    let congested_blocks = 3;
+    let increase_rate = Rational32::new(1, 10);
+    let sender_id: AccountId = "test0".parse().unwrap();
+    let mut env = setup_test_runtime_with(sender_id.clone(), PROTOCOL_VERSION, |config| {
+        let congestion_control_config = &mut config.congestion_control_config;
+        congestion_control_config.gas_price_floor_congestion_threshold = congestion_threshold;
+        congestion_control_config.gas_price_floor_congested_blocks = congested_blocks;
+        congestion_control_config.gas_price_floor_increase_rate = increase_rate;
+    });
+    let min_gas_price = env.clients[0].chain.block_economics_config.min_gas_price();
+    let max_gas_price = env.clients[0].chain.block_economics_config.max_gas_price();
+
+    // prepare a contract to call
+    let mut nonce = 10;
+    setup_contract(&mut env, &mut nonce);
+
+    // Congest the contract's shard with a burst of 100 PGas.
+    let signer = InMemorySigner::test_signer(&sender_id);
+    submit_n_100tgas_fns(&mut env, 1_000, &mut nonce, &signer);
+
+    let config = head_congestion_control_config(&env);
+    let mut congested_in_a_row = 0;
+    let mut floor_applied = false;
+    let mut congestion_resolved = false;
+    let mut peak_gas_price = 0;
+    let tip = env.clients[0].chain.head().unwrap();
+    for i in 1..300 {
+        env.produce_block(0, tip.height + i);
+        let block = env.clients[0].chain.get_head_block().unwrap();
+        let prev_header =
+            env.clients[0].chain.get_block_header(block.header().prev_hash()).unwrap();
+        let gas_price = prev_header.next_gas_price();
+        let next_gas_price = block.header().next_gas_price();
+        peak_gas_price = peak_gas_price.max(next_gas_price);
+
+        let congestion_level = block.chunks().block_congestion_info().max_congestion_level(&config);
+        if congestion_level >= congestion_threshold {
+            congested_in_a_row += 1;
+        } else {
+            congested_in_a_row = 0;
+        }
+
+        if congested_in_a_row >= congested_blocks {
+            // The gas price must grow at least by the increase rate.
+            let gas_price_floor = Block::compute_congestion_gas_price_floor(
+                gas_price,
+                increase_rate,
+                min_gas_price,
+                max_gas_price,
+            );
+            assert!(
+                next_gas_price >= gas_price_floor,
+                "{next_gas_price} >= {gas_price_floor} failed at congestion level {congestion_level}"
+            );
+            floor_applied = true;
+        } else if floor_applied {
+            // Without sustained congestion there is no floor beyond the
+            // genesis minimum.
+            let block_min_gas_price = env.clients[0]
+                .chain
+                .get_min_gas_price(block.header().prev_hash(), &block.chunks(), gas_price)
+                .unwrap();
+            assert_eq!(block_min_gas_price, min_gas_price);
+            congestion_resolved = true;
+        }
+
+        if congestion_resolved && next_gas_price < peak_gas_price {
+            break;
+        }
+    }
+
+    assert!(floor_applied, "the shard never stayed congested for {congested_blocks} blocks");
+    assert!(congestion_resolved, "loop timed out before the congestion was resolved");
+    let gas_price = env.clients[0].chain.get_head_block().unwrap().header().next_gas_price();
+    assert!(gas_price < peak_gas_price, "{gas_price} < {peak_gas_price} failed");
+}
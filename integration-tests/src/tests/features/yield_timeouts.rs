@@ -4,11 +4,13 @@ use near_crypto::InMemorySigner;
 use near_o11y::testonly::init_test_logger;
 use near_parameters::config::TEST_CONFIG_YIELD_TIMEOUT_LENGTH;
 use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS;
 use near_primitives::receipt::ReceiptEnum::{PromiseResume, PromiseYield};
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
 };
 use near_primitives::types::AccountId;
+use near_primitives::version::{PROTOCOL_VERSION, ProtocolFeature, ProtocolVersion};
 use near_primitives::views::FinalExecutionStatus;
 
 use crate::env::nightshade_setup::TestEnvNightshadeSetupExt;
@@ -22,7 +24,17 @@ const NEXT_BLOCK_HEIGHT_AFTER_SETUP: u64 = 5;
 
 // The height of the block in which we expect the yield timeout to trigger,
 // producing a YieldResume receipt.
-const YIELD_TIMEOUT_HEIGHT: u64 = YIELD_CREATE_HEIGHT + TEST_CONFIG_YIELD_TIMEOUT_LENGTH;
+const YIELD_TIMEOUT_HEIGHT: u64 =
+    YIELD_CREATE_HEIGHT + TEST_CONFIG_YIELD_TIMEOUT_LENGTH + YIELD_TIMEOUT_GRACE_BLOCKS;
+
+// The timeouts are resolved one block after they expire, so that a
+// `yield_resume` delivered in the block of the timeout still wins.
+const YIELD_TIMEOUT_GRACE_BLOCKS: u64 =
+    if PROTOCOL_VERSION >= ProtocolFeature::PromiseYieldTimeoutGraceWindow.protocol_version() {
+        PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS
+    } else {
+        0
+    };
 
 /// Helper function which checks the outgoing receipts from the latest block.
 /// Returns yield data ids for all PromiseYield and PromiseResume receipts.
@@ -57,9 +69,23 @@ fn find_yield_data_ids_from_latest_block(env: &TestEnv) -> Vec<CryptoHash> {
 fn prepare_env_with_yield(
     anticipated_yield_payload: Vec<u8>,
     test_env_gas_limit: Option<u64>,
+) -> (TestEnv, CryptoHash, CryptoHash) {
+    prepare_env_with_yield_at_protocol_version(
+        anticipated_yield_payload,
+        test_env_gas_limit,
+        PROTOCOL_VERSION,
+    )
+}
+
+/// Same as `prepare_env_with_yield`, with the chain starting at `protocol_version`.
+fn prepare_env_with_yield_at_protocol_version(
+    anticipated_yield_payload: Vec<u8>,
+    test_env_gas_limit: Option<u64>,
+    protocol_version: ProtocolVersion,
 ) -> (TestEnv, CryptoHash, CryptoHash) {
     init_test_logger();
     let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.protocol_version = protocol_version;
     if let Some(gas_limit) = test_env_gas_limit {
         genesis.config.gas_limit = gas_limit;
     }
@@ -306,6 +332,94 @@ fn yield_resume_just_before_timeout() {
     );
 }
 
+/// In this case the `yield_resume` is invoked in the block at the height at which the yield
+/// expires. The timeout is only resolved in the next block, after the resume.
+///
+/// Tests the `PromiseYieldTimeoutGraceWindow` feature.
+#[test]
+#[cfg(feature = "nightly")]
+fn yield_resume_at_expiration_height() {
+    let yield_payload = vec![6u8; 16];
+    let (mut env, yield_tx_hash, data_id) = prepare_env_with_yield(yield_payload.clone(), None);
+    let expiration_height = YIELD_TIMEOUT_HEIGHT - PROMISE_YIELD_TIMEOUT_GRACE_BLOCKS;
+    assert!(NEXT_BLOCK_HEIGHT_AFTER_SETUP < expiration_height);
+
+    for block_height in NEXT_BLOCK_HEIGHT_AFTER_SETUP..expiration_height {
+        // Submit txn so that yield_resume is invoked in the block at the expiration height
+        if block_height == expiration_height - 1 {
+            invoke_yield_resume(&mut env, data_id, yield_payload.clone());
+        }
+
+        env.produce_block(0, block_height);
+
+        // The transaction will not have a result until the yield execution is resumed
+        assert_eq!(
+            env.clients[0].chain.get_partial_transaction_result(&yield_tx_hash).unwrap().status,
+            FinalExecutionStatus::Started
+        );
+    }
+
+    // In this block the yield expires, but only the `yield_resume` host function produces a
+    // YieldResume receipt.
+    env.produce_block(0, expiration_height);
+    assert_eq!(find_yield_data_ids_from_latest_block(&env), vec![data_id]);
+    assert_eq!(
+        env.clients[0].chain.get_partial_transaction_result(&yield_tx_hash).unwrap().status,
+        FinalExecutionStatus::Started
+    );
+
+    // In this block the resume receipt is applied and the callback is executed with the resume
+    // payload, so the timeout resolved afterwards doesn't produce a receipt.
+    env.produce_block(0, YIELD_TIMEOUT_HEIGHT);
+    assert_eq!(find_yield_data_ids_from_latest_block(&env), vec![]);
+    assert_eq!(
+        env.clients[0].chain.get_partial_transaction_result(&yield_tx_hash).unwrap().status,
+        FinalExecutionStatus::SuccessValue(vec![16u8]),
+    );
+}
+
+/// In this case the `yield_resume` is invoked in the block after the one at the height at which
+/// the yield expires. Without the grace window the timeout was resolved in the block at the
+/// expiration height, so the callback received the timeout although the resume came before it
+/// was executed. With the grace window both are resolved in the same block and the resume wins.
+#[test]
+#[cfg(feature = "nightly")]
+fn yield_resume_after_expiration_height() {
+    let feature_protocol_version =
+        ProtocolFeature::PromiseYieldTimeoutGraceWindow.protocol_version();
+    let expiration_height = YIELD_CREATE_HEIGHT + TEST_CONFIG_YIELD_TIMEOUT_LENGTH;
+    for (protocol_version, expected_payload_len) in
+        [(feature_protocol_version - 1, 0u8), (feature_protocol_version, 16u8)]
+    {
+        let yield_payload = vec![6u8; 16];
+        let (mut env, yield_tx_hash, data_id) = prepare_env_with_yield_at_protocol_version(
+            yield_payload.clone(),
+            None,
+            protocol_version,
+        );
+        assert!(NEXT_BLOCK_HEIGHT_AFTER_SETUP < expiration_height);
+
+        for block_height in NEXT_BLOCK_HEIGHT_AFTER_SETUP..=expiration_height {
+            // Submit txn so that yield_resume is invoked in the block after the expiration height
+            if block_height == expiration_height {
+                invoke_yield_resume(&mut env, data_id, yield_payload.clone());
+            }
+
+            env.produce_block(0, block_height);
+        }
+
+        // In this block the `yield_resume` host function is invoked.
+        env.produce_block(0, expiration_height + 1);
+        // In this block the callback is executed with the payload of the receipt which came first.
+        env.produce_block(0, expiration_height + 2);
+        assert_eq!(
+            env.clients[0].chain.get_partial_transaction_result(&yield_tx_hash).unwrap().status,
+            FinalExecutionStatus::SuccessValue(vec![expected_payload_len]),
+            "protocol version {protocol_version}"
+        );
+    }
+}
+
 /// In this test we introduce congestion to delay the yield timeout so that we can invoke
 /// yield resume after the timeout height has passed.
 #[test]
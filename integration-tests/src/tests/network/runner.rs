@@ -137,6 +137,7 @@ fn setup_network_node(
         tx_routing_height_horizon: client_config.tx_routing_height_horizon,
         epoch_length: client_config.epoch_length,
         transaction_validity_period: genesis.config.transaction_validity_period,
+        tx_forwarding: client_config.tx_forwarding.clone(),
     };
     let tx_processor = spawn_tx_request_handler_actor(
         tx_processor_config,
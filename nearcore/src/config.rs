@@ -1,5 +1,7 @@
 use crate::download_file::{FileDownloadError, run_download_file};
 use crate::dyn_config::LOG_CONFIG_FILENAME;
+use crate::flat_state_checker::FlatStateCheckerConfig;
+use crate::memory_pressure::MemoryPressureConfig;
 use anyhow::{Context, anyhow, bail};
 use bytesize::ByteSize;
 use near_async::time::{Clock, Duration};
@@ -11,13 +13,14 @@ use near_chain_configs::test_utils::{
 use near_chain_configs::{
     BLOCK_PRODUCER_KICKOUT_THRESHOLD, CHUNK_PRODUCER_KICKOUT_THRESHOLD,
     CHUNK_VALIDATOR_ONLY_KICKOUT_THRESHOLD, ChunkDistributionNetworkConfig, ClientConfig,
-    EXPECTED_EPOCH_LENGTH, EpochSyncConfig, FAST_EPOCH_LENGTH, FISHERMEN_THRESHOLD,
-    GAS_PRICE_ADJUSTMENT_RATE, GCConfig, GENESIS_CONFIG_FILENAME, Genesis, GenesisConfig,
-    GenesisValidationMode, INITIAL_GAS_LIMIT, LogSummaryStyle, MAX_INFLATION_RATE,
-    MIN_BLOCK_PRODUCTION_DELAY, MIN_GAS_PRICE, MutableConfigValue, MutableValidatorSigner,
-    NEAR_BASE, NUM_BLOCK_PRODUCER_SEATS, NUM_BLOCKS_PER_YEAR, PROTOCOL_REWARD_RATE,
-    PROTOCOL_UPGRADE_STAKE_THRESHOLD, ReshardingConfig, StateSyncConfig,
-    TRANSACTION_VALIDITY_PERIOD, TrackedShardsConfig, default_chunk_wait_mult,
+    DataAvailabilitySamplingConfig, EXPECTED_EPOCH_LENGTH, EmptyChunkFallbackConfig,
+    EpochSyncConfig, FAST_EPOCH_LENGTH, FISHERMEN_THRESHOLD, GAS_PRICE_ADJUSTMENT_RATE, GCConfig,
+    GENESIS_CONFIG_FILENAME, Genesis, GenesisConfig, GenesisValidationMode, INITIAL_GAS_LIMIT,
+    LogSummaryStyle, MAX_INFLATION_RATE, MIN_BLOCK_PRODUCTION_DELAY, MIN_GAS_PRICE,
+    MutableConfigValue, MutableValidatorSigner, NEAR_BASE, NUM_BLOCK_PRODUCER_SEATS,
+    NUM_BLOCKS_PER_YEAR, PROTOCOL_REWARD_RATE, PROTOCOL_UPGRADE_STAKE_THRESHOLD, ReplicaConfig,
+    ReshardingConfig, ShardHotspotsConfig, StateSyncConfig, TRANSACTION_VALIDITY_PERIOD,
+    TrackedShardsConfig, TxForwardingConfig, default_chunk_wait_mult,
     default_enable_multiline_logging, default_epoch_sync,
     default_header_sync_expected_height_per_second, default_header_sync_initial_timeout,
     default_header_sync_progress_timeout, default_header_sync_stall_ban_timeout,
@@ -37,6 +40,7 @@ use near_epoch_manager::EpochManagerHandle;
 use near_jsonrpc::RpcConfig;
 use near_network::config::NetworkConfig;
 use near_network::tcp;
+use near_o11y::events::EventLogConfig;
 use near_o11y::log_config::LogConfig;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardLayout;
@@ -345,6 +349,40 @@ pub struct Config {
     /// latency due to the need of requesting chunks over the peer-to-peer network.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub chunk_distribution_network: Option<ChunkDistributionNetworkConfig>,
+    /// Produce chunks with few or no transactions for a shard when its chunks
+    /// are repeatedly missing from blocks.
+    pub empty_chunk_fallback: EmptyChunkFallbackConfig,
+    /// Stop forwarding transactions to chunk producers which are overloaded
+    /// or unresponsive, and buffer them meanwhile.
+    pub tx_forwarding: TxForwardingConfig,
+    /// Experimental: sample random parts of the chunks of untracked shards to
+    /// check that they are available.
+    pub data_availability_sampling: DataAvailabilitySamplingConfig,
+    /// Keep the accounts using the most gas and state writes of each shard,
+    /// served by the `/debug/api/shard_hotspots` debug RPC.
+    pub shard_hotspots: ShardHotspotsConfig,
+    /// Collect the storage usage and gas burnt of the accounts of the tracked
+    /// shards, which `neard database propose-shard-split` uses to choose the
+    /// boundary account of a shard split.
+    #[serde(skip_serializing_if = "is_false")]
+    pub save_account_usage_stats: bool,
+    /// Record the execution profile of each receipt applied by the node,
+    /// served by the `EXPERIMENTAL_tx_execution_profile` RPC.
+    #[serde(skip_serializing_if = "is_false")]
+    pub save_receipt_execution_profiles: bool,
+    /// Skip the validation and the execution of the chunks of the blocks
+    /// produced by trusted block producers, and apply the state updates
+    /// received from them instead.
+    pub replica: ReplicaConfig,
+    /// Serve the state updates of the recently applied chunks to the replicas.
+    #[serde(skip_serializing_if = "is_false")]
+    pub serve_replica_state_updates: bool,
+    /// Disable the optional caches and reject the expensive RPC requests when
+    /// the memory of the node grows too high.
+    pub memory_pressure: MemoryPressureConfig,
+    /// Compare the flat storage of the shards against the trie in the
+    /// background, and report the keys which don't match.
+    pub flat_state_checker: FlatStateCheckerConfig,
     /// OrphanStateWitnessPool keeps instances of ChunkStateWitness which can't be processed
     /// because the previous block isn't available. The witnesses wait in the pool until the
     /// required block appears. This variable controls how many witnesses can be stored in the pool.
@@ -366,6 +404,10 @@ pub struct Config {
     /// as a large number of incoming witnesses could cause denial of service.
     pub save_latest_witnesses: bool,
     pub transaction_request_handler_threads: usize,
+    /// Where to write the structured log of the chain events, see
+    /// `near_o11y::events`. Not written if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_log: Option<EventLogConfig>,
 }
 
 fn is_false(value: &bool) -> bool {
@@ -416,11 +458,22 @@ impl Default for Config {
             produce_chunk_add_transactions_time_limit:
                 default_produce_chunk_add_transactions_time_limit(),
             chunk_distribution_network: None,
+            empty_chunk_fallback: EmptyChunkFallbackConfig::default(),
+            tx_forwarding: TxForwardingConfig::default(),
+            data_availability_sampling: DataAvailabilitySamplingConfig::default(),
+            shard_hotspots: ShardHotspotsConfig::default(),
+            save_account_usage_stats: false,
+            save_receipt_execution_profiles: false,
+            replica: ReplicaConfig::default(),
+            serve_replica_state_updates: false,
+            memory_pressure: MemoryPressureConfig::default(),
+            flat_state_checker: FlatStateCheckerConfig::default(),
             orphan_state_witness_pool_size: default_orphan_state_witness_pool_size(),
             orphan_state_witness_max_size: default_orphan_state_witness_max_size(),
             max_loaded_contracts: 256,
             save_latest_witnesses: false,
             transaction_request_handler_threads: 4,
+            event_log: None,
         }
     }
 }
@@ -612,6 +665,14 @@ impl NearConfig {
                     "produce_chunk_add_transactions_time_limit",
                 ),
                 chunk_distribution_network: config.chunk_distribution_network,
+                empty_chunk_fallback: config.empty_chunk_fallback,
+                tx_forwarding: config.tx_forwarding,
+                data_availability_sampling: config.data_availability_sampling,
+                shard_hotspots: config.shard_hotspots,
+                save_account_usage_stats: config.save_account_usage_stats,
+                save_receipt_execution_profiles: config.save_receipt_execution_profiles,
+                replica: config.replica,
+                serve_replica_state_updates: config.serve_replica_state_updates,
                 orphan_state_witness_pool_size: config.orphan_state_witness_pool_size,
                 orphan_state_witness_max_size: config.orphan_state_witness_max_size,
                 save_latest_witnesses: config.save_latest_witnesses,
@@ -642,6 +703,19 @@ impl NearConfig {
         }
         None
     }
+
+    /// Disables the JSON RPC and Rosetta RPC servers, for nodes that are embedded
+    /// into other services and access the chain directly.
+    pub fn disable_rpc_servers(&mut self) {
+        #[cfg(feature = "json_rpc")]
+        {
+            self.rpc_config = None;
+        }
+        #[cfg(feature = "rosetta_rpc")]
+        {
+            self.rosetta_rpc_config = None;
+        }
+    }
 }
 
 impl NearConfig {
@@ -707,6 +781,7 @@ impl NightshadeRuntime {
             config.config.gc.gc_num_epochs_to_keep(),
             TrieConfig::from_store_config(&config.config.store),
             state_snapshot_config,
+            config.client_config.save_receipt_execution_profiles,
         ))
     }
 }
@@ -1696,6 +1771,8 @@ mod tests {
                     gc_fork_clean_step: 420,
                     gc_num_epochs_to_keep: 24,
                     gc_step_period: Duration::seconds(1),
+                    gc_resharding_parent_shards: true,
+                    gc_retention: Default::default(),
                 }
             } else {
                 GCConfig {
@@ -1703,6 +1780,8 @@ mod tests {
                     gc_fork_clean_step: 100,
                     gc_num_epochs_to_keep: 5,
                     gc_step_period: Duration::seconds(1),
+                    gc_resharding_parent_shards: true,
+                    gc_retention: Default::default(),
                 }
             };
             assert_eq!(want_gc, config.gc);
@@ -87,6 +87,18 @@ impl<'a> ConfigValidator<'a> {
             self.validation_errors.push_config_semantics_error(error_message);
         }
 
+        for (category, retention) in &self.config.gc.gc_retention {
+            if retention.num_epochs_to_keep > self.config.gc.gc_num_epochs_to_keep {
+                let error_message = format!(
+                    "gc.gc_retention.{}.num_epochs_to_keep is {}, it can't be more than gc_num_epochs_to_keep {}.",
+                    category.as_str(),
+                    retention.num_epochs_to_keep,
+                    self.config.gc.gc_num_epochs_to_keep
+                );
+                self.validation_errors.push_config_semantics_error(error_message);
+            }
+        }
+
         if let Some(state_sync) = &self.config.state_sync {
             if let Some(dump_config) = &state_sync.dump {
                 if let Some(restart_dump_for_shards) = &dump_config.restart_dump_for_shards {
@@ -187,6 +199,21 @@ impl<'a> ConfigValidator<'a> {
             );
             self.validation_errors.push_config_semantics_error(error_message);
         }
+
+        let memory_pressure = &self.config.memory_pressure;
+        if memory_pressure.enabled && memory_pressure.soft_limit > memory_pressure.hard_limit {
+            let error_message = format!(
+                "'config.memory_pressure.soft_limit' can't be higher than 'config.memory_pressure.hard_limit', got {} and {}.",
+                memory_pressure.soft_limit, memory_pressure.hard_limit
+            );
+            self.validation_errors.push_config_semantics_error(error_message);
+        }
+        let flat_state_checker = &self.config.flat_state_checker;
+        if flat_state_checker.enabled && flat_state_checker.keys_per_check == 0 {
+            let error_message =
+                "'config.flat_state_checker.keys_per_check' needs to be greater than 0".to_string();
+            self.validation_errors.push_config_semantics_error(error_message);
+        }
         self.validate_tracked_shards_config();
     }
 
@@ -309,4 +336,27 @@ mod tests {
         config.tx_routing_height_horizon = 1_000_000_000;
         validate_config(&config).unwrap();
     }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: 'config.memory_pressure.soft_limit' can't be higher than 'config.memory_pressure.hard_limit', got 2.0 KB and 1.0 KB."
+    )]
+    fn test_memory_pressure_soft_limit_above_hard_limit() {
+        let mut config = Config::default();
+        config.memory_pressure.enabled = true;
+        config.memory_pressure.soft_limit = bytesize::ByteSize::kb(2);
+        config.memory_pressure.hard_limit = bytesize::ByteSize::kb(1);
+        validate_config(&config).unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "\\nconfig.json semantic issue: 'config.flat_state_checker.keys_per_check' needs to be greater than 0"
+    )]
+    fn test_flat_state_checker_zero_keys_per_check() {
+        let mut config = Config::default();
+        config.flat_state_checker.enabled = true;
+        config.flat_state_checker.keys_per_check = 0;
+        validate_config(&config).unwrap();
+    }
 }
@@ -0,0 +1,323 @@
+//! Background checker of the flat storage against the trie, for the shards
+//! with flat storage of the node.
+//!
+//! Every check period, a batch of the flat storage keys of one of the shards
+//! is compared against the trie at the state root of the flat head, resuming
+//! after the last key checked for the shard, so that a pass over a shard is
+//! spread over many periods and the load on the node stays bounded. The trie
+//! is iterated over the same key range as the batch, so the keys of the trie
+//! missing from flat storage are detected too. The trie is read from the
+//! database directly, not through the shard cache, so that the checker
+//! neither evicts the nodes used by chunk application nor reads a cached
+//! node instead of the stored one.
+//!
+//! The keys whose flat storage value doesn't match the trie are logged and
+//! counted, but not repaired: the flat storage is created from the trie again
+//! by resyncing the state of the shard.
+use crate::metrics::{
+    FLAT_STATE_CHECKER_CHECKED_KEYS, FLAT_STATE_CHECKER_MISMATCHES, FLAT_STATE_CHECKER_PASSES,
+};
+use actix_rt::ArbiterHandle;
+use near_async::time::Duration;
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::errors::StorageError;
+use near_primitives::state::ValueRef;
+use near_store::adapter::StoreAdapter;
+use near_store::adapter::flat_store::FlatStoreAdapter;
+use near_store::flat::{FlatStorageReadyStatus, FlatStorageStatus};
+use near_store::{ShardTries, ShardUId, Store, Trie, TrieDBStorage};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct FlatStateCheckerConfig {
+    pub enabled: bool,
+    /// How often a batch of keys is checked.
+    #[serde(with = "near_async::time::serde_duration_as_std")]
+    pub check_period: Duration,
+    /// The number of the flat storage keys checked in a batch.
+    pub keys_per_check: usize,
+}
+
+impl Default for FlatStateCheckerConfig {
+    fn default() -> Self {
+        Self { enabled: false, check_period: Duration::seconds(1), keys_per_check: 1000 }
+    }
+}
+
+/// The result of checking a batch of the flat storage keys of a shard.
+#[derive(Debug, Default, PartialEq)]
+struct CheckedBatch {
+    num_checked: usize,
+    /// The keys whose value in flat storage doesn't match the trie, or which
+    /// are only in one of them, sorted.
+    mismatches: Vec<Vec<u8>>,
+    /// The last key checked, `None` if the batch reached the end of the shard.
+    last_key: Option<Vec<u8>>,
+}
+
+/// Compares at most `limit` keys of the flat storage of the shard following
+/// `after` with the keys of the trie in the same range.
+fn check_batch(
+    flat_store: &FlatStoreAdapter,
+    trie: &Trie,
+    shard_uid: ShardUId,
+    after: Option<&[u8]>,
+    limit: usize,
+) -> Result<CheckedBatch, StorageError> {
+    // The smallest key greater than `after`.
+    let from = after.map(|key| [key, &[0]].concat());
+    let mut flat_values = BTreeMap::new();
+    for item in flat_store.iter_range(shard_uid, from.as_deref(), None).take(limit) {
+        let (key, value) = item?;
+        flat_values.insert(key, value.to_value_ref());
+    }
+    let num_checked = flat_values.len();
+    let last_key = if num_checked < limit { None } else { flat_values.keys().next_back().cloned() };
+
+    let mut mismatches = Vec::new();
+    let mut trie_iter = trie.disk_iter()?;
+    trie_iter.seek(from.as_deref().unwrap_or_default())?;
+    for item in trie_iter {
+        let (key, value) = item?;
+        if last_key.as_ref().is_some_and(|last_key| &key > last_key) {
+            break;
+        }
+        if flat_values.remove(&key) != Some(ValueRef::new(&value)) {
+            mismatches.push(key);
+        }
+    }
+    // The keys left are missing from the trie.
+    mismatches.extend(flat_values.into_keys());
+    mismatches.sort();
+    Ok(CheckedBatch { num_checked, mismatches, last_key })
+}
+
+/// Which shard is checked next and where its check resumes: the shards are
+/// checked in turns, one batch each.
+#[derive(Default)]
+struct CheckCursors {
+    /// The last key checked for each shard in the current pass.
+    cursors: HashMap<ShardUId, Vec<u8>>,
+    /// The index of the shard to check next, among the checked shards.
+    next_shard_index: usize,
+}
+
+impl CheckCursors {
+    /// The shard to check next among `shard_uids` and the last key checked
+    /// for it. The cursors of the shards no longer checked are dropped.
+    fn next_shard(&mut self, shard_uids: &[ShardUId]) -> Option<(ShardUId, Option<Vec<u8>>)> {
+        self.cursors.retain(|shard_uid, _| shard_uids.contains(shard_uid));
+        if shard_uids.is_empty() {
+            return None;
+        }
+        let shard_uid = shard_uids[self.next_shard_index % shard_uids.len()];
+        self.next_shard_index = (self.next_shard_index + 1) % shard_uids.len();
+        Some((shard_uid, self.cursors.get(&shard_uid).cloned()))
+    }
+
+    /// Records the last key of the batch checked for the shard. Returns
+    /// whether the pass over the shard is done, in which case the next pass
+    /// starts from the first key.
+    fn advance(&mut self, shard_uid: ShardUId, last_key: Option<Vec<u8>>) -> bool {
+        match last_key {
+            Some(last_key) => {
+                self.cursors.insert(shard_uid, last_key);
+                false
+            }
+            None => {
+                self.cursors.remove(&shard_uid);
+                true
+            }
+        }
+    }
+}
+
+struct FlatStateChecker {
+    store: Store,
+    epoch_manager: Arc<dyn EpochManagerAdapter>,
+    tries: ShardTries,
+    keys_per_check: usize,
+    cursors: CheckCursors,
+}
+
+impl FlatStateChecker {
+    /// The shards of the epoch of the head which have flat storage.
+    fn shards_to_check(&self) -> anyhow::Result<Vec<ShardUId>> {
+        let head = self.store.chain_store().head()?;
+        let shard_layout = self.epoch_manager.get_shard_layout(&head.epoch_id)?;
+        let flat_storage_manager = self.tries.get_flat_storage_manager();
+        Ok(shard_layout
+            .shard_uids()
+            .filter(|shard_uid| {
+                flat_storage_manager.get_flat_storage_for_shard(*shard_uid).is_some()
+            })
+            .collect())
+    }
+
+    fn flat_head(&self, shard_uid: ShardUId) -> anyhow::Result<Option<FlatStorageReadyStatus>> {
+        match self.store.flat_store().get_flat_storage_status(shard_uid)? {
+            FlatStorageStatus::Ready(status) => Ok(Some(status)),
+            _ => Ok(None),
+        }
+    }
+
+    fn check_next_batch(&mut self) -> anyhow::Result<()> {
+        let shard_uids = self.shards_to_check()?;
+        let Some((shard_uid, cursor)) = self.cursors.next_shard(&shard_uids) else {
+            return Ok(());
+        };
+        let Some(status) = self.flat_head(shard_uid)? else {
+            return Ok(());
+        };
+        let chunk_extra =
+            self.store.chain_store().get_chunk_extra(&status.flat_head.hash, &shard_uid)?;
+        let storage = TrieDBStorage::new(self.store.trie_store(), shard_uid);
+        let trie = Trie::new(Arc::new(storage), *chunk_extra.state_root(), None);
+        let batch = check_batch(
+            &self.store.flat_store(),
+            &trie,
+            shard_uid,
+            cursor.as_deref(),
+            self.keys_per_check,
+        )?;
+        // The flat storage may have moved to a new head while it was read, in
+        // which case the values read don't all belong to the state root.
+        if self.flat_head(shard_uid)?.as_ref() != Some(&status) {
+            return Ok(());
+        }
+
+        let shard_uid_label = shard_uid.to_string();
+        FLAT_STATE_CHECKER_CHECKED_KEYS
+            .with_label_values(&[&shard_uid_label])
+            .inc_by(batch.num_checked as u64);
+        FLAT_STATE_CHECKER_MISMATCHES
+            .with_label_values(&[&shard_uid_label])
+            .inc_by(batch.mismatches.len() as u64);
+        for key in &batch.mismatches {
+            tracing::warn!(
+                target: "flat_state_checker",
+                %shard_uid,
+                flat_head = ?status.flat_head,
+                key = %hex::encode(key),
+                "Flat storage value doesn't match the trie"
+            );
+        }
+        if self.cursors.advance(shard_uid, batch.last_key) {
+            FLAT_STATE_CHECKER_PASSES.with_label_values(&[&shard_uid_label]).inc();
+            tracing::debug!(target: "flat_state_checker", %shard_uid, "Checked the whole flat storage of the shard");
+        }
+        Ok(())
+    }
+}
+
+pub fn spawn_flat_state_checker(
+    config: FlatStateCheckerConfig,
+    store: Store,
+    epoch_manager: Arc<dyn EpochManagerAdapter>,
+    tries: ShardTries,
+) -> ArbiterHandle {
+    tracing::info!(
+        target: "flat_state_checker",
+        keys_per_check = config.keys_per_check,
+        check_period = ?config.check_period,
+        "Spawning the flat state checker."
+    );
+    let mut checker = FlatStateChecker {
+        store,
+        epoch_manager,
+        tries,
+        keys_per_check: config.keys_per_check,
+        cursors: CheckCursors::default(),
+    };
+    let arbiter = actix_rt::Arbiter::new();
+    let mut interval = actix_rt::time::interval(config.check_period.unsigned_abs());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    arbiter.spawn(async move {
+        loop {
+            interval.tick().await;
+            if let Err(err) = checker.check_next_batch() {
+                tracing::warn!(target: "flat_state_checker", ?err, "Failed to check the flat storage");
+            }
+        }
+    });
+    arbiter.handle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::state::FlatStateValue;
+    use near_primitives::types::ShardId;
+    use near_store::test_utils::{
+        TestTriesBuilder, test_populate_flat_storage, test_populate_trie,
+    };
+
+    #[test]
+    fn test_check_batch() {
+        let tries = TestTriesBuilder::new().with_flat_storage(true).build();
+        let shard_uid = ShardUId::single_shard();
+        let changes = (0..5u8).map(|i| (vec![i], Some(vec![i; 10]))).collect::<Vec<_>>();
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes.clone());
+        test_populate_flat_storage(
+            &tries,
+            shard_uid,
+            &CryptoHash::hash_bytes(b"block"),
+            &CryptoHash::default(),
+            &changes,
+        );
+        // Key 1 is missing from flat storage, key 3 has another value and key
+        // 7 is missing from the trie.
+        let flat_store = tries.store().flat_store();
+        let mut store_update = flat_store.store_update();
+        store_update.set(shard_uid, vec![1], None);
+        store_update.set(shard_uid, vec![3], Some(FlatStateValue::on_disk(&[7; 10])));
+        store_update.set(shard_uid, vec![7], Some(FlatStateValue::on_disk(&[7; 10])));
+        store_update.commit().unwrap();
+
+        let storage = TrieDBStorage::new(tries.store().trie_store(), shard_uid);
+        let trie = Trie::new(Arc::new(storage), root, None);
+        let first = check_batch(&flat_store, &trie, shard_uid, None, 3).unwrap();
+        assert_eq!(
+            first,
+            CheckedBatch {
+                num_checked: 3,
+                mismatches: vec![vec![1], vec![3]],
+                last_key: Some(vec![3])
+            }
+        );
+        let second =
+            check_batch(&flat_store, &trie, shard_uid, first.last_key.as_deref(), 3).unwrap();
+        assert_eq!(
+            second,
+            CheckedBatch { num_checked: 2, mismatches: vec![vec![7]], last_key: None }
+        );
+    }
+
+    #[test]
+    fn test_check_cursors() {
+        let shard_uids = [ShardUId::new(1, ShardId::new(0)), ShardUId::new(1, ShardId::new(1))];
+        let mut cursors = CheckCursors::default();
+
+        // The shards are checked in turns, each resuming after its last key.
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[0], None)));
+        assert!(!cursors.advance(shard_uids[0], Some(vec![1])));
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[1], None)));
+        assert!(!cursors.advance(shard_uids[1], Some(vec![2])));
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[0], Some(vec![1]))));
+        assert!(cursors.advance(shard_uids[0], None));
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[1], Some(vec![2]))));
+        assert!(!cursors.advance(shard_uids[1], Some(vec![3])));
+        // The pass over the first shard is done, the next one starts over.
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[0], None)));
+
+        // The cursors of the shards no longer checked are dropped.
+        let new_shard_uids = [ShardUId::new(2, ShardId::new(2))];
+        assert_eq!(cursors.next_shard(&new_shard_uids), Some((new_shard_uids[0], None)));
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[0], None)));
+        assert_eq!(cursors.next_shard(&shard_uids), Some((shard_uids[1], None)));
+        assert_eq!(cursors.next_shard(&[]), None);
+    }
+}
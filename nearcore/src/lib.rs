@@ -2,6 +2,8 @@ pub use crate::config::NightshadeRuntimeExt;
 pub use crate::config::{NearConfig, init_configs, load_config, load_test_config};
 #[cfg(feature = "json_rpc")]
 use crate::entity_debug::EntityDebugHandlerImpl;
+use crate::flat_state_checker::spawn_flat_state_checker;
+use crate::memory_pressure::{MemoryPressureTargets, spawn_memory_pressure_watchdog};
 use crate::metrics::spawn_trie_metrics_loop;
 
 use crate::cold_storage::spawn_cold_store_loop;
@@ -12,7 +14,7 @@ use anyhow::Context;
 use cold_storage::ColdStoreLoopHandle;
 use near_async::actix::AddrWithAutoSpanContextExt;
 use near_async::actix_wrapper::{ActixWrapper, spawn_actix_actor};
-use near_async::futures::TokioRuntimeFutureSpawner;
+use near_async::futures::{ActixFutureSpawner, TokioRuntimeFutureSpawner};
 use near_async::messaging::{IntoMultiSender, IntoSender, LateBoundSender};
 use near_async::time::{self, Clock};
 use near_chain::rayon_spawner::RayonAsyncComputationSpawner;
@@ -30,12 +32,13 @@ use near_client::gc_actor::GCActor;
 use near_client::{
     ClientActor, ConfigUpdater, PartialWitnessActor, StartClientResult, TxRequestHandlerActor,
     TxRequestHandlerConfig, ViewClientActor, ViewClientActorInner, spawn_tx_request_handler_actor,
-    start_client,
+    start_client, start_tx_forwarding_flush_timer,
 };
 use near_epoch_manager::EpochManager;
 use near_epoch_manager::EpochManagerAdapter;
 use near_epoch_manager::shard_tracker::ShardTracker;
 use near_network::PeerManagerActor;
+use near_primitives::epoch_manager::EpochConfigStore;
 use near_primitives::genesis::GenesisId;
 use near_primitives::types::EpochId;
 use near_store::db::metadata::DbKind;
@@ -58,6 +61,8 @@ pub mod dyn_config;
 #[cfg(feature = "json_rpc")]
 pub mod entity_debug;
 mod entity_debug_serializer;
+pub mod flat_state_checker;
+pub mod memory_pressure;
 mod metrics;
 pub mod migrations;
 pub mod state_sync;
@@ -234,6 +239,20 @@ pub struct NearNode {
     pub shard_tracker: ShardTracker,
 }
 
+/// Checks the shard layouts of the epoch configs that the epoch manager loads
+/// for the chain, either built into the binary or from the `epoch_configs`
+/// directory of the home dir.
+fn validate_epoch_configs(home_dir: &Path, chain_id: &str) -> anyhow::Result<()> {
+    let config_dir = home_dir.join("epoch_configs");
+    let config_dir = config_dir.exists().then_some(config_dir);
+    if let Some(epoch_config_store) = EpochConfigStore::for_chain_id(chain_id, config_dir) {
+        epoch_config_store
+            .validate_shard_layouts()
+            .with_context(|| format!("invalid epoch configs for chain {chain_id}"))?;
+    }
+    Ok(())
+}
+
 pub fn start_with_config(home_dir: &Path, config: NearConfig) -> anyhow::Result<NearNode> {
     start_with_config_and_synchronization(home_dir, config, None, None)
 }
@@ -246,6 +265,10 @@ pub fn start_with_config_and_synchronization(
     shutdown_signal: Option<broadcast::Sender<()>>,
     config_updater: Option<ConfigUpdater>,
 ) -> anyhow::Result<NearNode> {
+    if let Some(event_log) = &config.config.event_log {
+        near_o11y::events::init_event_log(event_log, home_dir)
+            .with_context(|| format!("failed to open the event log {event_log:?}"))?;
+    }
     let storage = open_storage(home_dir, &mut config)?;
     let db_metrics_arbiter = if config.client_config.enable_statistics_export {
         let period = config.client_config.log_summary_period;
@@ -255,6 +278,7 @@ pub fn start_with_config_and_synchronization(
         None
     };
 
+    validate_epoch_configs(home_dir, &config.genesis.config.chain_id)?;
     let epoch_manager = EpochManager::new_arc_handle(
         storage.get_hot_store(),
         &config.genesis.config,
@@ -319,6 +343,38 @@ pub fn start_with_config_and_synchronization(
 
     let cold_store_loop_handle = spawn_cold_store_loop(&config, &storage, epoch_manager.clone())?;
 
+    #[cfg(feature = "json_rpc")]
+    let rpc_load_shedder =
+        near_jsonrpc::RpcLoadShedder::new(config.config.memory_pressure.shed_rpc_methods.clone());
+    let memory_pressure_arbiter = if config.config.memory_pressure.enabled {
+        let mut runtimes = vec![runtime.clone()];
+        if split_store.is_some() {
+            runtimes.push(view_runtime.clone());
+        }
+        let targets = MemoryPressureTargets {
+            tries: runtimes.iter().map(|runtime| runtime.get_tries()).collect(),
+            contract_caches: runtimes
+                .iter()
+                .map(|runtime| runtime.compiled_contract_cache().handle())
+                .collect(),
+            #[cfg(feature = "json_rpc")]
+            rpc_load_shedder: rpc_load_shedder.clone(),
+        };
+        Some(spawn_memory_pressure_watchdog(config.config.memory_pressure.clone(), targets))
+    } else {
+        None
+    };
+    let flat_state_checker_arbiter = if config.config.flat_state_checker.enabled {
+        Some(spawn_flat_state_checker(
+            config.config.flat_state_checker.clone(),
+            storage.get_hot_store(),
+            epoch_manager.clone(),
+            runtime.get_tries(),
+        ))
+    } else {
+        None
+    };
+
     let telemetry = ActixWrapper::new(TelemetryActor::new(config.telemetry_config.clone())).start();
     let chain_genesis = ChainGenesis::new(&config.genesis.config);
     let state_roots = near_store::get_genesis_state_roots(runtime.store())?
@@ -358,6 +414,7 @@ pub fn start_with_config_and_synchronization(
         runtime.get_flat_storage_manager(),
         network_adapter.as_multi_sender(),
         runtime.get_tries(),
+        config.client_config.resharding_config.clone(),
     );
     let (state_snapshot_addr, state_snapshot_arbiter) = spawn_actix_actor(state_snapshot_actor);
     state_snapshot_sender.bind(state_snapshot_addr.clone().with_auto_span_context());
@@ -401,29 +458,34 @@ pub fn start_with_config_and_synchronization(
         Arc::new(tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap());
 
     let state_sync_spawner = Arc::new(TokioRuntimeFutureSpawner(state_sync_runtime.clone()));
-    let StartClientResult { client_actor, client_arbiter_handle, resharding_handle, tx_pool } =
-        start_client(
-            Clock::real(),
-            config.client_config.clone(),
-            chain_genesis.clone(),
-            epoch_manager.clone(),
-            shard_tracker.clone(),
-            runtime.clone(),
-            node_id,
-            state_sync_spawner.clone(),
-            network_adapter.as_multi_sender(),
-            shards_manager_adapter.as_sender(),
-            config.validator_signer.clone(),
-            telemetry.with_auto_span_context().into_sender(),
-            Some(snapshot_callbacks),
-            shutdown_signal,
-            adv,
-            config_updater,
-            partial_witness_actor.clone().with_auto_span_context().into_multi_sender(),
-            true,
-            None,
-            resharding_sender.into_multi_sender(),
-        );
+    let StartClientResult {
+        client_actor,
+        client_arbiter_handle,
+        resharding_handle,
+        tx_pool,
+        block_notifications,
+    } = start_client(
+        Clock::real(),
+        config.client_config.clone(),
+        chain_genesis.clone(),
+        epoch_manager.clone(),
+        shard_tracker.clone(),
+        runtime.clone(),
+        node_id,
+        state_sync_spawner.clone(),
+        network_adapter.as_multi_sender(),
+        shards_manager_adapter.as_sender(),
+        config.validator_signer.clone(),
+        telemetry.with_auto_span_context().into_sender(),
+        Some(snapshot_callbacks),
+        shutdown_signal,
+        adv,
+        config_updater,
+        partial_witness_actor.clone().with_auto_span_context().into_multi_sender(),
+        true,
+        None,
+        resharding_sender.into_multi_sender(),
+    );
     client_adapter_for_shards_manager.bind(client_actor.clone().with_auto_span_context());
     client_adapter_for_partial_witness_actor.bind(client_actor.clone().with_auto_span_context());
     let (shards_manager_actor, shards_manager_arbiter_handle) = start_shards_manager(
@@ -443,6 +505,7 @@ pub fn start_with_config_and_synchronization(
         tx_routing_height_horizon: config.client_config.tx_routing_height_horizon,
         epoch_length: config.client_config.epoch_length,
         transaction_validity_period: config.genesis.config.transaction_validity_period,
+        tx_forwarding: config.client_config.tx_forwarding.clone(),
     };
     let tx_processor = spawn_tx_request_handler_actor(
         tx_processor_config,
@@ -453,6 +516,12 @@ pub fn start_with_config_and_synchronization(
         view_runtime.clone(),
         network_adapter.as_multi_sender(),
     );
+    start_tx_forwarding_flush_timer(
+        Clock::real(),
+        &config.client_config.tx_forwarding,
+        tx_processor.clone().with_auto_span_context().into_sender(),
+        &ActixFutureSpawner,
+    );
 
     let mut state_sync_dumper = StateSyncDumper {
         clock: Clock::real(),
@@ -505,9 +574,15 @@ pub fn start_with_config_and_synchronization(
             #[cfg(feature = "test_features")]
             _gc_actor.with_auto_span_context().into_multi_sender(),
             Arc::new(entity_debug_handler),
+            rpc_load_shedder,
+            block_notifications,
         ));
     }
 
+    // Only the RPC server subscribes to the block notifications.
+    #[cfg(not(feature = "json_rpc"))]
+    drop(block_notifications);
+
     #[cfg(feature = "rosetta_rpc")]
     if let Some(rosetta_rpc_config) = config.rosetta_rpc_config {
         rpc_servers.push((
@@ -538,6 +613,12 @@ pub fn start_with_config_and_synchronization(
     if let Some(db_metrics_arbiter) = db_metrics_arbiter {
         arbiters.push(db_metrics_arbiter);
     }
+    if let Some(memory_pressure_arbiter) = memory_pressure_arbiter {
+        arbiters.push(memory_pressure_arbiter);
+    }
+    if let Some(flat_state_checker_arbiter) = flat_state_checker_arbiter {
+        arbiters.push(flat_state_checker_arbiter);
+    }
 
     #[cfg(feature = "tx_generator")]
     let tx_generator = near_transactions_generator::actix_actor::start_tx_generator(
@@ -0,0 +1,196 @@
+//! Watchdog which degrades the node step by step when its resident memory
+//! grows too high, instead of letting the OOM killer take it down.
+//!
+//! Above the soft limit, the optional caches are given up: the view calls stop
+//! using the trie view caches, the in-memory cache of the compiled contracts
+//! stops growing and no memtries are loaded for the shards caught up for the
+//! next epoch. Above the hard limit, the node also rejects the requests of the
+//! expensive RPC methods. The node gets back to normal once the memory drops
+//! well below the limit.
+use crate::metrics::MEMORY_PRESSURE_LEVEL;
+use actix_rt::ArbiterHandle;
+use bytesize::ByteSize;
+use near_async::time::Duration;
+#[cfg(feature = "json_rpc")]
+use near_jsonrpc::RpcLoadShedder;
+use near_store::ShardTries;
+use near_vm_runner::ContractRuntimeCache;
+
+/// A level is left when the memory drops below this ratio of its limit, so
+/// that the node doesn't switch back and forth around the limit.
+const RECOVERY_RATIO: f64 = 0.9;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MemoryPressureConfig {
+    pub enabled: bool,
+    /// How often the resident memory of the process is checked.
+    #[serde(with = "near_async::time::serde_duration_as_std")]
+    pub check_period: Duration,
+    /// Resident memory above which the optional caches are disabled.
+    pub soft_limit: ByteSize,
+    /// Resident memory above which the requests of `shed_rpc_methods` are
+    /// rejected.
+    pub hard_limit: ByteSize,
+    pub shed_rpc_methods: Vec<String>,
+}
+
+impl Default for MemoryPressureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_period: Duration::seconds(5),
+            soft_limit: ByteSize::gb(48),
+            hard_limit: ByteSize::gb(56),
+            shed_rpc_methods: [
+                "query",
+                "EXPERIMENTAL_changes",
+                "EXPERIMENTAL_changes_in_block",
+                "EXPERIMENTAL_light_client_proof",
+                "light_client_proof",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum MemoryPressure {
+    Normal,
+    High,
+    Critical,
+}
+
+impl MemoryPressure {
+    /// Returns the level for the resident memory of the process, given the
+    /// current level.
+    fn next(self, config: &MemoryPressureConfig, rss: u64) -> Self {
+        let limit = |level| match level {
+            MemoryPressure::Normal => 0,
+            MemoryPressure::High => config.soft_limit.as_u64(),
+            MemoryPressure::Critical => config.hard_limit.as_u64(),
+        };
+        let reached = |level| {
+            let limit = limit(level);
+            // The current level and the levels below it are kept until the
+            // memory drops below the recovery threshold.
+            if level <= self { rss as f64 >= limit as f64 * RECOVERY_RATIO } else { rss >= limit }
+        };
+        [MemoryPressure::Critical, MemoryPressure::High]
+            .into_iter()
+            .find(|level| reached(*level))
+            .unwrap_or(MemoryPressure::Normal)
+    }
+}
+
+/// The parts of the node degraded under memory pressure.
+pub struct MemoryPressureTargets {
+    pub tries: Vec<ShardTries>,
+    pub contract_caches: Vec<Box<dyn ContractRuntimeCache>>,
+    #[cfg(feature = "json_rpc")]
+    pub rpc_load_shedder: RpcLoadShedder,
+}
+
+impl MemoryPressureTargets {
+    fn apply(&self, pressure: MemoryPressure) {
+        let low_memory = pressure >= MemoryPressure::High;
+        for tries in &self.tries {
+            tries.set_low_memory_mode(low_memory);
+        }
+        for cache in &self.contract_caches {
+            cache.memory_cache().set_insertions_enabled(!low_memory);
+        }
+        #[cfg(feature = "json_rpc")]
+        self.rpc_load_shedder.set_enabled(pressure == MemoryPressure::Critical);
+        MEMORY_PRESSURE_LEVEL.set(pressure as i64);
+    }
+}
+
+/// Parses the resident memory of a process out of its `/proc/<pid>/status`.
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line.trim_start_matches("VmRSS:").trim().strip_suffix("kB")?;
+    Some(kb.trim().parse::<u64>().ok()? * 1024)
+}
+
+fn current_rss() -> std::io::Result<Option<u64>> {
+    Ok(parse_vm_rss(&std::fs::read_to_string("/proc/self/status")?))
+}
+
+pub fn spawn_memory_pressure_watchdog(
+    config: MemoryPressureConfig,
+    targets: MemoryPressureTargets,
+) -> ArbiterHandle {
+    tracing::info!(
+        target: "memory_pressure",
+        soft_limit = %config.soft_limit,
+        hard_limit = %config.hard_limit,
+        "Spawning the memory pressure watchdog."
+    );
+    let arbiter = actix_rt::Arbiter::new();
+    let mut interval = actix_rt::time::interval(config.check_period.unsigned_abs());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    arbiter.spawn(async move {
+        let mut pressure = MemoryPressure::Normal;
+        loop {
+            interval.tick().await;
+            let rss = match current_rss() {
+                Ok(Some(rss)) => rss,
+                Ok(None) | Err(_) => {
+                    tracing::warn!(
+                        target: "memory_pressure",
+                        "Can't read the resident memory of the process, stopping the watchdog."
+                    );
+                    return;
+                }
+            };
+            let next_pressure = pressure.next(&config, rss);
+            if next_pressure == pressure {
+                continue;
+            }
+            tracing::warn!(
+                target: "memory_pressure",
+                rss = %ByteSize::b(rss),
+                from = ?pressure,
+                to = ?next_pressure,
+                "Memory pressure changed"
+            );
+            targets.apply(next_pressure);
+            pressure = next_pressure;
+        }
+    });
+    arbiter.handle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pressure_next() {
+        let config = MemoryPressureConfig {
+            soft_limit: ByteSize::b(1000),
+            hard_limit: ByteSize::b(2000),
+            ..Default::default()
+        };
+        use MemoryPressure::*;
+        assert_eq!(Normal.next(&config, 999), Normal);
+        assert_eq!(Normal.next(&config, 1000), High);
+        assert_eq!(Normal.next(&config, 2500), Critical);
+        // The levels are only left well below their limits.
+        assert_eq!(High.next(&config, 950), High);
+        assert_eq!(High.next(&config, 850), Normal);
+        assert_eq!(Critical.next(&config, 1900), Critical);
+        assert_eq!(Critical.next(&config, 1500), High);
+        assert_eq!(Critical.next(&config, 100), Normal);
+    }
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\tneard\nVmPeak:\t  200 kB\nVmRSS:\t   1234 kB\nThreads:\t8\n";
+        assert_eq!(parse_vm_rss(status), Some(1234 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tneard\n"), None);
+    }
+}
@@ -87,6 +87,41 @@ pub(crate) static STATE_SYNC_DUMP_EPOCH_HEIGHT: LazyLock<IntGaugeVec> = LazyLock
     .unwrap()
 });
 
+pub(crate) static MEMORY_PRESSURE_LEVEL: LazyLock<IntGauge> = LazyLock::new(|| {
+    try_create_int_gauge(
+        "near_memory_pressure_level",
+        "Memory pressure level set by the watchdog: 0 is normal, 1 is high, 2 is critical",
+    )
+    .unwrap()
+});
+
+pub(crate) static FLAT_STATE_CHECKER_CHECKED_KEYS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_flat_state_checker_checked_keys",
+        "Number of the flat storage keys compared against the trie by the flat state checker",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub(crate) static FLAT_STATE_CHECKER_MISMATCHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_flat_state_checker_mismatches",
+        "Number of the flat storage keys whose value doesn't match the trie",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
+pub(crate) static FLAT_STATE_CHECKER_PASSES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_flat_state_checker_passes",
+        "Number of the completed passes of the flat state checker over the flat storage of a shard",
+        &["shard_uid"],
+    )
+    .unwrap()
+});
+
 fn log_trie_item(key: &[u8], value: Vec<u8>) {
     if !tracing::level_enabled!(tracing::Level::TRACE) {
         return;
@@ -91,6 +91,12 @@ impl<'a> near_store::StoreMigrator for Migrator<'a> {
             42 => near_store::migrations::migrate_42_to_43(store),
             43 => Ok(()), // DBCol::ChunkApplyStats column added, no need to perform a migration
             44 => near_store::migrations::migrate_44_to_45(store),
+            45 => Ok(()), // DBCol::BlockProductionTimings column added, no need to perform a migration
+            46 => Ok(()), // DBCol::FlatStorageReshardingProgress column added, no need to perform a migration
+            47 => near_store::migrations::migrate_47_to_48(
+                store,
+                self.config.client_config.save_account_usage_stats,
+            ),
             DB_VERSION.. => unreachable!(),
         }
     }
@@ -433,6 +433,8 @@ class CongestionControlTest(unittest.TestCase):
 
         result = result['result']
         self.assertIn('congestion_level', result, result)
+        self.assertEqual(result['shard_id'], shard_id, result)
+        self.assertIn('congestion_info', result, result)
         return result['congestion_level']
 
 
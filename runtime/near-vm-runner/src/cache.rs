@@ -15,6 +15,7 @@ use std::any::Any;
 use std::collections::HashMap;
 use std::fmt;
 use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 #[cfg(not(windows))]
@@ -460,6 +461,8 @@ type AnyCacheValue = dyn Any + Send;
 /// Used primarily for storage of artifacts on a per-VM basis.
 pub struct AnyCache {
     cache: Option<Mutex<lru::LruCache<CryptoHash, Box<AnyCacheValue>>>>,
+    /// When set, the generated values are not put into the cache.
+    insertions_disabled: AtomicBool,
 }
 
 impl AnyCache {
@@ -470,9 +473,17 @@ impl AnyCache {
             } else {
                 None
             },
+            insertions_disabled: AtomicBool::new(false),
         }
     }
 
+    /// Stops or resumes putting new values into the cache, e.g. while the
+    /// node is short of memory. The values already in the cache are still
+    /// returned by lookups.
+    pub fn set_insertions_enabled(&self, enabled: bool) {
+        self.insertions_disabled.store(!enabled, Ordering::Relaxed);
+    }
+
     pub fn clear(&self) {
         if let Some(cache) = &self.cache {
             cache.lock().unwrap().clear();
@@ -543,7 +554,7 @@ impl AnyCache {
         }
         let generated = generate()?;
         let result = with(&*generated);
-        {
+        if !self.insertions_disabled.load(Ordering::Relaxed) {
             let mut guard = cache.lock().unwrap();
             guard.put(key, generated);
         }
@@ -669,6 +680,22 @@ mod tests {
         assert!(matches!(result, Err("mikan")));
     }
 
+    #[test]
+    fn any_cache_insertions_disabled() {
+        let cache = AnyCache::new(2);
+        let cached_key = CryptoHash::hash_bytes(b"cached");
+        let key = CryptoHash::hash_bytes(b"not cached");
+        let _ = cache.try_lookup(cached_key, || Ok::<_, ()>(Box::new(())), |_| ());
+        cache.set_insertions_enabled(false);
+        let _ = cache.try_lookup(key, || Ok::<_, ()>(Box::new(())), |_| ());
+        assert!(cache.contains(cached_key));
+        assert!(!cache.contains(key));
+
+        cache.set_insertions_enabled(true);
+        let _ = cache.try_lookup(key, || Ok::<_, ()>(Box::new(())), |_| ());
+        assert!(cache.contains(key));
+    }
+
     #[cfg(feature = "test_features")]
     #[test]
     fn test_clear_compiled_contract_cache() {
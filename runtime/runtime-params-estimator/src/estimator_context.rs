@@ -370,6 +370,7 @@ impl Testbed<'_> {
                 .tries
                 .apply_memtrie_changes(
                     &apply_result.trie_changes,
+                    &[],
                     shard_uid,
                     self.apply_state.block_height,
                 )
@@ -56,11 +56,18 @@ pub struct ReceiptSinkV2 {
     pub(crate) stats: ReceiptSinkStats,
 }
 
+/// The gas of the priority receipts forwarded to a shard past its outgoing gas
+/// limit is capped, in each chunk, at this fraction of the max outgoing gas, so
+/// that a flood of priority receipts can't defeat congestion control.
+pub(crate) const PRIORITY_OUTGOING_GAS_DIVISOR: Gas = 10;
+
 /// Limits for outgoing receipts to a shard.
 /// Receipts are sent out until the limit is hit, after that they're buffered.
 pub(crate) struct OutgoingLimit {
     pub gas: Gas,
     pub size: u64,
+    /// Gas left for the priority receipts forwarded past the gas limit.
+    pub priority_gas: Gas,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -88,13 +95,17 @@ impl ReceiptSink {
                     congestion.congestion_info,
                     congestion.missed_chunks_count,
                 );
-                let gas_limit = if shard_id != apply_state.shard_id {
-                    other_congestion_control.outgoing_gas_limit(apply_state.shard_id)
+                let (gas_limit, priority_gas_limit) = if shard_id != apply_state.shard_id {
+                    let config = &apply_state.config.congestion_control_config;
+                    (
+                        other_congestion_control.outgoing_gas_limit(apply_state.shard_id),
+                        config.max_outgoing_gas / PRIORITY_OUTGOING_GAS_DIVISOR,
+                    )
                 } else {
                     // No gas limits on receipts that stay on the same shard. Backpressure
                     // wouldn't help, the receipt takes the same memory if buffered or
                     // in the delayed receipts queue.
-                    Gas::MAX
+                    (Gas::MAX, Gas::MAX)
                 };
 
                 let size_limit = if ProtocolFeature::BandwidthScheduler.enabled(protocol_version) {
@@ -107,7 +118,12 @@ impl ReceiptSink {
                     other_congestion_control.outgoing_size_limit(apply_state.shard_id)
                 };
 
-                (shard_id, OutgoingLimit { gas: gas_limit, size: size_limit })
+                let limit = OutgoingLimit {
+                    gas: gas_limit,
+                    size: size_limit,
+                    priority_gas: priority_gas_limit,
+                };
+                (shard_id, limit)
             })
             .collect();
 
@@ -301,6 +317,7 @@ impl ReceiptSinkV2 {
                 gas,
                 size,
                 target_shard_id,
+                false,
                 &mut self.outgoing_limit,
                 &mut self.outgoing_receipts,
                 apply_state,
@@ -350,12 +367,14 @@ impl ReceiptSinkV2 {
         let shard = receipt.receiver_shard_id(&shard_layout)?;
         let size = compute_receipt_size(&receipt)?;
         let gas = compute_receipt_congestion_gas(&receipt, &apply_state.config)?;
+        let is_priority = is_priority_receipt(&receipt, apply_state);
 
         match Self::try_forward(
             receipt,
             gas,
             size,
             shard,
+            is_priority,
             &mut self.outgoing_limit,
             &mut self.outgoing_receipts,
             apply_state,
@@ -382,11 +401,16 @@ impl ReceiptSinkV2 {
     /// This does not take `&mut self` as first argument to make lifetime
     /// management easier. Instead it takes exactly the fields it requires,
     /// namely `outgoing_limit` and `outgoing_receipt`.
+    ///
+    /// With `is_priority` the receipt is also forwarded past the gas limit, as
+    /// long as it fits in the priority gas left for the shard. The gas limit is
+    /// still reduced but never below zero.
     fn try_forward(
         receipt: Receipt,
         gas: u64,
         mut size: u64,
         shard: ShardId,
+        is_priority: bool,
         outgoing_limit: &mut HashMap<ShardId, OutgoingLimit>,
         outgoing_receipts: &mut Vec<Receipt>,
         apply_state: &ApplyState,
@@ -425,21 +449,27 @@ impl ReceiptSinkV2 {
             };
 
         let default_outgoing_limit =
-            OutgoingLimit { gas: default_gas_limit, size: default_size_limit };
+            OutgoingLimit { gas: default_gas_limit, size: default_size_limit, priority_gas: 0 };
         let forward_limit = outgoing_limit.entry(shard).or_insert(default_outgoing_limit);
 
-        let can_forward =
+        let (fits_gas_limit, fits_size_limit) =
             if ProtocolFeature::BandwidthScheduler.enabled(apply_state.current_protocol_version) {
-                forward_limit.gas >= gas && forward_limit.size >= size
+                (forward_limit.gas >= gas, forward_limit.size >= size)
             } else {
-                forward_limit.gas > gas && forward_limit.size > size
+                (forward_limit.gas > gas, forward_limit.size > size)
             };
+        let use_priority_gas = !fits_gas_limit && is_priority && forward_limit.priority_gas >= gas;
+        let can_forward = (fits_gas_limit || use_priority_gas) && fits_size_limit;
 
         if can_forward {
             tracing::trace!(target: "runtime", ?shard, receipt_id=?receipt.receipt_id(), "forwarding buffered receipt");
             outgoing_receipts.push(receipt);
-            // underflow impossible: checked forward_limit > gas/size_to_forward above
-            forward_limit.gas -= gas;
+            // underflow impossible: checked forward_limit > gas/size_to_forward
+            // above, unless the priority gas is used
+            if use_priority_gas {
+                forward_limit.priority_gas -= gas;
+            }
+            forward_limit.gas = forward_limit.gas.saturating_sub(gas);
             forward_limit.size -= size;
             stats.forwarded_receipts.entry(shard).or_default().add_receipt(size, gas);
 
@@ -652,6 +682,24 @@ impl ReceiptSinkV2 {
     }
 }
 
+/// Whether a newly produced receipt should be forwarded ahead of the outgoing
+/// buffer, past the outgoing gas limit of the receiving shard, see
+/// [PRIORITY_OUTGOING_GAS_DIVISOR].
+///
+/// Refunds, promise resume receipts and receipts with a priority are only
+/// prioritized if `prioritize_outgoing_receipts` is enabled. Receipts that are
+/// already buffered are always forwarded in order.
+fn is_priority_receipt(receipt: &Receipt, apply_state: &ApplyState) -> bool {
+    if !ProtocolFeature::OutgoingReceiptsPriority.enabled(apply_state.current_protocol_version)
+        || !apply_state.config.congestion_control_config.prioritize_outgoing_receipts
+    {
+        return false;
+    }
+    receipt.predecessor_id().is_system()
+        || matches!(receipt.receipt(), ReceiptEnum::PromiseResume(_))
+        || receipt.priority().value() > 0
+}
+
 /// Get the receipt gas from the receipt that was retrieved from the state.
 /// If it is a [Receipt], the gas will be computed.
 /// If it s the [StateStoredReceipt], the size will be read from the metadata.
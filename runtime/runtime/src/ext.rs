@@ -559,7 +559,7 @@ pub struct AccountingState {
 }
 
 impl AccountingState {
-    fn get_counts(&self) -> TrieNodesCount {
+    pub(crate) fn get_counts(&self) -> TrieNodesCount {
         TrieNodesCount {
             db_reads: self.db_reads.load(Ordering::Relaxed),
             mem_reads: self.mem_reads.load(Ordering::Relaxed),
@@ -29,7 +29,7 @@ use near_parameters::{ActionCosts, RuntimeConfig};
 pub use near_primitives;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::bandwidth_scheduler::{BandwidthRequests, BlockBandwidthRequests};
-use near_primitives::chunk_apply_stats::ChunkApplyStatsV0;
+use near_primitives::chunk_apply_stats::{ChunkApplyStatsV0, ReceiptExecutionProfile};
 use near_primitives::congestion_info::{BlockCongestionInfo, CongestionInfo};
 use near_primitives::errors::{
     ActionError, ActionErrorKind, EpochError, IntegerOverflowError, InvalidAccessKeyError,
@@ -75,6 +75,7 @@ use near_vm_runner::ProfileDataV3;
 use near_vm_runner::logic::ReturnData;
 use near_vm_runner::logic::types::PromiseResult;
 pub use near_vm_runner::with_ext_cost_counter;
+use parallel_receipts::{ParallelReceiptOutput, ParallelReceipts};
 use pipelining::ReceiptPreparationPipeline;
 use rayon::prelude::*;
 use std::cmp::max;
@@ -93,6 +94,7 @@ mod conversions;
 pub mod ext;
 mod global_contracts;
 pub mod metrics;
+mod parallel_receipts;
 mod pipelining;
 mod prefetch;
 pub mod receipt_manager;
@@ -369,11 +371,26 @@ impl Default for ActionResult {
     }
 }
 
-pub struct Runtime {}
+pub struct Runtime {
+    /// Whether the transfers to distinct accounts are executed in parallel,
+    /// see the `parallel_receipts` module.
+    parallel_receipts: bool,
+    /// Whether a profile of every executed receipt is recorded in the stats
+    /// of the chunk application.
+    record_receipt_profiles: bool,
+}
 
 impl Runtime {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            parallel_receipts: cfg!(feature = "parallel_receipts"),
+            record_receipt_profiles: false,
+        }
+    }
+
+    pub fn with_receipt_profiles(mut self, record_receipt_profiles: bool) -> Self {
+        self.record_receipt_profiles = record_receipt_profiles;
+        self
     }
 
     fn print_log(log: &[LogEntry]) {
@@ -778,6 +795,38 @@ impl Runtime {
         stats: &mut ChunkApplyStatsV0,
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<ExecutionOutcomeWithId, RuntimeError> {
+        let (outcome, new_receipts) = self.execute_action_receipt(
+            state_update,
+            apply_state,
+            preparation_pipeline,
+            receipt,
+            validator_proposals,
+            stats,
+            epoch_info_provider,
+        )?;
+        for new_receipt in new_receipts {
+            receipt_sink.forward_or_buffer_receipt(
+                new_receipt,
+                apply_state,
+                state_update,
+                epoch_info_provider,
+            )?;
+        }
+        Ok(outcome)
+    }
+
+    /// Executes the action receipt without forwarding the receipts it
+    /// produces, which are returned together with the outcome.
+    fn execute_action_receipt(
+        &self,
+        state_update: &mut TrieUpdate,
+        apply_state: &ApplyState,
+        preparation_pipeline: &ReceiptPreparationPipeline,
+        receipt: &Receipt,
+        validator_proposals: &mut Vec<ValidatorStake>,
+        stats: &mut ChunkApplyStatsV0,
+        epoch_info_provider: &dyn EpochInfoProvider,
+    ) -> Result<(ExecutionOutcomeWithId, Vec<Receipt>), RuntimeError> {
         let _span = tracing::debug_span!(
             target: "runtime",
             "apply_action_receipt",
@@ -1016,33 +1065,18 @@ impl Runtime {
         }
 
         // Generating receipt IDs
-        let receipt_ids = result
-            .new_receipts
-            .into_iter()
-            .enumerate()
-            .filter_map(|(receipt_index, mut new_receipt)| {
-                let receipt_id = apply_state.create_receipt_id(receipt.receipt_id(), receipt_index);
-                new_receipt.set_receipt_id(receipt_id);
-                let is_action = matches!(
-                    new_receipt.receipt(),
-                    ReceiptEnum::Action(_) | ReceiptEnum::PromiseYield(_)
-                );
-
-                let res = receipt_sink.forward_or_buffer_receipt(
-                    new_receipt,
-                    apply_state,
-                    state_update,
-                    epoch_info_provider,
-                );
-                if let Err(e) = res {
-                    Some(Err(e))
-                } else if is_action {
-                    Some(Ok(receipt_id))
-                } else {
-                    None
-                }
-            })
-            .collect::<Result<_, _>>()?;
+        let mut new_receipts = result.new_receipts;
+        let mut receipt_ids = vec![];
+        for (receipt_index, new_receipt) in new_receipts.iter_mut().enumerate() {
+            let receipt_id = apply_state.create_receipt_id(receipt.receipt_id(), receipt_index);
+            new_receipt.set_receipt_id(receipt_id);
+            if matches!(
+                new_receipt.receipt(),
+                ReceiptEnum::Action(_) | ReceiptEnum::PromiseYield(_)
+            ) {
+                receipt_ids.push(receipt_id);
+            }
+        }
 
         let status = match result.result {
             Ok(ReturnData::ReceiptIndex(receipt_index)) => ExecutionStatus::SuccessReceiptId(
@@ -1055,7 +1089,7 @@ impl Runtime {
 
         Self::print_log(&result.logs);
 
-        Ok(ExecutionOutcomeWithId {
+        let outcome = ExecutionOutcomeWithId {
             id: *receipt.receipt_id(),
             outcome: ExecutionOutcome {
                 status,
@@ -1069,7 +1103,8 @@ impl Runtime {
                     *result.profile,
                 ))),
             },
-        })
+        };
+        Ok((outcome, new_receipts))
     }
 
     fn generate_refund_receipts(
@@ -1751,14 +1786,63 @@ impl Runtime {
         Ok(())
     }
 
+    /// Applies the output of a receipt executed in parallel in the same way as
+    /// [Runtime::apply_action_receipt] would apply it.
+    fn merge_parallel_receipt_output(
+        &self,
+        processing_state: &mut ApplyProcessingReceiptState,
+        receipt: &Receipt,
+        receipt_sink: &mut ReceiptSink,
+        validator_proposals: &mut Vec<ValidatorStake>,
+        mut output: ParallelReceiptOutput,
+    ) -> Result<ExecutionOutcomeWithId, RuntimeError> {
+        let ApplyProcessingReceiptState {
+            ref mut state_update,
+            apply_state,
+            epoch_info_provider,
+            ref mut stats,
+            ..
+        } = *processing_state;
+        state_update.commit(StateChangeCause::ActionReceiptProcessingStarted {
+            receipt_hash: receipt.get_hash(),
+        });
+        state_update.merge_fork(output.state_update);
+        for new_receipt in output.new_receipts {
+            receipt_sink.forward_or_buffer_receipt(
+                new_receipt,
+                apply_state,
+                state_update,
+                epoch_info_provider,
+            )?;
+        }
+        let balance = &mut stats.balance;
+        balance.tx_burnt_amount =
+            safe_add_balance(balance.tx_burnt_amount, output.balance.tx_burnt_amount)?;
+        balance.slashed_burnt_amount =
+            safe_add_balance(balance.slashed_burnt_amount, output.balance.slashed_burnt_amount)?;
+        balance.other_burnt_amount =
+            safe_add_balance(balance.other_burnt_amount, output.balance.other_burnt_amount)?;
+        balance.gas_deficit_amount =
+            safe_add_balance(balance.gas_deficit_amount, output.balance.gas_deficit_amount)?;
+        balance.global_actions_burnt_amount = safe_add_balance(
+            balance.global_actions_burnt_amount,
+            output.balance.global_actions_burnt_amount,
+        )?;
+        validator_proposals.append(&mut output.validator_proposals);
+        Ok(output.outcome)
+    }
+
     /// This function wraps [Runtime::process_receipt]. It adds a tracing span around the latter
     /// and populates various metrics.
+    ///
+    /// If the receipt was already executed by [`ParallelReceipts`], its output is merged instead.
     fn process_receipt_with_metrics<'a>(
         &self,
         receipt: &Receipt,
         processing_state: &mut ApplyProcessingReceiptState<'a>,
         mut receipt_sink: &mut ReceiptSink,
         mut validator_proposals: &mut Vec<ValidatorStake>,
+        parallel_output: Option<ParallelReceiptOutput>,
     ) -> Result<(), RuntimeError> {
         let span = tracing::debug_span!(
             target: "runtime",
@@ -1775,14 +1859,27 @@ impl Runtime {
         let trie = state_update.trie();
         let recorded_storage_size_before = trie.recorded_storage_size();
         let storage_proof_size_upper_bound_before = trie.recorded_storage_size_upper_bound();
+        let start_time = std::time::Instant::now();
+        let trie_nodes_before = processing_state.apply_state.trie_access_tracker_state.get_counts();
 
         // Main logic
-        let result = self.process_receipt(
-            processing_state,
-            receipt,
-            &mut receipt_sink,
-            &mut validator_proposals,
-        );
+        let result = match parallel_output {
+            Some(output) => self
+                .merge_parallel_receipt_output(
+                    processing_state,
+                    receipt,
+                    receipt_sink,
+                    validator_proposals,
+                    output,
+                )
+                .map(Some),
+            None => self.process_receipt(
+                processing_state,
+                receipt,
+                &mut receipt_sink,
+                &mut validator_proposals,
+            ),
+        };
 
         let shard_id_str = processing_state.apply_state.shard_id.to_string();
         let trie = processing_state.state_update.trie();
@@ -1817,6 +1914,33 @@ impl Runtime {
             span.record("gas_burnt", gas_burnt);
             span.record("compute_usage", compute_usage);
 
+            if self.record_receipt_profiles {
+                // The time of the receipts executed in parallel only covers the
+                // merge of their output.
+                let trie_nodes =
+                    processing_state.apply_state.trie_access_tracker_state.get_counts();
+                let outcome = &outcome_with_id.outcome;
+                let gas_profile = match &outcome.metadata {
+                    ExecutionMetadata::V3(profile) => (**profile).clone(),
+                    _ => Default::default(),
+                };
+                processing_state.stats.receipt_profiles.push(ReceiptExecutionProfile {
+                    receipt_id: outcome_with_id.id,
+                    executor_id: outcome.executor_id.clone(),
+                    gas_burnt,
+                    compute_usage,
+                    gas_profile,
+                    trie_node_db_reads: trie_nodes
+                        .db_reads
+                        .saturating_sub(trie_nodes_before.db_reads),
+                    trie_node_mem_reads: trie_nodes
+                        .mem_reads
+                        .saturating_sub(trie_nodes_before.mem_reads),
+                    recorded_storage_size: recorded_storage_diff as u64,
+                    outgoing_receipts: outcome.receipt_ids.clone(),
+                    execution_time_us: start_time.elapsed().as_micros() as u64,
+                });
+            }
             processing_state.outcomes.push(outcome_with_id);
         }
         Ok(())
@@ -1883,6 +2007,7 @@ impl Runtime {
                     &mut processing_state,
                     receipt_sink,
                     validator_proposals,
+                    None,
                 )?
             }
         }
@@ -1984,6 +2109,7 @@ impl Runtime {
                 &mut processing_state,
                 receipt_sink,
                 validator_proposals,
+                None,
             )?;
             processed_delayed_receipts.push(receipt);
         }
@@ -2028,7 +2154,9 @@ impl Runtime {
             &mut prep_lookahead_iter,
         );
 
-        for receipt in processing_state.incoming_receipts.iter() {
+        let incoming_receipts = processing_state.incoming_receipts;
+        let mut parallel_receipts = ParallelReceipts::new(self.parallel_receipts);
+        for (index, receipt) in incoming_receipts.iter().enumerate() {
             // Validating new incoming no matter whether we have available gas or not. We don't
             // want to store invalid receipts in state as delayed.
             validate_receipt(
@@ -2061,11 +2189,14 @@ impl Runtime {
                     }
                 }
 
+                let parallel_output =
+                    parallel_receipts.take(self, processing_state, incoming_receipts, index)?;
                 self.process_receipt_with_metrics(
                     &receipt,
                     &mut processing_state,
                     receipt_sink,
                     validator_proposals,
+                    parallel_output,
                 )?;
             }
         }
@@ -2423,7 +2554,8 @@ fn resolve_promise_yield_timeouts(
             })?;
 
         // Queue entries are ordered by expires_at
-        if queue_entry.expires_at > apply_state.block_height {
+        if queue_entry.resolve_height(processing_state.protocol_version) > apply_state.block_height
+        {
             break;
         }
 
@@ -2762,7 +2894,7 @@ pub mod estimator {
             apply_state.cache.as_ref().map(|c| c.handle()),
             state_update.contract_storage(),
         );
-        let apply_result = Runtime {}.apply_action_receipt(
+        let apply_result = Runtime::new().apply_action_receipt(
             state_update,
             apply_state,
             &empty_pipeline,
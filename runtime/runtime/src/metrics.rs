@@ -111,6 +111,16 @@ pub static YIELD_TIMEOUTS_PROCESSING_SECONDS_TOTAL: LazyLock<CounterVec> = LazyL
     .unwrap()
 });
 
+pub static PARALLEL_RECEIPTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_parallel_receipts_total",
+        "The number of receipts executed in parallel and the number of those whose outputs \
+         were applied, the rest got delayed",
+        &["shard_id", "status"],
+    )
+    .unwrap()
+});
+
 pub static TRANSACTION_PROCESSED_SUCCESSFULLY_TOTAL: LazyLock<IntCounter> = LazyLock::new(|| {
     try_create_int_counter(
         "near_transaction_processed_successfully_total",
@@ -0,0 +1,224 @@
+//! Parallel execution of the receipts which don't conflict with each other.
+//!
+//! The receipts of a chunk are applied one by one, each on top of the changes
+//! of the previous ones. However, a plain transfer to a named account only
+//! reads and writes the account and the access keys of its receiver, so a run
+//! of transfers to distinct receivers, whose accounts weren't changed yet in
+//! the chunk, gives the same result in any order. Such runs are executed in
+//! parallel, each receipt on its own fork of the state, and the outputs are
+//! merged in the original order once the serial loop reaches the receipts.
+//! The loop still checks the compute and storage proof limits before each
+//! receipt and discards the outputs of the receipts which get delayed, so the
+//! result of the chunk application doesn't depend on whether the parallel
+//! execution is enabled. All the other receipts are executed serially, as
+//! well as the local and the delayed receipts. The receiver of a local receipt
+//! is the signer of its transaction, whose account was just charged for it.
+use crate::metrics::PARALLEL_RECEIPTS_TOTAL;
+use crate::{ApplyProcessingReceiptState, Runtime};
+use near_primitives::action::Action;
+use near_primitives::chunk_apply_stats::{BalanceStats, ChunkApplyStatsV0};
+use near_primitives::errors::RuntimeError;
+use near_primitives::receipt::{Receipt, ReceiptEnum};
+use near_primitives::transaction::ExecutionOutcomeWithId;
+use near_primitives::trie_key::{TrieKey, trie_key_parsers};
+use near_primitives::types::validator_stake::ValidatorStake;
+use near_primitives_core::account::id::AccountType;
+use near_store::TrieUpdate;
+use rayon::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+/// Shorter runs of conflict-free receipts are executed serially, since there
+/// is nothing to parallelize.
+const MIN_PARALLEL_RECEIPTS: usize = 2;
+
+/// Output of a receipt executed ahead of its turn.
+pub(crate) struct ParallelReceiptOutput {
+    /// Fork of the chunk state with the committed changes of the receipt.
+    pub(crate) state_update: TrieUpdate,
+    pub(crate) outcome: ExecutionOutcomeWithId,
+    /// Receipts produced by the receipt, which are not forwarded yet.
+    pub(crate) new_receipts: Vec<Receipt>,
+    pub(crate) balance: BalanceStats,
+    pub(crate) validator_proposals: Vec<ValidatorStake>,
+}
+
+/// Executes the runs of conflict-free receipts of a list of receipts which is
+/// being applied serially.
+pub(crate) struct ParallelReceipts {
+    enabled: bool,
+    /// Index of the receipt whose output is at the front of `outputs`.
+    next_index: usize,
+    outputs: VecDeque<Result<ParallelReceiptOutput, RuntimeError>>,
+}
+
+impl ParallelReceipts {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self { enabled, next_index: 0, outputs: VecDeque::new() }
+    }
+
+    /// Returns the output of the receipt at `index` if it belongs to a run of
+    /// conflict-free receipts, executing the run starting at `index` if it
+    /// wasn't executed yet. Must be called in the increasing order of indices,
+    /// right before applying the receipt, and not for the delayed receipts.
+    pub(crate) fn take(
+        &mut self,
+        runtime: &Runtime,
+        processing_state: &ApplyProcessingReceiptState,
+        receipts: &[Receipt],
+        index: usize,
+    ) -> Result<Option<ParallelReceiptOutput>, RuntimeError> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let shard_id = processing_state.apply_state.shard_id.to_string();
+        // The outputs of the delayed receipts are discarded.
+        while self.next_index < index && self.outputs.pop_front().is_some() {
+            self.next_index += 1;
+        }
+        if self.outputs.is_empty() {
+            let receipts = &receipts[index..];
+            let len = conflict_free_len(receipts, &processing_state.state_update);
+            if len < MIN_PARALLEL_RECEIPTS {
+                return Ok(None);
+            }
+            self.outputs = execute(runtime, processing_state, &receipts[..len]).into();
+            self.next_index = index;
+            PARALLEL_RECEIPTS_TOTAL
+                .with_label_values(&[shard_id.as_str(), "executed"])
+                .inc_by(len as u64);
+        }
+        debug_assert_eq!(self.next_index, index);
+        self.next_index += 1;
+        PARALLEL_RECEIPTS_TOTAL.with_label_values(&[shard_id.as_str(), "applied"]).inc();
+        self.outputs.pop_front().transpose()
+    }
+}
+
+/// Whether the receipt only touches the account and the access keys of its
+/// receiver.
+fn is_parallelizable(receipt: &Receipt) -> bool {
+    let ReceiptEnum::Action(action_receipt) = receipt.receipt() else {
+        return false;
+    };
+    // Transfers to implicit accounts may create them, which touches more
+    // state, e.g. the wallet contract of ETH-implicit accounts.
+    action_receipt.input_data_ids.is_empty()
+        && !action_receipt.actions.is_empty()
+        && action_receipt.actions.iter().all(|action| matches!(action, Action::Transfer(_)))
+        && matches!(receipt.receiver_id().get_account_type(), AccountType::NamedAccount)
+}
+
+/// Returns the length of the run of receipts at the start of `receipts` which
+/// can be executed independently of each other and of the changes already
+/// done in `state_update`.
+fn conflict_free_len(receipts: &[Receipt], state_update: &TrieUpdate) -> usize {
+    let mut receivers = HashSet::new();
+    receipts
+        .iter()
+        .take_while(|receipt| {
+            let receiver_id = receipt.receiver_id();
+            is_parallelizable(receipt)
+                && receivers.insert(receiver_id)
+                && !state_update.has_changes_with_prefix(
+                    &TrieKey::Account { account_id: receiver_id.clone() }.to_vec(),
+                )
+                && !state_update.has_changes_with_prefix(
+                    &trie_key_parsers::get_raw_prefix_for_access_keys(receiver_id),
+                )
+        })
+        .count()
+}
+
+fn execute(
+    runtime: &Runtime,
+    processing_state: &ApplyProcessingReceiptState,
+    receipts: &[Receipt],
+) -> Vec<Result<ParallelReceiptOutput, RuntimeError>> {
+    let _span = tracing::debug_span!(
+        target: "runtime",
+        "execute_parallel_receipts",
+        num_receipts = receipts.len(),
+    )
+    .entered();
+    let ApplyProcessingReceiptState {
+        apply_state,
+        epoch_info_provider,
+        ref state_update,
+        ref pipeline_manager,
+        ..
+    } = *processing_state;
+    receipts
+        .par_iter()
+        .map(|receipt| {
+            let mut state_update = state_update.fork();
+            let mut stats = ChunkApplyStatsV0::new(apply_state.block_height, apply_state.shard_id);
+            let mut validator_proposals = vec![];
+            let (outcome, new_receipts) = runtime.execute_action_receipt(
+                &mut state_update,
+                apply_state,
+                pipeline_manager,
+                receipt,
+                &mut validator_proposals,
+                &mut stats,
+                epoch_info_provider,
+            )?;
+            Ok(ParallelReceiptOutput {
+                state_update,
+                outcome,
+                new_receipts,
+                balance: stats.balance,
+                validator_proposals,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, PublicKey};
+    use near_primitives::account::AccessKey;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::receipt::{DataReceipt, ReceiptPriority, ReceiptV0};
+    use near_primitives::types::AccountId;
+    use near_store::test_utils::TestTriesBuilder;
+    use near_store::{ShardUId, Trie, set_access_key};
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn transfer(receiver_id: &str) -> Receipt {
+        Receipt::new_balance_refund(&account(receiver_id), 1, ReceiptPriority::NoPriority)
+    }
+
+    #[test]
+    fn test_conflict_free_len() {
+        let tries = TestTriesBuilder::new().build();
+        let mut state_update = tries.new_trie_update(ShardUId::single_shard(), Trie::EMPTY_ROOT);
+        let receipts = [transfer("alice.near"), transfer("bob.near"), transfer("alice.near")];
+        assert_eq!(conflict_free_len(&receipts, &state_update), 2);
+        assert_eq!(conflict_free_len(&receipts[1..], &state_update), 2);
+
+        let data_receipt = Receipt::V0(ReceiptV0 {
+            predecessor_id: account("alice.near"),
+            receiver_id: account("bob.near"),
+            receipt_id: CryptoHash::default(),
+            receipt: ReceiptEnum::Data(DataReceipt { data_id: CryptoHash::default(), data: None }),
+        });
+        assert_eq!(conflict_free_len(&[transfer("alice.near"), data_receipt], &state_update), 1);
+        let implicit_account_id = "0".repeat(64);
+        assert_eq!(conflict_free_len(&[transfer(&implicit_account_id)], &state_update), 0);
+
+        // The receivers whose access keys were changed in the chunk conflict
+        // with the changes.
+        set_access_key(
+            &mut state_update,
+            account("bob.near"),
+            PublicKey::empty(KeyType::ED25519),
+            &AccessKey::full_access(),
+        );
+        assert_eq!(conflict_free_len(&receipts, &state_update), 1);
+        assert_eq!(conflict_free_len(&receipts[1..], &state_update), 0);
+    }
+}
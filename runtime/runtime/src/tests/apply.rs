@@ -1,6 +1,8 @@
 use super::{GAS_PRICE, to_yocto};
 use crate::config::safe_add_gas;
-use crate::congestion_control::{compute_receipt_congestion_gas, compute_receipt_size};
+use crate::congestion_control::{
+    PRIORITY_OUTGOING_GAS_DIVISOR, compute_receipt_congestion_gas, compute_receipt_size,
+};
 use crate::tests::{
     MAX_ATTACHED_GAS, create_receipt_for_create_account, create_receipt_with_actions,
     set_sha256_cost,
@@ -10,7 +12,7 @@ use crate::{SignedValidPeriodTransactions, total_prepaid_exec_fees};
 use assert_matches::assert_matches;
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signer};
 use near_o11y::testonly::init_test_logger;
-use near_parameters::{ActionCosts, RuntimeConfig};
+use near_parameters::{ActionCosts, ExtCosts, RuntimeConfig};
 use near_primitives::account::AccessKey;
 use near_primitives::action::delegate::{DelegateAction, NonDelegateAction, SignedDelegateAction};
 use near_primitives::action::{Action, DeleteAccountAction};
@@ -38,7 +40,7 @@ use near_primitives::utils::create_receipt_id_from_transaction;
 use near_primitives::version::{PROTOCOL_VERSION, ProtocolFeature};
 use near_store::test_utils::TestTriesBuilder;
 use near_store::trie::AccessOptions;
-use near_store::trie::receipts_column_helper::ShardsOutgoingReceiptBuffer;
+use near_store::trie::receipts_column_helper::{ShardsOutgoingReceiptBuffer, TrieQueue};
 use near_store::{
     MissingTrieValueContext, ShardTries, StorageError, Trie, get_account, set_access_key,
     set_account,
@@ -1112,6 +1114,59 @@ fn test_compute_usage_limit() {
     });
 }
 
+#[test]
+fn test_receipt_execution_profiles() {
+    let (runtime, tries, root, apply_state, signers, epoch_info_provider) =
+        setup_runtime(vec![alice_account()], to_yocto(1_000_000), to_yocto(500_000), 10u64.pow(15));
+    let deploy_contract_receipt = create_receipt_with_actions(
+        alice_account(),
+        signers[0].clone(),
+        vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::rs_contract().to_vec(),
+        })],
+    );
+    let call_receipt = create_receipt_with_actions(
+        alice_account(),
+        signers[0].clone(),
+        vec![Action::FunctionCall(Box::new(FunctionCallAction {
+            method_name: "ext_sha256".to_string(),
+            args: b"data".to_vec(),
+            gas: MAX_ATTACHED_GAS,
+            deposit: 0,
+        }))],
+    );
+    let receipts = [deploy_contract_receipt.clone(), call_receipt.clone()];
+    let apply = |runtime: &Runtime| {
+        runtime
+            .apply(
+                tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                &None,
+                &apply_state,
+                &receipts,
+                SignedValidPeriodTransactions::empty(),
+                &epoch_info_provider,
+                Default::default(),
+            )
+            .unwrap()
+    };
+
+    // The profiles are only recorded when enabled.
+    assert!(apply(&runtime).stats.receipt_profiles.is_empty());
+    let apply_result = apply(&Runtime::new().with_receipt_profiles(true));
+    let profiles = &apply_result.stats.receipt_profiles;
+    assert_eq!(profiles.len(), apply_result.outcomes.len());
+    for (profile, outcome) in profiles.iter().zip(&apply_result.outcomes) {
+        assert_eq!(profile.receipt_id, outcome.id);
+        assert_eq!(profile.executor_id, alice_account());
+        assert_eq!(profile.gas_burnt, outcome.outcome.gas_burnt);
+        assert_eq!(profile.outgoing_receipts, outcome.outcome.receipt_ids);
+    }
+    let call_profile =
+        profiles.iter().find(|profile| &profile.receipt_id == call_receipt.receipt_id()).unwrap();
+    assert!(call_profile.gas_profile.get_wasm_cost() > 0);
+    assert!(call_profile.gas_profile.get_ext_cost(ExtCosts::sha256_base) > 0);
+}
+
 #[test]
 fn test_compute_usage_limit_with_failed_receipt() {
     let (runtime, tries, root, apply_state, signers, epoch_info_provider) =
@@ -2613,6 +2668,171 @@ fn test_congestion_buffering() {
     }
 }
 
+/// Applies receipts producing refunds to a fully congested shard, with the
+/// given `prioritize_outgoing_receipts` and `max_outgoing_gas`. Returns the
+/// outgoing receipts, the number of buffered receipts and the number of
+/// buffered refunds, along with the runtime config.
+fn apply_refunds_to_congested_shard(
+    prioritize: bool,
+    max_outgoing_gas: Option<Gas>,
+    n: u64,
+) -> (Vec<Receipt>, u64, usize, Arc<RuntimeConfig>) {
+    let version = 3;
+    let accounts = vec![alice_account(), bob_account()];
+    let shard_layout = ShardLayout::multi_shard_custom(accounts.clone(), version);
+    let local_shard = shard_layout.account_id_to_shard_id(&alice_account());
+    let local_shard_uid = ShardUId::new(version, local_shard);
+    let receiver_shard = shard_layout.account_id_to_shard_id(&bob_account());
+    assert_ne!(local_shard, receiver_shard);
+
+    let deposit = to_yocto(10_000);
+    // The delegate actions produce function calls to bob, the receipts from
+    // bob fail and produce refunds to bob.
+    let mut receipts = generate_delegate_actions(deposit, n);
+    receipts.extend((0..n).map(|i| {
+        Receipt::V0(ReceiptV0 {
+            predecessor_id: bob_account(),
+            receiver_id: alice_account(),
+            receipt_id: hash(&i.to_le_bytes()),
+            receipt: ReceiptEnum::Action(ActionReceipt {
+                signer_id: bob_account(),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: GAS_PRICE,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions: vec![
+                    Action::Transfer(TransferAction { deposit }),
+                    // Only alice can delete her keys.
+                    Action::DeleteKey(Box::new(DeleteKeyAction {
+                        public_key: PublicKey::empty(KeyType::ED25519),
+                    })),
+                ],
+            }),
+        })
+    }));
+
+    let accounts_with_keys = accounts
+        .iter()
+        .map(|account| {
+            let signer = Arc::new(InMemorySigner::test_signer(account));
+            (account.clone(), vec![signer])
+        })
+        .collect::<Vec<_>>();
+    let (runtime, tries, root, mut apply_state, _) = setup_runtime_for_shard(
+        accounts_with_keys,
+        to_yocto(1_000_000),
+        to_yocto(500_000),
+        10u64.pow(15),
+        local_shard_uid,
+        &shard_layout,
+    );
+    let epoch_info_provider = MockEpochInfoProvider::new(shard_layout.clone());
+    apply_state.shard_id = local_shard;
+    let mut runtime_config = RuntimeConfig::test();
+    runtime_config.congestion_control_config.prioritize_outgoing_receipts = prioritize;
+    if let Some(max_outgoing_gas) = max_outgoing_gas {
+        runtime_config.congestion_control_config.max_outgoing_gas = max_outgoing_gas;
+    }
+    apply_state.config = Arc::new(runtime_config);
+
+    // Mark the receiver shard as fully congested, so that the outgoing gas
+    // limit to it is zero.
+    let max_congestion_incoming_gas: Gas =
+        apply_state.config.congestion_control_config.max_congestion_incoming_gas;
+    let receiver_congestion_info =
+        &mut apply_state.congestion_info.get_mut(&receiver_shard).unwrap().congestion_info;
+    receiver_congestion_info.add_delayed_receipt_gas(max_congestion_incoming_gas).unwrap();
+    receiver_congestion_info.set_allowed_shard(receiver_shard.into());
+    apply_state.congestion_info.insert(local_shard, Default::default());
+    assert_eq!(
+        apply_state.congestion_control(receiver_shard, 0).outgoing_gas_limit(local_shard),
+        0
+    );
+
+    let apply_result = runtime
+        .apply(
+            tries.get_trie_for_shard(local_shard_uid, root),
+            &None,
+            &apply_state,
+            &receipts,
+            SignedValidPeriodTransactions::empty(),
+            &epoch_info_provider,
+            Default::default(),
+        )
+        .unwrap();
+    let root = commit_apply_result(&apply_result, &mut apply_state, &tries, local_shard_uid);
+
+    let state = tries.get_trie_for_shard(local_shard_uid, root);
+    let mut buffers = ShardsOutgoingReceiptBuffer::load(&state).unwrap();
+    let buffered_refunds = buffers
+        .to_shard(receiver_shard)
+        .iter(&state, false)
+        .map(|receipt| receipt.unwrap().into_receipt())
+        .filter(|receipt| receipt.predecessor_id().is_system())
+        .count();
+    let num_buffered = buffers.buffer_len(receiver_shard).unwrap();
+    for receipt in &apply_result.outgoing_receipts {
+        assert!(receipt.predecessor_id().is_system());
+        assert_eq!(receipt.receiver_id(), &bob_account());
+    }
+    (apply_result.outgoing_receipts, num_buffered, buffered_refunds, apply_state.config.clone())
+}
+
+/// Test that with `prioritize_outgoing_receipts` refunds to a congested shard
+/// are forwarded right away, while other receipts are held back in the
+/// outgoing buffer.
+#[test]
+fn test_congestion_prioritized_refunds() {
+    init_test_logger();
+
+    let n = 3;
+    for prioritize in [false, true] {
+        let (outgoing_receipts, num_buffered, buffered_refunds, _) =
+            apply_refunds_to_congested_shard(prioritize, None, n);
+        if prioritize {
+            // Only the function calls are buffered.
+            assert_eq!(num_buffered, n);
+            assert_eq!(buffered_refunds, 0);
+            assert!(!outgoing_receipts.is_empty());
+        } else {
+            assert!(num_buffered > n);
+            assert!(buffered_refunds > 0);
+            assert_eq!(outgoing_receipts.len(), 0);
+        }
+    }
+}
+
+/// Test that the gas of the prioritized receipts forwarded to a congested
+/// shard is capped at a fraction of the max outgoing gas, and that the
+/// receipts over the cap are buffered.
+#[test]
+fn test_congestion_prioritized_receipts_cap() {
+    init_test_logger();
+
+    let n = 3;
+    let (refunds, _, _, config) = apply_refunds_to_congested_shard(true, None, n);
+    assert!(refunds.len() > 1);
+    let refunds_gas = refunds
+        .iter()
+        .map(|receipt| compute_receipt_congestion_gas(receipt, &config).unwrap())
+        .collect::<Vec<_>>();
+    assert!(refunds_gas.iter().all(|gas| *gas > 0));
+    // Only leave room for the largest refund.
+    let priority_gas = *refunds_gas.iter().max().unwrap();
+    let max_outgoing_gas = priority_gas * PRIORITY_OUTGOING_GAS_DIVISOR;
+
+    let (outgoing_receipts, num_buffered, buffered_refunds, config) =
+        apply_refunds_to_congested_shard(true, Some(max_outgoing_gas), n);
+    assert!(!outgoing_receipts.is_empty());
+    let forwarded_gas: Gas = outgoing_receipts
+        .iter()
+        .map(|receipt| compute_receipt_congestion_gas(receipt, &config).unwrap())
+        .sum();
+    assert!(forwarded_gas <= priority_gas);
+    assert!(buffered_refunds > 0);
+    assert_eq!(num_buffered, n + buffered_refunds as u64);
+}
+
 // Apply trie changes in `ApplyResult` and update `ApplyState` with new
 // congestion info for the next call to apply().
 fn commit_apply_result(
@@ -3039,3 +3259,215 @@ fn test_expired_transaction() {
         "should have not produced any outcomes for the expired tx"
     );
 }
+
+fn parallel_receipts_accounts(num_accounts: usize) -> Vec<AccountId> {
+    (0..num_accounts).map(|i| format!("account{i}.near").parse().unwrap()).collect()
+}
+
+fn transfer_receipt(index: u64, receiver_id: AccountId) -> Receipt {
+    Receipt::V0(ReceiptV0 {
+        predecessor_id: bob_account(),
+        receiver_id,
+        receipt_id: CryptoHash::hash_borsh(index),
+        receipt: ReceiptEnum::Action(ActionReceipt {
+            signer_id: bob_account(),
+            signer_public_key: PublicKey::empty(KeyType::ED25519),
+            gas_price: GAS_PRICE,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: vec![Action::Transfer(TransferAction {
+                deposit: to_yocto(1) + Balance::from(index),
+            })],
+        }),
+    })
+}
+
+/// Applies the receipts with and without the parallel execution of the
+/// conflict-free receipts, checks that the results are the same and returns
+/// the result.
+fn check_parallel_receipts(
+    accounts: Vec<AccountId>,
+    receipts: impl FnOnce(&[Arc<Signer>]) -> Vec<Receipt>,
+    config: RuntimeConfig,
+    gas_limit: Gas,
+) -> ApplyResult {
+    let (_, tries, root, mut apply_state, signers, epoch_info_provider) =
+        setup_runtime(accounts, to_yocto(1_000_000), to_yocto(500_000), gas_limit);
+    apply_state.config = Arc::new(config);
+    let receipts = receipts(&signers);
+    let apply = |parallel_receipts: bool| {
+        let trie = tries
+            .get_trie_for_shard(ShardUId::single_shard(), root)
+            .recording_reads_with_proof_size_limit(
+                apply_state.config.witness_config.main_storage_proof_size_soft_limit,
+            );
+        Runtime { parallel_receipts, ..Runtime::new() }
+            .apply(
+                trie,
+                &None,
+                &apply_state,
+                &receipts,
+                SignedValidPeriodTransactions::empty(),
+                &epoch_info_provider,
+                Default::default(),
+            )
+            .unwrap()
+    };
+
+    let serial = apply(false);
+    let parallel = apply(true);
+    assert_eq!(parallel.state_root, serial.state_root);
+    assert_eq!(parallel.trie_changes, serial.trie_changes);
+    assert_eq!(
+        borsh::to_vec(&parallel.state_changes).unwrap(),
+        borsh::to_vec(&serial.state_changes).unwrap()
+    );
+    assert_eq!(parallel.outcomes, serial.outcomes);
+    assert_eq!(parallel.outgoing_receipts, serial.outgoing_receipts);
+    assert_eq!(parallel.delayed_receipts_count, serial.delayed_receipts_count);
+    assert_eq!(parallel.proof, serial.proof);
+    assert_eq!(
+        borsh::to_vec(&parallel.stats.balance).unwrap(),
+        borsh::to_vec(&serial.stats.balance).unwrap()
+    );
+    serial
+}
+
+#[test]
+fn test_parallel_receipts_distinct_receivers() {
+    let accounts = parallel_receipts_accounts(8);
+    let apply_result = check_parallel_receipts(
+        accounts.clone(),
+        |_| parallel_transfer_receipts(&accounts),
+        RuntimeConfig::test(),
+        10u64.pow(15),
+    );
+    assert_eq!(apply_result.outcomes.len(), 8);
+    assert_eq!(apply_result.delayed_receipts_count, 0);
+}
+
+#[test]
+fn test_parallel_receipts_conflicting_receivers() {
+    let accounts = parallel_receipts_accounts(4);
+    let receivers = [0, 1, 0, 2, 2, 3, 1, 0];
+    let receipts = receivers
+        .iter()
+        .enumerate()
+        .map(|(index, &receiver)| transfer_receipt(index as u64, accounts[receiver].clone()))
+        .collect::<Vec<_>>();
+    let apply_result =
+        check_parallel_receipts(accounts, |_| receipts, RuntimeConfig::test(), 10u64.pow(15));
+    assert_eq!(apply_result.outcomes.len(), 8);
+}
+
+#[test]
+fn test_parallel_receipts_mixed_with_other_receipts() {
+    let accounts = parallel_receipts_accounts(4);
+    let receipts = |signers: &[Arc<Signer>]| {
+        let new_key = InMemorySigner::test_signer(&bob_account()).public_key();
+        vec![
+            transfer_receipt(0, accounts[0].clone()),
+            transfer_receipt(1, accounts[1].clone()),
+            // Changes the access keys of the receiver of the following transfer.
+            create_receipt_with_actions(
+                accounts[2].clone(),
+                signers[2].clone(),
+                vec![Action::AddKey(Box::new(AddKeyAction {
+                    public_key: new_key,
+                    access_key: AccessKey::full_access(),
+                }))],
+            ),
+            transfer_receipt(3, accounts[2].clone()),
+            transfer_receipt(4, accounts[3].clone()),
+            // Refunds the allowance of the access key of the receiver.
+            Receipt::new_gas_refund(
+                &accounts[3],
+                to_yocto(1),
+                signers[3].public_key(),
+                ReceiptPriority::NoPriority,
+            ),
+            Receipt::new_balance_refund(&accounts[0], to_yocto(1), ReceiptPriority::NoPriority),
+        ]
+    };
+    let apply_result =
+        check_parallel_receipts(accounts.clone(), receipts, RuntimeConfig::test(), 10u64.pow(15));
+    assert_eq!(apply_result.outcomes.len(), 7);
+}
+
+#[test]
+fn test_parallel_receipts_missing_receivers() {
+    let accounts = parallel_receipts_accounts(2);
+    let receipts = vec![
+        transfer_receipt(0, accounts[0].clone()),
+        transfer_receipt(1, "missing0.near".parse().unwrap()),
+        transfer_receipt(2, accounts[1].clone()),
+        transfer_receipt(3, "missing1.near".parse().unwrap()),
+    ];
+    let apply_result =
+        check_parallel_receipts(accounts, |_| receipts, RuntimeConfig::test(), 10u64.pow(15));
+    assert_matches!(
+        apply_result.outcomes[1].outcome.status,
+        ExecutionStatus::Failure(TxExecutionError::ActionError(_))
+    );
+    // The deposits of the failed transfers are refunded.
+    assert!(apply_result.outgoing_receipts.len() >= 2);
+}
+
+fn parallel_transfer_receipts(accounts: &[AccountId]) -> Vec<Receipt> {
+    accounts
+        .iter()
+        .enumerate()
+        .map(|(index, account_id)| transfer_receipt(index as u64, account_id.clone()))
+        .collect()
+}
+
+#[test]
+fn test_parallel_receipts_compute_limit() {
+    let accounts = parallel_receipts_accounts(8);
+    let receipt_exec_gas_fee = 1000;
+    let mut config = RuntimeConfig::free();
+    let fees = Arc::make_mut(&mut config.fees);
+    fees.action_fees[ActionCosts::new_action_receipt].execution = receipt_exec_gas_fee;
+    // Hit the limit at every position within the receipts executed in parallel.
+    for num_processed in 0..=accounts.len() {
+        let apply_result = check_parallel_receipts(
+            accounts.clone(),
+            |_| parallel_transfer_receipts(&accounts),
+            config.clone(),
+            receipt_exec_gas_fee * num_processed as u64,
+        );
+        assert_eq!(apply_result.outcomes.len(), num_processed);
+        assert_eq!(apply_result.delayed_receipts_count, (accounts.len() - num_processed) as u64);
+    }
+}
+
+#[test]
+fn test_parallel_receipts_storage_proof_size_limit() {
+    let accounts = parallel_receipts_accounts(8);
+    let apply_result = check_parallel_receipts(
+        accounts.clone(),
+        |_| parallel_transfer_receipts(&accounts),
+        RuntimeConfig::test(),
+        10u64.pow(15),
+    );
+    let PartialState::TrieValues(nodes) = apply_result.proof.unwrap().nodes;
+    let proof_size: usize = nodes.iter().map(|node| node.len()).sum();
+
+    // Hit the limit at various positions within the receipts executed in
+    // parallel.
+    let mut partially_delayed = false;
+    for limit in (0..proof_size).step_by(50) {
+        let mut config = RuntimeConfig::test();
+        config.witness_config.main_storage_proof_size_soft_limit = limit;
+        let apply_result = check_parallel_receipts(
+            accounts.clone(),
+            |_| parallel_transfer_receipts(&accounts),
+            config,
+            10u64.pow(15),
+        );
+        let delayed_receipts_count = apply_result.delayed_receipts_count as usize;
+        assert_eq!(apply_result.outcomes.len() + delayed_receipts_count, accounts.len());
+        partially_delayed |= delayed_receipts_count > 0 && delayed_receipts_count < accounts.len();
+    }
+    assert!(partially_delayed);
+}
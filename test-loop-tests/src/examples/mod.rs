@@ -1,7 +1,10 @@
 #[cfg(feature = "test_features")]
 mod missing_chunk;
 mod multinode;
+mod network_faults;
+mod per_node_config;
 mod resharding;
 mod restart_node;
 mod simple;
 mod validator_rotation;
+mod workloads;
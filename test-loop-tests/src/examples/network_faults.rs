@@ -0,0 +1,73 @@
+use itertools::Itertools;
+use near_async::test_loop::data::TestLoopData;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::setup::env::TestLoopEnv;
+use crate::utils::network_faults::NetworkFaults;
+
+/// Runs the chain over a lossy network with slow links, then cuts one
+/// validator off the others and checks that it catches up once the
+/// partition is healed.
+#[test]
+fn test_network_faults() {
+    init_test_logger();
+    let clients: Vec<AccountId> =
+        (0..4).map(|i| format!("account{}", i).parse().unwrap()).collect();
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&clients.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(10)
+        .validators_spec(validators_spec)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+    let faults = NetworkFaults::new(42)
+        .with_link_latency(&clients[0], &clients[1], Duration::ZERO, Duration::milliseconds(100))
+        .with_link_latency(&clients[1], &clients[0], Duration::ZERO, Duration::milliseconds(100))
+        .with_drop_rate("Approval", 0.1);
+    let TestLoopEnv { mut test_loop, node_datas, shared_state } = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients.clone())
+        .network_faults(faults)
+        .build()
+        .warmup();
+
+    let client_handles =
+        node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let head_height = |test_loop_data: &TestLoopData, i: usize| {
+        test_loop_data.get(&client_handles[i]).client.chain.head().unwrap().height
+    };
+
+    let start_height = head_height(&test_loop.data, 0);
+    test_loop.run_until(
+        |test_loop_data| head_height(test_loop_data, 0) > start_height + 10,
+        Duration::seconds(20),
+    );
+
+    // The other validators hold enough stake to keep producing blocks
+    // without the last one.
+    let (isolated, others) = clients.split_last().unwrap();
+    shared_state
+        .network_shared_state
+        .update_faults(|faults| faults.partition(&[isolated.clone()], others));
+    let partition_height = head_height(&test_loop.data, 0);
+    test_loop.run_until(
+        |test_loop_data| head_height(test_loop_data, 0) > partition_height + 10,
+        Duration::seconds(20),
+    );
+    assert!(head_height(&test_loop.data, 3) < head_height(&test_loop.data, 0));
+
+    shared_state.network_shared_state.update_faults(|faults| faults.heal_partitions());
+    let heal_height = head_height(&test_loop.data, 0);
+    test_loop.run_until(
+        |test_loop_data| head_height(test_loop_data, 3) > heal_height + 5,
+        Duration::seconds(30),
+    );
+
+    TestLoopEnv { test_loop, node_datas, shared_state }
+        .shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::GCConfig;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::testonly::init_test_logger;
+use near_parameters::{RuntimeConfig, RuntimeConfigStore};
+use near_primitives::epoch_manager::EpochConfigStore;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::setup::env::TestLoopEnv;
+use crate::utils::ONE_NEAR;
+
+fn gc_config(env: &TestLoopEnv, account_id: &AccountId) -> GCConfig {
+    let client_handle =
+        env.get_node_data_by_account_id(account_id).unwrap().client_sender.actor_handle();
+    env.test_loop.data.get(&client_handle).client.config.gc.clone()
+}
+
+/// Returns the kickout stake cap of the epoch config and the combined
+/// transaction size limit of the runtime config that `account_id` uses at
+/// its head.
+fn config_limits(env: &TestLoopEnv, account_id: &AccountId) -> (u8, usize) {
+    let client_handle =
+        env.get_node_data_by_account_id(account_id).unwrap().client_sender.actor_handle();
+    let client = &env.test_loop.data.get(&client_handle).client;
+    let head = client.chain.head().unwrap();
+    let epoch_config = client.epoch_manager.get_epoch_config(&head.epoch_id).unwrap();
+    let protocol_version = client.epoch_manager.get_epoch_protocol_version(&head.epoch_id).unwrap();
+    let runtime_config = client.runtime_adapter.get_runtime_config(protocol_version);
+    (
+        epoch_config.validator_max_kickout_stake_perc,
+        runtime_config.witness_config.combined_transactions_size_limit,
+    )
+}
+
+/// Runs a fleet of validators, an RPC node and an archival node where the
+/// last two have their own GC settings, and checks that each node keeps its
+/// config after a restart.
+#[test]
+fn test_per_node_config() {
+    init_test_logger();
+    let validators: Vec<AccountId> =
+        (0..3).map(|i| format!("validator{}", i).parse().unwrap()).collect();
+    let rpc: AccountId = "rpc".parse().unwrap();
+    let archival: AccountId = "archival".parse().unwrap();
+    let clients = validators.iter().chain([&rpc, &archival]).cloned().collect_vec();
+
+    let epoch_length = 5;
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&validators.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&clients, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .archival_clients(HashSet::from([archival.clone()]))
+        .gc_num_epochs_to_keep(3)
+        .config_modifier_for(&rpc, |config| {
+            config.gc.gc_num_epochs_to_keep = 4;
+            config.gc.gc_blocks_limit = 1;
+        })
+        .config_modifier_for(&archival, |config| {
+            config.gc.gc_num_epochs_to_keep = 10;
+        })
+        .build()
+        .warmup();
+
+    assert_eq!(gc_config(&env, &validators[0]).gc_num_epochs_to_keep, 3);
+    assert_eq!(gc_config(&env, &rpc).gc_num_epochs_to_keep, 4);
+    assert_eq!(gc_config(&env, &rpc).gc_blocks_limit, 1);
+    assert_eq!(gc_config(&env, &archival).gc_num_epochs_to_keep, 10);
+
+    env.test_loop.run_for(Duration::seconds(3 * epoch_length as i64));
+    env.restart_node(&rpc);
+    env.restart_node(&archival);
+    env.test_loop.run_for(Duration::seconds(2 * epoch_length as i64));
+
+    assert_eq!(gc_config(&env, &rpc).gc_num_epochs_to_keep, 4);
+    assert_eq!(gc_config(&env, &rpc).gc_blocks_limit, 1);
+    assert_eq!(gc_config(&env, &archival).gc_num_epochs_to_keep, 10);
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
+
+/// Runs a fleet of validators next to an RPC node with its own epoch and
+/// runtime config stores. The overridden fields don't change the outcome of
+/// the chunks the RPC node applies, so it must keep following the chain, and
+/// it must keep its configs after a restart.
+#[test]
+fn test_per_node_config_stores() {
+    init_test_logger();
+    let validators: Vec<AccountId> =
+        (0..3).map(|i| format!("validator{}", i).parse().unwrap()).collect();
+    let rpc: AccountId = "rpc".parse().unwrap();
+    let clients = validators.iter().chain([&rpc]).cloned().collect_vec();
+
+    let epoch_length = 5;
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&validators.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&clients, 1_000_000 * ONE_NEAR)
+        .build();
+    let protocol_version = genesis.config.protocol_version;
+    let epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let mut rpc_epoch_config = epoch_config.clone();
+    rpc_epoch_config.validator_max_kickout_stake_perc = 50;
+    let runtime_config = RuntimeConfig::test();
+    let default_size_limit = runtime_config.witness_config.combined_transactions_size_limit;
+    let mut rpc_runtime_config = runtime_config.clone();
+    rpc_runtime_config.witness_config.combined_transactions_size_limit = default_size_limit / 2;
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(EpochConfigStore::test_single_version(protocol_version, epoch_config))
+        .runtime_config_store(RuntimeConfigStore::with_one_config(runtime_config))
+        .clients(clients)
+        .epoch_config_store_for(
+            &rpc,
+            EpochConfigStore::test_single_version(protocol_version, rpc_epoch_config),
+        )
+        .runtime_config_store_for(&rpc, RuntimeConfigStore::with_one_config(rpc_runtime_config))
+        .build()
+        .warmup();
+
+    assert_eq!(config_limits(&env, &validators[0]), (100, default_size_limit));
+    assert_eq!(config_limits(&env, &rpc), (50, default_size_limit / 2));
+
+    env.test_loop.run_for(Duration::seconds(3 * epoch_length as i64));
+    env.restart_node(&rpc);
+    env.test_loop.run_for(Duration::seconds(2 * epoch_length as i64));
+
+    assert_eq!(config_limits(&env, &validators[0]), (100, default_size_limit));
+    assert_eq!(config_limits(&env, &rpc), (50, default_size_limit / 2));
+    let head_height = |account_id: &AccountId| {
+        let client_handle =
+            env.get_node_data_by_account_id(account_id).unwrap().client_sender.actor_handle();
+        env.test_loop.data.get(&client_handle).client.chain.head().unwrap().height
+    };
+    assert!(head_height(&rpc) + 1 >= head_height(&validators[0]));
+    assert!(head_height(&rpc) > 4 * epoch_length);
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -1,10 +1,13 @@
 use itertools::Itertools;
+use near_async::test_loop::data::TestLoopData;
 use near_async::time::Duration;
+use near_chain_configs::TrackedShardsConfig;
 use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
 use near_o11y::testonly::init_test_logger;
 use near_primitives::types::AccountId;
 
 use crate::setup::builder::{NodeStateBuilder, TestLoopBuilder};
+use crate::setup::env::TestLoopEnv;
 use crate::utils::ONE_NEAR;
 
 const NUM_CLIENTS: usize = 4;
@@ -48,7 +51,7 @@ fn test_restart_node() {
     env.test_loop.run_for(Duration::seconds(2 * epoch_length as i64));
 
     // restart node
-    env.restart_node("account0-restart", killed_node_state);
+    env.add_node("account0-restart", killed_node_state);
     env.test_loop.run_for(Duration::seconds(3 * epoch_length as i64));
 
     // Add new node
@@ -64,3 +67,117 @@ fn test_restart_node() {
     // be important for properly shutting down the nodes.
     env.shutdown_and_drain_remaining_events(Duration::seconds(20));
 }
+
+#[test]
+fn test_stop_and_start_node() {
+    init_test_logger();
+    let builder = TestLoopBuilder::new();
+
+    let accounts =
+        (0..100).map(|i| format!("account{}", i).parse().unwrap()).collect::<Vec<AccountId>>();
+    let clients = accounts.iter().take(NUM_CLIENTS).cloned().collect_vec();
+
+    let epoch_length = 10;
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&clients.iter().map(|t| t.as_str()).collect_vec(), &[]);
+
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    // The stopped validator is not kicked out, since the kickouts are disabled.
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+
+    let mut env = builder
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .build()
+        .warmup();
+
+    // Stop a validator in the middle of the epoch. The other nodes keep
+    // producing blocks without it.
+    env.test_loop.run_for(Duration::seconds(5));
+    env.stop_node(&accounts[1]);
+    assert_eq!(env.node_datas.len(), NUM_CLIENTS - 1);
+    let head_height = |env: &TestLoopEnv, data: &TestLoopData| {
+        env.node_datas
+            .iter()
+            .map(|node| data.get(&node.client_sender.actor_handle()).client.chain.head().unwrap())
+            .map(|head| head.height)
+            .min()
+            .unwrap()
+    };
+    let stop_height = head_height(&env, &env.test_loop.data);
+    env.test_loop.run_for(Duration::seconds(2 * epoch_length as i64));
+    assert!(head_height(&env, &env.test_loop.data) > stop_height + epoch_length);
+
+    // Start a new node, which syncs to the head of the other nodes.
+    let new_account = &accounts[NUM_CLIENTS];
+    env.start_new_node(new_account, |config| {
+        config.tracked_shards_config = TrackedShardsConfig::AllShards;
+    });
+    let new_node = env.get_node_data_by_account_id(new_account).unwrap();
+    assert_eq!(new_node.identifier, new_account.as_str());
+    let new_client_handle = new_node.client_sender.actor_handle();
+    let target_height = head_height(&env, &env.test_loop.data);
+    env.test_loop.run_until(
+        |data| data.get(&new_client_handle).client.chain.head().unwrap().height > target_height,
+        Duration::seconds(3 * epoch_length as i64),
+    );
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
+
+#[test]
+fn test_restart_node_from_store() {
+    init_test_logger();
+    let builder = TestLoopBuilder::new();
+
+    let accounts =
+        (0..100).map(|i| format!("account{}", i).parse().unwrap()).collect::<Vec<AccountId>>();
+    let clients = accounts.iter().take(NUM_CLIENTS).cloned().collect_vec();
+
+    let epoch_length = 10;
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&clients.iter().map(|t| t.as_str()).collect_vec(), &[]);
+
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+
+    let mut env = builder
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .build()
+        .warmup();
+
+    env.test_loop.run_for(Duration::seconds(5));
+    let account_id = &accounts[1];
+    let client_handle =
+        env.get_node_data_by_account_id(account_id).unwrap().client_sender.actor_handle();
+    let head_before_restart = env.test_loop.data.get(&client_handle).client.chain.head().unwrap();
+
+    // The restarted node starts from the head stored in its DB, not from genesis.
+    env.restart_node(account_id);
+    assert_eq!(env.node_datas.len(), NUM_CLIENTS);
+    let new_node = env.get_node_data_by_account_id(account_id).unwrap();
+    assert_ne!(new_node.identifier, account_id.as_str());
+    let new_client_handle = new_node.client_sender.actor_handle();
+    let head_after_restart =
+        env.test_loop.data.get(&new_client_handle).client.chain.head().unwrap();
+    assert_eq!(head_after_restart, head_before_restart);
+
+    let target_height = head_before_restart.height + epoch_length;
+    env.test_loop.run_until(
+        |data| data.get(&new_client_handle).client.chain.head().unwrap().height > target_height,
+        Duration::seconds(2 * epoch_length as i64),
+    );
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,76 @@
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::shard_layout::ShardLayout;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::transactions::{do_deploy_contract, make_accounts};
+use crate::utils::workloads::{Workload, run_workloads};
+use crate::utils::{ONE_NEAR, TGAS};
+
+/// Runs every kind of workload at the same time on a chain with two shards.
+#[test]
+fn test_workloads() {
+    init_test_logger();
+    let accounts = make_accounts(8);
+    let clients = accounts.iter().take(4).cloned().collect_vec();
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&clients.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let shard_layout = ShardLayout::multi_shard_custom(vec!["account4".parse().unwrap()], 1);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(10)
+        .shard_layout(shard_layout.clone())
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .track_all_shards()
+        .build()
+        .warmup();
+
+    // One contract on each shard.
+    let rpc_id = accounts[0].clone();
+    let contracts = vec![accounts[1].clone(), accounts[6].clone()];
+    for contract_id in &contracts {
+        do_deploy_contract(
+            &mut env,
+            &rpc_id,
+            contract_id,
+            near_test_contracts::rs_contract().to_vec(),
+        );
+    }
+    // Only the first account of the transfers is on the first shard.
+    let first_shard_id = shard_layout.account_id_to_shard_id(&accounts[0]);
+
+    // The workloads keep track of the nonces of their own signers, so they
+    // don't share any signer.
+    let workloads = vec![
+        Workload::transfers(vec![accounts[0].clone(), accounts[4].clone()])
+            .target_shards(vec![first_shard_id])
+            .num_blocks(10),
+        Workload::contract_calls(
+            vec![accounts[2].clone()],
+            contracts.clone(),
+            "log_something",
+            vec![],
+            10 * TGAS,
+        )
+        .num_blocks(10),
+        Workload::storage_writes(vec![accounts[3].clone()], contracts.clone()).num_blocks(10),
+        Workload::cross_shard_call_chains(
+            vec![accounts[5].clone(), accounts[7].clone()],
+            contracts,
+            4,
+        )
+        .txs_per_block(2)
+        .num_blocks(10),
+    ];
+    run_workloads(&mut env, &rpc_id, workloads, Duration::seconds(60));
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
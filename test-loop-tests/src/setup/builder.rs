@@ -1,12 +1,13 @@
 use itertools::Itertools;
 use near_chain_configs::test_genesis::TestGenesisBuilder;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tempfile::TempDir;
 
 use near_async::test_loop::TestLoopV2;
+use near_async::test_loop::schedule::EventSchedule;
 use near_async::time::{Clock, Duration};
 use near_chain_configs::{
     ClientConfig, DumpConfig, ExternalStorageConfig, ExternalStorageLocation, Genesis,
@@ -21,6 +22,7 @@ use near_store::Store;
 use near_store::genesis::initialize_genesis_state;
 use near_store::test_utils::{create_test_split_store, create_test_store};
 
+use crate::utils::network_faults::NetworkFaults;
 use crate::utils::peer_manager_actor::{TestLoopNetworkSharedState, UnreachableActor};
 
 use super::env::TestLoopEnv;
@@ -29,6 +31,23 @@ use super::state::{NodeSetupState, SharedState};
 
 pub(crate) const MIN_BLOCK_PROD_TIME: u64 = 600;
 
+/// The directory the event schedule of every test is recorded to, in a file
+/// named after the test, see [TestLoopBuilder::record_schedule].
+const RECORD_DIR_ENV_VAR: &str = "TEST_LOOP_RECORD_DIR";
+/// The event schedule file replayed by the tests, see
+/// [TestLoopBuilder::replay_schedule].
+const REPLAY_SCHEDULE_ENV_VAR: &str = "TEST_LOOP_REPLAY_SCHEDULE";
+/// The seed of the randomness of a test which doesn't set one, fixed so that
+/// two runs of the test are the same.
+const DEFAULT_SEED: u64 = 0;
+
+/// How the clock of a client differs from the test loop clock.
+#[derive(Clone, Copy, Default)]
+struct ClockSkew {
+    offset: Duration,
+    drift_rate: f64,
+}
+
 pub(crate) struct TestLoopBuilder {
     test_loop: TestLoopV2,
     genesis: Option<Genesis>,
@@ -47,14 +66,32 @@ pub(crate) struct TestLoopBuilder {
     runtime_config_store: Option<RuntimeConfigStore>,
     /// Custom function to change the configs before constructing each client.
     config_modifier: Option<Box<dyn Fn(&mut ClientConfig, usize)>>,
+    /// Custom functions to change the config of a single client, applied
+    /// after `config_modifier`.
+    config_modifiers_for: HashMap<AccountId, Box<dyn Fn(&mut ClientConfig)>>,
+    /// Epoch config stores used by some of the clients instead of `epoch_config_store`.
+    epoch_config_store_overrides: HashMap<AccountId, EpochConfigStore>,
+    /// Runtime config stores used by some of the clients instead of `runtime_config_store`.
+    runtime_config_store_overrides: HashMap<AccountId, RuntimeConfigStore>,
+    /// Clients whose clock doesn't show the test loop time.
+    clock_skews: HashMap<AccountId, ClockSkew>,
     /// Whether to do the warmup or not. See `skip_warmup` for more details.
     warmup_pending: Arc<AtomicBool>,
     /// Whether all nodes must track all shards.
     track_all_shards: bool,
     /// Whether to load mem tries for the tracked shards.
     load_memtries_for_tracked_shards: bool,
+    load_memtries_in_background: bool,
     /// Upgrade schedule which determines when the clients start voting for new protocol versions.
     upgrade_schedule: ProtocolUpgradeVotingSchedule,
+    /// Faults injected into the network from the start of the test.
+    network_faults: NetworkFaults,
+    /// Seed of the randomness of the test, `DEFAULT_SEED` if not set.
+    seed: Option<u64>,
+    /// File the event schedule of the test loop is recorded to.
+    record_schedule: Option<PathBuf>,
+    /// File of the event schedule replayed by the test loop.
+    replay_schedule: Option<PathBuf>,
 }
 
 impl TestLoopBuilder {
@@ -69,10 +106,19 @@ impl TestLoopBuilder {
             gc_num_epochs_to_keep: None,
             runtime_config_store: None,
             config_modifier: None,
+            config_modifiers_for: HashMap::new(),
+            epoch_config_store_overrides: HashMap::new(),
+            runtime_config_store_overrides: HashMap::new(),
+            clock_skews: HashMap::new(),
             warmup_pending: Arc::new(AtomicBool::new(true)),
             track_all_shards: false,
             load_memtries_for_tracked_shards: true,
+            load_memtries_in_background: false,
             upgrade_schedule: PROTOCOL_UPGRADE_SCHEDULE.clone(),
+            network_faults: NetworkFaults::default(),
+            seed: None,
+            record_schedule: None,
+            replay_schedule: None,
         }
     }
 
@@ -103,6 +149,45 @@ impl TestLoopBuilder {
         self
     }
 
+    /// Use `epoch_config_store` for the client of `account_id` instead of the
+    /// one shared by all the clients.
+    pub(crate) fn epoch_config_store_for(
+        mut self,
+        account_id: &AccountId,
+        epoch_config_store: EpochConfigStore,
+    ) -> Self {
+        self.epoch_config_store_overrides.insert(account_id.clone(), epoch_config_store);
+        self
+    }
+
+    /// Use `runtime_config_store` for the client of `account_id` instead of
+    /// the one shared by all the clients.
+    pub(crate) fn runtime_config_store_for(
+        mut self,
+        account_id: &AccountId,
+        runtime_config_store: RuntimeConfigStore,
+    ) -> Self {
+        self.runtime_config_store_overrides.insert(account_id.clone(), runtime_config_store);
+        self
+    }
+
+    /// Set the clock of the client of `account_id` `offset` ahead of the test
+    /// loop clock, or behind it if `offset` is negative. The offset applies
+    /// to both the monotonic and the UTC time.
+    pub(crate) fn clock_skew(mut self, account_id: &AccountId, offset: Duration) -> Self {
+        self.clock_skews.entry(account_id.clone()).or_default().offset = offset;
+        self
+    }
+
+    /// Make the clock of the client of `account_id` run `1 + drift_rate`
+    /// times as fast as the test loop clock, e.g. a `drift_rate` of 0.001
+    /// gains 1ms per second. The drift accumulates from the start of the test
+    /// and carries on across restarts of the client.
+    pub(crate) fn clock_drift(mut self, account_id: &AccountId, drift_rate: f64) -> Self {
+        self.clock_skews.entry(account_id.clone()).or_default().drift_rate = drift_rate;
+        self
+    }
+
     /// Set the clients for the test loop.
     pub(crate) fn clients(mut self, clients: Vec<AccountId>) -> Self {
         self.clients = clients;
@@ -130,6 +215,18 @@ impl TestLoopBuilder {
         self
     }
 
+    /// Custom function to change the config of the client of `account_id`.
+    /// It runs after the function given to `config_modifier`, so it can
+    /// override the changes made there for all the clients.
+    pub fn config_modifier_for(
+        mut self,
+        account_id: &AccountId,
+        modifier: impl Fn(&mut ClientConfig) + 'static,
+    ) -> Self {
+        self.config_modifiers_for.insert(account_id.clone(), Box::new(modifier));
+        self
+    }
+
     /// Do not automatically warmup the chain. Start from genesis instead.
     /// Note that this can cause unexpected issues, as the chain behaves
     /// somewhat differently (and correctly so) at genesis. So only skip
@@ -150,11 +247,48 @@ impl TestLoopBuilder {
         self
     }
 
+    /// Load the memtries in background when a node starts, including when it
+    /// is restarted, so that the node switches to them in the middle of an
+    /// epoch.
+    pub fn load_memtries_in_background(mut self, in_background: bool) -> Self {
+        self.load_memtries_in_background = in_background;
+        self
+    }
+
     pub fn protocol_upgrade_schedule(mut self, schedule: ProtocolUpgradeVotingSchedule) -> Self {
         self.upgrade_schedule = schedule;
         self
     }
 
+    /// Set the faults injected into the network, see [NetworkFaults].
+    pub fn network_faults(mut self, faults: NetworkFaults) -> Self {
+        self.network_faults = faults;
+        self
+    }
+
+    /// Set the seed of the randomness of the test, see `TestLoopData::seed`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Record the seed and the order of the events of the test loop to a
+    /// file, which can be replayed with [Self::replay_schedule]. It is written
+    /// when the test loop is dropped, also when the test fails. Setting
+    /// `TEST_LOOP_RECORD_DIR` records the schedule of every test to a file
+    /// named after the test in that directory.
+    pub fn record_schedule(mut self, path: PathBuf) -> Self {
+        self.record_schedule = Some(path);
+        self
+    }
+
+    /// Replay the event schedule recorded to a file by a previous run of the
+    /// test. Setting `TEST_LOOP_REPLAY_SCHEDULE` to the file replays it.
+    pub fn replay_schedule(mut self, path: PathBuf) -> Self {
+        self.replay_schedule = Some(path);
+        self
+    }
+
     /// Build the test loop environment.
     pub(crate) fn build(self) -> TestLoopEnv {
         self.ensure_genesis().ensure_epoch_config_store().ensure_clients().build_impl()
@@ -176,10 +310,54 @@ impl TestLoopBuilder {
             self.archival_clients.is_subset(&HashSet::from_iter(self.clients.iter().cloned())),
             "Archival accounts must be subset of the clients"
         );
+        let overridden_accounts = self
+            .config_modifiers_for
+            .keys()
+            .chain(self.epoch_config_store_overrides.keys())
+            .chain(self.runtime_config_store_overrides.keys())
+            .chain(self.clock_skews.keys());
+        for account_id in overridden_accounts {
+            assert!(
+                self.clients.contains(account_id),
+                "Config override for {account_id} which is not a client"
+            );
+        }
         self
     }
 
+    /// Configures the seed and the recording or replay of the event schedule
+    /// of the test loop.
+    fn setup_schedule(&mut self) {
+        let replay_schedule = self
+            .replay_schedule
+            .clone()
+            .or_else(|| std::env::var_os(REPLAY_SCHEDULE_ENV_VAR).map(PathBuf::from));
+        if let Some(path) = replay_schedule {
+            let schedule = EventSchedule::load(&path).unwrap_or_else(|err| {
+                panic!("Failed to load the event schedule from {}: {err}", path.display())
+            });
+            tracing::info!(target: "test", path = %path.display(), "Replaying the event schedule");
+            self.test_loop.replay_schedule(schedule);
+        } else {
+            let seed = self.seed.unwrap_or(DEFAULT_SEED);
+            tracing::info!(target: "test", seed, "Test loop seed");
+            self.test_loop.set_seed(seed);
+        }
+
+        let record_schedule = self.record_schedule.clone().or_else(|| {
+            let dir = PathBuf::from(std::env::var_os(RECORD_DIR_ENV_VAR)?);
+            let thread = std::thread::current();
+            let test_name = thread.name().unwrap_or("test").replace("::", "__");
+            Some(dir.join(format!("{test_name}.json")))
+        });
+        if let Some(path) = record_schedule {
+            tracing::info!(target: "test", path = %path.display(), "Recording the event schedule");
+            self.test_loop.record_schedule(path);
+        }
+    }
+
     fn build_impl(mut self) -> TestLoopEnv {
+        self.setup_schedule();
         let warmup_pending = self.warmup_pending.clone();
         self.test_loop.send_adhoc_event("warmup_pending".into(), move |_| {
             assert!(
@@ -212,11 +390,25 @@ impl TestLoopBuilder {
             tempdir: self.test_loop_data_dir,
             epoch_config_store: self.epoch_config_store.unwrap(),
             runtime_config_store: self.runtime_config_store,
-            network_shared_state: TestLoopNetworkSharedState::new(unreachable_actor_sender),
+            epoch_config_store_overrides: self.epoch_config_store_overrides,
+            runtime_config_store_overrides: self.runtime_config_store_overrides,
+            node_clocks: self
+                .clock_skews
+                .into_iter()
+                .map(|(account_id, skew)| {
+                    (account_id, self.test_loop.skewed_clock(skew.offset, skew.drift_rate))
+                })
+                .collect(),
+            network_shared_state: TestLoopNetworkSharedState::new(
+                unreachable_actor_sender,
+                self.network_faults,
+            ),
             upgrade_schedule: self.upgrade_schedule,
             chunks_storage: Default::default(),
             drop_conditions: Default::default(),
+            invariants: Default::default(),
             load_memtries_for_tracked_shards: self.load_memtries_for_tracked_shards,
+            load_memtries_in_background: self.load_memtries_in_background,
             warmup_pending: self.warmup_pending,
         };
         (self.test_loop, shared_state)
@@ -244,6 +436,9 @@ impl TestLoopBuilder {
             if let Some(config_modifier) = &self.config_modifier {
                 config_modifier(client_config, idx);
             }
+            if let Some(config_modifier) = self.config_modifiers_for.get(&account_id) {
+                config_modifier(client_config);
+            }
         };
         let tempdir_path = self.test_loop_data_dir.path().to_path_buf();
         NodeStateBuilder::new(genesis.clone(), tempdir_path)
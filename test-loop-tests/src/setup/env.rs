@@ -1,13 +1,16 @@
 use near_async::test_loop::TestLoopV2;
 use near_async::test_loop::data::TestLoopData;
 use near_async::time::Duration;
+use near_chain_configs::ClientConfig;
 use near_primitives::types::AccountId;
 use near_store::adapter::StoreAdapter;
 use std::sync::atomic::Ordering;
 
+use super::builder::NodeStateBuilder;
 use super::drop_condition::DropCondition;
 use super::setup::setup_client;
 use super::state::{NodeExecutionData, NodeSetupState, SharedState};
+use crate::utils::invariants::Invariant;
 
 pub struct TestLoopEnv {
     pub test_loop: TestLoopV2,
@@ -75,13 +78,15 @@ impl TestLoopEnv {
 
     /// Function to stop a node in test loop environment.
     /// Calling this function immediately stops all events with the given identifier.
-    /// This function returns the NodeState of the stopped node which can be used to restart the node.
+    /// This function returns the NodeState of the stopped node which can be passed to `add_node`
+    /// to restart the node.
     ///
     /// Note that other nodes may still continue to queue network events into the peer
     /// manager actor of the stopped node but this would not be processed.
     pub fn kill_node(&mut self, identifier: &str) -> NodeSetupState {
         // Make test_loop ignore all events from this node.
         self.test_loop.remove_events_with_identifier(identifier);
+        self.shared_state.invariants.borrow_mut().remove_client(identifier);
 
         // Build node_state
         let node_data = self
@@ -105,27 +110,102 @@ impl TestLoopEnv {
         NodeSetupState { account_id, client_config, store, split_store }
     }
 
-    /// Function to restart a node in test loop environment. This function takes in the new_identifier
-    /// and node_state of the stopped node as input.
+    /// Function to add a node in test loop environment. This function takes in the identifier
+    /// and node_state of the node as input. The node_state is either the state of a new node or
+    /// the state of a killed node, to restart it.
     ///
-    /// As long as the account_id in the node_state matches the account_id of the stopped node,
+    /// As long as the account_id in the node_state matches the account_id of the killed node,
     /// this function automatically takes care of properly redirecting all network messages to the new node.
     ///
     /// Additionally, we set the NetworkInfo for this node which is required for state sync to work.
-    pub fn restart_node(&mut self, new_identifier: &str, node_state: NodeSetupState) {
+    pub fn add_node(&mut self, identifier: &str, node_state: NodeSetupState) {
         // setup_client handles adding the account_id and peer_id details to network_shared_state
         let node_data =
-            setup_client(new_identifier, &mut self.test_loop, node_state, &self.shared_state);
+            setup_client(identifier, &mut self.test_loop, node_state, &self.shared_state);
         self.node_datas.push(node_data);
     }
 
-    /// Function to add a new node in test loop environment. This function takes in the identifier
-    /// and node_state of the new node as input.
+    /// Restarts the running node of `account_id`, as if the process crashed
+    /// and was started again. All its actors are torn down, dropping their
+    /// pending events, and rebuilt on top of the same store, so the node goes
+    /// through its startup path, e.g. resuming resharding or loading memtries.
     ///
-    /// We set the NetworkInfo for this node which is required for state sync to work.
-    pub fn add_node(&mut self, identifier: &str, node_state: NodeSetupState) {
-        // Logically this function is the same as restart_node
-        self.restart_node(identifier, node_state);
+    /// The restarted node gets a new identifier, the node data can be found
+    /// with `get_node_data_by_account_id`.
+    pub fn restart_node(&mut self, account_id: &AccountId) {
+        let node_state = self.stop_node(account_id);
+        let identifier = self.new_node_identifier(account_id);
+        self.add_node(&identifier, node_state);
+    }
+
+    /// Stops the running node of `account_id`, e.g. to simulate a validator
+    /// going offline in the middle of an epoch. Unlike `kill_node`, the node is
+    /// also removed from `node_datas`, so that the helpers going through all
+    /// the nodes only see the running ones.
+    ///
+    /// The returned state can be passed to `add_node` to bring the node back,
+    /// under a new identifier. `restart_node` does both at once.
+    pub fn stop_node(&mut self, account_id: &AccountId) -> NodeSetupState {
+        // A restarted node is added after the killed one, with the same account.
+        let index = self
+            .node_datas
+            .iter()
+            .rposition(|data| &data.account_id == account_id)
+            .expect("Node with account id not found");
+        let identifier = self.node_datas[index].identifier.clone();
+        let node_state = self.kill_node(&identifier);
+        let node_data = self.node_datas.remove(index);
+        self.test_loop.data.get_mut(&node_data.state_sync_dumper_handle).stop();
+        node_state
+    }
+
+    /// Starts a new node of `account_id` in the running network, e.g. to
+    /// simulate a node joining after resharding. The node starts from genesis
+    /// and has to sync to the head of the chain. `config_modifier` can adjust
+    /// its client config, for instance the shards it tracks.
+    pub fn start_new_node(
+        &mut self,
+        account_id: &AccountId,
+        config_modifier: impl Fn(&mut ClientConfig),
+    ) {
+        let genesis = self.shared_state.genesis.clone();
+        let tempdir_path = self.shared_state.tempdir.path().to_path_buf();
+        let node_state = NodeStateBuilder::new(genesis, tempdir_path)
+            .account_id(account_id.clone())
+            .config_modifier(config_modifier)
+            .build();
+        let identifier = self.new_node_identifier(account_id);
+        self.add_node(&identifier, node_state);
+    }
+
+    /// Returns an identifier for a new node of `account_id`. The identifiers
+    /// of the stopped nodes can't be reused, since the test loop keeps
+    /// ignoring their events. Every node gets its home directory named after
+    /// its identifier, so a free identifier is one without a directory.
+    fn new_node_identifier(&self, account_id: &AccountId) -> String {
+        let tempdir_path = self.shared_state.tempdir.path();
+        std::iter::once(account_id.to_string())
+            .chain((1..).map(|i| format!("{account_id}-{i}")))
+            .find(|identifier| !tempdir_path.join(identifier).exists())
+            .unwrap()
+    }
+
+    /// Registers an invariant that is checked every time the nodes reach a new
+    /// head, until the end of the test. A violation fails the test with the
+    /// name of the invariant and the block it was violated at. See
+    /// `utils::invariants` for the common ones.
+    ///
+    /// The end of test checks of the invariants are run by
+    /// `shutdown_and_drain_remaining_events`.
+    pub fn add_invariant(&mut self, name: &str, invariant: impl Invariant + 'static) {
+        let mut invariants = self.shared_state.invariants.borrow_mut();
+        if invariants.is_empty() {
+            let invariants = self.shared_state.invariants.clone();
+            self.test_loop.set_every_event_callback(move |test_loop_data| {
+                invariants.borrow_mut().check_block(test_loop_data);
+            });
+        }
+        invariants.add(name, invariant);
     }
 
     /// Used to finish off remaining events that are still in the loop. This can be necessary if the
@@ -133,6 +213,8 @@ impl TestLoopEnv {
     /// destructors may end up waiting forever. This also helps avoid a panic when destructing
     /// TestLoop itself, as it asserts that all events have been handled.
     pub fn shutdown_and_drain_remaining_events(mut self, timeout: Duration) {
+        self.shared_state.invariants.borrow_mut().check_end(&self.test_loop.data);
+
         // State sync dumper is not an Actor, handle stopping separately.
         for node_data in self.node_datas {
             self.test_loop.data.get_mut(&node_data.state_sync_dumper_handle).stop();
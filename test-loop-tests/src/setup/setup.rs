@@ -17,6 +17,7 @@ use near_client::gc_actor::GCActor;
 use near_client::sync_jobs_actor::SyncJobsActor;
 use near_client::{
     Client, PartialWitnessActor, TxRequestHandler, TxRequestHandlerConfig, ViewClientActorInner,
+    start_tx_forwarding_flush_timer,
 };
 use near_epoch_manager::EpochManager;
 use near_epoch_manager::shard_tracker::ShardTracker;
@@ -45,14 +46,25 @@ pub fn setup_client(
         tempdir,
         epoch_config_store,
         runtime_config_store,
+        epoch_config_store_overrides,
+        runtime_config_store_overrides,
+        node_clocks,
         network_shared_state,
         upgrade_schedule,
         chunks_storage,
         drop_conditions,
+        invariants,
         load_memtries_for_tracked_shards,
+        load_memtries_in_background,
         ..
     } = shared_state;
 
+    let epoch_config_store =
+        epoch_config_store_overrides.get(&account_id).unwrap_or(epoch_config_store);
+    let runtime_config_store =
+        runtime_config_store_overrides.get(&account_id).or(runtime_config_store.as_ref());
+    let clock = node_clocks.get(&account_id).cloned().unwrap_or_else(|| test_loop.clock());
+
     let client_adapter = LateBoundSender::new();
     let tx_processor_adapter = LateBoundSender::new();
     let network_adapter = LateBoundSender::new();
@@ -67,6 +79,7 @@ pub fn setup_client(
     let store_config = StoreConfig {
         path: Some(homedir.clone()),
         load_memtries_for_tracked_shards: *load_memtries_for_tracked_shards,
+        load_memtries_in_background: *load_memtries_in_background,
         ..Default::default()
     };
 
@@ -87,15 +100,17 @@ pub fn setup_client(
         ContractRuntimeCache::handle(&contract_cache),
         &genesis.config,
         epoch_manager.clone(),
-        runtime_config_store.clone(),
+        runtime_config_store.cloned(),
         TrieConfig::from_store_config(&store_config),
         client_config.gc.gc_num_epochs_to_keep,
+        client_config.save_receipt_execution_profiles,
     );
 
     let state_snapshot = StateSnapshotActor::new(
         runtime_adapter.get_flat_storage_manager(),
         network_adapter.as_multi_sender(),
         runtime_adapter.get_tries(),
+        client_config.resharding_config.clone(),
     );
 
     let delete_snapshot_callback =
@@ -122,7 +137,7 @@ pub fn setup_client(
     let peer_id = PeerId::new(create_test_signer(account_id.as_str()).public_key());
 
     let client = Client::new(
-        test_loop.clock(),
+        clock.clone(),
         client_config.clone(),
         chain_genesis.clone(),
         epoch_manager.clone(),
@@ -163,16 +178,17 @@ pub fn setup_client(
             ContractRuntimeCache::handle(&contract_cache),
             &genesis.config,
             view_epoch_manager.clone(),
-            runtime_config_store.clone(),
+            runtime_config_store.cloned(),
             TrieConfig::from_store_config(&store_config),
             client_config.gc.gc_num_epochs_to_keep,
+            client_config.save_receipt_execution_profiles,
         );
         (view_epoch_manager, view_shard_tracker, view_runtime_adapter)
     } else {
         (epoch_manager.clone(), shard_tracker.clone(), runtime_adapter.clone())
     };
     let view_client_actor = ViewClientActorInner::new(
-        test_loop.clock(),
+        clock.clone(),
         validator_signer.clone(),
         chain_genesis.clone(),
         view_epoch_manager.clone(),
@@ -185,7 +201,7 @@ pub fn setup_client(
     .unwrap();
 
     let shards_manager = ShardsManagerActor::new(
-        test_loop.clock(),
+        clock.clone(),
         validator_signer.clone(),
         epoch_manager.clone(),
         view_epoch_manager,
@@ -199,7 +215,7 @@ pub fn setup_client(
     );
 
     let client_actor = ClientActorInner::new(
-        test_loop.clock(),
+        clock.clone(),
         client,
         peer_id.clone(),
         network_adapter.as_multi_sender(),
@@ -216,6 +232,7 @@ pub fn setup_client(
         tx_routing_height_horizon: client_config.tx_routing_height_horizon,
         epoch_length: client_config.epoch_length,
         transaction_validity_period: genesis.config.transaction_validity_period,
+        tx_forwarding: client_config.tx_forwarding.clone(),
     };
     let tx_processor = TxRequestHandler::new(
         tx_processor_config,
@@ -228,7 +245,7 @@ pub fn setup_client(
     );
 
     let partial_witness_actor = PartialWitnessActor::new(
-        test_loop.clock(),
+        clock.clone(),
         network_adapter.as_multi_sender(),
         client_adapter.as_multi_sender(),
         validator_signer.clone(),
@@ -239,7 +256,7 @@ pub fn setup_client(
     );
 
     let peer_manager_actor = TestLoopPeerManagerActor::new(
-        test_loop.clock(),
+        clock.clone(),
         &account_id,
         network_shared_state,
         client_adapter.as_multi_sender(),
@@ -266,8 +283,8 @@ pub fn setup_client(
     let resharding_actor = ReshardingActor::new(runtime_adapter.store().clone(), &chain_genesis);
 
     let state_sync_dumper = StateSyncDumper {
-        clock: test_loop.clock(),
-        client_config,
+        clock: clock.clone(),
+        client_config: client_config.clone(),
         chain_genesis,
         epoch_manager,
         shard_tracker,
@@ -283,6 +300,12 @@ pub fn setup_client(
     let view_client_sender = test_loop.data.register_actor(identifier, view_client_actor, None);
     let tx_processor_sender =
         test_loop.data.register_actor(identifier, tx_processor, Some(tx_processor_adapter));
+    start_tx_forwarding_flush_timer(
+        clock,
+        &client_config.tx_forwarding,
+        tx_processor_sender.clone().into_sender(),
+        &test_loop.future_spawner(identifier),
+    );
     let shards_manager_sender =
         test_loop.data.register_actor(identifier, shards_manager, Some(shards_manager_adapter));
     let partial_witness_sender = test_loop.data.register_actor(
@@ -319,7 +342,8 @@ pub fn setup_client(
     // Add the client to the network shared state before returning data
     // Note that this can potentially overwrite an existing client with the same account_id
     // and all new messages would be redirected to the new client.
-    network_shared_state.add_client(&node_data);
+    network_shared_state.add_client(node_data.clone());
+    invariants.borrow_mut().add_client(identifier, node_data.client_sender.actor_handle());
 
     // Register all accumulated drop conditions
     for condition in drop_conditions {
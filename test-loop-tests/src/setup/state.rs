@@ -1,17 +1,18 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 
-use near_async::messaging::{IntoMultiSender, IntoSender, Sender};
+use near_async::messaging::{IntoMultiSender, IntoSender};
 use near_async::test_loop::data::TestLoopDataHandle;
 use near_async::test_loop::sender::TestLoopSender;
-use near_async::time::Duration;
+use near_async::time::{Clock, Duration};
 use near_chain_configs::{ClientConfig, Genesis};
 use near_chunks::shards_manager_actor::ShardsManagerActor;
 use near_client::client_actor::ClientActorInner;
 use near_client::{PartialWitnessActor, TxRequestHandler, ViewClientActorInner};
 use near_jsonrpc::ViewClientSenderForRpc;
-use near_network::shards_manager::ShardsManagerRequestFromNetwork;
-use near_network::state_witness::PartialWitnessSenderForNetwork;
 use near_parameters::RuntimeConfigStore;
 use near_primitives::epoch_manager::EpochConfigStore;
 use near_primitives::network::PeerId;
@@ -21,10 +22,9 @@ use near_store::Store;
 use nearcore::state_sync::StateSyncDumper;
 use tempfile::TempDir;
 
+use crate::utils::invariants::InvariantCheckers;
 use crate::utils::peer_manager_actor::{
-    ClientSenderForTestLoopNetwork, TestLoopNetworkBlockInfo, TestLoopNetworkSharedState,
-    TestLoopPeerManagerActor, TxRequestHandleSenderForTestLoopNetwork,
-    ViewClientSenderForTestLoopNetwork,
+    OneClientSenders, TestLoopNetworkNode, TestLoopNetworkSharedState, TestLoopPeerManagerActor,
 };
 
 use super::drop_condition::{DropCondition, TestLoopChunksStorage};
@@ -39,6 +39,13 @@ pub struct SharedState {
     pub tempdir: TempDir,
     pub epoch_config_store: EpochConfigStore,
     pub runtime_config_store: Option<RuntimeConfigStore>,
+    /// Epoch and runtime config stores of the nodes that don't use the ones
+    /// above. They are kept here so that the nodes use them after a restart.
+    pub epoch_config_store_overrides: HashMap<AccountId, EpochConfigStore>,
+    pub runtime_config_store_overrides: HashMap<AccountId, RuntimeConfigStore>,
+    /// Clocks of the nodes that don't use the test loop clock. They are
+    /// created once so that the drift of a node's clock survives a restart.
+    pub node_clocks: HashMap<AccountId, Clock>,
     /// Shared state across all the network actors. It handles the mapping between AccountId,
     /// PeerId, and the route back CryptoHash, so that individual network actors can do routing.
     pub network_shared_state: TestLoopNetworkSharedState,
@@ -47,7 +54,10 @@ pub struct SharedState {
     pub chunks_storage: Arc<Mutex<TestLoopChunksStorage>>,
     /// List of drop conditions that apply to all nodes in the network.
     pub drop_conditions: Vec<DropCondition>,
+    /// Invariants checked on every block, see `TestLoopEnv::add_invariant`.
+    pub invariants: Rc<RefCell<InvariantCheckers>>,
     pub load_memtries_for_tracked_shards: bool,
+    pub load_memtries_in_background: bool,
     /// Flag to indicate if warmup is pending. This is used to ensure that warmup is only done once.
     pub warmup_pending: Arc<AtomicBool>,
 }
@@ -78,21 +88,41 @@ pub struct NodeExecutionData {
     pub state_sync_dumper_handle: TestLoopDataHandle<StateSyncDumper>,
 }
 
-impl From<&NodeExecutionData> for AccountId {
-    fn from(data: &NodeExecutionData) -> AccountId {
-        data.account_id.clone()
+impl TestLoopNetworkNode for NodeExecutionData {
+    fn account_id(&self) -> AccountId {
+        self.account_id.clone()
     }
-}
 
-impl From<&NodeExecutionData> for PeerId {
-    fn from(data: &NodeExecutionData) -> PeerId {
-        data.peer_id.clone()
+    fn peer_id(&self) -> PeerId {
+        self.peer_id.clone()
     }
-}
 
-impl From<&NodeExecutionData> for ClientSenderForTestLoopNetwork {
-    fn from(data: &NodeExecutionData) -> ClientSenderForTestLoopNetwork {
-        data.client_sender.clone().with_delay(NETWORK_DELAY).into_multi_sender()
+    fn network_senders(&self, extra_latency: Duration) -> OneClientSenders {
+        let delay = NETWORK_DELAY + extra_latency;
+        OneClientSenders {
+            client_sender: self.client_sender.clone().with_delay(delay).into_multi_sender(),
+            view_client_sender: self
+                .view_client_sender
+                .clone()
+                .with_delay(delay)
+                .into_multi_sender(),
+            tx_processor_sender: self
+                .tx_processor_sender
+                .clone()
+                .with_delay(delay)
+                .into_multi_sender(),
+            partial_witness_sender: self
+                .partial_witness_sender
+                .clone()
+                .with_delay(delay)
+                .into_multi_sender(),
+            shards_manager_sender: self
+                .shards_manager_sender
+                .clone()
+                .with_delay(delay)
+                .into_sender(),
+            peer_manager_sender: self.peer_manager_sender.clone().with_delay(delay).into_sender(),
+        }
     }
 }
 
@@ -101,33 +131,3 @@ impl From<&NodeExecutionData> for ViewClientSenderForRpc {
         data.view_client_sender.clone().with_delay(NETWORK_DELAY).into_multi_sender()
     }
 }
-
-impl From<&NodeExecutionData> for ViewClientSenderForTestLoopNetwork {
-    fn from(data: &NodeExecutionData) -> ViewClientSenderForTestLoopNetwork {
-        data.view_client_sender.clone().with_delay(NETWORK_DELAY).into_multi_sender()
-    }
-}
-
-impl From<&NodeExecutionData> for PartialWitnessSenderForNetwork {
-    fn from(data: &NodeExecutionData) -> PartialWitnessSenderForNetwork {
-        data.partial_witness_sender.clone().with_delay(NETWORK_DELAY).into_multi_sender()
-    }
-}
-
-impl From<&NodeExecutionData> for Sender<ShardsManagerRequestFromNetwork> {
-    fn from(data: &NodeExecutionData) -> Sender<ShardsManagerRequestFromNetwork> {
-        data.shards_manager_sender.clone().with_delay(NETWORK_DELAY).into_sender()
-    }
-}
-
-impl From<&NodeExecutionData> for TxRequestHandleSenderForTestLoopNetwork {
-    fn from(data: &NodeExecutionData) -> TxRequestHandleSenderForTestLoopNetwork {
-        data.tx_processor_sender.clone().with_delay(NETWORK_DELAY).into_multi_sender()
-    }
-}
-
-impl From<&NodeExecutionData> for Sender<TestLoopNetworkBlockInfo> {
-    fn from(data: &NodeExecutionData) -> Sender<TestLoopNetworkBlockInfo> {
-        data.peer_manager_sender.clone().with_delay(NETWORK_DELAY).into_sender()
-    }
-}
@@ -0,0 +1,111 @@
+//! The nodes emit the events of the chain, attributed to each node of the
+//! test loop.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::events::{ChainEvent, EventRecord, set_event_sink};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn records(&self) -> Vec<EventRecord> {
+        let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+}
+
+#[test]
+fn test_chain_events() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> = (0..2).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let accounts_str = accounts.iter().map(|account| account.as_str()).collect_vec();
+    let epoch_length = 5;
+    let genesis_height = 1;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .genesis_height(genesis_height)
+        .epoch_length(epoch_length)
+        .validators_spec(ValidatorsSpec::desired_roles(&accounts_str, &[]))
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store =
+        TestEpochConfigBuilder::from_genesis(&genesis).build_store_for_genesis_protocol_version();
+    let buffer = SharedBuffer::default();
+    set_event_sink(Box::new(buffer.clone()));
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(accounts.clone())
+        .build()
+        .warmup();
+
+    let client_handle = env.node_datas[0].client_sender.actor_handle();
+    let target_height = genesis_height + 3 * epoch_length;
+    env.test_loop.run_until(
+        |data| data.get(&client_handle).client.chain.head().unwrap().height > target_height,
+        Duration::seconds(20),
+    );
+
+    // The events are written by a thread of their own.
+    let is_written = |records: &[EventRecord], account: &AccountId| {
+        records.iter().any(|record| {
+            record.node.as_deref() == Some(account.as_str())
+                && matches!(record.event, ChainEvent::BlockApplied { height, .. } if height == target_height)
+        })
+    };
+    let mut records = buffer.records();
+    for _ in 0..500 {
+        if accounts.iter().all(|account| is_written(&records, account)) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        records = buffer.records();
+    }
+
+    for account in &accounts {
+        let node_records = records
+            .iter()
+            .filter(|record| record.node.as_deref() == Some(account.as_str()))
+            .collect_vec();
+        let applied_heights = node_records
+            .iter()
+            .filter_map(|record| match record.event {
+                ChainEvent::BlockApplied { height, .. } => Some(height),
+                _ => None,
+            })
+            .collect_vec();
+        for height in genesis_height + 1..=target_height {
+            assert!(applied_heights.contains(&height), "{account} didn't apply block {height}");
+        }
+        let epoch_switch_heights = node_records
+            .iter()
+            .filter_map(|record| match record.event {
+                ChainEvent::EpochSwitch { height, .. } => Some(height),
+                _ => None,
+            })
+            .collect_vec();
+        assert!(epoch_switch_heights.len() >= 2, "{account}: {epoch_switch_heights:?}");
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
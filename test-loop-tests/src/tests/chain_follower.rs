@@ -0,0 +1,108 @@
+//! The chain follower streams the blocks of the node it runs on, together with
+//! the state changes of its tracked shards, across epoch boundaries.
+
+use std::path::PathBuf;
+
+use itertools::Itertools;
+use near_async::messaging::IntoMultiSender;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_chain_follower::{ChainFollowerConfig, ViewClientSenderForFollower, spawn_streamer};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::test_utils::create_user_test_signer;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{AccountId, Finality};
+use near_primitives::views::StateChangeCauseView;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::transactions::{get_next_nonce, get_shared_block_hash, run_tx};
+
+#[test]
+fn test_chain_follower_across_epoch_boundary() {
+    init_test_logger();
+    let validators: Vec<AccountId> =
+        (0..2).map(|i| format!("validator{i}").parse().unwrap()).collect();
+    let follower: AccountId = "follower".parse().unwrap();
+    let clients = validators.iter().chain([&follower]).cloned().collect_vec();
+
+    let epoch_length = 5;
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&validators.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&clients, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store =
+        TestEpochConfigBuilder::from_genesis(&genesis).build_store_for_genesis_protocol_version();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .track_all_shards()
+        .build()
+        .warmup();
+
+    let node_data = env.get_node_data_by_account_id(&follower).unwrap();
+    let view_client: ViewClientSenderForFollower =
+        node_data.view_client_sender.clone().into_multi_sender();
+    let client_handle = node_data.client_sender.actor_handle();
+    let start_block_height =
+        env.test_loop.data.get(&client_handle).client.chain.head().unwrap().height;
+    let follower_config = ChainFollowerConfig {
+        home_dir: PathBuf::new(),
+        start_block_height: Some(start_block_height),
+        finality: Finality::Final,
+        with_state_changes: true,
+        validate_genesis: false,
+    };
+    let mut stream = spawn_streamer(
+        env.test_loop.clock(),
+        view_client,
+        follower_config,
+        &env.test_loop.future_spawner("ChainFollower"),
+    );
+
+    let sender = &validators[0];
+    let tx = SignedTransaction::send_money(
+        get_next_nonce(&env.test_loop.data, &env.node_datas, sender),
+        sender.clone(),
+        validators[1].clone(),
+        &create_user_test_signer(sender),
+        ONE_NEAR,
+        get_shared_block_hash(&env.node_datas, &env.test_loop.data),
+    );
+    let tx_hash = tx.get_hash();
+    run_tx(&mut env.test_loop, &follower, tx, &env.node_datas, Duration::seconds(5));
+
+    let target_height = start_block_height + 2 * epoch_length;
+    let mut messages = vec![];
+    env.test_loop.run_until(
+        |_| {
+            while let Ok(message) = stream.try_recv() {
+                messages.push(message);
+            }
+            messages.last().is_some_and(|message| message.block.header.height >= target_height)
+        },
+        Duration::seconds(3 * epoch_length as i64),
+    );
+
+    assert_eq!(messages[0].block.header.height, start_block_height);
+    for (prev, next) in messages.iter().tuple_windows() {
+        assert_eq!(next.block.header.prev_hash, prev.block.header.hash);
+    }
+    let epoch_ids = messages.iter().map(|message| message.block.header.epoch_id).dedup().count();
+    assert!(epoch_ids >= 2, "the follower didn't cross an epoch boundary");
+    let has_tx_state_changes = messages.iter().any(|message| {
+        message.state_changes.values().flatten().any(|state_change| {
+            matches!(
+                state_change.cause,
+                StateChangeCauseView::TransactionProcessing { tx_hash: hash } if hash == tx_hash
+            )
+        })
+    });
+    assert!(has_tx_state_changes, "the state changes of the transaction are missing");
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
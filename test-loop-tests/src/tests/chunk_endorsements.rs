@@ -0,0 +1,96 @@
+//! The block producers know which chunk validators didn't endorse a chunk,
+//! and the chunk validators know when they sent their endorsements.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::MaybeNew;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::setup::drop_condition::DropCondition;
+use crate::utils::ONE_NEAR;
+
+#[test]
+fn test_missing_chunk_endorsements() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> = (0..8).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let accounts_str = accounts.iter().map(|account| account.as_str()).collect_vec();
+    let (block_and_chunk_producers, chunk_validators_only) = accounts_str.split_at(6);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(10)
+        .shard_layout(ShardLayout::simple_v1(&["account2", "account4", "account6"]))
+        .validators_spec(ValidatorsSpec::desired_roles(
+            block_and_chunk_producers,
+            chunk_validators_only,
+        ))
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    // Many mandates per shard, so that the chunks are endorsed without the
+    // endorsements of the dropped validator.
+    let epoch_config_store = TestEpochConfigBuilder::from_genesis(&genesis)
+        .target_validator_mandates_per_shard(16)
+        .build_store_for_genesis_protocol_version();
+
+    let dropped_account = accounts[7].clone();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(accounts.clone())
+        .build()
+        .drop(DropCondition::EndorsementsFrom(dropped_account.clone()))
+        .warmup();
+
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    env.test_loop.run_until(
+        |data| data.get(&client_handles[0]).client.chain.head().unwrap().height > 8,
+        Duration::seconds(10),
+    );
+
+    let clients =
+        client_handles.iter().map(|handle| &env.test_loop.data.get(handle).client).collect_vec();
+    let client = clients[0];
+    let height = client.chain.head().unwrap().height - 1;
+    let block = client.chain.get_block_by_height(height).unwrap();
+    let epoch_id = block.header().epoch_id();
+    let block_producer = client.epoch_manager.get_block_producer(epoch_id, height).unwrap();
+    let producer_index = accounts.iter().position(|account| account == &block_producer).unwrap();
+    let producer_views =
+        clients[producer_index].chunk_endorsement_tracker.chunk_endorsements_view(height);
+    let dropped_views = clients[7].chunk_endorsement_tracker.chunk_endorsements_view(height);
+
+    let mut num_validated_by_dropped_account = 0;
+    for chunk_header in block.chunks().iter() {
+        let MaybeNew::New(chunk_header) = chunk_header else {
+            continue;
+        };
+        let shard_id = chunk_header.shard_id();
+        let view = producer_views.iter().find(|view| view.shard_id == shard_id).unwrap();
+        assert_eq!(view.chunk_hash, Some(chunk_header.chunk_hash()));
+        assert!(view.first_collected_at.is_some());
+        assert!(view.endorsed_at.is_some());
+        assert!(!view.received.is_empty());
+        assert!(view.received.iter().all(|endorsement| endorsement.account_id != dropped_account));
+
+        let assignments = client
+            .epoch_manager
+            .get_chunk_validator_assignments(epoch_id, shard_id, height)
+            .unwrap();
+        if !assignments.contains(&dropped_account) {
+            continue;
+        }
+        num_validated_by_dropped_account += 1;
+        assert!(view.missing.contains(&dropped_account), "{view:?}");
+        // The endorsement was sent, and dropped by the network.
+        let dropped_view = dropped_views.iter().find(|view| view.shard_id == shard_id).unwrap();
+        assert!(dropped_view.sent_at.is_some());
+    }
+    assert!(num_validated_by_dropped_account > 0);
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -16,7 +16,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::test_utils::create_user_test_signer;
 use near_primitives::types::{AccountId, BlockHeight, EpochId, NumSeats};
-use rand::{Rng as _, thread_rng};
+use rand::Rng as _;
 
 use crate::setup::builder::TestLoopBuilder;
 use crate::setup::env::TestLoopEnv;
@@ -32,11 +32,6 @@ use crate::utils::rotating_validators_runner::RotatingValidatorsRunner;
 fn ultra_slow_test_consensus_with_epoch_switches() {
     init_test_logger();
 
-    let seed: u64 = thread_rng().r#gen();
-    println!("RNG seed: {seed}. If test fails use it to find the issue.");
-    let rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(seed);
-    let rng = Arc::new(RwLock::new(rng));
-
     let validators: Vec<Vec<AccountId>> = [
         ["test1.1", "test1.2", "test1.3", "test1.4", "test1.5", "test1.6", "test1.7", "test1.8"],
         ["test2.1", "test2.2", "test2.3", "test2.4", "test2.5", "test2.6", "test2.7", "test2.8"],
@@ -72,6 +67,10 @@ fn ultra_slow_test_consensus_with_epoch_switches() {
         .track_all_shards()
         .build()
         .warmup();
+    // Derived from the seed of the test loop so that a recorded schedule
+    // reproduces the skips as well.
+    let rng: rand::rngs::StdRng = rand::SeedableRng::seed_from_u64(env.test_loop.seed());
+    let rng = Arc::new(RwLock::new(rng));
 
     let min_delay = 3;
     let handler = Arc::new(RwLock::new(NetworkHandlingData::new(&env, validators)));
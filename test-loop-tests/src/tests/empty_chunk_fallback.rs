@@ -0,0 +1,127 @@
+//! Test that the chunk producer falls back to chunks without transactions when
+//! the chunks of a shard keep missing from blocks, e.g. because their state
+//! witnesses don't reach the chunk validators.
+
+use std::collections::HashMap;
+
+use near_async::time::Duration;
+use near_chain_configs::EmptyChunkFallbackConfig;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::MaybeNew;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::test_utils::create_user_test_signer;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{AccountId, BlockHeight};
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::setup::drop_condition::DropCondition;
+use crate::setup::env::TestLoopEnv;
+use crate::utils::ONE_NEAR;
+use crate::utils::transactions::{get_next_nonce, get_shared_block_hash, submit_tx};
+
+const EPOCH_LENGTH: u64 = 50;
+/// Heights, relative to the epoch start, at which the chunks are dropped.
+const DROPPED_CHUNKS: std::ops::Range<usize> = 10..15;
+const MISSING_CHUNKS_THRESHOLD: u64 = 3;
+
+#[test]
+fn test_empty_chunk_fallback() {
+    init_test_logger();
+    let accounts =
+        (0..3).map(|i| format!("account{}", i).parse().unwrap()).collect::<Vec<AccountId>>();
+    let validator = accounts[0].clone();
+    let shard_layout = ShardLayout::single_shard();
+    let shard_id = shard_layout.shard_ids().next().unwrap();
+
+    let validators_spec = ValidatorsSpec::desired_roles(&[validator.as_str()], &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(EPOCH_LENGTH)
+        .shard_layout(shard_layout)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+
+    // Drop the endorsements of a few consecutive chunks, as if their witnesses
+    // didn't reach the chunk validators.
+    let chunks_produced =
+        (0..EPOCH_LENGTH as usize).map(|height| !DROPPED_CHUNKS.contains(&height)).collect();
+    let TestLoopEnv { mut test_loop, node_datas, shared_state } = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(vec![validator.clone()])
+        .config_modifier(|config, _| {
+            config.empty_chunk_fallback = EmptyChunkFallbackConfig {
+                enabled: true,
+                missing_chunks_threshold: MISSING_CHUNKS_THRESHOLD,
+                max_transactions: 0,
+            };
+        })
+        .build()
+        .drop(DropCondition::ChunksProducedByHeight(HashMap::from([(shard_id, chunks_produced)])))
+        .warmup();
+
+    // Keep the transaction pool busy by submitting a transfer at every height.
+    let sender = accounts[1].clone();
+    let receiver = accounts[2].clone();
+    let signer = create_user_test_signer(&sender);
+    let mut nonce = get_next_nonce(&test_loop.data, &node_datas, &sender);
+    let client_handle = node_datas[0].client_sender.actor_handle();
+    let mut last_height: Option<BlockHeight> = None;
+    test_loop.run_until(
+        |test_loop_data| {
+            let tip = test_loop_data.get(&client_handle).client.chain.head().unwrap();
+            if last_height == Some(tip.height) {
+                return false;
+            }
+            last_height = Some(tip.height);
+            let block_hash = get_shared_block_hash(&node_datas, test_loop_data);
+            let tx = SignedTransaction::send_money(
+                nonce,
+                sender.clone(),
+                receiver.clone(),
+                &signer,
+                ONE_NEAR,
+                block_hash,
+            );
+            nonce += 1;
+            submit_tx(&node_datas, &validator, tx);
+            tip.height > EPOCH_LENGTH
+        },
+        Duration::seconds(EPOCH_LENGTH as i64 * 2),
+    );
+
+    // Collect the number of transactions in the chunk of every block, `None`
+    // if the chunk is missing.
+    let client = &test_loop.data.get(&client_handle).client;
+    let genesis_height = client.chain.genesis().height();
+    let head_height = client.chain.head().unwrap().height;
+    let chunks = (genesis_height + 1..=head_height)
+        .map(|height| {
+            let block = client.chain.get_block_by_height(height).unwrap();
+            match block.chunks().iter().next().unwrap() {
+                MaybeNew::New(chunk_header) => {
+                    let chunk = client.chain.get_chunk(&chunk_header.chunk_hash()).unwrap();
+                    Some(chunk.to_transactions().len())
+                }
+                MaybeNew::Old(_) => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    tracing::info!(target: "test", ?chunks, "Transactions per chunk");
+
+    let first_missing = chunks.iter().position(Option::is_none).unwrap();
+    let num_missing = chunks[first_missing..].iter().take_while(|chunk| chunk.is_none()).count();
+    assert_eq!(num_missing, DROPPED_CHUNKS.len());
+    // The shard recovers as soon as the chunks get endorsed again. The first
+    // chunk after the outage carries no transactions, and the next one picks
+    // up the ones accumulated in the pool in the meantime.
+    let after_outage = &chunks[first_missing + num_missing..];
+    assert!(after_outage.iter().all(Option::is_some), "chunks are missing after the outage");
+    assert_eq!(after_outage[0], Some(0));
+    assert!(after_outage[1].unwrap() > 0);
+
+    TestLoopEnv { test_loop, node_datas, shared_state }
+        .shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
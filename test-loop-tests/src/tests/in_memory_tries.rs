@@ -4,6 +4,7 @@ use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
 use near_o11y::testonly::init_test_logger;
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::types::AccountId;
+use near_store::MemTrieLoadingStatus;
 
 use crate::setup::builder::TestLoopBuilder;
 use crate::setup::env::TestLoopEnv;
@@ -95,3 +96,71 @@ fn test_load_memtrie_after_empty_chunks() {
     TestLoopEnv { test_loop, node_datas, shared_state }
         .shutdown_and_drain_remaining_events(Duration::seconds(20));
 }
+
+/// Restarts a node in the middle of an epoch with the memtries loaded in
+/// background. The node applies chunks without the memtries while they are
+/// loading, switches to them afterwards and keeps processing transactions.
+#[test]
+fn test_load_memtries_in_background_after_restart() {
+    init_test_logger();
+
+    let epoch_length = 10;
+    let shard_layout = ShardLayout::simple_v1(&["account2"]);
+    let accounts =
+        (0..4).map(|i| format!("account{}", i).parse().unwrap()).collect::<Vec<AccountId>>();
+    let client: AccountId = "cp0".parse().unwrap();
+    let validators_spec = ValidatorsSpec::desired_roles(&[client.as_str()], &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(epoch_length)
+        .shard_layout(shard_layout.clone())
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store = TestEpochConfigBuilder::build_store_from_genesis(&genesis);
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(vec![client.clone()])
+        .load_memtries_in_background(true)
+        .build()
+        .warmup();
+
+    let client_handle = env.node_datas[0].client_sender.actor_handle();
+    let start_height = env.test_loop.data.get(&client_handle).client.chain.head().unwrap().height;
+    env.test_loop.run_until(
+        |test_loop_data| {
+            test_loop_data.get(&client_handle).client.chain.head().unwrap().height
+                >= start_height + epoch_length * 3 / 2
+        },
+        Duration::seconds(20),
+    );
+
+    env.restart_node(&client);
+    let client_handle =
+        env.get_node_data_by_account_id(&client).unwrap().client_sender.actor_handle();
+    let tries = env.test_loop.data.get(&client_handle).client.runtime_adapter.get_tries();
+    // The memtries are switched to when the next block is applied.
+    for shard_uid in shard_layout.shard_uids() {
+        assert_eq!(
+            tries.get_memtrie_loading_status(shard_uid),
+            Some(MemTrieLoadingStatus::InProgress)
+        );
+        assert!(tries.get_memtries(shard_uid).is_none());
+    }
+
+    env.test_loop.run_until(
+        |_| {
+            shard_layout.shard_uids().all(|shard_uid| {
+                tries.get_memtrie_loading_status(shard_uid) == Some(MemTrieLoadingStatus::Done)
+            })
+        },
+        Duration::seconds(100),
+    );
+    for shard_uid in shard_layout.shard_uids() {
+        assert!(tries.get_memtries(shard_uid).is_some());
+    }
+
+    execute_money_transfers(&mut env.test_loop, &env.node_datas, &accounts).unwrap();
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
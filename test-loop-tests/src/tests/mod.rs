@@ -1,5 +1,8 @@
 mod bandwidth_scheduler;
 mod bandwidth_scheduler_protocol_upgrade;
+mod chain_events;
+mod chain_follower;
+mod chunk_endorsements;
 mod chunk_validator_kickout;
 mod chunks_management;
 mod congestion_control;
@@ -8,6 +11,7 @@ mod consensus;
 mod contract_distribution_cross_shard;
 mod contract_distribution_simple;
 mod create_delete_account;
+mod empty_chunk_fallback;
 mod epoch_sync;
 mod fix_chunk_producer_stake_threshold;
 mod fix_stake_threshold;
@@ -21,7 +25,17 @@ mod multinode_stateless_validators;
 mod optimistic_block;
 mod protocol_upgrade;
 mod reject_outdated_blocks;
+mod replica;
+mod resharding_adversarial;
+mod resharding_benchmark;
+mod resharding_clock_skew;
+mod resharding_proposal;
+mod resharding_restart;
+mod resharding_synthetic_state;
 mod resharding_v3;
+mod resharding_witness_size;
 mod state_sync;
 mod syncing;
+mod tx_execution_profile;
+mod tx_forwarding;
 mod view_requests_to_archival_node;
@@ -21,6 +21,7 @@ use crate::setup::builder::TestLoopBuilder;
 use crate::setup::drop_condition::DropCondition;
 use crate::setup::env::TestLoopEnv;
 use crate::utils::ONE_NEAR;
+use crate::utils::validators::assert_kickouts_match_dropped_chunks;
 
 /// Test upgrading the blockchain to another protocol version.
 /// Optionally make some chunks around epoch boundary missing.
@@ -91,11 +92,9 @@ pub(crate) fn test_protocol_upgrade(
         config.num_chunk_producer_seats = genesis_epoch_info.num_chunk_producer_seats;
         config.num_chunk_validator_seats = genesis_epoch_info.num_chunk_validator_seats;
 
-        if !chunk_ranges_to_drop.is_empty() {
-            config.block_producer_kickout_threshold = 0;
-            config.chunk_producer_kickout_threshold = 0;
-            config.chunk_validator_only_kickout_threshold = 0;
-        }
+        // Kickouts are checked against the dropped chunks at the end of the test, which
+        // doesn't account for validators exempted from the kickout.
+        config.validator_max_kickout_stake_perc = 100;
     };
     adjust_epoch_config(&mut old_epoch_config);
     adjust_epoch_config(&mut new_epoch_config);
@@ -193,6 +192,19 @@ pub(crate) fn test_protocol_upgrade(
     }
     assert_eq!(&*observed_missing_chunks.borrow(), &expected_missing_chunks);
 
+    // Validate that the validators were kicked out exactly as expected given the missing chunks.
+    // The last epoch is not finished yet, so its kickouts are not known.
+    let client = &test_loop.data.get(&client_handle).client;
+    let tip = client.chain.head().unwrap();
+    let old_epoch_ids = epoch_ids_with_old_protocol.borrow();
+    let new_epoch_ids = epoch_ids_with_new_protocol.borrow();
+    for epoch_id in old_epoch_ids.iter().chain(new_epoch_ids.iter()) {
+        if *epoch_id == tip.epoch_id {
+            continue;
+        }
+        assert_kickouts_match_dropped_chunks(client, epoch_id, &expected_missing_chunks);
+    }
+
     TestLoopEnv { test_loop, node_datas, shared_state }
         .shutdown_and_drain_remaining_events(Duration::seconds(20));
 }
@@ -0,0 +1,224 @@
+//! A replica follows the block producers through a resharding by applying
+//! their state updates instead of executing the chunks, and ends up with the
+//! same state as the producers. It applies the chunks when an update doesn't
+//! lead to the state root it claims.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::epoch_manager::{EpochConfigStore, ReshardingProposal};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::test_utils::create_test_signer;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::resharding::execute_money_transfers;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+#[test]
+fn test_replica_follows_producers_through_resharding() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> =
+        (0..20).map(|i| format!("account{i:02}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let replica: AccountId = "replica".parse().unwrap();
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(ShardLayout::multi_shard(3, 3))
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_shard_layout =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account).shard_layout;
+    // The genesis epoch has height 1.
+    let resharding_epoch_height = 4;
+    let proposal = ReshardingProposal {
+        epoch_height: resharding_epoch_height,
+        shard_layout: new_shard_layout.clone(),
+    };
+    let epoch_config_store =
+        EpochConfigStore::test_single_version(PROTOCOL_VERSION, base_epoch_config)
+            .with_resharding_proposal(PROTOCOL_VERSION, proposal);
+
+    let trusted_block_producer_keys = producers
+        .iter()
+        .map(|account| create_test_signer(account.as_str()).public_key())
+        .collect_vec();
+    let clients = producers.iter().cloned().chain([replica.clone()]).collect_vec();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(clients)
+        .epoch_config_store(epoch_config_store)
+        .track_all_shards()
+        .config_modifier(|config, _| config.serve_replica_state_updates = true)
+        .config_modifier_for(&replica, move |config| {
+            config.serve_replica_state_updates = false;
+            config.replica.enabled = true;
+            config.replica.trusted_block_producer_keys = trusted_block_producer_keys.clone();
+        })
+        .build()
+        .warmup();
+
+    let transfers = execute_money_transfers(accounts);
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let replica_handle = client_handles[producers.len()].clone();
+    let node_datas = env.node_datas.clone();
+    let client_account_id = producers[0].clone();
+    let start_height = env.test_loop.data.get(&replica_handle).client.chain.head().unwrap().height;
+    env.test_loop.run_until(
+        |data| {
+            transfers.call(&node_datas, data, client_account_id.clone());
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                client.epoch_manager.get_shard_layout(&head.epoch_id).unwrap() == new_shard_layout
+            })
+        },
+        Duration::seconds((6 * epoch_length) as i64),
+    );
+    let replica_client = &env.test_loop.data.get(&replica_handle).client;
+    let resharding_height = replica_client.chain.head().unwrap().height;
+    let hits_before_resharding = replica_client.chain.replica_state_updates.hits();
+    assert!(hits_before_resharding > 0, "the replica didn't apply any state updates");
+
+    // The replica keeps following the producers in the new shard layout.
+    env.test_loop.run_until(
+        |data| {
+            transfers.call(&node_datas, data, client_account_id.clone());
+            client_handles.iter().all(|handle| {
+                let head = data.get(handle).client.chain.head().unwrap();
+                head.height > resharding_height + epoch_length
+            })
+        },
+        Duration::seconds((2 * epoch_length) as i64),
+    );
+
+    // The replica has the same chunk extras as a producer in every shard.
+    let producer_client = &env.test_loop.data.get(&client_handles[0]).client;
+    let replica_client = &env.test_loop.data.get(&replica_handle).client;
+    assert!(
+        replica_client.chain.replica_state_updates.hits() > hits_before_resharding,
+        "the replica didn't apply any state updates after the resharding"
+    );
+    let end_height = replica_client.chain.head().unwrap().height;
+    for height in start_height + 1..=end_height {
+        let Ok(block) = replica_client.chain.get_block_by_height(height) else {
+            continue;
+        };
+        let block_hash = block.hash();
+        let shard_layout =
+            replica_client.epoch_manager.get_shard_layout(block.header().epoch_id()).unwrap();
+        for shard_uid in shard_layout.shard_uids() {
+            let replica_extra =
+                replica_client.chain.get_chunk_extra(block_hash, &shard_uid).unwrap();
+            let producer_extra =
+                producer_client.chain.get_chunk_extra(block_hash, &shard_uid).unwrap();
+            assert_eq!(replica_extra, producer_extra, "height {height} shard {shard_uid}");
+        }
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
+
+/// The producers serve updates whose state root doesn't match their state
+/// changes. The replica applies the chunks instead, and keeps following the
+/// producers without rejecting their blocks.
+#[cfg(feature = "test_features")]
+#[test]
+fn test_replica_applies_chunks_on_mismatching_state_updates() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> =
+        (0..10).map(|i| format!("account{i:02}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let replica: AccountId = "replica".parse().unwrap();
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(ShardLayout::multi_shard(2, 3))
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store =
+        TestEpochConfigBuilder::from_genesis(&genesis).build_store_for_genesis_protocol_version();
+
+    let trusted_block_producer_keys = producers
+        .iter()
+        .map(|account| create_test_signer(account.as_str()).public_key())
+        .collect_vec();
+    let clients = producers.iter().cloned().chain([replica.clone()]).collect_vec();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .track_all_shards()
+        .config_modifier(|config, _| config.serve_replica_state_updates = true)
+        .config_modifier_for(&replica, move |config| {
+            config.serve_replica_state_updates = false;
+            config.replica.enabled = true;
+            config.replica.trusted_block_producer_keys = trusted_block_producer_keys.clone();
+        })
+        .build()
+        .warmup();
+
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    for handle in &client_handles[..producers.len()] {
+        let client = &mut env.test_loop.data.get_mut(handle).client;
+        client.chain.replica_state_updates.adv_corrupt_state_roots = true;
+    }
+
+    let transfers = execute_money_transfers(accounts);
+    let replica_handle = client_handles[producers.len()].clone();
+    let node_datas = env.node_datas.clone();
+    let client_account_id = producers[0].clone();
+    let start_height = env.test_loop.data.get(&replica_handle).client.chain.head().unwrap().height;
+    let end_height = start_height + 2 * epoch_length;
+    env.test_loop.run_until(
+        |data| {
+            transfers.call(&node_datas, data, client_account_id.clone());
+            client_handles
+                .iter()
+                .all(|handle| data.get(handle).client.chain.head().unwrap().height >= end_height)
+        },
+        Duration::seconds((3 * epoch_length) as i64),
+    );
+
+    let producer_client = &env.test_loop.data.get(&client_handles[0]).client;
+    let replica_client = &env.test_loop.data.get(&replica_handle).client;
+    assert!(
+        replica_client.chain.replica_state_updates.hits() > 0,
+        "the replica didn't try any state updates"
+    );
+    for height in start_height + 1..=end_height {
+        let Ok(block) = replica_client.chain.get_block_by_height(height) else {
+            continue;
+        };
+        let block_hash = block.hash();
+        let shard_layout =
+            replica_client.epoch_manager.get_shard_layout(block.header().epoch_id()).unwrap();
+        for shard_uid in shard_layout.shard_uids() {
+            let replica_extra =
+                replica_client.chain.get_chunk_extra(block_hash, &shard_uid).unwrap();
+            let producer_extra =
+                producer_client.chain.get_chunk_extra(block_hash, &shard_uid).unwrap();
+            assert_eq!(replica_extra, producer_extra, "height {height} shard {shard_uid}");
+        }
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
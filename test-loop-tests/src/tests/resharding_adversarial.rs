@@ -0,0 +1,176 @@
+#![cfg(feature = "test_features")] // required for adversarial behaviors
+//! A chunk producer misbehaves on the chunk level around the resharding
+//! boundary. Its chunks are rejected by the chunk validators, and the chain
+//! goes through the resharding anyway.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use near_async::messaging::CanSend as _;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_client::client_actor::{AdvProduceChunksMode, NetworkAdversarialMessage};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::MaybeNew;
+use near_primitives::epoch_manager::EpochConfigStore;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::stateless_validation::ChunkProductionKey;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::setup::env::TestLoopEnv;
+use crate::utils::ONE_NEAR;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+/// Runs the resharding while the first producer misbehaves as set by
+/// `adversary(true)`, until an epoch after the resharding. None of its chunks
+/// may be included in that time.
+fn test_resharding_with_adversary(adversary: impl Fn(bool) -> Vec<NetworkAdversarialMessage>) {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> = (0..8).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let base_shard_layout = ShardLayout::multi_shard(3, 3);
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION - 1)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(base_shard_layout)
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, ONE_NEAR)
+        .build();
+
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_epoch_config =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account);
+    let new_shard_layout = new_epoch_config.shard_layout.clone();
+    let epoch_configs = vec![
+        (genesis.config.protocol_version, Arc::new(base_epoch_config)),
+        (genesis.config.protocol_version + 1, Arc::new(new_epoch_config)),
+    ];
+    let epoch_config_store = EpochConfigStore::test(BTreeMap::from_iter(epoch_configs));
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(producers.clone())
+        .epoch_config_store(epoch_config_store)
+        .build()
+        .warmup();
+
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let start_height =
+        env.test_loop.data.get(&client_handles[0]).client.chain.head().unwrap().height;
+    let set_adversary = |env: &mut TestLoopEnv, on: bool| {
+        let client_sender = env.node_datas[0].client_sender.clone();
+        let messages = adversary(on);
+        env.test_loop.send_adhoc_event(format!("set adversary {on}"), move |_| {
+            for message in messages {
+                client_sender.send(message);
+            }
+        });
+    };
+    set_adversary(&mut env, true);
+
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                client.epoch_manager.get_shard_layout(&head.epoch_id).unwrap() == new_shard_layout
+            })
+        },
+        Duration::seconds((4 * epoch_length) as i64),
+    );
+    let client = &env.test_loop.data.get(&client_handles[1]).client;
+    let resharding_height = client.chain.head().unwrap().height;
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let head = data.get(handle).client.chain.head().unwrap();
+                head.height > resharding_height + epoch_length
+            })
+        },
+        Duration::seconds((2 * epoch_length) as i64),
+    );
+
+    // The chunks of the adversary in the blocks seen by an honest node.
+    let client = &env.test_loop.data.get(&client_handles[1]).client;
+    let end_height = client.chain.head().unwrap().height;
+    let mut num_produced = 0;
+    let mut num_missing = 0;
+    for height in start_height + 1..=end_height {
+        let Ok(block) = client.chain.get_block_by_height(height) else {
+            continue;
+        };
+        let epoch_id =
+            client.epoch_manager.get_epoch_id_from_prev_block(block.header().prev_hash()).unwrap();
+        let shard_layout = client.epoch_manager.get_shard_layout(&epoch_id).unwrap();
+        for (shard_id, chunk_header) in shard_layout.shard_ids().zip(block.chunks().iter()) {
+            let key = ChunkProductionKey { epoch_id, height_created: height, shard_id };
+            let chunk_producer = client.epoch_manager.get_chunk_producer_info(&key).unwrap();
+            if chunk_producer.account_id() != &producers[0] {
+                continue;
+            }
+            match chunk_header {
+                MaybeNew::New(_) => num_produced += 1,
+                MaybeNew::Old(_) => num_missing += 1,
+            }
+        }
+    }
+    tracing::info!(target: "test", num_produced, num_missing, "chunks of the adversary");
+    assert_eq!(num_produced, 0, "chunks of the adversary were included");
+    assert!(num_missing > 0, "the adversary didn't have chunks to produce");
+
+    // The chain keeps going once the adversary behaves.
+    set_adversary(&mut env, false);
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let head = data.get(handle).client.chain.head().unwrap();
+                head.height > end_height + epoch_length
+            })
+        },
+        Duration::seconds((2 * epoch_length) as i64),
+    );
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
+
+#[test]
+fn test_resharding_with_corrupted_state_roots() {
+    test_resharding_with_adversary(|on| {
+        let mode = if on {
+            AdvProduceChunksMode::ProduceWithCorruptedStateRoot
+        } else {
+            AdvProduceChunksMode::Valid
+        };
+        vec![NetworkAdversarialMessage::AdvProduceChunks(mode)]
+    });
+}
+
+#[test]
+fn test_resharding_with_invalid_state_witnesses() {
+    test_resharding_with_adversary(|on| {
+        vec![NetworkAdversarialMessage::AdvProduceInvalidStateWitnesses(on)]
+    });
+}
+
+/// The other nodes never get the withheld parts, so the chunks of the
+/// adversary can't be included.
+#[test]
+fn test_resharding_with_withheld_chunk_parts() {
+    test_resharding_with_adversary(|on| {
+        let account_ids = if on {
+            (1..4).map(|i| format!("cp{i}").parse().unwrap()).collect()
+        } else {
+            HashSet::new()
+        };
+        vec![NetworkAdversarialMessage::AdvWithholdChunkParts(account_ids)]
+    });
+}
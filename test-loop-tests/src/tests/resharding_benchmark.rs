@@ -0,0 +1,182 @@
+//! Benchmark of the duration of resharding depending on the size of the split
+//! shard. It is not a correctness test, so it is ignored by default.
+//!
+//! For each state size, the parent shard is seeded in genesis with the given
+//! number of accounts, each having the given number of access keys, and split
+//! in the middle. The blocks are produced on the virtual clock of the test
+//! loop, while the resharding phases run inline and are timed on the real
+//! clock through the `near_resharding_phase_time` metric.
+//!
+//! Run with:
+//! ```text
+//! RESHARDING_BENCHMARK_STATE_SIZES=1000:10,100000:1 \
+//! RESHARDING_BENCHMARK_REPORT=report.json \
+//! cargo test -p test-loop-tests --release resharding_benchmark -- --ignored --nocapture
+//! ```
+//! The state sizes are comma separated pairs of the number of accounts and the
+//! number of access keys per account. The report is a JSON array with the
+//! duration of each phase in seconds, and is printed to stdout if no report
+//! path is given.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use near_async::time::Duration;
+use near_chain::metrics::RESHARDING_PHASE_TIME;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_crypto::{InMemorySigner, KeyType};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::epoch_manager::EpochConfigStore;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+use near_store::ShardUId;
+use near_store::flat::FlatStorageStatus;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+const DEFAULT_STATE_SIZES: &str = "1000:1,10000:10";
+
+struct StateSize {
+    num_accounts: usize,
+    keys_per_account: usize,
+}
+
+fn state_sizes() -> Vec<StateSize> {
+    let sizes = std::env::var("RESHARDING_BENCHMARK_STATE_SIZES")
+        .unwrap_or_else(|_| DEFAULT_STATE_SIZES.to_string());
+    sizes
+        .split(',')
+        .map(|size| {
+            let (num_accounts, keys_per_account) =
+                size.trim().split_once(':').expect("state size must be <accounts>:<keys>");
+            StateSize {
+                num_accounts: num_accounts.parse().unwrap(),
+                keys_per_account: keys_per_account.parse().unwrap(),
+            }
+        })
+        .collect()
+}
+
+fn phase_time_sum(shard_uid: ShardUId, phase: &str) -> f64 {
+    RESHARDING_PHASE_TIME.with_label_values(&[&shard_uid.to_string(), phase]).get_sample_sum()
+}
+
+/// Runs resharding of a shard of the given size and returns the report entry.
+fn run_resharding(state_size: &StateSize) -> serde_json::Value {
+    let StateSize { num_accounts, keys_per_account } = *state_size;
+    let accounts: Vec<AccountId> =
+        (0..num_accounts).map(|i| format!("account{i:08}").parse().unwrap()).collect();
+
+    let base_shard_layout = ShardLayout::multi_shard(3, 3);
+    let epoch_length = 5;
+    let chunk_producer = "cp0";
+    let validators_spec = ValidatorsSpec::desired_roles(&[chunk_producer], &[]);
+    let mut genesis_builder = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION - 1)
+        .validators_spec(validators_spec)
+        .shard_layout(base_shard_layout.clone())
+        .epoch_length(epoch_length);
+    for account_id in &accounts {
+        let access_keys = (1..keys_per_account).map(|i| {
+            InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, &i.to_string())
+                .public_key()
+        });
+        genesis_builder =
+            genesis_builder.add_user_account_with_keys(account_id.clone(), ONE_NEAR, access_keys);
+    }
+    let genesis = genesis_builder.build();
+
+    // All the accounts belong to the first shard, which is split in the middle.
+    let boundary_account = accounts[num_accounts / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_epoch_config =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account);
+    let parent_shard_uid = base_shard_layout.account_id_to_shard_uid(&boundary_account);
+    let children_shard_uids = new_epoch_config
+        .shard_layout
+        .get_children_shards_uids(parent_shard_uid.shard_id())
+        .unwrap();
+    let epoch_configs = vec![
+        (genesis.config.protocol_version, Arc::new(base_epoch_config)),
+        (genesis.config.protocol_version + 1, Arc::new(new_epoch_config)),
+    ];
+    let epoch_config_store = EpochConfigStore::test(BTreeMap::from_iter(epoch_configs));
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(vec![chunk_producer.parse().unwrap()])
+        .epoch_config_store(epoch_config_store)
+        .config_modifier(|config, _| {
+            // Don't throttle resharding, only its own work is measured.
+            let mut resharding_config = config.resharding_config.get();
+            resharding_config.batch_delay = Duration::ZERO;
+            config.resharding_config.update(resharding_config);
+        })
+        .build()
+        .warmup();
+
+    // The metrics are global, so only their increase during this run is reported.
+    let memtrie_split_start = phase_time_sum(parent_shard_uid, "memtrie_split");
+    let flat_storage_split_start = phase_time_sum(parent_shard_uid, "flat_storage_split");
+    let catchup_start: f64 = children_shard_uids
+        .iter()
+        .map(|shard_uid| phase_time_sum(*shard_uid, "flat_storage_catchup"))
+        .sum();
+
+    let client_handle = env.node_datas[0].client_sender.actor_handle();
+    let flat_storage_manager = env
+        .test_loop
+        .data
+        .get(&client_handle)
+        .client
+        .chain
+        .runtime_adapter
+        .get_flat_storage_manager();
+    let start = std::time::Instant::now();
+    env.test_loop.run_until(
+        |_| {
+            children_shard_uids.iter().all(|shard_uid| {
+                matches!(
+                    flat_storage_manager.get_flat_storage_status(*shard_uid),
+                    FlatStorageStatus::Ready(_)
+                )
+            })
+        },
+        Duration::seconds((6 * epoch_length) as i64),
+    );
+    let total = start.elapsed();
+
+    let catchup_end: f64 = children_shard_uids
+        .iter()
+        .map(|shard_uid| phase_time_sum(*shard_uid, "flat_storage_catchup"))
+        .sum();
+    let report = serde_json::json!({
+        "num_accounts": num_accounts,
+        "keys_per_account": keys_per_account,
+        "memtrie_split_sec":
+            phase_time_sum(parent_shard_uid, "memtrie_split") - memtrie_split_start,
+        "flat_storage_split_sec":
+            phase_time_sum(parent_shard_uid, "flat_storage_split") - flat_storage_split_start,
+        "flat_storage_catchup_sec": catchup_end - catchup_start,
+        "total_sec": total.as_secs_f64(),
+    });
+    tracing::info!(target: "test", %report, "resharding benchmark finished");
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(10));
+    report
+}
+
+#[test]
+#[ignore]
+fn resharding_benchmark() {
+    init_test_logger();
+
+    let reports: Vec<_> = state_sizes().iter().map(run_resharding).collect();
+    let reports = serde_json::to_string_pretty(&reports).unwrap();
+    match std::env::var("RESHARDING_BENCHMARK_REPORT") {
+        Ok(path) => std::fs::write(path, reports).unwrap(),
+        Err(_) => println!("{reports}"),
+    }
+}
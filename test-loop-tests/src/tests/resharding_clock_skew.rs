@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::MaybeNew;
+use near_primitives::epoch_manager::EpochConfigStore;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+/// The clocks of the producers are up to 2s apart from each other and drift
+/// further apart during the test. The chain still goes through the resharding
+/// and keeps producing blocks with the new shard layout.
+#[test]
+fn test_resharding_with_clock_skew() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> = (0..8).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let base_shard_layout = ShardLayout::multi_shard(3, 3);
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION - 1)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(base_shard_layout.clone())
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, ONE_NEAR)
+        .build();
+
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_epoch_config =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account);
+    let new_shard_layout = new_epoch_config.shard_layout.clone();
+    let epoch_configs = vec![
+        (genesis.config.protocol_version, Arc::new(base_epoch_config)),
+        (genesis.config.protocol_version + 1, Arc::new(new_epoch_config)),
+    ];
+    let epoch_config_store = EpochConfigStore::test(BTreeMap::from_iter(epoch_configs));
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(producers.clone())
+        .epoch_config_store(epoch_config_store)
+        .clock_skew(&producers[0], Duration::seconds(2))
+        .clock_skew(&producers[1], Duration::seconds(-2))
+        .clock_skew(&producers[2], Duration::seconds(1))
+        .clock_drift(&producers[2], 0.001)
+        .clock_drift(&producers[3], -0.001)
+        .build()
+        .warmup();
+
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let clocks_difference =
+        client.clock.now_utc() - env.test_loop.data.get(&client_handles[1]).client.clock.now_utc();
+    assert!(clocks_difference >= Duration::seconds(4), "{clocks_difference}");
+
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                client.epoch_manager.get_shard_layout(&head.epoch_id).unwrap() == new_shard_layout
+            })
+        },
+        Duration::seconds((4 * epoch_length) as i64),
+    );
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let resharding_height = client.chain.head().unwrap().height;
+
+    // Blocks and chunks keep coming in the new shard layout.
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let head = data.get(handle).client.chain.head().unwrap();
+                head.height > resharding_height + 2 * epoch_length
+            })
+        },
+        Duration::seconds((4 * epoch_length) as i64),
+    );
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let mut block = client.chain.get_block(&client.chain.head().unwrap().last_block_hash).unwrap();
+    let mut shards_with_new_chunks = HashSet::new();
+    for _ in 0..epoch_length {
+        assert_eq!(block.chunks().len(), new_shard_layout.num_shards() as usize);
+        for chunk_header in block.chunks().iter() {
+            if let MaybeNew::New(chunk_header) = chunk_header {
+                shards_with_new_chunks.insert(chunk_header.shard_id());
+            }
+        }
+        block = client.chain.get_block(block.header().prev_hash()).unwrap();
+    }
+    assert_eq!(shards_with_new_chunks, new_shard_layout.shard_ids().collect());
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
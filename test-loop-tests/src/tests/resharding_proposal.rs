@@ -0,0 +1,106 @@
+//! The shard layout changes at an epoch boundary on the same protocol version,
+//! as set by the resharding proposal of the epoch config store.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::epoch_manager::{EpochConfigStore, ReshardingProposal};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+#[test]
+fn test_resharding_proposal() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> = (0..8).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let base_shard_layout = ShardLayout::multi_shard(3, 3);
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(base_shard_layout.clone())
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, ONE_NEAR)
+        .build();
+
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_shard_layout =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account).shard_layout;
+    // The genesis epoch has height 1.
+    let resharding_epoch_height = 4;
+    let proposal = ReshardingProposal {
+        epoch_height: resharding_epoch_height,
+        shard_layout: new_shard_layout.clone(),
+    };
+    let epoch_config_store =
+        EpochConfigStore::test_single_version(PROTOCOL_VERSION, base_epoch_config)
+            .with_resharding_proposal(PROTOCOL_VERSION, proposal);
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(producers)
+        .epoch_config_store(epoch_config_store)
+        .build()
+        .warmup();
+
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                client.epoch_manager.get_shard_layout(&head.epoch_id).unwrap() == new_shard_layout
+            })
+        },
+        Duration::seconds((6 * epoch_length) as i64),
+    );
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let resharding_height = client.chain.head().unwrap().height;
+
+    // The chain keeps going in the new shard layout.
+    env.test_loop.run_until(
+        |data| {
+            client_handles.iter().all(|handle| {
+                let head = data.get(handle).client.chain.head().unwrap();
+                head.height > resharding_height + 2 * epoch_length
+            })
+        },
+        Duration::seconds((3 * epoch_length) as i64),
+    );
+
+    // Every epoch has the same protocol version, and the shard layout changed
+    // at the epoch of the proposal.
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let epoch_manager = &client.epoch_manager;
+    let mut block_hash = client.chain.head().unwrap().last_block_hash;
+    loop {
+        let header = client.chain.get_block_header(&block_hash).unwrap();
+        if header.is_genesis() {
+            break;
+        }
+        let epoch_info = epoch_manager.get_epoch_info(header.epoch_id()).unwrap();
+        assert_eq!(epoch_info.protocol_version(), PROTOCOL_VERSION);
+        let shard_layout = epoch_manager.get_shard_layout(header.epoch_id()).unwrap();
+        let expected_shard_layout = if epoch_info.epoch_height() >= resharding_epoch_height {
+            &new_shard_layout
+        } else {
+            &base_shard_layout
+        };
+        assert_eq!(&shard_layout, expected_shard_layout, "epoch {}", epoch_info.epoch_height());
+        assert_eq!(header.chunk_mask().len(), shard_layout.num_shards() as usize);
+        block_hash = *header.prev_hash();
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use bytesize::ByteSize;
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain::ChainStoreAccess;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_client::Client;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::epoch_manager::EpochConfigStore;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::state::FlatStateValue;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+use near_store::ShardUId;
+use near_store::adapter::StoreAdapter;
+use near_store::flat::FlatStorageStatus;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+/// Asserts that the flat storage of `shard_uid` at its flat head contains the
+/// same key-values as the trie.
+fn assert_flat_storage_matches_trie(client: &Client, shard_uid: ShardUId) {
+    let flat_storage_manager = client.chain.runtime_adapter.get_flat_storage_manager();
+    let FlatStorageStatus::Ready(status) = flat_storage_manager.get_flat_storage_status(shard_uid)
+    else {
+        panic!("flat storage of {shard_uid} is not ready");
+    };
+    let flat_head = status.flat_head.hash;
+
+    let state_root = *client.chain.get_chunk_extra(&flat_head, &shard_uid).unwrap().state_root();
+    let trie = client
+        .runtime_adapter
+        .get_view_trie_for_shard(shard_uid.shard_id(), &flat_head, state_root)
+        .unwrap();
+    let trie_state =
+        trie.lock_for_iter().iter().unwrap().collect::<Result<HashSet<_>, _>>().unwrap();
+
+    let trie_store = client.chain.chain_store().store().trie_store();
+    let flat_store_state = flat_storage_manager
+        .chunk_view(shard_uid, flat_head)
+        .unwrap()
+        .iter_range(None, None)
+        .map_ok(|(key, value)| {
+            let value = match value {
+                FlatStateValue::Ref(value) => {
+                    trie_store.get(shard_uid, &value.hash).unwrap().to_vec()
+                }
+                FlatStateValue::Inlined(data) => data,
+            };
+            (key, value)
+        })
+        .collect::<Result<HashSet<_>, _>>()
+        .unwrap();
+
+    assert!(!trie_state.is_empty());
+    assert_eq!(trie_state, flat_store_state, "flat storage of {shard_uid} doesn't match the trie");
+}
+
+/// The node is killed in the middle of the flat storage split of the parent
+/// shard. After the restart the split resumes from the last checkpoint, and
+/// the children end up with the same state as their tries.
+#[test]
+#[cfg_attr(not(feature = "test_features"), ignore)]
+fn test_resharding_resumes_flat_storage_split_after_restart() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> =
+        (0..100).map(|i| format!("account{i:08}").parse().unwrap()).collect();
+    let base_shard_layout = ShardLayout::multi_shard(3, 3);
+    let epoch_length = 6;
+    let chunk_producer = "cp0";
+    let validators_spec = ValidatorsSpec::desired_roles(&[chunk_producer], &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION - 1)
+        .validators_spec(validators_spec)
+        .shard_layout(base_shard_layout.clone())
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, ONE_NEAR)
+        .build();
+
+    // All the accounts belong to the first shard, which is split in the middle.
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_epoch_config =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account);
+    let parent_shard_uid = base_shard_layout.account_id_to_shard_uid(&boundary_account);
+    let children_shard_uids = new_epoch_config
+        .shard_layout
+        .get_children_shards_uids(parent_shard_uid.shard_id())
+        .unwrap();
+    let epoch_configs = vec![
+        (genesis.config.protocol_version, Arc::new(base_epoch_config)),
+        (genesis.config.protocol_version + 1, Arc::new(new_epoch_config)),
+    ];
+    let epoch_config_store = EpochConfigStore::test(BTreeMap::from_iter(epoch_configs));
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(vec![chunk_producer.parse().unwrap()])
+        .epoch_config_store(epoch_config_store)
+        // Memtries are loaded from flat storage, which isn't ready for the
+        // children when the node restarts.
+        .load_memtries_for_tracked_shards(false)
+        .config_modifier(|config, _| {
+            // Small batches, so that the split is interrupted halfway.
+            let mut resharding_config = config.resharding_config.get();
+            resharding_config.batch_size = ByteSize::b(100);
+            resharding_config.batch_delay = Duration::ZERO;
+            config.resharding_config.update(resharding_config);
+        })
+        .build()
+        .warmup();
+
+    let client_handle = env.node_datas[0].client_sender.actor_handle();
+    #[cfg(feature = "test_features")]
+    {
+        let client = &mut env.test_loop.data.get_mut(&client_handle).client;
+        client.chain.resharding_manager.flat_storage_resharder.adv_interrupt_split_after_batches =
+            Some(5);
+    }
+
+    // Wait for the split to be interrupted.
+    let flat_store =
+        env.test_loop.data.get(&client_handle).client.chain.chain_store().store().flat_store();
+    env.test_loop.run_until(
+        |data| {
+            let resharder =
+                &data.get(&client_handle).client.chain.resharding_manager.flat_storage_resharder;
+            resharder.resharding_event().is_none()
+                && children_shard_uids.iter().all(|shard_uid| {
+                    flat_store
+                        .get_resharding_progress(*shard_uid)
+                        .unwrap()
+                        .is_some_and(|progress| progress.checkpoint.is_some())
+                })
+        },
+        Duration::seconds((3 * epoch_length) as i64),
+    );
+    assert!(matches!(
+        flat_store.get_flat_storage_status(parent_shard_uid),
+        Ok(FlatStorageStatus::Resharding(_))
+    ));
+
+    let chunk_producer: AccountId = chunk_producer.parse().unwrap();
+    env.restart_node(&chunk_producer);
+    let client_handle =
+        env.get_node_data_by_account_id(&chunk_producer).unwrap().client_sender.actor_handle();
+
+    // The split and the catchup of the children complete after the restart.
+    env.test_loop.run_until(
+        |_| {
+            children_shard_uids.iter().all(|shard_uid| {
+                matches!(
+                    flat_store.get_flat_storage_status(*shard_uid),
+                    Ok(FlatStorageStatus::Ready(_))
+                )
+            })
+        },
+        Duration::seconds((3 * epoch_length) as i64),
+    );
+    env.test_loop.run_for(Duration::seconds(2));
+
+    let client = &env.test_loop.data.get(&client_handle).client;
+    for shard_uid in &children_shard_uids {
+        assert_eq!(flat_store.get_resharding_progress(*shard_uid), Ok(None));
+        assert_flat_storage_matches_trie(client, *shard_uid);
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,117 @@
+//! A shard holding a large synthetic state, with contracts, access keys and
+//! contract storage, is split, and the children hold all of its state.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::GenesisContents;
+use near_chain_configs::test_genesis::{
+    SyntheticStateSpec, TestEpochConfigBuilder, ValidatorsSpec,
+};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::epoch_manager::{EpochConfigStore, ReshardingProposal};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::state_record::StateRecord;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::resharding::execute_money_transfers;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+use crate::utils::sharding::get_memtrie_for_shard;
+
+/// The number of the access key and contract storage records in the state.
+fn count_records<'a>(records: impl Iterator<Item = &'a StateRecord>) -> (usize, usize) {
+    records.fold((0, 0), |(num_access_keys, num_data), record| match record {
+        StateRecord::AccessKey { .. } => (num_access_keys + 1, num_data),
+        StateRecord::Data { .. } => (num_access_keys, num_data + 1),
+        _ => (num_access_keys, num_data),
+    })
+}
+
+#[test]
+fn slow_test_resharding_synthetic_state() {
+    init_test_logger();
+
+    let synthetic_state = SyntheticStateSpec::new("synth", 2000)
+        .access_keys_per_account(2)
+        .contracts(near_test_contracts::ft_contract().to_vec(), 50)
+        .storage_records_per_contract(200)
+        .seed(42);
+    let synthetic_accounts = synthetic_state.account_ids();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(ShardLayout::multi_shard(3, 3))
+        .epoch_length(epoch_length)
+        .add_synthetic_state(synthetic_state)
+        .build();
+    let GenesisContents::Records { records } = &genesis.contents else {
+        unreachable!("the test genesis has records");
+    };
+    let (num_genesis_access_keys, num_genesis_data) = count_records(records.0.iter());
+
+    // All the synthetic accounts are in the first shard, which is split in
+    // the middle.
+    let boundary_account = synthetic_accounts[synthetic_accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_shard_layout =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account).shard_layout;
+    // The genesis epoch has height 1.
+    let resharding_epoch_height = 4;
+    let proposal = ReshardingProposal {
+        epoch_height: resharding_epoch_height,
+        shard_layout: new_shard_layout.clone(),
+    };
+    let epoch_config_store =
+        EpochConfigStore::test_single_version(PROTOCOL_VERSION, base_epoch_config)
+            .with_resharding_proposal(PROTOCOL_VERSION, proposal);
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(producers)
+        .epoch_config_store(epoch_config_store)
+        .track_all_shards()
+        .build()
+        .warmup();
+
+    let transfers =
+        execute_money_transfers(synthetic_accounts.iter().step_by(50).cloned().collect());
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let node_datas = env.node_datas.clone();
+    let client_account_id = node_datas[0].account_id.clone();
+    env.test_loop.run_until(
+        |data| {
+            transfers.call(&node_datas, data, client_account_id.clone());
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                let epoch_info = client.epoch_manager.get_epoch_info(&head.epoch_id).unwrap();
+                epoch_info.epoch_height() > resharding_epoch_height
+            })
+        },
+        Duration::seconds((6 * epoch_length) as i64),
+    );
+
+    // The transfers don't change the access keys and the contract storage, so
+    // the shards of the new layout hold exactly the ones of the genesis.
+    let client = &env.test_loop.data.get(&client_handles[0]).client;
+    let tip = client.chain.head().unwrap();
+    let shard_layout = client.epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+    assert_eq!(shard_layout, new_shard_layout);
+    let mut shard_records = vec![];
+    for shard_uid in shard_layout.shard_uids() {
+        let memtrie = get_memtrie_for_shard(client, &shard_uid, &tip.prev_block_hash);
+        for item in memtrie.lock_for_iter().iter().unwrap() {
+            let (key, value) = item.unwrap();
+            shard_records.extend(StateRecord::from_raw_key_value(&key, value));
+        }
+    }
+    assert_eq!(count_records(shard_records.iter()), (num_genesis_access_keys, num_genesis_data));
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -2,8 +2,8 @@ use assert_matches::assert_matches;
 use itertools::Itertools;
 use near_async::test_loop::data::TestLoopData;
 use near_async::time::Duration;
-use near_chain_configs::TrackedShardsConfig;
 use near_chain_configs::test_genesis::{TestGenesisBuilder, ValidatorsSpec};
+use near_chain_configs::{GCCategory, GCRetentionConfig, TrackedShardsConfig};
 use near_o11y::testonly::init_test_logger;
 use near_primitives::action::{GlobalContractDeployMode, GlobalContractIdentifier};
 use near_primitives::epoch_manager::EpochConfigStore;
@@ -18,6 +18,9 @@ use std::sync::Arc;
 use crate::setup::builder::TestLoopBuilder;
 use crate::setup::drop_condition::DropCondition;
 use crate::setup::env::TestLoopEnv;
+use crate::utils::invariants::{
+    ReceiptConservation, StateSanity, balance_conservation, chunk_inclusion,
+};
 use crate::utils::loop_action::{LoopAction, LoopActionStatus};
 use crate::utils::receipts::{
     ReceiptKind, check_receipts_presence_after_resharding_block,
@@ -26,12 +29,16 @@ use crate::utils::receipts::{
 #[cfg(feature = "test_features")]
 use crate::utils::resharding::fork_before_resharding_block;
 use crate::utils::resharding::{
-    TrackedShardSchedule, call_burn_gas_contract, call_promise_yield, check_state_cleanup,
-    delayed_receipts_repro_missing_trie_value, execute_money_transfers, execute_storage_operations,
-    promise_yield_repro_missing_trie_value, send_large_cross_shard_receipts,
+    TrackedShardSchedule, access_key_nonces_across_resharding, call_burn_gas_contract,
+    call_promise_yield, check_children_state_sync_headers, check_resharding_parent_shards_gc,
+    check_shard_lineage, check_state_cleanup, delayed_receipts_repro_missing_trie_value,
+    execute_money_transfers, execute_storage_operations, promise_yield_repro_missing_trie_value,
+    send_large_cross_shard_receipts, snapshot_during_flat_storage_split,
     temporary_account_during_resharding,
 };
-use crate::utils::setups::{derive_new_epoch_config_from_boundary, two_upgrades_voting_schedule};
+use crate::utils::setups::{
+    consecutive_upgrades_voting_schedule, derive_new_epoch_config_from_boundary,
+};
 use crate::utils::sharding::{
     get_shards_will_care_about, get_tracked_shards, print_and_assert_shard_accounts,
 };
@@ -39,7 +46,7 @@ use crate::utils::transactions::{
     check_txs, create_account, deploy_contract, deploy_global_contract, get_smallest_height_head,
     use_global_contract,
 };
-use crate::utils::trie_sanity::{TrieSanityCheck, check_state_shard_uid_mapping_after_resharding};
+use crate::utils::trie_sanity::check_state_shard_uid_mapping_after_resharding;
 use crate::utils::{ONE_NEAR, TGAS};
 use near_parameters::{RuntimeConfig, RuntimeConfigStore, vm};
 
@@ -156,6 +163,15 @@ struct TestReshardingParameters {
     num_epochs_to_wait: u64,
     /// If set, proceed with second resharding using the provided boundary account.
     second_resharding_boundary_account: Option<AccountId>,
+    /// Number of epochs between the first and the second resharding. The
+    /// boundary account of the second resharding can be in a child of the
+    /// first one, in which case the child must be split only after its own
+    /// resharding is completed.
+    epochs_between_reshardings: u64,
+    /// Garbage collection window length of all the clients.
+    gc_num_epochs_to_keep: u64,
+    /// Shorter garbage collection windows of some kinds of data.
+    gc_retention: BTreeMap<GCCategory, GCRetentionConfig>,
 }
 
 impl TestReshardingParametersBuilder {
@@ -163,7 +179,8 @@ impl TestReshardingParametersBuilder {
         // Give enough time for GC to kick in after resharding.
         let num_epochs_to_wait =
             self.num_epochs_to_wait.unwrap_or(DEFAULT_TESTLOOP_NUM_EPOCHS_TO_WAIT);
-        assert!(GC_NUM_EPOCHS_TO_KEEP + 3 < num_epochs_to_wait);
+        let gc_num_epochs_to_keep = self.gc_num_epochs_to_keep.unwrap_or(GC_NUM_EPOCHS_TO_KEEP);
+        assert!(gc_num_epochs_to_keep + 3 < num_epochs_to_wait);
         let epoch_length = self.epoch_length.unwrap_or(DEFAULT_EPOCH_LENGTH);
         let tracked_shard_schedule = self.tracked_shard_schedule.unwrap_or(None);
 
@@ -301,6 +318,9 @@ impl TestReshardingParametersBuilder {
             second_resharding_boundary_account: self
                 .second_resharding_boundary_account
                 .unwrap_or(None),
+            epochs_between_reshardings: self.epochs_between_reshardings.unwrap_or(1),
+            gc_num_epochs_to_keep,
+            gc_retention: self.gc_retention.unwrap_or_default(),
         }
     }
 
@@ -411,12 +431,14 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
     init_test_logger();
     let mut builder = TestLoopBuilder::new();
     let tracked_shard_schedule = params.tracked_shard_schedule.clone();
+    let gc_retention = params.gc_retention.clone();
 
     builder = builder.config_modifier(move |config, client_index| {
         // Adjust the resharding configuration to make the tests faster.
         let mut resharding_config = config.resharding_config.get();
         resharding_config.batch_delay = Duration::milliseconds(1);
         config.resharding_config.update(resharding_config);
+        config.gc.gc_retention = gc_retention.clone();
         // Set the tracked shard schedule if specified for the client at the given index.
         if let Some(tracked_shard_schedule) = &tracked_shard_schedule {
             if client_index == tracked_shard_schedule.client_index {
@@ -466,12 +488,22 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
         .build();
 
     if let Some(second_resharding_boundary_account) = &params.second_resharding_boundary_account {
+        // The shard layout doesn't change in the protocol upgrades between the
+        // two reshardings.
+        let epochs_between_reshardings = params.epochs_between_reshardings as u32;
+        for i in 1..epochs_between_reshardings {
+            epoch_configs.push((base_protocol_version + 1 + i, Arc::new(epoch_config.clone())));
+        }
         let second_resharding_epoch_config = derive_new_epoch_config_from_boundary(
             &epoch_config,
             second_resharding_boundary_account,
         );
-        epoch_configs.push((base_protocol_version + 2, Arc::new(second_resharding_epoch_config)));
-        let upgrade_schedule = two_upgrades_voting_schedule(base_protocol_version + 2);
+        let target_protocol_version = base_protocol_version + 1 + epochs_between_reshardings;
+        epoch_configs.push((target_protocol_version, Arc::new(second_resharding_epoch_config)));
+        let upgrade_schedule = consecutive_upgrades_voting_schedule(
+            target_protocol_version,
+            epochs_between_reshardings + 1,
+        );
         builder = builder.protocol_upgrade_schedule(upgrade_schedule);
         new_boundary_account = second_resharding_boundary_account.clone();
     }
@@ -482,8 +514,12 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
     } else {
         assert_eq!(expected_num_shards, initial_num_shards + 1);
     }
-    let parent_shard_uid =
-        base_epoch_config.shard_layout.account_id_to_shard_uid(&new_boundary_account);
+    // The parent of the last resharding, which is a child of the first
+    // resharding if the same shard is split twice.
+    let parent_shard_uid = epoch_configs[epoch_configs.len() - 2]
+        .1
+        .shard_layout
+        .account_id_to_shard_uid(&new_boundary_account);
     let epoch_config_store = EpochConfigStore::test(BTreeMap::from_iter(epoch_configs));
 
     if params.track_all_shards {
@@ -499,7 +535,7 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
         if params.short_yield_timeout {
             let mut wasm_config = vm::Config::clone(&runtime_config.wasm_config);
             // Assuming the promise yield is sent at h=9 and resharding happens at h=13, let's set
-            // the timeout to trigger at h=14.
+            // the timeout to expire at h=14.
             wasm_config.limit_config.yield_timeout_length_in_blocks = 5;
             runtime_config.wasm_config = Arc::new(wasm_config);
         }
@@ -516,7 +552,7 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
         .clients(params.clients)
         .archival_clients(params.archivals.iter().cloned().collect())
         .load_memtries_for_tracked_shards(params.load_memtries_for_tracked_shards)
-        .gc_num_epochs_to_keep(GC_NUM_EPOCHS_TO_KEEP)
+        .gc_num_epochs_to_keep(params.gc_num_epochs_to_keep)
         .build()
         .drop(DropCondition::ProtocolUpgradeChunkRange(
             base_protocol_version + 1,
@@ -576,8 +612,18 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
 
     let clients =
         client_handles.iter().map(|handle| &env.test_loop.data.get(handle).client).collect_vec();
-    let mut trie_sanity_check =
-        TrieSanityCheck::new(&clients, params.load_memtries_for_tracked_shards);
+    let state_sanity = StateSanity::new(
+        &clients,
+        params.load_memtries_for_tracked_shards,
+        expected_num_shards,
+        client_account_id.clone(),
+    );
+    env.add_invariant("state sanity", state_sanity);
+    env.add_invariant("balance conservation", balance_conservation);
+    env.add_invariant("receipt conservation", ReceiptConservation::new(2 * params.epoch_length));
+    if params.all_chunks_expected && params.chunk_ranges_to_drop.is_empty() {
+        env.add_invariant("chunk inclusion", chunk_inclusion);
+    }
 
     let num_epochs_to_wait = params.num_epochs_to_wait;
     let latest_block_height = Cell::new(0u64);
@@ -630,13 +676,6 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
             );
         }
 
-        // Check that all chunks are included.
-        if params.all_chunks_expected && params.chunk_ranges_to_drop.is_empty() {
-            assert!(block_header.chunk_mask().iter().all(|chunk_bit| *chunk_bit));
-        }
-
-        trie_sanity_check.assert_state_sanity(&clients, expected_num_shards);
-
         let epoch_height =
             client.epoch_manager.get_epoch_height_from_prev_block(&tip.prev_block_hash).unwrap();
 
@@ -648,20 +687,69 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
 
         // Return false if we have not resharded yet.
         if epoch_height_after_resharding.get().is_none() {
-            assert!(epoch_height < 5);
+            assert!(epoch_height < 4 + params.epochs_between_reshardings);
             if current_num_shards != expected_num_shards {
                 return false;
             }
             // Just resharded.
             resharding_block_hash.set(Some(tip.prev_block_hash));
+            // All the clients must switch to the new layout right after the
+            // resharding block, and split the parent from the state of its last
+            // applied chunk, even if the chunks of the parent shard were missing
+            // in the last blocks of the epoch.
+            for client in &clients {
+                let resharding_header =
+                    client.chain.get_block_header(&tip.prev_block_hash).unwrap();
+                let prev_shard_layout =
+                    client.epoch_manager.get_shard_layout(resharding_header.epoch_id()).unwrap();
+                assert_ne!(prev_shard_layout, shard_layout);
+                assert!(shard_layout.can_follow(&prev_shard_layout));
+                for parent_shard_uid in get_tracked_shards(client, &tip.prev_block_hash) {
+                    let parent_shard_id = parent_shard_uid.shard_id();
+                    let children = shard_layout.get_children_shards_uids(parent_shard_id).unwrap();
+                    if children == vec![parent_shard_uid] {
+                        continue;
+                    }
+                    let parent_shard_index =
+                        prev_shard_layout.get_shard_index(parent_shard_id).unwrap();
+                    let mut last_chunk_block_hash = tip.prev_block_hash;
+                    loop {
+                        let header = client.chain.get_block_header(&last_chunk_block_hash).unwrap();
+                        if header.chunk_mask()[parent_shard_index] {
+                            break;
+                        }
+                        last_chunk_block_hash = *header.prev_hash();
+                    }
+                    let last_chunk_extra = client
+                        .chain
+                        .get_chunk_extra(&last_chunk_block_hash, &parent_shard_uid)
+                        .unwrap();
+                    let parent_chunk_extra = client
+                        .chain
+                        .get_chunk_extra(&tip.prev_block_hash, &parent_shard_uid)
+                        .unwrap();
+                    assert_eq!(parent_chunk_extra.state_root(), last_chunk_extra.state_root());
+                    for child_shard_uid in children {
+                        client
+                            .chain
+                            .get_chunk_extra(&tip.prev_block_hash, &child_shard_uid)
+                            .unwrap();
+                    }
+                }
+            }
             epoch_height_after_resharding.set(Some(epoch_height));
             // Assert that we will have a chance for gc to kick in before the test is over.
             assert!(epoch_height + GC_NUM_EPOCHS_TO_KEEP < num_epochs_to_wait);
             println!("State after resharding:");
             print_and_assert_shard_accounts(&clients, &tip);
-            // In case of second resharding, we want it 1 epoch after the first resharding.
+            // In case of second resharding, we want it right after the planned
+            // number of epochs since the first resharding.
             if params.second_resharding_boundary_account.is_some() {
-                assert_eq!(epoch_height, epoch_height_after_first_resharding.get().unwrap() + 1);
+                assert_eq!(
+                    epoch_height,
+                    epoch_height_after_first_resharding.get().unwrap()
+                        + params.epochs_between_reshardings
+                );
             }
         }
 
@@ -688,9 +776,6 @@ fn test_resharding_v3_base(params: TestReshardingParameters) {
         // Give enough time to produce `num_epochs_to_wait` epochs.
         Duration::seconds((num_epochs_to_wait * params.epoch_length) as i64),
     );
-    let client = &env.test_loop.data.get(&client_handles[client_index]).client;
-    trie_sanity_check.check_epochs(client);
-
     env.shutdown_and_drain_remaining_events(Duration::seconds(20));
 }
 
@@ -699,6 +784,36 @@ fn slow_test_resharding_v3() {
     test_resharding_v3_base(TestReshardingParametersBuilder::default().build());
 }
 
+/// The state of the resharding block is collected before the block itself,
+/// and the receipts of the shards that a node doesn't track are collected
+/// before the ones of the tracked shards.
+#[test]
+fn slow_test_resharding_v3_gc_retention() {
+    let gc_retention = BTreeMap::from([
+        (
+            GCCategory::State,
+            GCRetentionConfig {
+                num_epochs_to_keep: GC_NUM_EPOCHS_TO_KEEP,
+                untracked_shards_num_epochs_to_keep: None,
+            },
+        ),
+        (
+            GCCategory::Receipts,
+            GCRetentionConfig {
+                num_epochs_to_keep: GC_NUM_EPOCHS_TO_KEEP + 1,
+                untracked_shards_num_epochs_to_keep: Some(GC_NUM_EPOCHS_TO_KEEP),
+            },
+        ),
+    ]);
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .gc_num_epochs_to_keep(GC_NUM_EPOCHS_TO_KEEP + 2)
+            .gc_retention(gc_retention)
+            .num_epochs_to_wait(DEFAULT_TESTLOOP_NUM_EPOCHS_TO_WAIT + 2)
+            .build(),
+    );
+}
+
 #[test]
 fn slow_test_resharding_v3_two_independent_splits() {
     let second_resharding_boundary_account = "account2".parse().unwrap();
@@ -712,6 +827,41 @@ fn slow_test_resharding_v3_two_independent_splits() {
     );
 }
 
+/// Splits a shard and, two epochs later, one of its children.
+#[test]
+fn slow_test_resharding_v3_split_child_shard() {
+    // The boundary of the first resharding is `NEW_BOUNDARY_ACCOUNT`, so this
+    // account is in its left child.
+    let second_resharding_boundary_account = "account4".parse().unwrap();
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .second_resharding_boundary_account(Some(second_resharding_boundary_account))
+            .epochs_between_reshardings(2)
+            .num_epochs_to_wait(DEFAULT_TESTLOOP_NUM_EPOCHS_TO_WAIT + 2)
+            // TODO(resharding) Adjust temporary account test to work with two reshardings.
+            .disable_temporary_account_test(true)
+            .epoch_length(TWO_RESHARDINGS_EPOCH_LENGTH)
+            .build(),
+    );
+}
+
+/// Splits a shard and, in the next epoch, one of its children, so that the
+/// second split may have to wait for the flat storage of its parent.
+#[test]
+fn slow_test_resharding_v3_split_child_shard_in_consecutive_epochs() {
+    let second_resharding_boundary_account = "account4".parse().unwrap();
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .second_resharding_boundary_account(Some(second_resharding_boundary_account))
+            .epochs_between_reshardings(1)
+            .num_epochs_to_wait(DEFAULT_TESTLOOP_NUM_EPOCHS_TO_WAIT + 1)
+            // TODO(resharding) Adjust temporary account test to work with two reshardings.
+            .disable_temporary_account_test(true)
+            .epoch_length(TWO_RESHARDINGS_EPOCH_LENGTH)
+            .build(),
+    );
+}
+
 // Takes a sequence of shard ids to track in consecutive epochs,
 // repeats the last element `repeat_last_elem_count` times,
 // and maps each element: |id| -> vec![id], to the format required by `TrackedShardSchedule`.
@@ -952,12 +1102,47 @@ fn slow_test_resharding_v3_sync_child() {
     );
 }
 
+// Same as `slow_test_resharding_v3_sync_child`, but the client also builds and validates the state
+// sync headers of both children for the sync hash of the first post-resharding epoch, with some
+// chunks missing right after resharding.
+#[test]
+fn slow_test_resharding_v3_sync_child_headers() {
+    let account_in_stable_shard: AccountId = "account0".parse().unwrap();
+    let split_boundary_account: AccountId = NEW_BOUNDARY_ACCOUNT.parse().unwrap();
+    let base_shard_layout = get_base_shard_layout(DEFAULT_SHARD_LAYOUT_VERSION);
+    let new_shard_layout =
+        ShardLayout::derive_shard_layout(&base_shard_layout, split_boundary_account.clone());
+    let child_shard_id = new_shard_layout.account_id_to_shard_id(&split_boundary_account);
+    let unrelated_shard_id = new_shard_layout.account_id_to_shard_id(&account_in_stable_shard);
+
+    let tracked_shard_sequence =
+        vec![unrelated_shard_id, unrelated_shard_id, unrelated_shard_id, child_shard_id];
+    let num_clients = 8;
+    let num_epochs_to_wait = DEFAULT_TESTLOOP_NUM_EPOCHS_TO_WAIT;
+    let tracked_shard_schedule = TrackedShardSchedule {
+        client_index: (num_clients - 1) as usize,
+        schedule: shard_sequence_to_schedule(tracked_shard_sequence, num_epochs_to_wait),
+    };
+    let chunk_ranges_to_drop = HashMap::from([(2, 0..2), (3, 0..1)]);
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .num_clients(num_clients)
+            .tracked_shard_schedule(Some(tracked_shard_schedule.clone()))
+            .chunk_ranges_to_drop(chunk_ranges_to_drop)
+            .add_loop_action(check_children_state_sync_headers())
+            .add_loop_action(check_state_cleanup(tracked_shard_schedule, num_epochs_to_wait, false))
+            .build(),
+    );
+}
+
 #[test]
 fn slow_test_resharding_v3_track_all_shards() {
     test_resharding_v3_base(
         TestReshardingParametersBuilder::default()
             .track_all_shards(true)
             .all_chunks_expected(true)
+            .add_loop_action(check_shard_lineage())
+            .add_loop_action(check_resharding_parent_shards_gc())
             .build(),
     );
 }
@@ -1005,6 +1190,20 @@ fn slow_test_resharding_v3_drop_chunks_all() {
     );
 }
 
+/// Drops the chunks of the parent shard for the second half of the epoch, up to
+/// the resharding block, and the first chunks of its children. The children
+/// must be split from the state of the last chunk applied for the parent.
+#[test]
+fn slow_test_resharding_v3_drop_parent_chunks_until_resharding() {
+    let chunk_ranges_to_drop = HashMap::from([(2, -5..2), (3, 0..2)]);
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .chunk_ranges_to_drop(chunk_ranges_to_drop)
+            .epoch_length(INCREASED_EPOCH_LENGTH)
+            .build(),
+    );
+}
+
 #[test]
 #[cfg(feature = "test_features")]
 fn slow_test_resharding_v3_resharding_block_in_fork() {
@@ -1114,6 +1313,22 @@ fn slow_test_resharding_v3_shard_shuffling_intense() {
     test_resharding_v3_base(params);
 }
 
+/// Checks that the nonces of an account which changes shards are validated the
+/// same way before and after resharding, including for transactions signed
+/// before resharding and included after it.
+#[test]
+fn slow_test_resharding_v3_access_key_nonces() {
+    let account_in_right_child: AccountId = "account6".parse().unwrap();
+    let receiver_account: AccountId = "account1".parse().unwrap();
+    let params = TestReshardingParametersBuilder::default()
+        .add_loop_action(access_key_nonces_across_resharding(
+            account_in_right_child,
+            receiver_account,
+        ))
+        .build();
+    test_resharding_v3_base(params);
+}
+
 /// Executes storage operations at every block height.
 /// In particular, checks that storage gas costs are computed correctly during
 /// resharding. Caught a bug with invalid storage costs computed during flat
@@ -1411,10 +1626,8 @@ fn slow_test_resharding_v3_load_memtrie_v2() {
 #[test]
 #[cfg_attr(not(feature = "test_features"), ignore)]
 fn slow_test_resharding_v3_slower_post_processing_tasks() {
-    // When there's a resharding task delay and single-shard tracking, the delay might be pushed out
-    // even further because the resharding task might have to wait for the state snapshot to be made
-    // before it can proceed, which might mean that flat storage won't be ready for the child shard for a whole epoch.
-    // So we extend the epoch length a bit in this case.
+    // When there's a resharding task delay and single-shard tracking, the flat storage of the child
+    // shard is ready only late in the epoch. So we extend the epoch length a bit in this case.
     test_resharding_v3_base(
         TestReshardingParametersBuilder::default()
             .delay_flat_state_resharding(2)
@@ -1423,6 +1636,20 @@ fn slow_test_resharding_v3_slower_post_processing_tasks() {
     );
 }
 
+/// The state snapshot of the new epoch is requested before the delayed flat storage split starts.
+/// The snapshot doesn't wait for the split, and the resharding doesn't wait for the snapshot.
+#[test]
+#[cfg_attr(not(feature = "test_features"), ignore)]
+fn slow_test_resharding_v3_state_snapshot_during_flat_storage_split() {
+    test_resharding_v3_base(
+        TestReshardingParametersBuilder::default()
+            .delay_flat_state_resharding(4)
+            .epoch_length(INCREASED_EPOCH_LENGTH)
+            .add_loop_action(snapshot_during_flat_storage_split())
+            .build(),
+    );
+}
+
 #[test]
 #[cfg_attr(not(feature = "test_features"), ignore)]
 fn slow_test_resharding_v3_shard_shuffling_slower_post_processing_tasks() {
@@ -0,0 +1,96 @@
+//! The state witnesses stay within the storage proof soft limit across a
+//! shard split, although the witness of the first chunk of a child also holds
+//! the proof of the split.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_epoch_manager::EpochManagerAdapter;
+use near_o11y::testonly::init_test_logger;
+use near_parameters::{RuntimeConfig, RuntimeConfigStore};
+use near_primitives::epoch_manager::{EpochConfigStore, ReshardingProposal};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::AccountId;
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::invariants::WitnessSizeLimit;
+use crate::utils::resharding::execute_money_transfers;
+use crate::utils::setups::derive_new_epoch_config_from_boundary;
+
+/// Low enough for the chunks of the busy shards to defer receipts.
+const MAIN_STORAGE_PROOF_SIZE_SOFT_LIMIT: usize = 10_000;
+/// The most a single transfer adds to the proof after the soft limit is hit.
+const RECEIPT_PROOF_SIZE_SLACK: usize = 10_000;
+
+// TODO(resharding): run on stable once `ReshardingWitnessSizeLimit` is stabilized.
+#[test]
+#[cfg_attr(not(feature = "nightly"), ignore)]
+fn test_resharding_witness_size_limit() {
+    init_test_logger();
+
+    let accounts: Vec<AccountId> =
+        (0..100).map(|i| format!("account{i:02}").parse().unwrap()).collect();
+    let producers: Vec<AccountId> = (0..4).map(|i| format!("cp{i}").parse().unwrap()).collect();
+    let producers_str = producers.iter().map(|account| account.as_str()).collect_vec();
+    let epoch_length = 6;
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .protocol_version(PROTOCOL_VERSION)
+        .validators_spec(ValidatorsSpec::desired_roles(&producers_str, &[]))
+        .shard_layout(ShardLayout::multi_shard(3, 3))
+        .epoch_length(epoch_length)
+        .add_user_accounts_simple(&accounts, 1_000_000 * ONE_NEAR)
+        .build();
+
+    let boundary_account = accounts[accounts.len() / 2].clone();
+    let base_epoch_config = TestEpochConfigBuilder::from_genesis(&genesis).build();
+    let new_shard_layout =
+        derive_new_epoch_config_from_boundary(&base_epoch_config, &boundary_account).shard_layout;
+    // The genesis epoch has height 1.
+    let resharding_epoch_height = 4;
+    let proposal = ReshardingProposal {
+        epoch_height: resharding_epoch_height,
+        shard_layout: new_shard_layout,
+    };
+    let epoch_config_store =
+        EpochConfigStore::test_single_version(PROTOCOL_VERSION, base_epoch_config)
+            .with_resharding_proposal(PROTOCOL_VERSION, proposal);
+
+    let mut runtime_config = RuntimeConfig::test();
+    runtime_config.witness_config.main_storage_proof_size_soft_limit =
+        MAIN_STORAGE_PROOF_SIZE_SOFT_LIMIT;
+
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .clients(producers)
+        .epoch_config_store(epoch_config_store)
+        .runtime_config_store(RuntimeConfigStore::with_one_config(runtime_config))
+        .config_modifier(|config, _| config.save_latest_witnesses = true)
+        .build()
+        .warmup();
+    env.add_invariant(
+        "witness size limit",
+        WitnessSizeLimit::new(MAIN_STORAGE_PROOF_SIZE_SOFT_LIMIT, RECEIPT_PROOF_SIZE_SLACK),
+    );
+
+    let transfers = execute_money_transfers(accounts);
+    let client_handles =
+        env.node_datas.iter().map(|data| data.client_sender.actor_handle()).collect_vec();
+    let node_datas = env.node_datas.clone();
+    let client_account_id = node_datas[0].account_id.clone();
+    env.test_loop.run_until(
+        |data| {
+            transfers.call(&node_datas, data, client_account_id.clone());
+            client_handles.iter().all(|handle| {
+                let client = &data.get(handle).client;
+                let head = client.chain.head().unwrap();
+                let epoch_info = client.epoch_manager.get_epoch_info(&head.epoch_id).unwrap();
+                epoch_info.epoch_height() > resharding_epoch_height
+            })
+        },
+        Duration::seconds((6 * epoch_length) as i64),
+    );
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,84 @@
+//! The nodes with `save_receipt_execution_profiles` enabled serve the
+//! execution profiles of the receipts of a transaction.
+
+use near_async::messaging::Handler;
+use near_async::time::Duration;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_client::GetTxExecutionProfile;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::test_utils::create_user_test_signer;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::transactions::{execute_tx, get_next_nonce, get_shared_block_hash, run_tx};
+use crate::utils::{ONE_NEAR, TGAS};
+
+#[test]
+fn test_tx_execution_profile() {
+    init_test_logger();
+
+    let account: AccountId = "account0".parse().unwrap();
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .validators_spec(ValidatorsSpec::desired_roles(&["account0"], &[]))
+        .add_user_account_simple(account.clone(), 1_000_000 * ONE_NEAR)
+        .build();
+    let epoch_config_store =
+        TestEpochConfigBuilder::from_genesis(&genesis).build_store_for_genesis_protocol_version();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(vec![account.clone()])
+        .config_modifier(|config, _| config.save_receipt_execution_profiles = true)
+        .build()
+        .warmup();
+
+    let signer = create_user_test_signer(&account);
+    let nonce = get_next_nonce(&env.test_loop.data, &env.node_datas, &account);
+    let deploy_tx = SignedTransaction::deploy_contract(
+        nonce,
+        &account,
+        near_test_contracts::rs_contract().into(),
+        &signer,
+        get_shared_block_hash(&env.node_datas, &env.test_loop.data),
+    );
+    run_tx(&mut env.test_loop, &account, deploy_tx, &env.node_datas, Duration::seconds(5));
+
+    let call_tx = SignedTransaction::call(
+        nonce + 1,
+        account.clone(),
+        account.clone(),
+        &signer,
+        0,
+        "ext_sha256".into(),
+        b"some data".to_vec(),
+        300 * TGAS,
+        get_shared_block_hash(&env.node_datas, &env.test_loop.data),
+    );
+    let call_tx_hash = call_tx.get_hash();
+    let outcome =
+        execute_tx(&mut env.test_loop, &account, call_tx, &env.node_datas, Duration::seconds(5))
+            .unwrap();
+
+    let view_client =
+        env.test_loop.data.get_mut(&env.node_datas[0].view_client_sender.actor_handle());
+    let profiles = view_client.handle(GetTxExecutionProfile { tx_hash: call_tx_hash }).unwrap();
+    assert_eq!(profiles.len(), outcome.receipts_outcome.len());
+    for (profile, receipt_outcome) in profiles.iter().zip(&outcome.receipts_outcome) {
+        assert_eq!(profile.receipt_id, receipt_outcome.id);
+        assert_eq!(profile.block_hash, receipt_outcome.block_hash);
+        assert_eq!(profile.gas_burnt, receipt_outcome.outcome.gas_burnt);
+        assert_eq!(profile.outgoing_receipts, receipt_outcome.outcome.receipt_ids);
+    }
+    let call_profile = &profiles[0];
+    assert_eq!(call_profile.executor_id, account);
+    for cost in ["WASM_INSTRUCTION", "SHA256_BASE"] {
+        assert!(
+            call_profile.gas_profile.iter().any(|gas| gas.cost == cost && gas.gas_used > 0),
+            "{cost} missing from {:?}",
+            call_profile.gas_profile
+        );
+    }
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,95 @@
+//! The RPC node buffers the transactions which the chunk producers can't take
+//! at the current height and forwards them at the next heights, even if no new
+//! transactions arrive in the meantime.
+
+use itertools::Itertools;
+use near_async::time::Duration;
+use near_chain_configs::TxForwardingConfig;
+use near_chain_configs::test_genesis::{TestEpochConfigBuilder, ValidatorsSpec};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::test_utils::create_user_test_signer;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::AccountId;
+
+use crate::setup::builder::TestLoopBuilder;
+use crate::utils::ONE_NEAR;
+use crate::utils::transactions::{check_txs, get_next_nonce, get_shared_block_hash, submit_tx};
+
+#[test]
+fn test_tx_forwarding_flushes_buffered_txs() {
+    init_test_logger();
+    let validators: Vec<AccountId> =
+        (0..2).map(|i| format!("validator{i}").parse().unwrap()).collect();
+    let rpc: AccountId = "rpc".parse().unwrap();
+    let users: Vec<AccountId> = (0..6).map(|i| format!("account{i}").parse().unwrap()).collect();
+    let clients = validators.iter().chain([&rpc]).cloned().collect_vec();
+
+    let validators_spec =
+        ValidatorsSpec::desired_roles(&validators.iter().map(|t| t.as_str()).collect_vec(), &[]);
+    let genesis = TestLoopBuilder::new_genesis_builder()
+        .epoch_length(100)
+        .validators_spec(validators_spec)
+        .add_user_accounts_simple(&clients.iter().chain(&users).cloned().collect_vec(), ONE_NEAR)
+        .build();
+    let epoch_config_store =
+        TestEpochConfigBuilder::from_genesis(&genesis).build_store_for_genesis_protocol_version();
+    let mut env = TestLoopBuilder::new()
+        .genesis(genesis)
+        .epoch_config_store(epoch_config_store)
+        .clients(clients)
+        .config_modifier_for(&rpc, |config| {
+            config.tx_forwarding = TxForwardingConfig {
+                enabled: true,
+                max_txs_per_height: 1,
+                ..TxForwardingConfig::default()
+            };
+        })
+        .build()
+        .warmup();
+
+    // Submit all the transactions at once. The chunk producers can only take
+    // one of them per height, so the rest is buffered by the RPC node, and no
+    // other transaction arrives afterwards to trigger their forwarding.
+    let block_hash = get_shared_block_hash(&env.node_datas, &env.test_loop.data);
+    let tx_hashes = users
+        .iter()
+        .map(|user| {
+            let tx = SignedTransaction::send_money(
+                get_next_nonce(&env.test_loop.data, &env.node_datas, user),
+                user.clone(),
+                validators[0].clone(),
+                &create_user_test_signer(user),
+                ONE_NEAR / 10,
+                block_hash,
+            );
+            let tx_hash = tx.get_hash();
+            submit_tx(&env.node_datas, &rpc, tx);
+            tx_hash
+        })
+        .collect_vec();
+
+    let client_handle = env.node_datas[0].client_sender.actor_handle();
+    env.test_loop.run_until(
+        |data| {
+            let chain = &data.get(&client_handle).client.chain;
+            tx_hashes.iter().all(|tx_hash| chain.get_partial_transaction_result(tx_hash).is_ok())
+        },
+        Duration::seconds(10),
+    );
+    check_txs(&env.test_loop.data, &env.node_datas, &validators[0], &tx_hashes);
+
+    // The transactions were forwarded across several heights.
+    let client = &env.test_loop.data.get(&client_handle).client;
+    let inclusion_heights = tx_hashes
+        .iter()
+        .map(|tx_hash| {
+            let outcome = client.chain.get_partial_transaction_result(tx_hash).unwrap();
+            let block_hash = outcome.transaction_outcome.block_hash;
+            client.chain.get_block_header(&block_hash).unwrap().height()
+        })
+        .unique()
+        .count();
+    assert!(inclusion_heights > 1, "the transactions were not rate limited");
+
+    env.shutdown_and_drain_remaining_events(Duration::seconds(20));
+}
@@ -0,0 +1,500 @@
+//! Invariants checked by the test loop on every block, see
+//! `TestLoopEnv::add_invariant`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+
+use itertools::Itertools;
+use near_async::test_loop::data::{TestLoopData, TestLoopDataHandle};
+use near_chain::ChainStoreAccess;
+use near_chain::types::Tip;
+use near_client::Client;
+use near_client::client_actor::ClientActorInner;
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::block::{Block, MaybeNew};
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::state::PartialState;
+use near_primitives::state_record::StateRecord;
+use near_primitives::stateless_validation::state_witness::ChunkStateWitness;
+use near_primitives::trie_key::col;
+use near_primitives::types::{
+    AccountId, Balance, BlockHeight, BlockHeightDelta, NumShards, ShardId,
+};
+
+use super::sharding::client_tracking_shard;
+use super::transactions::get_smallest_height_head;
+use super::trie_sanity::TrieSanityCheck;
+
+/// A property of the chain that has to hold at every block. A violation is
+/// reported by panicking, e.g. with `assert!`.
+pub trait Invariant {
+    /// Checks the invariant when the nodes reach the new head `tip`, the head
+    /// of the node with the smallest height.
+    fn check_block(&mut self, clients: &[&Client], tip: &Tip);
+
+    /// Checks the invariant once the test is over, e.g. that the checks which
+    /// can only be done at some blocks were done at some point.
+    fn check_end(&mut self, _clients: &[&Client]) {}
+}
+
+impl<F: FnMut(&[&Client], &Tip)> Invariant for F {
+    fn check_block(&mut self, clients: &[&Client], tip: &Tip) {
+        self(clients, tip)
+    }
+}
+
+/// The invariants registered by the test and the nodes they are checked on.
+/// The running nodes are kept up to date by `setup_client` and
+/// `TestLoopEnv::kill_node`.
+#[derive(Default)]
+pub struct InvariantCheckers {
+    invariants: Vec<(String, Box<dyn Invariant>)>,
+    /// The client of each running node, by node identifier.
+    clients: BTreeMap<String, TestLoopDataHandle<ClientActorInner>>,
+    last_checked_height: Option<BlockHeight>,
+}
+
+impl InvariantCheckers {
+    pub fn is_empty(&self) -> bool {
+        self.invariants.is_empty()
+    }
+
+    pub fn add(&mut self, name: &str, invariant: impl Invariant + 'static) {
+        self.invariants.push((name.to_string(), Box::new(invariant)));
+    }
+
+    pub fn add_client(&mut self, identifier: &str, handle: TestLoopDataHandle<ClientActorInner>) {
+        self.clients.insert(identifier.to_string(), handle);
+    }
+
+    pub fn remove_client(&mut self, identifier: &str) {
+        self.clients.remove(identifier);
+    }
+
+    fn clients<'a>(&self, data: &'a TestLoopData) -> Vec<&'a Client> {
+        self.clients.values().map(|handle| &data.get(handle).client).collect_vec()
+    }
+
+    /// Checks the invariants if the nodes reached a new head since the last
+    /// check. Called on every event of the test loop.
+    pub fn check_block(&mut self, data: &TestLoopData) {
+        if self.invariants.is_empty() || self.clients.is_empty() {
+            return;
+        }
+        let clients = self.clients(data);
+        let tip = get_smallest_height_head(&clients);
+        if self.last_checked_height.is_some_and(|height| height >= tip.height) {
+            return;
+        }
+        self.last_checked_height = Some(tip.height);
+        for (name, invariant) in &mut self.invariants {
+            let result = catch_unwind(AssertUnwindSafe(|| invariant.check_block(&clients, &tip)));
+            report_violation(name, Some(&tip), result);
+        }
+    }
+
+    /// Runs the end of test checks of the invariants.
+    pub fn check_end(&mut self, data: &TestLoopData) {
+        if self.invariants.is_empty() || self.clients.is_empty() {
+            return;
+        }
+        let clients = self.clients(data);
+        for (name, invariant) in &mut self.invariants {
+            let result = catch_unwind(AssertUnwindSafe(|| invariant.check_end(&clients)));
+            report_violation(name, None, result);
+        }
+    }
+}
+
+/// Panics with the name of the violated invariant, and the block it was
+/// violated at, if the check panicked.
+fn report_violation(name: &str, tip: Option<&Tip>, result: std::thread::Result<()>) {
+    let Err(payload) = result else {
+        return;
+    };
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        resume_unwind(payload);
+    };
+    match tip {
+        Some(tip) => panic!(
+            "invariant `{name}` violated at block #{} {}: {message}",
+            tip.height, tip.last_block_hash
+        ),
+        None => panic!("invariant `{name}` violated at the end of the test: {message}"),
+    }
+}
+
+/// The memtries, flat storage and disk tries of the shards tracked by the
+/// nodes hold the same state, see `TrieSanityCheck`. At the end of the test,
+/// every shard has been checked in every past epoch of the chain of the node
+/// of `end_check_account_id`.
+pub struct StateSanity {
+    check: TrieSanityCheck,
+    new_num_shards: NumShards,
+    end_check_account_id: AccountId,
+}
+
+impl StateSanity {
+    pub fn new(
+        clients: &[&Client],
+        load_memtries_for_tracked_shards: bool,
+        new_num_shards: NumShards,
+        end_check_account_id: AccountId,
+    ) -> Self {
+        let check = TrieSanityCheck::new(clients, load_memtries_for_tracked_shards);
+        Self { check, new_num_shards, end_check_account_id }
+    }
+}
+
+impl Invariant for StateSanity {
+    fn check_block(&mut self, clients: &[&Client], _tip: &Tip) {
+        self.check.assert_state_sanity(clients, self.new_num_shards);
+    }
+
+    fn check_end(&mut self, clients: &[&Client]) {
+        let client = clients
+            .iter()
+            .find(|client| {
+                client
+                    .validator_signer
+                    .get()
+                    .is_some_and(|signer| signer.validator_id() == &self.end_check_account_id)
+            })
+            .expect("the node of the end check is not running");
+        self.check.check_epochs(client);
+    }
+}
+
+/// Every block has the chunks of all the shards. The endorsements of the
+/// missing chunks are reported, see `chunk_endorsements_report`.
+pub fn chunk_inclusion(clients: &[&Client], tip: &Tip) {
+    let header = clients[0].chain.get_block_header(&tip.last_block_hash).unwrap();
+    let chunk_mask = header.chunk_mask();
+    if chunk_mask.iter().all(|included| *included) {
+        return;
+    }
+    let shard_layout = clients[0].epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+    let report = chunk_mask
+        .iter()
+        .enumerate()
+        .filter(|(_, included)| !**included)
+        .map(|(shard_index, _)| {
+            let shard_id = shard_layout.get_shard_id(shard_index).unwrap();
+            format!(
+                "shard {shard_id}:\n{}",
+                chunk_endorsements_report(clients, tip.height, shard_id)
+            )
+        })
+        .join("\n");
+    panic!("missing chunks at height {}: {chunk_mask:?}\n{report}", tip.height);
+}
+
+/// What each node knows about the endorsements of the chunk of the shard
+/// created at the height: the endorsements received by the block producers,
+/// the chunk validators they were missing from and when the chunk validators
+/// sent theirs.
+pub fn chunk_endorsements_report(
+    clients: &[&Client],
+    height_created: BlockHeight,
+    shard_id: ShardId,
+) -> String {
+    clients
+        .iter()
+        .filter_map(|client| {
+            let signer = client.validator_signer.get()?;
+            let views = client
+                .chunk_endorsement_tracker
+                .chunk_endorsements_view(height_created)
+                .into_iter()
+                .filter(|view| view.shard_id == shard_id)
+                .collect_vec();
+            (!views.is_empty()).then(|| format!("  {}: {views:?}", signer.validator_id()))
+        })
+        .join("\n")
+}
+
+/// The total supply of every block is the total supply of its previous block,
+/// plus the validator rewards minted at the first block of an epoch, minus the
+/// balance burnt by the chunks included in the block. At the blocks with no
+/// receipts in flight, the total supply is also the sum of the balances of
+/// all the accounts, read from the state of the nodes tracking the shards.
+pub fn balance_conservation(clients: &[&Client], tip: &Tip) {
+    let chain = &clients[0].chain;
+    let block = chain.get_block(&tip.last_block_hash).unwrap();
+    let header = block.header();
+    let Ok(prev_header) = chain.get_block_header(header.prev_hash()) else {
+        return;
+    };
+    let minted = if header.epoch_id() != prev_header.epoch_id() {
+        clients[0].epoch_manager.get_epoch_info(header.epoch_id()).unwrap().minted_amount()
+    } else {
+        0
+    };
+    let burnt: Balance = block
+        .chunks()
+        .iter()
+        .filter_map(|chunk| match chunk {
+            MaybeNew::New(chunk) => Some(chunk.prev_balance_burnt()),
+            MaybeNew::Old(_) => None,
+        })
+        .sum();
+    assert_eq!(
+        header.total_supply(),
+        prev_header.total_supply() + minted - burnt,
+        "total supply is not the previous {} plus the minted {minted} minus the burnt {burnt}",
+        prev_header.total_supply()
+    );
+    if let Some(sum_of_balances) = sum_of_balances(clients, &block) {
+        assert_eq!(
+            sum_of_balances,
+            header.total_supply(),
+            "sum of the account balances is not the total supply, with no receipts in flight"
+        );
+    }
+}
+
+/// The sum of the balances of the accounts after the block, or `None` if some
+/// receipt is in flight, i.e. sent or postponed and not applied yet, or some
+/// shard is not tracked by any node.
+fn sum_of_balances(clients: &[&Client], block: &Block) -> Option<Balance> {
+    if block.chunks().iter().any(|chunk| matches!(chunk, MaybeNew::Old(_))) {
+        return None;
+    }
+    let prev_hash = block.header().prev_hash();
+    let shard_layout =
+        clients[0].epoch_manager.get_shard_layout(block.header().epoch_id()).unwrap();
+    let mut total = 0;
+    for shard_uid in shard_layout.shard_uids() {
+        let shard_id = shard_uid.shard_id();
+        let (client, chunk_extra) = clients.iter().find_map(|client| {
+            if !client_tracking_shard(client, shard_id, prev_hash) {
+                return None;
+            }
+            Some((client, client.chain.get_chunk_extra(block.hash(), &shard_uid).ok()?))
+        })?;
+        let outgoing_receipts =
+            client.chain.chain_store().get_outgoing_receipts(block.hash(), shard_id).ok()?;
+        if !outgoing_receipts.is_empty() {
+            return None;
+        }
+        let trie = client
+            .runtime_adapter
+            .get_tries()
+            .get_view_trie_for_shard(shard_uid, *chunk_extra.state_root());
+        for item in trie.lock_for_iter().iter().unwrap() {
+            let (key, value) = item.unwrap();
+            if matches!(key[0], col::PROMISE_YIELD_RECEIPT | col::BUFFERED_RECEIPT) {
+                return None;
+            }
+            match StateRecord::from_raw_key_value(&key, value) {
+                Some(StateRecord::Account { account, .. }) => {
+                    total += account.amount() + account.locked();
+                }
+                Some(StateRecord::PostponedReceipt(_) | StateRecord::DelayedReceipt(_)) => {
+                    return None;
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Every receipt sent by a chunk is received exactly once, by the chunks of
+/// a final block at most `max_delay` blocks later, and every received receipt
+/// was sent before. The final blocks are checked in order, on the nodes
+/// tracking the shards.
+pub struct ReceiptConservation {
+    max_delay: BlockHeightDelta,
+    /// The receipts sent and not received yet, with the height they were
+    /// sent at.
+    in_flight: HashMap<CryptoHash, BlockHeight>,
+    received: HashSet<CryptoHash>,
+    last_checked_block: Option<(BlockHeight, CryptoHash)>,
+}
+
+impl ReceiptConservation {
+    pub fn new(max_delay: BlockHeightDelta) -> Self {
+        Self {
+            max_delay,
+            in_flight: HashMap::new(),
+            received: HashSet::new(),
+            last_checked_block: None,
+        }
+    }
+
+    /// The final blocks after the last checked one, in increasing height.
+    fn new_final_blocks(&self, client: &Client, tip: &Tip) -> Vec<Block> {
+        let chain = &client.chain;
+        let tip_header = chain.get_block_header(&tip.last_block_hash).unwrap();
+        let mut hash = *tip_header.last_final_block();
+        let mut blocks = Vec::new();
+        loop {
+            let Ok(block) = chain.get_block(&hash) else {
+                break;
+            };
+            // The genesis block has no chunks applied.
+            if block.header().prev_hash() == &CryptoHash::default() {
+                break;
+            }
+            if let Some((height, last_hash)) = self.last_checked_block {
+                if block.header().height() <= height {
+                    assert_eq!(*block.hash(), last_hash, "the final chain was reorganized");
+                    break;
+                }
+            }
+            hash = *block.header().prev_hash();
+            blocks.push(block);
+        }
+        blocks.reverse();
+        blocks
+    }
+
+    fn check_final_block(&mut self, clients: &[&Client], block: &Block) {
+        let height = block.header().height();
+        let prev_hash = block.header().prev_hash();
+        let epoch_id = block.header().epoch_id();
+        let shard_layout = clients[0].epoch_manager.get_shard_layout(epoch_id).unwrap();
+        // The receipts received by the block were sent by the chunks of the
+        // previous blocks, so they are checked before the sent ones.
+        for shard_id in shard_layout.shard_ids() {
+            let client = clients
+                .iter()
+                .find(|client| client_tracking_shard(client, shard_id, prev_hash))
+                .unwrap_or_else(|| panic!("no node tracks shard {shard_id}"));
+            let Ok(proofs) =
+                client.chain.chain_store().get_incoming_receipts(block.hash(), shard_id)
+            else {
+                continue;
+            };
+            for receipt in proofs.iter().flat_map(|proof| proof.0.iter()) {
+                let receipt_id = receipt.receipt_id();
+                assert!(
+                    self.received.insert(*receipt_id),
+                    "receipt {receipt_id} received twice, by shard {shard_id}"
+                );
+                assert!(
+                    self.in_flight.remove(receipt_id).is_some(),
+                    "receipt {receipt_id} received by shard {shard_id} was never sent"
+                );
+            }
+        }
+        for chunk in block.chunks().iter() {
+            let MaybeNew::New(chunk) = chunk else {
+                continue;
+            };
+            let shard_id = chunk.shard_id();
+            let receipts = clients
+                .iter()
+                .filter(|client| client_tracking_shard(client, shard_id, prev_hash))
+                .find_map(|client| {
+                    client.chain.chain_store().get_outgoing_receipts(block.hash(), shard_id).ok()
+                })
+                .unwrap_or_else(|| panic!("no node has the receipts sent by shard {shard_id}"));
+            for receipt in receipts.iter() {
+                let receipt_id = receipt.receipt_id();
+                assert!(
+                    self.in_flight.insert(*receipt_id, height).is_none()
+                        && !self.received.contains(receipt_id),
+                    "receipt {receipt_id} sent twice, by shard {shard_id}"
+                );
+            }
+        }
+        self.check_delays(height);
+    }
+
+    fn check_delays(&self, height: BlockHeight) {
+        let late = self
+            .in_flight
+            .iter()
+            .filter(|(_, sent_height)| **sent_height + self.max_delay < height)
+            .map(|(receipt_id, sent_height)| format!("{receipt_id} sent at {sent_height}"))
+            .join(", ");
+        assert!(late.is_empty(), "receipts not received after {} blocks: {late}", self.max_delay);
+    }
+}
+
+impl Invariant for ReceiptConservation {
+    fn check_block(&mut self, clients: &[&Client], tip: &Tip) {
+        for block in self.new_final_blocks(clients[0], tip) {
+            self.check_final_block(clients, &block);
+            self.last_checked_block = Some((block.header().height(), *block.hash()));
+        }
+    }
+}
+
+/// The storage proofs of the state witnesses of the chunks at every height,
+/// of the main and the implicit transitions together, are at most `limit`
+/// plus `slack`, the proof of the receipt which went over the soft limit. At
+/// the end of the test, the witnesses of the first chunks after a resharding
+/// have been checked. Needs `save_latest_witnesses` on the producers.
+pub struct WitnessSizeLimit {
+    limit: usize,
+    slack: usize,
+    checked_chunks: HashSet<ChunkHash>,
+    num_resharding_witnesses: usize,
+}
+
+impl WitnessSizeLimit {
+    pub fn new(limit: usize, slack: usize) -> Self {
+        Self { limit, slack, checked_chunks: HashSet::new(), num_resharding_witnesses: 0 }
+    }
+
+    /// Whether the witness spans a resharding, i.e. the epoch of the chunk
+    /// has a different shard layout than the epoch of its previous block.
+    fn is_resharding_witness(client: &Client, witness: &ChunkStateWitness) -> bool {
+        let epoch_manager = &client.epoch_manager;
+        let prev_epoch_id =
+            epoch_manager.get_epoch_id(witness.chunk_header.prev_block_hash()).unwrap();
+        epoch_manager.get_shard_layout(&prev_epoch_id).unwrap()
+            != epoch_manager.get_shard_layout(&witness.epoch_id).unwrap()
+    }
+}
+
+fn partial_state_size(state: &PartialState) -> usize {
+    let PartialState::TrieValues(values) = state;
+    values.iter().map(|value| value.len()).sum()
+}
+
+impl Invariant for WitnessSizeLimit {
+    fn check_block(&mut self, clients: &[&Client], tip: &Tip) {
+        for client in clients {
+            let witnesses =
+                client.chain.chain_store().get_latest_witnesses(Some(tip.height), None, None);
+            for witness in witnesses.unwrap() {
+                if !self.checked_chunks.insert(witness.chunk_header.chunk_hash()) {
+                    continue;
+                }
+                let main_size = partial_state_size(&witness.main_state_transition.base_state);
+                let implicit_size = witness
+                    .implicit_transitions
+                    .iter()
+                    .map(|transition| partial_state_size(&transition.base_state))
+                    .sum::<usize>();
+                let shard_id = witness.chunk_header.shard_id();
+                assert!(
+                    main_size + implicit_size <= self.limit + self.slack,
+                    "storage proof of the witness of shard {shard_id} is too big: main \
+                     {main_size}, implicit {implicit_size}, limit {}",
+                    self.limit
+                );
+                if Self::is_resharding_witness(client, &witness) {
+                    tracing::info!(
+                        target: "test", %shard_id, main_size, implicit_size,
+                        "witness of the first chunk after resharding"
+                    );
+                    self.num_resharding_witnesses += 1;
+                }
+            }
+        }
+    }
+
+    fn check_end(&mut self, _clients: &[&Client]) {
+        assert!(self.num_resharding_witnesses > 0, "no witness after a resharding was checked");
+    }
+}
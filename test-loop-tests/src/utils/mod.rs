@@ -9,8 +9,10 @@ use crate::setup::state::NodeExecutionData;
 
 pub(crate) mod client_queries;
 pub(crate) mod contract_distribution;
+pub(crate) mod invariants;
 pub(crate) mod loop_action;
 pub(crate) mod network;
+pub(crate) mod network_faults;
 pub(crate) mod peer_manager_actor;
 pub(crate) mod receipts;
 pub(crate) mod resharding;
@@ -20,6 +22,7 @@ pub(crate) mod sharding;
 pub(crate) mod transactions;
 pub(crate) mod trie_sanity;
 pub(crate) mod validators;
+pub(crate) mod workloads;
 
 pub(crate) const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 pub(crate) const TGAS: u64 = 1_000_000_000_000;
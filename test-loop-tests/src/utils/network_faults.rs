@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+use near_async::time::Duration;
+use near_primitives::types::AccountId;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// Faults injected into the test loop network: extra latency on the links
+/// between clients, drops of some kinds of network requests and partitions
+/// between groups of clients.
+///
+/// The randomness comes from a seeded rng, so as long as the test loop
+/// processes the same events in the same order, the same messages are
+/// delayed and dropped in every run.
+///
+/// The faults are given to `TestLoopBuilder::network_faults` and can be
+/// changed while the test runs with `TestLoopNetworkSharedState::update_faults`.
+#[derive(Clone)]
+pub struct NetworkFaults {
+    rng: ChaCha20Rng,
+    /// Extra latency of the messages sent from the first account to the second.
+    link_latencies: HashMap<(AccountId, AccountId), LatencyRange>,
+    /// Probability to drop a request, by the name of its `NetworkRequests`
+    /// variant, e.g. `"Approval"`.
+    drop_rates: HashMap<String, f64>,
+    /// Links over which nothing is delivered, in both directions.
+    partitioned_links: HashSet<(AccountId, AccountId)>,
+}
+
+/// Extra latency of a link, sampled uniformly in `[min, max]` for every
+/// message, in whole milliseconds.
+#[derive(Clone, Copy, Debug)]
+struct LatencyRange {
+    min: Duration,
+    max: Duration,
+}
+
+impl Default for NetworkFaults {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl NetworkFaults {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha20Rng::seed_from_u64(seed),
+            link_latencies: HashMap::new(),
+            drop_rates: HashMap::new(),
+            partitioned_links: HashSet::new(),
+        }
+    }
+
+    /// Delays the messages sent from `from` to `to` by an extra latency
+    /// between `min` and `max`.
+    pub fn with_link_latency(
+        mut self,
+        from: &AccountId,
+        to: &AccountId,
+        min: Duration,
+        max: Duration,
+    ) -> Self {
+        self.set_link_latency(from, to, min, max);
+        self
+    }
+
+    /// Drops the requests of the given `NetworkRequests` variant with the
+    /// given probability.
+    pub fn with_drop_rate(mut self, request_name: &str, rate: f64) -> Self {
+        self.set_drop_rate(request_name, rate);
+        self
+    }
+
+    pub fn set_link_latency(
+        &mut self,
+        from: &AccountId,
+        to: &AccountId,
+        min: Duration,
+        max: Duration,
+    ) {
+        assert!(Duration::ZERO <= min && min <= max, "Invalid latency range {min}..={max}");
+        self.link_latencies.insert((from.clone(), to.clone()), LatencyRange { min, max });
+    }
+
+    pub fn set_drop_rate(&mut self, request_name: &str, rate: f64) {
+        assert!((0.0..=1.0).contains(&rate), "Invalid drop rate {rate}");
+        self.drop_rates.insert(request_name.to_string(), rate);
+    }
+
+    /// Cuts all the links between the accounts of `side_a` and the accounts
+    /// of `side_b`, until the partitions are healed.
+    pub fn partition(&mut self, side_a: &[AccountId], side_b: &[AccountId]) {
+        for a in side_a {
+            for b in side_b {
+                self.partitioned_links.insert((a.clone(), b.clone()));
+                self.partitioned_links.insert((b.clone(), a.clone()));
+            }
+        }
+    }
+
+    /// Restores all the links cut by `partition`.
+    pub fn heal_partitions(&mut self) {
+        self.partitioned_links.clear();
+    }
+
+    pub(crate) fn is_partitioned(&self, from: &AccountId, to: &AccountId) -> bool {
+        self.partitioned_links.contains(&(from.clone(), to.clone()))
+    }
+
+    /// Returns the extra latency of the next message sent from `from` to `to`.
+    pub(crate) fn sample_latency(&mut self, from: &AccountId, to: &AccountId) -> Duration {
+        let Some(range) = self.link_latencies.get(&(from.clone(), to.clone())) else {
+            return Duration::ZERO;
+        };
+        let millis =
+            self.rng.gen_range(range.min.whole_milliseconds()..=range.max.whole_milliseconds());
+        Duration::milliseconds(millis as i64)
+    }
+
+    /// Returns whether the next request of the given variant should be dropped.
+    pub(crate) fn should_drop(&mut self, request_name: &str) -> bool {
+        match self.drop_rates.get(request_name) {
+            Some(rate) => self.rng.gen_bool(*rate),
+            None => false,
+        }
+    }
+}
@@ -14,8 +14,9 @@ use near_chain::BlockHeader;
 use near_client::{BlockApproval, BlockResponse, SetNetworkInfo};
 use near_network::client::{
     BlockHeadersRequest, BlockHeadersResponse, BlockRequest, ChunkEndorsementMessage,
-    EpochSyncRequestMessage, EpochSyncResponseMessage, OptimisticBlockMessage, ProcessTxRequest,
-    ProcessTxResponse,
+    ChunkPartsSampleResponseMessage, EpochSyncRequestMessage, EpochSyncResponseMessage,
+    OptimisticBlockMessage, ProcessTxRequest, ProcessTxResponse, ReplicaStateUpdateRequestMessage,
+    ReplicaStateUpdateResponseMessage,
 };
 use near_network::shards_manager::ShardsManagerRequestFromNetwork;
 use near_network::state_witness::{
@@ -34,6 +35,8 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::types::AccountId;
 
+use super::network_faults::NetworkFaults;
+
 /// Subset of ClientSenderForNetwork required for the TestLoop network.
 /// We skip over the message handlers from view client.
 #[derive(Clone, MultiSend, MultiSenderFrom)]
@@ -45,6 +48,9 @@ pub struct ClientSenderForTestLoopNetwork {
     pub epoch_sync_request: Sender<EpochSyncRequestMessage>,
     pub epoch_sync_response: Sender<EpochSyncResponseMessage>,
     pub optimistic_block_receiver: Sender<OptimisticBlockMessage>,
+    pub chunk_parts_sample_response: Sender<ChunkPartsSampleResponseMessage>,
+    pub replica_state_update_request: Sender<ReplicaStateUpdateRequestMessage>,
+    pub replica_state_update_response: Sender<ReplicaStateUpdateResponseMessage>,
     pub network_info: AsyncSender<SetNetworkInfo, ()>,
 }
 
@@ -88,6 +94,9 @@ type NetworkRequestHandler = Box<dyn Fn(NetworkRequests) -> Option<NetworkReques
 ///
 /// In case no handler is able to handle the request, the actor will panic.
 ///
+/// Before any handler, the requests are dropped according to the `NetworkFaults` of the shared
+/// state.
+///
 /// NOTE: To make the override functionality work with the default handlers, the handlers are tried in
 /// reverse order.
 ///
@@ -98,6 +107,7 @@ type NetworkRequestHandler = Box<dyn Fn(NetworkRequests) -> Option<NetworkReques
 pub struct TestLoopPeerManagerActor {
     handlers: Vec<NetworkRequestHandler>,
 
+    shared_state: TestLoopNetworkSharedState,
     client_sender: ClientSenderForTestLoopNetwork,
     genesis_id: GenesisId,
     last_block_headers: HashMap<PeerInfo, BlockHeader>,
@@ -147,7 +157,13 @@ impl TestLoopPeerManagerActor {
             network_message_to_shards_manager_handler(clock, &account_id, shared_state.clone()),
             network_message_to_state_snapshot_handler(),
         ];
-        Self { handlers, client_sender, genesis_id, last_block_headers: HashMap::new() }
+        Self {
+            handlers,
+            shared_state: shared_state.clone(),
+            client_sender,
+            genesis_id,
+            last_block_headers: HashMap::new(),
+        }
     }
 
     /// Register a new handler to override the default handlers.
@@ -195,21 +211,48 @@ pub struct TestLoopNetworkSharedState(Arc<Mutex<TestLoopNetworkSharedStateInner>
 
 struct TestLoopNetworkSharedStateInner {
     account_to_peer_id: HashMap<AccountId, PeerId>,
-    senders: HashMap<PeerId, Arc<OneClientSenders>>,
+    senders: HashMap<PeerId, NodeSenders>,
     // Everything sent using these senders should be dropped.
     drop_events_senders: Arc<OneClientSenders>,
     route_back: HashMap<CryptoHash, PeerId>,
     disallowed_peer_links: HashMap<PeerId, HashSet<PeerId>>,
+    faults: NetworkFaults,
 }
 
 /// Senders available for the networking layer, for one node in the test loop.
-struct OneClientSenders {
-    client_sender: ClientSenderForTestLoopNetwork,
-    view_client_sender: ViewClientSenderForTestLoopNetwork,
-    tx_processor_sender: TxRequestHandleSenderForTestLoopNetwork,
-    partial_witness_sender: PartialWitnessSenderForNetwork,
-    shards_manager_sender: Sender<ShardsManagerRequestFromNetwork>,
-    peer_manager_sender: Sender<TestLoopNetworkBlockInfo>,
+pub struct OneClientSenders {
+    pub client_sender: ClientSenderForTestLoopNetwork,
+    pub view_client_sender: ViewClientSenderForTestLoopNetwork,
+    pub tx_processor_sender: TxRequestHandleSenderForTestLoopNetwork,
+    pub partial_witness_sender: PartialWitnessSenderForNetwork,
+    pub shards_manager_sender: Sender<ShardsManagerRequestFromNetwork>,
+    pub peer_manager_sender: Sender<TestLoopNetworkBlockInfo>,
+}
+
+/// A node which can be added to the test loop network.
+pub trait TestLoopNetworkNode: Send + Sync + 'static {
+    fn account_id(&self) -> AccountId;
+    fn peer_id(&self) -> PeerId;
+    /// Senders to the actors of the node, which deliver the messages after
+    /// the usual network delay plus `extra_latency`.
+    fn network_senders(&self, extra_latency: Duration) -> OneClientSenders;
+}
+
+struct NodeSenders {
+    node: Box<dyn TestLoopNetworkNode>,
+    account_id: AccountId,
+    /// Senders of the node, by the extra latency of their messages.
+    by_latency: HashMap<Duration, Arc<OneClientSenders>>,
+}
+
+impl NodeSenders {
+    fn with_latency(&mut self, extra_latency: Duration) -> Arc<OneClientSenders> {
+        let node = &self.node;
+        self.by_latency
+            .entry(extra_latency)
+            .or_insert_with(|| Arc::new(node.network_senders(extra_latency)))
+            .clone()
+    }
 }
 
 /// This actor can be used in situations when we don't expect any events to reach it.
@@ -240,43 +283,30 @@ fn to_drop_events_senders(s: TestLoopSender<UnreachableActor>) -> Arc<OneClientS
 }
 
 impl TestLoopNetworkSharedState {
-    pub fn new(unreachable_actor_sender: TestLoopSender<UnreachableActor>) -> Self {
+    pub fn new(
+        unreachable_actor_sender: TestLoopSender<UnreachableActor>,
+        faults: NetworkFaults,
+    ) -> Self {
         let inner = TestLoopNetworkSharedStateInner {
             account_to_peer_id: HashMap::new(),
             senders: HashMap::new(),
             drop_events_senders: to_drop_events_senders(unreachable_actor_sender),
             route_back: HashMap::new(),
             disallowed_peer_links: HashMap::new(),
+            faults,
         };
         Self(Arc::new(Mutex::new(inner)))
     }
 
-    pub fn add_client<'a, D>(&self, data: &'a D)
-    where
-        AccountId: From<&'a D>,
-        PeerId: From<&'a D>,
-        ClientSenderForTestLoopNetwork: From<&'a D>,
-        ViewClientSenderForTestLoopNetwork: From<&'a D>,
-        TxRequestHandleSenderForTestLoopNetwork: From<&'a D>,
-        PartialWitnessSenderForNetwork: From<&'a D>,
-        Sender<ShardsManagerRequestFromNetwork>: From<&'a D>,
-        Sender<TestLoopNetworkBlockInfo>: From<&'a D>,
-    {
-        let account_id = AccountId::from(data);
-        let peer_id = PeerId::from(data);
+    pub fn add_client(&self, node: impl TestLoopNetworkNode) {
+        let account_id = node.account_id();
+        let peer_id = node.peer_id();
 
         let mut guard = self.0.lock().unwrap();
-        guard.account_to_peer_id.insert(account_id, peer_id.clone());
+        guard.account_to_peer_id.insert(account_id.clone(), peer_id.clone());
         guard.senders.insert(
             peer_id,
-            Arc::new(OneClientSenders {
-                client_sender: ClientSenderForTestLoopNetwork::from(data),
-                view_client_sender: ViewClientSenderForTestLoopNetwork::from(data),
-                tx_processor_sender: TxRequestHandleSenderForTestLoopNetwork::from(data),
-                partial_witness_sender: PartialWitnessSenderForNetwork::from(data),
-                shards_manager_sender: Sender::<ShardsManagerRequestFromNetwork>::from(data),
-                peer_manager_sender: Sender::<TestLoopNetworkBlockInfo>::from(data),
-            }),
+            NodeSenders { node: Box::new(node), account_id, by_latency: HashMap::new() },
         );
     }
 
@@ -292,6 +322,18 @@ impl TestLoopNetworkSharedState {
         guard.disallowed_peer_links = HashMap::new();
     }
 
+    /// Changes the faults injected into the network, e.g. to partition the
+    /// clients from a loop action and heal the partition later.
+    pub fn update_faults(&self, update: impl FnOnce(&mut NetworkFaults)) {
+        let mut guard = self.0.lock().unwrap();
+        update(&mut guard.faults);
+    }
+
+    fn should_drop_request(&self, request: &NetworkRequests) -> bool {
+        let mut guard = self.0.lock().unwrap();
+        guard.faults.should_drop(request.as_ref())
+    }
+
     fn account_to_peer_id(&self, account_id: &AccountId) -> PeerId {
         let guard = self.0.lock().unwrap();
         guard.account_to_peer_id.get(account_id).unwrap().clone()
@@ -304,26 +346,47 @@ impl TestLoopNetworkSharedState {
     ) -> bool {
         guard.disallowed_peer_links.get(from).and_then(|blocklist| blocklist.get(to)).is_some()
     }
+
+    /// Returns the senders for the messages sent from `origin` to `peer_id`,
+    /// with the faults of the link applied.
+    fn link_senders(
+        guard: &mut MutexGuard<TestLoopNetworkSharedStateInner>,
+        origin: &PeerId,
+        peer_id: &PeerId,
+    ) -> Arc<OneClientSenders> {
+        if Self::is_peer_link_disallowed(guard, origin, peer_id) {
+            return guard.drop_events_senders.clone();
+        }
+        let guard = &mut **guard;
+        let target = guard.senders.get_mut(peer_id).unwrap();
+        let Some(origin_account_id) = guard
+            .account_to_peer_id
+            .iter()
+            .find_map(|(account_id, id)| if id == origin { Some(account_id) } else { None })
+        else {
+            return target.with_latency(Duration::ZERO);
+        };
+        if guard.faults.is_partitioned(origin_account_id, &target.account_id) {
+            return guard.drop_events_senders.clone();
+        }
+        let extra_latency = guard.faults.sample_latency(origin_account_id, &target.account_id);
+        target.with_latency(extra_latency)
+    }
+
     fn senders_for_account(
         &self,
         origin: &AccountId,
         account_id: &AccountId,
     ) -> Arc<OneClientSenders> {
-        let guard = self.0.lock().unwrap();
-        let origin_peer_id = &guard.account_to_peer_id[origin];
-        let peer_id = &guard.account_to_peer_id[account_id];
-        if Self::is_peer_link_disallowed(&guard, origin_peer_id, peer_id) {
-            return guard.drop_events_senders.clone();
-        }
-        guard.senders.get(peer_id).unwrap().clone()
+        let mut guard = self.0.lock().unwrap();
+        let origin_peer_id = guard.account_to_peer_id[origin].clone();
+        let peer_id = guard.account_to_peer_id[account_id].clone();
+        Self::link_senders(&mut guard, &origin_peer_id, &peer_id)
     }
 
     fn senders_for_peer(&self, origin: &PeerId, peer_id: &PeerId) -> Arc<OneClientSenders> {
-        let guard = self.0.lock().unwrap();
-        if Self::is_peer_link_disallowed(&guard, origin, peer_id) {
-            return guard.drop_events_senders.clone();
-        }
-        guard.senders.get(peer_id).unwrap().clone()
+        let mut guard = self.0.lock().unwrap();
+        Self::link_senders(&mut guard, origin, peer_id)
     }
 
     fn generate_route_back(&self, peer_id: &PeerId) -> CryptoHash {
@@ -338,13 +401,10 @@ impl TestLoopNetworkSharedState {
         origin: &AccountId,
         route_back: &CryptoHash,
     ) -> Arc<OneClientSenders> {
-        let guard = self.0.lock().unwrap();
-        let origin_peer_id = &guard.account_to_peer_id[origin];
-        let peer_id = guard.route_back.get(route_back).unwrap();
-        if Self::is_peer_link_disallowed(&guard, origin_peer_id, peer_id) {
-            return guard.drop_events_senders.clone();
-        }
-        guard.senders.get(peer_id).unwrap().clone()
+        let mut guard = self.0.lock().unwrap();
+        let origin_peer_id = guard.account_to_peer_id[origin].clone();
+        let peer_id = guard.route_back.get(route_back).unwrap().clone();
+        Self::link_senders(&mut guard, &origin_peer_id, &peer_id)
     }
 
     fn accounts(&self) -> Vec<AccountId> {
@@ -371,6 +431,9 @@ impl Handler<PeerManagerMessageRequest> for TestLoopPeerManagerActor {
         let PeerManagerMessageRequest::NetworkRequests(request) = msg else {
             panic!("Unexpected message: {:?}", msg);
         };
+        if self.shared_state.should_drop_request(&request) {
+            return PeerManagerMessageResponse::NetworkResponses(NetworkResponses::NoResponse);
+        }
 
         // Iterate over the handlers in reverse order to allow for overriding the default handlers.
         let mut request = Some(request);
@@ -661,6 +724,42 @@ fn network_message_to_shards_manager_handler(
                 .send(ShardsManagerRequestFromNetwork::ProcessPartialEncodedChunkForward(forward));
             None
         }
+        NetworkRequests::ChunkPartsSampleRequest { target, request } => {
+            assert!(target != my_account_id, "Sending message to self not supported.");
+            let my_peer_id = shared_state.account_to_peer_id(&my_account_id);
+            let route_back = shared_state.generate_route_back(&my_peer_id);
+            shared_state.senders_for_account(&my_account_id, &target).shards_manager_sender.send(
+                ShardsManagerRequestFromNetwork::ProcessChunkPartsSampleRequest {
+                    chunk_parts_sample_request: request,
+                    route_back,
+                },
+            );
+            None
+        }
+        NetworkRequests::ChunkPartsSampleResponse { route_back, response } => {
+            shared_state
+                .senders_for_route_back(&my_account_id, &route_back)
+                .client_sender
+                .send(ChunkPartsSampleResponseMessage(response));
+            None
+        }
+        NetworkRequests::ReplicaStateUpdateRequest { target, request } => {
+            assert!(target != my_account_id, "Sending message to self not supported.");
+            let my_peer_id = shared_state.account_to_peer_id(&my_account_id);
+            let route_back = shared_state.generate_route_back(&my_peer_id);
+            shared_state
+                .senders_for_account(&my_account_id, &target)
+                .client_sender
+                .send(ReplicaStateUpdateRequestMessage { request, route_back });
+            None
+        }
+        NetworkRequests::ReplicaStateUpdateResponse { route_back, response } => {
+            shared_state
+                .senders_for_route_back(&my_account_id, &route_back)
+                .client_sender
+                .send(ReplicaStateUpdateResponseMessage(response));
+            None
+        }
         _ => Some(request),
     })
 }
@@ -1,5 +1,5 @@
-use std::cell::Cell;
-use std::collections::{BTreeMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZero;
 
 use assert_matches::assert_matches;
@@ -8,10 +8,14 @@ use bytesize::ByteSize;
 use itertools::Itertools;
 use near_async::test_loop::data::TestLoopData;
 use near_chain::ChainStoreAccess;
+use near_chain_configs::GCCategory;
 use near_client::Client;
-use near_client::{Query, QueryError::GarbageCollectedBlock};
-use near_crypto::Signer;
+use near_client::{
+    GetShardLayoutAtBlock, GetShardLineage, Query, QueryError::GarbageCollectedBlock,
+};
+use near_crypto::{InMemorySigner, KeyType, Signer};
 use near_epoch_manager::shard_assignment::shard_id_to_uid;
+use near_primitives::account::AccessKey;
 use near_primitives::action::{Action, FunctionCallAction};
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{
@@ -21,29 +25,30 @@ use near_primitives::test_utils::create_user_test_signer;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, BlockId, BlockReference, Gas, ShardId};
 use near_primitives::views::{
-    FinalExecutionStatus, QueryRequest, QueryResponse, QueryResponseKind,
+    AccountShardView, FinalExecutionStatus, QueryRequest, QueryResponse, QueryResponseKind,
 };
 use near_store::adapter::StoreAdapter;
 use near_store::adapter::trie_store::{TrieStoreAdapter, get_shard_uid_mapping};
 use near_store::db::refcount::decode_value_with_rc;
+use near_store::flat::{FlatStorageReshardingStatus, FlatStorageStatus};
 use near_store::trie::receipts_column_helper::{ShardsOutgoingReceiptBuffer, TrieQueue};
 use near_store::{DBCol, ShardUId, StorageError, Trie, TrieDBStorage, get};
-use rand::seq::SliceRandom;
-use rand::{Rng, SeedableRng};
-use rand_chacha::ChaCha20Rng;
 
 use super::sharding::{next_epoch_has_new_shard_layout, this_block_has_new_shard_layout};
 use crate::setup::state::NodeExecutionData;
 use crate::utils::loop_action::LoopAction;
-use crate::utils::sharding::{get_memtrie_for_shard, next_block_has_new_shard_layout};
+use crate::utils::sharding::{
+    get_memtrie_for_shard, next_block_has_new_shard_layout, shard_was_split,
+};
 use crate::utils::transactions::{
     check_txs, check_txs_remove_successful, delete_account, get_anchor_hash, get_next_nonce,
-    store_and_submit_tx, submit_tx,
+    get_next_nonce_for_key, get_shared_block_hash, store_and_submit_tx, submit_tx,
 };
+use crate::utils::workloads::{Workload, workloads_into_loop_action};
 use crate::utils::{ONE_NEAR, TGAS, get_node_data, retrieve_client_actor};
 use near_chain::types::Tip;
 use near_client::client_actor::ClientActorInner;
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{ShardLayout, get_block_shard_uid};
 use near_primitives::trie_key::TrieKey;
 use std::sync::Arc;
 
@@ -99,61 +104,11 @@ pub(crate) fn fork_before_resharding_block(
     LoopAction::new(action_fn, succeeded)
 }
 
+/// Returns a loop action sending random transfers between `account_ids` at
+/// every block height, until the end of the test. The transfers are chosen
+/// with the seed of the test loop.
 pub(crate) fn execute_money_transfers(account_ids: Vec<AccountId>) -> LoopAction {
-    const NUM_TRANSFERS_PER_BLOCK: usize = 20;
-
-    let latest_height = Cell::new(0);
-    let seed = rand::thread_rng().r#gen::<u64>();
-    println!("Random seed: {}", seed);
-
-    let (ran_transfers, succeeded) = LoopAction::shared_success_flag();
-    let action_fn = Box::new(
-        move |node_datas: &[NodeExecutionData],
-              test_loop_data: &mut TestLoopData,
-              client_account_id: AccountId| {
-            let client_actor =
-                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
-            let tip = client_actor.client.chain.head().unwrap();
-
-            // Run this action only once at every block height.
-            if latest_height.get() == tip.height {
-                return;
-            }
-            latest_height.set(tip.height);
-
-            let mut slice = [0u8; 32];
-            slice[0..8].copy_from_slice(&seed.to_le_bytes());
-            slice[8..16].copy_from_slice(&tip.height.to_le_bytes());
-            let mut rng: ChaCha20Rng = SeedableRng::from_seed(slice);
-
-            for _ in 0..NUM_TRANSFERS_PER_BLOCK {
-                let sender = account_ids.choose(&mut rng).unwrap().clone();
-                let receiver = account_ids.choose(&mut rng).unwrap().clone();
-
-                let clients = node_datas
-                    .iter()
-                    .map(|test_data| {
-                        &test_loop_data.get(&test_data.client_sender.actor_handle()).client
-                    })
-                    .collect_vec();
-
-                let anchor_hash = get_anchor_hash(&clients);
-                let nonce = get_next_nonce(&test_loop_data, &node_datas, &sender);
-                let amount = ONE_NEAR * rng.gen_range(1..=10);
-                let tx = SignedTransaction::send_money(
-                    nonce,
-                    sender.clone(),
-                    receiver.clone(),
-                    &create_user_test_signer(&sender).into(),
-                    amount,
-                    anchor_hash,
-                );
-                submit_tx(&node_datas, &client_account_id, tx);
-            }
-            ran_transfers.set(true);
-        },
-    );
-    LoopAction::new(action_fn, succeeded)
+    Workload::transfers(account_ids).txs_per_block(20).skip_outcome_checks().into_loop_action()
 }
 
 /// Returns a loop action that makes storage read and write at every block
@@ -247,102 +202,28 @@ pub(crate) fn execute_storage_operations(
     LoopAction::new(action_fn, succeeded)
 }
 
-/// Returns a loop action that invokes a costly method from a contract
-/// `CALLS_PER_BLOCK_HEIGHT` times per block height.
+/// Returns a loop action that invokes a costly method from a contract five
+/// times per block height, until one block after resharding. The objective is
+/// to pile up receipts (e.g. delayed).
 ///
-/// The account invoking the contract is taken in sequential order from `signed_ids`.
-///
-/// The account receiving the contract call is taken in sequential order from `receiver_ids`.
+/// The signers and receivers of the calls are chosen at random from
+/// `signer_ids` and `receiver_ids`.
 pub(crate) fn call_burn_gas_contract(
     signer_ids: Vec<AccountId>,
     receiver_ids: Vec<AccountId>,
     gas_burnt_per_call: Gas,
     epoch_length: u64,
 ) -> LoopAction {
-    const CALLS_PER_BLOCK_HEIGHT: usize = 5;
     // Set to a value large enough, so that transactions from the past epoch are settled.
     // Must be less than epoch length, otherwise won't be triggered before the test is finished.
     let tx_check_blocks_after_resharding = epoch_length - 2;
-
-    let resharding_height = Cell::new(None);
-    let nonce = Cell::new(102);
-    let txs = Cell::new(vec![]);
-    let latest_height = Cell::new(0);
-    let (checked_transactions, succeeded) = LoopAction::shared_success_flag();
-
-    let action_fn = Box::new(
-        move |node_datas: &[NodeExecutionData],
-              test_loop_data: &mut TestLoopData,
-              client_account_id: AccountId| {
-            let client_actor =
-                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
-            let tip = client_actor.client.chain.head().unwrap();
-
-            // Run this action only once at every block height.
-            if latest_height.get() == tip.height {
-                return;
-            }
-            latest_height.set(tip.height);
-
-            // After resharding: wait some blocks and check that all txs have been executed correctly.
-            if let Some(height) = resharding_height.get() {
-                if tip.height > height + tx_check_blocks_after_resharding {
-                    for (tx, tx_height) in txs.take() {
-                        let tx_outcome =
-                            client_actor.client.chain.get_partial_transaction_result(&tx);
-                        let status = tx_outcome.as_ref().map(|o| o.status.clone());
-                        let status = status.unwrap();
-                        tracing::debug!(target: "test", ?tx_height, ?tx, ?status, "transaction status");
-                        assert_matches!(status, FinalExecutionStatus::SuccessValue(_));
-                    }
-                    checked_transactions.set(true);
-                }
-            } else {
-                if next_block_has_new_shard_layout(client_actor.client.epoch_manager.as_ref(), &tip)
-                {
-                    tracing::debug!(target: "test", height=tip.height, "resharding height set");
-                    resharding_height.set(Some(tip.height));
-                }
-            }
-            // Before resharding and one block after: call the test contract a few times per block.
-            // The objective is to pile up receipts (e.g. delayed).
-            if tip.height <= resharding_height.get().unwrap_or(1000) + 1 {
-                for i in 0..CALLS_PER_BLOCK_HEIGHT {
-                    // Note that if the number of signers and receivers is the
-                    // same then the traffic will always flow the same way. It
-                    // would be nice to randomize it a bit.
-                    let signer_id = &signer_ids[i % signer_ids.len()];
-                    let receiver_id = &receiver_ids[i % receiver_ids.len()];
-                    let signer: Signer = create_user_test_signer(signer_id).into();
-                    nonce.set(nonce.get() + 1);
-                    let method_name = "burn_gas_raw".to_owned();
-                    let burn_gas: u64 = gas_burnt_per_call;
-                    let args = burn_gas.to_le_bytes().to_vec();
-                    let tx = SignedTransaction::call(
-                        nonce.get(),
-                        signer_id.clone(),
-                        receiver_id.clone(),
-                        &signer,
-                        1,
-                        method_name,
-                        args,
-                        gas_burnt_per_call + 10 * TGAS,
-                        tip.last_block_hash,
-                    );
-                    store_and_submit_tx(
-                        &node_datas,
-                        &client_account_id,
-                        &txs,
-                        &signer_id,
-                        &receiver_id,
-                        tip.height,
-                        tx,
-                    );
-                }
-            }
-        },
-    );
-    LoopAction::new(action_fn, succeeded)
+    let args = gas_burnt_per_call.to_le_bytes().to_vec();
+    let gas = gas_burnt_per_call + 10 * TGAS;
+    Workload::contract_calls(signer_ids, receiver_ids, "burn_gas_raw", args, gas)
+        .txs_per_block(5)
+        .stop_after_resharding(1)
+        .outcome_deadline(tx_check_blocks_after_resharding)
+        .into_loop_action()
 }
 
 /// Send 3MB receipts from `signer_ids` shards to `receiver_ids` shards.
@@ -494,140 +375,41 @@ pub(crate) fn call_promise_yield(
     signer_ids: Vec<AccountId>,
     receiver_ids: Vec<AccountId>,
 ) -> LoopAction {
-    let resharding_height: Cell<Option<u64>> = Cell::new(None);
-    let txs = Cell::new(vec![]);
-    let latest_height = Cell::new(0);
-    let promise_txs_sent = Cell::new(false);
-    let nonce = Cell::new(102);
-    let yield_payload = vec![];
-    let (checked_transactions, succeeded) = LoopAction::shared_success_flag();
-
-    let action_fn = Box::new(
-        move |node_datas: &[NodeExecutionData],
-              test_loop_data: &mut TestLoopData,
-              client_account_id: AccountId| {
-            let client_actor =
-                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
-            let tip = client_actor.client.chain.head().unwrap();
-
-            // Run this action only once at every block height.
-            if latest_height.get() == tip.height {
-                return;
-            }
-            latest_height.set(tip.height);
-
-            // The operation to be done depends on the current block height in relation to the
-            // resharding height.
-            match (resharding_height.get(), latest_height.get()) {
-                // Resharding happened in the previous block.
-                // Maybe send the resume transaction.
-                (Some(resharding), latest) if latest == resharding + 1 && call_resume => {
-                    for (signer_id, receiver_id) in
-                        signer_ids.clone().into_iter().zip(receiver_ids.clone().into_iter())
-                    {
-                        let signer: Signer = create_user_test_signer(&signer_id).into();
-                        nonce.set(nonce.get() + 1);
-                        let tx = SignedTransaction::call(
-                            nonce.get(),
-                            signer_id.clone(),
-                            receiver_id.clone(),
-                            &signer,
-                            1,
-                            "call_yield_resume_read_data_id_from_storage".to_string(),
-                            yield_payload.clone(),
-                            300 * TGAS,
-                            tip.last_block_hash,
-                        );
-                        store_and_submit_tx(
-                            &node_datas,
-                            &client_account_id,
-                            &txs,
-                            &signer_id,
-                            &receiver_id,
-                            tip.height,
-                            tx,
-                        );
-                    }
-                }
-                // Resharding happened a few blocks in the past.
-                // Check transactions' outcomes.
-                (Some(resharding), latest) if latest == resharding + 4 => {
-                    let txs = txs.take();
-                    assert_ne!(txs.len(), 0);
-                    for (tx, tx_height) in txs {
-                        let tx_outcome =
-                            client_actor.client.chain.get_partial_transaction_result(&tx);
-                        let status = tx_outcome.as_ref().map(|o| o.status.clone());
-                        let status = status.unwrap();
-                        tracing::debug!(target: "test", ?tx_height, ?tx, ?status, "transaction status");
-                        assert_matches!(status, FinalExecutionStatus::SuccessValue(_));
-                    }
-                    checked_transactions.set(true);
-                }
-                (Some(_resharding), _latest) => {}
-                // Resharding didn't happen in the past.
-                (None, _) => {
-                    let epoch_manager = client_actor.client.epoch_manager.as_ref();
-                    // Check if resharding will happen in this block.
-                    if next_block_has_new_shard_layout(epoch_manager, &tip) {
-                        tracing::debug!(target: "test", height=tip.height, "resharding height set");
-                        resharding_height.set(Some(tip.height));
-                        return;
-                    }
-                    // Before resharding, send a set of promise transactions close to the resharding boundary, just once.
-                    if promise_txs_sent.get() {
-                        return;
-                    }
-
-                    let will_reshard =
-                        epoch_manager.will_shard_layout_change(&tip.prev_block_hash).unwrap();
-                    if !will_reshard {
-                        return;
-                    }
-                    let epoch_length = client_actor.client.config.epoch_length;
-                    let epoch_start =
-                        epoch_manager.get_epoch_start_height(&tip.last_block_hash).unwrap();
-                    if tip.height + 5 < epoch_start + epoch_length {
-                        return;
-                    }
-
-                    for (signer_id, receiver_id) in
-                        signer_ids.clone().into_iter().zip(receiver_ids.clone().into_iter())
-                    {
-                        let signer: Signer = create_user_test_signer(&signer_id).into();
-                        nonce.set(nonce.get() + 1);
-                        let tx = SignedTransaction::call(
-                            nonce.get(),
-                            signer_id.clone(),
-                            receiver_id.clone(),
-                            &signer,
-                            0,
-                            "call_yield_create_return_promise".to_string(),
-                            yield_payload.clone(),
-                            300 * TGAS,
-                            tip.last_block_hash,
-                        );
-                        store_and_submit_tx(
-                            &node_datas,
-                            &client_account_id,
-                            &txs,
-                            &signer_id,
-                            &receiver_id,
-                            tip.height,
-                            tx,
-                        );
-                    }
-                    promise_txs_sent.set(true);
-                }
-            }
-        },
-    );
-    LoopAction::new(action_fn, succeeded)
+    let paired_calls = |method_name: &str| {
+        Workload::contract_calls(
+            signer_ids.clone(),
+            receiver_ids.clone(),
+            method_name,
+            vec![],
+            300 * TGAS,
+        )
+        .paired()
+        .num_blocks(1)
+    };
+    // Send the promise transactions close to the resharding boundary. They
+    // only succeed once resumed or timed out after resharding.
+    let mut workloads = vec![
+        paired_calls("call_yield_create_return_promise")
+            .start_before_resharding(4)
+            .outcome_deadline(8),
+    ];
+    if call_resume {
+        // Resharding happened in the previous block.
+        workloads.push(
+            paired_calls("call_yield_resume_read_data_id_from_storage").start_after_resharding(1),
+        );
+    }
+    workloads_into_loop_action(workloads)
 }
 
 /// After resharding and gc-period, assert the deleted `account_id`
 /// is still accessible through archival node view client (if available),
 /// and it is not accessible through a regular, RPC node.
+///
+/// `height` is the height of the first block after resharding. The archival
+/// node must serve the account both in the last block before resharding,
+/// which is in the parent shard layout, and in the resharding block, which is
+/// in the child shard layout and still reads the State of the parent shard.
 fn check_deleted_account_availability(
     node_datas: &[NodeExecutionData],
     test_loop_data: &mut TestLoopData,
@@ -645,27 +427,105 @@ fn check_deleted_account_availability(
 
     let rpc_node_result = {
         let view_client = test_loop_data.get_mut(&rpc_view_client_handle);
-        near_async::messaging::Handler::handle(view_client, msg.clone())
+        near_async::messaging::Handler::handle(view_client, msg)
     };
     assert_matches!(rpc_node_result, Err(GarbageCollectedBlock { .. }));
 
-    if let Some(archival_id) = archival_id {
-        let archival_node_data = get_node_data(node_datas, &archival_id);
-        let archival_view_client_handle = archival_node_data.view_client_sender.actor_handle();
-        let archival_node_result = {
-            let view_client = test_loop_data.get_mut(&archival_view_client_handle);
-            near_async::messaging::Handler::handle(view_client, msg)
-        };
-        assert_matches!(
-            archival_node_result,
-            Ok(QueryResponse { kind: QueryResponseKind::ViewAccount(_), .. })
+    let Some(archival_id) = archival_id else {
+        return;
+    };
+    let (resharding_block, parent_layout, child_layout) = {
+        let client = &retrieve_client_actor(node_datas, test_loop_data, archival_id).client;
+        let header = client.chain.get_block_by_height(height).unwrap().header().clone();
+        let prev_header = client.chain.get_block_header(header.prev_hash()).unwrap();
+        let epoch_manager = &client.epoch_manager;
+        let child_layout = epoch_manager.get_shard_layout(header.epoch_id()).unwrap();
+        let parent_layout = epoch_manager.get_shard_layout(prev_header.epoch_id()).unwrap();
+        (header, parent_layout, child_layout)
+    };
+    assert_ne!(parent_layout, child_layout);
+    let child_shard_id = child_layout.account_id_to_shard_id(account_id);
+    let child_shard_uid = ShardUId::from_shard_id_and_layout(child_shard_id, &child_layout);
+    let parent_shard_id = child_layout.get_parent_shard_id(child_shard_id).unwrap();
+    let parent_shard_uid = ShardUId::from_shard_id_and_layout(parent_shard_id, &parent_layout);
+
+    let archival_node_data = get_node_data(node_datas, &archival_id);
+    let archival_view_client_handle = archival_node_data.view_client_sender.actor_handle();
+    let view_client = test_loop_data.get_mut(&archival_view_client_handle);
+    let expected_shards = [
+        (*resharding_block.prev_hash(), parent_layout, parent_shard_id, parent_shard_uid),
+        (*resharding_block.hash(), child_layout, child_shard_id, child_shard_uid),
+    ];
+    for (block_hash, shard_layout, shard_id, shard_uid) in expected_shards {
+        let block_reference = BlockReference::BlockId(BlockId::Hash(block_hash));
+        for request in [
+            QueryRequest::ViewAccount { account_id: account_id.clone() },
+            QueryRequest::ViewState {
+                account_id: account_id.clone(),
+                prefix: vec![].into(),
+                include_proof: false,
+            },
+        ] {
+            let msg = Query::new(block_reference.clone(), request);
+            let archival_node_result =
+                near_async::messaging::Handler::handle(&mut *view_client, msg);
+            assert_matches!(
+                archival_node_result,
+                Ok(QueryResponse {
+                    kind: QueryResponseKind::ViewAccount(_) | QueryResponseKind::ViewState(_),
+                    ..
+                })
+            );
+        }
+
+        // The archival node also tells in which shard of the historical shard
+        // layout the account was, and which shard its State is read from.
+        let request =
+            GetShardLayoutAtBlock { block_reference, account_id: Some(account_id.clone()) };
+        let shard_layout_view = near_async::messaging::Handler::handle(&mut *view_client, request)
+            .expect("failed to get the shard layout at block");
+        assert_eq!(shard_layout_view.shard_layout, shard_layout);
+        assert_eq!(
+            shard_layout_view.account_shard,
+            Some(AccountShardView {
+                account_id: account_id.clone(),
+                shard_id,
+                shard_uid,
+                state_shard_uid: parent_shard_uid,
+            })
+        );
+    }
+}
+
+/// Checks that the state of the resharding block is garbage collected on the
+/// RPC node, while the block and the outcome of the account deletion are kept.
+fn check_state_collected_before_block(
+    node_datas: &[NodeExecutionData],
+    test_loop_data: &mut TestLoopData,
+    rpc_id: &AccountId,
+    delete_account_tx_hash: &CryptoHash,
+    height: u64,
+) {
+    let client = &retrieve_client_actor(node_datas, test_loop_data, rpc_id).client;
+    let block = client.chain.get_block_by_height(height).unwrap();
+    let block_hash = block.hash();
+    let shard_layout = client.epoch_manager.get_shard_layout(block.header().epoch_id()).unwrap();
+    let store = client.chain.chain_store().store();
+    for shard_uid in shard_layout.shard_uids() {
+        let key = get_block_shard_uid(block_hash, &shard_uid);
+        assert!(
+            store.get(DBCol::TrieChanges, &key).unwrap().is_none(),
+            "trie changes of {shard_uid} at #{height} weren't garbage collected"
         );
     }
+    client.chain.get_execution_outcome(delete_account_tx_hash).unwrap();
 }
 
 /// Loop action testing a scenario where a temporary account is deleted after resharding.
 /// After `gc_num_epochs_to_keep epochs` we assert that the account
 /// is not accessible through RPC node but it is still accessible through archival node.
+/// If the state is kept for fewer epochs than the blocks, we first assert that
+/// the state of the resharding block is gone while the block is still there.
 ///
 /// The `temporary_account_id` must be a subaccount of the `originator_id`.
 pub(crate) fn temporary_account_during_resharding(
@@ -680,6 +540,7 @@ pub(crate) fn temporary_account_during_resharding(
 
     let delete_account_tx_hash = Cell::new(None);
     let checked_deleted_account = Cell::new(false);
+    let checked_collected_state = Cell::new(false);
 
     let (done, succeeded) = LoopAction::shared_success_flag();
     let action_fn = Box::new(
@@ -700,7 +561,10 @@ pub(crate) fn temporary_account_during_resharding(
             }
             latest_height.set(tip.height);
             let epoch_length = client_actor.client.config.epoch_length;
-            let gc_num_epochs_to_keep = client_actor.client.config.gc.gc_num_epochs_to_keep;
+            let gc_config = &client_actor.client.config.gc;
+            let gc_num_epochs_to_keep = gc_config.gc_num_epochs_to_keep;
+            let state_num_epochs_to_keep =
+                gc_config.gc_category_num_epochs_to_keep(GCCategory::State);
 
             if resharding_height.get().is_none() {
                 if !this_block_has_new_shard_layout(
@@ -735,10 +599,30 @@ pub(crate) fn temporary_account_during_resharding(
                 checked_deleted_account.set(true);
             }
 
+            // The resharding block is kept for at least another epoch at this height.
+            if state_num_epochs_to_keep + 1 < gc_num_epochs_to_keep
+                && latest_height.get()
+                    == resharding_height.get().unwrap()
+                        + (state_num_epochs_to_keep + 1) * epoch_length
+            {
+                check_state_collected_before_block(
+                    node_datas,
+                    test_loop_data,
+                    &rpc_id,
+                    &delete_account_tx_hash.get().unwrap(),
+                    resharding_height.get().unwrap(),
+                );
+                checked_collected_state.set(true);
+            }
+
             if latest_height.get() < target_height.get().unwrap() {
                 return;
             }
             assert!(checked_deleted_account.get());
+            assert!(
+                checked_collected_state.get()
+                    || state_num_epochs_to_keep + 1 >= gc_num_epochs_to_keep
+            );
             // Since gc window passed after the account was deleted,
             // check that it is not accessible through regular node,
             // but it is accessible through archival node.
@@ -756,6 +640,181 @@ pub(crate) fn temporary_account_during_resharding(
     LoopAction::new(action_fn, succeeded)
 }
 
+/// Returns a loop action that checks the access key nonces of `signer_id`
+/// across resharding. The account should be in one of the child shards.
+///
+/// Transfers are sent at every height of the epoch before resharding,
+/// including transactions signed in the last block before resharding but
+/// submitted after it, with both the default key and a key added before
+/// resharding. After resharding, a nonce just below the upper bound derived
+/// from the height must be accepted and a nonce above it must be rejected.
+pub(crate) fn access_key_nonces_across_resharding(
+    signer_id: AccountId,
+    receiver_id: AccountId,
+) -> LoopAction {
+    const TX_CHECK_DEADLINE: u64 = 5;
+    let latest_height = Cell::new(0);
+    let nonce = Cell::new(None);
+    let signed_before_resharding = Cell::new(vec![]);
+    let resharding_height = Cell::new(None);
+    let txs = Cell::new(vec![]);
+    let nonce_too_large_tx_hash = Cell::new(None);
+
+    let signer: Signer = create_user_test_signer(&signer_id);
+    let added_key_signer = InMemorySigner::from_seed(signer_id.clone(), KeyType::ED25519, "added");
+
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            if done.get() {
+                return;
+            }
+
+            let client_actor =
+                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
+            let tip = client_actor.client.chain.head().unwrap();
+
+            // Run this action only once at every block height.
+            if latest_height.get() == tip.height {
+                return;
+            }
+            latest_height.set(tip.height);
+            let epoch_manager = client_actor.client.epoch_manager.clone();
+            let block_hash = get_shared_block_hash(node_datas, test_loop_data);
+            let send_money = |nonce, signer: &Signer| {
+                SignedTransaction::send_money(
+                    nonce,
+                    signer_id.clone(),
+                    receiver_id.clone(),
+                    signer,
+                    ONE_NEAR,
+                    block_hash,
+                )
+            };
+            let next_nonce = || {
+                let next = nonce.get().unwrap();
+                nonce.set(Some(next + 1));
+                next
+            };
+
+            // Add the key right away, so that its nonce is derived from a
+            // height before resharding.
+            if nonce.get().is_none() {
+                nonce.set(Some(get_next_nonce(test_loop_data, node_datas, &signer_id)));
+                let tx = SignedTransaction::add_key(
+                    next_nonce(),
+                    signer_id.clone(),
+                    &signer,
+                    added_key_signer.public_key(),
+                    AccessKey::full_access(),
+                    block_hash,
+                );
+                store_and_submit_tx(
+                    node_datas,
+                    &client_account_id,
+                    &txs,
+                    &signer_id,
+                    &signer_id,
+                    tip.height,
+                    tx,
+                );
+                return;
+            }
+
+            if resharding_height.get().is_none() {
+                if this_block_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                    // Just resharded, submit the transactions signed in the
+                    // last block before resharding.
+                    for tx in signed_before_resharding.take() {
+                        store_and_submit_tx(
+                            node_datas,
+                            &client_account_id,
+                            &txs,
+                            &signer_id,
+                            &receiver_id,
+                            tip.height,
+                            tx,
+                        );
+                    }
+                    resharding_height.set(Some(tip.height));
+                    return;
+                }
+                if !next_epoch_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                    return;
+                }
+                store_and_submit_tx(
+                    node_datas,
+                    &client_account_id,
+                    &txs,
+                    &signer_id,
+                    &receiver_id,
+                    tip.height,
+                    send_money(next_nonce(), &signer),
+                );
+                if next_block_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                    let added_key_nonce = get_next_nonce_for_key(
+                        test_loop_data,
+                        node_datas,
+                        &signer_id,
+                        added_key_signer.public_key(),
+                    );
+                    signed_before_resharding.set(vec![
+                        send_money(next_nonce(), &signer),
+                        send_money(added_key_nonce, &added_key_signer),
+                    ]);
+                }
+                return;
+            }
+
+            let resharding_height = resharding_height.get().unwrap();
+            if tip.height == resharding_height + TX_CHECK_DEADLINE {
+                check_txs(
+                    test_loop_data,
+                    node_datas,
+                    &client_account_id,
+                    &txs.take().into_iter().map(|(tx_hash, _)| tx_hash).collect_vec(),
+                );
+                // The upper bound is derived from the height of the chunk
+                // including the transaction, which is above the current height.
+                let max_nonce = (tip.height + 1) * AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER - 1;
+                store_and_submit_tx(
+                    node_datas,
+                    &client_account_id,
+                    &txs,
+                    &signer_id,
+                    &receiver_id,
+                    tip.height,
+                    send_money(max_nonce, &signer),
+                );
+                // Way above the upper bound of any height reached in the test.
+                let nonce_too_large =
+                    (tip.height + 1000) * AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER;
+                let tx = send_money(nonce_too_large, &added_key_signer);
+                nonce_too_large_tx_hash.set(Some(tx.get_hash()));
+                submit_tx(node_datas, &client_account_id, tx);
+            }
+
+            if tip.height < resharding_height + 2 * TX_CHECK_DEADLINE {
+                return;
+            }
+            check_txs(
+                test_loop_data,
+                node_datas,
+                &client_account_id,
+                &txs.take().into_iter().map(|(tx_hash, _)| tx_hash).collect_vec(),
+            );
+            let client =
+                &retrieve_client_actor(node_datas, test_loop_data, &client_account_id).client;
+            let tx_hash = nonce_too_large_tx_hash.get().unwrap();
+            assert!(client.chain.get_partial_transaction_result(&tx_hash).is_err());
+            done.set(true);
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
 /// Removes from State column all entries where key does not start with `the_only_shard_uid` ShardUId prefix.
 fn retain_the_only_shard_state(client: &Client, the_only_shard_uid: ShardUId) {
     let store = client.chain.chain_store.store().trie_store();
@@ -860,6 +919,298 @@ pub(crate) fn check_state_cleanup(
     LoopAction::new(action_fn, succeeded)
 }
 
+/// Checks that the client builds and validates the state sync headers of the
+/// children for the sync hash of the first epoch after resharding. The prev
+/// chunk of a child in the header may be the last chunk of its parent.
+pub(crate) fn check_children_state_sync_headers() -> LoopAction {
+    let resharding_epoch_id = Cell::new(None);
+
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            if done.get() {
+                return;
+            }
+            let client_actor =
+                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
+            let epoch_manager = client_actor.client.epoch_manager.clone();
+            let chain = &mut client_actor.client.chain;
+            let tip = chain.head().unwrap();
+
+            if this_block_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                resharding_epoch_id.set(Some(tip.epoch_id));
+            }
+            if resharding_epoch_id.get() != Some(tip.epoch_id) {
+                return;
+            }
+            let Some(sync_hash) = chain.get_sync_hash(&tip.last_block_hash).unwrap() else {
+                return;
+            };
+
+            let shard_layout = epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+            let children = shard_layout
+                .shard_ids()
+                .filter(|shard_id| shard_was_split(&shard_layout, *shard_id))
+                .collect_vec();
+            assert_eq!(children.len(), 2);
+            for shard_id in children {
+                let parent_shard_id = shard_layout.get_parent_shard_id(shard_id).unwrap();
+                let header = chain
+                    .state_sync_adapter
+                    .compute_state_response_header(shard_id, sync_hash)
+                    .unwrap();
+                assert_eq!(header.cloned_chunk().cloned_header().shard_id(), shard_id);
+                let prev_chunk_shard_id = header.cloned_prev_chunk_header().unwrap().shard_id();
+                assert!(
+                    prev_chunk_shard_id == shard_id || prev_chunk_shard_id == parent_shard_id,
+                    "unexpected prev chunk of shard {prev_chunk_shard_id} for child {shard_id}"
+                );
+                chain.state_sync_adapter.set_state_header(shard_id, sync_hash, header).unwrap();
+            }
+            done.set(true);
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
+/// Checks that the view client returns the lineage of the split shard and of
+/// its children at the first block after resharding.
+pub(crate) fn check_shard_lineage() -> LoopAction {
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            if done.get() {
+                return;
+            }
+            let client =
+                &retrieve_client_actor(node_datas, test_loop_data, &client_account_id).client;
+            let epoch_manager = client.epoch_manager.clone();
+            let tip = client.chain.head().unwrap();
+            if !this_block_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                return;
+            }
+
+            let prev_epoch_id = epoch_manager.get_epoch_id(&tip.prev_block_hash).unwrap();
+            let prev_shard_layout = epoch_manager.get_shard_layout(&prev_epoch_id).unwrap();
+            let shard_layout = epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+            let child_shard_id = shard_layout
+                .shard_ids()
+                .find(|shard_id| shard_was_split(&shard_layout, *shard_id))
+                .unwrap();
+            let parent_shard_id = shard_layout.get_parent_shard_id(child_shard_id).unwrap();
+            let parent = ShardUId::from_shard_id_and_layout(parent_shard_id, &prev_shard_layout);
+            let children = shard_layout.get_children_shards_uids(parent_shard_id).unwrap();
+
+            let view_client_handle =
+                get_node_data(node_datas, &client_account_id).view_client_sender.actor_handle();
+            let view_client = test_loop_data.get_mut(&view_client_handle);
+            let request = GetShardLineage { epoch_id: prev_epoch_id, shard_uid: parent };
+            let lineage = near_async::messaging::Handler::handle(&mut *view_client, request)
+                .expect("failed to get the lineage of the parent");
+            assert_eq!(lineage.descendants.first(), Some(&children));
+            for child in children {
+                let request = GetShardLineage { epoch_id: tip.epoch_id, shard_uid: child };
+                let lineage = near_async::messaging::Handler::handle(&mut *view_client, request)
+                    .expect("failed to get the lineage of the child");
+                assert_eq!(lineage.ancestors.first(), Some(&parent));
+                // The children aren't shards of the epoch of the parent.
+                let request = GetShardLineage { epoch_id: prev_epoch_id, shard_uid: child };
+                assert!(
+                    near_async::messaging::Handler::handle(&mut *view_client, request).is_err()
+                );
+            }
+            done.set(true);
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
+/// Checks that once garbage collection passes the resharding block, the parent
+/// shards have neither a memtrie nor a flat storage in the node, while the
+/// State of the parents which the children still read is kept.
+pub(crate) fn check_resharding_parent_shards_gc() -> LoopAction {
+    let resharding = RefCell::new(None);
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            if done.get() {
+                return;
+            }
+            let client =
+                &retrieve_client_actor(node_datas, test_loop_data, &client_account_id).client;
+            let epoch_manager = client.epoch_manager.clone();
+            let tip = client.chain.head().unwrap();
+            let shard_layout = epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+            if resharding.borrow().is_none() {
+                if !this_block_has_new_shard_layout(epoch_manager.as_ref(), &tip) {
+                    return;
+                }
+                let prev_epoch_id = epoch_manager.get_epoch_id(&tip.prev_block_hash).unwrap();
+                let prev_shard_layout = epoch_manager.get_shard_layout(&prev_epoch_id).unwrap();
+                let parents = prev_shard_layout
+                    .shard_uids()
+                    .filter(|shard_uid| !shard_layout.shard_uids().contains(shard_uid))
+                    .collect_vec();
+                let resharding_height =
+                    client.chain.get_block_header(&tip.prev_block_hash).unwrap().height();
+                *resharding.borrow_mut() = Some((resharding_height, parents));
+                return;
+            }
+            let (resharding_height, parents) = resharding.borrow().clone().unwrap();
+            if client.chain.chain_store().tail().unwrap() <= resharding_height {
+                return;
+            }
+
+            let store = client.chain.chain_store().store();
+            let tries = client.runtime_adapter.get_tries();
+            for parent in parents {
+                assert!(tries.get_memtries(parent).is_none(), "memtrie of {parent} is loaded");
+                assert_eq!(
+                    store.flat_store().get_flat_storage_status(parent),
+                    Ok(FlatStorageStatus::Empty),
+                    "flat storage of {parent} wasn't removed"
+                );
+                let state_in_use = shard_layout
+                    .shard_uids()
+                    .any(|shard_uid| get_shard_uid_mapping(&store, shard_uid) == parent);
+                if state_in_use {
+                    let has_state = store
+                        .iter_raw_bytes(DBCol::State)
+                        .any(|kv| kv.unwrap().0.starts_with(&parent.to_bytes()));
+                    assert!(has_state, "State of {parent} was removed while a child reads it");
+                }
+            }
+            done.set(true);
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
+/// Checks that a state snapshot requested while the flat storage of the children is still being
+/// split is taken within one block, and that it includes the children. The catchup of the
+/// children waits for a pending snapshot request, so it also checks that the flat head of every
+/// child catching up in the node advances within one block, i.e. that neither the snapshot nor
+/// the resharding waits for the other for more than one block.
+pub(crate) fn snapshot_during_flat_storage_split() -> LoopAction {
+    let latest_height = Cell::new(0);
+    // The pending snapshot request, with the height it was seen at and the children whose flat
+    // storage was being split at that point.
+    let pending_request = Cell::new(None::<(CryptoHash, u64)>);
+    let splitting_children = Cell::new(vec![]);
+    let snapshot_checked = Cell::new(false);
+    // The status of every child catching up in the node, with the height it was first seen at.
+    let catching_up_children = RefCell::new(HashMap::<ShardUId, (FlatStorageStatus, u64)>::new());
+
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            if done.get() {
+                return;
+            }
+
+            let client_actor =
+                retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
+            let client = &client_actor.client;
+            let tip = client.chain.head().unwrap();
+
+            // Run this action only once at every block height.
+            if latest_height.get() == tip.height {
+                return;
+            }
+            latest_height.set(tip.height);
+
+            let shard_layout = client.epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+            let flat_store = client.chain.chain_store().store().flat_store();
+            let children_status = shard_layout
+                .shard_uids()
+                .map(|shard_uid| {
+                    (shard_uid, flat_store.get_flat_storage_status(shard_uid).unwrap())
+                })
+                .collect_vec();
+
+            let mut catching_up_children = catching_up_children.borrow_mut();
+            catching_up_children.retain(|shard_uid, (status, _)| {
+                children_status.contains(&(*shard_uid, status.clone()))
+            });
+            for (shard_uid, status) in &children_status {
+                if !matches!(
+                    status,
+                    FlatStorageStatus::Resharding(FlatStorageReshardingStatus::CatchingUp(_))
+                ) {
+                    continue;
+                }
+                let (_, since_height) =
+                    catching_up_children.entry(*shard_uid).or_insert((status.clone(), tip.height));
+                assert!(
+                    tip.height <= *since_height + 1,
+                    "the flat storage of {shard_uid} is stuck at {status:?} since height {since_height}, now at height {}",
+                    tip.height
+                );
+            }
+
+            let flat_storage_manager = client.chain.runtime_adapter.get_flat_storage_manager();
+            let wanted_hash = flat_storage_manager.snapshot_hash_wanted();
+            match (pending_request.get(), wanted_hash) {
+                (Some((requested_hash, requested_height)), Some(wanted_hash))
+                    if requested_hash == wanted_hash =>
+                {
+                    assert!(
+                        tip.height <= requested_height + 1,
+                        "the state snapshot of {requested_hash} requested at height {requested_height} is still not taken at height {}",
+                        tip.height
+                    );
+                }
+                (_, Some(wanted_hash)) => {
+                    let children = children_status
+                        .iter()
+                        .filter(|(_, status)| {
+                            *status
+                                == FlatStorageStatus::Resharding(
+                                    FlatStorageReshardingStatus::CreatingChild,
+                                )
+                        })
+                        .map(|(shard_uid, _)| *shard_uid)
+                        .collect_vec();
+                    pending_request.set(Some((wanted_hash, tip.height)));
+                    splitting_children.set(children);
+                }
+                (Some((requested_hash, _)), None) => {
+                    pending_request.set(None);
+                    let children = splitting_children.take();
+                    if children.is_empty() {
+                        return;
+                    }
+                    let tries = client.chain.runtime_adapter.get_tries();
+                    for child in children {
+                        let status =
+                            tries.get_snapshot_flat_storage_status(requested_hash, child).unwrap();
+                        assert_matches!(status, FlatStorageStatus::Ready(_), "{child}");
+                    }
+                    snapshot_checked.set(true);
+                }
+                (None, None) => {}
+            }
+
+            // Done once the snapshot was checked and the flat storage of the children is ready.
+            let resharding_in_progress = children_status
+                .iter()
+                .any(|(_, status)| matches!(status, FlatStorageStatus::Resharding(_)));
+            if snapshot_checked.get() && !resharding_in_progress {
+                done.set(true);
+            }
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
 /// Repro case for the issue of 'Missing TrieValue' after GC period for refcounted trie nodes
 /// that are duplicated to both children during resharding. This particular scenario tests
 /// promise yield indices.
@@ -68,21 +68,23 @@ pub fn derive_new_epoch_config_from_boundary(
     epoch_config
 }
 
-/// Two protocol upgrades would happen as soon as possible,
-/// usually in two consecutive epochs, unless upgrade voting decides differently.
-pub fn two_upgrades_voting_schedule(
+/// `num_upgrades` protocol upgrades, up to `target_protocol_version`, would
+/// happen as soon as possible, usually in consecutive epochs, unless upgrade
+/// voting decides differently.
+pub fn consecutive_upgrades_voting_schedule(
     target_protocol_version: ProtocolVersion,
+    num_upgrades: u32,
 ) -> ProtocolUpgradeVotingSchedule {
-    let past_datetime_1 =
-        ProtocolUpgradeVotingSchedule::parse_datetime("1970-01-01 00:00:00").unwrap();
-    let past_datetime_2 =
-        ProtocolUpgradeVotingSchedule::parse_datetime("1970-01-02 00:00:00").unwrap();
-    let voting_schedule = vec![
-        (past_datetime_1, target_protocol_version - 1),
-        (past_datetime_2, target_protocol_version),
-    ];
+    let voting_schedule = (1..=num_upgrades)
+        .map(|i| {
+            let past_datetime =
+                ProtocolUpgradeVotingSchedule::parse_datetime(&format!("1970-01-{i:02} 00:00:00"))
+                    .unwrap();
+            (past_datetime, target_protocol_version - num_upgrades + i)
+        })
+        .collect();
     ProtocolUpgradeVotingSchedule::new_from_env_or_schedule(
-        target_protocol_version - 2,
+        target_protocol_version - num_upgrades,
         target_protocol_version,
         voting_schedule,
     )
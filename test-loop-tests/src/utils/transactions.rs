@@ -14,7 +14,7 @@ use near_async::test_loop::sender::TestLoopSender;
 use near_async::time::Duration;
 use near_chain::Error;
 use near_client::{Client, ProcessTxResponse, TxRequestHandler};
-use near_crypto::Signer;
+use near_crypto::{PublicKey, Signer};
 use near_network::client::ProcessTxRequest;
 use near_primitives::action::{GlobalContractDeployMode, GlobalContractIdentifier};
 use near_primitives::block::Tip;
@@ -69,16 +69,23 @@ pub fn get_next_nonce(
     account_id: &AccountId,
 ) -> u64 {
     let signer: Signer = create_user_test_signer(&account_id);
+    get_next_nonce_for_key(test_loop_data, node_datas, account_id, signer.public_key())
+}
+
+/// Get next available nonce for the given access key of the account.
+pub fn get_next_nonce_for_key(
+    test_loop_data: &TestLoopData,
+    node_datas: &[NodeExecutionData],
+    account_id: &AccountId,
+    public_key: PublicKey,
+) -> u64 {
     let clients = node_datas
         .iter()
         .map(|data| &test_loop_data.get(&data.client_sender.actor_handle()).client)
         .collect_vec();
     let response = clients.runtime_query(
         account_id,
-        QueryRequest::ViewAccessKey {
-            account_id: account_id.clone(),
-            public_key: signer.public_key(),
-        },
+        QueryRequest::ViewAccessKey { account_id: account_id.clone(), public_key },
     );
     let QueryResponseKind::AccessKey(access_key) = response.kind else {
         panic!("Expected AccessKey response");
@@ -714,6 +721,9 @@ impl TransactionRunner {
             ProcessTxResponse::DoesNotTrackShard => {
                 panic!("Transaction submitted to a node that doesn't track the shard")
             }
+            ProcessTxResponse::ForwardingOverloaded { shard_id } => {
+                panic!("Chunk producers of shard {shard_id} are overloaded")
+            }
         };
         Some(res)
     }
@@ -56,11 +56,9 @@ impl TrieSanityCheck {
         tip: &Tip,
         new_num_shards: NumShards,
     ) -> &mut EpochTrieCheck {
-        let protocol_version =
-            client.epoch_manager.get_epoch_protocol_version(&tip.epoch_id).unwrap();
         let shards_pending_resharding = client
             .epoch_manager
-            .get_shard_uids_pending_resharding(protocol_version, PROTOCOL_VERSION)
+            .get_shard_uids_pending_resharding(&tip.epoch_id, PROTOCOL_VERSION)
             .unwrap();
         let shard_layout = client.epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
         let is_resharded = shard_layout.num_shards() == new_num_shards;
@@ -211,11 +209,9 @@ fn assert_state_sanity(
     let is_resharded = shard_layout.num_shards() == new_num_shards;
     let mut checked_shards = Vec::new();
 
-    let protocol_version =
-        client.epoch_manager.get_epoch_protocol_version(&final_head.epoch_id).unwrap();
     let shards_pending_resharding = client
         .epoch_manager
-        .get_shard_uids_pending_resharding(protocol_version, PROTOCOL_VERSION)
+        .get_shard_uids_pending_resharding(&final_head.epoch_id, PROTOCOL_VERSION)
         .unwrap();
 
     for shard_uid in shard_layout.shard_uids() {
@@ -377,12 +373,15 @@ pub fn check_state_shard_uid_mapping_after_resharding(
     // Whether we found any value in DB for which we could test the mapping.
     let mut has_any_parent_shard_uid_prefix = false;
     let trie_store = store.trie_store();
+    // The parent itself is mapped to an ancestor if it was created by an earlier
+    // resharding.
+    let parent_shard_uid_prefix = get_shard_uid_mapping(&store, parent_shard_uid);
     for kv in store.iter_raw_bytes(DBCol::State) {
         let (key, value) = kv.unwrap();
         let shard_uid = ShardUId::try_from_slice(&key[0..8]).unwrap();
         // Just after resharding, no State data must be keyed using children ShardUIds.
         assert!(!shard_uid_mapping.contains_key(&shard_uid));
-        if shard_uid != parent_shard_uid {
+        if shard_uid != parent_shard_uid_prefix {
             continue;
         }
         has_any_parent_shard_uid_prefix = true;
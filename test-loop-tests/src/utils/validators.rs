@@ -1,4 +1,10 @@
+use std::collections::{BTreeMap, HashMap};
+
 use near_client::Client;
+use near_primitives::epoch_info::EpochInfo;
+use near_primitives::types::{
+    AccountId, BlockHeight, EpochId, ShardId, ValidatorKickoutReason, ValidatorStats,
+};
 
 /// Get all validator account names for the latest epoch.
 pub(crate) fn get_epoch_all_validators(client: &Client) -> Vec<String> {
@@ -7,3 +13,173 @@ pub(crate) fn get_epoch_all_validators(client: &Client) -> Vec<String> {
     let all_validators = client.epoch_manager.get_epoch_all_validators(&epoch_id).unwrap();
     all_validators.into_iter().map(|vs| vs.account_id().to_string()).collect()
 }
+
+/// Production stats of a single validator in an epoch, as expected by the test.
+#[derive(Default, Debug)]
+struct ExpectedValidatorStats {
+    blocks: ValidatorStats,
+    chunks: ValidatorStats,
+    endorsements: ValidatorStats,
+}
+
+/// Asserts that the validators kicked out for their performance in `epoch_id` are
+/// exactly the ones expected given the chunks dropped by the test.
+///
+/// `dropped_chunks` maps shard id to the heights at which the chunk of that shard was
+/// dropped. Every other chunk is assumed to be included in the block at its height and
+/// endorsed by all of its chunk validators.
+///
+/// The stats are computed independently of the epoch manager, and checked against the
+/// kickouts recorded in the epoch info of the epoch after next. Kickouts for other
+/// reasons (e.g. unstaking) are ignored. The epoch must already be finished.
+///
+/// The epoch must be configured with `validator_max_kickout_stake_perc` set to 100,
+/// so that no validator is exempted from the kickout.
+pub(crate) fn assert_kickouts_match_dropped_chunks(
+    client: &Client,
+    epoch_id: &EpochId,
+    dropped_chunks: &BTreeMap<ShardId, Vec<BlockHeight>>,
+) {
+    let epoch_manager = client.epoch_manager.as_ref();
+    let epoch_config = epoch_manager.get_epoch_config(epoch_id).unwrap();
+    assert_eq!(
+        epoch_config.validator_max_kickout_stake_perc, 100,
+        "kickout exemptions are not supported by the checker"
+    );
+    let epoch_info = epoch_manager.get_epoch_info(epoch_id).unwrap();
+    let shard_layout = epoch_manager.get_shard_layout(epoch_id).unwrap();
+    let genesis_height = client.chain.genesis().height();
+    let head_height = client.chain.head().unwrap().height;
+    let is_dropped = |shard_id: ShardId, height: BlockHeight| {
+        dropped_chunks.get(&shard_id).is_some_and(|heights| heights.contains(&height))
+    };
+
+    let mut stats: HashMap<AccountId, ExpectedValidatorStats> = epoch_info
+        .validators_iter()
+        .map(|validator| (validator.take_account_id(), ExpectedValidatorStats::default()))
+        .collect();
+    let mut last_block_hash = None;
+    let mut height = epoch_manager.get_epoch_start_from_epoch_id(epoch_id).unwrap();
+    loop {
+        assert!(height <= head_height, "epoch {epoch_id:?} is not finished yet");
+        let Ok(header) = client.chain.get_block_header_by_height(height) else {
+            // Skipped heights are accounted for by the next block.
+            height += 1;
+            continue;
+        };
+        if header.epoch_id() != epoch_id {
+            break;
+        }
+        last_block_hash = Some(*header.hash());
+        height += 1;
+        // The genesis block is not part of the epoch stats.
+        if header.height() == genesis_height {
+            continue;
+        }
+
+        let prev_height = client.chain.get_block_header(header.prev_hash()).unwrap().height();
+        for skipped_height in prev_height + 1..header.height() {
+            let producer = sampled_block_producer(&epoch_info, skipped_height);
+            stats.entry(producer).or_default().blocks.expected += 1;
+        }
+        let producer =
+            stats.entry(sampled_block_producer(&epoch_info, header.height())).or_default();
+        producer.blocks.expected += 1;
+        producer.blocks.produced += 1;
+
+        // Chunks are always attributed to the height following the previous block.
+        let chunk_height = prev_height + 1;
+        let chunk_validators = epoch_info.sample_chunk_validators(chunk_height);
+        for shard_info in shard_layout.shard_infos() {
+            let shard_id = shard_info.shard_id();
+            let produced = u64::from(!is_dropped(shard_id, chunk_height));
+            let producer_id =
+                epoch_info.sample_chunk_producer(&shard_layout, shard_id, chunk_height).unwrap();
+            let producer =
+                stats.entry(epoch_info.validator_account_id(producer_id).clone()).or_default();
+            producer.chunks.expected += 1;
+            producer.chunks.produced += produced;
+
+            let shard_validators =
+                chunk_validators.get(shard_info.shard_index()).map_or(&[][..], Vec::as_slice);
+            for (validator_id, _) in shard_validators {
+                let validator = stats
+                    .entry(epoch_info.validator_account_id(*validator_id).clone())
+                    .or_default();
+                validator.endorsements.expected += 1;
+                validator.endorsements.produced += produced;
+            }
+        }
+    }
+    let last_block_hash = last_block_hash.unwrap();
+
+    // Validators kicked out at the end of the previous epoch can't be spared below.
+    let next_epoch_id = epoch_manager.get_next_epoch_id(&last_block_hash).unwrap();
+    let next_epoch_info = epoch_manager.get_epoch_info(&next_epoch_id).unwrap();
+    let prev_kickout = next_epoch_info.validator_kickout();
+
+    let mut expected_kickout = HashMap::new();
+    let mut max_block_producer: Option<(AccountId, u64)> = None;
+    for validator in epoch_info.validators_iter() {
+        let account_id = validator.account_id();
+        let stats = &stats[account_id];
+        if !prev_kickout.contains_key(account_id)
+            && max_block_producer
+                .as_ref()
+                .is_none_or(|(_, produced)| stats.blocks.produced > *produced)
+        {
+            max_block_producer = Some((account_id.clone(), stats.blocks.produced));
+        }
+        let reason = if stats.blocks.less_than(epoch_config.block_producer_kickout_threshold) {
+            ValidatorKickoutReason::NotEnoughBlocks {
+                produced: stats.blocks.produced,
+                expected: stats.blocks.expected,
+            }
+        } else if stats.chunks.less_than(epoch_config.chunk_producer_kickout_threshold) {
+            ValidatorKickoutReason::NotEnoughChunks {
+                produced: stats.chunks.produced,
+                expected: stats.chunks.expected,
+            }
+        } else if stats.blocks.expected == 0
+            && stats.chunks.expected == 0
+            && stats.endorsements.less_than(epoch_config.chunk_validator_only_kickout_threshold)
+        {
+            ValidatorKickoutReason::NotEnoughChunkEndorsements {
+                produced: stats.endorsements.produced,
+                expected: stats.endorsements.expected,
+            }
+        } else {
+            continue;
+        };
+        expected_kickout.insert(account_id.clone(), reason);
+    }
+    // If every validator would be kicked out, the one which produced the most blocks stays.
+    let all_kicked_out = epoch_info.validators_iter().all(|validator| {
+        let account_id = validator.account_id();
+        expected_kickout.contains_key(account_id) || prev_kickout.contains_key(account_id)
+    });
+    if let Some((account_id, _)) = max_block_producer.filter(|_| all_kicked_out) {
+        expected_kickout.remove(&account_id);
+    }
+
+    let next_next_epoch_info = epoch_manager.get_epoch_info(&EpochId(last_block_hash)).unwrap();
+    let actual_kickout: HashMap<AccountId, ValidatorKickoutReason> = next_next_epoch_info
+        .validator_kickout()
+        .iter()
+        .filter(|(_, reason)| {
+            matches!(
+                reason,
+                ValidatorKickoutReason::NotEnoughBlocks { .. }
+                    | ValidatorKickoutReason::NotEnoughChunks { .. }
+                    | ValidatorKickoutReason::NotEnoughChunkEndorsements { .. }
+            )
+        })
+        .map(|(account_id, reason)| (account_id.clone(), reason.clone()))
+        .collect();
+    tracing::info!(target: "test", ?epoch_id, ?stats, ?actual_kickout, "Checking kickouts");
+    assert_eq!(actual_kickout, expected_kickout, "unexpected kickouts for epoch {epoch_id:?}");
+}
+
+fn sampled_block_producer(epoch_info: &EpochInfo, height: BlockHeight) -> AccountId {
+    epoch_info.validator_account_id(epoch_info.sample_block_producer(height)).clone()
+}
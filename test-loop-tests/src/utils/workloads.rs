@@ -0,0 +1,517 @@
+//! Reusable traffic generators for test loop tests.
+//!
+//! A [Workload] describes the transactions to send at every block height:
+//! which kind, from which signers to which receivers, how many per block and
+//! at which heights, possibly relative to a resharding. It turns into a
+//! [LoopAction], which also asserts that every transaction it sent eventually
+//! succeeded.
+//!
+//! Each workload keeps track of the nonces of its own signers, so the
+//! workloads running at the same time must not share signers.
+//!
+//! The contract workloads expect the test contract from
+//! `near_test_contracts::rs_contract()` to be deployed on the receivers.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use assert_matches::assert_matches;
+use itertools::Itertools;
+use near_async::test_loop::data::TestLoopData;
+use near_async::time::Duration;
+use near_chain::Error;
+use near_crypto::Signer;
+use near_primitives::hash::CryptoHash;
+use near_primitives::test_utils::create_user_test_signer;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{AccountId, BlockHeight, Gas, ShardId};
+use near_primitives::views::FinalExecutionStatus;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::setup::env::TestLoopEnv;
+use crate::setup::state::NodeExecutionData;
+use crate::utils::loop_action::{LoopAction, LoopActionStatus};
+use crate::utils::sharding::{next_block_has_new_shard_layout, next_epoch_has_new_shard_layout};
+use crate::utils::transactions::{get_anchor_hash, get_next_nonce, submit_tx};
+use crate::utils::{ONE_NEAR, TGAS, retrieve_client_actor};
+
+/// Default number of blocks after which the outcome of a transaction must be
+/// known.
+const TX_CHECK_DEADLINE: BlockHeight = 5;
+
+/// Gas attached to every hop of a cross shard call chain.
+const GAS_PER_HOP: Gas = 20 * TGAS;
+
+/// Kind of the transactions sent by a [Workload].
+#[derive(Clone, Debug)]
+pub(crate) enum WorkloadKind {
+    /// Money transfers from the signers to the receivers.
+    Transfers,
+    /// Calls of a method of the contracts deployed on the receivers, e.g.
+    /// `ft_transfer` of token contracts.
+    ContractCalls { method_name: String, args: Vec<u8>, gas: Gas },
+    /// Calls of `write_key_value` of the test contract, each writing a new key.
+    StorageWrites,
+    /// Calls of `call_promise` of the test contract going through `hops`
+    /// receivers, chosen at random, one after another.
+    CrossShardCallChains { hops: usize },
+}
+
+/// Block height at which a [Workload] starts sending transactions.
+///
+/// The resharding height is the height of the last block of the old shard
+/// layout.
+#[derive(Clone, Copy, Debug)]
+enum WorkloadStart {
+    /// At the first height the workload is called at.
+    Immediately,
+    /// At most this many blocks before the resharding height.
+    BeforeResharding(u64),
+    /// This many blocks after the resharding height.
+    AfterResharding(u64),
+}
+
+/// Traffic sent at every block height, see the module documentation.
+pub(crate) struct Workload {
+    kind: WorkloadKind,
+    signers: Vec<AccountId>,
+    receivers: Vec<AccountId>,
+    txs_per_block: usize,
+    /// Every signer sends one transaction to the receiver at the same index,
+    /// instead of `txs_per_block` random pairs.
+    paired: bool,
+    /// Only the signers on these shards send transactions.
+    target_shards: Option<Vec<ShardId>>,
+    start: WorkloadStart,
+    /// Number of block heights to send transactions at. The transactions are
+    /// sent until the end of the test if neither this nor
+    /// `stop_after_resharding` is set.
+    num_blocks: Option<u64>,
+    /// Number of blocks after the resharding height to send transactions at.
+    stop_after_resharding: Option<u64>,
+    check_outcomes: bool,
+    /// Number of blocks after which the outcome of a transaction must be known.
+    outcome_deadline: BlockHeight,
+    /// Seed of the random choices of the transactions, the seed of the test
+    /// loop if not set.
+    seed: Option<u64>,
+}
+
+impl Workload {
+    fn new(kind: WorkloadKind, signers: Vec<AccountId>, receivers: Vec<AccountId>) -> Self {
+        assert!(!signers.is_empty() && !receivers.is_empty(), "Workload without accounts");
+        Self {
+            kind,
+            signers,
+            receivers,
+            txs_per_block: 5,
+            paired: false,
+            target_shards: None,
+            start: WorkloadStart::Immediately,
+            num_blocks: None,
+            stop_after_resharding: None,
+            check_outcomes: true,
+            outcome_deadline: TX_CHECK_DEADLINE,
+            seed: None,
+        }
+    }
+
+    /// Transfers between the given accounts.
+    pub fn transfers(accounts: Vec<AccountId>) -> Self {
+        Self::new(WorkloadKind::Transfers, accounts.clone(), accounts)
+    }
+
+    pub fn contract_calls(
+        signers: Vec<AccountId>,
+        contracts: Vec<AccountId>,
+        method_name: &str,
+        args: Vec<u8>,
+        gas: Gas,
+    ) -> Self {
+        let kind = WorkloadKind::ContractCalls { method_name: method_name.to_string(), args, gas };
+        Self::new(kind, signers, contracts)
+    }
+
+    pub fn storage_writes(signers: Vec<AccountId>, contracts: Vec<AccountId>) -> Self {
+        Self::new(WorkloadKind::StorageWrites, signers, contracts)
+    }
+
+    pub fn cross_shard_call_chains(
+        signers: Vec<AccountId>,
+        contracts: Vec<AccountId>,
+        hops: usize,
+    ) -> Self {
+        // The gas of all the hops must fit into the gas of the transaction.
+        assert!(0 < hops && hops <= 10, "Unsupported number of hops {hops}");
+        Self::new(WorkloadKind::CrossShardCallChains { hops }, signers, contracts)
+    }
+
+    pub fn txs_per_block(mut self, txs_per_block: usize) -> Self {
+        self.txs_per_block = txs_per_block;
+        self
+    }
+
+    /// Makes every signer send one transaction per block height to the
+    /// receiver at the same index, e.g. to resume the promises it yielded.
+    pub fn paired(mut self) -> Self {
+        assert_eq!(self.signers.len(), self.receivers.len(), "Unpaired signers and receivers");
+        self.paired = true;
+        self
+    }
+
+    /// Sends the transactions only from the signers on the given shards of
+    /// the current shard layout.
+    pub fn target_shards(mut self, shard_ids: Vec<ShardId>) -> Self {
+        self.target_shards = Some(shard_ids);
+        self
+    }
+
+    pub fn num_blocks(mut self, num_blocks: u64) -> Self {
+        self.num_blocks = Some(num_blocks);
+        self
+    }
+
+    /// Starts sending the transactions when the chain is at most
+    /// `num_blocks` blocks before the resharding height, i.e. the height of
+    /// the last block of the old shard layout.
+    pub fn start_before_resharding(mut self, num_blocks: u64) -> Self {
+        self.start = WorkloadStart::BeforeResharding(num_blocks);
+        self
+    }
+
+    /// Starts sending the transactions `num_blocks` blocks after the
+    /// resharding height.
+    pub fn start_after_resharding(mut self, num_blocks: u64) -> Self {
+        self.start = WorkloadStart::AfterResharding(num_blocks);
+        self
+    }
+
+    /// Stops sending the transactions `num_blocks` blocks after the
+    /// resharding height.
+    pub fn stop_after_resharding(mut self, num_blocks: u64) -> Self {
+        self.stop_after_resharding = Some(num_blocks);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Doesn't check the outcomes of the transactions, e.g. when some of them
+    /// are expected to be dropped.
+    pub fn skip_outcome_checks(mut self) -> Self {
+        self.check_outcomes = false;
+        self
+    }
+
+    /// Sets the number of blocks after which the transactions must have
+    /// succeeded, e.g. when they are delayed on congested shards.
+    pub fn outcome_deadline(mut self, num_blocks: BlockHeight) -> Self {
+        self.outcome_deadline = num_blocks;
+        self
+    }
+
+    /// Whether the workload stops sending transactions at some point.
+    fn is_limited(&self) -> bool {
+        self.num_blocks.is_some() || self.stop_after_resharding.is_some()
+    }
+
+    /// Returns the loop action sending the transactions.
+    ///
+    /// The outcomes are read from the client the action is called for, so
+    /// with outcome checks that client must track all the shards. The action
+    /// succeeds once all the transactions are sent and succeeded, or once the
+    /// first ones are sent if the number of blocks isn't limited.
+    pub fn into_loop_action(self) -> LoopAction {
+        let first_height = Cell::new(None);
+        let latest_height = Cell::new(0);
+        let resharding_height = Cell::new(None);
+        let next_nonces = RefCell::new(HashMap::<AccountId, u64>::new());
+        let txs = Cell::new(Vec::<(CryptoHash, BlockHeight)>::new());
+        let rng = RefCell::new(None);
+        let (done, succeeded) = LoopAction::shared_success_flag();
+
+        let action_fn = Box::new(
+            move |node_datas: &[NodeExecutionData],
+                  test_loop_data: &mut TestLoopData,
+                  client_account_id: AccountId| {
+                let client_actor =
+                    retrieve_client_actor(node_datas, test_loop_data, &client_account_id);
+                let tip = client_actor.client.chain.head().unwrap();
+
+                // Run this action only once at every block height.
+                if latest_height.get() == tip.height {
+                    return;
+                }
+                latest_height.set(tip.height);
+                let epoch_manager = client_actor.client.epoch_manager.as_ref();
+                if resharding_height.get().is_none()
+                    && next_block_has_new_shard_layout(epoch_manager, &tip)
+                {
+                    tracing::debug!(target: "test", height=tip.height, "resharding height set");
+                    resharding_height.set(Some(tip.height));
+                }
+
+                let start_height = match first_height.get() {
+                    Some(start_height) => start_height,
+                    None => {
+                        let started = match self.start {
+                            WorkloadStart::Immediately => true,
+                            WorkloadStart::BeforeResharding(num_blocks) => {
+                                let epoch_start = epoch_manager
+                                    .get_epoch_start_height(&tip.last_block_hash)
+                                    .unwrap();
+                                let epoch_length = client_actor.client.config.epoch_length;
+                                next_epoch_has_new_shard_layout(epoch_manager, &tip)
+                                    && tip.height + num_blocks + 1 >= epoch_start + epoch_length
+                            }
+                            WorkloadStart::AfterResharding(num_blocks) => resharding_height
+                                .get()
+                                .is_some_and(|height| tip.height >= height + num_blocks),
+                        };
+                        if !started {
+                            return;
+                        }
+                        first_height.set(Some(tip.height));
+                        tip.height
+                    }
+                };
+
+                if self.check_outcomes {
+                    let chain = &client_actor.client.chain;
+                    let remaining_txs = txs.take().into_iter().filter(|(tx_hash, tx_height)| {
+                        let status =
+                            chain.get_partial_transaction_result(tx_hash).map(|o| o.status);
+                        if tx_height + self.outcome_deadline < tip.height {
+                            assert_matches!(
+                                status,
+                                Ok(FinalExecutionStatus::SuccessValue(_)),
+                                "transaction {tx_hash} sent at height {tx_height} didn't succeed"
+                            );
+                            return false;
+                        }
+                        match status {
+                            Ok(FinalExecutionStatus::SuccessValue(_)) => false,
+                            Ok(
+                                FinalExecutionStatus::NotStarted | FinalExecutionStatus::Started,
+                            )
+                            | Err(Error::DBNotFoundErr(_)) => true,
+                            _ => panic!("transaction {tx_hash} failed: {status:?}"),
+                        }
+                    });
+                    txs.set(remaining_txs.collect());
+                }
+
+                let finished = self.num_blocks.is_some_and(|n| tip.height >= start_height + n)
+                    || self.stop_after_resharding.is_some_and(|n| {
+                        resharding_height.get().is_some_and(|height| tip.height > height + n)
+                    });
+                if finished {
+                    let all_checked = !self.check_outcomes || {
+                        let pending = txs.take();
+                        let all_checked = pending.is_empty();
+                        txs.set(pending);
+                        all_checked
+                    };
+                    done.set(all_checked);
+                    return;
+                }
+
+                let shard_layout = epoch_manager.get_shard_layout(&tip.epoch_id).unwrap();
+                let is_target_signer = |signer: &AccountId| {
+                    self.target_shards.as_ref().is_none_or(|shard_ids| {
+                        shard_ids.contains(&shard_layout.account_id_to_shard_id(signer))
+                    })
+                };
+                let signers =
+                    self.signers.iter().filter(|signer| is_target_signer(*signer)).collect_vec();
+                assert!(!signers.is_empty(), "No signer on the target shards");
+
+                let clients = node_datas
+                    .iter()
+                    .map(|data| &test_loop_data.get(&data.client_sender.actor_handle()).client)
+                    .collect_vec();
+                let anchor_hash = get_anchor_hash(&clients);
+                let mut rng = rng.borrow_mut();
+                let rng = rng.get_or_insert_with(|| {
+                    let seed = self.seed.unwrap_or(test_loop_data.seed());
+                    tracing::info!(target: "test", kind = ?self.kind, seed, "workload random seed");
+                    ChaCha20Rng::seed_from_u64(seed)
+                });
+                let pairs = if self.paired {
+                    self.signers
+                        .iter()
+                        .zip(&self.receivers)
+                        .filter(|(signer, _)| is_target_signer(*signer))
+                        .map(|(signer, receiver)| (signer.clone(), receiver.clone()))
+                        .collect_vec()
+                } else {
+                    (0..self.txs_per_block)
+                        .map(|_| {
+                            let signer = (*signers.choose(rng).unwrap()).clone();
+                            (signer, self.receivers.choose(rng).unwrap().clone())
+                        })
+                        .collect_vec()
+                };
+                let mut sent_txs = txs.take();
+                for (signer_id, receiver_id) in pairs {
+                    // Other transactions of the signer may have been sent
+                    // since the last transaction of this workload.
+                    let mut next_nonces = next_nonces.borrow_mut();
+                    let next_nonce = next_nonces.entry(signer_id.clone()).or_default();
+                    let nonce =
+                        (*next_nonce).max(get_next_nonce(test_loop_data, node_datas, &signer_id));
+                    *next_nonce = nonce + 1;
+
+                    let tx =
+                        self.make_tx(rng, nonce, signer_id, receiver_id, tip.height, anchor_hash);
+                    sent_txs.push((tx.get_hash(), tip.height));
+                    submit_tx(node_datas, &client_account_id, tx);
+                }
+                txs.set(if self.check_outcomes { sent_txs } else { vec![] });
+                if !self.is_limited() {
+                    done.set(true);
+                }
+            },
+        );
+        LoopAction::new(action_fn, succeeded)
+    }
+
+    fn make_tx(
+        &self,
+        rng: &mut ChaCha20Rng,
+        nonce: u64,
+        signer_id: AccountId,
+        receiver_id: AccountId,
+        height: BlockHeight,
+        anchor_hash: CryptoHash,
+    ) -> SignedTransaction {
+        let signer: Signer = create_user_test_signer(&signer_id).into();
+        match &self.kind {
+            WorkloadKind::Transfers => {
+                let amount = ONE_NEAR * rng.gen_range(1..=10);
+                SignedTransaction::send_money(
+                    nonce,
+                    signer_id,
+                    receiver_id,
+                    &signer,
+                    amount,
+                    anchor_hash,
+                )
+            }
+            WorkloadKind::ContractCalls { method_name, args, gas } => SignedTransaction::call(
+                nonce,
+                signer_id,
+                receiver_id,
+                &signer,
+                0,
+                method_name.clone(),
+                args.clone(),
+                *gas,
+                anchor_hash,
+            ),
+            WorkloadKind::StorageWrites => {
+                // The key is unique to the transaction, so that every call
+                // adds a new value to the state.
+                let args = near_primitives::test_utils::encode(&[rng.r#gen(), height]);
+                SignedTransaction::call(
+                    nonce,
+                    signer_id,
+                    receiver_id,
+                    &signer,
+                    0,
+                    "write_key_value".to_string(),
+                    args,
+                    20 * TGAS,
+                    anchor_hash,
+                )
+            }
+            WorkloadKind::CrossShardCallChains { hops } => {
+                let hops = (1..*hops).map(|_| self.receivers.choose(rng).unwrap()).collect_vec();
+                let args = serde_json::to_vec(&call_chain_promises(&hops)).unwrap();
+                SignedTransaction::call(
+                    nonce,
+                    signer_id,
+                    receiver_id,
+                    &signer,
+                    0,
+                    "call_promise".to_string(),
+                    args,
+                    GAS_PER_HOP * (hops.len() as u64 + 2),
+                    anchor_hash,
+                )
+            }
+        }
+    }
+}
+
+/// Returns the arguments of `call_promise` calling `call_promise` on each of
+/// `contracts` in turn, and ending with `noop` on the last one.
+fn call_chain_promises(contracts: &[&AccountId]) -> serde_json::Value {
+    let Some((contract_id, next_contracts)) = contracts.split_first() else {
+        return serde_json::json!([]);
+    };
+    let (method_name, arguments) = if next_contracts.is_empty() {
+        ("noop", serde_json::json!([]))
+    } else {
+        ("call_promise", call_chain_promises(next_contracts))
+    };
+    serde_json::json!([{
+        "create": {
+            "account_id": contract_id.as_str(),
+            "method_name": method_name,
+            "arguments": arguments,
+            "amount": "0",
+            "gas": GAS_PER_HOP * (next_contracts.len() as u64 + 1),
+        },
+        "id": 0,
+    }])
+}
+
+/// Returns a loop action running all the workloads at the same time, which
+/// succeeds once all of them succeeded.
+pub(crate) fn workloads_into_loop_action(workloads: Vec<Workload>) -> LoopAction {
+    let actions = workloads.into_iter().map(Workload::into_loop_action).collect_vec();
+    let (done, succeeded) = LoopAction::shared_success_flag();
+    let action_fn = Box::new(
+        move |node_datas: &[NodeExecutionData],
+              test_loop_data: &mut TestLoopData,
+              client_account_id: AccountId| {
+            for action in &actions {
+                action.call(node_datas, test_loop_data, client_account_id.clone());
+            }
+            done.set(
+                actions
+                    .iter()
+                    .all(|action| matches!(action.get_status(), LoopActionStatus::Succeeded)),
+            );
+        },
+    );
+    LoopAction::new(action_fn, succeeded)
+}
+
+/// Runs the test loop until all the workloads succeed, sending their
+/// transactions to `rpc_id`, which should track all the shards.
+pub(crate) fn run_workloads(
+    env: &mut TestLoopEnv,
+    rpc_id: &AccountId,
+    workloads: Vec<Workload>,
+    maximum_duration: Duration,
+) {
+    assert!(
+        workloads.iter().all(Workload::is_limited),
+        "Only workloads with a limited number of blocks can finish"
+    );
+    let action = workloads_into_loop_action(workloads);
+    let node_datas = &env.node_datas;
+    env.test_loop.run_until(
+        |test_loop_data| {
+            action.call(node_datas, test_loop_data, rpc_id.clone());
+            matches!(action.get_status(), LoopActionStatus::Succeeded)
+        },
+        maximum_duration,
+    );
+}
@@ -0,0 +1,489 @@
+//! Invariants which must hold for every block of a live chain, and for every
+//! pair of consecutive blocks.
+use std::fmt;
+use std::sync::Arc;
+
+use near_chain_configs::ProtocolConfigView;
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::num_rational::Rational32;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::{Balance, Gas};
+use near_primitives::version::{ProtocolFeature, ProtocolVersion};
+use near_primitives::views::BlockView;
+
+/// Parameters of an epoch which the invariants depend on.
+pub(crate) struct EpochParams {
+    pub protocol_version: ProtocolVersion,
+    pub shard_layout: ShardLayout,
+    pub min_gas_price: Balance,
+    pub max_gas_price: Balance,
+    pub gas_price_adjustment_rate: Rational32,
+}
+
+impl From<ProtocolConfigView> for EpochParams {
+    fn from(config: ProtocolConfigView) -> Self {
+        Self {
+            protocol_version: config.protocol_version,
+            shard_layout: config.shard_layout,
+            min_gas_price: config.min_gas_price,
+            max_gas_price: config.max_gas_price,
+            gas_price_adjustment_rate: config.gas_price_adjustment_rate,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Invariant {
+    /// The total supply changes only by the tokens burnt in the new chunks,
+    /// and by the tokens minted at the start of an epoch.
+    SupplyConservation,
+    /// The chunk mask marks exactly the new chunks of the block, and only
+    /// those are endorsed.
+    ChunkMask,
+    /// A missing chunk carries over the header of the previous chunk of the
+    /// shard, and with it the congestion info.
+    Congestion,
+    /// The gas price follows the demand based adjustment. With the congestion
+    /// gas price floor it may only be raised above it.
+    GasPrice,
+    /// A new epoch keeps the shard layout or splits a single shard.
+    ShardLayoutTransition,
+}
+
+impl Invariant {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::SupplyConservation => "supply_conservation",
+            Self::ChunkMask => "chunk_mask",
+            Self::Congestion => "congestion",
+            Self::GasPrice => "gas_price",
+            Self::ShardLayoutTransition => "shard_layout_transition",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Violation {
+    pub invariant: Invariant,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.invariant.name(), self.message)
+    }
+}
+
+/// Checks the invariants of the streamed blocks. The cross-block invariants
+/// are only checked when the block directly follows the previous one.
+#[derive(Default)]
+pub(crate) struct InvariantChecker {
+    prev: Option<(BlockView, Arc<EpochParams>)>,
+}
+
+impl InvariantChecker {
+    /// Forgets the previous block, e.g. after failing to get the next one.
+    pub(crate) fn reset(&mut self) {
+        self.prev = None;
+    }
+
+    /// Checks the block with the parameters of its epoch and returns the
+    /// violated invariants.
+    pub(crate) fn check_block(
+        &mut self,
+        block: &BlockView,
+        epoch_params: Arc<EpochParams>,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        // The genesis block doesn't follow the rules of the regular blocks.
+        if block.header.prev_hash != CryptoHash::default() {
+            check_chunks(block, &epoch_params, &mut violations);
+            match &self.prev {
+                Some((prev_block, prev_epoch_params))
+                    if prev_block.header.hash == block.header.prev_hash =>
+                {
+                    check_supply(prev_block, block, &mut violations);
+                    check_gas_price(prev_block, block, &epoch_params, &mut violations);
+                    if prev_block.header.epoch_id == block.header.epoch_id {
+                        check_missing_chunks(prev_block, block, &mut violations);
+                    } else if !epoch_params.shard_layout.can_follow(&prev_epoch_params.shard_layout)
+                    {
+                        violations.push(Violation {
+                            invariant: Invariant::ShardLayoutTransition,
+                            message: format!(
+                                "shard layout {:?} can't follow {:?}",
+                                epoch_params.shard_layout, prev_epoch_params.shard_layout
+                            ),
+                        });
+                    }
+                }
+                _ => {
+                    tracing::debug!(
+                        target: crate::CANARY,
+                        height = block.header.height,
+                        "Previous block is not known, skipping cross-block invariants"
+                    );
+                }
+            }
+        }
+        self.prev = Some((block.clone(), epoch_params));
+        violations
+    }
+}
+
+fn check_chunks(block: &BlockView, epoch_params: &EpochParams, violations: &mut Vec<Violation>) {
+    let header = &block.header;
+    let mut violation =
+        |message| violations.push(Violation { invariant: Invariant::ChunkMask, message });
+    let num_shards = epoch_params.shard_layout.num_shards() as usize;
+    if header.chunk_mask.len() != num_shards || block.chunks.len() != num_shards {
+        violation(format!(
+            "expected {num_shards} chunks, got chunk mask of {} and {} chunks",
+            header.chunk_mask.len(),
+            block.chunks.len()
+        ));
+        return;
+    }
+    if let Some(endorsements) = &header.chunk_endorsements {
+        if endorsements.len() != num_shards {
+            violation(format!(
+                "expected endorsements for {num_shards} shards, got {}",
+                endorsements.len()
+            ));
+            return;
+        }
+    }
+
+    for (shard_index, chunk) in block.chunks.iter().enumerate() {
+        let expected_shard_id = epoch_params.shard_layout.get_shard_id(shard_index).ok();
+        if expected_shard_id != Some(chunk.shard_id) {
+            violation(format!("chunk of shard {} at index {shard_index}", chunk.shard_id));
+        }
+        let is_new_chunk = chunk.is_new_chunk(header.height);
+        if header.chunk_mask[shard_index] != is_new_chunk {
+            violation(format!(
+                "chunk mask of shard {} is {}, but the chunk was included at height {}",
+                chunk.shard_id, header.chunk_mask[shard_index], chunk.height_included
+            ));
+        }
+        let Some(endorsements) = &header.chunk_endorsements else {
+            continue;
+        };
+        let is_endorsed = endorsements[shard_index].iter().any(|byte| *byte != 0);
+        if is_endorsed != header.chunk_mask[shard_index] {
+            violation(format!(
+                "chunk mask of shard {} is {}, but the endorsements are {:?}",
+                chunk.shard_id, header.chunk_mask[shard_index], endorsements[shard_index]
+            ));
+        }
+    }
+}
+
+fn check_supply(prev_block: &BlockView, block: &BlockView, violations: &mut Vec<Violation>) {
+    let balance_burnt: Balance = block
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.is_new_chunk(block.header.height))
+        .map(|chunk| chunk.balance_burnt)
+        .sum();
+    let prev_total_supply = prev_block.header.total_supply;
+    let total_supply = block.header.total_supply;
+    // Rewards are only minted in the first block of an epoch.
+    let is_epoch_start = prev_block.header.epoch_id != block.header.epoch_id;
+    let is_valid = if is_epoch_start {
+        total_supply + balance_burnt >= prev_total_supply
+    } else {
+        total_supply + balance_burnt == prev_total_supply
+    };
+    if !is_valid {
+        violations.push(Violation {
+            invariant: Invariant::SupplyConservation,
+            message: format!(
+                "total supply changed from {prev_total_supply} to {total_supply} with {balance_burnt} burnt"
+            ),
+        });
+    }
+}
+
+fn check_gas_price(
+    prev_block: &BlockView,
+    block: &BlockView,
+    epoch_params: &EpochParams,
+    violations: &mut Vec<Violation>,
+) {
+    let new_chunks = block
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.is_new_chunk(block.header.height))
+        .collect::<Vec<_>>();
+    let gas_used: Gas = new_chunks.iter().map(|chunk| chunk.gas_used).sum();
+    let gas_limit: Gas = new_chunks.iter().map(|chunk| chunk.gas_limit).sum();
+    let expected_gas_price = Block::compute_next_gas_price(
+        prev_block.header.gas_price,
+        gas_used,
+        gas_limit,
+        epoch_params.gas_price_adjustment_rate,
+        epoch_params.min_gas_price,
+        epoch_params.max_gas_price,
+    );
+    let gas_price = block.header.gas_price;
+    let is_valid =
+        if ProtocolFeature::CongestionGasPriceFloor.enabled(epoch_params.protocol_version) {
+            expected_gas_price <= gas_price && gas_price <= epoch_params.max_gas_price
+        } else {
+            expected_gas_price == gas_price
+        };
+    if !is_valid {
+        violations.push(Violation {
+            invariant: Invariant::GasPrice,
+            message: format!(
+                "gas price changed from {} to {gas_price}, expected {expected_gas_price} with {gas_used} gas used out of {gas_limit}",
+                prev_block.header.gas_price
+            ),
+        });
+    }
+}
+
+fn check_missing_chunks(
+    prev_block: &BlockView,
+    block: &BlockView,
+    violations: &mut Vec<Violation>,
+) {
+    for (chunk, prev_chunk) in block.chunks.iter().zip(prev_block.chunks.iter()) {
+        if chunk.is_new_chunk(block.header.height) || chunk.chunk_hash == prev_chunk.chunk_hash {
+            continue;
+        }
+        violations.push(Violation {
+            invariant: Invariant::Congestion,
+            message: format!(
+                "chunk of shard {} is missing, but its header {} differs from the previous one {} (congestion info {:?} and {:?})",
+                chunk.shard_id,
+                chunk.chunk_hash,
+                prev_chunk.chunk_hash,
+                chunk.congestion_info,
+                prev_chunk.congestion_info
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use near_primitives::hash::{CryptoHash, hash};
+    use near_primitives::num_rational::Rational32;
+    use near_primitives::shard_layout::ShardLayout;
+    use near_primitives::types::{BlockHeight, ShardId};
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_primitives::views::{BlockHeaderView, BlockView, ChunkHeaderView};
+
+    use super::{EpochParams, Invariant, InvariantChecker};
+
+    const GAS_LIMIT: u64 = 1000;
+    const BALANCE_BURNT: u128 = 10;
+
+    fn epoch_params(shard_layout: ShardLayout) -> Arc<EpochParams> {
+        Arc::new(EpochParams {
+            protocol_version: PROTOCOL_VERSION,
+            shard_layout,
+            min_gas_price: 100,
+            max_gas_price: 10_000,
+            gas_price_adjustment_rate: Rational32::new(1, 100),
+        })
+    }
+
+    fn chunk_view(shard_id: ShardId, height_included: BlockHeight) -> ChunkHeaderView {
+        ChunkHeaderView {
+            chunk_hash: hash(format!("chunk {shard_id} {height_included}").as_bytes()),
+            prev_block_hash: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            prev_state_root: CryptoHash::default(),
+            encoded_merkle_root: CryptoHash::default(),
+            encoded_length: 0,
+            height_created: height_included,
+            height_included,
+            shard_id,
+            // Half of the gas limit keeps the gas price as is.
+            gas_used: GAS_LIMIT / 2,
+            gas_limit: GAS_LIMIT,
+            rent_paid: 0,
+            validator_reward: 0,
+            balance_burnt: BALANCE_BURNT,
+            outgoing_receipts_root: CryptoHash::default(),
+            tx_root: CryptoHash::default(),
+            validator_proposals: vec![],
+            congestion_info: None,
+            bandwidth_requests: None,
+            signature: Default::default(),
+        }
+    }
+
+    /// Returns a block with new and endorsed chunks for all the shards.
+    fn block_view(
+        height: BlockHeight,
+        prev_hash: CryptoHash,
+        epoch_id: CryptoHash,
+        shard_layout: &ShardLayout,
+        total_supply: u128,
+    ) -> BlockView {
+        let num_shards = shard_layout.num_shards() as usize;
+        let chunks = (0..num_shards)
+            .map(|shard_index| chunk_view(shard_layout.get_shard_id(shard_index).unwrap(), height))
+            .collect();
+        let header = BlockHeaderView {
+            height,
+            prev_height: Some(height - 1),
+            epoch_id,
+            next_epoch_id: epoch_id,
+            hash: hash(format!("block {height}").as_bytes()),
+            prev_hash,
+            prev_state_root: CryptoHash::default(),
+            block_body_hash: None,
+            chunk_receipts_root: CryptoHash::default(),
+            chunk_headers_root: CryptoHash::default(),
+            chunk_tx_root: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            chunks_included: num_shards as u64,
+            challenges_root: CryptoHash::default(),
+            timestamp: 0,
+            timestamp_nanosec: 0,
+            random_value: CryptoHash::default(),
+            validator_proposals: vec![],
+            chunk_mask: vec![true; num_shards],
+            gas_price: 1000,
+            block_ordinal: Some(height),
+            rent_paid: 0,
+            validator_reward: 0,
+            total_supply,
+            challenges_result: vec![],
+            last_final_block: CryptoHash::default(),
+            last_ds_final_block: CryptoHash::default(),
+            next_bp_hash: CryptoHash::default(),
+            block_merkle_root: CryptoHash::default(),
+            epoch_sync_data_hash: None,
+            approvals: vec![],
+            signature: Default::default(),
+            latest_protocol_version: PROTOCOL_VERSION,
+            chunk_endorsements: Some(vec![vec![1]; num_shards]),
+        };
+        BlockView { author: "test0".parse().unwrap(), header, chunks }
+    }
+
+    /// Returns the next block in the same epoch, which burns the tokens of
+    /// its chunks.
+    fn next_block_view(prev_block: &BlockView, shard_layout: &ShardLayout) -> BlockView {
+        let num_shards = shard_layout.num_shards() as u128;
+        block_view(
+            prev_block.header.height + 1,
+            prev_block.header.hash,
+            prev_block.header.epoch_id,
+            shard_layout,
+            prev_block.header.total_supply - BALANCE_BURNT * num_shards,
+        )
+    }
+
+    /// Checks the blocks in turn and returns all the violated invariants.
+    fn check_blocks(blocks: &[(&BlockView, &Arc<EpochParams>)]) -> Vec<Invariant> {
+        let mut checker = InvariantChecker::default();
+        blocks
+            .iter()
+            .flat_map(|(block, epoch_params)| checker.check_block(block, Arc::clone(epoch_params)))
+            .map(|violation| violation.invariant)
+            .collect()
+    }
+
+    fn shard_layout() -> ShardLayout {
+        ShardLayout::v2(vec!["b".parse().unwrap()], vec![ShardId::new(0), ShardId::new(1)], None)
+    }
+
+    /// Returns two valid consecutive blocks and the parameters of their epoch.
+    fn two_blocks() -> (BlockView, BlockView, Arc<EpochParams>) {
+        let shard_layout = shard_layout();
+        let first = block_view(1, hash(b"genesis"), hash(b"epoch"), &shard_layout, 1_000_000);
+        let second = next_block_view(&first, &shard_layout);
+        (first, second, epoch_params(shard_layout))
+    }
+
+    #[test]
+    fn test_supply_conservation() {
+        let (first, mut second, params) = two_blocks();
+        assert_eq!(check_blocks(&[(&first, &params), (&second, &params)]), vec![]);
+
+        second.header.total_supply += 1;
+        assert_eq!(
+            check_blocks(&[(&first, &params), (&second, &params)]),
+            vec![Invariant::SupplyConservation]
+        );
+    }
+
+    #[test]
+    fn test_chunk_mask() {
+        let (first, mut second, params) = two_blocks();
+        assert_eq!(check_blocks(&[(&first, &params), (&second, &params)]), vec![]);
+
+        // The new chunk of the first shard is endorsed but not in the mask.
+        second.header.chunk_mask[0] = false;
+        assert_eq!(
+            check_blocks(&[(&first, &params), (&second, &params)]),
+            vec![Invariant::ChunkMask, Invariant::ChunkMask]
+        );
+    }
+
+    #[test]
+    fn test_missing_chunk_congestion() {
+        let (first, mut second, params) = two_blocks();
+        // The missing chunk of the first shard carries over its previous header.
+        second.chunks[0] = first.chunks[0].clone();
+        second.header.chunk_mask[0] = false;
+        second.header.chunk_endorsements.as_mut().unwrap()[0] = vec![0];
+        second.header.total_supply += BALANCE_BURNT;
+        assert_eq!(check_blocks(&[(&first, &params), (&second, &params)]), vec![]);
+
+        second.chunks[0].chunk_hash = hash(b"other chunk");
+        assert_eq!(
+            check_blocks(&[(&first, &params), (&second, &params)]),
+            vec![Invariant::Congestion]
+        );
+    }
+
+    #[test]
+    fn test_gas_price() {
+        let (first, mut second, params) = two_blocks();
+        assert_eq!(check_blocks(&[(&first, &params), (&second, &params)]), vec![]);
+
+        // Half of the gas limit was used, so the gas price can't go down.
+        second.header.gas_price -= 1;
+        assert_eq!(
+            check_blocks(&[(&first, &params), (&second, &params)]),
+            vec![Invariant::GasPrice]
+        );
+    }
+
+    #[test]
+    fn test_shard_layout_transition() {
+        let (first, _, params) = two_blocks();
+        let check_next_epoch = |shard_layout: ShardLayout| {
+            // The rewards minted at the start of the epoch cover the burnt tokens.
+            let total_supply = first.header.total_supply;
+            let second =
+                block_view(2, first.header.hash, hash(b"next epoch"), &shard_layout, total_supply);
+            check_blocks(&[(&first, &params), (&second, &epoch_params(shard_layout))])
+        };
+
+        let split_shard_layout =
+            ShardLayout::derive_shard_layout(&shard_layout(), "d".parse().unwrap());
+        assert_eq!(check_next_epoch(split_shard_layout), vec![]);
+
+        let unrelated_shard_layout = ShardLayout::v2(
+            vec!["c".parse().unwrap(), "d".parse().unwrap()],
+            (0..3).map(ShardId::new).collect(),
+            None,
+        );
+        assert_eq!(
+            check_next_epoch(unrelated_shard_layout),
+            vec![Invariant::ShardLayoutTransition]
+        );
+    }
+}
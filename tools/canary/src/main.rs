@@ -0,0 +1,128 @@
+//! Canary which follows a live chain and continuously checks invariants across
+//! its blocks. Violations are logged and exported as prometheus metrics, so
+//! that they can be alerted on.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::{App, HttpServer, web};
+use clap::Parser;
+use near_chain_follower::{ChainFollower, ChainFollowerConfig};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, Finality};
+use tracing::{error, info, warn};
+
+use crate::invariants::{EpochParams, InvariantChecker};
+use crate::source::ChainSource;
+
+mod invariants;
+mod metrics;
+mod source;
+
+pub(crate) const CANARY: &str = "canary";
+
+/// Follows the chain and checks invariants across its final blocks.
+#[derive(clap::Parser)]
+struct Cli {
+    /// Listen address for prometheus metrics.
+    #[clap(long, default_value = "0.0.0.0:4040")]
+    prometheus_addr: String,
+    /// Height of the first block to check. Defaults to the latest final block.
+    #[clap(long)]
+    start_block_height: Option<BlockHeight>,
+    #[clap(subcommand)]
+    source: SourceCommand,
+}
+
+#[derive(clap::Subcommand)]
+enum SourceCommand {
+    /// Polls the blocks from the JSON RPC server of a node.
+    Rpc {
+        #[clap(long, default_value = "http://localhost:3030")]
+        rpc_server_addr: String,
+    },
+    /// Follows the chain with an embedded non-validating node.
+    Follower {
+        /// Directory with the configs of the node. Defaults to ~/.near
+        #[clap(long)]
+        home_dir: Option<PathBuf>,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let env_filter = near_o11y::EnvFilterBuilder::from_env().finish()?;
+    let _subscriber = near_o11y::default_subscriber(env_filter, &Default::default()).global();
+    let cli = Cli::parse();
+
+    let system = actix::System::new();
+    system.block_on(async move {
+        let server = HttpServer::new(|| {
+            App::new().service(
+                web::resource("/metrics").route(web::get().to(near_jsonrpc::prometheus_handler)),
+            )
+        })
+        .bind(&cli.prometheus_addr)?
+        .workers(1)
+        .shutdown_timeout(3)
+        .disable_signals()
+        .run();
+        tokio::spawn(server);
+
+        let source = match cli.source {
+            SourceCommand::Rpc { rpc_server_addr } => ChainSource::Rpc {
+                server_addr: rpc_server_addr,
+                finality: Finality::Final,
+                start_block_height: cli.start_block_height,
+            },
+            SourceCommand::Follower { home_dir } => {
+                ChainSource::Follower(ChainFollower::new(ChainFollowerConfig {
+                    home_dir: home_dir.unwrap_or_else(near_chain_follower::get_default_home),
+                    start_block_height: cli.start_block_height,
+                    finality: Finality::Final,
+                    with_state_changes: false,
+                    validate_genesis: false,
+                })?)
+            }
+        };
+        run(source).await
+    })
+}
+
+async fn run(source: ChainSource) -> anyhow::Result<()> {
+    info!(target: CANARY, "Starting the canary...");
+    let mut blocks = source.blocks();
+    let mut checker = InvariantChecker::default();
+    let mut epoch_params: Option<(CryptoHash, Arc<EpochParams>)> = None;
+    while let Some(block) = blocks.recv().await {
+        let height = block.header.height;
+        let epoch_id = block.header.epoch_id;
+        let cached_epoch_params = epoch_params
+            .as_ref()
+            .filter(|(cached_epoch_id, _)| *cached_epoch_id == epoch_id)
+            .map(|(_, params)| params.clone());
+        let block_epoch_params = match cached_epoch_params {
+            Some(params) => params,
+            None => match source.epoch_params(block.header.hash).await {
+                Ok(params) => {
+                    let params = Arc::new(params);
+                    epoch_params = Some((epoch_id, params.clone()));
+                    params
+                }
+                Err(err) => {
+                    warn!(target: CANARY, height, ?err, "Failed to get the epoch parameters, skipping block");
+                    checker.reset();
+                    continue;
+                }
+            },
+        };
+
+        for violation in checker.check_block(&block, block_epoch_params) {
+            error!(target: CANARY, height, hash = %block.header.hash, %violation, "Invariant violated");
+            metrics::CANARY_INVARIANT_VIOLATIONS
+                .with_label_values(&[violation.invariant.name()])
+                .inc();
+        }
+        metrics::CANARY_LAST_CHECKED_HEIGHT.set(height as i64);
+    }
+    info!(target: CANARY, "Block stream ended, stopping the canary");
+    Ok(())
+}
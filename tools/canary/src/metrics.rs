@@ -0,0 +1,18 @@
+use near_o11y::metrics::{
+    IntCounterVec, IntGauge, try_create_int_counter_vec, try_create_int_gauge,
+};
+use std::sync::LazyLock;
+
+pub(crate) static CANARY_INVARIANT_VIOLATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "near_canary_invariant_violations_total",
+        "Number of invariant violations found in the checked blocks",
+        &["invariant"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CANARY_LAST_CHECKED_HEIGHT: LazyLock<IntGauge> = LazyLock::new(|| {
+    try_create_int_gauge("near_canary_last_checked_height", "Height of the last checked block")
+        .unwrap()
+});
@@ -0,0 +1,126 @@
+//! Sources of the blocks checked by the canary: the JSON RPC of a node or an
+//! embedded chain follower.
+use std::time::Duration;
+
+use anyhow::anyhow;
+use near_chain_follower::ChainFollower;
+use near_jsonrpc::client::JsonRpcClient;
+use near_jsonrpc::primitives::errors::{RpcError, RpcErrorKind};
+use near_jsonrpc::primitives::types::config::RpcProtocolConfigRequest;
+use near_o11y::WithSpanContextExt;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality};
+use near_primitives::views::BlockView;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::CANARY;
+use crate::invariants::EpochParams;
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+pub(crate) enum ChainSource {
+    /// Polls the blocks from the JSON RPC server at `server_addr`.
+    Rpc { server_addr: String, finality: Finality, start_block_height: Option<BlockHeight> },
+    /// Gets the blocks from an embedded non-validating node.
+    Follower(ChainFollower),
+}
+
+impl ChainSource {
+    /// Starts streaming the blocks in the order of their heights. Heights
+    /// without a block are skipped. Must be called from within an actix system.
+    pub(crate) fn blocks(&self) -> mpsc::Receiver<BlockView> {
+        let (sender, receiver) = mpsc::channel(100);
+        match self {
+            Self::Rpc { server_addr, finality, start_block_height } => {
+                let client = near_jsonrpc::client::new_client(server_addr);
+                actix::spawn(poll_rpc(client, finality.clone(), *start_block_height, sender));
+            }
+            Self::Follower(follower) => {
+                let mut messages = follower.streamer();
+                actix::spawn(async move {
+                    while let Some(message) = messages.recv().await {
+                        if sender.send(message.block).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+        receiver
+    }
+
+    /// Fetches the parameters of the epoch of the given block.
+    pub(crate) async fn epoch_params(&self, block_hash: CryptoHash) -> anyhow::Result<EpochParams> {
+        let block_reference = BlockReference::BlockId(BlockId::Hash(block_hash));
+        let config_view = match self {
+            Self::Rpc { server_addr, .. } => {
+                near_jsonrpc::client::new_client(server_addr)
+                    .EXPERIMENTAL_protocol_config(RpcProtocolConfigRequest { block_reference })
+                    .await
+                    .map_err(|err| anyhow!("get protocol config failed: {err}"))?
+                    .config_view
+            }
+            Self::Follower(follower) => {
+                let (view_client, _) = follower.client_actors();
+                view_client
+                    .send(near_client::GetProtocolConfig(block_reference).with_span_context())
+                    .await??
+            }
+        };
+        Ok(EpochParams::from(config_view))
+    }
+}
+
+async fn poll_rpc(
+    client: JsonRpcClient,
+    finality: Finality,
+    start_block_height: Option<BlockHeight>,
+    sink: mpsc::Sender<BlockView>,
+) {
+    let mut next_block_height = start_block_height;
+    'main: loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let latest_block = match client.block(BlockReference::Finality(finality.clone())).await {
+            Ok(block) => block,
+            Err(err) => {
+                warn!(target: CANARY, %err, "Failed to fetch the latest block");
+                continue;
+            }
+        };
+        let latest_block_height = latest_block.header.height;
+        let start_block_height = *next_block_height.get_or_insert(latest_block_height);
+
+        for block_height in start_block_height..=latest_block_height {
+            let block = if block_height == latest_block_height {
+                latest_block.clone()
+            } else {
+                match client.block_by_id(BlockId::Height(block_height)).await {
+                    Ok(block) => block,
+                    Err(err) if is_unknown_block(&err) => {
+                        debug!(target: CANARY, block_height, "Skipping missing block");
+                        next_block_height = Some(block_height + 1);
+                        continue;
+                    }
+                    Err(err) => {
+                        // Retry this height later.
+                        warn!(target: CANARY, block_height, %err, "Failed to fetch the block");
+                        continue 'main;
+                    }
+                }
+            };
+            if sink.send(block).await.is_err() {
+                break 'main;
+            }
+            next_block_height = Some(block_height + 1);
+        }
+    }
+}
+
+fn is_unknown_block(err: &RpcError) -> bool {
+    let Some(RpcErrorKind::HandlerError(serde_json::Value::Object(err))) = &err.error_struct else {
+        return false;
+    };
+    matches!(err.get("name"), Some(serde_json::Value::String(name)) if name == "UNKNOWN_BLOCK")
+}
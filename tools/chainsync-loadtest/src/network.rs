@@ -205,6 +205,7 @@ impl Network {
                         account_id: peer.peer_info.account_id,
                         prefer_peer: true,
                         shard_id: ch.shard_id(),
+                        epoch_shard_uid: None,
                         only_archival: false,
                         min_height: ch.height_included(),
                     },
@@ -266,6 +267,9 @@ impl Network {
             epoch_sync_request: noop().into_sender(),
             epoch_sync_response: noop().into_sender(),
             optimistic_block_receiver: noop().into_sender(),
+            chunk_parts_sample_response: noop().into_sender(),
+            replica_state_update_request: noop().into_sender(),
+            replica_state_update_response: noop().into_sender(),
         }
     }
 }
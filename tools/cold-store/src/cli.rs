@@ -8,7 +8,12 @@ use near_epoch_manager::{EpochManager, EpochManagerAdapter, EpochManagerHandle};
 use near_primitives::block::Tip;
 use near_primitives::epoch_block_info::BlockInfo;
 use near_primitives::hash::CryptoHash;
-use near_store::archive::cold_storage::{copy_all_data_to_cold, update_cold_db, update_cold_head};
+use near_primitives::shard_layout::{ShardLayout, ShardUId};
+use near_primitives::types::EpochId;
+use near_store::archive::cold_storage::{
+    CopyAllDataToColdStatus, check_shards_in_cold, copy_all_data_to_cold, copy_shards_to_cold,
+    update_cold_db, update_cold_head,
+};
 use near_store::db::metadata::DbKind;
 use near_store::{COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY, TAIL_KEY};
 use near_store::{DBCol, NodeStorage, Store, StoreOpener};
@@ -57,6 +62,13 @@ enum SubCommand {
     /// Modifies cold db from config to be considered not initialized.
     /// Doesn't actually delete any data, except for HEAD and COLD_HEAD in BlockMisc.
     ResetCold(ResetColdCmd),
+    /// Copy the State, chunks and block data of the shards retired by the
+    /// reshardings up to the hot HEAD, with the ShardUId mapping, to cold storage.
+    /// Requires --readwrite and the node to be stopped.
+    CopyRetiredShards(CopyRetiredShardsCmd),
+    /// Check that the data of the shards retired by the reshardings up to the hot
+    /// HEAD is fully present in cold storage, so that it can be removed from hot storage.
+    CheckRetiredShards,
 }
 
 impl ColdStoreCommand {
@@ -96,6 +108,10 @@ impl ColdStoreCommand {
             SubCommand::PrepareHot(cmd) => cmd.run(&storage, &home_dir, &near_config),
             SubCommand::CheckStateRoot(cmd) => cmd.run(&storage),
             SubCommand::ResetCold(cmd) => cmd.run(&storage),
+            SubCommand::CopyRetiredShards(cmd) => cmd.run(&storage, epoch_manager.as_ref()),
+            SubCommand::CheckRetiredShards => {
+                check_retired_shards(&storage, epoch_manager.as_ref())
+            }
         }
     }
 
@@ -675,3 +691,110 @@ impl ResetColdCmd {
         Ok(())
     }
 }
+
+/// Returns the shards of the shard layouts from genesis up to the hot HEAD that
+/// are not in the shard layout of the hot HEAD anymore.
+fn get_retired_shard_uids(
+    storage: &NodeStorage,
+    epoch_manager: &EpochManagerHandle,
+) -> anyhow::Result<Vec<ShardUId>> {
+    let head = storage
+        .get_hot_store()
+        .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?
+        .ok_or_else(|| anyhow::anyhow!("HEAD not found in hot storage"))?;
+    let head_shard_uids: Vec<ShardUId> =
+        epoch_manager.get_shard_layout(&head.epoch_id)?.shard_uids().collect();
+
+    let mut retired_shard_uids = vec![];
+    for shard_layout in epoch_manager.get_shard_layouts_up_to_epoch(&head.epoch_id)? {
+        for shard_uid in shard_layout.shard_uids() {
+            if !head_shard_uids.contains(&shard_uid) && !retired_shard_uids.contains(&shard_uid) {
+                retired_shard_uids.push(shard_uid);
+            }
+        }
+    }
+    println!("Retired shards: {:?}", retired_shard_uids);
+    Ok(retired_shard_uids)
+}
+
+/// Returns the shard layout of the epoch of the blocks following the block
+/// `prev_block_hash`, as expected by `copy_shards_to_cold` and
+/// `check_shards_in_cold`.
+fn shard_layout_after(
+    epoch_manager: &EpochManagerHandle,
+    prev_block_hash: &CryptoHash,
+) -> Result<ShardLayout> {
+    // The genesis block has no previous block and is in the genesis epoch.
+    let shard_layout = if prev_block_hash == &CryptoHash::default() {
+        epoch_manager.get_shard_layout(&EpochId::default())
+    } else {
+        epoch_manager.get_shard_layout_from_prev_block(prev_block_hash)
+    };
+    shard_layout.map_err(std::io::Error::other)
+}
+
+#[derive(clap::Args)]
+struct CopyRetiredShardsCmd {
+    /// Threshold size of the write transaction.
+    #[clap(short = 'b', long, default_value_t = 500_000_000)]
+    batch_size: usize,
+}
+
+impl CopyRetiredShardsCmd {
+    pub fn run(
+        self,
+        storage: &NodeStorage,
+        epoch_manager: &EpochManagerHandle,
+    ) -> anyhow::Result<()> {
+        let cold_db =
+            storage.cold_db().ok_or_else(|| anyhow::anyhow!("Cold storage is not configured"))?;
+        let shard_uids = get_retired_shard_uids(storage, epoch_manager)?;
+        let keep_going = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+        match copy_shards_to_cold(
+            cold_db.clone(),
+            &storage.get_hot_store(),
+            &shard_uids,
+            &|prev_block_hash| shard_layout_after(epoch_manager, prev_block_hash),
+            self.batch_size,
+            &keep_going,
+        )? {
+            CopyAllDataToColdStatus::EverythingCopied => Ok(()),
+            CopyAllDataToColdStatus::Interrupted => {
+                Err(anyhow::anyhow!("Copying the retired shards was interrupted"))
+            }
+        }
+    }
+}
+
+fn check_retired_shards(
+    storage: &NodeStorage,
+    epoch_manager: &EpochManagerHandle,
+) -> anyhow::Result<()> {
+    let cold_store = storage
+        .get_cold_store()
+        .ok_or_else(|| anyhow::anyhow!("Cold storage is not configured"))?;
+    let shard_uids = get_retired_shard_uids(storage, epoch_manager)?;
+    let checks = check_shards_in_cold(
+        &storage.get_hot_store(),
+        &cold_store,
+        &shard_uids,
+        &|prev_block_hash| shard_layout_after(epoch_manager, prev_block_hash),
+    )?;
+
+    let mut num_missing = 0;
+    for col in DBCol::iter() {
+        let Some(check) = checks.get(&col) else { continue };
+        println!(
+            "Performed {} {:?} checks, {} missing in cold storage",
+            check.num_checked, col, check.num_missing
+        );
+        num_missing += check.num_missing;
+    }
+    if num_missing > 0 {
+        return Err(anyhow::anyhow!(
+            "{num_missing} keys of the retired shards are missing in cold storage"
+        ));
+    }
+    println!("The retired shards are fully present in cold storage");
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use near_chain::resharding::conservation::{
+    ReceiptQueue, ShardConservationSummary, check_split_conservation,
+};
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_chain_configs::GenesisValidationMode;
+use near_epoch_manager::{EpochManager, EpochManagerAdapter};
+use near_primitives::types::BlockHeight;
+use near_store::adapter::StoreAdapter;
+use near_store::flat::FlatStorageManager;
+use near_store::{ShardTries, ShardUId, StateSnapshotConfig, TrieConfig};
+use nearcore::{load_config, open_storage};
+
+/// Checks that the shard splits at the resharding block, the last block with
+/// the old shard layout, preserve the total balance and the pending receipts
+/// of the parent shards.
+#[derive(Parser)]
+pub(crate) struct CheckReshardingConservationCommand {
+    /// The height of the resharding block.
+    #[arg(long)]
+    height: BlockHeight,
+}
+
+impl CheckReshardingConservationCommand {
+    pub(crate) fn run(
+        &self,
+        home: &PathBuf,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let mut near_config = load_config(home, genesis_validation).unwrap();
+        let node_storage = open_storage(&home, &mut near_config).unwrap();
+        let store = node_storage.get_hot_store();
+        let chain_store = ChainStore::new(
+            store.clone(),
+            false,
+            near_config.genesis.config.transaction_validity_period,
+        );
+        let epoch_manager =
+            EpochManager::new_arc_handle(store.clone(), &near_config.genesis.config, None);
+
+        let block_hash = chain_store.get_block_hash_by_height(self.height)?;
+        let header = chain_store.get_block_header(&block_hash)?;
+        let shard_layout = epoch_manager.get_shard_layout(header.epoch_id())?;
+        let next_epoch_id = epoch_manager.get_next_epoch_id(&block_hash)?;
+        let next_shard_layout = epoch_manager.get_shard_layout(&next_epoch_id)?;
+        if !epoch_manager.is_next_block_epoch_start(&block_hash)?
+            || shard_layout == next_shard_layout
+        {
+            anyhow::bail!("block #{} {} is not a resharding block", self.height, block_hash);
+        }
+
+        let shard_uids =
+            shard_layout.shard_uids().chain(next_shard_layout.shard_uids()).collect::<Vec<_>>();
+        let shard_tries = ShardTries::new(
+            store.trie_store(),
+            TrieConfig::default(),
+            &shard_uids,
+            FlatStorageManager::new(store.flat_store()),
+            StateSnapshotConfig::Disabled,
+        );
+        let summary = |shard_uid: ShardUId| -> anyhow::Result<ShardConservationSummary> {
+            let chunk_extra = chain_store.get_chunk_extra(&block_hash, &shard_uid)?;
+            let trie = shard_tries.get_view_trie_for_shard(shard_uid, *chunk_extra.state_root());
+            Ok(ShardConservationSummary::from_trie(&trie)?)
+        };
+
+        let mut num_failed = 0;
+        for parent_shard_uid in shard_layout.shard_uids() {
+            let Some(children_shard_uids) =
+                next_shard_layout.get_children_shards_uids(parent_shard_uid.shard_id())
+            else {
+                continue;
+            };
+            if children_shard_uids == [parent_shard_uid] {
+                continue;
+            }
+            println!("Parent shard {}, children {:?}", parent_shard_uid, children_shard_uids);
+            let parent = summary(parent_shard_uid)?;
+            let children = children_shard_uids
+                .iter()
+                .map(|shard_uid| summary(*shard_uid))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            for (shard_uid, summary) in std::iter::once((&parent_shard_uid, &parent))
+                .chain(children_shard_uids.iter().zip(&children))
+            {
+                println!(
+                    "  {}: {} accounts, total balance {}",
+                    shard_uid, summary.num_accounts, summary.total_balance
+                );
+                for queue in [
+                    ReceiptQueue::Delayed,
+                    ReceiptQueue::Buffered,
+                    ReceiptQueue::Postponed,
+                    ReceiptQueue::PromiseYield,
+                ] {
+                    let num_receipts = summary.receipts.get(&queue).map_or(0, |ids| ids.len());
+                    println!(
+                        "    {:?} receipts: {} {}",
+                        queue,
+                        num_receipts,
+                        summary.receipts_hash(queue)
+                    );
+                }
+            }
+            match check_split_conservation(&parent, &children.iter().collect::<Vec<_>>()) {
+                Ok(()) => println!("  OK"),
+                Err(err) => {
+                    println!("  FAILED: {}", err);
+                    num_failed += 1;
+                }
+            }
+        }
+        if num_failed > 0 {
+            anyhow::bail!("{} shard splits aren't conservative", num_failed);
+        }
+        Ok(())
+    }
+}
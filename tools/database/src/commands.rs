@@ -4,11 +4,14 @@ use crate::analyze_data_size_distribution::AnalyzeDataSizeDistributionCommand;
 use crate::analyze_delayed_receipt::AnalyzeDelayedReceiptCommand;
 use crate::analyze_gas_usage::AnalyzeGasUsageCommand;
 use crate::analyze_high_load::HighLoadStatsCommand;
+use crate::check_resharding_conservation::CheckReshardingConservationCommand;
 use crate::compact::RunCompactionCommand;
 use crate::corrupt::CorruptStateSnapshotCommand;
 use crate::drop_column::DropColumnCommand;
 use crate::make_snapshot::MakeSnapshotCommand;
 use crate::memtrie::LoadMemTrieCommand;
+use crate::propose_shard_split::ProposeShardSplitCommand;
+use crate::resharding_dry_run::ReshardingDryRunCommand;
 use crate::run_migrations::RunMigrationsCommand;
 use crate::set_version::SetVersionCommand;
 use crate::state_perf::StatePerfCommand;
@@ -35,6 +38,9 @@ enum SubCommand {
     /// Change DbKind of hot or cold db.
     ChangeDbKind(ChangeDbKindCommand),
 
+    /// Check that the shard splits at a resharding block preserve the balances and receipts
+    CheckReshardingConservation(CheckReshardingConservationCommand),
+
     /// Run SST file compaction on database
     CompactDatabase(RunCompactionCommand),
 
@@ -47,6 +53,13 @@ enum SubCommand {
     /// Make snapshot of the database
     MakeSnapshot(MakeSnapshotCommand),
 
+    /// Propose the boundary account of a shard split from the collected account usage
+    ProposeShardSplit(ProposeShardSplitCommand),
+
+    /// Split a shard at a new boundary account without writing to the database, and report the
+    /// time, the keys of the children and the peak memory
+    ReshardingDryRun(ReshardingDryRunCommand),
+
     /// Run migrations
     RunMigrations(RunMigrationsCommand),
 
@@ -80,6 +93,7 @@ impl DatabaseCommand {
             SubCommand::AnalyzeDataSizeDistribution(cmd) => cmd.run(home),
             SubCommand::AnalyzeGasUsage(cmd) => cmd.run(home, genesis_validation),
             SubCommand::ChangeDbKind(cmd) => cmd.run(home, genesis_validation),
+            SubCommand::CheckReshardingConservation(cmd) => cmd.run(home, genesis_validation),
             SubCommand::CompactDatabase(cmd) => cmd.run(home),
             SubCommand::CorruptStateSnapshot(cmd) => cmd.run(home),
             SubCommand::DropColumn(cmd) => cmd.run(home, genesis_validation),
@@ -87,6 +101,8 @@ impl DatabaseCommand {
                 let near_config = load_config(home, genesis_validation);
                 cmd.run(home, &near_config.config.store, near_config.config.archival_config())
             }
+            SubCommand::ProposeShardSplit(cmd) => cmd.run(home, genesis_validation),
+            SubCommand::ReshardingDryRun(cmd) => cmd.run(home, genesis_validation),
             SubCommand::RunMigrations(cmd) => cmd.run(home, genesis_validation),
             SubCommand::StatePerf(cmd) => cmd.run(home),
             SubCommand::LoadMemTrie(cmd) => cmd.run(home, genesis_validation),
@@ -5,12 +5,15 @@ mod analyze_delayed_receipt;
 mod analyze_gas_usage;
 mod analyze_high_load;
 mod block_iterators;
+mod check_resharding_conservation;
 pub mod commands;
 mod compact;
 mod corrupt;
 mod drop_column;
 mod make_snapshot;
 mod memtrie;
+mod propose_shard_split;
+mod resharding_dry_run;
 mod run_migrations;
 mod set_version;
 mod state_perf;
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::Parser;
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_chain_configs::GenesisValidationMode;
+use near_epoch_manager::{EpochManager, EpochManagerAdapter};
+use near_primitives::shard_layout::boundary_selection::{
+    AccountUsageStats, ShardSplitSide, SplitMetric,
+};
+use near_primitives::types::{AccountId, ShardId};
+use near_store::DBCol;
+use nearcore::{load_config, open_storage};
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum Metric {
+    StateSize,
+    GasUsage,
+    Combined,
+}
+
+impl From<Metric> for SplitMetric {
+    fn from(metric: Metric) -> Self {
+        match metric {
+            Metric::StateSize => SplitMetric::StateSize,
+            Metric::GasUsage => SplitMetric::GasUsage,
+            Metric::Combined => SplitMetric::Combined,
+        }
+    }
+}
+
+/// Proposes the boundary account of a shard split from the account usage
+/// collected by the node with `save_account_usage_stats` enabled, and prints
+/// the shard layout derived from the current one.
+#[derive(Parser)]
+pub(crate) struct ProposeShardSplitCommand {
+    /// The shard of the current shard layout to split. If not given, a split
+    /// is proposed for every shard.
+    #[arg(long)]
+    shard_id: Option<ShardId>,
+
+    /// The load that the split should halve.
+    #[arg(long, value_enum, default_value_t = Metric::Combined)]
+    metric: Metric,
+}
+
+fn display_side(name: &str, side: &ShardSplitSide, total: &ShardSplitSide) {
+    let percentage = |part: u128, total: u128| {
+        if total > 0 { format!("{:.1}%", part as f64 / total as f64 * 100.0) } else { "-".into() }
+    };
+    println!(
+        "    {name}: {} accounts, storage usage {} ({}), gas burnt {:.2} TGas ({})",
+        side.num_accounts,
+        side.storage_usage,
+        percentage(side.storage_usage, total.storage_usage),
+        side.gas_burnt as f64 / 1e12,
+        percentage(side.gas_burnt, total.gas_burnt),
+    );
+}
+
+impl ProposeShardSplitCommand {
+    pub(crate) fn run(
+        &self,
+        home: &PathBuf,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let mut near_config = load_config(home, genesis_validation).unwrap();
+        let node_storage = open_storage(&home, &mut near_config).unwrap();
+        let store = node_storage.get_hot_store();
+        let chain_store = ChainStore::new(
+            store.clone(),
+            false,
+            near_config.genesis.config.transaction_validity_period,
+        );
+        let head = chain_store.head()?;
+        let epoch_manager =
+            EpochManager::new_arc_handle(store.clone(), &near_config.genesis.config, None);
+        let shard_layout = epoch_manager.get_shard_layout(&head.epoch_id)?;
+
+        let mut accounts = BTreeMap::new();
+        for item in store.iter_ser::<AccountUsageStats>(DBCol::AccountUsageStats) {
+            let (key, stats) = item?;
+            let account_id = AccountId::from_str(std::str::from_utf8(&key)?)?;
+            accounts.insert(account_id, stats);
+        }
+        if accounts.is_empty() {
+            anyhow::bail!("no account usage stats found, is save_account_usage_stats enabled?");
+        }
+        println!("Account usage stats of {} accounts", accounts.len());
+        println!("Shard layout at height {}: {:?}", head.height, shard_layout);
+
+        let shard_ids = match self.shard_id {
+            Some(shard_id) => vec![shard_id],
+            None => shard_layout.shard_ids().collect(),
+        };
+        for shard_id in shard_ids {
+            println!();
+            println!("Shard {}", shard_id);
+            let Some((new_shard_layout, proposal)) = shard_layout.derive_shard_layout_from_usage(
+                shard_id,
+                &accounts,
+                self.metric.into(),
+            ) else {
+                println!("  No split for this shard");
+                continue;
+            };
+            let mut total = proposal.left.clone();
+            total.num_accounts += proposal.right.num_accounts;
+            total.storage_usage += proposal.right.storage_usage;
+            total.gas_burnt += proposal.right.gas_burnt;
+            println!("  Boundary account: {}", proposal.boundary_account);
+            display_side("Left (account < boundary_account)", &proposal.left, &total);
+            display_side("Right (account >= boundary_account)", &proposal.right, &total);
+            println!("  New boundary accounts: {:?}", new_shard_layout.boundary_accounts());
+            println!("  New shard layout: {:?}", new_shard_layout);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use clap::Parser;
+use near_chain::flat_storage_resharder::{SplitKeyDestination, split_key_destination};
+use near_chain_configs::GenesisValidationMode;
+use near_epoch_manager::{EpochManager, EpochManagerAdapter};
+use near_primitives::block::Tip;
+use near_primitives::shard_layout::{ShardLayout, ShardUId, get_block_shard_uid};
+use near_primitives::types::AccountId;
+use near_primitives::types::chunk_extra::ChunkExtra;
+use near_store::adapter::StoreAdapter;
+use near_store::adapter::flat_store::FlatStoreAdapter;
+use near_store::flat::{FlatStorageStatus, ParentSplitParameters};
+use near_store::trie::AccessOptions;
+use near_store::trie::mem::loading::load_trie_from_flat_state_and_delta;
+use near_store::trie::mem::memtrie_update::TrackingMode;
+use near_store::trie::ops::resharding::RetainMode;
+use near_store::{DBCol, HEAD_KEY, NodeStorage};
+
+use crate::utils::open_rocksdb;
+
+/// Splits the shard of the current shard layout holding a hypothetical
+/// boundary account, with the database opened read-only, and reports how long
+/// the split of the flat storage and of the memtrie took, how many keys went
+/// to each child and the peak memory of the process.
+///
+/// Both splits are done at the flat head of the parent shard. Unlike the
+/// resharding of a node, the flat state deltas after it aren't split.
+///
+/// Example usage: neard database resharding-dry-run --boundary-account aurora.near
+#[derive(Parser)]
+pub(crate) struct ReshardingDryRunCommand {
+    /// The new boundary account. The shard which holds it is split.
+    #[arg(long)]
+    boundary_account: AccountId,
+    /// Load the memtrie of the parent shard on a single thread.
+    #[arg(long)]
+    no_parallel: bool,
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+struct ChildStats {
+    num_keys: u64,
+    size: u64,
+}
+
+impl ChildStats {
+    fn add(&mut self, size: usize) {
+        self.num_keys += 1;
+        self.size += size as u64;
+    }
+}
+
+/// Returns the stats of the left and the right child of the split of the
+/// parent's flat storage.
+fn split_flat_state(
+    flat_store: &FlatStoreAdapter,
+    parent_shard_uid: ShardUId,
+    split_params: &ParentSplitParameters,
+) -> anyhow::Result<[ChildStats; 2]> {
+    let mut left = ChildStats::default();
+    let mut right = ChildStats::default();
+    for item in flat_store.iter(parent_shard_uid) {
+        let (key, value) = item?;
+        let size = key.len() + value.value_len();
+        match split_key_destination(&key, split_params)? {
+            SplitKeyDestination::Child(shard_uid) if shard_uid == split_params.left_child_shard => {
+                left.add(size)
+            }
+            SplitKeyDestination::Child(_) => right.add(size),
+            SplitKeyDestination::AllChildren => {
+                left.add(size);
+                right.add(size);
+            }
+            SplitKeyDestination::LeftChild => left.add(size),
+        }
+    }
+    Ok([left, right])
+}
+
+/// The peak resident set size of the process. Only available on Linux.
+fn peak_memory() -> Option<ByteSize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kib = line.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(ByteSize::kib(kib))
+}
+
+impl ReshardingDryRunCommand {
+    pub(crate) fn run(
+        &self,
+        home: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(&home, genesis_validation)
+            .unwrap_or_else(|e| panic!("Error loading config: {:#}", e));
+        let rocksdb = Arc::new(open_rocksdb(home, near_store::Mode::ReadOnly)?);
+        let store = NodeStorage::new(rocksdb).get_hot_store();
+        let head = store.get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?.context("head not found")?;
+        let epoch_manager =
+            EpochManager::new_arc_handle(store.clone(), &near_config.genesis.config, Some(home));
+        let shard_layout = epoch_manager.get_shard_layout(&head.epoch_id)?;
+        if shard_layout.boundary_accounts().contains(&self.boundary_account) {
+            anyhow::bail!("{} is already a boundary account", self.boundary_account);
+        }
+
+        let parent_shard_uid = shard_layout.account_id_to_shard_uid(&self.boundary_account);
+        let new_shard_layout =
+            ShardLayout::derive_shard_layout(&shard_layout, self.boundary_account.clone());
+        let children_shard_uids = new_shard_layout
+            .get_children_shards_uids(parent_shard_uid.shard_id())
+            .context("the parent shard has no children")?;
+        let &[left_child_shard, right_child_shard] = children_shard_uids.as_slice() else {
+            anyhow::bail!("the parent shard isn't split in two: {:?}", children_shard_uids);
+        };
+        println!("Shard layout at height {}: {:?}", head.height, shard_layout);
+        println!("New shard layout: {:?}", new_shard_layout);
+        println!(
+            "Splitting {} into {} and {}",
+            parent_shard_uid, left_child_shard, right_child_shard
+        );
+
+        let flat_store = store.flat_store();
+        let flat_head = match flat_store.get_flat_storage_status(parent_shard_uid)? {
+            FlatStorageStatus::Ready(status) => status.flat_head,
+            status => {
+                anyhow::bail!("flat storage of {} is not ready: {:?}", parent_shard_uid, status)
+            }
+        };
+        let split_params = ParentSplitParameters {
+            left_child_shard,
+            right_child_shard,
+            shard_layout: new_shard_layout,
+            resharding_blocks: vec![],
+            flat_head,
+        };
+
+        println!("Splitting the flat storage at height {}...", flat_head.height);
+        let start_time = Instant::now();
+        let [left, right] = split_flat_state(&flat_store, parent_shard_uid, &split_params)?;
+        println!("Flat storage split took {:?}", start_time.elapsed());
+        for (shard_uid, stats) in [(left_child_shard, &left), (right_child_shard, &right)] {
+            println!("  {}: {} keys, {}", shard_uid, stats.num_keys, ByteSize::b(stats.size));
+        }
+
+        println!("Loading the memtrie of {}...", parent_shard_uid);
+        let start_time = Instant::now();
+        let memtries =
+            load_trie_from_flat_state_and_delta(&store, parent_shard_uid, None, !self.no_parallel)?;
+        println!("Memtrie loading took {:?}", start_time.elapsed());
+        let chunk_extra = store
+            .get_ser::<ChunkExtra>(
+                DBCol::ChunkExtra,
+                &get_block_shard_uid(&flat_head.hash, &parent_shard_uid),
+            )?
+            .context("chunk extra of the flat head not found")?;
+        for (shard_uid, retain_mode) in
+            [(left_child_shard, RetainMode::Left), (right_child_shard, RetainMode::Right)]
+        {
+            let start_time = Instant::now();
+            let trie_changes = memtries
+                .update(*chunk_extra.state_root(), TrackingMode::None)?
+                .retain_split_shard(&self.boundary_account, retain_mode, AccessOptions::DEFAULT);
+            println!(
+                "Memtrie split of {} took {:?}, state root {}",
+                shard_uid,
+                start_time.elapsed(),
+                trie_changes.new_root
+            );
+        }
+
+        match peak_memory() {
+            Some(peak_memory) => println!("Peak memory: {}", peak_memory),
+            None => println!("Peak memory: unavailable"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::{ShardLayout, ShardUId};
+    use near_primitives::state::FlatStateValue;
+    use near_primitives::trie_key::TrieKey;
+    use near_store::adapter::StoreAdapter;
+    use near_store::flat::{BlockInfo, ParentSplitParameters};
+    use near_store::test_utils::create_test_store;
+
+    use super::{ChildStats, split_flat_state};
+
+    #[test]
+    fn test_split_flat_state() {
+        let shard_layout = ShardLayout::single_shard();
+        let parent_shard_uid = ShardUId::from_shard_id_and_layout(
+            shard_layout.shard_ids().next().unwrap(),
+            &shard_layout,
+        );
+        let new_shard_layout =
+            ShardLayout::derive_shard_layout(&shard_layout, "b".parse().unwrap());
+        let children_shard_uids =
+            new_shard_layout.get_children_shards_uids(parent_shard_uid.shard_id()).unwrap();
+        let &[left_child_shard, right_child_shard] = children_shard_uids.as_slice() else {
+            panic!("the parent shard isn't split in two: {:?}", children_shard_uids);
+        };
+        let split_params = ParentSplitParameters {
+            left_child_shard,
+            right_child_shard,
+            shard_layout: new_shard_layout,
+            resharding_blocks: vec![],
+            flat_head: BlockInfo::genesis(CryptoHash::default(), 0),
+        };
+
+        let keys_and_values = [
+            // Left child.
+            (TrieKey::Account { account_id: "a".parse().unwrap() }, vec![1; 10]),
+            // Right child.
+            (TrieKey::Account { account_id: "c".parse().unwrap() }, vec![2; 20]),
+            (
+                TrieKey::ContractData { account_id: "c".parse().unwrap(), key: b"key".to_vec() },
+                vec![3; 30],
+            ),
+            // Both children.
+            (TrieKey::DelayedReceiptIndices, vec![4; 40]),
+            // Only the left child.
+            (TrieKey::BufferedReceiptIndices, vec![5; 50]),
+        ];
+        let store = create_test_store().flat_store();
+        let mut store_update = store.store_update();
+        let mut sizes = vec![];
+        for (key, value) in keys_and_values {
+            let key = key.to_vec();
+            sizes.push(key.len() + value.len());
+            store_update.set(parent_shard_uid, key, Some(FlatStateValue::inlined(&value)));
+        }
+        store_update.commit().unwrap();
+
+        let stats = split_flat_state(&store, parent_shard_uid, &split_params).unwrap();
+        let size = |indexes: &[usize]| indexes.iter().map(|i| sizes[*i] as u64).sum::<u64>();
+        assert_eq!(
+            stats,
+            [
+                ChildStats { num_keys: 3, size: size(&[0, 3, 4]) },
+                ChildStats { num_keys: 3, size: size(&[1, 2, 3]) },
+            ]
+        );
+    }
+}
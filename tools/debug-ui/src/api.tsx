@@ -266,6 +266,7 @@ export interface TrackedShardsResponse {
         TrackedShards: {
             shards_tracked_this_epoch: boolean[];
             shards_tracked_next_epoch: boolean[];
+            memtrie_loading_status: (string | null)[];
         };
     };
 }
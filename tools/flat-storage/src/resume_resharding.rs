@@ -71,6 +71,9 @@ fn create_chain_and_executor(
         save_trie_changes: config.client_config.save_trie_changes,
         background_migration_threads: config.client_config.client_background_migration_threads,
         resharding_config: config.client_config.resharding_config.clone(),
+        shard_hotspots: config.client_config.shard_hotspots.clone(),
+        save_account_usage_stats: false,
+        serve_replica_state_updates: false,
     };
     let executor = Arc::new(SerialExecutor::new(ChainStore::new(
         node_storage.get_hot_store(),
@@ -404,7 +404,7 @@ fn commit_to_existing_state(
         num_trie_node_deletions = trie_changes.deletions().len()
     );
     let state_root = shard_tries.apply_all(&trie_changes, shard_uid, &mut update);
-    shard_tries.apply_memtrie_changes(&trie_changes, shard_uid, root.update_height);
+    shard_tries.apply_memtrie_changes(&trie_changes, &[], shard_uid, root.update_height);
     // We may not have loaded memtries (some commands don't need to), so check.
     if let Some(memtries) = shard_tries.get_memtries(shard_uid) {
         memtries.write().unwrap().delete_until_height(root.update_height);
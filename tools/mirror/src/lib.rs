@@ -278,7 +278,7 @@ impl From<GetChunkError> for ChainError {
 
 impl From<near_chain_primitives::Error> for ChainError {
     fn from(err: near_chain_primitives::Error) -> Self {
-        match err {
+        match err.root() {
             near_chain_primitives::Error::DBNotFoundErr(_) => Self::Unknown,
             _ => Self::other(err),
         }
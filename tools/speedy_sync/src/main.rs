@@ -3,7 +3,9 @@ use near_async::messaging::{IntoMultiSender, noop};
 use near_chain::rayon_spawner::RayonAsyncComputationSpawner;
 use near_chain::types::{ChainConfig, Tip};
 use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode};
-use near_chain_configs::{GenesisValidationMode, MutableConfigValue, ReshardingConfig};
+use near_chain_configs::{
+    GenesisValidationMode, MutableConfigValue, ReshardingConfig, ShardHotspotsConfig,
+};
 use near_epoch_manager::EpochManager;
 use near_epoch_manager::epoch_info_aggregator::EpochInfoAggregator;
 use near_epoch_manager::shard_tracker::ShardTracker;
@@ -261,6 +263,9 @@ fn load_snapshot(load_cmd: LoadCmd) {
                 ReshardingConfig::default(),
                 "resharding_config",
             ),
+            shard_hotspots: ShardHotspotsConfig::default(),
+            save_account_usage_stats: false,
+            serve_replica_state_updates: false,
         },
         None,
         Arc::new(RayonAsyncComputationSpawner),
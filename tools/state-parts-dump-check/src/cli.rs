@@ -15,6 +15,7 @@ use near_primitives::state_sync::ShardStateSyncResponseHeader;
 use near_primitives::types::{
     BlockHeight, BlockId, BlockReference, EpochId, EpochReference, Finality, ShardId, StateRoot,
 };
+use near_primitives::version::{PROTOCOL_VERSION, ProtocolVersion};
 use near_primitives::views::ChunkHeaderView;
 use near_store::Trie;
 use nearcore::state_sync::extract_part_id_from_part_file_name;
@@ -80,6 +81,9 @@ pub struct SingleCheckCommand {
     state_root: StateRoot,
     #[clap(long)]
     shard_id: ShardId,
+    /// Protocol version of the epoch, it determines how the state is split into parts.
+    #[clap(long, default_value_t = PROTOCOL_VERSION)]
+    protocol_version: ProtocolVersion,
 }
 
 impl StatePartsDumpCheckCommand {
@@ -132,6 +136,7 @@ impl SingleCheckCommand {
                 self.epoch_height,
                 self.shard_id,
                 self.state_root,
+                self.protocol_version,
                 root_dir,
                 s3_bucket,
                 s3_region,
@@ -214,6 +219,7 @@ struct DumpCheckIterInfo {
     epoch_height: u64,
     shard_layout: ShardLayout,
     state_roots: HashMap<ShardId, CryptoHash>,
+    protocol_version: ProtocolVersion,
 }
 
 fn create_external_connection(
@@ -246,10 +252,15 @@ fn create_external_connection(
     }
 }
 
-fn validate_state_part(state_root: &StateRoot, part_id: PartId, part: &[u8]) -> bool {
+fn validate_state_part(
+    state_root: &StateRoot,
+    part_id: PartId,
+    part: &[u8],
+    protocol_version: ProtocolVersion,
+) -> bool {
     match BorshDeserialize::try_from_slice(part) {
         Ok(trie_nodes) => {
-            match Trie::validate_state_part(state_root, part_id, trie_nodes) {
+            match Trie::validate_state_part(state_root, part_id, trie_nodes, protocol_version) {
                 Ok(_) => true,
                 // Storage error should not happen
                 Err(err) => {
@@ -412,6 +423,7 @@ fn run_loop_all_shards(
                     dump_check_iter_info.epoch_height,
                     shard_id,
                     *dump_check_iter_info.state_roots.get(&shard_id).unwrap(),
+                    dump_check_iter_info.protocol_version,
                     root_dir,
                     s3_bucket,
                     s3_region,
@@ -448,6 +460,7 @@ async fn run_single_check_with_3_retries(
     epoch_height: u64,
     shard_id: ShardId,
     state_root: StateRoot,
+    protocol_version: ProtocolVersion,
     root_dir: Option<PathBuf>,
     s3_bucket: Option<String>,
     s3_region: Option<String>,
@@ -468,6 +481,7 @@ async fn run_single_check_with_3_retries(
             epoch_height,
             shard_id,
             state_root,
+            protocol_version,
             root_dir,
             s3_bucket,
             s3_region,
@@ -504,6 +518,7 @@ async fn check_parts(
     epoch_height: u64,
     shard_id: ShardId,
     state_root: StateRoot,
+    protocol_version: ProtocolVersion,
     external: &ExternalConnection,
 ) -> anyhow::Result<bool> {
     let directory_path = external_storage_location_directory(
@@ -585,6 +600,7 @@ async fn check_parts(
                 shard_id,
                 state_root,
                 num_parts,
+                protocol_version,
                 external,
             )
             .await
@@ -649,6 +665,7 @@ async fn run_single_check(
     current_epoch_height: u64,
     shard_id: ShardId,
     state_root: StateRoot,
+    protocol_version: ProtocolVersion,
     root_dir: Option<PathBuf>,
     s3_bucket: Option<String>,
     s3_region: Option<String>,
@@ -689,9 +706,17 @@ async fn run_single_check(
     };
 
     parts_done = parts_done
-        || check_parts(&chain_id, &epoch_id, current_epoch_height, shard_id, state_root, &external)
-            .await
-            .unwrap_or(false);
+        || check_parts(
+            &chain_id,
+            &epoch_id,
+            current_epoch_height,
+            shard_id,
+            state_root,
+            protocol_version,
+            &external,
+        )
+        .await
+        .unwrap_or(false);
     headers_done = headers_done
         || check_headers(&chain_id, &epoch_id, current_epoch_height, shard_id, &external)
             .await
@@ -715,6 +740,7 @@ async fn process_part_with_3_retries(
     shard_id: ShardId,
     state_root: StateRoot,
     num_parts: u64,
+    protocol_version: ProtocolVersion,
     external: ExternalConnection,
 ) -> anyhow::Result<()> {
     let mut retries = 0;
@@ -737,6 +763,7 @@ async fn process_part_with_3_retries(
                 shard_id,
                 state_root,
                 num_parts,
+                protocol_version,
                 external,
             ),
         )
@@ -820,6 +847,7 @@ async fn process_part(
     shard_id: ShardId,
     state_root: StateRoot,
     num_parts: u64,
+    protocol_version: ProtocolVersion,
     external: ExternalConnection,
 ) -> anyhow::Result<()> {
     tracing::info!(part_id, "process_part started.");
@@ -827,7 +855,8 @@ async fn process_part(
     let location =
         external_storage_location(&chain_id, &epoch_id, epoch_height, shard_id, &file_type);
     let part = external.get_file(shard_id, &location, &file_type).await?;
-    let is_part_valid = validate_state_part(&state_root, PartId::new(part_id, num_parts), &part);
+    let is_part_valid =
+        validate_state_part(&state_root, PartId::new(part_id, num_parts), &part, protocol_version);
     if is_part_valid {
         crate::metrics::STATE_SYNC_DUMP_CHECK_NUM_PARTS_VALID
             .with_label_values(&[&shard_id.to_string(), &chain_id.to_string()])
@@ -968,5 +997,6 @@ async fn get_processing_epoch_information(
         epoch_height: latest_epoch_height,
         shard_layout: protocol_config.config_view.shard_layout,
         state_roots,
+        protocol_version: protocol_config.config_view.protocol_version,
     }))
 }
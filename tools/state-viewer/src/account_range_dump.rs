@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use near_chain::types::RuntimeAdapter;
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_epoch_manager::EpochManagerAdapter;
+use near_epoch_manager::shard_assignment::shard_id_to_uid;
+use near_primitives::errors::StorageError;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::state::PartialState;
+use near_primitives::trie_key::col;
+use near_primitives::types::{AccountId, BlockHeight, ShardId, StateRoot};
+use near_primitives::views::StateItem;
+use near_primitives_core::serialize::to_base64;
+use near_store::trie::AccessOptions;
+use near_store::{NibbleSlice, Store, Trie};
+use nearcore::NearConfig;
+
+use crate::util::{LoadTrieMode, load_trie_stop_at_height};
+
+/// Dumps the state of the accounts in a range, across the shards the node
+/// tracks, at a given block. For every shard the dump contains the raw state
+/// items and the trie nodes visited to read them, so that the items can be
+/// verified against the state root of the shard and no item of the range can
+/// be left out.
+#[derive(clap::Parser)]
+pub struct DumpAccountRangeCmd {
+    /// First account of the range.
+    #[clap(long)]
+    from_account_id: AccountId,
+    /// Account right after the range. If not set, the range includes all the
+    /// accounts after `from_account_id`.
+    #[clap(long)]
+    to_account_id: Option<AccountId>,
+    /// Height of the block to dump the state at. The latest block by default.
+    #[clap(long)]
+    height: Option<BlockHeight>,
+    /// JSON file to write the dump to.
+    #[clap(long, value_parser)]
+    output: PathBuf,
+}
+
+#[derive(serde::Serialize)]
+struct AccountRangeDump {
+    block_hash: CryptoHash,
+    block_height: BlockHeight,
+    from_account_id: AccountId,
+    to_account_id: Option<AccountId>,
+    shards: Vec<ShardAccountRangeDump>,
+}
+
+#[derive(serde::Serialize)]
+struct ShardAccountRangeDump {
+    shard_id: ShardId,
+    /// State root of the shard after applying the chunk of the block.
+    state_root: StateRoot,
+    values: Vec<StateItem>,
+    /// Base64 encoded trie nodes and values, which is the same format as the
+    /// proof of the `view_state` query.
+    proof: Vec<String>,
+}
+
+impl DumpAccountRangeCmd {
+    pub(crate) fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let mode = self.height.map_or(LoadTrieMode::Latest, LoadTrieMode::Height);
+        let (epoch_manager, runtime, _, header) =
+            load_trie_stop_at_height(store.clone(), home_dir, &near_config, mode);
+        let chain_store = ChainStore::new(
+            store,
+            near_config.client_config.save_trie_changes,
+            near_config.genesis.config.transaction_validity_period,
+        );
+        let block_hash = header.hash();
+        let epoch_id = header.epoch_id();
+        let shard_layout = epoch_manager.get_shard_layout(epoch_id).unwrap();
+
+        let mut shards = vec![];
+        for shard_id in
+            shards_in_range(&shard_layout, &self.from_account_id, self.to_account_id.as_ref())
+        {
+            let shard_uid = shard_id_to_uid(epoch_manager.as_ref(), shard_id, epoch_id).unwrap();
+            // The node only has the chunk extra for the shards it tracks.
+            let Ok(chunk_extra) = chain_store.get_chunk_extra(block_hash, &shard_uid) else {
+                eprintln!("Skipping shard {shard_id}, which is not tracked at block {block_hash}");
+                continue;
+            };
+            let state_root = *chunk_extra.state_root();
+            let trie = runtime.get_trie_for_shard(shard_id, block_hash, state_root, false).unwrap();
+            let (values, proof) =
+                dump_trie_account_range(&trie, &self.from_account_id, self.to_account_id.as_ref())
+                    .unwrap();
+            println!("Dumped {} state items of shard {shard_id}", values.len());
+            let proof = proof.iter().map(|node| to_base64(node)).collect();
+            shards.push(ShardAccountRangeDump { shard_id, state_root, values, proof });
+        }
+
+        let dump = AccountRangeDump {
+            block_hash: *block_hash,
+            block_height: header.height(),
+            from_account_id: self.from_account_id,
+            to_account_id: self.to_account_id,
+            shards,
+        };
+        let file = std::fs::File::create(&self.output).unwrap();
+        serde_json::to_writer(std::io::BufWriter::new(file), &dump).unwrap();
+        println!("Saved the dump to {}", self.output.display());
+    }
+}
+
+/// Returns the shards which contain the accounts in `[from, to)`.
+fn shards_in_range(
+    shard_layout: &ShardLayout,
+    from: &AccountId,
+    to: Option<&AccountId>,
+) -> Vec<ShardId> {
+    let first_shard_id = shard_layout.account_id_to_shard_id(from);
+    let next_shard_ids = shard_layout
+        .boundary_accounts()
+        .iter()
+        .filter(|boundary_account| {
+            *boundary_account > from && to.is_none_or(|to| *boundary_account < to)
+        })
+        .map(|boundary_account| shard_layout.account_id_to_shard_id(boundary_account));
+    std::iter::once(first_shard_id).chain(next_shard_ids).collect()
+}
+
+/// Reads the state items of the accounts in `[from, to)` from the trie.
+/// Returns them together with the trie nodes and values visited on the way,
+/// which are enough to read the same items from the root of the trie.
+fn dump_trie_account_range(
+    trie: &Trie,
+    from: &AccountId,
+    to: Option<&AccountId>,
+) -> Result<(Vec<StateItem>, Vec<Arc<[u8]>>), StorageError> {
+    let trie = trie.recording_reads_new_recorder();
+    let mut values = vec![];
+    // Within a column, the keys are ordered by the account id, since the
+    // separator following the account id is lower than any account id
+    // character.
+    for (column, _) in col::COLUMNS_WITH_ACCOUNT_ID_IN_KEY {
+        let begin = [&[column][..], from.as_bytes()].concat();
+        let end = match to {
+            Some(to) => [&[column][..], to.as_bytes()].concat(),
+            None => vec![column + 1],
+        };
+        let path_begin: Vec<_> = NibbleSlice::new(&begin).iter().collect();
+        let path_end: Vec<_> = NibbleSlice::new(&end).iter().collect();
+        let items = trie.disk_iter()?.visit_nodes_interval(&path_begin, &path_end)?;
+        for key in items.into_iter().filter_map(|item| item.key).dedup() {
+            let value = trie.get(&key, AccessOptions::DEFAULT)?.ok_or_else(|| {
+                StorageError::StorageInconsistentState(format!(
+                    "Missing value of the visited key {key:?}"
+                ))
+            })?;
+            values.push(StateItem { key: key.into(), value: value.into() });
+        }
+    }
+    let PartialState::TrieValues(proof) = trie.recorded_storage().unwrap().nodes;
+    Ok((values, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::trie_key::TrieKey;
+    use near_store::PartialStorage;
+    use near_store::test_utils::{TestTriesBuilder, test_populate_trie};
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn account_key(name: &str) -> Vec<u8> {
+        TrieKey::Account { account_id: account(name) }.to_vec()
+    }
+
+    fn contract_data_key(name: &str, key: &[u8]) -> Vec<u8> {
+        TrieKey::ContractData { account_id: account(name), key: key.to_vec() }.to_vec()
+    }
+
+    fn keys(values: &[StateItem]) -> Vec<Vec<u8>> {
+        values.iter().map(|item| item.key.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_dump_trie_account_range() {
+        let trie_changes = ["alice.near", "alice.nearx", "bob.near", "carol.near"]
+            .into_iter()
+            .flat_map(|name| {
+                [
+                    (account_key(name), Some(vec![1])),
+                    (contract_data_key(name, b"key"), Some(vec![2])),
+                ]
+            })
+            .collect();
+        let tries = TestTriesBuilder::new().build();
+        let shard_uid = ShardUId::single_shard();
+        let state_root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, trie_changes);
+        let trie = tries.get_trie_for_shard(shard_uid, state_root);
+
+        let from = account("alice.nearx");
+        let to = account("carol.near");
+        let (values, proof) = dump_trie_account_range(&trie, &from, Some(&to)).unwrap();
+        let expected_keys = vec![
+            account_key("alice.nearx"),
+            account_key("bob.near"),
+            contract_data_key("alice.nearx", b"key"),
+            contract_data_key("bob.near", b"key"),
+        ];
+        assert_eq!(keys(&values), expected_keys);
+
+        // The proof is enough to read the same items from the state root.
+        let partial_storage = PartialStorage { nodes: PartialState::TrieValues(proof) };
+        let recorded_trie = Trie::from_recorded_storage(partial_storage, state_root, false);
+        let (recorded_values, _) =
+            dump_trie_account_range(&recorded_trie, &from, Some(&to)).unwrap();
+        assert_eq!(recorded_values, values);
+
+        let (values, _) = dump_trie_account_range(&trie, &to, None).unwrap();
+        assert_eq!(
+            keys(&values),
+            vec![account_key("carol.near"), contract_data_key("carol.near", b"key")]
+        );
+    }
+
+    #[test]
+    fn test_shards_in_range() {
+        let shard_ids: Vec<_> = [3, 1, 2].into_iter().map(ShardId::new).collect();
+        let shard_layout =
+            ShardLayout::v2(vec![account("b"), account("d")], shard_ids.clone(), None);
+        assert_eq!(
+            shards_in_range(&shard_layout, &account("a"), Some(&account("b"))),
+            vec![shard_ids[0]]
+        );
+        assert_eq!(
+            shards_in_range(&shard_layout, &account("a"), Some(&account("c"))),
+            &shard_ids[..2]
+        );
+        assert_eq!(shards_in_range(&shard_layout, &account("b"), None), &shard_ids[1..]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::account_range_dump::DumpAccountRangeCmd;
 use crate::commands::*;
 use crate::congestion_control::CongestionControlCmd;
 use crate::contract_accounts::ContractAccountFilter;
@@ -64,6 +65,10 @@ pub enum StateViewerSubCommand {
     /// Run a readonly Debug UI API server so the Debug UI can be used to query this node.
     #[clap(alias = "debug_ui")]
     DebugUI(DebugUICmd),
+    /// Dump the state of a range of accounts at a given block, with the trie
+    /// nodes proving it against the state roots, to a JSON file.
+    #[clap(alias = "dump_account_range")]
+    DumpAccountRange(DumpAccountRangeCmd),
     /// Dump contract data in storage of given account to binary file.
     #[clap(alias = "dump_account_storage")]
     DumpAccountStorage(DumpAccountStorageCmd),
@@ -177,6 +182,7 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::DebugUI(cmd) => {
                 cmd.run(home_dir, near_config, storage.get_hot_store(), storage.get_cold_store())
             }
+            StateViewerSubCommand::DumpAccountRange(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpAccountStorage(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpCode(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpState(cmd) => cmd.run(home_dir, near_config, store),
@@ -234,6 +234,7 @@ fn format_block_misc_value<'a>(key: &'a [u8], value: &'a [u8]) -> Box<dyn Debug
         || key == near_store::CHUNK_TAIL_KEY
         || key == near_store::FORK_TAIL_KEY
         || key == near_store::LARGEST_TARGET_HEIGHT_KEY
+        || key.starts_with(near_store::GC_CATEGORY_TAIL_KEY)
     {
         Box::new(BlockHeight::try_from_slice(value).unwrap())
     } else if key == near_store::LATEST_KNOWN_KEY {
@@ -413,7 +413,8 @@ async fn load_state_parts(
                 assert!(chain.runtime_adapter.validate_state_part(
                     &state_root,
                     PartId::new(part_id, num_parts),
-                    &part
+                    &part,
+                    &epoch_id,
                 ));
                 tracing::info!(target: "state-parts", part_id, part_length = part.len(), elapsed_sec = timer.elapsed().as_secs_f64(), "Validated a state part");
             }
@@ -0,0 +1,246 @@
+//! Simulates the storage operations of a contract method and estimates how
+//! much they contribute to the state witness and how much gas they cost.
+//!
+//! The operations are applied to a trie holding a copy of the contract state,
+//! going through the same trie lookups and storage proof recording as in the
+//! runtime. The gas only covers the storage host functions, i.e. neither the
+//! wasm execution nor the register operations around them.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use near_parameters::{ExtCosts, RuntimeConfig};
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, Gas, StateChangeCause, StoreKey};
+use near_primitives::views::StateItem;
+use near_store::test_utils::{TestTriesBuilder, test_populate_trie};
+use near_store::trie::{AccessOptions, AccessTracker};
+use near_store::{KeyLookupMode, Trie, TrieUpdate};
+
+/// A storage host function called by the contract method.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StorageOperation {
+    /// `storage_read` of the key.
+    Read { key: StoreKey },
+    /// `storage_write` of a value of `value_len` bytes to the key.
+    Write { key: StoreKey, value_len: usize },
+    /// `storage_remove` of the key.
+    Remove { key: StoreKey },
+}
+
+impl StorageOperation {
+    fn key(&self) -> &StoreKey {
+        match self {
+            Self::Read { key } | Self::Write { key, .. } | Self::Remove { key } => key,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct OperationEstimate {
+    pub gas: Gas,
+    /// Trie nodes read for the first time, charged as `touching_trie_node`.
+    pub touched_trie_nodes: u64,
+    /// Trie nodes read again, charged as `read_cached_trie_node`.
+    pub cached_trie_nodes: u64,
+    /// Increase of the storage proof size upper bound.
+    pub storage_proof_size: usize,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct SimulationReport {
+    pub operations: Vec<OperationEstimate>,
+    /// Total gas of the storage operations.
+    pub gas: Gas,
+    /// Upper bound of the storage proof size at the end of the method, which
+    /// is checked against `per_receipt_storage_proof_size_limit`. Includes the
+    /// extra size charged for removals.
+    pub storage_proof_size_upper_bound: usize,
+    /// Size of the storage proof once the changes are applied to the trie.
+    pub storage_proof_size: usize,
+    pub per_receipt_storage_proof_size_limit: usize,
+    pub main_storage_proof_size_soft_limit: usize,
+}
+
+impl SimulationReport {
+    /// Whether the method would fail for exceeding the storage proof size
+    /// limit of a receipt.
+    pub fn exceeds_receipt_limit(&self) -> bool {
+        self.storage_proof_size_upper_bound > self.per_receipt_storage_proof_size_limit
+    }
+}
+
+/// Tracks the trie node accesses like the runtime does for the contracts, so
+/// that the same nodes are charged as touched only once.
+#[derive(Debug, Default)]
+struct CountingAccessTracker {
+    db_reads: AtomicU64,
+    mem_reads: AtomicU64,
+    cache: Mutex<HashMap<CryptoHash, Arc<[u8]>>>,
+}
+
+impl CountingAccessTracker {
+    fn counts(&self) -> (u64, u64) {
+        (self.db_reads.load(Ordering::Relaxed), self.mem_reads.load(Ordering::Relaxed))
+    }
+}
+
+impl AccessTracker for CountingAccessTracker {
+    fn track_mem_lookup(&self, key: &CryptoHash) -> Option<Arc<[u8]>> {
+        let value = Arc::clone(self.cache.lock().unwrap().get(key)?);
+        self.mem_reads.fetch_add(1, Ordering::Relaxed);
+        Some(value)
+    }
+
+    fn track_disk_lookup(&self, key: CryptoHash, value: Arc<[u8]>) {
+        self.db_reads.fetch_add(1, Ordering::Relaxed);
+        self.cache.lock().unwrap().insert(key, value);
+    }
+}
+
+/// Applies the operations of a single method call of `account_id` on top of
+/// its `state` and estimates their storage proof size and gas under `config`.
+///
+/// Only the contract state is in the simulated trie, so the trie nodes above
+/// the contract account in its shard are not accounted for.
+pub fn simulate(
+    account_id: &AccountId,
+    state: Vec<StateItem>,
+    operations: &[StorageOperation],
+    config: &RuntimeConfig,
+) -> anyhow::Result<SimulationReport> {
+    let contract_data_key = |key: &StoreKey| TrieKey::ContractData {
+        account_id: account_id.clone(),
+        key: key.to_vec(),
+    };
+    let tries = TestTriesBuilder::new().build();
+    let shard_uid = ShardLayout::single_shard().shard_uids().next().unwrap();
+    let changes = state
+        .into_iter()
+        .map(|item| (contract_data_key(&item.key).to_vec(), Some(item.value.to_vec())))
+        .collect();
+    let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+
+    let trie = tries.get_trie_for_shard(shard_uid, root).recording_reads_new_recorder();
+    let mut trie_update = TrieUpdate::new(trie);
+    let tracker = CountingAccessTracker::default();
+    let options = AccessOptions::contract_runtime(&tracker);
+    let ext_costs = &config.wasm_config.ext_costs;
+    let cost = |ext_cost: ExtCosts, count: u64| ext_cost.gas(ext_costs) * count;
+
+    let mut estimates = Vec::with_capacity(operations.len());
+    for operation in operations {
+        let (db_reads_before, mem_reads_before) = tracker.counts();
+        let storage_proof_size_before = trie_update.trie().recorded_storage_size_upper_bound();
+        let trie_key = contract_data_key(operation.key());
+        let key_len = operation.key().len() as u64;
+
+        // Like the runtime, writes and removals look up the previous value
+        // through the trie, so that its path is recorded as well.
+        let prev_value_len =
+            match trie_update.get_ref(&trie_key, KeyLookupMode::MemOrTrie, options)? {
+                Some(value_ptr) => {
+                    value_ptr.deref_value(options)?;
+                    Some(value_ptr.len() as u64)
+                }
+                None => None,
+            };
+        let mut gas = match operation {
+            StorageOperation::Read { .. } => {
+                cost(ExtCosts::storage_read_base, 1)
+                    + cost(ExtCosts::storage_read_key_byte, key_len)
+                    + cost(ExtCosts::storage_read_value_byte, prev_value_len.unwrap_or_default())
+            }
+            StorageOperation::Write { value_len, .. } => {
+                trie_update.set(trie_key, vec![0; *value_len]);
+                cost(ExtCosts::storage_write_base, 1)
+                    + cost(ExtCosts::storage_write_key_byte, key_len)
+                    + cost(ExtCosts::storage_write_value_byte, *value_len as u64)
+                    + cost(ExtCosts::storage_write_evicted_byte, prev_value_len.unwrap_or_default())
+            }
+            StorageOperation::Remove { .. } => {
+                trie_update.remove(trie_key);
+                cost(ExtCosts::storage_remove_base, 1)
+                    + cost(ExtCosts::storage_remove_key_byte, key_len)
+                    + cost(
+                        ExtCosts::storage_remove_ret_value_byte,
+                        prev_value_len.unwrap_or_default(),
+                    )
+            }
+        };
+        let (db_reads, mem_reads) = tracker.counts();
+        let touched_trie_nodes = db_reads - db_reads_before;
+        let cached_trie_nodes = mem_reads - mem_reads_before;
+        gas += cost(ExtCosts::touching_trie_node, touched_trie_nodes)
+            + cost(ExtCosts::read_cached_trie_node, cached_trie_nodes);
+        estimates.push(OperationEstimate {
+            gas,
+            touched_trie_nodes,
+            cached_trie_nodes,
+            storage_proof_size: trie_update.trie().recorded_storage_size_upper_bound()
+                - storage_proof_size_before,
+        });
+    }
+
+    let storage_proof_size_upper_bound = trie_update.trie().recorded_storage_size_upper_bound();
+    trie_update.commit(StateChangeCause::NotWritableToDisk);
+    let trie = trie_update.finalize()?.trie;
+    Ok(SimulationReport {
+        gas: estimates.iter().map(|estimate| estimate.gas).sum(),
+        operations: estimates,
+        storage_proof_size_upper_bound,
+        storage_proof_size: trie.recorded_storage_size(),
+        per_receipt_storage_proof_size_limit: config
+            .wasm_config
+            .limit_config
+            .per_receipt_storage_proof_size_limit,
+        main_storage_proof_size_soft_limit: config
+            .witness_config
+            .main_storage_proof_size_soft_limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::types::StoreValue;
+
+    fn key(index: u32) -> StoreKey {
+        format!("key{index}").into_bytes().into()
+    }
+
+    fn simulate_on_state(num_items: u32, operations: &[StorageOperation]) -> SimulationReport {
+        let account_id: AccountId = "contract.near".parse().unwrap();
+        let state = (0..num_items)
+            .map(|index| StateItem { key: key(index), value: StoreValue::from(vec![1; 100]) })
+            .collect();
+        simulate(&account_id, state, operations, &RuntimeConfig::test()).unwrap()
+    }
+
+    #[test]
+    fn test_read_records_value() {
+        let report = simulate_on_state(100, &[StorageOperation::Read { key: key(7) }]);
+        assert!(report.operations[0].touched_trie_nodes > 0);
+        assert!(report.storage_proof_size_upper_bound >= 100);
+        assert_eq!(report.gas, report.operations[0].gas);
+    }
+
+    #[test]
+    fn test_repeated_read_is_cached() {
+        let read = StorageOperation::Read { key: key(7) };
+        let report = simulate_on_state(100, &[read.clone(), read]);
+        assert_eq!(report.operations[1].touched_trie_nodes, 0);
+        assert_eq!(report.operations[1].storage_proof_size, 0);
+        assert!(report.operations[1].gas < report.operations[0].gas);
+    }
+
+    #[test]
+    fn test_removal_is_charged_extra() {
+        let report = simulate_on_state(100, &[StorageOperation::Remove { key: key(7) }]);
+        assert!(report.operations[0].storage_proof_size >= 2000);
+        assert!(report.storage_proof_size < report.storage_proof_size_upper_bound);
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, anyhow};
+use clap::Parser;
+use near_jsonrpc::primitives::types::config::RpcProtocolConfigRequest;
+use near_jsonrpc::primitives::types::query::RpcQueryRequest;
+use near_parameters::RuntimeConfigStore;
+use near_primitives::types::{AccountId, BlockReference, Finality};
+use near_primitives::views::{QueryRequest, QueryResponseKind};
+use near_storage_proof_simulator::{StorageOperation, simulate};
+
+/// Estimates the storage proof size and gas of the storage operations of a
+/// contract method, on top of the current state of the contract.
+#[derive(clap::Parser)]
+struct Cli {
+    /// JSON RPC server of a node of the network, usually a test network.
+    #[clap(long, default_value = "http://localhost:3030")]
+    rpc_server_addr: String,
+    /// Account of the contract.
+    #[clap(long)]
+    account_id: AccountId,
+    /// JSON file with the list of storage operations of the method, e.g.
+    /// `[{"op": "read", "key": "<base64>"}, {"op": "write", "key": "<base64>", "value_len": 100}]`.
+    #[clap(long)]
+    operations: PathBuf,
+    /// Simulates the operations on an empty contract state instead of
+    /// fetching the current one.
+    #[clap(long)]
+    empty_state: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let operations: Vec<StorageOperation> = serde_json::from_slice(
+        &std::fs::read(&cli.operations)
+            .with_context(|| format!("failed to read {}", cli.operations.display()))?,
+    )?;
+
+    let (state, config_view) = actix::System::new().block_on(async {
+        let client = near_jsonrpc::client::new_client(&cli.rpc_server_addr);
+        let block_reference = BlockReference::Finality(Finality::Final);
+        let config_view = client
+            .EXPERIMENTAL_protocol_config(RpcProtocolConfigRequest {
+                block_reference: block_reference.clone(),
+            })
+            .await
+            .map_err(|err| anyhow!("get protocol config failed: {err}"))?
+            .config_view;
+        if cli.empty_state {
+            return anyhow::Ok((vec![], config_view));
+        }
+        let response = client
+            .query(RpcQueryRequest {
+                block_reference,
+                request: QueryRequest::ViewState {
+                    account_id: cli.account_id.clone(),
+                    prefix: vec![].into(),
+                    include_proof: false,
+                },
+            })
+            .await
+            .map_err(|err| anyhow!("view state failed: {err}"))?;
+        let QueryResponseKind::ViewState(view_state) = response.kind else {
+            anyhow::bail!("unexpected response to view state: {:?}", response.kind);
+        };
+        Ok((view_state.values, config_view))
+    })?;
+
+    let config_store = RuntimeConfigStore::for_chain_id(&config_view.chain_id);
+    let runtime_config = config_store.get_config(config_view.protocol_version);
+    let report = simulate(&cli.account_id, state, &operations, runtime_config)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if report.exceeds_receipt_limit() {
+        eprintln!(
+            "The storage proof size upper bound {} exceeds the limit of a receipt {}",
+            report.storage_proof_size_upper_bound, report.per_receipt_storage_proof_size_limit
+        );
+    }
+    Ok(())
+}